@@ -1,5 +1,5 @@
 use super::AABB;
-use crate::Point;
+use crate::{Float, Point};
 
 /// A trait for objects that can be bounded using an [`AABB`]
 ///
@@ -11,6 +11,27 @@ pub trait Bounded {
     fn aabb(&self) -> AABB;
     /// Return the centroid of self.
     fn centroid(&self) -> Point;
+    /// Return the [`AABB`] surrounding the part of self which is inside `clip`.
+    ///
+    /// The default implementation clips the [`AABB`] of the whole object, shapes such as triangles
+    /// can give tighter bounds, for the spatial splits of [`BVH::build_spatial`].
+    ///
+    /// [`AABB`]: struct.AABB.html
+    /// [`BVH::build_spatial`]: ../bvh/struct.BVH.html#method.build_spatial
+    fn clipped_aabb(&self, clip: &AABB) -> AABB {
+        self.aabb().intersection(clip)
+    }
+    /// Return the shortest distance from self to the given [`Point`], used by [`BVH::nearest`].
+    ///
+    /// The default implementation returns the distance to the [`AABB`] of self, which is exact for
+    /// points and boxes, and a lower bound for other objects.
+    ///
+    /// [`AABB`]: struct.AABB.html
+    /// [`BVH::nearest`]: ../bvh/struct.BVH.html#method.nearest
+    /// [`Point`]: ../type.Point.html
+    fn distance_to_point(&self, point: Point) -> Float {
+        self.aabb().distance_to_point(point)
+    }
 }
 
 /// Implementation of [`Bounded`] for [`AABB`]
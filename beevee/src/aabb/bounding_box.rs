@@ -1,6 +1,6 @@
 //! An Axis-Alighned Bounding Box.
 
-use crate::{Axis, Point, Vector};
+use crate::{Axis, Float, Point, Vector};
 use std::fmt::{Display, Formatter, Result};
 
 /// An Axis-Aligned Bounding Box.
@@ -32,8 +32,8 @@ impl AABB {
     /// ```
     #[must_use]
     pub fn empty() -> Self {
-        let lowest = std::f32::NEG_INFINITY;
-        let highest = std::f32::INFINITY;
+        let lowest = Float::NEG_INFINITY;
+        let highest = Float::INFINITY;
         AABB {
             low: Point::new(highest, highest, highest),
             high: Point::new(lowest, lowest, lowest),
@@ -198,6 +198,45 @@ impl AABB {
         self
     }
 
+    /// Return the [`AABB`] enclosing the space inside both `self` and the other one, which is
+    /// empty if they do not overlap.
+    ///
+    /// [`AABB`]: struct.AABB.html
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Point;
+    /// # use beevee::aabb::AABB;
+    /// #
+    /// let aabb = AABB::with_bounds(Point::new(0., 0., 0.), Point::new(2., 2., 2.));
+    /// let other = AABB::with_bounds(Point::new(1., 1., 1.), Point::new(3., 3., 3.));
+    /// let far = AABB::with_bounds(Point::new(5., 5., 5.), Point::new(6., 6., 6.));
+    ///
+    /// assert_eq!(
+    ///     aabb.intersection(&other),
+    ///     AABB::with_bounds(Point::new(1., 1., 1.), Point::new(2., 2., 2.))
+    /// );
+    /// assert!(aabb.intersection(&far).is_empty());
+    /// ```
+    pub fn intersection(&self, other: &Self) -> Self {
+        let low = Point::new(
+            self.low.x.max(other.low.x),
+            self.low.y.max(other.low.y),
+            self.low.z.max(other.low.z),
+        );
+        let high = Point::new(
+            self.high.x.min(other.high.x),
+            self.high.y.min(other.high.y),
+            self.high.z.min(other.high.z),
+        );
+        let ans = AABB { low, high };
+        if ans.is_empty() {
+            AABB::empty()
+        } else {
+            ans
+        }
+    }
+
     /// Return a vector correspondin to the diagonal from `low` to `high` for the [`AABB`].
     ///
     /// [`AABB`]: struct.AABB.html
@@ -262,6 +301,7 @@ impl AABB {
     ///
     /// # Examples
     /// ```
+    /// # use beevee::Float;
     /// # use beevee::Point;
     /// # use beevee::aabb::AABB;
     /// #
@@ -269,9 +309,9 @@ impl AABB {
     /// let high = Point::new(1., 1., 1.);
     /// let aabb = AABB::with_bounds(low, high);
     ///
-    /// assert!((aabb.surface() - 6.).abs() < std::f32::EPSILON);
+    /// assert!((aabb.surface() - 6.).abs() < Float::EPSILON);
     /// ```
-    pub fn surface(&self) -> f32 {
+    pub fn surface(&self) -> Float {
         let diagonal = self.diagonal();
         2. * (diagonal.x * diagonal.y + diagonal.x * diagonal.z + diagonal.y * diagonal.z)
     }
@@ -282,6 +322,7 @@ impl AABB {
     ///
     /// # Examples
     /// ```
+    /// # use beevee::Float;
     /// # use beevee::Point;
     /// # use beevee::aabb::AABB;
     /// #
@@ -289,9 +330,9 @@ impl AABB {
     /// let high = Point::new(1., 1., 1.);
     /// let aabb = AABB::with_bounds(low, high);
     ///
-    /// assert!((aabb.volume() - 1.).abs() < std::f32::EPSILON);
+    /// assert!((aabb.volume() - 1.).abs() < Float::EPSILON);
     /// ```
-    pub fn volume(&self) -> f32 {
+    pub fn volume(&self) -> Float {
         let diagonal = self.diagonal();
         diagonal.x * diagonal.y * diagonal.z
     }
@@ -343,6 +384,7 @@ impl AABB {
     ///
     /// # Examples
     /// ```
+    /// # use beevee::Float;
     /// # use beevee::Point;
     /// # use beevee::aabb::AABB;
     /// #
@@ -350,10 +392,11 @@ impl AABB {
     /// let high = Point::new(1., 1., 1.);
     /// let aabb = AABB::with_bounds(low, high);
     ///
-    /// assert!((aabb.distance_to_point(Point::new(-1., 0., 0.)) - 1.).abs() < std::f32::EPSILON);
+    /// assert!((aabb.distance_to_point(Point::new(-1., 0., 0.)) - 1.).abs() < Float::EPSILON);
     /// ```
     ///
     /// ```
+    /// # use beevee::Float;
     /// # use beevee::Point;
     /// # use beevee::aabb::AABB;
     /// #
@@ -362,10 +405,10 @@ impl AABB {
     /// let aabb = AABB::with_bounds(low, high);
     ///
     /// // Returns 0. when the point is contained by the AABB
-    /// assert!(aabb.distance_to_point(Point::new(0.5, 0.5, 0.5)).abs() < std::f32::EPSILON);
+    /// assert!(aabb.distance_to_point(Point::new(0.5, 0.5, 0.5)).abs() < Float::EPSILON);
     /// ```
-    pub fn distance_to_point(&self, point: Point) -> f32 {
-        f32::sqrt(self.sqdist_to_point(point))
+    pub fn distance_to_point(&self, point: Point) -> Float {
+        Float::sqrt(self.sqdist_to_point(point))
     }
 
     /// Return the square of the shortest distance from an [`AABB`] to a [`Point`].
@@ -375,6 +418,7 @@ impl AABB {
     ///
     /// # Examples
     /// ```
+    /// # use beevee::Float;
     /// # use beevee::Point;
     /// # use beevee::aabb::AABB;
     /// #
@@ -382,10 +426,11 @@ impl AABB {
     /// let high = Point::new(1., 1., 1.);
     /// let aabb = AABB::with_bounds(low, high);
     ///
-    /// assert!((aabb.sqdist_to_point(Point::new(-1., 0., 0.)) - 1.).abs() < std::f32::EPSILON);
+    /// assert!((aabb.sqdist_to_point(Point::new(-1., 0., 0.)) - 1.).abs() < Float::EPSILON);
     /// ```
     ///
     /// ```
+    /// # use beevee::Float;
     /// # use beevee::Point;
     /// # use beevee::aabb::AABB;
     /// #
@@ -394,9 +439,9 @@ impl AABB {
     /// let aabb = AABB::with_bounds(low, high);
     ///
     /// // Returns 0. when the point is contained by the AABB
-    /// assert!(aabb.sqdist_to_point(Point::new(0.5, 0.5, 0.5)).abs() < std::f32::EPSILON);
+    /// assert!(aabb.sqdist_to_point(Point::new(0.5, 0.5, 0.5)).abs() < Float::EPSILON);
     /// ```
-    pub fn sqdist_to_point(&self, point: Point) -> f32 {
+    pub fn sqdist_to_point(&self, point: Point) -> Float {
         let dx = (self.low.x - point.x).max(0.).max(point.x - self.high.x);
         let dy = (self.low.y - point.y).max(0.).max(point.y - self.high.y);
         let dz = (self.low.z - point.z).max(0.).max(point.z - self.high.z);
@@ -1,4 +1,4 @@
-use crate::{Point, Vector};
+use crate::{Float, Point, Vector};
 use std::fmt::{Display, Formatter, Result};
 use std::ops::{Index, IndexMut};
 
@@ -88,7 +88,7 @@ impl<T> IndexMut<Axis> for [T] {
 /// assert_eq!(point[Axis::X], 0.);
 /// ```
 impl Index<Axis> for Point {
-    type Output = f32;
+    type Output = Float;
 
     fn index(&self, axis: Axis) -> &Self::Output {
         match axis {
@@ -113,7 +113,7 @@ impl Index<Axis> for Point {
 /// assert_eq!(point, Point::new(3., 1., 2.));
 /// ```
 impl IndexMut<Axis> for Point {
-    fn index_mut(&mut self, axis: Axis) -> &mut f32 {
+    fn index_mut(&mut self, axis: Axis) -> &mut Float {
         match axis {
             Axis::X => &mut self.x,
             Axis::Y => &mut self.y,
@@ -135,7 +135,7 @@ impl IndexMut<Axis> for Point {
 /// assert_eq!(point[Axis::X], 0.);
 /// ```
 impl Index<Axis> for Vector {
-    type Output = f32;
+    type Output = Float;
 
     fn index(&self, axis: Axis) -> &Self::Output {
         match axis {
@@ -160,7 +160,7 @@ impl Index<Axis> for Vector {
 /// assert_eq!(point, Vector::new(3., 1., 2.));
 /// ```
 impl IndexMut<Axis> for Vector {
-    fn index_mut(&mut self, axis: Axis) -> &mut f32 {
+    fn index_mut(&mut self, axis: Axis) -> &mut Float {
         match axis {
             Axis::X => &mut self.x,
             Axis::Y => &mut self.y,
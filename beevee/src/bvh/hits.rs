@@ -0,0 +1,191 @@
+use super::{Intersected, Node, NodeEnum, BVH};
+use crate::aabb::AABB;
+use crate::ray::Ray;
+use crate::Float;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+impl BVH {
+    /// Iterate over every object of the [`BVH`] intersected by the given [`Ray`], along with the
+    /// distance to their intersection, from the nearest to the furthest.
+    ///
+    /// Nodes are only visited when the next hit is requested, and no closer hit is possible: the
+    /// iterator can be stopped early, e.g: after going through a few transparent objects. Each
+    /// object is returned once, at the distance returned by its [`Intersected::intersect`].
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`Intersected::intersect`]: trait.Intersected.html#tymethod.intersect
+    /// [`Ray`]: ../ray/struct.Ray.html
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::{Point, Vector};
+    /// # use beevee::aabb::{AABB, Bounded};
+    /// # use beevee::bvh::{BVH, Intersected};
+    /// # use beevee::ray::Ray;
+    /// #
+    /// # #[derive(Clone, Debug, PartialEq)]
+    /// # struct Cube(AABB);
+    /// #
+    /// # impl Bounded for Cube {
+    /// #     fn aabb(&self) -> AABB {
+    /// #         self.0
+    /// #     }
+    /// #     fn centroid(&self) -> Point {
+    /// #         self.0.centroid()
+    /// #     }
+    /// # }
+    /// #
+    /// # impl Intersected for Cube {
+    /// #     fn intersect(&self, ray: &Ray) -> Option<Float> {
+    /// #         ray.aabb_intersection(&self.0)
+    /// #     }
+    /// # }
+    /// #
+    /// let mut cubes: Vec<_> = (0..100)
+    ///     .map(|i| {
+    ///         let low = Point::new(((i * 37) % 100) as Float, (i % 2) as Float, 0.);
+    ///         Cube(AABB::with_bounds(low, low + Vector::new(0.5, 0.5, 0.5)))
+    ///     })
+    ///     .collect();
+    /// let bvh = BVH::with_max_capacity(&mut cubes, 4);
+    ///
+    /// let ray = Ray::new(Point::new(-1., 0.25, 0.25), Vector::x_axis());
+    /// let hits: Vec<_> = bvh.walk_all(&ray, &cubes).collect();
+    /// assert_eq!(hits.len(), 50);
+    /// // The hits are sorted by distance, the first one being the same as for walk
+    /// assert!(hits.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    /// assert_eq!(hits.first().cloned(), bvh.walk(&ray, &cubes));
+    ///
+    /// // Objects referenced by several leaves of a spatial BVH are only returned once
+    /// let bvh = BVH::build_spatial(&cubes, 0.5);
+    /// assert_eq!(bvh.walk_all(&ray, &cubes).count(), 50);
+    /// ```
+    pub fn walk_all<'a, O: Intersected>(&'a self, ray: &'a Ray, objects: &'a [O]) -> Hits<'a, O> {
+        let mut queue = BinaryHeap::new();
+        if let Some(distance) = entry_distance(ray, &self.tree.bounds) {
+            queue.push(Candidate {
+                distance,
+                kind: CandidateKind::Node(&self.tree),
+            });
+        }
+        Hits {
+            ray,
+            objects,
+            references: &self.references,
+            queue,
+            seen: HashSet::new(),
+        }
+    }
+}
+
+/// An iterator over the objects of a [`BVH`] hit by a ray, from the nearest to the furthest,
+/// returned by [`BVH::walk_all`].
+///
+/// [`BVH`]: struct.BVH.html
+/// [`BVH::walk_all`]: struct.BVH.html#method.walk_all
+pub struct Hits<'a, O> {
+    ray: &'a Ray,
+    objects: &'a [O],
+    references: &'a [usize],
+    queue: BinaryHeap<Candidate<'a, O>>,
+    /// The objects of a spatial BVH which were already intersected, to only return them once.
+    seen: HashSet<usize>,
+}
+
+impl<'a, O: Intersected> Iterator for Hits<'a, O> {
+    type Item = (Float, &'a O);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(Candidate { distance, kind }) = self.queue.pop() {
+            match kind {
+                // No other candidate can be closer
+                CandidateKind::Hit(object) => return Some((distance, object)),
+                CandidateKind::Node(node) => match &node.kind {
+                    NodeEnum::Leaf => self.push_leaf(node),
+                    NodeEnum::Internal { left, right } => {
+                        for child in [left, right].iter() {
+                            if let Some(distance) = entry_distance(self.ray, &child.bounds) {
+                                self.queue.push(Candidate {
+                                    distance,
+                                    kind: CandidateKind::Node(child),
+                                });
+                            }
+                        }
+                    }
+                },
+            }
+        }
+        None
+    }
+}
+
+impl<'a, O: Intersected> Hits<'a, O> {
+    fn push_leaf(&mut self, node: &Node) {
+        let indices: Box<dyn Iterator<Item = usize>> = if self.references.is_empty() {
+            Box::new(node.begin..node.end)
+        } else {
+            Box::new(self.references[node.begin..node.end].iter().copied())
+        };
+        for index in indices {
+            if !self.references.is_empty() && !self.seen.insert(index) {
+                continue;
+            }
+            let object = &self.objects[index];
+            if let Some(distance) = object.intersect(self.ray) {
+                self.queue.push(Candidate {
+                    distance,
+                    kind: CandidateKind::Hit(object),
+                });
+            }
+        }
+    }
+}
+
+/// Get the distance along the ray at which it enters the bounds, which is 0 when it starts inside
+/// of them, or `None` if it misses them.
+fn entry_distance(ray: &Ray, bounds: &AABB) -> Option<Float> {
+    // Rays starting inside of the bounds only intersect them on their way out
+    let distance = ray.aabb_intersection(bounds)?;
+    Some(if bounds.contains(&ray.origin) {
+        0.
+    } else {
+        distance
+    })
+}
+
+/// A node left to visit, or an object hit, at the given distance along the ray.
+struct Candidate<'a, O> {
+    distance: Float,
+    kind: CandidateKind<'a, O>,
+}
+
+enum CandidateKind<'a, O> {
+    Node(&'a Node),
+    Hit(&'a O),
+}
+
+impl<'a, O> PartialEq for Candidate<'a, O> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<'a, O> Eq for Candidate<'a, O> {}
+
+impl<'a, O> PartialOrd for Candidate<'a, O> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a, O> Ord for Candidate<'a, O> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed to pop the nearest candidate first out of the max-heap, NaNs last
+        other
+            .distance
+            .partial_cmp(&self.distance)
+            .unwrap_or_else(|| other.distance.is_nan().cmp(&self.distance.is_nan()))
+    }
+}
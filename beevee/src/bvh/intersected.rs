@@ -1,5 +1,6 @@
 use crate::aabb::Bounded;
 use crate::ray::Ray;
+use crate::Float;
 
 /// The trait for any object to be used in the [`BVH`].
 ///
@@ -7,5 +8,5 @@ use crate::ray::Ray;
 pub trait Intersected: Bounded {
     /// Return None if there is no intersection, or the distance along the ray to the closest
     /// intersection
-    fn intersect(&self, ray: &Ray) -> Option<f32>;
+    fn intersect(&self, ray: &Ray) -> Option<Float>;
 }
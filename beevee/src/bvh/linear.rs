@@ -0,0 +1,168 @@
+use super::tree::{bounds_from_slice, Proxy};
+use super::{Node, NodeEnum, BVH};
+use crate::aabb::{Bounded, AABB};
+use crate::{Axis, Point};
+
+/// The number of objects under which nodes of a linear [`BVH`] are not split.
+///
+/// [`BVH`]: struct.BVH.html
+const LINEAR_LEAF_SIZE: usize = 4;
+
+impl BVH {
+    /// Build a [`BVH`] for the given slice of objects by sorting them along a Morton curve, and
+    /// splitting them where their Morton codes differ, instead of using the SAH heuristic.
+    ///
+    /// This is much faster than [`build`], but the resulting tree is less efficient to walk: it
+    /// is meant for interactive previews, where the objects change more often than rays are cast.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`build`]: #method.build
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::{Point, Vector};
+    /// # use beevee::aabb::{AABB, Bounded};
+    /// # use beevee::bvh::{BVH, Intersected};
+    /// # use beevee::ray::Ray;
+    /// #
+    /// # #[derive(Clone, Debug, PartialEq)]
+    /// # struct Sphere {
+    /// #     center: Point,
+    /// #     radius: Float,
+    /// # }
+    /// #
+    /// # impl Bounded for Sphere {
+    /// #     fn aabb(&self) -> AABB {
+    /// #         let delt = Vector::new(self.radius, self.radius, self.radius);
+    /// #         AABB::with_bounds(self.center - delt, self.center + delt)
+    /// #     }
+    /// #     fn centroid(&self) -> Point {
+    /// #         self.center
+    /// #     }
+    /// # }
+    /// #
+    /// # impl Intersected for Sphere {
+    /// #     fn intersect(&self, ray: &Ray) -> Option<Float> {
+    /// #         let delt = self.center - ray.origin;
+    /// #         let tca = ray.direction.dot(&delt);
+    /// #         let d2 = delt.norm_squared() - tca * tca;
+    /// #         let r_2 = self.radius * self.radius;
+    /// #         if d2 > r_2 {
+    /// #             return None;
+    /// #         }
+    /// #         let t_0 = tca - (r_2 - d2).sqrt();
+    /// #         if t_0 < 0. { None } else { Some(t_0) }
+    /// #     }
+    /// # }
+    /// #
+    /// // Using the same sphere definition than build
+    /// let mut spheres: Vec<_> = (0..100)
+    ///     .map(|i| Sphere{ center: Point::new((i % 10) as Float, (i / 10) as Float, 0.), radius: 0.25 })
+    ///     .collect();
+    /// let bvh = BVH::build_linear(&mut spheres);
+    /// assert!(bvh.is_sound(&spheres));
+    ///
+    /// let ray = Ray::new(Point::new(3., 7., -5.), Vector::z_axis());
+    /// let (dist, obj) = bvh.walk(&ray, &spheres).unwrap();
+    /// assert_eq!(obj.center, Point::new(3., 7., 0.));
+    /// assert_eq!(dist, 4.75);
+    ///
+    /// // Objects at the same place are split evenly
+    /// let mut stacked = vec![spheres[0].clone(); 16];
+    /// let bvh = BVH::build_linear(&mut stacked);
+    /// assert!(bvh.is_sound(&stacked));
+    /// ```
+    pub fn build_linear<O: Bounded>(objects: &mut [O]) -> Self {
+        let bounds = centroid_bounds(objects);
+        // The codes are cached, the sort being stable keeps the construction deterministic
+        objects.sort_by_cached_key(|o| morton_code(&o.centroid(), &bounds));
+        let codes: Vec<_> = (objects.iter())
+            .map(|o| morton_code(&o.centroid(), &bounds))
+            .collect();
+        let tree = emit_node(objects, &codes, 0, objects.len());
+        Self {
+            tree,
+            references: Vec::new(),
+        }
+    }
+
+    /// Build a [`BVH`] as [`build_linear`] does, without reordering the objects, returning the
+    /// order in which they must be stored instead, as [`build_order`] does.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`build_linear`]: #method.build_linear
+    /// [`build_order`]: #method.build_order
+    pub fn build_linear_order<O: Bounded>(objects: &[O]) -> (Self, Vec<usize>) {
+        let mut proxies = Proxy::from_objects(objects);
+        let bvh = Self::build_linear(&mut proxies);
+        (bvh, Proxy::order(proxies))
+    }
+}
+
+/// Return the bounds of the centroids of the objects.
+fn centroid_bounds<O: Bounded>(objects: &[O]) -> AABB {
+    objects
+        .iter()
+        .fold(AABB::empty(), |acc, o| acc.grow(&o.centroid()))
+}
+
+/// Spread the 10 lowest bits of `bits` apart, inserting two zeros between each of them.
+fn expand_bits(bits: u32) -> u32 {
+    let mut bits = bits & 0x3ff;
+    bits = (bits | bits << 16) & 0x0300_00ff;
+    bits = (bits | bits << 8) & 0x0300_f00f;
+    bits = (bits | bits << 4) & 0x030c_30c3;
+    bits = (bits | bits << 2) & 0x0924_9249;
+    bits
+}
+
+/// Compute the 30 bits Morton code of a point, interleaving its coordinates quantized on 10 bits
+/// inside of the given bounds. Points close to each other have close codes.
+fn morton_code(point: &Point, bounds: &AABB) -> u32 {
+    let extent = bounds.diagonal();
+    let quantize = |axis: Axis| {
+        let offset = if extent[axis] > 0. {
+            (point[axis] - bounds.low[axis]) / extent[axis]
+        } else {
+            0.
+        };
+        // Casting saturates, and turns NaN into 0
+        (offset * 1023.) as u32
+    };
+    expand_bits(quantize(Axis::X)) << 2
+        | expand_bits(quantize(Axis::Y)) << 1
+        | expand_bits(quantize(Axis::Z))
+}
+
+/// Return the index at which the sorted codes start having their highest differing bit set, or
+/// the middle of the slice if they are all the same.
+fn find_split(codes: &[u32]) -> usize {
+    let (first, last) = (codes[0], codes[codes.len() - 1]);
+    if first == last {
+        return codes.len() / 2;
+    }
+    let bit = 1 << (31 - (first ^ last).leading_zeros());
+    // The codes all share the bits above, the ones without this bit come first
+    codes.partition_point(|code| code & bit == 0)
+}
+
+fn emit_node<O: Bounded>(objects: &[O], codes: &[u32], begin: usize, end: usize) -> Node {
+    if end - begin <= LINEAR_LEAF_SIZE {
+        return Node {
+            bounds: bounds_from_slice(&objects[begin..end]),
+            begin,
+            end,
+            kind: NodeEnum::Leaf,
+        };
+    }
+    let split = begin + find_split(&codes[begin..end]);
+    let left = Box::new(emit_node(objects, codes, begin, split));
+    let right = Box::new(emit_node(objects, codes, split, end));
+    Node {
+        bounds: left.bounds.union(&right.bounds),
+        begin,
+        end,
+        kind: NodeEnum::Internal { left, right },
+    }
+}
@@ -1,7 +1,331 @@
 //! The Boudning Volume Hiearchy
 
+mod hits;
+pub use hits::*;
+
 mod intersected;
 pub use intersected::*;
 
+mod linear;
+
+mod nearest;
+
+mod packet;
+
+mod spatial;
+
 mod tree;
 pub use tree::*;
+
+mod validation;
+pub use validation::Violation;
+
+mod visitor;
+pub use visitor::*;
+
+mod wide;
+pub use wide::*;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::aabb::{Bounded, AABB};
+    use crate::ray::Ray;
+    use crate::{Float, Point, Vector};
+    use nalgebra::Unit;
+
+    /// A box intersected through its bounds, so that flat and empty boxes can be tested.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Cube(AABB);
+
+    impl Bounded for Cube {
+        fn aabb(&self) -> AABB {
+            self.0
+        }
+
+        fn centroid(&self) -> Point {
+            self.0.centroid()
+        }
+    }
+
+    impl Intersected for Cube {
+        fn intersect(&self, ray: &Ray) -> Option<Float> {
+            ray.aabb_intersection(&self.0)
+        }
+    }
+
+    /// A xorshift generator, to draw the same scenes on every run without any dependency.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> Float {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 40) as Float / (1u64 << 24) as Float
+        }
+
+        fn point(&mut self, scale: Float) -> Point {
+            Point::new(self.next(), self.next(), self.next()) * scale
+        }
+
+        fn ray(&mut self) -> Ray {
+            let direction = Vector::new(self.next(), self.next(), self.next()) * 2.;
+            let direction = direction - Vector::new(1., 1., 1.);
+            Ray::new(
+                self.point(12.) - Vector::new(1., 1., 1.),
+                Unit::new_normalize(direction),
+            )
+        }
+    }
+
+    fn cube(low: Point, size: Vector) -> Cube {
+        Cube(AABB::with_bounds(low, low + size))
+    }
+
+    /// The scenes every variant is checked against, named for the failure messages.
+    fn scenes() -> Vec<(&'static str, Vec<Cube>)> {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let random = (0..300)
+            .map(|_| {
+                let size = Vector::new(rng.next(), rng.next(), rng.next());
+                cube(rng.point(10.), size)
+            })
+            .collect();
+        let flat = (0..100)
+            .map(|_| {
+                let size = Vector::new(rng.next(), rng.next(), 0.);
+                cube(Point::new(rng.next() * 10., rng.next() * 10., 5.), size)
+            })
+            .collect();
+        let points = (0..100)
+            .map(|_| cube(rng.point(10.), Vector::zeros()))
+            .collect();
+        let same = vec![cube(Point::new(4., 4., 4.), Vector::new(2., 2., 2.)); 50];
+        vec![
+            ("random", random),
+            ("flat", flat),
+            ("points", points),
+            ("same", same),
+            (
+                "single",
+                vec![cube(Point::new(4., 4., 4.), Vector::new(1., 2., 3.))],
+            ),
+            ("empty", Vec::new()),
+        ]
+    }
+
+    fn rays() -> Vec<Ray> {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        (0..200).map(|_| rng.ray()).collect()
+    }
+
+    fn brute_force(ray: &Ray, objects: &[Cube]) -> Option<Float> {
+        (objects.iter().filter_map(|o| o.intersect(ray))).min_by(|a, b| a.total_cmp(b))
+    }
+
+    /// Check a hit against the brute force one, the objects of equal distances being
+    /// interchangeable.
+    fn check(name: &str, ray: &Ray, hit: Option<(Float, &Cube)>, objects: &[Cube]) {
+        let expected = brute_force(ray, objects);
+        assert_eq!(hit.map(|(t, _)| t), expected, "{}: {}", name, ray);
+        if let Some((t, object)) = hit {
+            assert_eq!(object.intersect(ray), Some(t), "{}: {}", name, ray);
+        }
+    }
+
+    /// Every binary BVH built over the objects, along with the objects in their order.
+    fn builds(objects: &[Cube]) -> Vec<(String, BVH, Vec<Cube>)> {
+        let mut builds = Vec::new();
+        let mut sorted = objects.to_vec();
+        builds.push(("build".to_string(), BVH::build(&mut sorted), sorted));
+        for &capacity in &[1, 4] {
+            let mut sorted = objects.to_vec();
+            let bvh = BVH::with_max_capacity(&mut sorted, capacity);
+            builds.push((format!("capacity {}", capacity), bvh, sorted));
+        }
+        let (bvh, order) = BVH::build_order(objects);
+        let ordered = order.iter().map(|&i| objects[i].clone()).collect();
+        builds.push(("build_order".to_string(), bvh, ordered));
+        let mut sorted = objects.to_vec();
+        builds.push(("linear".to_string(), BVH::build_linear(&mut sorted), sorted));
+        let (bvh, order) = BVH::build_linear_order(objects);
+        let ordered = order.iter().map(|&i| objects[i].clone()).collect();
+        builds.push(("linear_order".to_string(), bvh, ordered));
+        for &budget in &[0., 0.3, 2.] {
+            let bvh = BVH::build_spatial(objects, budget);
+            builds.push((format!("spatial {}", budget), bvh, objects.to_vec()));
+        }
+        builds
+    }
+
+    #[test]
+    fn builds_are_sound() {
+        for (scene, objects) in scenes() {
+            for (name, bvh, objects) in builds(&objects) {
+                assert_eq!(bvh.validate(&objects), vec![], "{} {}", scene, name);
+                let wide = BVH4::collapse(&bvh);
+                assert_eq!(wide.validate(&objects), vec![], "{} {}", scene, name);
+            }
+        }
+    }
+
+    #[test]
+    fn walk_matches_brute_force() {
+        let rays = rays();
+        for (scene, objects) in scenes() {
+            for (name, bvh, objects) in builds(&objects) {
+                let name = format!("{} {}", scene, name);
+                for ray in rays.iter() {
+                    check(&name, ray, bvh.walk(ray, &objects), &objects);
+                    let hit = bvh.walk_with(ray, &objects, |o| o.intersect(ray).map(|t| (t, t)));
+                    check(&name, ray, hit, &objects);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wide_walk_matches_brute_force() {
+        let rays = rays();
+        for (scene, objects) in scenes() {
+            for (name, bvh, objects) in builds(&objects) {
+                let (bvh4, bvh8) = (BVH4::collapse(&bvh), BVH8::collapse(&bvh));
+                for ray in rays.iter() {
+                    check(
+                        &format!("{} {} 4", scene, name),
+                        ray,
+                        bvh4.walk(ray, &objects),
+                        &objects,
+                    );
+                    check(
+                        &format!("{} {} 8", scene, name),
+                        ray,
+                        bvh8.walk(ray, &objects),
+                        &objects,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packets_match_brute_force() {
+        let rays = rays();
+        for (scene, objects) in scenes() {
+            for (name, bvh, objects) in builds(&objects) {
+                let name = format!("{} {}", scene, name);
+                let bvh4 = BVH4::collapse(&bvh);
+                for packet in rays.chunks_exact(4) {
+                    let packet = [packet[0], packet[1], packet[2], packet[3]];
+                    let hits = bvh.walk_packet(&packet, &objects);
+                    let wide_hits = bvh4.walk_packet(&packet, &objects);
+                    for ((ray, hit), wide_hit) in packet.iter().zip(hits).zip(wide_hits) {
+                        check(&name, ray, hit, &objects);
+                        check(&name, ray, wide_hit, &objects);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn walk_all_matches_brute_force() {
+        let rays = rays();
+        for (scene, objects) in scenes() {
+            for (name, bvh, objects) in builds(&objects) {
+                for ray in rays.iter() {
+                    let mut expected: Vec<_> =
+                        objects.iter().filter_map(|o| o.intersect(ray)).collect();
+                    expected.sort_by(|a, b| a.total_cmp(b));
+                    let hits: Vec<_> = bvh.walk_all(ray, &objects).map(|(t, _)| t).collect();
+                    assert_eq!(hits, expected, "{} {}: {}", scene, name, ray);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn traversal_visits_every_hit_object() {
+        /// Visit every node hit by the ray, collecting the addresses of the objects of their
+        /// leaves.
+        struct Collect(Vec<*const Cube>);
+
+        impl Visitor<Cube> for Collect {
+            fn descend(&mut self, _: &AABB, distance: Option<Float>) -> bool {
+                distance.is_some()
+            }
+
+            fn visit(&mut self, object: &Cube) {
+                self.0.push(object);
+            }
+        }
+
+        let rays = rays();
+        for (scene, objects) in scenes() {
+            for (name, bvh, objects) in builds(&objects) {
+                for ray in rays.iter() {
+                    let mut visitor = Collect(Vec::new());
+                    bvh.traverse_with(ray, &objects, &mut visitor);
+                    for object in objects.iter().filter(|o| o.intersect(ray).is_some()) {
+                        let visited = visitor.0.contains(&(object as *const Cube));
+                        assert!(visited, "{} {}: {}", scene, name, ray);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let mut rng = Rng(0xda94_2042_e4dd_58b5);
+        let points: Vec<_> = (0..100).map(|_| rng.point(12.)).collect();
+        for (scene, objects) in scenes() {
+            for (name, bvh, objects) in builds(&objects) {
+                for point in points.iter() {
+                    let expected = (objects.iter().map(|o| o.distance_to_point(*point)))
+                        .min_by(|a, b| a.total_cmp(b));
+                    let nearest = bvh.nearest(*point, &objects).map(|(d, _)| d);
+                    assert_eq!(nearest, expected, "{} {}: {}", scene, name, point);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn refit_matches_brute_force() {
+        let rays = rays();
+        for (scene, objects) in scenes() {
+            for (name, mut bvh, mut objects) in builds(&objects) {
+                let mut wide = BVH4::collapse(&bvh);
+                for (i, object) in objects.iter_mut().enumerate() {
+                    let shift = Vector::new(0., (i % 3) as Float, -((i % 5) as Float));
+                    object.0 = AABB::with_bounds(object.0.low + shift, object.0.high + shift);
+                }
+                bvh.refit(&objects);
+                wide.refit(&objects);
+                let name = format!("{} {}", scene, name);
+                for ray in rays.iter() {
+                    check(&name, ray, bvh.walk(ray, &objects), &objects);
+                    check(&name, ray, wide.walk(ray, &objects), &objects);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn written_wide_bvh_matches_brute_force() {
+        let rays = rays();
+        for (scene, objects) in scenes() {
+            for (name, bvh, objects) in builds(&objects) {
+                let mut bytes = Vec::new();
+                BVH8::collapse(&bvh).write(&mut bytes).unwrap();
+                let read = BVH8::read(bytes.as_slice(), objects.len()).unwrap();
+                let name = format!("{} {}", scene, name);
+                for ray in rays.iter() {
+                    check(&name, ray, read.walk(ray, &objects), &objects);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use super::tree::leaf_objects;
+use super::{Node, NodeEnum, BVH};
+use crate::aabb::Bounded;
+use crate::{Float, Point};
+
+impl BVH {
+    /// Return the object of the [`BVH`] closest to the given [`Point`], along with its distance to
+    /// it as given by [`Bounded::distance_to_point`], or `None` if the [`BVH`] is empty.
+    ///
+    /// Nodes are visited from the nearest to the furthest, skipping those which are further than
+    /// the closest object found so far.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`Bounded::distance_to_point`]: ../aabb/trait.Bounded.html#method.distance_to_point
+    /// [`Point`]: ../type.Point.html
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::Point;
+    /// # use beevee::bvh::BVH;
+    /// #
+    /// let mut points: Vec<_> = (0..100)
+    ///     .map(|i| Point::new((i % 10) as Float, (i / 10) as Float, 0.))
+    ///     .collect();
+    /// let bvh = BVH::build_linear(&mut points);
+    ///
+    /// let (dist, nearest) = bvh.nearest(Point::new(3.2, 6.9, 1.), &points).unwrap();
+    /// assert_eq!(nearest, &Point::new(3., 7., 0.));
+    /// assert!((dist - (0.04 as Float + 0.01 + 1.).sqrt()).abs() < 1e-6);
+    ///
+    /// let empty: &mut [Point] = &mut [];
+    /// assert_eq!(BVH::build_linear(empty).nearest(Point::origin(), empty), None);
+    /// ```
+    pub fn nearest<'o, O: Bounded>(
+        &self,
+        point: Point,
+        objects: &'o [O],
+    ) -> Option<(Float, &'o O)> {
+        self.nearest_with(point, objects, |o| o.distance_to_point(point))
+    }
+
+    /// Find the object closest to the given [`Point`] like [`nearest`], using the given function to
+    /// compute the distance from each object to the point.
+    ///
+    /// The distance of an object must not be smaller than the distance of its bounds to the
+    /// [`Point`], or it might be missed.
+    ///
+    /// [`Point`]: ../type.Point.html
+    /// [`nearest`]: #method.nearest
+    pub fn nearest_with<'o, O, F>(
+        &self,
+        point: Point,
+        objects: &'o [O],
+        distance: F,
+    ) -> Option<(Float, &'o O)>
+    where
+        F: Fn(&O) -> Float,
+    {
+        let mut closest = None;
+        nearest_rec_helper(
+            point,
+            objects,
+            &self.references,
+            &self.tree,
+            &distance,
+            &mut closest,
+        );
+        closest
+    }
+}
+
+fn nearest_rec_helper<'o, O, F>(
+    point: Point,
+    objects: &'o [O],
+    references: &[usize],
+    node: &Node,
+    distance: &F,
+    closest: &mut Option<(Float, &'o O)>,
+) where
+    F: Fn(&O) -> Float,
+{
+    let min = |closest: &Option<(Float, &O)>| closest.map_or(Float::INFINITY, |(dist, _)| dist);
+    match &node.kind {
+        NodeEnum::Leaf => {
+            for o in leaf_objects(objects, references, node.begin, node.end) {
+                let dist = distance(o);
+                if dist < min(closest) {
+                    *closest = Some((dist, o));
+                }
+            }
+        }
+        NodeEnum::Internal { left, right } => {
+            let left_dist = left.bounds.distance_to_point(point);
+            let right_dist = right.bounds.distance_to_point(point);
+            // Pick the short and far nodes
+            let (near, far, near_dist, far_dist) = if left_dist < right_dist {
+                (left, right, left_dist, right_dist)
+            } else {
+                (right, left, right_dist, left_dist)
+            };
+            // Don't recurse if we know we cannot possibly find a closer object
+            if near_dist < min(closest) {
+                nearest_rec_helper(point, objects, references, near, distance, closest);
+            }
+            // The closest object may have been found in the nearest node
+            if far_dist < min(closest) {
+                nearest_rec_helper(point, objects, references, far, distance, closest);
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use super::tree::leaf_objects;
+use super::{Intersected, Node, NodeEnum, BVH};
+use crate::ray::{Ray, RayPacket};
+use crate::Float;
+
+impl BVH {
+    /// Find the closest intersection of each [`Ray`] of a packet of `N` rays, as [`walk`] would
+    /// for each of them. The rays walk the [`BVH`] together, so that each node is fetched once
+    /// for the whole packet, and its bounds are tested against every ray at once.
+    ///
+    /// This is faster than walking each ray on its own for coherent rays going in similar
+    /// directions, e.g: the camera rays of neighbouring pixels.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`Ray`]: ../ray/struct.Ray.html
+    /// [`walk`]: #method.walk
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::{Point, Vector};
+    /// # use beevee::aabb::{AABB, Bounded};
+    /// # use beevee::bvh::{BVH, Intersected};
+    /// # use beevee::ray::Ray;
+    /// # use nalgebra::Unit;
+    /// #
+    /// # #[derive(Clone, Debug, PartialEq)]
+    /// # struct Cube(AABB);
+    /// #
+    /// # impl Bounded for Cube {
+    /// #     fn aabb(&self) -> AABB {
+    /// #         self.0
+    /// #     }
+    /// #     fn centroid(&self) -> Point {
+    /// #         self.0.centroid()
+    /// #     }
+    /// # }
+    /// #
+    /// # impl Intersected for Cube {
+    /// #     fn intersect(&self, ray: &Ray) -> Option<Float> {
+    /// #         ray.aabb_intersection(&self.0)
+    /// #     }
+    /// # }
+    /// #
+    /// let mut cubes: Vec<_> = (0..100)
+    ///     .map(|i| {
+    ///         let low = Point::new((i % 10) as Float, (i / 10) as Float, 5.);
+    ///         Cube(AABB::with_bounds(low, low + Vector::new(0.5, 0.5, 0.5)))
+    ///     })
+    ///     .collect();
+    /// let bvh = BVH::with_max_capacity(&mut cubes, 4);
+    ///
+    /// // Four rays looking at the grid of cubes, the last one missing all of them
+    /// let direction = Unit::new_normalize(Vector::new(0.01, 0.01, 1.));
+    /// let ray = |x: Float, y: Float| Ray::new(Point::new(x, y, 0.), direction);
+    /// let rays = [ray(0.2, 0.2), ray(3.2, 7.2), ray(9.2, 9.2), ray(0.7, 0.2)];
+    /// let hits = bvh.walk_packet(&rays, &cubes);
+    ///
+    /// for (ray, hit) in rays.iter().zip(hits.iter()) {
+    ///     assert_eq!(hit, &bvh.walk(ray, &cubes));
+    /// }
+    /// assert_eq!(hits[1].map(|(_, cube)| cube.0.low), Some(Point::new(3., 7., 5.)));
+    /// assert_eq!(hits[3], None);
+    /// ```
+    pub fn walk_packet<'o, O: Intersected, const N: usize>(
+        &self,
+        rays: &[Ray; N],
+        objects: &'o [O],
+    ) -> [Option<(Float, &'o O)>; N] {
+        self.walk_packet_with(rays, objects, |lane, o| {
+            o.intersect(&rays[lane]).map(|t| (t, t))
+        })
+    }
+
+    /// Iterate over the [`BVH`] like [`walk_packet`], using the given function to intersect the
+    /// objects with the ray of the given lane of the packet, as [`walk_with`] does.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`walk_packet`]: #method.walk_packet
+    /// [`walk_with`]: #method.walk_with
+    pub fn walk_packet_with<'o, O, H, F, const N: usize>(
+        &self,
+        rays: &[Ray; N],
+        objects: &'o [O],
+        intersect: F,
+    ) -> [Option<(H, &'o O)>; N]
+    where
+        F: Fn(usize, &O) -> Option<(Float, H)>,
+    {
+        let mut walker = PacketWalker::new(rays, objects, &self.references, &intersect);
+        let distances = walker.packet.aabb_intersections(&self.tree.bounds);
+        if walker.is_active(&distances) {
+            walker.visit(&self.tree, &distances);
+        }
+        walker.closest
+    }
+}
+
+/// The state of a traversal of a BVH by a packet of rays, to find the closest intersection along
+/// each of them.
+pub(super) struct PacketWalker<'o, 'f, O, H, F, const N: usize> {
+    pub(super) packet: RayPacket<N>,
+    objects: &'o [O],
+    references: &'f [usize],
+    intersect: &'f F,
+    min: [Float; N],
+    pub(super) closest: [Option<(H, &'o O)>; N],
+}
+
+impl<'o, 'f, O, H, F, const N: usize> PacketWalker<'o, 'f, O, H, F, N>
+where
+    F: Fn(usize, &O) -> Option<(Float, H)>,
+{
+    pub(super) fn new(
+        rays: &[Ray; N],
+        objects: &'o [O],
+        references: &'f [usize],
+        intersect: &'f F,
+    ) -> Self {
+        PacketWalker {
+            packet: RayPacket::new(rays),
+            objects,
+            references,
+            intersect,
+            min: [Float::INFINITY; N],
+            closest: [(); N].map(|_| None),
+        }
+    }
+
+    /// Whether any ray could find a closer intersection inside bounds it enters at `distances`.
+    pub(super) fn is_active(&self, distances: &[Float; N]) -> bool {
+        (0..N).any(|lane| distances[lane] < self.min[lane])
+    }
+
+    /// Return the distance at which the first of the rays still looking for a closer
+    /// intersection enters bounds, to visit the nearest ones first.
+    pub(super) fn nearest(&self, distances: &[Float; N]) -> Float {
+        (0..N)
+            .filter(|&lane| distances[lane] < self.min[lane])
+            .map(|lane| distances[lane])
+            .fold(Float::INFINITY, Float::min)
+    }
+
+    /// Intersect the objects of the leaf spanning `begin..end` with the rays entering its bounds
+    /// at `distances`.
+    pub(super) fn visit_leaf(&mut self, begin: usize, end: usize, distances: &[Float; N]) {
+        for o in leaf_objects(self.objects, self.references, begin, end) {
+            for (lane, &distance) in distances.iter().enumerate() {
+                if distance >= self.min[lane] {
+                    continue;
+                }
+                match (self.intersect)(lane, o) {
+                    Some((dist, hit)) if dist < self.min[lane] => {
+                        self.min[lane] = dist;
+                        self.closest[lane] = Some((hit, o));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn visit(&mut self, node: &Node, distances: &[Float; N]) {
+        match &node.kind {
+            NodeEnum::Leaf => self.visit_leaf(node.begin, node.end, distances),
+            NodeEnum::Internal { left, right } => {
+                let mut children = [
+                    (left.as_ref(), self.packet.aabb_intersections(&left.bounds)),
+                    (
+                        right.as_ref(),
+                        self.packet.aabb_intersections(&right.bounds),
+                    ),
+                ];
+                if self.nearest(&children[1].1) < self.nearest(&children[0].1) {
+                    children.swap(0, 1);
+                }
+                for (child, distances) in children.iter() {
+                    // Closer intersections may have been found in the previous child
+                    if self.is_active(distances) {
+                        self.visit(child, distances);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use super::tree::{bounds_from_slice, compare_centroids, compute_sah};
+use super::{Node, NodeEnum, BVH};
+use crate::aabb::{Bounded, AABB};
+use crate::{Axis, Float, Point};
+
+/// The number of objects under which nodes of a spatial [`BVH`] are not split.
+///
+/// [`BVH`]: struct.BVH.html
+const SPATIAL_MAX_CAPACITY: usize = 32;
+
+/// The number of bins along each axis in which the candidate spatial splits are evaluated.
+const SPATIAL_BINS: usize = 32;
+
+/// Spatial splits are only tried when the children of the best object split overlap by more than
+/// this fraction of the root's surface.
+const SPATIAL_OVERLAP: Float = 1e-5;
+
+/// The bins are grown by this fraction of their width when clipping objects, so that rounding
+/// errors do not leave gaps between the parts of a split object.
+const SPATIAL_PADDING: Float = 1e-4;
+
+impl BVH {
+    /// Build a [`BVH`] for the given slice of objects, splitting the space as well as the objects
+    /// when it reduces the overlap of the nodes: an object straddling a split is referenced by
+    /// both children, which are bounded by the part of the object on their side, as given by
+    /// [`Bounded::clipped_aabb`].
+    ///
+    /// This gives faster trees than [`build`] for scenes made of large objects of uneven sizes,
+    /// e.g: long thin triangles, at the cost of a slower construction. `budget` is the fraction of
+    /// additional references allowed, e.g: `0.3` for at most 30% more references than objects. The
+    /// objects are not reordered, a spatial tree refers to them through its leaves.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`Bounded::clipped_aabb`]: ../aabb/trait.Bounded.html#method.clipped_aabb
+    /// [`build`]: #method.build
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::{Point, Vector};
+    /// # use beevee::aabb::{AABB, Bounded};
+    /// # use beevee::bvh::{BVH, BVH4, Intersected};
+    /// # use beevee::ray::Ray;
+    /// #
+    /// # #[derive(Clone, Debug, PartialEq)]
+    /// # struct Cube(AABB);
+    /// #
+    /// # impl Bounded for Cube {
+    /// #     fn aabb(&self) -> AABB {
+    /// #         self.0
+    /// #     }
+    /// #     fn centroid(&self) -> Point {
+    /// #         self.0.centroid()
+    /// #     }
+    /// # }
+    /// #
+    /// # impl Intersected for Cube {
+    /// #     fn intersect(&self, ray: &Ray) -> Option<Float> {
+    /// #         ray.aabb_intersection(&self.0)
+    /// #     }
+    /// # }
+    /// #
+    /// // Long thin bars crossing a grid of small cubes
+    /// let mut cubes: Vec<_> = (0..400)
+    ///     .map(|i| {
+    ///         let low = Point::new((i % 20) as Float, (i / 20) as Float, 0.);
+    ///         Cube(AABB::with_bounds(low, low + Vector::new(0.5, 0.5, 0.5)))
+    ///     })
+    ///     .collect();
+    /// cubes.extend((0..20).map(|i| {
+    ///     let low = Point::new(0., i as Float + 0.25, 1.);
+    ///     Cube(AABB::with_bounds(low, low + Vector::new(20., 0.1, 0.1)))
+    /// }));
+    /// let bvh = BVH::build_spatial(&cubes, 0.3);
+    /// assert!(bvh.is_sound(&cubes));
+    ///
+    /// // The same intersections are found as with the object-split tree
+    /// let mut sorted = cubes.clone();
+    /// let reference = BVH::build(&mut sorted);
+    /// let wide = BVH4::collapse(&bvh);
+    /// for i in 0..40 {
+    ///     let ray = Ray::new(Point::new(i as Float / 2. + 0.1, 3.3, -5.), Vector::z_axis());
+    ///     let expected = reference.walk(&ray, &sorted);
+    ///     assert_eq!(bvh.walk(&ray, &cubes), expected);
+    ///     assert_eq!(wide.walk(&ray, &cubes), expected);
+    /// }
+    /// ```
+    pub fn build_spatial<O: Bounded>(objects: &[O], budget: Float) -> Self {
+        let references: Vec<_> = (objects.iter().enumerate())
+            .map(|(index, o)| Reference::new(index, o.aabb()))
+            .collect();
+        let mut builder = SpatialBuilder {
+            objects,
+            root_surface: bounds_from_slice(&references).surface(),
+            // Casting saturates, and turns negative budgets into 0
+            budget: (budget * objects.len() as Float) as usize,
+            references: Vec::with_capacity(objects.len()),
+        };
+        let tree = builder.build_node(references);
+        Self {
+            tree,
+            references: builder.references,
+        }
+    }
+}
+
+/// A reference to an object, or to the part of it which is inside a node of the [`BVH`].
+///
+/// [`BVH`]: struct.BVH.html
+#[derive(Clone, Copy, Debug)]
+struct Reference {
+    index: usize,
+    aabb: AABB,
+}
+
+impl Reference {
+    fn new(index: usize, aabb: AABB) -> Self {
+        Reference { index, aabb }
+    }
+}
+
+impl Bounded for Reference {
+    fn aabb(&self) -> AABB {
+        self.aabb
+    }
+
+    fn centroid(&self) -> Point {
+        self.aabb.centroid()
+    }
+}
+
+/// The best spatial split found for a node: the plane's position along the axis, as the index of
+/// the first bin on its right.
+struct SpatialSplit {
+    axis: Axis,
+    bin: usize,
+    cost: Float,
+}
+
+/// The bins of a node along an axis.
+struct Bins {
+    low: Float,
+    width: Float,
+}
+
+impl Bins {
+    fn new(bounds: &AABB, axis: Axis) -> Self {
+        Bins {
+            low: bounds.low[axis],
+            width: (bounds.high[axis] - bounds.low[axis]) / SPATIAL_BINS as Float,
+        }
+    }
+
+    /// Return the index of the bin containing the given coordinate.
+    fn index(&self, coordinate: Float) -> usize {
+        // Casting saturates, and turns NaN into 0
+        (((coordinate - self.low) / self.width) as usize).min(SPATIAL_BINS - 1)
+    }
+
+    /// Return the position of the plane at the start of the given bin.
+    fn plane(&self, bin: usize) -> Float {
+        self.low + bin as Float * self.width
+    }
+
+    /// Return the slab of `bounds` between the planes at the start of the given bins, padded to
+    /// make up for rounding errors.
+    fn slab(&self, bounds: &AABB, axis: Axis, first: usize, last: usize) -> AABB {
+        let padding = self.width * SPATIAL_PADDING;
+        let mut slab = *bounds;
+        slab.low[axis] = self.plane(first) - padding;
+        slab.high[axis] = self.plane(last) + padding;
+        slab
+    }
+}
+
+struct SpatialBuilder<'o, O> {
+    objects: &'o [O],
+    root_surface: Float,
+    budget: usize,
+    references: Vec<usize>,
+}
+
+impl<'o, O: Bounded> SpatialBuilder<'o, O> {
+    fn build_node(&mut self, mut refs: Vec<Reference>) -> Node {
+        let aabb = bounds_from_slice(&refs);
+        // Don't split nodes under capacity
+        if refs.len() <= SPATIAL_MAX_CAPACITY {
+            return self.leaf(aabb, &refs);
+        }
+        // Calculate the SAH heuristic for this slice
+        let (split, axis, cost) = compute_sah(&mut refs, aabb.surface(), SPATIAL_MAX_CAPACITY);
+        // Avoid degenerate cases, and recenter the split inside [0, refs.len())
+        let split = if split == 0 || split >= refs.len() - 1 {
+            refs.len() / 2
+        } else {
+            split
+        };
+        refs.sort_by(|lhs, rhs| compare_centroids(lhs, rhs, axis));
+
+        // Only look for a spatial split if the children of the object split overlap enough
+        let overlap =
+            bounds_from_slice(&refs[..split]).intersection(&bounds_from_slice(&refs[split..]));
+        let spatial = if self.budget > 0
+            && !overlap.is_empty()
+            && overlap.surface() / self.root_surface > SPATIAL_OVERLAP
+        {
+            self.find_spatial_split(&refs, &aabb)
+                .filter(|spatial| spatial.cost < cost)
+        } else {
+            None
+        };
+
+        // Only split if the heuristic shows that it is worth it
+        let best = spatial.as_ref().map_or(cost, |spatial| spatial.cost);
+        if best >= refs.len() as Float {
+            return self.leaf(aabb, &refs);
+        }
+        let (left, right) =
+            match spatial.and_then(|spatial| self.spatial_partition(&refs, &aabb, spatial)) {
+                Some(sides) => sides,
+                None => {
+                    let right = refs.split_off(split);
+                    (refs, right)
+                }
+            };
+        // Children are built in order, their references are contiguous
+        let left = Box::new(self.build_node(left));
+        let right = Box::new(self.build_node(right));
+        Node {
+            bounds: left.bounds.union(&right.bounds),
+            begin: left.begin,
+            end: right.end,
+            kind: NodeEnum::Internal { left, right },
+        }
+    }
+
+    fn leaf(&mut self, bounds: AABB, refs: &[Reference]) -> Node {
+        let begin = self.references.len();
+        self.references.extend(refs.iter().map(|r| r.index));
+        Node {
+            bounds,
+            begin,
+            end: self.references.len(),
+            kind: NodeEnum::Leaf,
+        }
+    }
+
+    /// Return the part of the referenced object which is inside `clip`.
+    fn clip(&self, reference: &Reference, clip: &AABB) -> AABB {
+        self.objects[reference.index]
+            .clipped_aabb(clip)
+            .intersection(&reference.aabb)
+    }
+
+    /// Bin the clipped parts of the references along each axis, and return the spatial split with
+    /// the lowest SAH cost, if any.
+    fn find_spatial_split(&self, refs: &[Reference], bounds: &AABB) -> Option<SpatialSplit> {
+        let surface = bounds.surface();
+        let mut best: Option<SpatialSplit> = None;
+        for &axis in [Axis::X, Axis::Y, Axis::Z].iter() {
+            let bins = Bins::new(bounds, axis);
+            if bins.width <= 0. {
+                continue;
+            }
+            let mut boxes = [AABB::empty(); SPATIAL_BINS];
+            let mut entries = [0usize; SPATIAL_BINS];
+            let mut exits = [0usize; SPATIAL_BINS];
+            for reference in refs {
+                let first = bins.index(reference.aabb.low[axis]);
+                let last = bins.index(reference.aabb.high[axis]);
+                entries[first] += 1;
+                exits[last] += 1;
+                if first == last {
+                    boxes[first].union_mut(&reference.aabb);
+                    continue;
+                }
+                for (bin, aabb) in boxes.iter_mut().enumerate().take(last + 1).skip(first) {
+                    let clipped = self.clip(reference, &bins.slab(bounds, axis, bin, bin + 1));
+                    if !clipped.is_empty() {
+                        aabb.union_mut(&clipped);
+                    }
+                }
+            }
+
+            // Sweep from the right to get the bounds of each right side
+            let mut right_surfaces = [0.; SPATIAL_BINS];
+            let mut right_box = AABB::empty();
+            for bin in (1..SPATIAL_BINS).rev() {
+                right_box.union_mut(&boxes[bin]);
+                right_surfaces[bin] = right_box.surface();
+            }
+
+            // Then from the left, evaluating the cost of each plane
+            let mut left_box = AABB::empty();
+            let mut left_count = 0;
+            let mut right_count = refs.len();
+            for bin in 1..SPATIAL_BINS {
+                left_box.union_mut(&boxes[bin - 1]);
+                left_count += entries[bin - 1];
+                right_count -= exits[bin - 1];
+                if left_count == 0 || right_count == 0 {
+                    continue;
+                }
+                let cost = 1. / SPATIAL_MAX_CAPACITY as Float
+                    + (left_count as Float * left_box.surface()
+                        + right_count as Float * right_surfaces[bin])
+                        / surface;
+                if best.as_ref().map_or(Float::INFINITY, |best| best.cost) > cost {
+                    best = Some(SpatialSplit { axis, bin, cost });
+                }
+            }
+        }
+        best
+    }
+
+    /// Partition the references on each side of the spatial split, duplicating those straddling
+    /// it while the budget allows. Return `None` if one of the sides ends up empty.
+    fn spatial_partition(
+        &mut self,
+        refs: &[Reference],
+        bounds: &AABB,
+        split: SpatialSplit,
+    ) -> Option<(Vec<Reference>, Vec<Reference>)> {
+        let axis = split.axis;
+        let bins = Bins::new(bounds, axis);
+        let plane = bins.plane(split.bin);
+        let (left_slab, right_slab) = (
+            bins.slab(bounds, axis, 0, split.bin),
+            bins.slab(bounds, axis, split.bin, SPATIAL_BINS),
+        );
+        let (mut left, mut right) = (Vec::new(), Vec::new());
+        for reference in refs {
+            if bins.index(reference.aabb.high[axis]) < split.bin {
+                left.push(*reference);
+            } else if bins.index(reference.aabb.low[axis]) >= split.bin {
+                right.push(*reference);
+            } else if self.budget == 0 {
+                // Out of budget, keep the reference whole on the side of its centroid
+                if reference.centroid()[axis] < plane {
+                    left.push(*reference);
+                } else {
+                    right.push(*reference);
+                }
+            } else {
+                let left_part = self.clip(reference, &left_slab);
+                let right_part = self.clip(reference, &right_slab);
+                match (left_part.is_empty(), right_part.is_empty()) {
+                    (false, false) => {
+                        self.budget -= 1;
+                        left.push(Reference::new(reference.index, left_part));
+                        right.push(Reference::new(reference.index, right_part));
+                    }
+                    (false, true) => left.push(Reference::new(reference.index, left_part)),
+                    (true, false) => right.push(Reference::new(reference.index, right_part)),
+                    // Keep the object even if clipping lost it to rounding errors
+                    (true, true) => left.push(*reference),
+                }
+            }
+        }
+        if left.is_empty() || right.is_empty() {
+            None
+        } else {
+            Some((left, right))
+        }
+    }
+}
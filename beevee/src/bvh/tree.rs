@@ -1,13 +1,15 @@
-use super::Intersected;
-use crate::aabb::AABB;
+use super::validation::Validator;
+use super::{Intersected, Violation};
+use crate::aabb::{Bounded, AABB};
 use crate::ray::Ray;
-use crate::Axis;
+use crate::{Axis, Float, Point};
+use std::cmp::Ordering;
 
 /// An enum representing either an internal or a leaf node of the [`BVH`]
 ///
 /// [`BVH`]: struct.BVH.html
 #[derive(Clone, Debug, PartialEq)]
-enum NodeEnum {
+pub(super) enum NodeEnum {
     Internal { left: Box<Node>, right: Box<Node> },
     Leaf,
 }
@@ -15,11 +17,11 @@ enum NodeEnum {
 /// A node representing either an internal or a leaf node of the [`BVH`]
 ///
 #[derive(Clone, Debug, PartialEq)]
-struct Node {
-    bounds: AABB,
-    begin: usize,
-    end: usize,
-    kind: NodeEnum,
+pub(super) struct Node {
+    pub(super) bounds: AABB,
+    pub(super) begin: usize,
+    pub(super) end: usize,
+    pub(super) kind: NodeEnum,
 }
 
 /// The BVH containing all the objects of type O.
@@ -28,7 +30,13 @@ struct Node {
 /// [`Intersected`]: trait.Intersected.html
 #[derive(Clone, Debug, PartialEq)]
 pub struct BVH {
-    tree: Node,
+    pub(super) tree: Node,
+    /// The indices of the objects referenced by the leaves of a [`BVH`] built with spatial splits,
+    /// where an object can appear in several leaves. Empty otherwise, the leaves then index the
+    /// objects directly.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    pub(super) references: Vec<usize>,
 }
 
 impl BVH {
@@ -37,6 +45,7 @@ impl BVH {
     ///
     /// # Examples
     /// ```
+    /// # use beevee::Float;
     /// use beevee::{Point, Vector};
     /// use beevee::aabb::{AABB, Bounded};
     /// use beevee::bvh::{BVH, Intersected};
@@ -45,7 +54,7 @@ impl BVH {
     /// #[derive(Clone, Debug, PartialEq)]
     /// struct Sphere {
     ///     center: Point,
-    ///     radius: f32,
+    ///     radius: Float,
     /// }
     ///
     /// impl Bounded for Sphere {
@@ -59,7 +68,7 @@ impl BVH {
     /// }
     ///
     /// impl Intersected for Sphere {
-    ///     fn intersect(&self, ray: &Ray) -> Option<f32> {
+    ///     fn intersect(&self, ray: &Ray) -> Option<Float> {
     ///         use std::mem;
     ///
     ///         let delt = self.center - ray.origin;
@@ -100,8 +109,13 @@ impl BVH {
     /// leaf-node. The max capacity is not respected when the SAH heuristic indicate that it would
     /// be better to iterate over all objects instead of splitting.
     ///
+    /// The construction is deterministic: objects are only reordered by stable sorts, objects
+    /// with the same centroid coordinate keeping their relative order. Building a [`BVH`] twice
+    /// from the same slice results in the same tree and the same order of objects.
+    ///
     /// # Examples
     /// ```
+    /// # use beevee::Float;
     /// use beevee::{Point, Vector};
     /// use beevee::aabb::{AABB, Bounded};
     /// use beevee::bvh::{BVH, Intersected};
@@ -110,7 +124,7 @@ impl BVH {
     /// #[derive(Clone, Debug, PartialEq)]
     /// struct Sphere {
     ///     center: Point,
-    ///     radius: f32,
+    ///     radius: Float,
     /// }
     ///
     /// impl Bounded for Sphere {
@@ -124,7 +138,7 @@ impl BVH {
     /// }
     ///
     /// impl Intersected for Sphere {
-    ///     fn intersect(&self, ray: &Ray) -> Option<f32> {
+    ///     fn intersect(&self, ray: &Ray) -> Option<Float> {
     ///         use std::mem;
     ///
     ///         let delt = self.center - ray.origin;
@@ -156,19 +170,101 @@ impl BVH {
     ///
     /// let spheres: &mut [Sphere] = &mut [Sphere{ center: Point::origin(), radius: 2.5 }];
     /// let bvh = BVH::with_max_capacity(spheres, 32);
+    ///
+    /// // Many objects sharing the same centroid coordinates
+    /// let grid: Vec<_> = (0..64)
+    ///     .map(|i| Sphere {
+    ///         center: Point::new((i % 4) as Float, (i / 16) as Float, 0.),
+    ///         radius: (i / 4 % 4 + 1) as Float / 8.,
+    ///     })
+    ///     .collect();
+    /// let (mut first, mut second) = (grid.clone(), grid);
+    /// let bvh = BVH::with_max_capacity(&mut first, 4);
+    /// assert_eq!(BVH::with_max_capacity(&mut second, 4), bvh);
+    /// assert_eq!(first, second);
     /// ```
     pub fn with_max_capacity<O: Intersected>(objects: &mut [O], max_cap: usize) -> Self {
         let tree = build_node(objects, 0, objects.len(), max_cap);
-        Self { tree }
+        Self {
+            tree,
+            references: Vec::new(),
+        }
     }
 
-    /// Return the true if the [`BVH`] has been built soundly:
+    /// Build a [`BVH`] as [`build`] does, without reordering the objects: return the order in
+    /// which they must be stored to be walked with it instead, the object at index `i` of the
+    /// [`BVH`] being `objects[order[i]]`.
+    ///
+    /// This is the same [`BVH`], and the same order, as [`build`] would have given. The order can
+    /// be stored along with the [`BVH`] to apply it again without building the tree, e.g: when the
+    /// objects are loaded again from disk.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`build`]: #method.build
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// use beevee::Point;
+    /// use beevee::aabb::AABB;
+    /// use beevee::bvh::BVH;
+    /// # use beevee::bvh::Intersected;
+    /// # use beevee::ray::Ray;
+    /// #
+    /// # #[derive(Clone, Debug, PartialEq)]
+    /// # struct Cube(AABB);
+    /// #
+    /// # impl beevee::aabb::Bounded for Cube {
+    /// #     fn aabb(&self) -> AABB {
+    /// #         self.0
+    /// #     }
+    /// #     fn centroid(&self) -> Point {
+    /// #         self.0.centroid()
+    /// #     }
+    /// # }
+    /// #
+    /// # impl Intersected for Cube {
+    /// #     fn intersect(&self, ray: &Ray) -> Option<Float> {
+    /// #         ray.aabb_intersection(&self.0)
+    /// #     }
+    /// # }
+    ///
+    /// let cubes: Vec<_> = (0..100)
+    ///     .map(|i| {
+    ///         let low = Point::new(((i * 7) % 100) as Float, 0., 0.);
+    ///         Cube(AABB::with_bounds(low, low + beevee::Vector::new(0.5, 0.5, 0.5)))
+    ///     })
+    ///     .collect();
+    /// let (bvh, order) = BVH::build_order(&cubes);
+    ///
+    /// let mut sorted = cubes.clone();
+    /// assert_eq!(BVH::build(&mut sorted), bvh);
+    /// let ordered: Vec<_> = order.iter().map(|&i| cubes[i].clone()).collect();
+    /// assert_eq!(ordered, sorted);
+    /// ```
+    pub fn build_order<O: Bounded>(objects: &[O]) -> (Self, Vec<usize>) {
+        let mut proxies = Proxy::from_objects(objects);
+        let tree = build_node(&mut proxies, 0, objects.len(), 32);
+        let bvh = Self {
+            tree,
+            references: Vec::new(),
+        };
+        (bvh, Proxy::order(proxies))
+    }
+
+    /// Return true if the [`BVH`] has been built soundly:
     /// * Each child node is contained inside the parent's bounding box.
     /// * Each object in a leaf node is inside the node's bounding box.
     /// * There is no missing object indices.
     ///
+    /// Use [`validate`] to know which checks failed.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`validate`]: #method.validate
+    ///
     /// # Examples
     /// ```
+    /// # use beevee::Float;
     /// # use beevee::{Point, Vector};
     /// # use beevee::aabb::{AABB, Bounded};
     /// # use beevee::bvh::{BVH, Intersected};
@@ -177,7 +273,7 @@ impl BVH {
     /// # #[derive(Clone, Debug, PartialEq)]
     /// # struct Sphere {
     /// #     center: Point,
-    /// #     radius: f32,
+    /// #     radius: Float,
     /// # }
     /// #
     /// # impl Bounded for Sphere {
@@ -191,7 +287,7 @@ impl BVH {
     /// # }
     /// #
     /// # impl Intersected for Sphere {
-    /// #     fn intersect(&self, ray: &Ray) -> Option<f32> {
+    /// #     fn intersect(&self, ray: &Ray) -> Option<Float> {
     /// #         use std::mem;
     /// #
     /// #         let delt = self.center - ray.origin;
@@ -225,28 +321,168 @@ impl BVH {
     /// let spheres: &mut [Sphere] = &mut [Sphere{ center: Point::origin(), radius: 2.5 }];
     /// let bvh = BVH::with_max_capacity(spheres, 32);
     /// assert!(bvh.is_sound(spheres));
+    ///
+    /// // Nodes are split when containing more objects than their capacity
+    /// let mut spheres: Vec<_> = (0..100)
+    ///     .map(|i| Sphere{ center: Point::new((i % 10) as Float, (i / 10) as Float, 0.), radius: 0.25 })
+    ///     .collect();
+    /// let bvh = BVH::with_max_capacity(&mut spheres, 4);
+    /// assert!(bvh.is_sound(&spheres));
+    /// let ray = Ray::new(Point::new(3., 7., -5.), Vector::z_axis());
+    /// let (_, obj) = bvh.walk(&ray, &spheres).unwrap();
+    /// assert_eq!(obj.center, Point::new(3., 7., 0.));
     /// ```
     pub fn is_sound<O: Intersected>(&self, objects: &[O]) -> bool {
-        fn check_node<O: Intersected>(objects: &[O], node: &Node) -> bool {
-            if node.begin > node.end {
-                return false;
+        self.validate(objects).is_empty()
+    }
+
+    /// Check that the [`BVH`] has been built soundly, as [`is_sound`] does, returning every
+    /// [`Violation`] found instead of a `bool`.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`Violation`]: enum.Violation.html
+    /// [`is_sound`]: #method.is_sound
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::{Float, Point};
+    /// # use beevee::bvh::{BVH, Violation};
+    /// #
+    /// let mut points: Vec<_> = (0..100)
+    ///     .map(|i| Point::new((i % 10) as Float, (i / 10) as Float, 0.))
+    ///     .collect();
+    /// let bvh = BVH::build_linear(&mut points);
+    /// assert_eq!(bvh.validate(&points), vec![]);
+    ///
+    /// // Objects which are not in the tree are reported
+    /// let mut more = points.clone();
+    /// more.push(Point::origin());
+    /// assert_eq!(bvh.validate(&more), vec![Violation::MissingObject { object: 100 }]);
+    ///
+    /// // As are leaves going past the end of the objects
+    /// let report = bvh.validate(&points[..50]);
+    /// assert!(report.iter().any(|v| matches!(v, Violation::InvalidRange { .. })));
+    ///
+    /// // And objects which moved out of their leaf
+    /// points[42] = Point::new(-1., -1., -1.);
+    /// match bvh.validate(&points)[..] {
+    ///     [Violation::ObjectOutside { object: 42, .. }] => {}
+    ///     ref report => panic!("unexpected report: {:?}", report),
+    /// }
+    /// ```
+    pub fn validate<O: Bounded>(&self, objects: &[O]) -> Vec<Violation> {
+        fn check_node<O: Bounded>(validator: &mut Validator<O>, node: &Node, index: &mut usize) {
+            let current = *index;
+            *index += 1;
+            match &node.kind {
+                NodeEnum::Leaf => validator.leaf(current, &node.bounds, node.begin, node.end),
+                NodeEnum::Internal { left, right } => {
+                    validator.range(current, node.begin, node.end);
+                    for child in [left, right].iter() {
+                        validator.child(current, &node.bounds, *index, &child.bounds);
+                        check_node(validator, child, index);
+                    }
+                }
             }
-            match node.kind {
-                NodeEnum::Leaf => objects[node.begin..node.end]
-                    .iter()
-                    .all(|o| node.bounds.union(&o.aabb()) == node.bounds),
-                NodeEnum::Internal {
-                    ref left,
-                    ref right,
-                } => {
-                    check_node(objects, left.as_ref())
-                        && check_node(objects, right.as_ref())
-                        && node.bounds.union(&left.bounds) == node.bounds
-                        && node.bounds.union(&right.bounds) == node.bounds
+        }
+        let mut validator = Validator::new(objects, &self.references);
+        check_node(&mut validator, &self.tree, &mut 0);
+        validator.finish()
+    }
+
+    /// Update the bounds of every node of the [`BVH`] after its objects have moved, keeping the
+    /// tree's structure and the order of the objects. This is much cheaper than building a new
+    /// [`BVH`], but the tree gets less efficient as the objects move away from where they were
+    /// when it was built.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::{Point, Vector};
+    /// # use beevee::aabb::{AABB, Bounded};
+    /// # use beevee::bvh::{BVH, Intersected};
+    /// # use beevee::ray::Ray;
+    /// #
+    /// # #[derive(Clone, Debug, PartialEq)]
+    /// # struct Sphere {
+    /// #     center: Point,
+    /// #     radius: Float,
+    /// # }
+    /// #
+    /// # impl Bounded for Sphere {
+    /// #     fn aabb(&self) -> AABB {
+    /// #         let delt = Vector::new(self.radius, self.radius, self.radius);
+    /// #         AABB::with_bounds(self.center - delt, self.center + delt)
+    /// #     }
+    /// #     fn centroid(&self) -> Point {
+    /// #         self.center
+    /// #     }
+    /// # }
+    /// #
+    /// # impl Intersected for Sphere {
+    /// #     fn intersect(&self, ray: &Ray) -> Option<Float> {
+    /// #         let delt = self.center - ray.origin;
+    /// #         let tca = ray.direction.dot(&delt);
+    /// #         let d2 = delt.norm_squared() - tca * tca;
+    /// #         let r_2 = self.radius * self.radius;
+    /// #         if d2 > r_2 {
+    /// #             return None;
+    /// #         }
+    /// #         let t_0 = tca - (r_2 - d2).sqrt();
+    /// #         if t_0 < 0. { None } else { Some(t_0) }
+    /// #     }
+    /// # }
+    /// #
+    /// // Using the same sphere definition than build
+    /// let mut spheres: Vec<_> = (0..100)
+    ///     .map(|i| Sphere{ center: Point::new((i % 10) as Float, (i / 10) as Float, 0.), radius: 0.25 })
+    ///     .collect();
+    /// let mut bvh = BVH::with_max_capacity(&mut spheres, 4);
+    ///
+    /// // Move every sphere away from the ray's origin
+    /// for sphere in spheres.iter_mut() {
+    ///     sphere.center.z += 10.;
+    /// }
+    /// assert!(!bvh.is_sound(&spheres));
+    /// bvh.refit(&spheres);
+    /// assert!(bvh.is_sound(&spheres));
+    ///
+    /// let ray = Ray::new(Point::new(3., 7., -5.), Vector::z_axis());
+    /// let (dist, obj) = bvh.walk(&ray, &spheres).unwrap();
+    /// assert_eq!(obj.center, Point::new(3., 7., 10.));
+    /// assert_eq!(dist, 14.75);
+    /// ```
+    pub fn refit<O: Intersected>(&mut self, objects: &[O]) {
+        self.refit_with(objects, O::aabb)
+    }
+
+    /// Update the bounds of every node of the [`BVH`], as in [`refit`], using `bounds` to get the
+    /// bounds of each object, e.g: to only cover part of their movement.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`refit`]: #method.refit
+    pub fn refit_with<O, F>(&mut self, objects: &[O], bounds: F)
+    where
+        F: Fn(&O) -> AABB,
+    {
+        fn refit_node<O, F>(objects: &[O], references: &[usize], node: &mut Node, bounds: &F)
+        where
+            F: Fn(&O) -> AABB,
+        {
+            node.bounds = match &mut node.kind {
+                NodeEnum::Leaf => leaf_objects(objects, references, node.begin, node.end)
+                    .map(bounds)
+                    .fold(AABB::empty(), |acc, other| acc.union(&other)),
+                NodeEnum::Internal { left, right } => {
+                    refit_node(objects, references, left, bounds);
+                    refit_node(objects, references, right, bounds);
+                    left.bounds.union(&right.bounds)
                 }
             }
-        };
-        check_node(objects, &self.tree)
+        }
+        refit_node(objects, &self.references, &mut self.tree, &bounds)
     }
 
     /// Iterate recursively over the [`BVH`] to find an intersection point with the given [`Ray`].
@@ -257,6 +493,7 @@ impl BVH {
     /// [`Ray`]: ../ray/struct.Ray.html
     /// # Examples
     /// ```
+    /// # use beevee::Float;
     /// # use beevee::{Point, Vector};
     /// # use beevee::aabb::{AABB, Bounded};
     /// # use beevee::bvh::{BVH, Intersected};
@@ -265,7 +502,7 @@ impl BVH {
     /// # #[derive(Clone, Debug, PartialEq)]
     /// # struct Sphere {
     /// #     center: Point,
-    /// #     radius: f32,
+    /// #     radius: Float,
     /// # }
     /// #
     /// # impl Bounded for Sphere {
@@ -279,7 +516,7 @@ impl BVH {
     /// # }
     /// #
     /// # impl Intersected for Sphere {
-    /// #     fn intersect(&self, ray: &Ray) -> Option<f32> {
+    /// #     fn intersect(&self, ray: &Ray) -> Option<Float> {
     /// #         use std::mem;
     /// #
     /// #         let delt = self.center - ray.origin;
@@ -322,29 +559,128 @@ impl BVH {
     /// assert_eq!(dist, 0.5);
     /// assert_eq!(obj, &spheres[0]);
     /// ```
-    pub fn walk<'o, O: Intersected>(&self, ray: &Ray, objects: &'o [O]) -> Option<(f32, &'o O)> {
-        walk_rec_helper(ray, objects, &self.tree, std::f32::INFINITY)
+    pub fn walk<'o, O: Intersected>(&self, ray: &Ray, objects: &'o [O]) -> Option<(Float, &'o O)> {
+        self.walk_with(ray, objects, |o| o.intersect(ray).map(|t| (t, t)))
+    }
+
+    /// Iterate over the [`BVH`] like [`walk`], using the given function to intersect the objects.
+    /// The function returns the distance to the intersection along with any information computed
+    /// while intersecting, which is returned for the closest object instead of only its distance.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`walk`]: struct.BVH.html#method.walk
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::{Point, Vector};
+    /// # use beevee::aabb::{AABB, Bounded};
+    /// # use beevee::bvh::{BVH, Intersected};
+    /// # use beevee::ray::Ray;
+    /// #
+    /// # #[derive(Clone, Debug, PartialEq)]
+    /// # struct Sphere {
+    /// #     center: Point,
+    /// #     radius: Float,
+    /// # }
+    /// #
+    /// # impl Bounded for Sphere {
+    /// #     fn aabb(&self) -> AABB {
+    /// #         let delt = Vector::new(self.radius, self.radius, self.radius);
+    /// #         AABB::with_bounds(self.center - delt, self.center + delt)
+    /// #     }
+    /// #     fn centroid(&self) -> Point {
+    /// #         self.center
+    /// #     }
+    /// # }
+    /// #
+    /// # impl Intersected for Sphere {
+    /// #     fn intersect(&self, ray: &Ray) -> Option<Float> {
+    /// #         use std::mem;
+    /// #
+    /// #         let delt = self.center - ray.origin;
+    /// #         let tca = ray.direction.dot(&delt);
+    /// #         let d2 = delt.norm_squared() - tca * tca;
+    /// #         let r_2 = self.radius * self.radius;
+    /// #
+    /// #         if d2 > r_2 {
+    /// #             return None;
+    /// #         }
+    /// #
+    /// #         let thc = (r_2 - d2).sqrt();
+    /// #         let mut t_0 = tca - thc;
+    /// #         let mut t_1 = tca + thc;
+    /// #
+    /// #         if t_0 > t_1 {
+    /// #             mem::swap(&mut t_0, &mut t_1)
+    /// #         }
+    /// #         if t_0 < 0. {
+    /// #             t_0 = t_1
+    /// #         }
+    /// #         if t_0 < 0. {
+    /// #             None
+    /// #         } else {
+    /// #             Some(t_0)
+    /// #         }
+    /// #     }
+    /// # }
+    /// #
+    /// // Using the same sphere definition than build
+    /// let spheres: &mut [Sphere] = &mut [Sphere{ center: Point::origin(), radius: 0.5 }];
+    /// let bvh = BVH::with_max_capacity(spheres, 32);
+    ///
+    /// // Return the intersection point along with the distance
+    /// let ray = Ray::new(Point::new(-1., 0., 0.), Vector::x_axis());
+    /// let res = bvh.walk_with(&ray, spheres, |s| {
+    ///     s.intersect(&ray).map(|t| (t, ray.origin + ray.direction.as_ref() * t))
+    /// });
+    ///
+    /// assert!(res.is_some());
+    /// let (point, obj) = res.unwrap();
+    /// assert_eq!(point, Point::new(-0.5, 0., 0.));
+    /// assert_eq!(obj, &spheres[0]);
+    /// ```
+    pub fn walk_with<'o, O, H, F>(
+        &self,
+        ray: &Ray,
+        objects: &'o [O],
+        intersect: F,
+    ) -> Option<(H, &'o O)>
+    where
+        F: Fn(&O) -> Option<(Float, H)>,
+    {
+        let references = &self.references;
+        walk_rec_helper(
+            ray,
+            objects,
+            references,
+            &self.tree,
+            Float::INFINITY,
+            &intersect,
+        )
+        .map(|(_, hit, o)| (hit, o))
     }
 }
 
-fn walk_rec_helper<'o, O: Intersected>(
+fn walk_rec_helper<'o, O, H, F>(
     ray: &Ray,
     objects: &'o [O],
+    references: &[usize],
     node: &Node,
-    min: f32,
-) -> Option<(f32, &'o O)> {
-    use std::cmp::Ordering;
-
+    min: Float,
+    intersect: &F,
+) -> Option<(Float, H, &'o O)>
+where
+    F: Fn(&O) -> Option<(Float, H)>,
+{
     match &node.kind {
         // Return the smallest intersection distance on leaf nodes
-        NodeEnum::Leaf => objects[node.begin..node.end]
-            .iter()
-            // This turns the Option<f32> of an intersection into an Option<(f32, &O)>
-            .filter_map(|o| o.intersect(ray).map(|d| (d, o)))
+        NodeEnum::Leaf => leaf_objects(objects, references, node.begin, node.end)
+            // This turns the Option<(Float, H)> of an intersection into an Option<(Float, H, &O)>
+            .filter_map(|o| intersect(o).map(|(d, hit)| (d, hit, o)))
             // Discard values that are too far away
-            .filter(|(dist, _)| dist < &min)
+            .filter(|(dist, _, _)| dist < &min)
             // Only keep the minimum value, if there is one
-            .min_by(|(lhs, _), (rhs, _)| lhs.partial_cmp(rhs).unwrap_or(Ordering::Equal)),
+            .min_by(|(lhs, _, _), (rhs, _, _)| lhs.partial_cmp(rhs).unwrap_or(Ordering::Equal)),
 
         // Recursively find the best node otherwise
         NodeEnum::Internal { left, right } => {
@@ -361,38 +697,102 @@ fn walk_rec_helper<'o, O: Intersected>(
                 return None;
             }
             // Recurse to the nearest Node first
-            let nearest_res = walk_rec_helper(ray, objects, near.as_ref(), min);
+            let nearest_res =
+                walk_rec_helper(ray, objects, references, near.as_ref(), min, intersect);
             // Return immediately if there is no point going to the right at all
             if far_dist > min {
                 return nearest_res;
             }
             match nearest_res {
                 // Short-circuit if we know it is shorter than any point in the far node
-                Some((t, obj)) if t <= far_dist => Some((t, obj)),
+                Some((t, hit, obj)) if t <= far_dist => Some((t, hit, obj)),
                 // We have short_dist <= far_dist <= min in this scenario
                 // With the eventual val.0 in the [short_dist, min) window
                 val => {
                     // Compute the new minimal distance encountered
-                    let min = val.map_or(min, |(t, _)| min.min(t));
+                    let min = val.as_ref().map_or(min, |(t, _, _)| min.min(*t));
                     // Recursing with this new minimum can only return None or a better intersecion
-                    walk_rec_helper(ray, objects, far.as_ref(), min).or(val)
+                    walk_rec_helper(ray, objects, references, far.as_ref(), min, intersect).or(val)
                 }
             }
         }
     }
 }
 
-fn bounds_from_slice<O: Intersected>(objects: &[O]) -> AABB {
+/// Iterate over the objects of the leaf spanning `begin..end`, going through the references of
+/// the [`BVH`] if it has any.
+///
+/// [`BVH`]: struct.BVH.html
+pub(super) fn leaf_objects<'o, 'r, O>(
+    objects: &'o [O],
+    references: &'r [usize],
+    begin: usize,
+    end: usize,
+) -> impl Iterator<Item = &'o O> + 'r
+where
+    'o: 'r,
+{
+    let (direct, referenced) = if references.is_empty() {
+        (&objects[begin..end], references)
+    } else {
+        (&objects[..0], &references[begin..end])
+    };
+    direct
+        .iter()
+        .chain(referenced.iter().map(move |&i| &objects[i]))
+}
+
+/// Stands for an object while building a [`BVH`] without moving it, caching its bounds.
+///
+/// [`BVH`]: struct.BVH.html
+pub(super) struct Proxy {
+    pub(super) index: usize,
+    pub(super) aabb: AABB,
+    pub(super) centroid: Point,
+}
+
+impl Proxy {
+    pub(super) fn from_objects<O: Bounded>(objects: &[O]) -> Vec<Self> {
+        objects
+            .iter()
+            .enumerate()
+            .map(|(index, o)| Proxy {
+                index,
+                aabb: o.aabb(),
+                centroid: o.centroid(),
+            })
+            .collect()
+    }
+
+    /// Return the indices of the objects the proxies stand for, in their order.
+    pub(super) fn order(proxies: Vec<Self>) -> Vec<usize> {
+        proxies.into_iter().map(|proxy| proxy.index).collect()
+    }
+}
+
+impl Bounded for Proxy {
+    fn aabb(&self) -> AABB {
+        self.aabb
+    }
+
+    fn centroid(&self) -> Point {
+        self.centroid
+    }
+}
+
+pub(super) fn bounds_from_slice<O: Bounded>(objects: &[O]) -> AABB {
     objects
         .iter()
         .map(|o| o.aabb())
         .fold(AABB::empty(), |acc, other| acc.union(&other))
 }
 
-fn build_node<O: Intersected>(objects: &mut [O], begin: usize, end: usize, max_cap: usize) -> Node {
-    let aabb = bounds_from_slice(objects);
+fn build_node<O: Bounded>(objects: &mut [O], begin: usize, end: usize, max_cap: usize) -> Node {
+    // Only look at the objects contained in this node
+    let slice = &mut objects[begin..end];
+    let aabb = bounds_from_slice(slice);
     // Don't split nodes under capacity
-    if objects.len() <= max_cap {
+    if slice.len() <= max_cap {
         return Node {
             bounds: aabb,
             begin,
@@ -401,9 +801,9 @@ fn build_node<O: Intersected>(objects: &mut [O], begin: usize, end: usize, max_c
         };
     }
     // Calculate the SAH heuristic for this slice
-    let (split, axis, cost) = compute_sah(&mut objects[begin..end], aabb.surface(), max_cap);
+    let (split, axis, cost) = compute_sah(slice, aabb.surface(), max_cap);
     // Only split if the heuristic shows that it is worth it
-    if cost >= objects.len() as f32 {
+    if cost >= slice.len() as Float {
         return Node {
             bounds: aabb,
             begin,
@@ -411,19 +811,16 @@ fn build_node<O: Intersected>(objects: &mut [O], begin: usize, end: usize, max_c
             kind: NodeEnum::Leaf,
         };
     }
-    // Avoid degenerate cases, and recenter the split inside [begin, end)
+    // Avoid degenerate cases, and recenter the split inside [0, slice.len())
     let split = if split == 0 || split >= (end - begin - 1) {
-        begin + (end - begin) / 2
+        (end - begin) / 2
     } else {
-        begin + split
+        split
     };
     // Project along chosen axis
-    pdqselect::select_by(objects, split, |lhs, rhs| {
-        lhs.centroid()[axis]
-            .partial_cmp(&rhs.centroid()[axis])
-            .expect("Can't use Nans in the SAH computation")
-    });
+    slice.sort_by(|lhs, rhs| compare_centroids(lhs, rhs, axis));
     // Construct children recurivsely on [begin, split) and [split, end)
+    let split = begin + split;
     let left = Box::new(build_node(objects, begin, split, max_cap));
     let right = Box::new(build_node(objects, split, end, max_cap));
     // Build the node recursivelly
@@ -437,30 +834,26 @@ fn build_node<O: Intersected>(objects: &mut [O], begin: usize, end: usize, max_c
 
 /// Returns the index at which to split for SAH, the Axis along which to split, and the calculated
 /// cost.
-fn compute_sah<O: Intersected>(
+pub(super) fn compute_sah<O: Bounded>(
     objects: &mut [O],
-    surface: f32,
+    surface: Float,
     max_cap: usize,
-) -> (usize, Axis, f32) {
+) -> (usize, Axis, Float) {
     // FIXME(Bruno): too imperative to my taste...
     let mut mid = objects.len() / 2;
     let mut dim = Axis::X; // Arbitrary split
-    let mut min = std::f32::INFINITY;
+    let mut min = Float::INFINITY;
 
     // Pre-allocate the vectors
-    let mut left_surfaces = Vec::<f32>::with_capacity(objects.len() - 1);
-    let mut right_surfaces = Vec::<f32>::with_capacity(objects.len() - 1);
+    let mut left_surfaces = Vec::<Float>::with_capacity(objects.len() - 1);
+    let mut right_surfaces = Vec::<Float>::with_capacity(objects.len() - 1);
 
     // For each axis compute the cost
     for &axis in [Axis::X, Axis::Y, Axis::Z].iter() {
         left_surfaces.clear();
         right_surfaces.clear();
         // Sort in order along the axis
-        objects.sort_by(|lhs, rhs| {
-            lhs.centroid()[axis]
-                .partial_cmp(&rhs.centroid()[axis])
-                .expect("Can't use NaNs in the SAH computation")
-        });
+        objects.sort_by(|lhs, rhs| compare_centroids(lhs, rhs, axis));
 
         // Compute the surface for each possible split
         {
@@ -479,9 +872,9 @@ fn compute_sah<O: Intersected>(
         for left_count in 1..objects.len() {
             let right_count = objects.len() - left_count;
 
-            let cost = 1. / max_cap as f32
-                + (left_count as f32 * left_surfaces[left_count - 1]
-                    + right_count as f32 * right_surfaces[right_count])
+            let cost = 1. / max_cap as Float
+                + (left_count as Float * left_surfaces[left_count - 1]
+                    + right_count as Float * right_surfaces[right_count - 1])
                     / surface;
 
             if cost < min {
@@ -493,3 +886,12 @@ fn compute_sah<O: Intersected>(
     }
     (mid, dim, min)
 }
+
+/// Compare the centroids of two objects along an axis. The comparison is used with stable sorts,
+/// to keep the relative order of objects with equal coordinates and build the tree
+/// deterministically. NaN coordinates are ordered after every other one, instead of panicking.
+pub(super) fn compare_centroids<O: Bounded>(lhs: &O, rhs: &O, axis: Axis) -> Ordering {
+    let (lhs, rhs) = (lhs.centroid()[axis], rhs.centroid()[axis]);
+    lhs.partial_cmp(&rhs)
+        .unwrap_or_else(|| lhs.is_nan().cmp(&rhs.is_nan()))
+}
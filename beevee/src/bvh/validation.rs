@@ -0,0 +1,148 @@
+use crate::aabb::{Bounded, AABB};
+
+/// A problem found in a [`BVH`] or [`WideBVH`] by their `validate` method.
+///
+/// Nodes are identified by their index: in depth-first order starting from the root for a
+/// [`BVH`], or their index in the tree for a [`WideBVH`], whose leaves are identified by their
+/// parent. Objects are identified by their index in the slice of objects.
+///
+/// [`BVH`]: struct.BVH.html
+/// [`WideBVH`]: struct.WideBVH.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The range of objects of the node is reversed, or goes past the end of the objects.
+    InvalidRange {
+        /// The node with an invalid range.
+        node: usize,
+        /// The start of its range.
+        begin: usize,
+        /// The end of its range.
+        end: usize,
+    },
+    /// A leaf of a tree built with spatial splits references an object which does not exist.
+    InvalidReference {
+        /// The leaf containing the reference.
+        node: usize,
+        /// The invalid object index.
+        reference: usize,
+    },
+    /// The bounds of a child node are not inside the bounds of its parent.
+    ChildOutside {
+        /// The parent node.
+        node: usize,
+        /// The child node.
+        child: usize,
+    },
+    /// An object of a leaf is not inside the leaf's bounds, or does not overlap them at all for a
+    /// tree built with spatial splits.
+    ObjectOutside {
+        /// The leaf containing the object.
+        node: usize,
+        /// The object outside of its bounds.
+        object: usize,
+    },
+    /// An object is not contained in any leaf.
+    MissingObject {
+        /// The missing object.
+        object: usize,
+    },
+    /// An object is contained in several leaves of a tree without spatial splits.
+    DuplicateObject {
+        /// The duplicated object.
+        object: usize,
+    },
+}
+
+/// Gathers the [`Violation`]s found while going through the nodes of a tree.
+///
+/// [`Violation`]: enum.Violation.html
+pub(super) struct Validator<'a, O> {
+    objects: &'a [O],
+    references: &'a [usize],
+    /// How many leaves each object is contained in.
+    counts: Vec<usize>,
+    violations: Vec<Violation>,
+}
+
+impl<'a, O: Bounded> Validator<'a, O> {
+    pub(super) fn new(objects: &'a [O], references: &'a [usize]) -> Self {
+        Validator {
+            objects,
+            references,
+            counts: vec![0; objects.len()],
+            violations: Vec::new(),
+        }
+    }
+
+    /// Check that the range of a node is valid, returning false otherwise.
+    pub(super) fn range(&mut self, node: usize, begin: usize, end: usize) -> bool {
+        let len = if self.references.is_empty() {
+            self.objects.len()
+        } else {
+            self.references.len()
+        };
+        let valid = begin <= end && end <= len;
+        if !valid {
+            self.violations
+                .push(Violation::InvalidRange { node, begin, end });
+        }
+        valid
+    }
+
+    /// Check that the bounds of a child are inside the bounds of its parent.
+    pub(super) fn child(&mut self, node: usize, bounds: &AABB, child: usize, inner: &AABB) {
+        if bounds.union(inner) != *bounds {
+            self.violations
+                .push(Violation::ChildOutside { node, child });
+        }
+    }
+
+    /// Check the objects of a leaf against its bounds.
+    pub(super) fn leaf(&mut self, node: usize, bounds: &AABB, begin: usize, end: usize) {
+        if !self.range(node, begin, end) {
+            return;
+        }
+        let spatial = !self.references.is_empty();
+        for index in begin..end {
+            let object = if spatial {
+                self.references[index]
+            } else {
+                index
+            };
+            let aabb = match self.objects.get(object) {
+                Some(o) => o.aabb(),
+                None => {
+                    let reference = object;
+                    self.violations
+                        .push(Violation::InvalidReference { node, reference });
+                    continue;
+                }
+            };
+            self.counts[object] += 1;
+            // Objects can be split across the leaves of a spatial tree, only part of them is inside
+            let inside = if spatial {
+                !aabb.intersection(bounds).is_empty()
+            } else {
+                bounds.union(&aabb) == *bounds
+            };
+            if !inside {
+                self.violations
+                    .push(Violation::ObjectOutside { node, object });
+            }
+        }
+    }
+
+    /// Check that each object is in exactly one leaf, or at least one for spatial trees, and
+    /// return every violation found.
+    pub(super) fn finish(mut self) -> Vec<Violation> {
+        let spatial = !self.references.is_empty();
+        for (object, &count) in self.counts.iter().enumerate() {
+            if count == 0 {
+                self.violations.push(Violation::MissingObject { object });
+            } else if count > 1 && !spatial {
+                self.violations.push(Violation::DuplicateObject { object });
+            }
+        }
+        self.violations
+    }
+}
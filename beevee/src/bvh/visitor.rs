@@ -0,0 +1,110 @@
+use super::tree::leaf_objects;
+use super::{NodeEnum, BVH};
+use crate::aabb::AABB;
+use crate::ray::Ray;
+use crate::Float;
+use std::cmp::Ordering;
+
+/// A trait for custom traversals of a [`BVH`] with [`BVH::traverse_with`], deciding which nodes
+/// are visited, and accumulating anything needed from the objects they contain.
+///
+/// [`BVH`]: struct.BVH.html
+/// [`BVH::traverse_with`]: struct.BVH.html#method.traverse_with
+pub trait Visitor<O> {
+    /// Return true if the node with the given bounds should be visited, `distance` being the
+    /// distance along the ray at which it enters the bounds, or `None` if it misses them.
+    fn descend(&mut self, bounds: &AABB, distance: Option<Float>) -> bool;
+    /// Visit an object of a leaf node which was descended into.
+    fn visit(&mut self, object: &O);
+}
+
+impl BVH {
+    /// Traverse the [`BVH`] along the given [`Ray`], letting the [`Visitor`] decide which nodes
+    /// are visited and what to do with the objects of their leaves, e.g: to gather statistics, or
+    /// for queries which are not covered by [`walk`].
+    ///
+    /// The children of a node are visited from the nearest to the furthest along the ray, those
+    /// missed by the ray last. Objects referenced by several leaves of a [`BVH`] built with
+    /// [`build_spatial`] are visited once per leaf.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`Ray`]: ../ray/struct.Ray.html
+    /// [`Visitor`]: trait.Visitor.html
+    /// [`build_spatial`]: #method.build_spatial
+    /// [`walk`]: #method.walk
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::{Point, Vector};
+    /// # use beevee::aabb::AABB;
+    /// # use beevee::bvh::{BVH, Visitor};
+    /// # use beevee::ray::Ray;
+    /// #
+    /// /// Collect the points closer than a distance to the ray's line, counting visited nodes.
+    /// struct Cylinder<'r> {
+    ///     ray: &'r Ray,
+    ///     radius: Float,
+    ///     nodes: usize,
+    ///     found: Vec<Point>,
+    /// }
+    ///
+    /// impl<'r> Visitor<Point> for Cylinder<'r> {
+    ///     fn descend(&mut self, bounds: &AABB, _: Option<Float>) -> bool {
+    ///         self.nodes += 1;
+    ///         let delt = Vector::new(self.radius, self.radius, self.radius);
+    ///         let grown = AABB::with_bounds(bounds.low - delt, bounds.high + delt);
+    ///         self.ray.aabb_intersection(&grown).is_some()
+    ///     }
+    ///
+    ///     fn visit(&mut self, point: &Point) {
+    ///         let delt = point - self.ray.origin;
+    ///         let along = delt.dot(&self.ray.direction);
+    ///         if along >= 0. && (delt.norm_squared() - along * along).sqrt() <= self.radius {
+    ///             self.found.push(*point);
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut points: Vec<_> = (0..100)
+    ///     .map(|i| Point::new((i % 10) as Float, (i / 10) as Float, 0.))
+    ///     .collect();
+    /// let bvh = BVH::build_linear(&mut points);
+    ///
+    /// let ray = Ray::new(Point::new(-1., 3.1, 0.), Vector::x_axis());
+    /// let mut visitor = Cylinder { ray: &ray, radius: 0.5, nodes: 0, found: Vec::new() };
+    /// bvh.traverse_with(&ray, &points, &mut visitor);
+    /// assert_eq!(visitor.found.len(), 10);
+    /// assert!(visitor.found.iter().all(|p| p.y == 3.));
+    /// // The tree has more than 50 nodes, only part of them were visited
+    /// assert!(visitor.nodes < 30);
+    /// ```
+    pub fn traverse_with<O, V: Visitor<O>>(&self, ray: &Ray, objects: &[O], visitor: &mut V) {
+        let mut stack = vec![(&self.tree, ray.aabb_intersection(&self.tree.bounds))];
+        while let Some((node, distance)) = stack.pop() {
+            // Ask the visitor as late as possible, to let it use what it found in previous nodes
+            if !visitor.descend(&node.bounds, distance) {
+                continue;
+            }
+            match &node.kind {
+                NodeEnum::Leaf => leaf_objects(objects, &self.references, node.begin, node.end)
+                    .for_each(|o| visitor.visit(o)),
+                NodeEnum::Internal { left, right } => {
+                    let left = (left.as_ref(), ray.aabb_intersection(&left.bounds));
+                    let right = (right.as_ref(), ray.aabb_intersection(&right.bounds));
+                    // Nodes which are missed come after the others
+                    let left_first = match (left.1, right.1) {
+                        (Some(lhs), Some(rhs)) => lhs.partial_cmp(&rhs) != Some(Ordering::Greater),
+                        (lhs, _) => lhs.is_some(),
+                    };
+                    // The nearest node is pushed last to be visited first
+                    if left_first {
+                        stack.extend_from_slice(&[right, left]);
+                    } else {
+                        stack.extend_from_slice(&[left, right]);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,783 @@
+use super::packet::PacketWalker;
+use super::tree::leaf_objects;
+use super::validation::Validator;
+use super::{Intersected, Node, NodeEnum, Violation, BVH};
+use crate::aabb::{Bounded, AABB};
+use crate::ray::{slab, Lanes, Ray, FAR_PADDING};
+use crate::{Float, Point, Vector};
+use std::cmp::Ordering;
+use std::io::{self, Read, Write};
+
+/// A [`WideBVH`] whose nodes have up to 4 children, tested with a single SSE instruction per
+/// operation.
+///
+/// [`WideBVH`]: struct.WideBVH.html
+pub type BVH4 = WideBVH<4>;
+
+/// A [`WideBVH`] whose nodes have up to 8 children.
+///
+/// [`WideBVH`]: struct.WideBVH.html
+pub type BVH8 = WideBVH<8>;
+
+/// The first bytes written by [`WideBVH::write`].
+///
+/// [`WideBVH::write`]: struct.WideBVH.html#method.write
+#[cfg(not(feature = "f64"))]
+const MAGIC: &[u8; 4] = b"BVHW";
+
+/// The first bytes written by [`WideBVH::write`], whose coordinates are `f64`.
+///
+/// [`WideBVH::write`]: struct.WideBVH.html#method.write
+#[cfg(feature = "f64")]
+const MAGIC: &[u8; 4] = b"BVHD";
+
+/// A child of a [`WideNode`]: either another node, or a range of objects.
+///
+/// [`WideNode`]: struct.WideNode.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WideChild {
+    /// Another node, given by its index in [`WideBVH::nodes`].
+    ///
+    /// [`WideBVH::nodes`]: struct.WideBVH.html#method.nodes
+    Node(usize),
+    /// A leaf, whose objects are given by [`WideBVH::object_index`] for each position in
+    /// `begin..end`.
+    ///
+    /// [`WideBVH::object_index`]: struct.WideBVH.html#method.object_index
+    Leaf {
+        /// The first position of the leaf's objects.
+        begin: usize,
+        /// The position after the last of the leaf's objects.
+        end: usize,
+    },
+}
+
+/// A node of a [`WideBVH`], storing the bounds of its children coordinate by coordinate so that a
+/// ray can be tested against all of them at once.
+///
+/// [`WideBVH`]: struct.WideBVH.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct WideNode<const N: usize> {
+    /// The lowest coordinates of each child's bounds, axis by axis.
+    pub low: [[Float; N]; 3],
+    /// The highest coordinates of each child's bounds, axis by axis.
+    pub high: [[Float; N]; 3],
+    /// The children of the node, only the first `count` of them being used.
+    pub children: [WideChild; N],
+    /// The number of children of the node.
+    pub count: usize,
+}
+
+/// A [`BVH`] whose nodes have up to `N` children instead of 2, built by collapsing a binary
+/// [`BVH`]. The bounds of the children of a node are intersected with a ray all at once, using
+/// SIMD instructions when available: the tree is shallower, and each ray visits fewer nodes.
+///
+/// [`BVH`]: struct.BVH.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct WideBVH<const N: usize> {
+    nodes: Vec<WideNode<N>>,
+    /// The references of the collapsed [`BVH`], if it was built with spatial splits.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    references: Vec<usize>,
+}
+
+impl<const N: usize> WideBVH<N> {
+    /// Build a [`WideBVH`] for the given slice of objects, by collapsing the [`BVH`] built with
+    /// [`BVH::build`].
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`BVH::build`]: struct.BVH.html#method.build
+    /// [`WideBVH`]: struct.WideBVH.html
+    pub fn build<O: Intersected>(objects: &mut [O]) -> Self {
+        Self::collapse(&BVH::build(objects))
+    }
+
+    /// Build a [`WideBVH`] for the given slice of objects, by collapsing the [`BVH`] built with
+    /// [`BVH::with_max_capacity`].
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`BVH::with_max_capacity`]: struct.BVH.html#method.with_max_capacity
+    /// [`WideBVH`]: struct.WideBVH.html
+    pub fn with_max_capacity<O: Intersected>(objects: &mut [O], max_cap: usize) -> Self {
+        Self::collapse(&BVH::with_max_capacity(objects, max_cap))
+    }
+
+    /// Collapse a binary [`BVH`] into a [`WideBVH`] over the same objects, in the same order.
+    ///
+    /// Each node of the [`WideBVH`] takes the place of a subtree of the [`BVH`], repeatedly
+    /// opening its largest internal node until it has `N` children.
+    ///
+    /// [`BVH`]: struct.BVH.html
+    /// [`WideBVH`]: struct.WideBVH.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is smaller than 2.
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::{Point, Vector};
+    /// # use beevee::aabb::{AABB, Bounded};
+    /// # use beevee::bvh::{BVH, BVH4, BVH8, Intersected};
+    /// # use beevee::ray::Ray;
+    /// #
+    /// # #[derive(Clone, Debug, PartialEq)]
+    /// # struct Sphere {
+    /// #     center: Point,
+    /// #     radius: Float,
+    /// # }
+    /// #
+    /// # impl Bounded for Sphere {
+    /// #     fn aabb(&self) -> AABB {
+    /// #         let delt = Vector::new(self.radius, self.radius, self.radius);
+    /// #         AABB::with_bounds(self.center - delt, self.center + delt)
+    /// #     }
+    /// #     fn centroid(&self) -> Point {
+    /// #         self.center
+    /// #     }
+    /// # }
+    /// #
+    /// # impl Intersected for Sphere {
+    /// #     fn intersect(&self, ray: &Ray) -> Option<Float> {
+    /// #         let delt = self.center - ray.origin;
+    /// #         let tca = ray.direction.dot(&delt);
+    /// #         let d2 = delt.norm_squared() - tca * tca;
+    /// #         let r_2 = self.radius * self.radius;
+    /// #         if d2 > r_2 {
+    /// #             return None;
+    /// #         }
+    /// #         let t_0 = tca - (r_2 - d2).sqrt();
+    /// #         if t_0 < 0. { None } else { Some(t_0) }
+    /// #     }
+    /// # }
+    /// #
+    /// // Using the same sphere definition than build
+    /// let mut spheres: Vec<_> = (0..1000)
+    ///     .map(|i| Sphere {
+    ///         center: Point::new((i % 10) as Float, (i / 10 % 10) as Float, (i / 100) as Float),
+    ///         radius: 0.25,
+    ///     })
+    ///     .collect();
+    /// let bvh = BVH::with_max_capacity(&mut spheres, 4);
+    /// let bvh4 = BVH4::collapse(&bvh);
+    /// let bvh8 = BVH8::collapse(&bvh);
+    /// assert!(bvh4.is_sound(&spheres));
+    /// assert!(bvh8.is_sound(&spheres));
+    ///
+    /// // The wide trees find the same intersections as the binary one
+    /// for i in 0..100 {
+    ///     let origin = Point::new((i % 10) as Float + 0.1, (i / 10) as Float - 0.1, -5.);
+    ///     let ray = Ray::new(origin, Vector::z_axis());
+    ///     let expected = bvh.walk(&ray, &spheres);
+    ///     assert!(expected.is_some());
+    ///     assert_eq!(bvh4.walk(&ray, &spheres), expected);
+    ///     assert_eq!(bvh8.walk(&ray, &spheres), expected);
+    /// }
+    /// let ray = Ray::new(Point::new(-1., 0., 0.), -Vector::x_axis());
+    /// assert_eq!(bvh4.walk(&ray, &spheres), None);
+    /// ```
+    pub fn collapse(bvh: &BVH) -> Self {
+        assert!(N >= 2, "a wide BVH's nodes must have at least 2 children");
+        let mut nodes = Vec::new();
+        collapse_node(&bvh.tree, &mut nodes);
+        Self {
+            nodes,
+            references: bvh.references.clone(),
+        }
+    }
+
+    /// Return true if the [`WideBVH`] has been built soundly, as for [`BVH::is_sound`].
+    ///
+    /// [`BVH::is_sound`]: struct.BVH.html#method.is_sound
+    /// [`WideBVH`]: struct.WideBVH.html
+    pub fn is_sound<O: Intersected>(&self, objects: &[O]) -> bool {
+        self.validate(objects).is_empty()
+    }
+
+    /// Check that the [`WideBVH`] has been built soundly, returning every [`Violation`] found, as
+    /// [`BVH::validate`] does.
+    ///
+    /// [`BVH::validate`]: struct.BVH.html#method.validate
+    /// [`Violation`]: enum.Violation.html
+    /// [`WideBVH`]: struct.WideBVH.html
+    pub fn validate<O: Bounded>(&self, objects: &[O]) -> Vec<Violation> {
+        let mut validator = Validator::new(objects, &self.references);
+        for (index, node) in self.nodes.iter().enumerate() {
+            for lane in 0..node.count {
+                let bounds = node.bounds(lane);
+                match node.children[lane] {
+                    WideChild::Leaf { begin, end } => validator.leaf(index, &bounds, begin, end),
+                    WideChild::Node(child) => {
+                        validator.child(index, &bounds, child, &self.nodes[child].union())
+                    }
+                }
+            }
+        }
+        validator.finish()
+    }
+
+    /// Update the bounds of every node of the [`WideBVH`] after its objects have moved, as
+    /// [`BVH::refit`] does.
+    ///
+    /// [`BVH::refit`]: struct.BVH.html#method.refit
+    /// [`WideBVH`]: struct.WideBVH.html
+    pub fn refit<O: Intersected>(&mut self, objects: &[O]) {
+        self.refit_with(objects, O::aabb)
+    }
+
+    /// Update the bounds of every node of the [`WideBVH`], using `bounds` to get the bounds of
+    /// each object, as [`BVH::refit_with`] does.
+    ///
+    /// [`BVH::refit_with`]: struct.BVH.html#method.refit_with
+    /// [`WideBVH`]: struct.WideBVH.html
+    pub fn refit_with<O, F>(&mut self, objects: &[O], bounds: F)
+    where
+        F: Fn(&O) -> AABB,
+    {
+        // Children are always stored after their parent, update them first
+        for index in (0..self.nodes.len()).rev() {
+            for lane in 0..self.nodes[index].count {
+                let aabb = match self.nodes[index].children[lane] {
+                    WideChild::Leaf { begin, end } => {
+                        leaf_objects(objects, &self.references, begin, end)
+                            .map(&bounds)
+                            .fold(AABB::empty(), |acc, other| acc.union(&other))
+                    }
+                    WideChild::Node(child) => self.nodes[child].union(),
+                };
+                self.nodes[index].set_bounds(lane, &aabb);
+            }
+        }
+    }
+
+    /// Write the [`WideBVH`] in a compact binary format, to be read back by [`read`] instead of
+    /// building it again.
+    ///
+    /// The objects are not written: they must be stored in the same order when reading it back,
+    /// e.g: the order given by [`BVH::build_order`].
+    ///
+    /// [`BVH::build_order`]: struct.BVH.html#method.build_order
+    /// [`WideBVH`]: struct.WideBVH.html
+    /// [`read`]: #method.read
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::Point;
+    /// # use beevee::aabb::AABB;
+    /// # use beevee::bvh::{BVH, BVH4};
+    /// #
+    /// let points: Vec<_> = (0..100)
+    ///     .map(|i| Point::new((i % 10) as Float, (i / 10) as Float, 0.))
+    ///     .collect();
+    /// let (bvh, order) = BVH::build_order(&points);
+    /// let bvh = BVH4::collapse(&bvh);
+    ///
+    /// let mut bytes = Vec::new();
+    /// bvh.write(&mut bytes).unwrap();
+    /// assert_eq!(BVH4::read(bytes.as_slice(), points.len()).unwrap(), bvh);
+    ///
+    /// // Trees of another width, truncated, or for fewer objects are rejected
+    /// assert!(beevee::bvh::BVH8::read(bytes.as_slice(), points.len()).is_err());
+    /// assert!(BVH4::read(&bytes[..bytes.len() - 1], points.len()).is_err());
+    /// assert!(BVH4::read(bytes.as_slice(), points.len() - 1).is_err());
+    /// ```
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(N as u32).to_le_bytes())?;
+        writer.write_all(&(self.nodes.len() as u64).to_le_bytes())?;
+        for node in self.nodes.iter() {
+            writer.write_all(&(node.count as u32).to_le_bytes())?;
+            for coordinates in node.low.iter().chain(node.high.iter()) {
+                for coordinate in coordinates.iter() {
+                    writer.write_all(&coordinate.to_le_bytes())?;
+                }
+            }
+            for child in node.children.iter() {
+                let (kind, first, second) = match *child {
+                    WideChild::Node(index) => (0u8, index, 0),
+                    WideChild::Leaf { begin, end } => (1u8, begin, end),
+                };
+                writer.write_all(&[kind])?;
+                writer.write_all(&(first as u64).to_le_bytes())?;
+                writer.write_all(&(second as u64).to_le_bytes())?;
+            }
+        }
+        writer.write_all(&(self.references.len() as u64).to_le_bytes())?;
+        for &reference in self.references.iter() {
+            writer.write_all(&(reference as u64).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read a [`WideBVH`] written by [`write`], for a slice of `objects` objects.
+    ///
+    /// The tree's structure is checked, returning an error of kind [`InvalidData`] if it was not
+    /// written by [`write`] for a [`WideBVH`] of the same width, or if its leaves refer to objects
+    /// outside of the slice.
+    ///
+    /// [`InvalidData`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidData
+    /// [`WideBVH`]: struct.WideBVH.html
+    /// [`write`]: #method.write
+    pub fn read<R: Read>(mut reader: R, objects: usize) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a wide BVH"));
+        }
+        if read_u32(&mut reader)? as usize != N {
+            return Err(invalid("wide BVH of another width"));
+        }
+        let len = read_u64(&mut reader)? as usize;
+        let mut nodes = Vec::new();
+        for index in 0..len {
+            let mut node = WideNode::empty();
+            node.count = read_u32(&mut reader)? as usize;
+            if node.count > N {
+                return Err(invalid("too many children in wide BVH node"));
+            }
+            for coordinates in node.low.iter_mut().chain(node.high.iter_mut()) {
+                for coordinate in coordinates.iter_mut() {
+                    *coordinate = read_float(&mut reader)?;
+                }
+            }
+            for child in node.children.iter_mut() {
+                let mut kind = [0];
+                reader.read_exact(&mut kind)?;
+                let first = read_u64(&mut reader)? as usize;
+                let second = read_u64(&mut reader)? as usize;
+                *child = match kind[0] {
+                    // Children are stored after their parent, which also rules out cycles
+                    0 if index < first && first < len => WideChild::Node(first),
+                    1 if first <= second => WideChild::Leaf {
+                        begin: first,
+                        end: second,
+                    },
+                    _ => return Err(invalid("invalid child in wide BVH node")),
+                };
+            }
+            nodes.push(node);
+        }
+        let len = read_u64(&mut reader)? as usize;
+        let references: Vec<_> = (0..len)
+            .map(|_| read_u64(&mut reader).map(|reference| reference as usize))
+            .collect::<io::Result<_>>()?;
+        if references.iter().any(|&reference| reference >= objects) {
+            return Err(invalid("wide BVH reference to a non-existent object"));
+        }
+        // Leaves index the references, or the objects directly when there are none
+        let positions = if references.is_empty() {
+            objects
+        } else {
+            references.len()
+        };
+        let leaves = nodes
+            .iter()
+            .flat_map(|node| node.children[..node.count].iter());
+        for child in leaves {
+            if let WideChild::Leaf { end, .. } = *child {
+                if end > positions {
+                    return Err(invalid("wide BVH leaf outside of the objects"));
+                }
+            }
+        }
+        Ok(Self { nodes, references })
+    }
+
+    /// Get the nodes of the [`WideBVH`], the root being the first one, e.g: to upload them to a
+    /// GPU and walk them there.
+    ///
+    /// The children of the nodes are stored after their parent. Unused children have empty
+    /// bounds, which no ray can enter.
+    ///
+    /// [`WideBVH`]: struct.WideBVH.html
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::Point;
+    /// # use beevee::bvh::{BVH, BVH4, WideChild};
+    /// #
+    /// let points: Vec<_> = (0..100)
+    ///     .map(|i| Point::new((i % 10) as Float, (i / 10) as Float, 0.))
+    ///     .collect();
+    /// let (bvh, _) = BVH::build_order(&points);
+    /// let bvh = BVH4::collapse(&bvh);
+    ///
+    /// // Every object is found in exactly one leaf
+    /// let mut found = vec![0; points.len()];
+    /// for node in bvh.nodes() {
+    ///     for child in node.children[..node.count].iter() {
+    ///         if let WideChild::Leaf { begin, end } = *child {
+    ///             for position in begin..end {
+    ///                 found[bvh.object_index(position)] += 1;
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// assert!(found.iter().all(|&count| count == 1));
+    /// ```
+    pub fn nodes(&self) -> &[WideNode<N>] {
+        &self.nodes
+    }
+
+    /// Get the index, in the slice of objects the [`WideBVH`] was built for, of the object found
+    /// at the given position of a [`WideChild::Leaf`].
+    ///
+    /// Objects are referenced more than once by trees built with spatial splits: the positions
+    /// of their leaves are not the indices of their objects.
+    ///
+    /// [`WideBVH`]: struct.WideBVH.html
+    /// [`WideChild::Leaf`]: enum.WideChild.html#variant.Leaf
+    pub fn object_index(&self, position: usize) -> usize {
+        if self.references.is_empty() {
+            position
+        } else {
+            self.references[position]
+        }
+    }
+
+    /// Iterate over the [`WideBVH`] to find an intersection point with the given [`Ray`], as
+    /// [`BVH::walk`] does.
+    ///
+    /// [`BVH::walk`]: struct.BVH.html#method.walk
+    /// [`Ray`]: ../ray/struct.Ray.html
+    /// [`WideBVH`]: struct.WideBVH.html
+    pub fn walk<'o, O: Intersected>(&self, ray: &Ray, objects: &'o [O]) -> Option<(Float, &'o O)> {
+        self.walk_with(ray, objects, |o| o.intersect(ray).map(|t| (t, t)))
+    }
+
+    /// Iterate over the [`WideBVH`] like [`walk`], using the given function to intersect the
+    /// objects, as [`BVH::walk_with`] does.
+    ///
+    /// Children are visited from the nearest to the furthest along the ray, skipping those whose
+    /// bounds are missed by the ray, or are further than the closest intersection found so far.
+    ///
+    /// [`BVH::walk_with`]: struct.BVH.html#method.walk_with
+    /// [`WideBVH`]: struct.WideBVH.html
+    /// [`walk`]: #method.walk
+    pub fn walk_with<'o, O, H, F>(
+        &self,
+        ray: &Ray,
+        objects: &'o [O],
+        intersect: F,
+    ) -> Option<(H, &'o O)>
+    where
+        F: Fn(&O) -> Option<(Float, H)>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        // Avoid NaNs when an axis-aligned ray starts on the plane of a bounding box's face, the
+        // inverses of the direction's components are at worst infinite but never NaN themselves
+        let clamp = |inv: Float| inv.clamp(Float::MIN, Float::MAX);
+        let inv_direction = ray.inv_direction.map(clamp);
+        let mut walker = Walker {
+            origin: ray.origin,
+            inv_direction,
+            objects,
+            references: &self.references,
+            intersect: &intersect,
+            min: Float::INFINITY,
+            closest: None,
+        };
+        walker.visit(&self.nodes, 0);
+        walker.closest
+    }
+
+    /// Find the closest intersection of each [`Ray`] of a packet of `M` rays, as
+    /// [`BVH::walk_packet`] does.
+    ///
+    /// The bounds of each child of a node are tested against every ray of the packet at once.
+    ///
+    /// [`BVH::walk_packet`]: struct.BVH.html#method.walk_packet
+    /// [`Ray`]: ../ray/struct.Ray.html
+    ///
+    /// # Examples
+    /// ```
+    /// # use beevee::Float;
+    /// # use beevee::{Point, Vector};
+    /// # use beevee::aabb::{AABB, Bounded};
+    /// # use beevee::bvh::{Intersected, BVH8};
+    /// # use beevee::ray::Ray;
+    /// # use nalgebra::Unit;
+    /// #
+    /// # #[derive(Clone, Debug, PartialEq)]
+    /// # struct Cube(AABB);
+    /// #
+    /// # impl Bounded for Cube {
+    /// #     fn aabb(&self) -> AABB {
+    /// #         self.0
+    /// #     }
+    /// #     fn centroid(&self) -> Point {
+    /// #         self.0.centroid()
+    /// #     }
+    /// # }
+    /// #
+    /// # impl Intersected for Cube {
+    /// #     fn intersect(&self, ray: &Ray) -> Option<Float> {
+    /// #         ray.aabb_intersection(&self.0)
+    /// #     }
+    /// # }
+    /// #
+    /// let mut cubes: Vec<_> = (0..100)
+    ///     .map(|i| {
+    ///         let low = Point::new((i % 10) as Float, (i / 10) as Float, 5.);
+    ///         Cube(AABB::with_bounds(low, low + Vector::new(0.5, 0.5, 0.5)))
+    ///     })
+    ///     .collect();
+    /// let bvh = BVH8::build(&mut cubes);
+    ///
+    /// let direction = Unit::new_normalize(Vector::new(0.01, 0.01, 1.));
+    /// let rays: Vec<_> = (0..8)
+    ///     .map(|i| Ray::new(Point::new(i as Float + 0.2, 0.4 * i as Float, 0.), direction))
+    ///     .collect();
+    /// let rays = [rays[0], rays[1], rays[2], rays[3], rays[4], rays[5], rays[6], rays[7]];
+    /// let hits = bvh.walk_packet(&rays, &cubes);
+    /// for (ray, hit) in rays.iter().zip(hits.iter()) {
+    ///     assert_eq!(hit, &bvh.walk(ray, &cubes));
+    /// }
+    /// assert!(hits.iter().any(Option::is_some) && hits.iter().any(Option::is_none));
+    /// ```
+    pub fn walk_packet<'o, O: Intersected, const M: usize>(
+        &self,
+        rays: &[Ray; M],
+        objects: &'o [O],
+    ) -> [Option<(Float, &'o O)>; M] {
+        self.walk_packet_with(rays, objects, |lane, o| {
+            o.intersect(&rays[lane]).map(|t| (t, t))
+        })
+    }
+
+    /// Iterate over the [`WideBVH`] like [`walk_packet`], using the given function to intersect
+    /// the objects with the ray of the given lane of the packet, as [`BVH::walk_packet_with`]
+    /// does.
+    ///
+    /// [`BVH::walk_packet_with`]: struct.BVH.html#method.walk_packet_with
+    /// [`WideBVH`]: struct.WideBVH.html
+    /// [`walk_packet`]: #method.walk_packet
+    pub fn walk_packet_with<'o, O, H, F, const M: usize>(
+        &self,
+        rays: &[Ray; M],
+        objects: &'o [O],
+        intersect: F,
+    ) -> [Option<(H, &'o O)>; M]
+    where
+        F: Fn(usize, &O) -> Option<(Float, H)>,
+    {
+        let mut walker = PacketWalker::new(rays, objects, &self.references, &intersect);
+        if !self.nodes.is_empty() {
+            visit_packet(&mut walker, &self.nodes, 0);
+        }
+        walker.closest
+    }
+}
+
+/// Visit the children of a node of a [`WideBVH`] entered by a packet of rays, from the nearest
+/// to the furthest.
+///
+/// [`WideBVH`]: struct.WideBVH.html
+fn visit_packet<O, H, F, const N: usize, const M: usize>(
+    walker: &mut PacketWalker<O, H, F, M>,
+    nodes: &[WideNode<N>],
+    index: usize,
+) where
+    F: Fn(usize, &O) -> Option<(Float, H)>,
+{
+    let node = &nodes[index];
+    let mut children = [(0, [Float::INFINITY; M], Float::INFINITY); N];
+    let mut hits = 0;
+    for lane in 0..node.count {
+        let distances = walker.packet.aabb_intersections(&node.bounds(lane));
+        if walker.is_active(&distances) {
+            children[hits] = (lane, distances, walker.nearest(&distances));
+            hits += 1;
+        }
+    }
+    let children = &mut children[..hits];
+    children.sort_unstable_by(|lhs, rhs| lhs.2.partial_cmp(&rhs.2).unwrap_or(Ordering::Equal));
+    for (lane, distances, _) in children.iter() {
+        // Closer intersections may have been found in the previous children
+        if !walker.is_active(distances) {
+            continue;
+        }
+        match node.children[*lane] {
+            WideChild::Leaf { begin, end } => walker.visit_leaf(begin, end, distances),
+            WideChild::Node(child) => visit_packet(walker, nodes, child),
+        }
+    }
+}
+
+/// The state of a traversal of a [`WideBVH`], to find the closest intersection along a ray.
+///
+/// [`WideBVH`]: struct.WideBVH.html
+struct Walker<'o, 'f, O, H, F> {
+    origin: Point,
+    inv_direction: Vector,
+    objects: &'o [O],
+    references: &'f [usize],
+    intersect: &'f F,
+    min: Float,
+    closest: Option<(H, &'o O)>,
+}
+
+impl<'o, 'f, O, H, F> Walker<'o, 'f, O, H, F>
+where
+    F: Fn(&O) -> Option<(Float, H)>,
+{
+    fn visit<const N: usize>(&mut self, nodes: &[WideNode<N>], index: usize) {
+        let node = &nodes[index];
+        let distances = node.intersect(&self.origin, &self.inv_direction);
+        // Sort the children which were hit from the nearest to the furthest
+        let mut order = [0; N];
+        let mut hits = 0;
+        for (lane, distance) in distances.iter().enumerate().take(node.count) {
+            if *distance < self.min {
+                order[hits] = lane;
+                hits += 1;
+            }
+        }
+        let order = &mut order[..hits];
+        order.sort_unstable_by(|&lhs, &rhs| {
+            distances[lhs]
+                .partial_cmp(&distances[rhs])
+                .unwrap_or(Ordering::Equal)
+        });
+        for &lane in order.iter() {
+            // Closer intersections may have been found in the previous children
+            if distances[lane] > self.min {
+                break;
+            }
+            match node.children[lane] {
+                WideChild::Leaf { begin, end } => {
+                    for o in leaf_objects(self.objects, self.references, begin, end) {
+                        match (self.intersect)(o) {
+                            Some((dist, hit)) if dist < self.min => {
+                                self.min = dist;
+                                self.closest = Some((hit, o));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                WideChild::Node(child) => self.visit(nodes, child),
+            }
+        }
+    }
+}
+
+impl<const N: usize> WideNode<N> {
+    fn empty() -> Self {
+        WideNode {
+            low: [[Float::INFINITY; N]; 3],
+            high: [[Float::NEG_INFINITY; N]; 3],
+            children: [WideChild::Leaf { begin: 0, end: 0 }; N],
+            count: 0,
+        }
+    }
+
+    fn bounds(&self, lane: usize) -> AABB {
+        AABB {
+            low: Point::new(self.low[0][lane], self.low[1][lane], self.low[2][lane]),
+            high: Point::new(self.high[0][lane], self.high[1][lane], self.high[2][lane]),
+        }
+    }
+
+    fn set_bounds(&mut self, lane: usize, bounds: &AABB) {
+        for axis in 0..3 {
+            self.low[axis][lane] = bounds.low[axis];
+            self.high[axis][lane] = bounds.high[axis];
+        }
+    }
+
+    /// Return the bounds of all the children of the node.
+    fn union(&self) -> AABB {
+        (0..self.count)
+            .map(|lane| self.bounds(lane))
+            .fold(AABB::empty(), |acc, other| acc.union(&other))
+    }
+
+    /// Return the distance at which the ray enters the bounds of each child, or infinity if it
+    /// misses them. The ray's origin is at distance 0 of the bounds containing it.
+    fn intersect(&self, origin: &Point, inv_direction: &Vector) -> [Float; N] {
+        let mut near = [0.; N];
+        let mut far = [Float::INFINITY; N];
+        for axis in 0..3 {
+            slab(
+                Lanes::Each(&self.low[axis]),
+                Lanes::Each(&self.high[axis]),
+                Lanes::Splat(origin[axis]),
+                Lanes::Splat(inv_direction[axis]),
+                (&mut near, &mut far),
+            );
+        }
+        let mut distances = [Float::INFINITY; N];
+        for lane in 0..N {
+            if near[lane] <= far[lane] * FAR_PADDING {
+                distances[lane] = near[lane];
+            }
+        }
+        distances
+    }
+}
+
+/// Append the wide node replacing the subtree rooted at `node`, and its descendants, to `nodes`.
+/// Return the index of the new node.
+fn collapse_node<const N: usize>(node: &Node, nodes: &mut Vec<WideNode<N>>) -> usize {
+    let mut children = vec![node];
+    // Open the largest internal node, keeping the children in the order of the objects
+    while children.len() < N {
+        let largest = children
+            .iter()
+            .enumerate()
+            .filter(|(_, child)| matches!(child.kind, NodeEnum::Internal { .. }))
+            .max_by(|(_, lhs), (_, rhs)| {
+                let (lhs, rhs) = (lhs.bounds.surface(), rhs.bounds.surface());
+                lhs.partial_cmp(&rhs).unwrap_or(Ordering::Equal)
+            })
+            .map(|(index, _)| index);
+        match largest.map(|index| (index, &children[index].kind)) {
+            Some((index, NodeEnum::Internal { left, right })) => {
+                children.splice(index..=index, vec![left.as_ref(), right.as_ref()]);
+            }
+            _ => break,
+        }
+    }
+    let index = nodes.len();
+    nodes.push(WideNode::empty());
+    for (lane, child) in children.into_iter().enumerate() {
+        let kind = match child.kind {
+            NodeEnum::Leaf => WideChild::Leaf {
+                begin: child.begin,
+                end: child.end,
+            },
+            NodeEnum::Internal { .. } => WideChild::Node(collapse_node(child, nodes)),
+        };
+        let node = &mut nodes[index];
+        node.set_bounds(lane, &child.bounds);
+        node.children[lane] = kind;
+        node.count = lane + 1;
+    }
+    index
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_float<R: Read>(reader: &mut R) -> io::Result<Float> {
+    let mut bytes = [0; std::mem::size_of::<Float>()];
+    reader.read_exact(&mut bytes)?;
+    Ok(Float::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
@@ -1,16 +1,42 @@
 #![warn(missing_docs)]
 
 //! A Bounding Volume Hierarchy crate for use with ray-tracing.
+//!
+//! Coordinates and distances are stored as [`Float`], which is `f32` by default. Enabling the
+//! `f64` feature uses `f64` instead, for scenes whose extents are too large for the precision of
+//! `f32`.
+//!
+//! [`Float`]: type.Float.html
+
+/// The floating point type of the coordinates and distances.
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+
+/// The floating point type of the coordinates and distances.
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+/// Basic mathematical constants of the [`Float`] type.
+///
+/// [`Float`]: type.Float.html
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+
+/// Basic mathematical constants of the [`Float`] type.
+///
+/// [`Float`]: type.Float.html
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
 
 /// The point to describe the [`AABB`]'s corners.
 ///
 /// [`AABB`]: aabb/struct.AABB.html
-pub type Point = nalgebra::Point3<f32>;
+pub type Point = nalgebra::Point3<Float>;
 
 /// The Vector to describe the [`Ray`]'s direction.
 ///
 /// [`Ray`]: ray/struct.Ray.html
-pub type Vector = nalgebra::Vector3<f32>;
+pub type Vector = nalgebra::Vector3<Float>;
 
 /// The module relating to Axis-Aligned Bouding Boxes.
 pub mod aabb;
@@ -1,5 +1,5 @@
 use crate::aabb::AABB;
-use crate::{Point, Vector};
+use crate::{Float, Point, Vector};
 use nalgebra::Unit;
 use std::fmt::{Display, Formatter, Result};
 
@@ -15,6 +15,12 @@ pub struct Ray {
     pub direction: Unit<Vector>,
     /// The inverse of each coefficient of the ray's direction.
     pub inv_direction: Vector,
+    /// The instant at which the ray is cast, for scenes whose content moves over time.
+    pub time: Float,
+    /// The width of the ray's footprint at its origin, when it stands for a cone of rays.
+    pub footprint: Float,
+    /// How much the ray's footprint widens per unit of distance travelled.
+    pub spread: Float,
 }
 
 impl Ray {
@@ -36,9 +42,82 @@ impl Ray {
             origin,
             direction,
             inv_direction,
+            time: 0.,
+            footprint: 0.,
+            spread: 0.,
         }
     }
 
+    /// Set the instant at which the [`Ray`] is cast, which is `0.` by default.
+    ///
+    /// [`Ray`]: struct.Ray.html
+    ///
+    /// # Examples
+    /// ```
+    /// use beevee::{Point, Vector};
+    /// use beevee::ray::Ray;
+    ///
+    /// let ray = Ray::new(Point::origin(), Vector::x_axis()).with_time(0.5);
+    /// assert_eq!(ray.time, 0.5);
+    /// ```
+    #[must_use]
+    pub fn with_time(mut self, time: Float) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Make the [`Ray`] stand for a cone of rays, e.g: all those going through a pixel, whose
+    /// footprint is `footprint` wide at its origin and widens by `spread` per unit of distance.
+    /// A [`Ray`] has no footprint by default.
+    ///
+    /// [`Ray`]: struct.Ray.html
+    ///
+    /// # Examples
+    /// ```
+    /// use beevee::{Point, Vector};
+    /// use beevee::ray::Ray;
+    ///
+    /// let ray = Ray::new(Point::origin(), Vector::x_axis()).with_footprint(0.5, 0.25);
+    /// assert_eq!(ray.footprint_at(2.), 1.);
+    /// ```
+    #[must_use]
+    pub fn with_footprint(mut self, footprint: Float, spread: Float) -> Self {
+        self.footprint = footprint;
+        self.spread = spread;
+        self
+    }
+
+    /// Return the width of the [`Ray`]'s footprint after travelling `distance`.
+    ///
+    /// [`Ray`]: struct.Ray.html
+    pub fn footprint_at(&self, distance: Float) -> Float {
+        self.footprint + self.spread * distance
+    }
+
+    /// Create the [`Ray`] leaving from a surface this one reached after travelling `distance`,
+    /// e.g: when it is reflected, keeping its time and the footprint it had at the surface.
+    ///
+    /// [`Ray`]: struct.Ray.html
+    ///
+    /// # Examples
+    /// ```
+    /// use beevee::{Point, Vector};
+    /// use beevee::ray::Ray;
+    ///
+    /// let ray = Ray::new(Point::origin(), Vector::x_axis())
+    ///     .with_time(0.5)
+    ///     .with_footprint(0., 0.25);
+    /// let reflected = ray.bounced(Point::new(2., 0., 0.), -Vector::x_axis(), 2.);
+    /// assert_eq!(reflected.time, 0.5);
+    /// assert_eq!(reflected.footprint_at(1.), 0.75);
+    /// ```
+    #[must_use]
+    pub fn bounced(&self, origin: Point, direction: Unit<Vector>, distance: Float) -> Self {
+        Ray::new(origin, direction)
+            .with_time(self.time)
+            .with_footprint(self.footprint_at(distance), self.spread)
+    }
+
     /// Return the distance to intersect with an [`AABB`], or [`None`] if there's no intersection.
     ///
     /// [`AABB`]: ../aabb/struct.AABB.html
@@ -78,7 +157,7 @@ impl Ray {
     ///
     /// assert_eq!(ray.aabb_intersection(&aabb), None);
     /// ```
-    pub fn aabb_intersection(&self, aabb: &AABB) -> Option<f32> {
+    pub fn aabb_intersection(&self, aabb: &AABB) -> Option<Float> {
         use crate::Axis;
         let min_max = |axis: Axis| {
             let a = (aabb.high[axis] - self.origin[axis]) * self.inv_direction[axis];
@@ -150,3 +229,170 @@ impl Display for Ray {
         )
     }
 }
+
+/// Make up for the rounding errors of the slab test, so that no object is missed by a ray grazing
+/// the bounds of its node.
+pub(crate) const FAR_PADDING: Float = 1. + 4. * Float::EPSILON;
+
+/// A [`RayPacket`] of 4 rays, tested with a single SSE instruction per operation.
+///
+/// [`RayPacket`]: struct.RayPacket.html
+pub type RayPacket4 = RayPacket<4>;
+
+/// A [`RayPacket`] of 8 rays.
+///
+/// [`RayPacket`]: struct.RayPacket.html
+pub type RayPacket8 = RayPacket<8>;
+
+/// A group of `N` [`Ray`]s intersected together, e.g: the rays going through neighbouring
+/// pixels, which tend to visit the same nodes of a [`BVH`].
+///
+/// Their coordinates are stored axis by axis, so that a bounding box is tested against all of
+/// them at once, using SIMD instructions when available.
+///
+/// [`BVH`]: ../bvh/struct.BVH.html
+/// [`Ray`]: struct.Ray.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct RayPacket<const N: usize> {
+    origin: [[Float; N]; 3],
+    inv_direction: [[Float; N]; 3],
+}
+
+impl<const N: usize> RayPacket<N> {
+    /// Gather the given rays into a [`RayPacket`].
+    ///
+    /// [`RayPacket`]: struct.RayPacket.html
+    pub fn new(rays: &[Ray; N]) -> Self {
+        // Avoid NaNs when an axis-aligned ray starts on the plane of a bounding box's face
+        let clamp = |inv: Float| inv.clamp(Float::MIN, Float::MAX);
+        let mut packet = RayPacket {
+            origin: [[0.; N]; 3],
+            inv_direction: [[0.; N]; 3],
+        };
+        for (lane, ray) in rays.iter().enumerate() {
+            for axis in 0..3 {
+                packet.origin[axis][lane] = ray.origin[axis];
+                packet.inv_direction[axis][lane] = clamp(ray.inv_direction[axis]);
+            }
+        }
+        packet
+    }
+
+    /// Return the distance at which each ray enters an [`AABB`], or infinity if it misses it. A
+    /// ray starting inside of the [`AABB`] enters it at distance 0.
+    ///
+    /// [`AABB`]: ../aabb/struct.AABB.html
+    ///
+    /// # Examples
+    /// ```
+    /// use beevee::{Float, Point, Vector};
+    /// use beevee::aabb::AABB;
+    /// use beevee::ray::{Ray, RayPacket4};
+    ///
+    /// let aabb = AABB::with_bounds(Point::new(1., -1., -1.), Point::new(3., 1., 1.));
+    /// let packet = RayPacket4::new(&[
+    ///     Ray::new(Point::origin(), Vector::x_axis()),
+    ///     Ray::new(Point::origin(), Vector::y_axis()),
+    ///     Ray::new(Point::new(2., 0., 0.), Vector::z_axis()),
+    ///     Ray::new(Point::new(4., 0., 0.), -Vector::x_axis()),
+    /// ]);
+    ///
+    /// assert_eq!(packet.aabb_intersections(&aabb), [1., Float::INFINITY, 0., 1.]);
+    /// ```
+    pub fn aabb_intersections(&self, aabb: &AABB) -> [Float; N] {
+        let mut near = [0.; N];
+        let mut far = [Float::INFINITY; N];
+        for axis in 0..3 {
+            slab(
+                Lanes::Splat(aabb.low[axis]),
+                Lanes::Splat(aabb.high[axis]),
+                Lanes::Each(&self.origin[axis]),
+                Lanes::Each(&self.inv_direction[axis]),
+                (&mut near, &mut far),
+            );
+        }
+        let mut distances = [Float::INFINITY; N];
+        for lane in 0..N {
+            if near[lane] <= far[lane] * FAR_PADDING {
+                distances[lane] = near[lane];
+            }
+        }
+        distances
+    }
+}
+
+/// One of the operands of [`slab`], either shared by every lane or given for each of them.
+///
+/// [`slab`]: fn.slab.html
+#[derive(Clone, Copy)]
+pub(crate) enum Lanes<'a, const N: usize> {
+    /// The same value for every lane.
+    Splat(Float),
+    /// One value per lane.
+    Each(&'a [Float; N]),
+}
+
+impl<const N: usize> Lanes<'_, N> {
+    fn get(self, lane: usize) -> Float {
+        match self {
+            Lanes::Splat(value) => value,
+            Lanes::Each(values) => values[lane],
+        }
+    }
+
+    /// Load the values of the 4 lanes starting at `lane`.
+    ///
+    /// # Safety
+    ///
+    /// `lane + 4` must be at most `N`.
+    #[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+    unsafe fn load(self, lane: usize) -> std::arch::x86_64::__m128 {
+        use std::arch::x86_64::*;
+        match self {
+            Lanes::Splat(value) => _mm_set1_ps(value),
+            Lanes::Each(values) => _mm_loadu_ps(values.as_ptr().add(lane)),
+        }
+    }
+}
+
+/// Narrow the `[near, far]` ranges of distances at which each lane's ray is inside its bounding
+/// box, to those where it is between its `low` and `high` planes along one axis, e.g: for a packet
+/// of rays against one box, or for one ray against the children of a wide BVH node.
+pub(crate) fn slab<const N: usize>(
+    low: Lanes<N>,
+    high: Lanes<N>,
+    origin: Lanes<N>,
+    inv_direction: Lanes<N>,
+    (near, far): (&mut [Float; N], &mut [Float; N]),
+) {
+    #[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+    let simd_lanes = N - N % 4;
+    // SAFETY: SSE is always available on x86_64, and the unaligned loads and stores only access
+    // the first `simd_lanes` elements of each array.
+    #[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+    unsafe {
+        use std::arch::x86_64::*;
+        for lane in (0..simd_lanes).step_by(4) {
+            let origin = origin.load(lane);
+            let inv_direction = inv_direction.load(lane);
+            let t_low = _mm_mul_ps(_mm_sub_ps(low.load(lane), origin), inv_direction);
+            let t_high = _mm_mul_ps(_mm_sub_ps(high.load(lane), origin), inv_direction);
+            let near_ptr = near.as_mut_ptr().add(lane);
+            let far_ptr = far.as_mut_ptr().add(lane);
+            let t_near = _mm_max_ps(_mm_loadu_ps(near_ptr), _mm_min_ps(t_low, t_high));
+            let t_far = _mm_min_ps(_mm_loadu_ps(far_ptr), _mm_max_ps(t_low, t_high));
+            _mm_storeu_ps(near_ptr, t_near);
+            _mm_storeu_ps(far_ptr, t_far);
+        }
+    }
+    #[cfg(not(all(target_arch = "x86_64", not(feature = "f64"))))]
+    let simd_lanes = 0;
+    for lane in simd_lanes..N {
+        let origin = origin.get(lane);
+        let inv_direction = inv_direction.get(lane);
+        let t_low = (low.get(lane) - origin) * inv_direction;
+        let t_high = (high.get(lane) - origin) * inv_direction;
+        near[lane] = near[lane].max(t_low.min(t_high));
+        far[lane] = far[lane].min(t_low.max(t_high));
+    }
+}
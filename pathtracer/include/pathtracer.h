@@ -0,0 +1,62 @@
+/*
+ * The C interface of the pathtracer, built with the `ffi` feature.
+ *
+ * Scenes and images are opaque pointers, which must be freed with `pt_free_scene` and
+ * `pt_free_image`. Functions which fail return a null pointer, the reason being available from
+ * `pt_last_error`.
+ *
+ * The shared library is built with:
+ *
+ *     cargo rustc --package pathtracer --lib --release --features ffi --crate-type cdylib
+ */
+
+#ifndef PATHTRACER_H
+#define PATHTRACER_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A scene loaded from its description. */
+typedef struct PtScene PtScene;
+
+/* A rendered image, made of 8-bit RGB pixels, row by row. */
+typedef struct PtImage PtImage;
+
+/*
+ * Called with the number of pixels rendered so far, the total number of pixels to render, and
+ * the user data given to `pt_render`, from the rendering threads, possibly several at once.
+ */
+typedef void (*PtProgress)(uint64_t done, uint64_t total, void *user_data);
+
+/* Get the reason of the last failure on the current thread, or NULL if none failed yet. */
+const char *pt_last_error(void);
+
+/* Load the scene described at `path`, or in the bundle found at `path`. */
+PtScene *pt_load_scene(const char *path);
+
+/* Free a scene returned by `pt_load_scene`, doing nothing for NULL. */
+void pt_free_scene(PtScene *scene);
+
+/*
+ * Render the scene, drawing every random sample from the given seed, and reporting its progress
+ * to `progress` if it is not NULL. The same scene may be rendered from several threads at once.
+ */
+PtImage *pt_render(const PtScene *scene, uint64_t seed, PtProgress progress, void *user_data);
+
+/*
+ * Get the pixels of an image, 3 bytes per pixel row by row from the top, writing its dimensions
+ * into `width` and `height` if they are not NULL. The pixels stay valid until the image is freed.
+ */
+const uint8_t *pt_get_pixels(const PtImage *image, uint32_t *width, uint32_t *height);
+
+/* Free an image returned by `pt_render`, doing nothing for NULL. */
+void pt_free_image(PtImage *image);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PATHTRACER_H */
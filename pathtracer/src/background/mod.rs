@@ -0,0 +1,47 @@
+//! Various background implementations
+
+use super::core::LinearColor;
+use super::Vector;
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// All the existing `Background` implementation.
+///
+/// Backgrounds are told apart by their fields, to allow using a plain color as a background.
+#[serde(untagged)]
+#[allow(missing_docs)]
+#[enum_dispatch::enum_dispatch]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub enum BackgroundEnum {
+    UniformBackground,
+    SkyBackground,
+    PhysicalSkyBackground,
+}
+
+/// Represent what is seen by rays which do not hit any object in the scene.
+#[enum_dispatch::enum_dispatch(BackgroundEnum)]
+pub trait Background: std::fmt::Debug {
+    /// Get the color seen when looking in the given direction
+    fn color(&self, direction: &Unit<Vector>) -> LinearColor;
+}
+
+impl Default for BackgroundEnum {
+    fn default() -> Self {
+        UniformBackground::new(LinearColor::black()).into()
+    }
+}
+
+impl From<LinearColor> for BackgroundEnum {
+    fn from(color: LinearColor) -> Self {
+        UniformBackground::new(color).into()
+    }
+}
+
+mod physical_sky;
+pub use physical_sky::*;
+
+mod sky;
+pub use sky::*;
+
+mod uniform;
+pub use uniform::*;
@@ -0,0 +1,325 @@
+use super::Background;
+use crate::consts::FRAC_PI_2;
+use crate::core::LinearColor;
+use crate::{Float, Vector};
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// The apparent diameter of the sun's disk, in degrees.
+const SUN_SIZE: Float = 0.53;
+/// The radiance of the sun's disk outside of the atmosphere.
+const SUN_RADIANCE: Float = 2e5;
+/// The radiance corresponding to a luminance of 1 kcd/m², as given by the sky model.
+const LUMINANCE_SCALE: Float = 0.1;
+/// The wavelengths of the red, green, and blue channels, in micrometers.
+const WAVELENGTHS: [Float; 3] = [0.65, 0.55, 0.45];
+
+/// A physically based sky, lit by the sun, using the analytic model of Preetham et al.
+///
+/// The sky's color depends on the sun's elevation, and on the atmosphere's turbidity: 2 for a
+/// very clear sky, up to 10 for a hazy one. The sun's disk itself is attenuated by the atmosphere,
+/// turning red as it sets.
+///
+/// A luminance of 1 corresponds to 10,000 cd/m², a clear sky being around that bright at its
+/// zenith, and the sun a hundred thousand times brighter. Unless its number of `samples` is zero,
+/// the sky is also sampled as a light by the scene, in proportion to its brightness.
+///
+/// The sky's up direction is the Y-axis, unless the scene uses another up axis.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PhysicalSkyBackground {
+    /// The direction pointing towards the sun.
+    #[serde(deserialize_with = "crate::serialize::vector_normalizer")]
+    sun_direction: Unit<Vector>,
+    /// The haziness of the atmosphere.
+    #[serde(default = "default_turbidity")]
+    turbidity: Float,
+    /// The factor multiplying the radiance of the sky and the sun.
+    #[serde(default = "crate::serialize::default_identity")]
+    intensity: Float,
+    /// The color seen below the horizon.
+    #[serde(default)]
+    ground: LinearColor,
+    /// The number of shadow rays towards the sky used at each shading point.
+    #[serde(default = "default_samples")]
+    samples: u32,
+    #[serde(skip, default = "Vector::y_axis")]
+    up: Unit<Vector>,
+}
+
+fn default_turbidity() -> Float {
+    3.
+}
+
+fn default_samples() -> u32 {
+    4
+}
+
+/// The coefficients of the Perez distribution of a quantity over the sky.
+struct Perez([Float; 5]);
+
+impl Perez {
+    /// Get the coefficients from their linear dependency on the turbidity.
+    fn new(turbidity: Float, coefficients: [(Float, Float); 5]) -> Self {
+        let mut ans = [0.; 5];
+        for (value, (slope, offset)) in ans.iter_mut().zip(coefficients.iter()) {
+            *value = slope * turbidity + offset;
+        }
+        Perez(ans)
+    }
+
+    /// The distribution's value at an angle `theta` from the zenith, and `gamma` from the sun.
+    fn at(&self, cos_theta: Float, gamma: Float) -> Float {
+        let [a, b, c, d, e] = self.0;
+        let cos_gamma = gamma.cos();
+        (1. + a * (b / cos_theta.max(1e-3)).exp())
+            * (1. + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+    }
+}
+
+impl PhysicalSkyBackground {
+    /// Creates a new `PhysicalSkyBackground`, with the given turbidity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::background::{Background, PhysicalSkyBackground};
+    /// # use pathtracer::Vector;
+    /// # use nalgebra::Unit;
+    /// #
+    /// let sky = PhysicalSkyBackground::new(Unit::new_normalize(Vector::new(1.0, 1.0, 0.0)), 3.0);
+    /// // The clear sky is blue
+    /// let zenith = sky.color(&Vector::y_axis());
+    /// assert!(zenith.b > zenith.r);
+    /// ```
+    pub fn new(sun_direction: Unit<Vector>, turbidity: Float) -> Self {
+        PhysicalSkyBackground {
+            sun_direction,
+            turbidity,
+            intensity: 1.,
+            ground: LinearColor::black(),
+            samples: default_samples(),
+            up: Vector::y_axis(),
+        }
+    }
+
+    /// Multiply the radiance of the sky and the sun by a factor.
+    pub fn with_intensity(self, intensity: Float) -> Self {
+        PhysicalSkyBackground { intensity, ..self }
+    }
+
+    /// Use another color below the horizon.
+    pub fn with_ground(self, ground: LinearColor) -> Self {
+        PhysicalSkyBackground { ground, ..self }
+    }
+
+    /// Use the given number of shadow rays towards the sky at each shading point, or only use the
+    /// sky as a background if it is zero.
+    pub fn with_samples(self, samples: u32) -> Self {
+        PhysicalSkyBackground { samples, ..self }
+    }
+
+    /// Use another direction as the sky's up direction.
+    pub fn with_up(self, up: Unit<Vector>) -> Self {
+        PhysicalSkyBackground { up, ..self }
+    }
+
+    /// Get the sky's up direction.
+    pub fn up(&self) -> &Unit<Vector> {
+        &self.up
+    }
+
+    /// Get the direction pointing towards the sun.
+    pub fn sun_direction(&self) -> &Unit<Vector> {
+        &self.sun_direction
+    }
+
+    /// Get the number of shadow rays towards the sky used at each shading point.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Get the cosine of the angle between the center of the sun's disk and its edge.
+    pub(crate) fn sun_cos(&self) -> Float {
+        (SUN_SIZE / 2.).to_radians().cos()
+    }
+
+    /// Get the radiance of the sun's disk, attenuated by the atmosphere, or black once the sun
+    /// has set.
+    pub(crate) fn sun_radiance(&self) -> LinearColor {
+        let elevation = self.sun_direction.dot(&self.up);
+        if elevation <= 0. {
+            return LinearColor::black();
+        }
+        // The relative length of the path through the atmosphere, as given by Kasten and Young
+        let zenith = elevation.min(1.).acos().to_degrees();
+        let air_mass = 1. / (elevation + 0.50572 * (96.07995 - zenith).powf(-1.6364));
+        // Rayleigh scattering by the air, and Mie scattering by the aerosols
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let [r, g, b] = WAVELENGTHS.map(|lambda| {
+            let depth = 0.008735 * lambda.powf(-4.08) + beta * lambda.powf(-1.3);
+            SUN_RADIANCE * self.intensity * (-air_mass * depth).exp()
+        });
+        LinearColor::new(r, g, b)
+    }
+
+    /// Get the radiance of the sky in the direction, without the sun's disk.
+    pub(crate) fn sky_color(&self, direction: &Unit<Vector>) -> LinearColor {
+        let cos_theta = direction.dot(&self.up);
+        if cos_theta < 0. {
+            return self.ground.clone();
+        }
+        let turbidity = self.turbidity;
+        // The sky is modeled with the sun at most on the horizon
+        // Dot products of unit vectors are never NaN, clamping only undoes rounding errors
+        let theta_sun = self.sun_direction.dot(&self.up).clamp(0., 1.).acos();
+        let gamma = direction.dot(&self.sun_direction).clamp(-1., 1.).acos();
+        let (t, t2) = (turbidity, turbidity * turbidity);
+        let (s, s2, s3) = (theta_sun, theta_sun.powi(2), theta_sun.powi(3));
+
+        let chi = (4. / 9. - turbidity / 120.) * (crate::consts::PI - 2. * theta_sun);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_x = t2 * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s)
+            + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s + 0.00394)
+            + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s + 0.25886);
+        let zenith_y = t2 * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s)
+            + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s + 0.00516)
+            + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s + 0.26688);
+
+        let luminance = Perez::new(
+            turbidity,
+            [
+                (0.1787, -1.4630),
+                (-0.3554, 0.4275),
+                (-0.0227, 5.3251),
+                (0.1206, -2.5771),
+                (-0.0670, 0.3703),
+            ],
+        );
+        let x = Perez::new(
+            turbidity,
+            [
+                (-0.0193, -0.2592),
+                (-0.0665, 0.0008),
+                (-0.0004, 0.2125),
+                (-0.0641, -0.8989),
+                (-0.0033, 0.0452),
+            ],
+        );
+        let y = Perez::new(
+            turbidity,
+            [
+                (-0.0167, -0.2608),
+                (-0.0950, 0.0092),
+                (-0.0079, 0.2102),
+                (-0.0441, -1.6537),
+                (-0.0109, 0.0529),
+            ],
+        );
+        // Each quantity is relative to its value at the zenith
+        let relative =
+            |perez: &Perez| perez.at(cos_theta, gamma) / perez.at(1., theta_sun.min(FRAC_PI_2));
+        let big_y = (zenith_luminance * relative(&luminance)).max(0.) * LUMINANCE_SCALE;
+        let x = zenith_x * relative(&x);
+        let y = zenith_y * relative(&y);
+        // Convert from the xyY color space to linear sRGB
+        let (big_x, big_z) = (x / y * big_y, (1. - x - y) / y * big_y);
+        let r = 3.2406 * big_x - 1.5372 * big_y - 0.4986 * big_z;
+        let g = -0.9689 * big_x + 1.8758 * big_y + 0.0415 * big_z;
+        let b = 0.0557 * big_x - 0.2040 * big_y + 1.0570 * big_z;
+        LinearColor::new(r.max(0.), g.max(0.), b.max(0.)) * self.intensity
+    }
+
+    /// Whether the direction points towards the sun's disk.
+    pub(crate) fn is_in_sun(&self, direction: &Unit<Vector>) -> bool {
+        direction.dot(&self.sun_direction) >= self.sun_cos()
+    }
+}
+
+impl Background for PhysicalSkyBackground {
+    fn color(&self, direction: &Unit<Vector>) -> LinearColor {
+        let sky = self.sky_color(direction);
+        if direction.dot(&self.up) >= 0. && self.is_in_sun(direction) {
+            sky + self.sun_radiance()
+        } else {
+            sky
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::background::BackgroundEnum;
+
+    fn simple_sky() -> PhysicalSkyBackground {
+        PhysicalSkyBackground::new(Unit::new_normalize(Vector::new(1., 1., 0.)), 3.)
+    }
+
+    #[test]
+    fn new_works() {
+        assert_eq!(
+            simple_sky(),
+            PhysicalSkyBackground {
+                sun_direction: Unit::new_normalize(Vector::new(1., 1., 0.)),
+                turbidity: 3.,
+                intensity: 1.,
+                ground: LinearColor::black(),
+                samples: 4,
+                up: Vector::y_axis(),
+            }
+        )
+    }
+
+    #[test]
+    fn sky_is_brighter_around_the_sun() {
+        let sky = simple_sky();
+        let near_sun = Unit::new_normalize(Vector::new(1., 1.1, 0.));
+        let opposite = Unit::new_normalize(Vector::new(-1., 1.1, 0.));
+        assert!(sky.color(&near_sun).luminance() > 2. * sky.color(&opposite).luminance());
+        // Luminance is in tens of kcd/m², the zenith of a clear sky being a few of them
+        let zenith = sky.color(&Vector::y_axis()).luminance();
+        assert!(zenith > 0.1 && zenith < 2., "{}", zenith);
+    }
+
+    #[test]
+    fn sun_works() {
+        let sky = simple_sky();
+        let sun = sky.color(sky.sun_direction());
+        assert!(sun.luminance() > 1e4 * sky.color(&Vector::y_axis()).luminance());
+        // The setting sun turns red, the blue light being scattered away
+        let sunset = PhysicalSkyBackground::new(Unit::new_normalize(Vector::new(1., 0.02, 0.)), 3.);
+        let setting = sunset.sun_radiance();
+        assert!(setting.r > 2. * setting.b);
+        let set = PhysicalSkyBackground::new(Unit::new_normalize(Vector::new(1., -0.1, 0.)), 3.);
+        assert_eq!(set.sun_radiance(), LinearColor::black());
+    }
+
+    #[test]
+    fn ground_works() {
+        let sky = simple_sky().with_ground(LinearColor::new(0.5, 0.25, 0.));
+        assert_eq!(
+            sky.color(&-Vector::y_axis()),
+            LinearColor::new(0.5, 0.25, 0.)
+        )
+    }
+
+    #[test]
+    fn hazy_sky_is_whiter() {
+        let saturation = |color: LinearColor| color.b / color.r;
+        let direction = Unit::new_normalize(Vector::new(-1., 2., 1.));
+        let clear = simple_sky().color(&direction);
+        let hazy = PhysicalSkyBackground::new(Unit::new_normalize(Vector::new(1., 1., 0.)), 8.)
+            .color(&direction);
+        assert!(saturation(hazy) < saturation(clear));
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            sun_direction: [2.0, 2.0, 0.0]
+            turbidity: 3.0
+        "#;
+        let background: BackgroundEnum = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(background, simple_sky().into())
+    }
+}
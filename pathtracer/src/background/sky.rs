@@ -0,0 +1,245 @@
+use super::Background;
+use crate::core::LinearColor;
+use crate::{Float, Vector};
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// The sun's disk as seen in a [`SkyBackground`].
+///
+/// [`SkyBackground`]: struct.SkyBackground.html
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Sun {
+    /// The direction pointing towards the sun.
+    #[serde(deserialize_with = "crate::serialize::vector_normalizer")]
+    direction: Unit<Vector>,
+    /// The color of the sun's disk.
+    color: LinearColor,
+    /// The apparent diameter of the sun's disk, in degrees.
+    #[serde(default = "default_sun_size")]
+    size: Float,
+}
+
+fn default_sun_size() -> Float {
+    0.53
+}
+
+impl Sun {
+    /// Creates a new `Sun`, with its apparent diameter given in degrees.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::background::Sun;
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::Vector;
+    /// #
+    /// let sun = Sun::new(
+    ///     Vector::y_axis(),
+    ///     LinearColor::new(10.0, 10.0, 8.0),
+    ///     0.53,
+    /// );
+    /// ```
+    pub fn new(direction: Unit<Vector>, color: LinearColor, size: Float) -> Self {
+        Sun {
+            direction,
+            color,
+            size,
+        }
+    }
+
+    fn is_visible(&self, direction: &Unit<Vector>) -> bool {
+        let half_angle = (self.size / 2.).to_radians();
+        direction.dot(&self.direction) >= half_angle.cos()
+    }
+}
+
+/// A procedural sky, going from its horizon color up to its zenith color, above a uniform ground.
+///
+/// The sky's up direction is the Y-axis, unless the scene uses another up axis.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SkyBackground {
+    zenith: LinearColor,
+    horizon: LinearColor,
+    ground: LinearColor,
+    #[serde(default)]
+    sun: Option<Sun>,
+    #[serde(skip, default = "Vector::y_axis")]
+    up: Unit<Vector>,
+}
+
+impl SkyBackground {
+    /// Creates a new `SkyBackground`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::background::{SkyBackground, Sun};
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::Vector;
+    /// # use nalgebra::Unit;
+    /// #
+    /// let sky = SkyBackground::new(
+    ///     LinearColor::new(0.1, 0.3, 0.8), // zenith
+    ///     LinearColor::new(0.7, 0.8, 1.0), // horizon
+    ///     LinearColor::new(0.3, 0.25, 0.2), // ground
+    ///     Some(Sun::new(
+    ///         Unit::new_normalize(Vector::new(1.0, 1.0, 0.0)),
+    ///         LinearColor::new(10.0, 10.0, 8.0),
+    ///         0.53,
+    ///     )),
+    /// );
+    /// ```
+    pub fn new(
+        zenith: LinearColor,
+        horizon: LinearColor,
+        ground: LinearColor,
+        sun: Option<Sun>,
+    ) -> Self {
+        SkyBackground {
+            zenith,
+            horizon,
+            ground,
+            sun,
+            up: Vector::y_axis(),
+        }
+    }
+
+    /// Use another direction as the sky's up direction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::background::{Background, SkyBackground};
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::Vector;
+    /// #
+    /// let sky = SkyBackground::new(
+    ///     LinearColor::new(0.1, 0.3, 0.8), // zenith
+    ///     LinearColor::new(0.7, 0.8, 1.0), // horizon
+    ///     LinearColor::new(0.3, 0.25, 0.2), // ground
+    ///     None,
+    /// )
+    /// .with_up(Vector::z_axis());
+    /// assert_eq!(sky.color(&Vector::z_axis()), LinearColor::new(0.1, 0.3, 0.8));
+    /// ```
+    pub fn with_up(self, up: Unit<Vector>) -> Self {
+        SkyBackground { up, ..self }
+    }
+
+    /// Get the sky's up direction.
+    pub fn up(&self) -> &Unit<Vector> {
+        &self.up
+    }
+}
+
+impl Background for SkyBackground {
+    fn color(&self, direction: &Unit<Vector>) -> LinearColor {
+        let elevation = direction.dot(&self.up);
+        if elevation < 0. {
+            return self.ground.clone();
+        }
+        // Interpolate on the elevation angle, for a smooth gradient up to the zenith
+        let t = elevation.min(1.).asin() / crate::consts::FRAC_PI_2;
+        let sky = self.horizon.clone() * (1. - t) + self.zenith.clone() * t;
+        match &self.sun {
+            Some(sun) if sun.is_visible(direction) => sky + sun.color.clone(),
+            _ => sky,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::background::BackgroundEnum;
+
+    fn simple_sky() -> SkyBackground {
+        SkyBackground::new(
+            LinearColor::new(0., 0., 1.),
+            LinearColor::new(1., 1., 1.),
+            LinearColor::new(0.5, 0.25, 0.),
+            Some(Sun::new(
+                Vector::z_axis(),
+                LinearColor::new(10., 10., 10.),
+                1.,
+            )),
+        )
+    }
+
+    #[test]
+    fn zenith_works() {
+        let sky = simple_sky();
+        assert_eq!(sky.color(&Vector::y_axis()), LinearColor::new(0., 0., 1.))
+    }
+
+    #[test]
+    fn horizon_works() {
+        let sky = simple_sky();
+        assert_eq!(sky.color(&Vector::x_axis()), LinearColor::new(1., 1., 1.))
+    }
+
+    #[test]
+    fn gradient_works() {
+        let sky = simple_sky();
+        let direction = Unit::new_normalize(Vector::new(1., Float::sqrt(3.), 0.));
+        // 60° of elevation is two thirds of the way up
+        let color = sky.color(&direction);
+        assert!((color.r - 1. / 3.).abs() < 1e-5);
+        assert!((color.b - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ground_works() {
+        let sky = simple_sky();
+        assert_eq!(
+            sky.color(&-Vector::y_axis()),
+            LinearColor::new(0.5, 0.25, 0.)
+        )
+    }
+
+    #[test]
+    fn sun_works() {
+        let sky = simple_sky();
+        assert_eq!(
+            sky.color(&Vector::z_axis()),
+            LinearColor::new(11., 11., 11.)
+        );
+        let next_to_sun = Unit::new_normalize(Vector::new(0.1, 0., 1.));
+        assert_eq!(sky.color(&next_to_sun), LinearColor::new(1., 1., 1.));
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            zenith: {r: 0.0, g: 0.0, b: 1.0}
+            horizon: {r: 1.0, g: 1.0, b: 1.0}
+            ground: {r: 0.5, g: 0.25, b: 0.0}
+            sun:
+              direction: [0.0, 0.0, 2.0]
+              color: {r: 10.0, g: 10.0, b: 10.0}
+              size: 1.0
+        "#;
+        let sky: SkyBackground = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(sky, simple_sky())
+    }
+
+    #[test]
+    fn background_deserialization_works() {
+        let yaml = r#"
+            zenith: {r: 0.0, g: 0.0, b: 1.0}
+            horizon: {r: 1.0, g: 1.0, b: 1.0}
+            ground: {r: 0.5, g: 0.25, b: 0.0}
+        "#;
+        let background: BackgroundEnum = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            background,
+            SkyBackground::new(
+                LinearColor::new(0., 0., 1.),
+                LinearColor::new(1., 1., 1.),
+                LinearColor::new(0.5, 0.25, 0.),
+                None,
+            )
+            .into()
+        )
+    }
+}
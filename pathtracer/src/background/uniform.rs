@@ -0,0 +1,65 @@
+use super::Background;
+use crate::core::LinearColor;
+use crate::Vector;
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// A background with the same color in all directions.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct UniformBackground {
+    color: LinearColor,
+}
+
+impl UniformBackground {
+    /// Creates a new `UniformBackground`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::background::UniformBackground;
+    /// # use pathtracer::core::LinearColor;
+    /// #
+    /// let uni_back = UniformBackground::new(LinearColor::new(0.5, 0.5, 0.5));
+    /// ```
+    pub fn new(color: LinearColor) -> Self {
+        UniformBackground { color }
+    }
+}
+
+impl Background for UniformBackground {
+    fn color(&self, _: &Unit<Vector>) -> LinearColor {
+        self.color.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn new_works() {
+        let color = LinearColor::new(0.2, 0.4, 0.6);
+        let background = UniformBackground::new(color.clone());
+        assert_eq!(background, UniformBackground { color })
+    }
+
+    #[test]
+    fn color_works() {
+        let background = UniformBackground::new(LinearColor::new(0.25, 0.5, 1.));
+        assert_eq!(
+            background.color(&Vector::x_axis()),
+            LinearColor::new(0.25, 0.5, 1.)
+        )
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = "{r: 0.25, g: 0.5, b: 1.0}";
+        let background: UniformBackground = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            background,
+            UniformBackground::new(LinearColor::new(0.25, 0.5, 1.))
+        )
+    }
+}
@@ -1,8 +1,9 @@
 //! Camera related logic
 
 use super::film::Film;
-use crate::{Point, Vector};
-use serde::{Deserialize, Deserializer};
+use super::motion::Motion;
+use crate::{Float, Point, Vector};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// Represent an abstract camera to observe the scene.
 #[derive(Debug, PartialEq)]
@@ -11,6 +12,10 @@ pub struct Camera {
     origin: Point,
     /// The film to represent each pixel in the scene.
     film: Film,
+    /// The instants at which the shutter opens and closes.
+    shutter: [Float; 2],
+    /// The camera's movement over time, if it is not still.
+    motion: Option<Motion>,
 }
 
 impl Camera {
@@ -20,13 +25,14 @@ impl Camera {
     ///
     /// ```
     /// # use pathtracer::core::Camera;
+    /// # use pathtracer::Float;
     /// use pathtracer::{Point, Vector};
     ///
     /// let cam = Camera::new(
     ///     Point::new(-1., 0., 0.),
     ///     Vector::new(1., 0., 0.),
     ///     Vector::new(0., 1., 0.),
-    ///     2. * f32::atan(1.), /* 90° in radian */
+    ///     2. * Float::atan(1.), /* 90° in radian */
     ///     1.,
     ///     1080,
     ///     1080,
@@ -36,16 +42,72 @@ impl Camera {
         origin: Point,
         forward: Vector,
         up: Vector,
-        fov: f32,
-        dist_to_image: f32,
+        fov: Float,
+        dist_to_image: Float,
         x: u32,
         y: u32,
     ) -> Self {
         let right = forward.cross(&up);
         let center = origin + forward.normalize() * dist_to_image;
-        let screen_size = 2. * f32::tan(fov / 2.) * dist_to_image;
+        let screen_size = 2. * Float::tan(fov / 2.) * dist_to_image;
         let film = Film::new(x, y, screen_size, center, up, right);
-        Camera { origin, film }
+        Camera {
+            origin,
+            film,
+            shutter: [0., 0.],
+            motion: None,
+        }
+    }
+
+    /// Creates a new `Camera` at `origin`, looking towards `target`.
+    ///
+    /// The `up` vector does not need to be orthogonal to the viewing direction, only to point
+    /// roughly upwards: the film is kept level with it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Camera;
+    /// # use pathtracer::Float;
+    /// use pathtracer::{Point, Vector};
+    ///
+    /// let cam = Camera::look_at(
+    ///     Point::new(-1., 1., 0.),
+    ///     Point::origin(),
+    ///     Vector::new(0., 1., 0.),
+    ///     2. * Float::atan(1.), /* 90° in radian */
+    ///     1.,
+    ///     1080,
+    ///     1080,
+    /// );
+    /// let (x, y) = cam.film().pixel_ratio(540., 540.);
+    /// let center = cam.film().pixel_at_ratio(x, y);
+    /// // The film's center is in the viewing direction
+    /// let direction = (center - cam.origin()).normalize();
+    /// assert!((direction - Vector::new(1., -1., 0.).normalize()).norm() < 1e-5);
+    /// ```
+    pub fn look_at(
+        origin: Point,
+        target: Point,
+        up: Vector,
+        fov: Float,
+        dist_to_image: Float,
+        x: u32,
+        y: u32,
+    ) -> Self {
+        let forward = (target - origin).normalize();
+        let right = forward.cross(&up);
+        // Looking straight along `up` leaves the film's orientation unconstrained, pick any
+        let right = if right.norm() > 1e-6 {
+            right
+        } else {
+            forward
+                .cross(&Vector::x())
+                .try_normalize(1e-6)
+                .unwrap_or_else(Vector::z)
+        };
+        let up = right.cross(&forward);
+        Camera::new(origin, forward, up, fov, dist_to_image, x, y)
     }
 
     /// Get the `Camera`'s [`Film`].
@@ -64,6 +126,102 @@ impl Camera {
         &self.film
     }
 
+    /// Mirror the `Camera`'s image horizontally, to look at a scene using a left-handed
+    /// coordinate system.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Camera;
+    /// #
+    /// let cam = Camera::default().mirrored();
+    /// ```
+    #[must_use]
+    pub fn mirrored(self) -> Self {
+        Camera {
+            film: self.film.mirrored(),
+            ..self
+        }
+    }
+
+    /// Keep the shutter open between two instants, blurring the objects moving meanwhile.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Camera;
+    /// #
+    /// let cam = Camera::default().with_shutter(0.0, 0.5);
+    /// assert_eq!(cam.shutter(), [0.0, 0.5]);
+    /// ```
+    pub fn with_shutter(mut self, open: Float, close: Float) -> Self {
+        self.shutter = [open, close];
+        self
+    }
+
+    /// Get the instants at which the `Camera`'s shutter opens and closes.
+    pub fn shutter(&self) -> [Float; 2] {
+        self.shutter
+    }
+
+    /// Move the camera over time, e.g: to animate it over the frames of the scene. The camera
+    /// rays are cast from where the camera is at their instant.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, Keyframe, Motion};
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let dolly = Motion::new(
+    ///     Point::origin(),
+    ///     vec![
+    ///         Keyframe::new(1.0, Vector::zeros()),
+    ///         Keyframe::new(24.0, Vector::new(0.0, 0.0, 5.0)),
+    ///     ],
+    /// );
+    /// let cam = Camera::default().with_motion(dolly.clone());
+    /// assert_eq!(cam.motion(), Some(&dolly));
+    /// ```
+    pub fn with_motion(mut self, motion: Motion) -> Self {
+        self.motion = Some(motion);
+        self
+    }
+
+    /// Get the `Camera`'s movement over time, if it is not still.
+    pub fn motion(&self) -> Option<&Motion> {
+        self.motion.as_ref()
+    }
+
+    /// Whether the `Camera` is [`mirrored`], its film's right side pointing to the left of its
+    /// viewing direction.
+    ///
+    /// [`mirrored`]: #method.mirrored
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Camera;
+    /// #
+    /// assert!(!Camera::default().is_mirrored());
+    /// assert!(Camera::default().mirrored().is_mirrored());
+    /// ```
+    pub fn is_mirrored(&self) -> bool {
+        let (forward, up, right) = self.basis();
+        forward.cross(&up).dot(&right) < 0.
+    }
+
+    /// Get the vectors from the `Camera`'s origin to the center of its film, and from that center
+    /// to the middle of the film's top and right edges.
+    fn basis(&self) -> (Vector, Vector, Vector) {
+        let center = self.film.pixel_at_ratio(0.5, 0.5);
+        (
+            center - self.origin,
+            self.film.pixel_at_ratio(0.5, 0.) - center,
+            self.film.pixel_at_ratio(1., 0.5) - center,
+        )
+    }
+
     /// Get the `Camera`'s `Point` of origin.
     ///
     /// # Examples
@@ -78,6 +236,48 @@ impl Camera {
     pub fn origin(&self) -> &Point {
         &self.origin
     }
+
+    /// Get the distance from the `Camera` to a point along its viewing direction, which is
+    /// negative for points behind it.
+    pub fn depth(&self, point: &Point) -> Float {
+        let center = self.film.pixel_at_ratio(0.5, 0.5);
+        (point - self.origin).dot(&(center - self.origin).normalize())
+    }
+
+    /// Get the screen coordinates at which a point is seen by the `Camera`, or `None` if it is
+    /// behind it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Camera;
+    /// # use pathtracer::Float;
+    /// use pathtracer::{Point, Vector};
+    ///
+    /// let cam = Camera::new(
+    ///     Point::origin(),
+    ///     Vector::new(0., 0., 1.),
+    ///     Vector::new(0., 1., 0.),
+    ///     2. * Float::atan(1.), /* 90° in radian */
+    ///     1.,
+    ///     100,
+    ///     100,
+    /// );
+    /// let (x, y) = cam.project(&Point::new(0., 5., 10.)).unwrap();
+    /// assert!((x - 50.).abs() < 1e-3 && (y - 25.).abs() < 1e-3);
+    /// assert!(cam.project(&Point::new(0., 0., -1.)).is_none());
+    /// ```
+    pub fn project(&self, point: &Point) -> Option<(Float, Float)> {
+        let depth = self.depth(point);
+        if depth <= 0. {
+            return None;
+        }
+        // Scale the point down onto the film's plane
+        let center = self.film.pixel_at_ratio(0.5, 0.5);
+        let distance = (center - self.origin).norm();
+        let on_film = self.origin + (point - self.origin) * (distance / depth);
+        Some(self.film.coord_of(&on_film))
+    }
 }
 
 impl Default for Camera {
@@ -87,6 +287,7 @@ impl Default for Camera {
     ///
     /// ```
     /// # use pathtracer::core::Camera;
+    /// # use pathtracer::Float;
     /// use pathtracer::{Point, Vector};
     ///
     /// let default = Camera::default();
@@ -94,7 +295,7 @@ impl Default for Camera {
     ///     Point::new(0., 0., 0.),
     ///     Vector::new(1., 0., 0.),
     ///     Vector::new(0., 1., 0.),
-    ///     2. * f32::atan(1.), /* 90° in radian */
+    ///     2. * Float::atan(1.), /* 90° in radian */
     ///     1.,
     ///     1080,
     ///     1080,
@@ -107,7 +308,7 @@ impl Default for Camera {
             Point::origin(),
             Vector::new(1., 0., 0.),
             Vector::new(0., 1., 0.),
-            2. * f32::atan(1.), /* 90° in radian */
+            2. * Float::atan(1.), /* 90° in radian */
             1.,
             1080,
             1080,
@@ -115,28 +316,87 @@ impl Default for Camera {
     }
 }
 
-#[derive(Debug, Deserialize)]
+/// The ways to describe where a camera is looking in a scene file.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum SerializedOrientation {
+    /// The film's basis given explicitly.
+    Explicit { forward: Vector, up: Vector },
+    /// A point being looked at, with the film kept level with `up`, pointing along Y by default.
+    LookAt {
+        look_at: Point,
+        #[serde(default = "Vector::y")]
+        up: Vector,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 struct SerializedCamera {
     origin: Point,
-    forward: Vector,
-    up: Vector,
-    fov: f32,
-    distance_to_image: f32,
+    #[serde(flatten)]
+    orientation: SerializedOrientation,
+    fov: Float,
+    #[serde(default = "crate::serialize::default_identity")]
+    distance_to_image: Float,
     x: u32,
     y: u32,
+    #[serde(default)]
+    shutter: [Float; 2],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    motion: Option<Motion>,
 }
 
 impl From<SerializedCamera> for Camera {
     fn from(cam: SerializedCamera) -> Self {
-        Camera::new(
-            cam.origin,
-            cam.forward,
-            cam.up,
-            std::f32::consts::PI * cam.fov / 180.,
-            cam.distance_to_image,
-            cam.x,
-            cam.y,
-        )
+        let fov = crate::consts::PI * cam.fov / 180.;
+        let [open, close] = cam.shutter;
+        let camera = match cam.orientation {
+            SerializedOrientation::Explicit { forward, up } => Camera::new(
+                cam.origin,
+                forward,
+                up,
+                fov,
+                cam.distance_to_image,
+                cam.x,
+                cam.y,
+            ),
+            SerializedOrientation::LookAt { look_at, up } => Camera::look_at(
+                cam.origin,
+                look_at,
+                up,
+                fov,
+                cam.distance_to_image,
+                cam.x,
+                cam.y,
+            ),
+        };
+        Camera {
+            motion: cam.motion,
+            ..camera.with_shutter(open, close)
+        }
+    }
+}
+
+impl From<&Camera> for SerializedCamera {
+    fn from(cam: &Camera) -> Self {
+        let (forward, up, right) = cam.basis();
+        let distance_to_image = forward.norm();
+        // The film's largest side spans the field of view
+        let screen_size = 2. * up.norm().max(right.norm());
+        let fov = 2. * (screen_size / 2. / distance_to_image).atan();
+        SerializedCamera {
+            origin: cam.origin,
+            orientation: SerializedOrientation::Explicit {
+                forward: forward / distance_to_image,
+                up: up.normalize(),
+            },
+            fov: fov.to_degrees(),
+            distance_to_image,
+            x: cam.film.width(),
+            y: cam.film.height(),
+            shutter: cam.shutter,
+            motion: cam.motion.clone(),
+        }
     }
 }
 
@@ -150,24 +410,70 @@ impl<'de> Deserialize<'de> for Camera {
     }
 }
 
+/// A [`mirrored`] camera is written as the camera it mirrors, the scene recording that it is
+/// left-handed.
+///
+/// [`mirrored`]: struct.Camera.html#method.mirrored
+impl Serialize for Camera {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        SerializedCamera::from(self).serialize(serializer)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    /// Compare cameras up to rounding errors, which differ between `f32` and `f64`.
+    fn assert_close(cam: &Camera, expected: &Camera) {
+        assert!((cam.origin - expected.origin).norm() < 1e-5);
+        assert_eq!(cam.shutter, expected.shutter);
+        assert_eq!(cam.motion, expected.motion);
+        assert_eq!(cam.film.width(), expected.film.width());
+        assert_eq!(cam.film.height(), expected.film.height());
+        for &(x, y) in &[(0., 0.), (1., 0.), (0., 1.)] {
+            let (lhs, rhs) = (
+                cam.film.pixel_at_ratio(x, y),
+                expected.film.pixel_at_ratio(x, y),
+            );
+            assert!((lhs - rhs).norm() < 1e-5, "{} != {}", lhs, rhs);
+        }
+    }
+
+    /// The camera at `(-1, 0, 0)` looking along the X axis used throughout the tests.
+    fn simple_camera() -> Camera {
+        Camera {
+            origin: Point::new(-1., 0., 0.),
+            film: Film::new(
+                1080,
+                1080,
+                2.,
+                Point::origin(),
+                Vector::new(0., 1., 0.),
+                Vector::new(0., 0., 1.),
+            ),
+            shutter: [0., 0.],
+            motion: None,
+        }
+    }
+
     #[test]
     fn new_works() {
         let cam = Camera::new(
             Point::new(-1., 0., 0.),
             Vector::new(1., 0., 0.),
             Vector::new(0., 1., 0.),
-            2. * f32::atan(1.), /* 90° in radian */
+            2. * Float::atan(1.), /* 90° in radian */
             1.,
             1080,
             1080,
         );
-        assert_eq!(
-            cam,
-            Camera {
+        assert_close(
+            &cam,
+            &Camera {
                 origin: Point::new(-1., 0., 0.),
                 film: Film::new(
                     1080,
@@ -176,8 +482,10 @@ mod test {
                     Point::origin(),
                     Vector::new(0., 1., 0.),
                     Vector::new(0., 0., 1.),
-                )
-            }
+                ),
+                shutter: [0., 0.],
+                motion: None,
+            },
         )
     }
 
@@ -193,9 +501,9 @@ mod test {
             y: 1080
         "#;
         let cam: Camera = serde_yaml::from_str(yaml).unwrap();
-        assert_eq!(
-            cam,
-            Camera {
+        assert_close(
+            &cam,
+            &Camera {
                 origin: Point::new(-1., 0., 0.),
                 film: Film::new(
                     1080,
@@ -204,8 +512,92 @@ mod test {
                     Point::origin(),
                     Vector::new(0., 1., 0.),
                     Vector::new(0., 0., 1.),
-                )
-            }
+                ),
+                shutter: [0., 0.],
+                motion: None,
+            },
+        )
+    }
+
+    #[test]
+    fn look_at_works() {
+        let cam = Camera::look_at(
+            Point::new(-1., 0., 0.),
+            Point::new(5., 0., 0.),
+            Vector::new(1., 1., 0.),
+            2. * Float::atan(1.), /* 90° in radian */
+            1.,
+            1080,
+            1080,
+        );
+        // The tilted up vector is made orthogonal to the viewing direction
+        assert_close(&cam, &simple_camera());
+    }
+
+    #[test]
+    fn look_at_along_up_works() {
+        let cam = Camera::look_at(
+            Point::origin(),
+            Point::new(0., -1., 0.),
+            Vector::y(),
+            2. * Float::atan(1.), /* 90° in radian */
+            1.,
+            1080,
+            1080,
+        );
+        let (x, y) = cam.film().pixel_ratio(540., 540.);
+        let center = cam.film().pixel_at_ratio(x, y);
+        assert!((center - Point::new(0., -1., 0.)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn look_at_deserialization_works() {
+        let yaml = r#"
+            origin: [-1.0, 0.0, 0.0]
+            look_at: [5.0, 0.0, 0.0]
+            fov: 90.0
+            x: 1080
+            y: 1080
+        "#;
+        let cam: Camera = serde_yaml::from_str(yaml).unwrap();
+        assert_close(&cam, &simple_camera());
+    }
+
+    #[test]
+    fn shutter_deserialization_works() {
+        let yaml = r#"
+            origin: [-1.0, 0.0, 0.0]
+            look_at: [5.0, 0.0, 0.0]
+            fov: 90.0
+            x: 1080
+            y: 1080
+            shutter: [0.0, 0.5]
+        "#;
+        let cam: Camera = serde_yaml::from_str(yaml).unwrap();
+        assert_close(&cam, &simple_camera().with_shutter(0., 0.5));
+    }
+
+    #[test]
+    fn serialization_works() {
+        let cam = Camera::look_at(
+            Point::new(-1., 2., 0.),
+            Point::new(3., 0., 1.),
+            Vector::y(),
+            1.2,
+            2.,
+            640,
+            480,
         )
+        .with_shutter(0., 0.5);
+        let yaml = serde_yaml::to_string(&cam).unwrap();
+        let deserialized: Camera = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(deserialized.shutter(), [0., 0.5]);
+        assert_eq!(deserialized.origin(), cam.origin());
+        for &(x, y) in &[(0., 0.), (320., 240.), (640., 480.)] {
+            let (x, y) = cam.film().pixel_ratio(x, y);
+            let pixel = cam.film().pixel_at_ratio(x, y);
+            let deserialized = deserialized.film().pixel_at_ratio(x, y);
+            assert!((pixel - deserialized).norm() < 1e-5);
+        }
     }
 }
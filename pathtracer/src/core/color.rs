@@ -1,7 +1,8 @@
 //! Color definition and operations
 
+use crate::Float;
 use derive_more::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign, Sum};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::ops::{Div, DivAssign, Mul, MulAssign};
 
 #[derive(
@@ -18,15 +19,16 @@ use std::ops::{Div, DivAssign, Mul, MulAssign};
     SubAssign,
     Sum,
     Deserialize,
+    Serialize,
 )]
 /// A structure to represent operations in the linear RGB colorspace.
 pub struct LinearColor {
     /// The color's red component
-    pub r: f32,
+    pub r: Float,
     /// The color's green component
-    pub g: f32,
+    pub g: Float,
     /// The color's blue component
-    pub b: f32,
+    pub b: Float,
 }
 
 impl LinearColor {
@@ -66,7 +68,7 @@ impl LinearColor {
     /// #
     /// let color = LinearColor::new(1.0, 0.0, 0.0); // bright red!
     /// ```
-    pub fn new(r: f32, g: f32, b: f32) -> Self {
+    pub fn new(r: Float, g: Float, b: Float) -> Self {
         LinearColor { r, g, b }
     }
 
@@ -82,7 +84,7 @@ impl LinearColor {
     /// assert_eq!(color.clamp(), LinearColor::new(1.0, 0.0, 0.5))
     /// ```
     pub fn clamp(self) -> Self {
-        fn clamp(v: f32) -> f32 {
+        fn clamp(v: Float) -> Float {
             if v > 1. {
                 1.
             } else if v < 0. {
@@ -93,6 +95,99 @@ impl LinearColor {
         };
         LinearColor::new(clamp(self.r), clamp(self.g), clamp(self.b))
     }
+
+    /// Computes the relative luminance of the color, using the Rec. 709 coefficients.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// #
+    /// assert_eq!(LinearColor::black().luminance(), 0.0);
+    /// assert!((LinearColor::new(1.0, 1.0, 1.0).luminance() - 1.0).abs() < 1e-6);
+    /// ```
+    pub fn luminance(&self) -> Float {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    /// Get the color's channels as `f32`, the most precise way buffers and files store them.
+    // The cast is a no-op unless `Float` is `f64`
+    #[allow(clippy::unnecessary_cast)]
+    pub(crate) fn to_f32(&self) -> [f32; 3] {
+        [self.r as f32, self.g as f32, self.b as f32]
+    }
+
+    /// Creates the color of the light emitted by a black body at a temperature given in Kelvin,
+    /// with a luminance of 1, e.g: about 2700K for a tungsten bulb, or 6500K for daylight.
+    ///
+    /// Colors outside of the sRGB gamut, such as the deep red of very low temperatures, lose their
+    /// negative components.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// #
+    /// let tungsten = LinearColor::from_temperature(2700.);
+    /// assert!(tungsten.r > tungsten.g && tungsten.g > tungsten.b);
+    /// assert!((tungsten.luminance() - 1.0).abs() < 1e-5);
+    /// let sky = LinearColor::from_temperature(12000.);
+    /// assert!(sky.b > sky.r);
+    /// ```
+    // The cast is a no-op when `Float` is `f64`
+    #[allow(clippy::unnecessary_cast)]
+    pub fn from_temperature(temperature: Float) -> Self {
+        // Integrate Planck's law against the CIE 1931 color matching functions
+        let (mut x, mut y, mut z) = (0., 0., 0.);
+        for step in 0..=BLACKBODY_STEPS {
+            let wavelength = 380. + 400. * f64::from(step) / f64::from(BLACKBODY_STEPS);
+            let radiance = planck(wavelength, temperature as f64);
+            let [cx, cy, cz] = color_matching(wavelength);
+            x += radiance * cx;
+            y += radiance * cy;
+            z += radiance * cz;
+        }
+        // Convert from the XYZ color space to linear sRGB
+        let color = LinearColor::new(
+            (3.2406 * x - 1.5372 * y - 0.4986 * z).max(0.) as Float,
+            (-0.9689 * x + 1.8758 * y + 0.0415 * z).max(0.) as Float,
+            (0.0557 * x - 0.2040 * y + 1.0570 * z).max(0.) as Float,
+        );
+        let luminance = color.luminance();
+        if luminance > 0. && luminance.is_finite() {
+            color / luminance
+        } else {
+            LinearColor::black()
+        }
+    }
+}
+
+/// The number of wavelengths at which the visible spectrum is sampled for black bodies.
+const BLACKBODY_STEPS: u32 = 80;
+
+/// The spectral radiance of a black body at a wavelength given in nanometers, up to a constant
+/// factor.
+fn planck(wavelength: f64, temperature: f64) -> f64 {
+    // Work in micrometers, to keep the values in range
+    let wavelength = wavelength / 1000.;
+    // The second radiation constant, in micrometer-Kelvin
+    const C2: f64 = 14_387.77;
+    1. / (wavelength.powi(5) * ((C2 / (wavelength * temperature)).exp() - 1.))
+}
+
+/// The CIE 1931 color matching functions at a wavelength given in nanometers, using the
+/// multi-lobe fit of Wyman, Sloan and Shirley.
+fn color_matching(wavelength: f64) -> [f64; 3] {
+    let lobe = |mean: f64, low: f64, high: f64| {
+        let t = (wavelength - mean) / if wavelength < mean { low } else { high };
+        (-0.5 * t * t).exp()
+    };
+    [
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+            - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    ]
 }
 
 impl Default for LinearColor {
@@ -137,14 +232,44 @@ impl DivAssign for LinearColor {
     }
 }
 
+/// Decode a color component from the sRGB transfer function, used by 8-bit images, to linear
+/// light.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::core::srgb_to_linear;
+/// #
+/// assert_eq!(srgb_to_linear(0.), 0.);
+/// assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+/// assert!((srgb_to_linear(1.) - 1.).abs() < 1e-6);
+/// ```
+pub fn srgb_to_linear(value: Float) -> Float {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a linear color component with the sRGB transfer function, the inverse of
+/// [`srgb_to_linear`].
+///
+/// [`srgb_to_linear`]: fn.srgb_to_linear.html
+pub fn linear_to_srgb(value: Float) -> Float {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// Encode the color as an 8-bit sRGB pixel, clamping it.
 impl From<LinearColor> for image::Rgb<u8> {
     fn from(mut color: LinearColor) -> Self {
         color = color.clamp();
-        image::Rgb([
-            (color.r * 255.) as u8,
-            (color.g * 255.) as u8,
-            (color.b * 255.) as u8,
-        ])
+        let channel = |value: Float| (linear_to_srgb(value) * 255.).round() as u8;
+        image::Rgb([channel(color.r), channel(color.g), channel(color.b)])
     }
 }
 
@@ -347,4 +472,35 @@ mod test {
             }
         )
     }
+
+    #[test]
+    fn srgb_round_trips() {
+        for i in 0..=100 {
+            let value = i as Float / 100.;
+            assert!((srgb_to_linear(linear_to_srgb(value)) - value).abs() < 1e-5);
+        }
+        // Both pieces of the curve join
+        assert!((linear_to_srgb(0.003_130_8) * 255. - 0.04045 * 255.).abs() < 1e-2);
+    }
+
+    #[test]
+    fn rgb_is_encoded() {
+        let rgb: image::Rgb<u8> = LinearColor::new(1., 0.215_861, -1.).into();
+        assert_eq!(rgb, image::Rgb([255, 128, 0]));
+    }
+
+    #[test]
+    fn temperature_works() {
+        // Daylight is close to white
+        let daylight = LinearColor::from_temperature(6500.);
+        for channel in &[daylight.r, daylight.g, daylight.b] {
+            assert!((channel - 1.).abs() < 0.1, "{:?}", daylight);
+        }
+        // Colors go from red to blue as the temperature rises
+        let warm = LinearColor::from_temperature(1900.);
+        let cold = LinearColor::from_temperature(20000.);
+        assert!(warm.r / warm.b > daylight.r / daylight.b);
+        assert!(cold.r / cold.b < daylight.r / daylight.b);
+        assert_eq!(LinearColor::from_temperature(0.), LinearColor::black());
+    }
 }
@@ -0,0 +1,153 @@
+//! Coordinate system conventions of scenes and assets
+
+use crate::{Float, Vector};
+use nalgebra::{Matrix3, Unit};
+use serde::{Deserialize, Serialize};
+
+/// The axis pointing upwards.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpAxis {
+    /// The Y axis points up, e.g: most real-time engines and OBJ exports.
+    #[default]
+    Y,
+    /// The Z axis points up, e.g: Blender and most CAD software.
+    Z,
+}
+
+/// The handedness of a coordinate system.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Handedness {
+    /// A right-handed coordinate system.
+    #[default]
+    Right,
+    /// A left-handed coordinate system.
+    Left,
+}
+
+/// A coordinate system convention, the renderer itself using the default Y up right-handed one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct CoordinateSystem {
+    /// The axis pointing upwards.
+    #[serde(default)]
+    pub up_axis: UpAxis,
+    /// The handedness of the coordinate system.
+    #[serde(default)]
+    pub handedness: Handedness,
+}
+
+impl CoordinateSystem {
+    /// Creates a new `CoordinateSystem`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{CoordinateSystem, Handedness, UpAxis};
+    /// #
+    /// let blender = CoordinateSystem::new(UpAxis::Z, Handedness::Right);
+    /// ```
+    pub fn new(up_axis: UpAxis, handedness: Handedness) -> Self {
+        CoordinateSystem {
+            up_axis,
+            handedness,
+        }
+    }
+
+    /// The unit vector pointing upwards, in this coordinate system.
+    pub fn up(&self) -> Unit<Vector> {
+        match self.up_axis {
+            UpAxis::Y => Vector::y_axis(),
+            UpAxis::Z => Vector::z_axis(),
+        }
+    }
+
+    /// Whether objects seen with a right-handed camera would appear mirrored.
+    pub fn is_left_handed(&self) -> bool {
+        self.handedness == Handedness::Left
+    }
+
+    /// The matrix converting coordinates from this system into the Y up right-handed one.
+    fn to_native(self) -> Matrix3<Float> {
+        // Going from left to right-handed flips the axis which is neither up nor X
+        let (depth, sign) = match self.handedness {
+            Handedness::Right => (1., -1.),
+            Handedness::Left => (-1., 1.),
+        };
+        match self.up_axis {
+            UpAxis::Y => Matrix3::new(1., 0., 0., 0., 1., 0., 0., 0., depth),
+            // Z up right-handed maps (x, y, z) to (x, z, -y)
+            UpAxis::Z => Matrix3::new(1., 0., 0., 0., 0., 1., 0., sign, 0.),
+        }
+    }
+
+    /// The matrix converting coordinates from this system into another one.
+    ///
+    /// Its determinant is negative when both systems have a different handedness.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{CoordinateSystem, Handedness, UpAxis};
+    /// # use pathtracer::Vector;
+    /// #
+    /// let blender = CoordinateSystem::new(UpAxis::Z, Handedness::Right);
+    /// let native = CoordinateSystem::default();
+    /// let up = blender.conversion_to(&native) * Vector::z();
+    /// assert_eq!(up, Vector::y());
+    /// ```
+    pub fn conversion_to(&self, other: &Self) -> Matrix3<Float> {
+        // The conversion matrices are orthogonal, their inverse is their transpose
+        other.to_native().transpose() * self.to_native()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn new_works() {
+        let system = CoordinateSystem::new(UpAxis::Z, Handedness::Left);
+        assert_eq!(
+            system,
+            CoordinateSystem {
+                up_axis: UpAxis::Z,
+                handedness: Handedness::Left,
+            }
+        )
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = "{up_axis: z, handedness: left}";
+        let system: CoordinateSystem = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(system, CoordinateSystem::new(UpAxis::Z, Handedness::Left))
+    }
+
+    #[test]
+    fn default_is_native() {
+        let system: CoordinateSystem = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(system.to_native(), Matrix3::identity());
+        assert_eq!(system.up(), Vector::y_axis());
+        assert!(!system.is_left_handed());
+    }
+
+    #[test]
+    fn conversion_keeps_up() {
+        let systems = [
+            CoordinateSystem::new(UpAxis::Y, Handedness::Right),
+            CoordinateSystem::new(UpAxis::Y, Handedness::Left),
+            CoordinateSystem::new(UpAxis::Z, Handedness::Right),
+            CoordinateSystem::new(UpAxis::Z, Handedness::Left),
+        ];
+        for from in systems.iter() {
+            for to in systems.iter() {
+                let conversion = from.conversion_to(to);
+                assert_eq!(conversion * from.up().into_inner(), to.up().into_inner());
+                let mirrored = from.handedness != to.handedness;
+                assert_eq!(conversion.determinant() < 0., mirrored);
+            }
+        }
+    }
+}
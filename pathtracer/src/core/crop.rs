@@ -0,0 +1,129 @@
+//! Rendering only a region of the image
+
+use crate::Float;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// A rectangular region of the image, the only one to be rendered.
+///
+/// In a scene file, it is written either in pixels, or in coordinates going from 0.0 to 1.0 on
+/// each axis of the image:
+///
+/// ```yaml
+/// crop: {pixels: [100, 50, 320, 240]}
+/// crop: {ratios: [0.25, 0.25, 0.5, 0.5]}
+/// ```
+#[serde(rename_all = "lowercase")]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Crop {
+    /// The `[x, y, width, height]` of the region in pixels, from the top-left corner.
+    Pixels([u32; 4]),
+    /// The `[x, y, width, height]` of the region as ratios of the image's size.
+    Ratios([Float; 4]),
+}
+
+impl Crop {
+    /// Get the `(x, y, width, height)` of the region in pixels, for an image of the given size,
+    /// clipped to fit inside of it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Crop;
+    /// #
+    /// let crop = Crop::Ratios([0.25, 0.5, 0.5, 1.0]);
+    /// assert_eq!(crop.window(100, 200), (25, 100, 50, 100));
+    /// let crop = Crop::Pixels([90, 10, 20, 20]);
+    /// assert_eq!(crop.window(100, 200), (90, 10, 10, 20));
+    /// ```
+    pub fn window(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let [x, y, w, h] = match *self {
+            Crop::Pixels(pixels) => pixels,
+            Crop::Ratios(ratios) => {
+                let [x, y, w, h] = ratios;
+                let to_pixels =
+                    |ratio: Float, size: u32| (ratio * size as Float).round().max(0.) as u32;
+                [
+                    to_pixels(x, width),
+                    to_pixels(y, height),
+                    to_pixels(w, width),
+                    to_pixels(h, height),
+                ]
+            }
+        };
+        let (x, y) = (x.min(width), y.min(height));
+        (x, y, w.min(width - x), h.min(height - y))
+    }
+}
+
+/// Parse a crop from `x,y,width,height`, as pixels when the values are integers and as ratios of
+/// the image's size when they are decimal numbers, e.g: `0.25,0.25,0.5,0.5`.
+impl FromStr for Crop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values: Vec<&str> = s.split(',').map(str::trim).collect();
+        if values.len() != 4 {
+            return Err(format!("expected 'x,y,width,height', got '{}'", s));
+        }
+        let mut pixels = [0; 4];
+        let mut ratios = [0.; 4];
+        for (i, value) in values.iter().enumerate() {
+            let invalid = || format!("invalid crop value '{}'", value);
+            if value.contains('.') {
+                ratios[i] = value.parse().map_err(|_| invalid())?;
+            } else {
+                pixels[i] = value.parse().map_err(|_| invalid())?;
+            }
+        }
+        match values.iter().filter(|value| value.contains('.')).count() {
+            0 => Ok(Crop::Pixels(pixels)),
+            4 => Ok(Crop::Ratios(ratios)),
+            _ => Err(format!("cannot mix pixels and ratios in '{}'", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn window_works() {
+        assert_eq!(Crop::Pixels([1, 2, 3, 4]).window(10, 10), (1, 2, 3, 4));
+        assert_eq!(
+            Crop::Ratios([0., 0., 1., 1.]).window(640, 480),
+            (0, 0, 640, 480)
+        );
+    }
+
+    #[test]
+    fn window_is_clipped() {
+        assert_eq!(Crop::Pixels([8, 8, 4, 4]).window(10, 10), (8, 8, 2, 2));
+        assert_eq!(Crop::Pixels([20, 0, 4, 4]).window(10, 10), (10, 0, 0, 4));
+        assert_eq!(
+            Crop::Ratios([-0.5, 0.5, 2., 2.]).window(10, 10),
+            (0, 5, 10, 5)
+        );
+    }
+
+    #[test]
+    fn from_str_works() {
+        assert_eq!("1,2,3,4".parse(), Ok(Crop::Pixels([1, 2, 3, 4])));
+        assert_eq!(
+            "0.5, 0.25, 0.5, 1.0".parse(),
+            Ok(Crop::Ratios([0.5, 0.25, 0.5, 1.]))
+        );
+        assert!("1,2,3".parse::<Crop>().is_err());
+        assert!("1,2,3,0.5".parse::<Crop>().is_err());
+        assert!("a,2,3,4".parse::<Crop>().is_err());
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let crop: Crop = serde_yaml::from_str("pixels: [1, 2, 3, 4]").unwrap();
+        assert_eq!(crop, Crop::Pixels([1, 2, 3, 4]));
+        let crop: Crop = serde_yaml::from_str("ratios: [0.0, 0.5, 1.0, 0.5]").unwrap();
+        assert_eq!(crop, Crop::Ratios([0., 0.5, 1., 0.5]));
+    }
+}
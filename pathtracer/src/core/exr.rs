@@ -0,0 +1,261 @@
+//! Multi-layer OpenEXR images, written from floating point images
+
+use super::{FrameBuffer, Precision, F16};
+use std::io::{self, Write};
+
+/// The magic number starting every OpenEXR file.
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+/// The longest attribute and channel names allowed without flagging the file as using long names.
+const SHORT_NAME: usize = 31;
+
+/// A layer of an OpenEXR image: some channels of a [`FrameBuffer`], stored under a name.
+///
+/// [`FrameBuffer`]: ../framebuffer/struct.FrameBuffer.html
+#[derive(Clone, Copy, Debug)]
+pub struct ExrLayer<'a> {
+    name: &'a str,
+    channels: &'a [&'a str],
+    buffer: &'a FrameBuffer,
+}
+
+impl<'a> ExrLayer<'a> {
+    /// Creates a new `ExrLayer`, naming the red, green, and blue channels of the buffer in order,
+    /// e.g: `&["X", "Y", "Z"]` for a normal layer, or only `&["Z"]` to keep the first channel of
+    /// a depth layer.
+    ///
+    /// The channels are stored as `<name>.<channel>`, or as `<channel>` for an unnamed layer,
+    /// which compositing tools show as the main image.
+    pub fn new(name: &'a str, channels: &'a [&'a str], buffer: &'a FrameBuffer) -> Self {
+        assert!(channels.len() <= 3, "a buffer only has 3 channels");
+        ExrLayer {
+            name,
+            channels,
+            buffer,
+        }
+    }
+}
+
+/// A channel of the image, as stored in the file.
+struct Channel<'a> {
+    name: String,
+    buffer: &'a FrameBuffer,
+    /// The index of the color component stored in the channel.
+    component: usize,
+}
+
+impl Channel<'_> {
+    /// The OpenEXR pixel type and size of the channel's values.
+    fn pixel_type(&self) -> (i32, usize) {
+        match self.buffer.precision() {
+            Precision::Half => (1, 2),
+            Precision::Single => (2, 4),
+        }
+    }
+
+    fn write_row<W: Write>(&self, writer: &mut W, y: u32) -> io::Result<()> {
+        let (width, _) = self.buffer.dimensions();
+        for x in 0..width {
+            let value = self.buffer.get(x, y).to_f32()[self.component];
+            match self.buffer.precision() {
+                Precision::Half => writer.write_all(&F16::from(value).to_bits().to_le_bytes())?,
+                Precision::Single => writer.write_all(&value.to_le_bytes())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend(name.as_bytes());
+    header.push(0);
+    header.extend(kind.as_bytes());
+    header.push(0);
+    header.extend(&(value.len() as i32).to_le_bytes());
+    header.extend(value);
+}
+
+/// Write layers of the same dimensions as a single-part, uncompressed, OpenEXR image.
+///
+/// Each channel is stored at the [`Precision`] of its buffer, as half or single precision
+/// floating point numbers.
+///
+/// # Panics
+///
+/// If the layers do not all have the same dimensions.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::core::{write_exr, ExrLayer, FrameBuffer, LinearColor, Precision};
+/// #
+/// let mut beauty = FrameBuffer::new(4, 4, Precision::Half);
+/// beauty.set(1, 2, &LinearColor::new(4.0, 2.0, 1.0));
+/// let depth = FrameBuffer::new(4, 4, Precision::Single);
+/// let mut exr = Vec::new();
+/// write_exr(
+///     &mut exr,
+///     &[
+///         ExrLayer::new("", &["R", "G", "B"], &beauty),
+///         ExrLayer::new("depth", &["Z"], &depth),
+///     ],
+/// )
+/// .unwrap();
+/// assert!(exr.starts_with(&[0x76, 0x2f, 0x31, 0x01]));
+/// ```
+///
+/// [`Precision`]: ../framebuffer/enum.Precision.html
+pub fn write_exr<W: Write>(mut writer: W, layers: &[ExrLayer]) -> io::Result<()> {
+    let (width, height) = layers
+        .first()
+        .map_or((0, 0), |layer| layer.buffer.dimensions());
+    assert!(
+        (layers.iter()).all(|layer| layer.buffer.dimensions() == (width, height)),
+        "layers of different dimensions"
+    );
+    let mut channels: Vec<_> = layers
+        .iter()
+        .flat_map(|layer| {
+            (layer.channels.iter().enumerate()).map(move |(component, channel)| Channel {
+                name: match layer.name {
+                    "" => channel.to_string(),
+                    name => format!("{}.{}", name, channel),
+                },
+                buffer: layer.buffer,
+                component,
+            })
+        })
+        .collect();
+    // Channels are stored in the alphabetical order of their names
+    channels.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+
+    let mut list = Vec::new();
+    for channel in &channels {
+        list.extend(channel.name.as_bytes());
+        list.push(0);
+        list.extend(&channel.pixel_type().0.to_le_bytes());
+        // Perceptually linear flag, reserved bytes, and sampling along x and y
+        list.extend(&[0, 0, 0, 0]);
+        list.extend(&1i32.to_le_bytes());
+        list.extend(&1i32.to_le_bytes());
+    }
+    list.push(0);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|value| value.to_le_bytes().to_vec())
+        .collect();
+    let mut header = Vec::new();
+    attribute(&mut header, "channels", "chlist", &list);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    let long_names = channels
+        .iter()
+        .any(|channel| channel.name.len() > SHORT_NAME);
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&[2, if long_names { 0x04 } else { 0 }, 0, 0])?;
+    writer.write_all(&header)?;
+    // Without compression, each block holds a single row of pixels
+    let row_size: usize = (channels.iter())
+        .map(|channel| channel.pixel_type().1 * width as usize)
+        .sum();
+    let first_block = MAGIC.len() + 4 + header.len() + 8 * height as usize;
+    for y in 0..height as usize {
+        let offset = first_block + y * (8 + row_size);
+        writer.write_all(&(offset as u64).to_le_bytes())?;
+    }
+    for y in 0..height {
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(row_size as i32).to_le_bytes())?;
+        for channel in &channels {
+            channel.write_row(&mut writer, y)?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::LinearColor;
+    use std::convert::TryInto;
+
+    fn read_i32(bytes: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    }
+
+    #[test]
+    fn write_exr_works() {
+        let mut beauty = FrameBuffer::new(2, 3, Precision::Single);
+        beauty.set(1, 2, &LinearColor::new(4., 2., 1.));
+        let mut depth = FrameBuffer::new(2, 3, Precision::Half);
+        depth.set(0, 1, &LinearColor::new(0.5, 0., 0.));
+        let mut exr = Vec::new();
+        write_exr(
+            &mut exr,
+            &[
+                ExrLayer::new("depth", &["Z"], &depth),
+                ExrLayer::new("", &["R", "G", "B"], &beauty),
+            ],
+        )
+        .unwrap();
+
+        // Each row has 3 single precision channels and a half precision one
+        let row_size = 2 * (3 * 4 + 2);
+        let blocks = exr.len() - 3 * (8 + row_size);
+
+        assert_eq!(&exr[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+        let header = String::from_utf8_lossy(&exr[8..blocks - 24]);
+        let order: Vec<_> = ["B\0", "G\0", "R\0", "depth.Z\0"]
+            .iter()
+            .map(|name| header.find(name).unwrap())
+            .collect();
+        assert!(order.windows(2).all(|pair| pair[0] < pair[1]));
+        for y in 0..3 {
+            let offset = u64::from_le_bytes(
+                (exr[blocks - 24 + 8 * y..blocks - 16 + 8 * y])
+                    .try_into()
+                    .unwrap(),
+            ) as usize;
+            assert_eq!(offset, blocks + y * (8 + row_size));
+            assert_eq!(read_i32(&exr, offset), y as i32);
+            assert_eq!(read_i32(&exr, offset + 4), row_size as i32);
+        }
+        // The last row's blue channel comes first, then its green and red channels
+        let last = blocks + 2 * (8 + row_size) + 8;
+        let value = |at: usize| f32::from_le_bytes(exr[at..at + 4].try_into().unwrap());
+        assert_eq!(value(last + 4), 1.);
+        assert_eq!(value(last + 8 + 4), 2.);
+        assert_eq!(value(last + 16 + 4), 4.);
+        // The depth of the middle row is stored as half precision
+        let middle = blocks + (8 + row_size) + 8 + 3 * 8;
+        assert_eq!(
+            u16::from_le_bytes([exr[middle], exr[middle + 1]]),
+            F16::from(0.5).to_bits()
+        );
+    }
+
+    #[test]
+    fn long_names_are_flagged() {
+        let buffer = FrameBuffer::new(1, 1, Precision::Single);
+        let name = "a-light-with-a-really-long-name";
+        let mut exr = Vec::new();
+        write_exr(&mut exr, &[ExrLayer::new(name, &["R"], &buffer)]).unwrap();
+        assert_eq!(exr[5], 0x04);
+    }
+}
@@ -1,6 +1,6 @@
 //! Camera film logic
 
-use crate::{Point, Vector};
+use crate::{Float, Point, Vector};
 
 /// Represent an abstract camera film, to know where each pixel is in space.
 #[derive(Debug, PartialEq)]
@@ -30,11 +30,18 @@ impl Film {
     ///     Vector::new(1.0, 0.0, 0.0)
     /// );
     /// ```
-    pub fn new(x: u32, y: u32, screen_size: f32, center: Point, up: Vector, right: Vector) -> Self {
+    pub fn new(
+        x: u32,
+        y: u32,
+        screen_size: Float,
+        center: Point,
+        up: Vector,
+        right: Vector,
+    ) -> Self {
         let (x_size, y_size) = if x > y {
-            (screen_size, screen_size * y as f32 / x as f32)
+            (screen_size, screen_size * y as Float / x as Float)
         } else {
-            (screen_size * x as f32 / y as f32, screen_size)
+            (screen_size * x as Float / y as Float, screen_size)
         };
         Film {
             x,
@@ -73,6 +80,39 @@ impl Film {
         self.y
     }
 
+    /// Get the distance between two neighbouring pixels on the `Film`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Film;
+    /// #
+    /// let film = Film::default(); // 1080x1080 film, width of 1.0
+    /// assert_eq!(film.pixel_size(), 1.0 / 1080.0);
+    /// ```
+    pub fn pixel_size(&self) -> Float {
+        self.ratio_right.norm() / self.x as Float
+    }
+
+    /// Mirror the `Film` horizontally, swapping its left and right sides.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Film;
+    /// use pathtracer::Point;
+    ///
+    /// let film = Film::default().mirrored(); // 1080x1080 film, width of 1.0
+    /// assert_eq!(film.pixel_at_ratio(0.0, 0.5), Point::new(0.5, 0.0, 0.0));
+    /// ```
+    #[must_use]
+    pub fn mirrored(self) -> Self {
+        Film {
+            ratio_right: -self.ratio_right,
+            ..self
+        }
+    }
+
     /// Get a ratio of the pixel's position on the screen.
     ///
     /// # Examples
@@ -85,8 +125,8 @@ impl Film {
     /// assert_eq!(x, 0.1);
     /// assert_eq!(y, 0.9);
     /// ```
-    pub fn pixel_ratio(&self, x: f32, y: f32) -> (f32, f32) {
-        (x / self.x as f32, y / self.y as f32)
+    pub fn pixel_ratio(&self, x: Float, y: Float) -> (Float, Float) {
+        (x / self.x as Float, y / self.y as Float)
     }
 
     /// Get a pixel's absolute position from a relative screen ratio.
@@ -102,12 +142,34 @@ impl Film {
     /// let pos: Point = film.pixel_at_ratio(x, y);
     /// assert_eq!(pos, Point::new(-0.4, -0.5, 0.0));
     /// ```
-    pub fn pixel_at_ratio(&self, x: f32, y: f32) -> Point {
+    pub fn pixel_at_ratio(&self, x: Float, y: Float) -> Point {
         let delt_x = x - 0.5;
         let delt_y = 0.5 - y;
         self.center + self.ratio_right * delt_x + self.ratio_up * delt_y
     }
 
+    /// Get the screen coordinates of a point of the `Film`'s plane, the inverse of
+    /// [`pixel_at_coord`].
+    ///
+    /// [`pixel_at_coord`]: #method.pixel_at_coord
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Film;
+    /// use pathtracer::Point;
+    ///
+    /// let film = Film::default(); // 1080x1080 film, width of 1.0
+    /// let (x, y) = film.coord_of(&Point::new(-0.4, -0.5, 0.0));
+    /// assert!((x - 108.0).abs() < 1e-3 && (y - 1080.0).abs() < 1e-3);
+    /// ```
+    pub fn coord_of(&self, point: &Point) -> (Float, Float) {
+        let delt = point - self.center;
+        let x = delt.dot(&self.ratio_right) / self.ratio_right.norm_squared() + 0.5;
+        let y = 0.5 - delt.dot(&self.ratio_up) / self.ratio_up.norm_squared();
+        (x * self.x as Float, y * self.y as Float)
+    }
+
     /// Get a pixel's absolute position from screen coordinates.
     ///
     /// # Examples
@@ -121,7 +183,7 @@ impl Film {
     /// assert_eq!(pos, Point::new(-0.4, -0.5, 0.0));
     /// ```
     pub fn pixel_at_coord(&self, x: u32, y: u32) -> Point {
-        let (x, y) = self.pixel_ratio(x as f32, y as f32);
+        let (x, y) = self.pixel_ratio(x as Float, y as Float);
         self.pixel_at_ratio(x, y)
     }
 }
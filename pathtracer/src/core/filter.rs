@@ -0,0 +1,269 @@
+//! Pixel reconstruction filters
+
+use crate::Float;
+use serde::{Deserialize, Serialize};
+
+/// How the samples taken around a pixel are weighted to compute its color when anti-aliasing.
+///
+/// Each filter is separable, and spans a square of `2 * radius` pixels around the pixel's center.
+/// Wider filters give smoother images, and filters with negative lobes such as Mitchell-Netravali
+/// keep them sharp.
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Filter {
+    /// Weigh every sample equally.
+    Box {
+        /// Half the width of the filter, 0.5 by default to cover the pixel exactly.
+        #[serde(default = "default_box_radius")]
+        radius: Float,
+    },
+    /// Weigh the samples linearly less the farther they are from the pixel's center.
+    Tent {
+        /// Half the width of the filter, 1.0 by default.
+        #[serde(default = "crate::serialize::default_identity")]
+        radius: Float,
+    },
+    /// Weigh the samples with a gaussian centered on the pixel, shifted to reach zero at the
+    /// radius.
+    Gaussian {
+        /// Half the width of the filter, 1.5 by default.
+        #[serde(default = "default_gaussian_radius")]
+        radius: Float,
+        /// The standard deviation of the gaussian, in pixels, 0.5 by default.
+        #[serde(default = "default_sigma")]
+        sigma: Float,
+    },
+    /// The Mitchell-Netravali cubic filter, trading blurring against ringing.
+    Mitchell {
+        /// Half the width of the filter, 2.0 by default.
+        #[serde(default = "default_mitchell_radius")]
+        radius: Float,
+        /// The blurring parameter, 1/3 by default.
+        #[serde(default = "default_mitchell_parameter")]
+        b: Float,
+        /// The ringing parameter, 1/3 by default.
+        #[serde(default = "default_mitchell_parameter")]
+        c: Float,
+    },
+}
+
+fn default_box_radius() -> Float {
+    0.5
+}
+
+fn default_gaussian_radius() -> Float {
+    1.5
+}
+
+fn default_sigma() -> Float {
+    0.5
+}
+
+fn default_mitchell_radius() -> Float {
+    2.
+}
+
+fn default_mitchell_parameter() -> Float {
+    1. / 3.
+}
+
+impl Default for Filter {
+    /// A box filter covering the pixel.
+    fn default() -> Self {
+        Filter::Box {
+            radius: default_box_radius(),
+        }
+    }
+}
+
+impl Filter {
+    /// Creates a new Mitchell-Netravali filter with the recommended parameters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Filter;
+    /// #
+    /// let filter = Filter::mitchell(2.0);
+    /// assert_eq!(filter.radius(), 2.0);
+    /// assert!(filter.weight(0.0, 0.0) > 0.0);
+    /// // The negative lobes sharpen the image
+    /// assert!(filter.weight(1.5, 0.0) < 0.0);
+    /// assert_eq!(filter.weight(2.5, 0.0), 0.0);
+    /// ```
+    pub fn mitchell(radius: Float) -> Self {
+        Filter::Mitchell {
+            radius,
+            b: default_mitchell_parameter(),
+            c: default_mitchell_parameter(),
+        }
+    }
+
+    /// Get the distance from the pixel's center, in pixels, after which samples are ignored.
+    pub fn radius(&self) -> Float {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. } => radius,
+        }
+    }
+
+    /// Get the weight of a sample at the given offset from the pixel's center, in pixels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Filter;
+    /// #
+    /// let tent = Filter::Tent { radius: 1.0 };
+    /// assert_eq!(tent.weight(0.0, 0.0), 1.0);
+    /// assert_eq!(tent.weight(0.5, 0.5), 0.25);
+    /// assert_eq!(tent.weight(1.0, 0.0), 0.0);
+    /// ```
+    pub fn weight(&self, x: Float, y: Float) -> Float {
+        self.weight_1d(x) * self.weight_1d(y)
+    }
+
+    /// Get the offset from the pixel's center of a sample at `(u, v)` in `[0, 1)²`, stretched
+    /// uniformly over the filter's square.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Filter;
+    /// #
+    /// let filter = Filter::Tent { radius: 1.0 };
+    /// assert_eq!(filter.offset(0.5, 0.5), (0.0, 0.0));
+    /// assert_eq!(filter.offset(0.0, 0.75), (-1.0, 0.5));
+    /// ```
+    pub fn offset(&self, u: Float, v: Float) -> (Float, Float) {
+        let radius = self.radius();
+        ((2. * u - 1.) * radius, (2. * v - 1.) * radius)
+    }
+
+    fn weight_1d(&self, x: Float) -> Float {
+        let x = x.abs();
+        if x > self.radius() {
+            return 0.;
+        }
+        match *self {
+            Filter::Box { .. } => 1.,
+            Filter::Tent { radius } => radius - x,
+            Filter::Gaussian { radius, sigma } => {
+                let gaussian = |x: Float| (-x * x / (2. * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(radius)).max(0.)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                // The cubic is defined over [-2, 2]
+                let x = 2. * x / radius;
+                let (x2, x3) = (x * x, x * x * x);
+                let value = if x < 1. {
+                    (12. - 9. * b - 6. * c) * x3 + (-18. + 12. * b + 6. * c) * x2 + (6. - 2. * b)
+                } else {
+                    (-b - 6. * c) * x3
+                        + (6. * b + 30. * c) * x2
+                        + (-12. * b - 48. * c) * x
+                        + (8. * b + 24. * c)
+                };
+                value / 6.
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Integrate the filter over its square with the midpoint rule.
+    fn integral(filter: &Filter) -> Float {
+        const STEPS: u32 = 200;
+        let radius = filter.radius();
+        let step = 2. * radius / STEPS as Float;
+        let mut sum = 0.;
+        for i in 0..STEPS {
+            for j in 0..STEPS {
+                let x = -radius + (i as Float + 0.5) * step;
+                let y = -radius + (j as Float + 0.5) * step;
+                sum += filter.weight(x, y) * step * step;
+            }
+        }
+        sum
+    }
+
+    #[test]
+    fn default_is_box() {
+        let filter = Filter::default();
+        assert_eq!(filter.radius(), 0.5);
+        assert_eq!(filter.weight(0.4, -0.4), 1.);
+        assert_eq!(filter.weight(0.6, 0.), 0.);
+    }
+
+    #[test]
+    fn filters_are_positive_at_center() {
+        let filters = [
+            Filter::default(),
+            Filter::Tent { radius: 1. },
+            Filter::Gaussian {
+                radius: 1.5,
+                sigma: 0.5,
+            },
+            Filter::mitchell(2.),
+        ];
+        for filter in filters.iter() {
+            assert!(filter.weight(0., 0.) > 0., "{:?}", filter);
+            assert!(integral(filter) > 0., "{:?}", filter);
+            // The filters are symmetric
+            assert_eq!(filter.weight(0.3, 0.2), filter.weight(-0.3, 0.2));
+            assert_eq!(filter.weight(0.3, 0.2), filter.weight(0.2, 0.3));
+            assert_eq!(filter.weight(filter.radius() + 0.1, 0.), 0.);
+        }
+    }
+
+    #[test]
+    fn gaussian_reaches_zero_at_radius() {
+        let filter = Filter::Gaussian {
+            radius: 1.5,
+            sigma: 0.5,
+        };
+        assert!(filter.weight(1.49, 0.) < 1e-3);
+        assert!(filter.weight(0.5, 0.) < filter.weight(0.25, 0.));
+    }
+
+    #[test]
+    fn mitchell_is_continuous() {
+        let filter = Filter::mitchell(2.);
+        // The two pieces of the cubic join at half the radius
+        let (before, after) = (filter.weight(0.999, 0.), filter.weight(1.001, 0.));
+        assert!((before - after).abs() < 1e-3);
+        assert!(filter.weight(1.99, 0.).abs() < 1e-3);
+        // Close to a unit integral, the cubic being normalized over [-2, 2]
+        assert!((integral(&Filter::mitchell(2.)) - 1.).abs() < 1e-2);
+    }
+
+    #[test]
+    fn offsets_cover_the_filter() {
+        let filter = Filter::Tent { radius: 1.5 };
+        assert_eq!(filter.offset(0., 0.), (-1.5, -1.5));
+        assert_eq!(filter.offset(1., 0.5), (1.5, 0.));
+        assert_eq!(filter.offset(0.25, 0.75), (-0.75, 0.75));
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let filter: Filter = serde_yaml::from_str("{type: gaussian, radius: 2.0}").unwrap();
+        assert_eq!(
+            filter,
+            Filter::Gaussian {
+                radius: 2.,
+                sigma: 0.5
+            }
+        );
+        let filter: Filter = serde_yaml::from_str("type: mitchell").unwrap();
+        assert_eq!(filter, Filter::mitchell(2.));
+        let filter: Filter = serde_yaml::from_str("type: box").unwrap();
+        assert_eq!(filter, Filter::default());
+    }
+}
@@ -0,0 +1,388 @@
+//! Floating point images, accumulated while rendering
+
+use super::{linear_to_srgb, LinearColor, F16};
+use crate::Float;
+use crate::{Error, Result};
+use image::{ImageBuffer, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// How precisely the colors of a [`FrameBuffer`] are stored.
+///
+/// [`FrameBuffer`]: struct.FrameBuffer.html
+#[serde(rename_all = "lowercase")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Precision {
+    /// 32-bit floating point numbers.
+    #[default]
+    Single,
+    /// 16-bit floating point numbers, using half the memory, but only keeping about 3 significant
+    /// digits.
+    Half,
+}
+
+/// The number of bits per channel of the images a [`FrameBuffer`] is saved as.
+///
+/// [`FrameBuffer`]: struct.FrameBuffer.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BitDepth {
+    /// 8 bits per channel, supported by every image format.
+    #[default]
+    Eight,
+    /// 16 bits per channel, e.g: for PNG images, avoiding banding in smooth gradients.
+    Sixteen,
+}
+
+impl FromStr for BitDepth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(BitDepth::Eight),
+            "16" => Ok(BitDepth::Sixteen),
+            _ => Err(format!("expected a bit depth of 8 or 16, got '{}'", s)),
+        }
+    }
+}
+
+/// Check whether a path refers to a portable float map, from its '.pfm' extension.
+pub fn is_pfm<P: AsRef<Path>>(path: P) -> bool {
+    (path.as_ref().extension()).is_some_and(|ext| ext.eq_ignore_ascii_case("pfm"))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Channels {
+    Single(Vec<f32>),
+    Half(Vec<F16>),
+}
+
+/// An image of linear colors, stored at a given [`Precision`].
+///
+/// Colors are always computed at single precision, and only rounded when stored.
+///
+/// [`Precision`]: enum.Precision.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    channels: Channels,
+}
+
+impl FrameBuffer {
+    /// Creates a new black `FrameBuffer`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{FrameBuffer, LinearColor, Precision};
+    /// #
+    /// let mut buffer = FrameBuffer::new(4, 2, Precision::Half);
+    /// buffer.set(3, 1, &LinearColor::new(0.5, 1.0, 2.0));
+    /// assert_eq!(buffer.get(3, 1), LinearColor::new(0.5, 1.0, 2.0));
+    /// assert_eq!(buffer.get(0, 0), LinearColor::black());
+    /// ```
+    pub fn new(width: u32, height: u32, precision: Precision) -> Self {
+        let len = (width * height * 3) as usize;
+        let channels = match precision {
+            Precision::Single => Channels::Single(vec![0.; len]),
+            Precision::Half => Channels::Half(vec![F16::default(); len]),
+        };
+        FrameBuffer {
+            width,
+            height,
+            channels,
+        }
+    }
+
+    /// Get the `FrameBuffer`'s width and height.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Get the `Precision` at which the colors are stored.
+    pub fn precision(&self) -> Precision {
+        match self.channels {
+            Channels::Single(_) => Precision::Single,
+            Channels::Half(_) => Precision::Half,
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        ((y * self.width + x) * 3) as usize
+    }
+
+    /// Get the color of a pixel.
+    pub fn get(&self, x: u32, y: u32) -> LinearColor {
+        let index = self.index(x, y);
+        let [r, g, b] = match &self.channels {
+            Channels::Single(values) => [values[index], values[index + 1], values[index + 2]],
+            Channels::Half(values) => [
+                values[index].into(),
+                values[index + 1].into(),
+                values[index + 2].into(),
+            ],
+        };
+        LinearColor::new(r as Float, g as Float, b as Float)
+    }
+
+    /// Set the color of a pixel, rounding it to the buffer's `Precision`.
+    pub fn set(&mut self, x: u32, y: u32, color: &LinearColor) {
+        let index = self.index(x, y);
+        let color = color.to_f32();
+        match &mut self.channels {
+            Channels::Single(values) => values[index..index + 3].copy_from_slice(&color),
+            Channels::Half(values) => {
+                for (value, &channel) in values[index..index + 3].iter_mut().zip(&color) {
+                    *value = channel.into();
+                }
+            }
+        }
+    }
+
+    /// Add the rows of another `FrameBuffer` below this one's, e.g: to assemble rows rendered
+    /// separately.
+    ///
+    /// Returns an error if they do not have the same width and `Precision`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{FrameBuffer, LinearColor, Precision};
+    /// #
+    /// let mut buffer = FrameBuffer::new(2, 0, Precision::Single);
+    /// let mut row = FrameBuffer::new(2, 1, Precision::Single);
+    /// row.set(1, 0, &LinearColor::new(1.0, 1.0, 1.0));
+    /// buffer.append(row.clone())?;
+    /// buffer.append(row)?;
+    /// assert_eq!(buffer.dimensions(), (2, 2));
+    /// assert_eq!(buffer.get(1, 1), LinearColor::new(1.0, 1.0, 1.0));
+    /// # Ok::<(), pathtracer::Error>(())
+    /// ```
+    pub fn append(&mut self, other: FrameBuffer) -> Result<()> {
+        if self.width != other.width {
+            return Err(Error::InvalidParameter(format!(
+                "cannot append a buffer {} pixels wide to one {} pixels wide",
+                other.width, self.width
+            )));
+        }
+        match (&mut self.channels, other.channels) {
+            (Channels::Single(values), Channels::Single(other)) => values.extend(other),
+            (Channels::Half(values), Channels::Half(other)) => values.extend(other),
+            _ => {
+                return Err(Error::InvalidParameter(
+                    "cannot append buffers of different precisions".to_string(),
+                ))
+            }
+        }
+        self.height += other.height;
+        Ok(())
+    }
+
+    /// Blend another `FrameBuffer` of the same dimensions into this one, e.g: to average passes
+    /// rendered one after the other, giving a weight of `1 / n` to the `n`-th pass.
+    ///
+    /// Returns an error if they do not have the same dimensions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{FrameBuffer, LinearColor, Precision};
+    /// # use pathtracer::Float;
+    /// #
+    /// let mut average = FrameBuffer::new(1, 1, Precision::Single);
+    /// for (n, value) in [1.0, 2.0, 6.0].iter().enumerate() {
+    ///     let mut pass = FrameBuffer::new(1, 1, Precision::Single);
+    ///     pass.set(0, 0, &LinearColor::new(*value, 0.0, 0.0));
+    ///     average.blend(&pass, 1.0 / (n + 1) as Float)?;
+    /// }
+    /// assert_eq!(average.get(0, 0), LinearColor::new(3.0, 0.0, 0.0));
+    /// # Ok::<(), pathtracer::Error>(())
+    /// ```
+    pub fn blend(&mut self, other: &FrameBuffer, weight: Float) -> Result<()> {
+        if self.dimensions() != other.dimensions() {
+            return Err(Error::InvalidParameter(format!(
+                "cannot blend a {:?} buffer into a {:?} one",
+                other.dimensions(),
+                self.dimensions()
+            )));
+        }
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = self.get(x, y) * (1. - weight) + other.get(x, y) * weight;
+                self.set(x, y, &color);
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert the `FrameBuffer` to an 8-bit sRGB image, clamping its colors.
+    pub fn to_image(&self) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| self.get(x, y).into())
+    }
+
+    /// Convert the `FrameBuffer` to a 16-bit sRGB image, clamping its colors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{FrameBuffer, LinearColor, Precision};
+    /// # use image::Rgb;
+    /// #
+    /// let mut buffer = FrameBuffer::new(1, 1, Precision::Single);
+    /// buffer.set(0, 0, &LinearColor::new(2.0, 0.5, 0.0));
+    /// assert_eq!(buffer.to_image16().get_pixel(0, 0), &Rgb([65535, 48192, 0]));
+    /// ```
+    pub fn to_image16(&self) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let color = self.get(x, y).clamp();
+            let channel = |value: Float| (linear_to_srgb(value) * 65535.).round() as u16;
+            Rgb([channel(color.r), channel(color.g), channel(color.b)])
+        })
+    }
+
+    /// Write the `FrameBuffer` as a little-endian portable float map, keeping its colors as they
+    /// were rendered.
+    ///
+    /// The rows of a portable float map are stored from the bottom of the image to its top.
+    pub fn write_pfm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                for channel in &self.get(x, y).to_f32() {
+                    writer.write_all(&channel.to_le_bytes())?;
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    /// Save the `FrameBuffer` to a file, as a portable float map if its extension is '.pfm', or
+    /// as an image of the given `BitDepth` in the format given by its extension otherwise.
+    pub fn save<P: AsRef<Path>>(&self, path: P, depth: BitDepth) -> Result<()> {
+        let path = path.as_ref();
+        let result = if is_pfm(path) {
+            File::create(path)
+                .and_then(|file| self.write_pfm(BufWriter::new(file)))
+                .map_err(Into::into)
+        } else {
+            match depth {
+                BitDepth::Eight => self.to_image().save(path),
+                BitDepth::Sixteen => self.to_image16().save(path),
+            }
+            .map_err(Into::into)
+        };
+        result.map_err(|err: crate::Error| err.with_path(path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn new_works() {
+        let buffer = FrameBuffer::new(3, 2, Precision::Single);
+        assert_eq!(buffer.dimensions(), (3, 2));
+        assert_eq!(buffer.precision(), Precision::Single);
+        assert_eq!(
+            buffer,
+            FrameBuffer {
+                width: 3,
+                height: 2,
+                channels: Channels::Single(vec![0.; 18]),
+            }
+        )
+    }
+
+    #[test]
+    fn half_precision_rounds_colors() {
+        let mut buffer = FrameBuffer::new(1, 1, Precision::Half);
+        assert_eq!(buffer.precision(), Precision::Half);
+        let color = LinearColor::new(0.1, 10.3, 1000.7);
+        buffer.set(0, 0, &color);
+        let stored = buffer.get(0, 0);
+        assert_ne!(stored, color);
+        for (stored, value) in [stored.r, stored.g, stored.b]
+            .iter()
+            .zip(&[color.r, color.g, color.b])
+        {
+            assert!((stored - value).abs() / value < 1e-3);
+        }
+    }
+
+    #[test]
+    fn mismatched_buffers_are_rejected() {
+        let mut buffer = FrameBuffer::new(2, 1, Precision::Single);
+        let half = FrameBuffer::new(2, 1, Precision::Half);
+        assert!(buffer.append(half).is_err());
+        let narrow = FrameBuffer::new(1, 1, Precision::Single);
+        assert!(buffer.append(narrow.clone()).is_err());
+        assert!(buffer.blend(&narrow, 0.5).is_err());
+        assert_eq!(buffer.dimensions(), (2, 1));
+    }
+
+    #[test]
+    fn to_image_works() {
+        let mut buffer = FrameBuffer::new(2, 1, Precision::Half);
+        buffer.set(1, 0, &LinearColor::new(2., 1., 0.));
+        let image = buffer.to_image();
+        assert_eq!(image.get_pixel(0, 0), &image::Rgb([0, 0, 0]));
+        assert_eq!(image.get_pixel(1, 0), &image::Rgb([255, 255, 0]));
+    }
+
+    #[test]
+    fn write_pfm_works() {
+        let mut buffer = FrameBuffer::new(2, 2, Precision::Single);
+        buffer.set(1, 0, &LinearColor::new(2., 1., 0.5));
+        let mut pfm = Vec::new();
+        buffer.write_pfm(&mut pfm).unwrap();
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&pfm[..header.len()], header);
+        let values: Vec<f32> = pfm[header.len()..]
+            .chunks(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        // The top row comes last
+        assert_eq!(values.len(), 12);
+        assert_eq!(&values[..6], &[0.; 6]);
+        assert_eq!(&values[6..], &[0., 0., 0., 2., 1., 0.5]);
+    }
+
+    #[test]
+    fn save_works() {
+        let directory = std::env::temp_dir().join(format!("framebuffer-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut buffer = FrameBuffer::new(2, 1, Precision::Half);
+        buffer.set(1, 0, &LinearColor::new(1., 0.25, 0.));
+
+        let png = directory.join("image.png");
+        buffer.save(&png, BitDepth::Sixteen).unwrap();
+        let image = image::open(&png).unwrap().into_rgb16();
+        assert_eq!(image.get_pixel(1, 0), &Rgb([65535, 35199, 0]));
+
+        let pfm = directory.join("image.PFM");
+        buffer.save(&pfm, BitDepth::Sixteen).unwrap();
+        assert!(std::fs::read(&pfm).unwrap().starts_with(b"PF\n2 1\n"));
+
+        let err = buffer.save(directory.join("image.unknown"), BitDepth::Eight);
+        assert!(matches!(err, Err(crate::Error::Image { .. })));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn bit_depth_parsing_works() {
+        assert_eq!("8".parse(), Ok(BitDepth::Eight));
+        assert_eq!("16".parse(), Ok(BitDepth::Sixteen));
+        assert!("32".parse::<BitDepth>().is_err());
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let precision: Precision = serde_yaml::from_str("half").unwrap();
+        assert_eq!(precision, Precision::Half);
+    }
+}
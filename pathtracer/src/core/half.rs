@@ -0,0 +1,133 @@
+//! Half precision floating point numbers, used to store large buffers compactly
+
+/// A 16-bit IEEE 754 floating point number, which only supports conversions from and to `f32`.
+///
+/// It has about 3 significant decimal digits, and can represent values up to 65504.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct F16(u16);
+
+impl F16 {
+    /// Get the raw bits of the number.
+    pub fn to_bits(self) -> u16 {
+        self.0
+    }
+}
+
+impl From<f32> for F16 {
+    /// Convert to the closest half precision value, rounding ties to even. Values too large to be
+    /// represented become infinite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::F16;
+    /// #
+    /// assert_eq!(f32::from(F16::from(0.5)), 0.5);
+    /// assert_eq!(f32::from(F16::from(1.0 / 3.0)), 0.33325195);
+    /// assert_eq!(f32::from(F16::from(1e6)), f32::INFINITY);
+    /// ```
+    fn from(value: f32) -> Self {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xff) as i32;
+        let mantissa = bits & 0x7f_ffff;
+        // Infinities, and NaNs which must stay NaNs
+        if exponent == 0xff {
+            let nan = if mantissa == 0 { 0 } else { 0x200 };
+            return F16(sign | 0x7c00 | nan);
+        }
+        let exponent = exponent - 127 + 15;
+        if exponent >= 0x1f {
+            return F16(sign | 0x7c00);
+        }
+        // Round the mantissa to nearest, ties to even, the carry correctly going into the exponent
+        let round = |value: u32, shift: u32| {
+            let (truncated, remainder) = (value >> shift, value & ((1 << shift) - 1));
+            let halfway = 1 << (shift - 1);
+            let up = remainder > halfway || (remainder == halfway && truncated & 1 == 1);
+            truncated + up as u32
+        };
+        if exponent <= 0 {
+            // Too small even for a subnormal value
+            if exponent < -10 {
+                return F16(sign);
+            }
+            let subnormal = round(mantissa | 0x80_0000, (14 - exponent) as u32);
+            return F16(sign | subnormal as u16);
+        }
+        F16(sign | round(((exponent as u32) << 23) | mantissa, 13) as u16)
+    }
+}
+
+impl From<F16> for f32 {
+    fn from(value: F16) -> Self {
+        let bits = value.0;
+        let sign = ((bits & 0x8000) as u32) << 16;
+        let exponent = ((bits >> 10) & 0x1f) as u32;
+        let mantissa = (bits & 0x3ff) as u32;
+        match exponent {
+            0 => {
+                let magnitude = mantissa as f32 / (1 << 24) as f32;
+                if sign == 0 {
+                    magnitude
+                } else {
+                    -magnitude
+                }
+            }
+            0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+            _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip(value: f32) -> f32 {
+        F16::from(value).into()
+    }
+
+    #[test]
+    fn exact_values_round_trip() {
+        for &value in &[
+            0.,
+            -0.,
+            1.,
+            -2.,
+            0.25,
+            1024.,
+            65504.,
+            6.103_515_6e-5,
+            5.960_464_5e-8,
+        ] {
+            assert_eq!(round_trip(value), value);
+        }
+        assert_eq!(F16::from(-0.).to_bits(), 0x8000);
+    }
+
+    #[test]
+    fn rounding_works() {
+        // Ties are rounded to even
+        assert_eq!(round_trip(1. + 1. / 2048.), 1.);
+        assert_eq!(round_trip(1. + 3. / 2048.), 1. + 4. / 2048.);
+        // The carry goes into the exponent
+        assert_eq!(round_trip(2. - 1. / 4096.), 2.);
+        // The relative error stays small
+        for i in 1..1000 {
+            let value = i as f32 * 0.731;
+            assert!((round_trip(value) - value).abs() / value < 1. / 2048.);
+        }
+    }
+
+    #[test]
+    fn special_values_work() {
+        assert_eq!(round_trip(f32::INFINITY), f32::INFINITY);
+        assert_eq!(round_trip(f32::NEG_INFINITY), f32::NEG_INFINITY);
+        assert!(round_trip(f32::NAN).is_nan());
+        assert_eq!(round_trip(65520.), f32::INFINITY);
+        assert_eq!(round_trip(1e-10), 0.);
+        // Subnormal values
+        assert_eq!(round_trip(3. * 5.960_464_5e-8), 3. * 5.960_464_5e-8);
+    }
+}
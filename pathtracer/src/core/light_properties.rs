@@ -1,9 +1,10 @@
 //! Light property coefficients (diffuse, specular, transparency, reflectivity...)
 
 use super::color::LinearColor;
-use serde::Deserialize;
+use crate::Float;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 /// This enum stores the reflectivity or transparency information.
 pub enum ReflTransEnum {
@@ -11,20 +12,42 @@ pub enum ReflTransEnum {
     Transparency {
         /// The transparency coefficient.
         #[serde(rename = "transparency")]
-        coef: f32,
+        coef: Float,
         /// The diffraction index.
-        index: f32,
+        index: Float,
     },
     /// Reflectivity properties.
     Reflectivity {
         /// The reflectivity coefficient.
         #[serde(rename = "reflectivity")]
-        coef: f32,
+        coef: Float,
     },
 }
 
+impl ReflTransEnum {
+    /// The reflectivity or transparency coefficient.
+    pub fn coef(&self) -> Float {
+        match *self {
+            ReflTransEnum::Transparency { coef, .. } => coef,
+            ReflTransEnum::Reflectivity { coef } => coef,
+        }
+    }
+
+    fn scaled(&self, factor: Float) -> Self {
+        match *self {
+            ReflTransEnum::Transparency { coef, index } => ReflTransEnum::Transparency {
+                coef: coef * factor,
+                index,
+            },
+            ReflTransEnum::Reflectivity { coef } => ReflTransEnum::Reflectivity {
+                coef: coef * factor,
+            },
+        }
+    }
+}
+
 /// A structure holding all the physical proprerties relating to light at a point.
-#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub struct LightProperties {
     /// The diffuse component.
     pub diffuse: LinearColor,
@@ -61,6 +84,69 @@ impl LightProperties {
             refl_trans,
         }
     }
+
+    /// Linearly interpolate between two sets of properties, `factor` going from `self` at 0.0 to
+    /// `other` at 1.0.
+    ///
+    /// Reflectivity and transparency cannot be combined: when mixing them, the one with the
+    /// largest weighted coefficient is kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::light_properties::{LightProperties, ReflTransEnum};
+    /// # use pathtracer::core::color::LinearColor;
+    /// #
+    /// let matte = LightProperties::new(LinearColor::new(1., 1., 1.), LinearColor::black(), None);
+    /// let mirror = LightProperties::new(
+    ///     LinearColor::black(),
+    ///     LinearColor::black(),
+    ///     Some(ReflTransEnum::Reflectivity { coef: 1. }),
+    /// );
+    /// let mixed = matte.mix(&mirror, 0.25);
+    /// assert_eq!(mixed.diffuse, LinearColor::new(0.75, 0.75, 0.75));
+    /// assert_eq!(mixed.refl_trans, Some(ReflTransEnum::Reflectivity { coef: 0.25 }));
+    /// ```
+    pub fn mix(&self, other: &Self, factor: Float) -> Self {
+        use ReflTransEnum::*;
+
+        let lerp = |a: Float, b: Float| a * (1. - factor) + b * factor;
+        let refl_trans = match (&self.refl_trans, &other.refl_trans) {
+            (None, None) => None,
+            (Some(lhs), None) => Some(lhs.scaled(1. - factor)),
+            (None, Some(rhs)) => Some(rhs.scaled(factor)),
+            (Some(Reflectivity { coef: lhs }), Some(Reflectivity { coef: rhs })) => {
+                Some(Reflectivity {
+                    coef: lerp(*lhs, *rhs),
+                })
+            }
+            (
+                Some(Transparency {
+                    coef: lhs,
+                    index: i,
+                }),
+                Some(Transparency {
+                    coef: rhs,
+                    index: j,
+                }),
+            ) => Some(Transparency {
+                coef: lerp(*lhs, *rhs),
+                index: lerp(*i, *j),
+            }),
+            (Some(lhs), Some(rhs)) => {
+                if lhs.coef() * (1. - factor) >= rhs.coef() * factor {
+                    Some(lhs.scaled(1. - factor))
+                } else {
+                    Some(rhs.scaled(factor))
+                }
+            }
+        };
+        LightProperties {
+            diffuse: self.diffuse.clone() * (1. - factor) + other.diffuse.clone() * factor,
+            specular: self.specular.clone() * (1. - factor) + other.specular.clone() * factor,
+            refl_trans,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -140,4 +226,37 @@ mod test {
             )
         )
     }
+
+    #[test]
+    fn mix_works() {
+        let glass = LightProperties::new(
+            LinearColor::new(1., 1., 1.),
+            LinearColor::black(),
+            Some(ReflTransEnum::Transparency {
+                coef: 1.,
+                index: 1.5,
+            }),
+        );
+        let mirror = LightProperties::new(
+            LinearColor::black(),
+            LinearColor::new(1., 1., 1.),
+            Some(ReflTransEnum::Reflectivity { coef: 0.5 }),
+        );
+        assert_eq!(glass.mix(&mirror, 0.), glass);
+        assert_eq!(
+            glass.mix(&mirror, 0.5),
+            LightProperties::new(
+                LinearColor::new(0.5, 0.5, 0.5),
+                LinearColor::new(0.5, 0.5, 0.5),
+                Some(ReflTransEnum::Transparency {
+                    coef: 0.5,
+                    index: 1.5,
+                }),
+            )
+        );
+        assert_eq!(
+            glass.mix(&mirror, 0.9).refl_trans,
+            Some(ReflTransEnum::Reflectivity { coef: 0.45 })
+        );
+    }
 }
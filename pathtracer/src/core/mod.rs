@@ -6,8 +6,40 @@ pub use camera::*;
 pub mod color;
 pub use color::*;
 
+pub mod coordinates;
+pub use coordinates::*;
+
+pub mod crop;
+pub use crop::*;
+
+pub mod exr;
+pub use exr::*;
+
 pub mod film;
 pub use film::*;
 
+pub mod filter;
+pub use filter::*;
+
+pub mod framebuffer;
+pub use framebuffer::*;
+
+pub mod half;
+pub use half::*;
+
 pub mod light_properties;
 pub use light_properties::*;
+
+pub mod motion;
+pub use motion::*;
+
+pub mod noise;
+pub use noise::*;
+
+pub mod sampler;
+pub use sampler::*;
+
+pub mod sampling;
+
+pub mod transform;
+pub use transform::*;
@@ -0,0 +1,382 @@
+//! Movement of objects over time, used to render motion blur
+
+use super::transform::Rotation;
+use crate::{Float, Point, Vector};
+use beevee::{aabb::AABB, ray::Ray};
+use nalgebra::{Similarity3, Translation3, Unit, UnitQuaternion};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// The largest angle by which an object can rotate between the instants used to bound its motion.
+const MAX_ANGLE_STEP: Float = crate::consts::PI / 16.;
+
+/// The placement of a moving object at a given instant, relative to where it is put in the scene.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Keyframe {
+    /// The instant of the keyframe, in the same unit as the camera's shutter.
+    pub time: Float,
+    /// The offset from the object's position.
+    #[serde(default = "Vector::zeros")]
+    pub translate: Vector,
+    /// The rotation around the motion's pivot, if any.
+    #[serde(default)]
+    pub rotate: Option<Rotation>,
+    /// The uniform scaling around the motion's pivot.
+    #[serde(default = "crate::serialize::default_identity")]
+    pub scale: Float,
+}
+
+impl Keyframe {
+    /// Creates a new `Keyframe`, moving the object by `translate` at `time`.
+    pub fn new(time: Float, translate: Vector) -> Self {
+        Keyframe {
+            time,
+            translate,
+            rotate: None,
+            scale: 1.,
+        }
+    }
+
+    /// Rotate the object around the motion's pivot.
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotate = Some(rotation);
+        self
+    }
+
+    /// Scale the object around the motion's pivot.
+    pub fn with_scale(mut self, scale: Float) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    fn rotation(&self) -> UnitQuaternion<Float> {
+        self.rotate
+            .as_ref()
+            .map_or_else(UnitQuaternion::identity, Rotation::quaternion)
+    }
+}
+
+/// The movement of an object over time, interpolated between keyframes.
+///
+/// Before its first keyframe, and after its last one, the object stays still.
+///
+/// In a scene file, it is written as:
+///
+/// ```yaml
+/// pivot: [0.0, 1.0, 0.0]
+/// keyframes:
+///   - time: 0.0
+///   - time: 1.0
+///     translate: [0.5, 0.0, 0.0]
+///     rotate: {euler: [0.0, 45.0, 0.0]}
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Motion {
+    /// The point around which the object is rotated and scaled.
+    pivot: Point,
+    /// The keyframes, sorted by time.
+    keyframes: Vec<Keyframe>,
+}
+
+impl Motion {
+    /// Creates a new `Motion` going through each keyframe, which do not need to be sorted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Keyframe, Motion};
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let motion = Motion::new(
+    ///     Point::origin(),
+    ///     vec![
+    ///         Keyframe::new(1.0, Vector::new(2.0, 0.0, 0.0)),
+    ///         Keyframe::new(0.0, Vector::zeros()),
+    ///     ],
+    /// );
+    /// let point = motion.at(0.25) * Point::new(0.0, 1.0, 0.0);
+    /// assert_eq!(point, Point::new(0.5, 1.0, 0.0));
+    /// ```
+    pub fn new(pivot: Point, mut keyframes: Vec<Keyframe>) -> Self {
+        keyframes.sort_by(|lhs, rhs| lhs.time.total_cmp(&rhs.time));
+        Motion { pivot, keyframes }
+    }
+
+    /// Get the transformation moving the object from where it is put in the scene to where it is
+    /// at `time`.
+    pub fn at(&self, time: Float) -> Similarity3<Float> {
+        let (first, last) = match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Similarity3::identity(),
+        };
+        let (translate, rotation, scale) = if time <= first.time {
+            (first.translate, first.rotation(), first.scale)
+        } else if time >= last.time {
+            (last.translate, last.rotation(), last.scale)
+        } else {
+            // There is at least two keyframes when strictly between the first and last
+            let next = self
+                .keyframes
+                .iter()
+                .position(|key| key.time > time)
+                .unwrap();
+            let (from, to) = (&self.keyframes[next - 1], &self.keyframes[next]);
+            let t = (time - from.time) / (to.time - from.time);
+            let (start, end) = (from.rotation(), to.rotation());
+            (
+                from.translate.lerp(&to.translate, t),
+                start.try_slerp(&end, t, 1e-6).unwrap_or(start),
+                from.scale + (to.scale - from.scale) * t,
+            )
+        };
+        // Rotate and scale around the pivot before moving it
+        let pivot = self.pivot.coords;
+        let offset = pivot + translate - (rotation * pivot) * scale;
+        Similarity3::from_parts(Translation3::from(offset), rotation, scale)
+    }
+
+    /// Get the ray which hits the object, as it is put in the scene, where `ray` hits it at the
+    /// ray's time. Distances along the returned ray must be multiplied by the returned factor to
+    /// get distances along `ray`.
+    pub fn ray_to_rest(&self, ray: &Ray) -> (Ray, Float) {
+        let motion = self.at(ray.time);
+        let inverse = motion.inverse();
+        let origin = inverse * ray.origin;
+        let direction = Unit::new_normalize(inverse * ray.direction.into_inner());
+        (
+            Ray::new(origin, direction).with_time(ray.time),
+            motion.scaling(),
+        )
+    }
+
+    /// Get the normal of the moving object at `time`, from its normal at rest.
+    pub fn normal_at(&self, time: Float, normal: &Unit<Vector>) -> Unit<Vector> {
+        self.at(time).isometry.rotation * normal
+    }
+
+    /// Get the bounds covering every position of an object bounded by `aabb` when at rest.
+    pub fn aabb(&self, aabb: &AABB) -> AABB {
+        match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => self.aabb_between(aabb, first.time, last.time),
+            _ => *aabb,
+        }
+    }
+
+    /// Get the bounds covering every position between `start` and `end` of an object bounded by
+    /// `aabb` when at rest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use beevee::aabb::AABB;
+    /// # use pathtracer::core::{Keyframe, Motion};
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let motion = Motion::new(
+    ///     Point::origin(),
+    ///     vec![
+    ///         Keyframe::new(0.0, Vector::zeros()),
+    ///         Keyframe::new(10.0, Vector::new(10.0, 0.0, 0.0)),
+    ///     ],
+    /// );
+    /// let aabb = AABB::with_bounds(Point::origin(), Point::new(1.0, 1.0, 1.0));
+    /// assert_eq!(
+    ///     motion.aabb_between(&aabb, 2.0, 3.0),
+    ///     AABB::with_bounds(Point::new(2.0, 0.0, 0.0), Point::new(4.0, 1.0, 1.0)),
+    /// );
+    /// ```
+    pub fn aabb_between(&self, aabb: &AABB, start: Float, end: Float) -> AABB {
+        let corners: Vec<_> = (0..8)
+            .map(|i| {
+                let pick = |bit, axis: usize| {
+                    if i & bit == 0 {
+                        aabb.low[axis]
+                    } else {
+                        aabb.high[axis]
+                    }
+                };
+                Point::new(pick(1, 0), pick(2, 1), pick(4, 2))
+            })
+            .collect();
+        let reach = (corners.iter())
+            .map(|corner| (corner - self.pivot).norm())
+            .fold(0., Float::max);
+        let mut bounds = AABB::empty();
+        let mut cover = |time: Float| {
+            let motion = self.at(time);
+            for corner in &corners {
+                bounds.grow_mut(&(motion * corner));
+            }
+        };
+        cover(start);
+        cover(end);
+        let mut padding: Float = 0.;
+        for segment in self.keyframes.windows(2) {
+            let (from, to) = (&segment[0], &segment[1]);
+            if to.time <= start || from.time >= end {
+                continue;
+            }
+            // Corners move along arcs when rotating, sample them finely enough to bound those
+            let angle = from.rotation().angle_to(&to.rotation());
+            let steps = (angle / MAX_ANGLE_STEP).ceil().max(1.);
+            for step in 1..steps as u32 {
+                let time = from.time + (to.time - from.time) * step as Float / steps;
+                if start < time && time < end {
+                    cover(time);
+                }
+            }
+            // Keyframes are where the object changes direction
+            if start < from.time {
+                cover(from.time);
+            }
+            let sagitta = 1. - (angle / steps / 2.).cos();
+            padding = padding.max(reach * from.scale.max(to.scale) * sagitta);
+        }
+        let padding = Vector::repeat(padding);
+        AABB::with_bounds(bounds.low - padding, bounds.high + padding)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SerializedMotion {
+    #[serde(default = "Point::origin")]
+    pivot: Point,
+    keyframes: Vec<Keyframe>,
+}
+
+impl From<SerializedMotion> for Motion {
+    fn from(motion: SerializedMotion) -> Self {
+        Motion::new(motion.pivot, motion.keyframes)
+    }
+}
+
+impl<'de> Deserialize<'de> for Motion {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let motion: SerializedMotion = Deserialize::deserialize(deserializer)?;
+        Ok(motion.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(lhs: Point, rhs: Point) {
+        assert!((lhs - rhs).norm() < 1e-5, "{} != {}", lhs, rhs)
+    }
+
+    fn spinning() -> Motion {
+        Motion::new(
+            Point::new(1., 0., 0.),
+            vec![
+                Keyframe::new(0., Vector::zeros()),
+                Keyframe::new(1., Vector::new(0., 2., 0.))
+                    .with_rotation(Rotation::Euler([0., 0., 90.]))
+                    .with_scale(3.),
+            ],
+        )
+    }
+
+    #[test]
+    fn new_sorts_keyframes() {
+        let motion = Motion::new(
+            Point::origin(),
+            vec![
+                Keyframe::new(1., Vector::x()),
+                Keyframe::new(0., Vector::zeros()),
+            ],
+        );
+        assert_eq!(
+            motion,
+            Motion {
+                pivot: Point::origin(),
+                keyframes: vec![
+                    Keyframe::new(0., Vector::zeros()),
+                    Keyframe::new(1., Vector::x()),
+                ],
+            }
+        )
+    }
+
+    #[test]
+    fn at_works() {
+        let motion = spinning();
+        let point = Point::new(2., 0., 0.);
+        assert_close(motion.at(0.) * point, point);
+        // Rotated and scaled around the pivot, then moved
+        assert_close(motion.at(1.) * point, Point::new(1., 5., 0.));
+        // Still outside of the keyframes
+        assert_close(motion.at(-1.) * point, point);
+        assert_close(motion.at(2.) * point, Point::new(1., 5., 0.));
+        // Halfway through the rotation
+        let half = crate::consts::FRAC_1_SQRT_2 * 2.;
+        assert_close(motion.at(0.5) * point, Point::new(1. + half, 1. + half, 0.));
+    }
+
+    #[test]
+    fn ray_to_rest_works() {
+        let motion = spinning();
+        let ray = Ray::new(Point::new(1., 10., 0.), -Vector::y_axis()).with_time(1.);
+        let (rest, scale) = motion.ray_to_rest(&ray);
+        assert_eq!(scale, 3.);
+        assert_eq!(rest.time, 1.);
+        // The point hit at rest is where the moving one is hit
+        let distance = 5.;
+        let moving = ray.origin + ray.direction.as_ref() * distance;
+        let at_rest = rest.origin + rest.direction.as_ref() * (distance / scale);
+        assert_close(motion.at(1.) * at_rest, moving);
+    }
+
+    #[test]
+    fn aabb_covers_motion() {
+        let motion = spinning();
+        let aabb = AABB::with_bounds(Point::new(1.5, -0.5, -0.5), Point::new(2.5, 0.5, 0.5));
+        let bounds = motion.aabb(&aabb);
+        for i in 0..=100 {
+            let moved = motion.at(i as Float / 100.);
+            for point in &[aabb.low, aabb.high, Point::new(2.5, 0., 0.)] {
+                assert!(bounds.contains(&(moved * point)));
+            }
+        }
+    }
+
+    #[test]
+    fn aabb_between_covers_keyframes() {
+        let motion = Motion::new(
+            Point::origin(),
+            vec![
+                Keyframe::new(0., Vector::zeros()),
+                Keyframe::new(1., Vector::new(5., 0., 0.)),
+                Keyframe::new(2., Vector::zeros()),
+            ],
+        );
+        let aabb = AABB::with_bounds(Point::origin(), Point::new(1., 1., 1.));
+        let whole = AABB::with_bounds(Point::origin(), Point::new(6., 1., 1.));
+        assert_eq!(motion.aabb(&aabb), whole);
+        assert_eq!(motion.aabb_between(&aabb, 0.5, 1.5), aabb_at(2.5, 6.));
+        assert_eq!(motion.aabb_between(&aabb, 1.5, 2.), aabb_at(0., 3.5));
+        // The object stays still after its last keyframe
+        assert_eq!(motion.aabb_between(&aabb, 3., 4.), aabb);
+    }
+
+    fn aabb_at(low: Float, high: Float) -> AABB {
+        AABB::with_bounds(Point::new(low, 0., 0.), Point::new(high, 1., 1.))
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            pivot: [1.0, 0.0, 0.0]
+            keyframes:
+              - time: 1.0
+                translate: [0.0, 2.0, 0.0]
+                rotate: {euler: [0.0, 0.0, 90.0]}
+                scale: 3.0
+              - time: 0.0
+        "#;
+        let motion: Motion = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(motion, spinning())
+    }
+}
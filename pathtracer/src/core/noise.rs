@@ -0,0 +1,402 @@
+//! Seeded procedural noise
+
+use crate::{Float, Point, Vector};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// The approximate average of the absolute value of [`Perlin::noise`], used to fade the octaves
+/// of turbulence which are too fine to be seen.
+///
+/// [`Perlin::noise`]: struct.Perlin.html#method.noise
+const TURBULENCE_AVERAGE: Float = 0.2;
+
+/// The approximate average of [`Worley::distance`], used to fade the octaves which are too fine
+/// to be seen.
+///
+/// [`Worley::distance`]: struct.Worley.html#method.distance
+const WORLEY_AVERAGE: Float = 0.5;
+
+/// A seeded 3D gradient noise, following Ken Perlin's improved noise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Perlin {
+    /// The shuffled lattice hashes, repeated twice to avoid wrapping the indices.
+    permutation: Vec<usize>,
+}
+
+impl Perlin {
+    /// Creates a new `Perlin` noise, whose lattice is shuffled using the given seed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Perlin;
+    /// # use pathtracer::Point;
+    /// #
+    /// let noise = Perlin::new(42);
+    /// let value = noise.noise(&Point::new(0.5, 1.25, 2.0));
+    /// assert!((-1.0..=1.0).contains(&value));
+    /// ```
+    pub fn new(seed: u64) -> Self {
+        let permutation = shuffled_lattice(&mut StdRng::seed_from_u64(seed));
+        Perlin { permutation }
+    }
+
+    /// Return the value of the noise at the given point, between -1 and 1.
+    ///
+    /// The noise is zero on every point of the integer lattice.
+    pub fn noise(&self, point: &Point) -> Float {
+        let floor = point.coords.map(Float::floor);
+        let cell = floor.map(|c| (c as i32 & 255) as usize);
+        let (x, y, z) = (point.x - floor.x, point.y - floor.y, point.z - floor.z);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.permutation;
+        let a = p[cell.x] + cell.y;
+        let aa = p[a] + cell.z;
+        let ab = p[a + 1] + cell.z;
+        let b = p[cell.x + 1] + cell.y;
+        let ba = p[b] + cell.z;
+        let bb = p[b + 1] + cell.z;
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1., y, z)),
+                lerp(u, grad(p[ab], x, y - 1., z), grad(p[bb], x - 1., y - 1., z)),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(p[aa + 1], x, y, z - 1.),
+                    grad(p[ba + 1], x - 1., y, z - 1.),
+                ),
+                lerp(
+                    u,
+                    grad(p[ab + 1], x, y - 1., z - 1.),
+                    grad(p[bb + 1], x - 1., y - 1., z - 1.),
+                ),
+            ),
+        )
+    }
+
+    /// Return the sum of `octaves` layers of noise, each one with twice the frequency and half
+    /// the amplitude of the previous one, normalized to stay between -1 and 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Perlin;
+    /// # use pathtracer::Point;
+    /// #
+    /// let noise = Perlin::new(42);
+    /// let point = Point::new(0.5, 1.25, 2.0);
+    /// assert_eq!(noise.fractal(&point, 1), noise.noise(&point));
+    /// ```
+    pub fn fractal(&self, point: &Point, octaves: u32) -> Float {
+        self.filtered_fractal(point, octaves, 0.)
+    }
+
+    /// Return the same sum as [`fractal`], but band-limited for a footprint `footprint` wide in
+    /// noise space: octaves whose features are smaller than the footprint fade out to their
+    /// average of zero, instead of aliasing.
+    ///
+    /// [`fractal`]: #method.fractal
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Perlin;
+    /// # use pathtracer::Point;
+    /// #
+    /// let noise = Perlin::new(42);
+    /// let point = Point::new(0.5, 1.25, 2.0);
+    /// assert_eq!(noise.filtered_fractal(&point, 4, 0.0), noise.fractal(&point, 4));
+    /// // Even the first octave is too fine to be seen
+    /// assert_eq!(noise.filtered_fractal(&point, 4, 2.0), 0.0);
+    /// ```
+    pub fn filtered_fractal(&self, point: &Point, octaves: u32, footprint: Float) -> Float {
+        sum_octaves(point, octaves, footprint, 0., |p| self.noise(p))
+    }
+
+    /// Return the sum of `octaves` layers of the absolute value of the noise, as for
+    /// [`filtered_fractal`], which gives billowing features between 0 and 1 e.g: for clouds or
+    /// marble veins.
+    ///
+    /// [`filtered_fractal`]: #method.filtered_fractal
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Perlin;
+    /// # use pathtracer::Point;
+    /// #
+    /// let noise = Perlin::new(42);
+    /// let point = Point::new(0.5, 1.25, 2.0);
+    /// assert_eq!(noise.turbulence(&point, 1, 0.0), noise.noise(&point).abs());
+    /// ```
+    pub fn turbulence(&self, point: &Point, octaves: u32, footprint: Float) -> Float {
+        sum_octaves(point, octaves, footprint, TURBULENCE_AVERAGE, |p| {
+            self.noise(p).abs()
+        })
+    }
+}
+
+/// A seeded 3D cellular noise, following Steven Worley's, with one feature point in each cell of
+/// the integer lattice.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Worley {
+    /// The shuffled lattice hashes, repeated twice to avoid wrapping the indices.
+    permutation: Vec<usize>,
+    /// The position of the feature point inside each cell, indexed by the cell's hash.
+    features: Vec<Vector>,
+}
+
+impl Worley {
+    /// Creates a new `Worley` noise, whose feature points are placed using the given seed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Worley;
+    /// # use pathtracer::Point;
+    /// #
+    /// let noise = Worley::new(42);
+    /// let value = noise.distance(&Point::new(0.5, 1.25, 2.0));
+    /// assert!((0.0..=1.0).contains(&value));
+    /// ```
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let permutation = shuffled_lattice(&mut rng);
+        let features = (0..256)
+            .map(|_| Vector::new(rng.gen(), rng.gen(), rng.gen()))
+            .collect();
+        Worley {
+            permutation,
+            features,
+        }
+    }
+
+    /// Return the distance from the given point to the nearest feature point, clamped to 1.
+    pub fn distance(&self, point: &Point) -> Float {
+        let floor = point.coords.map(Float::floor);
+        let p = &self.permutation;
+        let mut nearest = Float::INFINITY;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let offset = Vector::new(dx as Float, dy as Float, dz as Float);
+                    let cell = (floor + offset).map(|c| (c as i32 & 255) as usize);
+                    let hash = p[p[p[cell.x] + cell.y] + cell.z];
+                    let feature = floor + offset + self.features[hash];
+                    nearest = nearest.min((feature - point.coords).norm_squared());
+                }
+            }
+        }
+        nearest.sqrt().min(1.)
+    }
+
+    /// Return the sum of `octaves` layers of distances, each one with twice the frequency and
+    /// half the amplitude of the previous one, normalized to stay between 0 and 1. Octaves with
+    /// features smaller than the footprint fade out to their average value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Worley;
+    /// # use pathtracer::Point;
+    /// #
+    /// let noise = Worley::new(42);
+    /// let point = Point::new(0.5, 1.25, 2.0);
+    /// assert_eq!(noise.filtered_fractal(&point, 1, 0.0), noise.distance(&point));
+    /// ```
+    pub fn filtered_fractal(&self, point: &Point, octaves: u32, footprint: Float) -> Float {
+        sum_octaves(point, octaves, footprint, WORLEY_AVERAGE, |p| {
+            self.distance(p)
+        })
+    }
+}
+
+/// Shuffle the hashes of the lattice cells, repeated twice to avoid wrapping the indices.
+fn shuffled_lattice(rng: &mut StdRng) -> Vec<usize> {
+    let mut permutation: Vec<usize> = (0..256).collect();
+    permutation.shuffle(rng);
+    permutation
+        .iter()
+        .chain(permutation.iter())
+        .cloned()
+        .collect()
+}
+
+/// Return the sum of `octaves` layers of `noise`, each one with twice the frequency and half the
+/// amplitude of the previous one, normalized by the sum of amplitudes.
+///
+/// Layers with features smaller than `footprint` fade out to their `average`, instead of
+/// aliasing.
+fn sum_octaves<F>(point: &Point, octaves: u32, footprint: Float, average: Float, noise: F) -> Float
+where
+    F: Fn(&Point) -> Float,
+{
+    let mut total = 0.;
+    let mut amplitude = 1.;
+    let mut frequency = 1.;
+    let mut max_value = 0.;
+    for _ in 0..octaves {
+        // Fully keep features at least twice as large as the footprint, drop smaller ones
+        let weight = (2. - 2. * footprint * frequency).clamp(0., 1.);
+        // A NaN footprint drops the layer
+        let weight = if weight.is_nan() { 0. } else { weight };
+        let value = if weight > 0. {
+            noise(&(point * frequency)) * weight + average * (1. - weight)
+        } else {
+            average
+        };
+        total += value * amplitude;
+        max_value += amplitude;
+        amplitude /= 2.;
+        frequency *= 2.;
+    }
+    if max_value > 0. {
+        total / max_value
+    } else {
+        0.
+    }
+}
+
+fn fade(t: Float) -> Float {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: Float, a: Float, b: Float) -> Float {
+    a + t * (b - a)
+}
+
+/// Return the dot product of the offset with one of 12 gradient directions chosen by the hash.
+fn grad(hash: usize, x: Float, y: Float, z: Float) -> Float {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    let u = if h & 1 == 0 { u } else { -u };
+    let v = if h & 2 == 0 { v } else { -v };
+    u + v
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample_points() -> impl Iterator<Item = Point> {
+        (0..1000).map(|i| {
+            let i = i as Float;
+            Point::new(i * 0.173, i * -0.291 + 3., i * 0.057 - 20.)
+        })
+    }
+
+    #[test]
+    fn noise_is_zero_on_lattice() {
+        let noise = Perlin::new(0);
+        assert_eq!(noise.noise(&Point::new(1., 2., 3.)), 0.);
+        assert_eq!(noise.noise(&Point::new(-7., 0., 300.)), 0.);
+    }
+
+    #[test]
+    fn noise_is_bounded() {
+        let noise = Perlin::new(1);
+        assert!(sample_points()
+            .map(|p| noise.noise(&p))
+            .all(|v| (-1. ..=1.).contains(&v)))
+    }
+
+    #[test]
+    fn noise_is_deterministic() {
+        let lhs = Perlin::new(2);
+        let rhs = Perlin::new(2);
+        assert!(sample_points().all(|p| lhs.noise(&p) == rhs.noise(&p)))
+    }
+
+    #[test]
+    fn seeds_give_different_noises() {
+        let lhs = Perlin::new(3);
+        let rhs = Perlin::new(4);
+        assert!(sample_points().any(|p| lhs.noise(&p) != rhs.noise(&p)))
+    }
+
+    #[test]
+    fn fractal_is_bounded() {
+        let noise = Perlin::new(5);
+        assert!(sample_points()
+            .map(|p| noise.fractal(&p, 4))
+            .all(|v| (-1. ..=1.).contains(&v)))
+    }
+
+    #[test]
+    fn filtered_fractal_fades_octaves() {
+        let noise = Perlin::new(6);
+        assert!(sample_points().all(|p| noise.filtered_fractal(&p, 4, 0.) == noise.fractal(&p, 4)));
+        // Only the first octave remains, as the others are finer than the footprint
+        assert!(sample_points().all(|p| {
+            let filtered = noise.filtered_fractal(&p, 4, 0.5);
+            (filtered - noise.noise(&p) / 1.875).abs() < 1e-6
+        }));
+        assert!(sample_points().all(|p| noise.filtered_fractal(&p, 4, 1.) == 0.))
+    }
+
+    #[test]
+    fn turbulence_is_bounded() {
+        let noise = Perlin::new(7);
+        assert!(sample_points()
+            .map(|p| noise.turbulence(&p, 4, 0.))
+            .all(|v| (0. ..=1.).contains(&v)))
+    }
+
+    #[test]
+    fn turbulence_average_is_close() {
+        let noise = Perlin::new(8);
+        let average = sample_points()
+            .map(|p| noise.noise(&p).abs())
+            .sum::<Float>()
+            / 1000.;
+        assert!((average - TURBULENCE_AVERAGE).abs() < 0.05, "{}", average);
+        // Octaves which are too fine fade out to the average
+        assert!(sample_points()
+            .all(|p| { (noise.turbulence(&p, 4, 10.) - TURBULENCE_AVERAGE).abs() < 1e-6 }));
+    }
+
+    #[test]
+    fn worley_is_zero_on_features() {
+        let noise = Worley::new(9);
+        let cell = noise.permutation[noise.permutation[noise.permutation[1] + 2] + 3];
+        let feature = Point::new(1., 2., 3.) + noise.features[cell];
+        assert_eq!(noise.distance(&feature), 0.);
+    }
+
+    #[test]
+    fn worley_is_bounded() {
+        let noise = Worley::new(10);
+        assert!(sample_points()
+            .map(|p| noise.filtered_fractal(&p, 4, 0.))
+            .all(|v| (0. ..=1.).contains(&v)))
+    }
+
+    #[test]
+    fn worley_average_is_close() {
+        let noise = Worley::new(11);
+        let average = sample_points().map(|p| noise.distance(&p)).sum::<Float>() / 1000.;
+        assert!((average - WORLEY_AVERAGE).abs() < 0.05, "{}", average);
+    }
+
+    #[test]
+    fn worley_is_deterministic() {
+        let lhs = Worley::new(12);
+        let rhs = Worley::new(12);
+        assert!(sample_points().all(|p| lhs.distance(&p) == rhs.distance(&p)))
+    }
+}
@@ -0,0 +1,267 @@
+//! Sample patterns over the unit square
+
+use crate::Float;
+use rand::{seq::SliceRandom, Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// How the two-dimensional samples of a domain, such as a pixel or the surface of a light, are
+/// spread over it.
+#[serde(rename_all = "lowercase")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Sampler {
+    /// Independent random points for the pixels, and a [`stratified`] pattern for the lights.
+    ///
+    /// [`stratified`]: fn.stratified.html
+    #[default]
+    Random,
+    /// Correlated multi-jittered points for every domain, see [`cmj`].
+    ///
+    /// [`cmj`]: fn.cmj.html
+    Cmj,
+}
+
+impl Sampler {
+    /// Generate `count` points in `[0, 1)²` to anti-alias a pixel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::Sampler;
+    /// # use rand::rngs::StdRng;
+    /// # use rand::SeedableRng;
+    /// #
+    /// let mut rng = StdRng::seed_from_u64(42);
+    /// let points = Sampler::Cmj.pixel_samples(16, &mut rng);
+    /// // Each quarter of the pixel gets its share of the samples
+    /// let left = points.iter().filter(|&&(x, _)| x < 0.5).count();
+    /// assert_eq!(left, 8);
+    /// ```
+    pub fn pixel_samples(self, count: u32, rng: &mut dyn RngCore) -> Vec<(Float, Float)> {
+        match self {
+            Sampler::Random => (0..count).map(|_| (rng.gen(), rng.gen())).collect(),
+            Sampler::Cmj => cmj(count, rng.gen()),
+        }
+    }
+
+    /// Generate `count` points in `[0, 1)²` spread evenly over the surface of a light.
+    pub fn light_samples(self, count: u32, rng: &mut dyn RngCore) -> Vec<(Float, Float)> {
+        match self {
+            Sampler::Random => stratified(count, rng),
+            Sampler::Cmj => cmj(count, rng.gen()),
+        }
+    }
+}
+
+/// Generate `count` points in `[0, 1)²`, each of them uniformly distributed, but covering the
+/// square more evenly than independent random points.
+///
+/// A jittered grid is used when `count` is a perfect square, otherwise each point is put in its
+/// own row and column (N-rooks sampling).
+pub fn stratified(count: u32, rng: &mut dyn RngCore) -> Vec<(Float, Float)> {
+    let side = (count as Float).sqrt().round() as u32;
+    if side * side == count {
+        let side_f = side as Float;
+        return (0..count)
+            .map(|i| {
+                let (x, y) = ((i % side) as Float, (i / side) as Float);
+                (
+                    (x + rng.gen::<Float>()) / side_f,
+                    (y + rng.gen::<Float>()) / side_f,
+                )
+            })
+            .collect();
+    }
+    let mut rows: Vec<_> = (0..count).collect();
+    rows.shuffle(rng);
+    let count_f = count as Float;
+    rows.into_iter()
+        .enumerate()
+        .map(|(column, row)| {
+            (
+                (column as Float + rng.gen::<Float>()) / count_f,
+                (row as Float + rng.gen::<Float>()) / count_f,
+            )
+        })
+        .collect()
+}
+
+/// Generate `count` correlated multi-jittered points in `[0, 1)²`, as described by Kensler in
+/// "Correlated Multi-Jittered Sampling".
+///
+/// The points are both on a jittered grid of about `√count` by `√count` cells, and each in its
+/// own row and column, even when `count` is not a perfect square. The `pattern` scrambles the
+/// points deterministically: different patterns give independent sets of points.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::core::cmj;
+/// # use pathtracer::Float;
+/// #
+/// let points = cmj(5, 42);
+/// assert_eq!(points.len(), 5);
+/// // Each point is in its own fifth of the square vertically
+/// for i in 0..5 {
+///     let row = |y: Float| (y * 5.0) as u32 == i;
+///     assert_eq!(points.iter().filter(|&&(_, y)| row(y)).count(), 1);
+/// }
+/// assert_eq!(points, cmj(5, 42));
+/// ```
+pub fn cmj(count: u32, pattern: u32) -> Vec<(Float, Float)> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let columns = ((count as Float).sqrt() as u32).max(1);
+    let rows = count.div_ceil(columns);
+    (0..count)
+        .map(|index| {
+            let s = permute(index, count, pattern.wrapping_mul(0x51633e2d));
+            let column = permute(s % columns, columns, pattern.wrapping_mul(0x68bc21eb));
+            let row = permute(s / columns, rows, pattern.wrapping_mul(0x02e5be93));
+            let jitter_x = random_float(s, pattern.wrapping_mul(0x967a889b));
+            let jitter_y = random_float(s, pattern.wrapping_mul(0x368cc8b7));
+            let x =
+                (column as Float + (row as Float + jitter_x) / rows as Float) / columns as Float;
+            let y = (s as Float + jitter_y) / count as Float;
+            // Keep the rounding from reaching the end of the range
+            (x.min(ONE_BELOW), y.min(ONE_BELOW))
+        })
+        .collect()
+}
+
+/// The largest float below one.
+const ONE_BELOW: Float = 1. - Float::EPSILON / 2.;
+
+/// Permute `index` among `[0, length)`, a different permutation being used for each `pattern`.
+fn permute(mut index: u32, length: u32, pattern: u32) -> u32 {
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    // Cycle walk the permutation of the next power of two until landing inside the range
+    loop {
+        index ^= pattern;
+        index = index.wrapping_mul(0xe170893d);
+        index ^= pattern >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= pattern >> 8;
+        index = index.wrapping_mul(0x0929eb3f);
+        index ^= pattern >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | pattern >> 27);
+        index = index.wrapping_mul(0x6935fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dcb303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e501cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860a3df);
+        index &= mask;
+        index ^= index >> 5;
+        if index < length {
+            break;
+        }
+    }
+    (index + pattern) % length
+}
+
+/// Hash `index` to a float in `[0, 1)`, a different hash being used for each `pattern`.
+fn random_float(mut index: u32, pattern: u32) -> Float {
+    index ^= pattern;
+    index ^= index >> 17;
+    index ^= index >> 10;
+    index = index.wrapping_mul(0xb36534e5);
+    index ^= index >> 12;
+    index ^= index >> 21;
+    index = index.wrapping_mul(0x93fc4795);
+    index ^= 0xdf6e307f;
+    index ^= index >> 17;
+    index = index.wrapping_mul(1 | pattern >> 18);
+    (index >> 8) as Float / (1 << 24) as Float
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn in_unit_square(points: &[(Float, Float)]) -> bool {
+        (points.iter()).all(|&(s, t)| (0. ..1.).contains(&s) && (0. ..1.).contains(&t))
+    }
+
+    #[test]
+    fn stratified_covers_every_stratum() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for &count in &[1, 4, 9, 5] {
+            let points = stratified(count, &mut rng);
+            assert_eq!(points.len(), count as usize);
+            assert!(in_unit_square(&points));
+        }
+        // Each of the 5 rows and columns contain exactly one point
+        let points = stratified(5, &mut rng);
+        for i in 0..5 {
+            let stratum = |x: Float| (x * 5.) as usize == i;
+            assert_eq!(points.iter().filter(|(s, _)| stratum(*s)).count(), 1);
+            assert_eq!(points.iter().filter(|(_, t)| stratum(*t)).count(), 1);
+        }
+    }
+
+    #[test]
+    fn cmj_is_multi_jittered() {
+        for pattern in 0..16 {
+            let points = cmj(16, pattern);
+            assert!(in_unit_square(&points));
+            for i in 0..16 {
+                // Each of the 16 rows and columns contain exactly one point
+                let stratum = |x: Float| (x * 16.) as u32 == i;
+                assert_eq!(points.iter().filter(|(s, _)| stratum(*s)).count(), 1);
+                assert_eq!(points.iter().filter(|(_, t)| stratum(*t)).count(), 1);
+                // As does each cell of the 4 by 4 grid
+                let cell = |(s, t): (Float, Float)| (s * 4.) as u32 + 4 * (t * 4.) as u32 == i;
+                assert_eq!(points.iter().filter(|&&point| cell(point)).count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn cmj_handles_any_count() {
+        for count in 0..40 {
+            let points = cmj(count, 7);
+            assert_eq!(points.len(), count as usize);
+            assert!(in_unit_square(&points));
+        }
+    }
+
+    #[test]
+    fn cmj_patterns_are_independent() {
+        assert_eq!(cmj(8, 1), cmj(8, 1));
+        assert_ne!(cmj(8, 1), cmj(8, 2));
+        // The points are uniformly distributed over the patterns
+        let points: Vec<_> = (0..1000).flat_map(|pattern| cmj(3, pattern)).collect();
+        let mean = |coordinate: fn(&(Float, Float)) -> Float| {
+            points.iter().map(coordinate).sum::<Float>() / points.len() as Float
+        };
+        assert!((mean(|p| p.0) - 0.5).abs() < 0.02);
+        assert!((mean(|p| p.1) - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn samplers_give_the_count() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for &sampler in &[Sampler::Random, Sampler::Cmj] {
+            assert_eq!(sampler.pixel_samples(7, &mut rng).len(), 7);
+            assert_eq!(sampler.light_samples(7, &mut rng).len(), 7);
+        }
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let sampler: Sampler = serde_yaml::from_str("cmj").unwrap();
+        assert_eq!(sampler, Sampler::Cmj);
+        let sampler: Sampler = serde_yaml::from_str("random").unwrap();
+        assert_eq!(sampler, Sampler::default());
+    }
+}
@@ -0,0 +1,310 @@
+//! Warping of uniform random numbers into directions and points, along with their probability
+//! densities.
+//!
+//! Directions are sampled in a local space around the Z axis, and brought into the scene with
+//! [`to_world`].
+//!
+//! [`to_world`]: fn.to_world.html
+
+use crate::consts::PI;
+use crate::{Float, Vector};
+use nalgebra::Unit;
+
+/// Build two unit vectors orthogonal to `axis` and to each other, completing it into a basis.
+pub fn orthonormal_basis(axis: &Unit<Vector>) -> (Vector, Vector) {
+    // Avoid a nearly parallel helper axis
+    let helper = if axis.x.abs() > 0.9 {
+        Vector::y()
+    } else {
+        Vector::x()
+    };
+    let tangent = axis.cross(&helper).normalize();
+    let bitangent = axis.cross(&tangent);
+    (tangent, bitangent)
+}
+
+/// Bring a direction sampled around the Z axis into the space around `axis`.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::core::sampling::{cosine_hemisphere, to_world};
+/// # use pathtracer::Vector;
+/// #
+/// let normal = Vector::y_axis();
+/// let direction = to_world(&normal, &cosine_hemisphere(0.3, 0.6));
+/// assert!(direction.dot(&normal) > 0.0);
+/// ```
+pub fn to_world(axis: &Unit<Vector>, local: &Vector) -> Unit<Vector> {
+    let (tangent, bitangent) = orthonormal_basis(axis);
+    Unit::new_normalize(tangent * local.x + bitangent * local.y + axis.as_ref() * local.z)
+}
+
+/// A local direction from its cosine with the Z axis and its azimuth.
+pub fn spherical(cos: Float, phi: Float) -> Vector {
+    let sin = (1. - cos * cos).max(0.).sqrt();
+    Vector::new(sin * phi.cos(), sin * phi.sin(), cos)
+}
+
+/// Sample a direction uniformly over the whole sphere.
+pub fn uniform_sphere(u: Float, v: Float) -> Vector {
+    spherical(1. - 2. * u, 2. * PI * v)
+}
+
+/// The density of [`uniform_sphere`] over solid angles.
+///
+/// [`uniform_sphere`]: fn.uniform_sphere.html
+pub fn uniform_sphere_pdf() -> Float {
+    1. / (4. * PI)
+}
+
+/// Sample a direction uniformly over the hemisphere around the Z axis.
+pub fn uniform_hemisphere(u: Float, v: Float) -> Vector {
+    spherical(1. - u, 2. * PI * v)
+}
+
+/// The density of [`uniform_hemisphere`] over solid angles.
+///
+/// [`uniform_hemisphere`]: fn.uniform_hemisphere.html
+pub fn uniform_hemisphere_pdf() -> Float {
+    1. / (2. * PI)
+}
+
+/// Sample a direction over the hemisphere around the Z axis, proportionally to its cosine with
+/// the axis, e.g: for diffuse surfaces.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::core::sampling::{cosine_hemisphere, cosine_hemisphere_pdf};
+/// #
+/// let direction = cosine_hemisphere(0.0, 0.0);
+/// assert_eq!(direction.z, 1.0);
+/// assert_eq!(cosine_hemisphere_pdf(direction.z), pathtracer::consts::FRAC_1_PI);
+/// ```
+pub fn cosine_hemisphere(u: Float, v: Float) -> Vector {
+    let radius = u.sqrt();
+    let theta = 2. * PI * v;
+    let z = (1. - u).max(0.).sqrt();
+    Vector::new(radius * theta.cos(), radius * theta.sin(), z)
+}
+
+/// The density of [`cosine_hemisphere`] over solid angles, for a direction of cosine `cos` with
+/// the axis.
+///
+/// [`cosine_hemisphere`]: fn.cosine_hemisphere.html
+pub fn cosine_hemisphere_pdf(cos: Float) -> Float {
+    cos.max(0.) / PI
+}
+
+/// Sample a direction uniformly in the cone around the Z axis, given one minus the cosine of its
+/// half-angle, which keeps its precision for narrow cones.
+pub fn uniform_cone(one_minus_cos: Float, u: Float, v: Float) -> Vector {
+    spherical(1. - u * one_minus_cos, 2. * PI * v)
+}
+
+/// The density of [`uniform_cone`] over solid angles, the inverse of the cone's solid angle.
+///
+/// [`uniform_cone`]: fn.uniform_cone.html
+pub fn uniform_cone_pdf(one_minus_cos: Float) -> Float {
+    1. / (2. * PI * one_minus_cos)
+}
+
+/// The anisotropic GGX (Trowbridge-Reitz) normal distribution, for a half-vector in local space,
+/// with a roughness of `alpha_x` along the X axis and `alpha_y` along the Y axis.
+pub fn ggx_d(half: &Vector, alpha_x: Float, alpha_y: Float) -> Float {
+    let (x, y) = (half.x / alpha_x, half.y / alpha_y);
+    let t = x * x + y * y + half.z * half.z;
+    1. / (PI * alpha_x * alpha_y * t * t)
+}
+
+/// Sample a half-vector in local space, proportionally to `ggx_d(half) * half.z`.
+pub fn ggx_half_vector(alpha_x: Float, alpha_y: Float, u: Float, v: Float) -> Vector {
+    // Stretch the slopes of the distribution of unit roughness
+    let tan = (u / (1. - u).max(1e-7)).sqrt();
+    let phi = 2. * PI * v;
+    Vector::new(alpha_x * tan * phi.cos(), alpha_y * tan * phi.sin(), 1.).normalize()
+}
+
+/// The density of [`ggx_half_vector`] over solid angles.
+///
+/// [`ggx_half_vector`]: fn.ggx_half_vector.html
+pub fn ggx_half_vector_pdf(half: &Vector, alpha_x: Float, alpha_y: Float) -> Float {
+    ggx_d(half, alpha_x, alpha_y) * half.z.max(0.)
+}
+
+/// Sample a point uniformly on the unit disk, with Shirley and Chiu's concentric mapping which
+/// keeps the stratification of `(u, v)`.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::core::sampling::uniform_disk;
+/// #
+/// assert_eq!(uniform_disk(0.5, 0.5), (0.0, 0.0));
+/// let (x, y) = uniform_disk(1.0, 0.5);
+/// assert!((x - 1.0).abs() < 1e-6 && y.abs() < 1e-6);
+/// ```
+pub fn uniform_disk(u: Float, v: Float) -> (Float, Float) {
+    let (x, y) = (2. * u - 1., 2. * v - 1.);
+    if x == 0. && y == 0. {
+        return (0., 0.);
+    }
+    let (radius, theta) = if x.abs() > y.abs() {
+        (x, PI / 4. * (y / x))
+    } else {
+        (y, PI / 2. - PI / 4. * (x / y))
+    };
+    (radius * theta.cos(), radius * theta.sin())
+}
+
+/// The density of [`uniform_disk`] over the disk's area.
+///
+/// [`uniform_disk`]: fn.uniform_disk.html
+pub fn uniform_disk_pdf() -> Float {
+    1. / PI
+}
+
+/// Sample uniformly distributed barycentric coordinates `(s, t)` of a triangle, the point being
+/// `origin + s * u + t * v` for a triangle spanned by the edges `u` and `v`. The density over
+/// the triangle's area is the inverse of its area.
+pub fn uniform_triangle(u: Float, v: Float) -> (Float, Float) {
+    // Fold the far half of the parallelogram back onto the triangle
+    if u + v > 1. {
+        (1. - u, 1. - v)
+    } else {
+        (u, v)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A grid of `n` by `n` points in `[0, 1)²`.
+    fn grid(n: u32) -> impl Iterator<Item = (Float, Float)> {
+        (0..n).flat_map(move |i| {
+            (0..n).map(move |j| {
+                (
+                    (i as Float + 0.5) / n as Float,
+                    (j as Float + 0.5) / n as Float,
+                )
+            })
+        })
+    }
+
+    /// Estimate the integral of `f` over the directions sampled by `sample` with density `pdf`.
+    fn estimate(
+        sample: impl Fn(Float, Float) -> Vector,
+        pdf: impl Fn(&Vector) -> Float,
+        f: impl Fn(&Vector) -> Float,
+    ) -> Float {
+        let n = 200;
+        (grid(n))
+            .map(|(u, v)| {
+                let direction = sample(u, v);
+                f(&direction) / pdf(&direction)
+            })
+            .sum::<Float>()
+            / (n * n) as Float
+    }
+
+    #[test]
+    fn basis_is_orthonormal() {
+        for axis in &[Vector::x_axis(), Vector::y_axis(), -Vector::z_axis()] {
+            let (tangent, bitangent) = orthonormal_basis(axis);
+            assert!((tangent.norm() - 1.).abs() < 1e-6);
+            assert!((bitangent.norm() - 1.).abs() < 1e-6);
+            assert!(tangent.dot(axis).abs() < 1e-6);
+            assert!(bitangent.dot(axis).abs() < 1e-6);
+            assert!(tangent.dot(&bitangent).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn cosine_hemisphere_stays_above_surface() {
+        for normal in &[
+            Vector::x_axis(),
+            Vector::y_axis(),
+            Unit::new_normalize(Vector::new(1., 1., 1.)),
+        ] {
+            for (u, v) in grid(8) {
+                let direction = to_world(normal, &cosine_hemisphere(u, v));
+                assert!(direction.dot(normal) >= 0.);
+                assert!((direction.norm() - 1.).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn densities_are_normalized() {
+        let one = |_: &Vector| 1.;
+        let sphere = estimate(uniform_sphere, |_| uniform_sphere_pdf(), one);
+        assert!((sphere - 4. * PI).abs() < 1e-2);
+        let hemisphere = estimate(uniform_hemisphere, |_| uniform_hemisphere_pdf(), one);
+        assert!((hemisphere - 2. * PI).abs() < 1e-2);
+        // The cosine-weighted hemisphere integrates the cosine exactly
+        let cosine = estimate(cosine_hemisphere, |d| cosine_hemisphere_pdf(d.z), |d| d.z);
+        assert!((cosine - PI).abs() < 1e-3);
+        let cone = estimate(
+            |u, v| uniform_cone(0.1, u, v),
+            |_| uniform_cone_pdf(0.1),
+            one,
+        );
+        assert!((cone - 2. * PI * 0.1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn samples_have_the_expected_spread() {
+        for (u, v) in grid(16) {
+            assert!(uniform_hemisphere(u, v).z >= 0.);
+            assert!(uniform_cone(0.1, u, v).z >= 0.9 - 1e-6);
+            let (x, y) = uniform_disk(u, v);
+            assert!(x * x + y * y <= 1. + 1e-5);
+            let (s, t) = uniform_triangle(u, v);
+            assert!(s >= 0. && t >= 0. && s + t <= 1.);
+        }
+    }
+
+    #[test]
+    fn ggx_is_normalized() {
+        // Integrate `D * cos` with a midpoint rule over the cosine and azimuth
+        let integrate = |alpha_x, alpha_y| {
+            let (steps, azimuths) = (20_000, 64);
+            (0..azimuths)
+                .map(|j| {
+                    let phi = 2. * PI * (j as Float + 0.5) / azimuths as Float;
+                    (0..steps)
+                        .map(|i| {
+                            let cos = (i as Float + 0.5) / steps as Float;
+                            ggx_d(&spherical(cos, phi), alpha_x, alpha_y) * cos / steps as Float
+                        })
+                        .sum::<Float>()
+                        * 2.
+                        * PI
+                        / azimuths as Float
+                })
+                .sum::<Float>()
+        };
+        for &(alpha_x, alpha_y) in &[(0.1, 0.1), (0.5, 0.5), (1., 1.), (0.2, 0.6)] {
+            let total = integrate(alpha_x, alpha_y);
+            assert!((total - 1.).abs() < 1e-2, "{}", total);
+        }
+    }
+
+    #[test]
+    fn ggx_samples_match_pdf() {
+        for &(alpha_x, alpha_y) in &[(0.3, 0.3), (0.3, 0.1)] {
+            // Estimating the same integral with both strategies gives the same result
+            let pdf = |half: &Vector| ggx_half_vector_pdf(half, alpha_x, alpha_y);
+            let f = |half: &Vector| pdf(half) * half.z;
+            let total = estimate(|u, v| ggx_half_vector(alpha_x, alpha_y, u, v), pdf, f);
+            let reference = estimate(uniform_hemisphere, |_| uniform_hemisphere_pdf(), f);
+            assert!((total - reference).abs() < 0.05 * reference);
+            for (u, v) in grid(8) {
+                let half = ggx_half_vector(alpha_x, alpha_y, u, v);
+                assert!((half.norm() - 1.).abs() < 1e-5);
+            }
+        }
+    }
+}
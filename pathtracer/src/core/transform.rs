@@ -0,0 +1,206 @@
+//! Affine transformations described by a sequence of simple components
+
+use crate::{Float, Vector};
+use nalgebra::{Affine3, Matrix4, Quaternion, Translation3, UnitQuaternion};
+use serde::{Deserialize, Serialize};
+
+/// A rotation, either as Euler angles or as a quaternion.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    /// Angles in degrees around the X, Y and Z axes, applied in that order.
+    Euler([Float; 3]),
+    /// A quaternion given as `[x, y, z, w]`, like glTF. It does not need to be normalized.
+    Quaternion([Float; 4]),
+}
+
+impl From<UnitQuaternion<Float>> for Rotation {
+    fn from(rotation: UnitQuaternion<Float>) -> Self {
+        Rotation::Quaternion([rotation.i, rotation.j, rotation.k, rotation.w])
+    }
+}
+
+impl Rotation {
+    /// The unit quaternion corresponding to this rotation.
+    pub fn quaternion(&self) -> UnitQuaternion<Float> {
+        match *self {
+            Rotation::Euler([x, y, z]) => {
+                UnitQuaternion::from_euler_angles(x.to_radians(), y.to_radians(), z.to_radians())
+            }
+            Rotation::Quaternion([x, y, z, w]) => {
+                UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z))
+            }
+        }
+    }
+}
+
+/// A scaling, either uniform or along each axis.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Scale {
+    /// The same factor along all axes.
+    Uniform(Float),
+    /// A factor along each of the X, Y and Z axes.
+    Axes(Vector),
+}
+
+/// A component of a [`Transform`].
+///
+/// [`Transform`]: struct.Transform.html
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransformComponent {
+    /// Move by the given offset.
+    Translate(Vector),
+    /// Rotate around the origin.
+    Rotate(Rotation),
+    /// Scale around the origin.
+    Scale(Scale),
+}
+
+impl TransformComponent {
+    /// The matrix, in homogeneous coordinates, corresponding to this component.
+    pub fn matrix(&self) -> Matrix4<Float> {
+        match self {
+            TransformComponent::Translate(offset) => Translation3::from(*offset).to_homogeneous(),
+            TransformComponent::Rotate(rotation) => rotation.quaternion().to_homogeneous(),
+            TransformComponent::Scale(Scale::Uniform(factor)) => Matrix4::new_scaling(*factor),
+            TransformComponent::Scale(Scale::Axes(factors)) => {
+                Matrix4::new_nonuniform_scaling(factors)
+            }
+        }
+    }
+}
+
+/// An affine transformation, described as a list of components applied in order.
+///
+/// In a scene file, it is written as a list such as:
+///
+/// ```yaml
+/// - scale: 2.0
+/// - rotate: {euler: [0.0, 90.0, 0.0]}
+/// - translate: [1.0, 0.0, 0.0]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Transform {
+    components: Vec<TransformComponent>,
+}
+
+impl Transform {
+    /// Creates a new `Transform` applying each component in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Rotation, Transform, TransformComponent};
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let transform = Transform::new(vec![
+    ///     TransformComponent::Rotate(Rotation::Euler([0.0, 0.0, 90.0])),
+    ///     TransformComponent::Translate(Vector::new(0.0, 0.0, 1.0)),
+    /// ]);
+    /// let point = transform.affine() * Point::new(1.0, 0.0, 0.0);
+    /// assert!((point - Point::new(0.0, 1.0, 1.0)).norm() < 1e-6);
+    /// ```
+    pub fn new(components: Vec<TransformComponent>) -> Self {
+        Transform { components }
+    }
+
+    /// Add a component, applied after all the others.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Scale, Transform, TransformComponent};
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let transform = Transform::default()
+    ///     .then(TransformComponent::Scale(Scale::Uniform(2.0)))
+    ///     .then(TransformComponent::Translate(Vector::new(1.0, 0.0, 0.0)));
+    /// let point = transform.affine() * Point::new(1.0, 0.0, 0.0);
+    /// assert_eq!(point, Point::new(3.0, 0.0, 0.0));
+    /// ```
+    pub fn then(mut self, component: TransformComponent) -> Self {
+        self.components.push(component);
+        self
+    }
+
+    /// Whether the transform has no component at all.
+    pub fn is_identity(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    /// The affine transformation corresponding to all the components.
+    pub fn affine(&self) -> Affine3<Float> {
+        let matrix = (self.components.iter()).fold(Matrix4::identity(), |acc, component| {
+            component.matrix() * acc
+        });
+        Affine3::from_matrix_unchecked(matrix)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Point;
+
+    fn assert_close(lhs: Point, rhs: Point) {
+        assert!((lhs - rhs).norm() < 1e-5, "{} != {}", lhs, rhs)
+    }
+
+    #[test]
+    fn empty_is_identity() {
+        let transform = Transform::default();
+        assert!(transform.is_identity());
+        assert_eq!(transform.affine(), Affine3::identity());
+    }
+
+    #[test]
+    fn components_are_applied_in_order() {
+        let point = Point::new(1., 0., 0.);
+        let translate = TransformComponent::Translate(Vector::new(1., 0., 0.));
+        let scale = TransformComponent::Scale(Scale::Uniform(2.));
+        let transform = Transform::new(vec![translate.clone(), scale.clone()]);
+        assert_close(transform.affine() * point, Point::new(4., 0., 0.));
+        let transform = Transform::new(vec![scale, translate]);
+        assert_close(transform.affine() * point, Point::new(3., 0., 0.));
+    }
+
+    #[test]
+    fn euler_and_quaternion_agree() {
+        let half = crate::consts::FRAC_1_SQRT_2;
+        let euler = Rotation::Euler([0., 90., 0.]);
+        let quaternion = Rotation::Quaternion([0., half, 0., half]);
+        let point = Point::new(1., 0., 0.);
+        let rotated = euler.quaternion() * point;
+        assert_close(rotated, Point::new(0., 0., -1.));
+        assert_close(quaternion.quaternion() * point, rotated);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            - scale: [1.0, 2.0, 3.0]
+            - rotate: {euler: [90.0, 0.0, 0.0]}
+            - rotate: {quaternion: [0.0, 0.0, 0.0, 2.0]}
+            - translate: [0.0, 0.0, 1.0]
+            - scale: 0.5
+        "#;
+        let transform: Transform = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            transform,
+            Transform::new(vec![
+                TransformComponent::Scale(Scale::Axes(Vector::new(1., 2., 3.))),
+                TransformComponent::Rotate(Rotation::Euler([90., 0., 0.])),
+                TransformComponent::Rotate(Rotation::Quaternion([0., 0., 0., 2.])),
+                TransformComponent::Translate(Vector::new(0., 0., 1.)),
+                TransformComponent::Scale(Scale::Uniform(0.5)),
+            ])
+        );
+        assert_close(
+            transform.affine() * Point::new(0., 1., 0.),
+            Point::new(0., 0., 1.5),
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! The errors returned by the crate
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A `Result` whose errors are the crate's [`Error`].
+///
+/// [`Error`]: enum.Error.html
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Everything which can go wrong when loading, building, or saving scenes.
+///
+/// The errors happening while working on a file, e.g: a mesh or a texture used by the scene, keep
+/// the path of that file, which is shown when displaying them.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::mesh::load_mesh;
+/// # use pathtracer::Error;
+/// # use std::path::Path;
+/// #
+/// let err = load_mesh("nowhere.obj").unwrap_err();
+/// assert!(matches!(err, Error::Io { .. }));
+/// assert_eq!(err.path(), Some(Path::new("nowhere.obj")));
+/// assert!(err.to_string().starts_with("nowhere.obj: "));
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A file could not be read or written.
+    #[error("{}{source}", path_prefix(.path))]
+    Io {
+        /// The file, if known.
+        path: Option<PathBuf>,
+        /// What went wrong.
+        source: io::Error,
+    },
+    /// An image could not be decoded or encoded.
+    #[error("{}{source}", path_prefix(.path))]
+    Image {
+        /// The image's file, if known.
+        path: Option<PathBuf>,
+        /// What went wrong.
+        source: image::ImageError,
+    },
+    /// The content of a file, e.g: a mesh or an archive, does not have the expected format.
+    #[error("{}{}{message}", path_prefix(.path), line_prefix(.line))]
+    Parse {
+        /// The file, if known.
+        path: Option<PathBuf>,
+        /// The line at fault, for text formats.
+        line: Option<usize>,
+        /// What was expected.
+        message: String,
+    },
+    /// A scene description could not be read, or does not describe a scene.
+    #[error("{}{source}", path_prefix(.path))]
+    Scene {
+        /// The description's file, if known.
+        path: Option<PathBuf>,
+        /// What went wrong.
+        source: serde_yaml::Error,
+    },
+    /// A value given to the crate is outside of the values it can take.
+    #[error("{0}")]
+    InvalidParameter(String),
+    /// A scene refers by name to something it does not define, e.g: a material or an object.
+    #[error("unknown {kind} '{name}'")]
+    UnknownName {
+        /// What the name refers to, e.g: `"material"`.
+        kind: &'static str,
+        /// The name itself.
+        name: String,
+    },
+}
+
+impl Error {
+    /// Creates a new [`Error::Parse`], for the given line of a file.
+    ///
+    /// [`Error::Parse`]: enum.Error.html#variant.Parse
+    pub(crate) fn parse<S: Into<String>>(line: usize, message: S) -> Self {
+        Error::Parse {
+            path: None,
+            line: Some(line),
+            message: message.into(),
+        }
+    }
+
+    /// Creates a new [`Error::Parse`], for a whole file.
+    ///
+    /// [`Error::Parse`]: enum.Error.html#variant.Parse
+    pub(crate) fn invalid_data<S: Into<String>>(message: S) -> Self {
+        Error::Parse {
+            path: None,
+            line: None,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a new [`Error::UnknownName`], for a `kind` of thing named `name`.
+    ///
+    /// [`Error::UnknownName`]: enum.Error.html#variant.UnknownName
+    pub(crate) fn unknown_name<S: Into<String>>(kind: &'static str, name: S) -> Self {
+        Error::UnknownName {
+            kind,
+            name: name.into(),
+        }
+    }
+
+    /// Attach the file which was being worked on, unless the error already refers to one.
+    pub fn with_path<P: AsRef<Path>>(mut self, file: P) -> Self {
+        match &mut self {
+            Error::Io { path, .. }
+            | Error::Image { path, .. }
+            | Error::Parse { path, .. }
+            | Error::Scene { path, .. } => {
+                path.get_or_insert_with(|| file.as_ref().to_path_buf());
+            }
+            Error::InvalidParameter(_) | Error::UnknownName { .. } => {}
+        }
+        self
+    }
+
+    /// Get the file which was being worked on, if known.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Io { path, .. }
+            | Error::Image { path, .. }
+            | Error::Parse { path, .. }
+            | Error::Scene { path, .. } => path.as_deref(),
+            Error::InvalidParameter(_) | Error::UnknownName { .. } => None,
+        }
+    }
+}
+
+/// Shows the file at fault, if known, in front of the message.
+fn path_prefix(path: &Option<PathBuf>) -> String {
+    path.as_ref()
+        .map(|path| format!("{}: ", path.display()))
+        .unwrap_or_default()
+}
+
+/// Shows the line at fault, if known, in front of the message.
+fn line_prefix(line: &Option<usize>) -> String {
+    line.map(|line| format!("line {}: ", line))
+        .unwrap_or_default()
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io { path: None, source }
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(source: image::ImageError) -> Self {
+        Error::Image { path: None, source }
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(source: serde_yaml::Error) -> Self {
+        Error::Scene { path: None, source }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display_works() {
+        let err = Error::parse(3, "invalid vertex coordinate").with_path("mesh.obj");
+        assert_eq!(
+            err.to_string(),
+            "mesh.obj: line 3: invalid vertex coordinate"
+        );
+        let err = Error::invalid_data("truncated archive");
+        assert_eq!(err.to_string(), "truncated archive");
+        let err = Error::InvalidParameter("no objects".to_string()).with_path("scene.yaml");
+        assert_eq!(err.to_string(), "no objects");
+    }
+
+    #[test]
+    fn with_path_keeps_first_path() {
+        let source = io::Error::new(io::ErrorKind::NotFound, "not found");
+        let err = Error::from(source)
+            .with_path("texture.png")
+            .with_path("material.mtl");
+        assert_eq!(err.path(), Some(Path::new("texture.png")));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+}
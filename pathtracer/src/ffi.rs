@@ -0,0 +1,338 @@
+//! A C interface to load and render scenes, to embed the renderer into other applications
+//!
+//! Scenes and images are handed out as opaque pointers, which must be given back to
+//! [`pt_free_scene`] and [`pt_free_image`] once they are not needed anymore. Functions which fail
+//! return a null pointer, the reason being available from [`pt_last_error`]. Panics do not unwind
+//! into the caller, they are reported as failures instead.
+//!
+//! The matching declarations are found in `include/pathtracer.h`. The crate is only built as a
+//! Rust library by default, the shared library to link against is built with:
+//!
+//! ```sh
+//! cargo rustc --package pathtracer --lib --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! [`pt_free_scene`]: fn.pt_free_scene.html
+//! [`pt_free_image`]: fn.pt_free_image.html
+//! [`pt_last_error`]: fn.pt_last_error.html
+
+use crate::render::{Progress, Scene};
+use crate::serialize;
+use crate::Error;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A scene loaded from its description.
+pub struct PtScene(Scene);
+
+/// A rendered image, made of 8-bit RGB pixels, row by row.
+pub struct PtImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+/// The function called with the number of pixels rendered so far, the total number of pixels to
+/// render, and the user data given to [`pt_render`].
+///
+/// It is called from the rendering threads, possibly from several of them at once.
+///
+/// [`pt_render`]: fn.pt_render.html
+pub type PtProgress = extern "C" fn(done: u64, total: u64, user_data: *mut c_void);
+
+/// The user data of a progress callback, which the caller of [`pt_render`] vouches can be used
+/// from any thread.
+///
+/// [`pt_render`]: fn.pt_render.html
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record the reason of the last failure of the current thread.
+fn set_last_error(err: impl ToString) {
+    // Interior nul bytes cannot be part of a C string
+    let message = err.to_string().replace('\0', " ");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+}
+
+/// Run the body of an exported function, returning `failed` and recording the reason of the
+/// failure if it panics, as unwinding into the caller is undefined behaviour.
+fn catch_panic<T>(failed: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        let message = (payload.downcast_ref::<&str>().copied())
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown reason");
+        set_last_error(format!("panicked: {}", message));
+        failed
+    })
+}
+
+/// Get the reason of the last failure of a function called from the current thread, or a null
+/// pointer if none failed yet.
+///
+/// The message is owned by the library, and stays valid until the next failure on this thread.
+#[no_mangle]
+pub extern "C" fn pt_last_error() -> *const c_char {
+    catch_panic(ptr::null(), || {
+        LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+    })
+}
+
+/// Load the scene described at `path`, or in the bundle found at `path`.
+///
+/// Returns a null pointer if the scene could not be loaded.
+///
+/// # Safety
+///
+/// `path` must be a valid nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn pt_load_scene(path: *const c_char) -> *mut PtScene {
+    catch_panic(ptr::null_mut(), || {
+        if path.is_null() {
+            set_last_error("no path was given");
+            return ptr::null_mut();
+        }
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(path) => Path::new(path),
+            Err(err) => {
+                set_last_error(err);
+                return ptr::null_mut();
+            }
+        };
+        match load_scene(path) {
+            Ok(scene) => Box::into_raw(Box::new(PtScene(scene))),
+            Err(err) => {
+                set_last_error(err);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// The number of bundles extracted so far, to give each of them its own directory.
+static BUNDLE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Load a scene, extracting it first if it is bundled.
+fn load_scene(path: &Path) -> crate::Result<Scene> {
+    let parse = |description: String| {
+        serde_yaml::from_str(&description).map_err(|err| Error::from(err).with_path(path))
+    };
+    if serialize::BundleFormat::from_path(path).is_none() {
+        return parse(serialize::load_description(path)?);
+    }
+    // Scenes may be loaded from several threads at once
+    let count = BUNDLE_COUNT.fetch_add(1, Ordering::Relaxed);
+    let name = format!("pathtracer-ffi-{}-{}", std::process::id(), count);
+    let directory = std::env::temp_dir().join(name);
+    let scene = serialize::unpack(path, &directory).and_then(parse);
+    std::fs::remove_dir_all(&directory).ok();
+    scene
+}
+
+/// Free a scene returned by [`pt_load_scene`].
+///
+/// [`pt_load_scene`]: fn.pt_load_scene.html
+///
+/// # Safety
+///
+/// `scene` must be null, or a scene returned by [`pt_load_scene`] which was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pt_free_scene(scene: *mut PtScene) {
+    catch_panic((), || {
+        if !scene.is_null() {
+            drop(Box::from_raw(scene));
+        }
+    })
+}
+
+/// Render the scene, drawing every random sample from the given seed, and reporting its
+/// progress to `progress` if it is not null.
+///
+/// Returns a null pointer if no scene was given. The same scene may be rendered from several
+/// threads at once, each render reporting to its own `progress`.
+///
+/// # Safety
+///
+/// `scene` must be a scene returned by [`pt_load_scene`] which was not freed yet, and
+/// `user_data` must be usable from any thread by `progress`.
+///
+/// [`pt_load_scene`]: fn.pt_load_scene.html
+#[no_mangle]
+pub unsafe extern "C" fn pt_render(
+    scene: *const PtScene,
+    seed: u64,
+    progress: Option<PtProgress>,
+    user_data: *mut c_void,
+) -> *mut PtImage {
+    catch_panic(ptr::null_mut(), || {
+        let scene = match scene.as_ref() {
+            Some(PtScene(scene)) => scene,
+            None => {
+                set_last_error("no scene was given");
+                return ptr::null_mut();
+            }
+        };
+        let user_data = UserData(user_data);
+        let image = match progress {
+            Some(progress) => {
+                let progress: Progress =
+                    Arc::new(move |done, total| progress(done, total, user_data.0));
+                scene.render_with_progress(seed, &progress)
+            }
+            None => scene.render_with_seed(seed),
+        };
+        let (width, height) = image.dimensions();
+        Box::into_raw(Box::new(PtImage {
+            width,
+            height,
+            pixels: image.into_raw(),
+        }))
+    })
+}
+
+/// Get the pixels of an image, writing its dimensions into `width` and `height` if they are not
+/// null.
+///
+/// The pixels are stored row by row from the top of the image, 3 bytes per pixel in RGB order.
+/// They are owned by the image, and stay valid until it is freed.
+///
+/// # Safety
+///
+/// `image` must be an image returned by [`pt_render`] which was not freed yet, `width` and
+/// `height` must be null or valid pointers.
+///
+/// [`pt_render`]: fn.pt_render.html
+#[no_mangle]
+pub unsafe extern "C" fn pt_get_pixels(
+    image: *const PtImage,
+    width: *mut u32,
+    height: *mut u32,
+) -> *const u8 {
+    catch_panic(ptr::null(), || {
+        let image = match image.as_ref() {
+            Some(image) => image,
+            None => {
+                set_last_error("no image was given");
+                return ptr::null();
+            }
+        };
+        if let Some(width) = width.as_mut() {
+            *width = image.width;
+        }
+        if let Some(height) = height.as_mut() {
+            *height = image.height;
+        }
+        image.pixels.as_ptr()
+    })
+}
+
+/// Free an image returned by [`pt_render`].
+///
+/// [`pt_render`]: fn.pt_render.html
+///
+/// # Safety
+///
+/// `image` must be null, or an image returned by [`pt_render`] which was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pt_free_image(image: *mut PtImage) {
+    catch_panic((), || {
+        if !image.is_null() {
+            drop(Box::from_raw(image));
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicU64;
+
+    extern "C" fn record(done: u64, total: u64, user_data: *mut c_void) {
+        let rendered = unsafe { &*(user_data as *const AtomicU64) };
+        assert!(done <= total);
+        rendered.fetch_max(done, Ordering::Relaxed);
+    }
+
+    unsafe fn render_counting(scene: *const PtScene) -> u64 {
+        let rendered = AtomicU64::new(0);
+        let user_data = &rendered as *const _ as *mut c_void;
+        pt_free_image(pt_render(scene, 42, Some(record), user_data));
+        rendered.load(Ordering::Relaxed)
+    }
+
+    #[test]
+    fn render_works() {
+        let yaml = r#"
+            aliasing_limit: 0
+            reflection_limit: 0
+            background: {r: 1.0, g: 0.0, b: 0.0}
+            camera:
+              origin: [0.0, 0.0, 0.0]
+              forward: [0.0, 0.0, 1.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 4
+              y: 3
+        "#;
+        let path = std::env::temp_dir().join(format!("ffi-{}.yaml", std::process::id()));
+        std::fs::write(&path, yaml).unwrap();
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        unsafe {
+            let scene = pt_load_scene(path.as_ptr());
+            assert!(!scene.is_null());
+            let rendered = AtomicU64::new(0);
+            let user_data = &rendered as *const _ as *mut c_void;
+            let image = pt_render(scene, 42, Some(record), user_data);
+            assert_eq!(rendered.load(Ordering::Relaxed), 12);
+            let (mut width, mut height) = (0, 0);
+            let pixels = pt_get_pixels(image, &mut width, &mut height);
+            assert_eq!((width, height), (4, 3));
+            let pixels = std::slice::from_raw_parts(pixels, 4 * 3 * 3);
+            assert_eq!(&pixels[..3], &[255, 0, 0]);
+            pt_free_image(image);
+            // Each concurrent render reports to its own callback
+            let address = scene as usize;
+            let counts = rayon::join(
+                || render_counting(address as *const PtScene),
+                || render_counting(address as *const PtScene),
+            );
+            assert_eq!(counts, (12, 12));
+            pt_free_scene(scene);
+            std::fs::remove_file(path.to_str().unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let path = CString::new("does/not/exist.yaml").unwrap();
+        unsafe {
+            assert!(pt_load_scene(path.as_ptr()).is_null());
+            assert!(!pt_last_error().is_null());
+            let message = CStr::from_ptr(pt_last_error()).to_str().unwrap();
+            assert!(message.contains("exist.yaml"), "{}", message);
+            assert!(pt_render(ptr::null_mut(), 0, None, ptr::null_mut()).is_null());
+            pt_free_scene(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn panics_are_reported() {
+        let failed = catch_panic(ptr::null_mut::<PtImage>(), || panic!("at the disco"));
+        assert!(failed.is_null());
+        let message = unsafe { CStr::from_ptr(pt_last_error()) }.to_str().unwrap();
+        assert_eq!(message, "panicked: at the disco");
+        assert_eq!(catch_panic(0, || 42), 42);
+    }
+}
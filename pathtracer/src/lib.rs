@@ -5,13 +5,35 @@
 /// 3D points and vectors
 pub use beevee::{Point, Vector};
 
+/// The floating point type used throughout the renderer, following the one chosen for [`beevee`]
+///
+/// [`beevee`]: ../beevee/index.html
+pub use beevee::Float;
+
+/// Basic mathematical constants of the [`Float`] type
+///
+/// [`Float`]: type.Float.html
+pub use beevee::consts;
+
 /// A 2D point coordinate
-pub type Point2D = nalgebra::Point2<f32>;
+pub type Point2D = nalgebra::Point2<Float>;
+
+pub use error::{Error, Result};
 
+pub mod background;
 pub mod core;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod light;
 pub mod material;
+pub mod mesh;
+pub mod modifier;
+pub mod post;
 pub mod render;
 pub mod serialize;
 pub mod shape;
+pub mod testing;
 pub mod texture;
+#[cfg(feature = "wasm")]
+pub mod wasm;
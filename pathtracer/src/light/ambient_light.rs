@@ -1,12 +1,19 @@
-use super::Light;
+use super::{Light, LightLink};
 use crate::core::LinearColor;
 use crate::Point;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// Represent an ambient lighting which is equal in all points of the scene.
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct AmbientLight {
+    #[serde(deserialize_with = "crate::serialize::light_color")]
     color: LinearColor,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default, flatten)]
+    link: LightLink,
 }
 
 impl AmbientLight {
@@ -21,7 +28,30 @@ impl AmbientLight {
     /// let amb_light = AmbientLight::new(LinearColor::new(1.0, 0.0, 1.0));
     /// ```
     pub fn new(color: LinearColor) -> Self {
-        AmbientLight { color }
+        AmbientLight {
+            color,
+            name: None,
+            group: None,
+            link: LightLink::default(),
+        }
+    }
+
+    /// Give a name to the light, identifying its contribution to the rendered image.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Add the light to a group, whose contributions to the rendered image are output together.
+    pub fn with_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// Restrict the objects lit by the light.
+    pub fn with_link(mut self, link: LightLink) -> Self {
+        self.link = link;
+        self
     }
 }
 
@@ -29,6 +59,18 @@ impl Light for AmbientLight {
     fn illumination(&self, _: &Point) -> LinearColor {
         self.color.clone()
     }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    fn link(&self) -> Option<&LightLink> {
+        Some(&self.link)
+    }
 }
 
 #[cfg(test)]
@@ -39,7 +81,12 @@ mod test {
     fn new_works() {
         let color = LinearColor::new(1., 1., 1.);
         let light = AmbientLight::new(color.clone());
-        let res = AmbientLight { color };
+        let res = AmbientLight {
+            color,
+            name: None,
+            group: None,
+            link: LightLink::default(),
+        };
         assert_eq!(light, res)
     }
 
@@ -0,0 +1,364 @@
+use super::{outline, Light, LightLink, LightSample, SpatialLight};
+use crate::core::{LinearColor, Sampler};
+use crate::texture::{Texture, TextureEnum};
+use crate::{Float, Point, Point2D, Vector};
+use nalgebra::Unit;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Represent a rectangular light, emitting from the side its normal points towards.
+///
+/// The rectangle is spanned by two edges starting at one of its corners, its normal being their
+/// cross product. The light itself is not visible to the camera.
+///
+/// Its emission can be modulated by a texture, or gobo, stretched over the rectangle: the texel
+/// `(0, 0)` is at `corner`, and `(1, 1)` at the opposite corner.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct AreaLight {
+    /// One of the corners of the rectangle.
+    corner: Point,
+    /// The first edge starting at `corner`.
+    u: Vector,
+    /// The second edge starting at `corner`.
+    v: Vector,
+    /// The radiance emitted by each point of the rectangle.
+    #[serde(deserialize_with = "crate::serialize::light_color")]
+    color: LinearColor,
+    /// The number of shadow rays used at each shading point.
+    #[serde(default = "default_samples")]
+    samples: u32,
+    /// The texture multiplying the emitted radiance, if any.
+    #[serde(default)]
+    gobo: Option<TextureEnum>,
+    /// The name identifying the light's contribution, if any.
+    #[serde(default)]
+    name: Option<String>,
+    /// The group of lights whose contributions are output together, if any.
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default, flatten)]
+    link: LightLink,
+}
+
+fn default_samples() -> u32 {
+    4
+}
+
+impl AreaLight {
+    /// Creates a new `AreaLight`, using the given number of shadow rays at each shading point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::light::AreaLight;
+    /// # use pathtracer::core::color::LinearColor;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// // A 1x1 ceiling light, facing down
+    /// let area_light = AreaLight::new(
+    ///     Point::new(0.0, 2.0, 0.0),
+    ///     Vector::new(1.0, 0.0, 0.0),
+    ///     Vector::new(0.0, 0.0, 1.0),
+    ///     LinearColor::new(1.0, 1.0, 1.0),
+    ///     16,
+    /// );
+    /// ```
+    pub fn new(corner: Point, u: Vector, v: Vector, color: LinearColor, samples: u32) -> Self {
+        AreaLight {
+            corner,
+            u,
+            v,
+            color,
+            samples,
+            gobo: None,
+            name: None,
+            group: None,
+            link: LightLink::default(),
+        }
+    }
+
+    /// Modulate the light's emission by a texture.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::light::AreaLight;
+    /// # use pathtracer::core::color::LinearColor;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let stained_glass = AreaLight::new(
+    ///     Point::new(0.0, 2.0, 0.0),
+    ///     Vector::new(1.0, 0.0, 0.0),
+    ///     Vector::new(0.0, 0.0, 1.0),
+    ///     LinearColor::new(1.0, 1.0, 1.0),
+    ///     16,
+    /// )
+    /// .with_gobo(UniformTexture::new(LinearColor::new(0.2, 0.2, 1.0)).into());
+    /// ```
+    pub fn with_gobo(mut self, gobo: TextureEnum) -> Self {
+        self.gobo = Some(gobo);
+        self
+    }
+
+    /// Give a name to the light, identifying its contribution to the rendered image.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Add the light to a group, whose contributions to the rendered image are output together.
+    pub fn with_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// Restrict the objects lit by the light.
+    pub fn with_link(mut self, link: LightLink) -> Self {
+        self.link = link;
+        self
+    }
+
+    fn area(&self) -> Float {
+        self.u.cross(&self.v).norm()
+    }
+
+    fn center(&self) -> Point {
+        self.corner + (self.u + self.v) / 2.
+    }
+
+    /// Sample the light from the point `(s, t)` of the rectangle, in `[0, 1)²`.
+    fn sample_at(&self, point: &Point, s: Float, t: Float) -> LightSample {
+        let target = self.corner + self.u * s + self.v * t;
+        let delt = target - point;
+        let distance = delt.norm();
+        let direction = Unit::new_normalize(delt);
+        let normal = self.u.cross(&self.v).normalize();
+        let cos = -direction.dot(&normal);
+        let (radiance, pdf) = if cos > 0. {
+            // Convert the uniform density over the area into one over solid angles
+            let radiance = match &self.gobo {
+                Some(gobo) => self.color.clone() * gobo.texel_color(Point2D::new(s, t)),
+                None => self.color.clone(),
+            };
+            (radiance, distance * distance / (cos * self.area()))
+        } else {
+            (LinearColor::black(), 1.)
+        };
+        LightSample {
+            direction,
+            distance,
+            radiance,
+            pdf,
+        }
+    }
+}
+
+impl Light for AreaLight {
+    fn illumination(&self, point: &Point) -> LinearColor {
+        self.sample_at(point, 0.5, 0.5).radiance
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    fn link(&self) -> Option<&LightLink> {
+        Some(&self.link)
+    }
+}
+
+impl SpatialLight for AreaLight {
+    fn to_source(&self, point: &Point) -> (Unit<Vector>, Float) {
+        let delt = self.center() - point;
+        let dist = delt.norm();
+        (Unit::new_normalize(delt), dist)
+    }
+
+    fn position(&self) -> Option<Point> {
+        Some(self.center())
+    }
+
+    fn power(&self) -> Float {
+        self.color.luminance() * self.area()
+    }
+
+    fn sample_li(&self, point: &Point, rng: &mut dyn RngCore) -> LightSample {
+        self.sample_at(point, rng.gen(), rng.gen())
+    }
+
+    fn sample_li_stratified(
+        &self,
+        point: &Point,
+        sampler: Sampler,
+        rng: &mut dyn RngCore,
+    ) -> Vec<LightSample> {
+        sampler
+            .light_samples(self.samples.max(1), rng)
+            .into_iter()
+            .map(|(s, t)| self.sample_at(point, s, t))
+            .collect()
+    }
+
+    fn pdf_li(&self, point: &Point, direction: &Unit<Vector>) -> Float {
+        let normal = self.u.cross(&self.v).normalize();
+        let cos = -direction.dot(&normal);
+        if cos <= 0. {
+            return 0.;
+        }
+        let distance = (point - self.corner).dot(&normal) / cos;
+        let offset = point + direction.as_ref() * distance - self.corner;
+        // Express the hit point in the rectangle's coordinates, even if its edges are skewed
+        let (across_u, across_v) = (self.v.cross(&normal), normal.cross(&self.u));
+        let s = offset.dot(&across_u) / self.u.dot(&across_u);
+        let t = offset.dot(&across_v) / self.v.dot(&across_v);
+        if distance <= 0. || !(0. ..=1.).contains(&s) || !(0. ..=1.).contains(&t) {
+            return 0.;
+        }
+        distance * distance / (cos * self.area())
+    }
+
+    fn outline(&self, _: &Point, size: Float) -> Vec<[Point; 2]> {
+        let corners = [
+            self.corner,
+            self.corner + self.u,
+            self.corner + self.u + self.v,
+            self.corner + self.v,
+        ];
+        let mut segments: Vec<_> = (0..4).map(|i| [corners[i], corners[(i + 1) % 4]]).collect();
+        // Show the side towards which the light is emitted
+        let normal = Unit::new_normalize(self.u.cross(&self.v));
+        segments.extend(outline::arrow(&self.center(), &normal, size));
+        segments
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn simple_light() -> AreaLight {
+        AreaLight::new(
+            Point::new(-1., 2., -1.),
+            Vector::new(2., 0., 0.),
+            Vector::new(0., 0., 2.),
+            LinearColor::new(1., 1., 1.),
+            16,
+        )
+    }
+
+    #[test]
+    fn new_works() {
+        assert_eq!(
+            simple_light(),
+            AreaLight {
+                corner: Point::new(-1., 2., -1.),
+                u: Vector::new(2., 0., 0.),
+                v: Vector::new(0., 0., 2.),
+                color: LinearColor::new(1., 1., 1.),
+                samples: 16,
+                gobo: None,
+                name: None,
+                group: None,
+                link: LightLink::default(),
+            }
+        )
+    }
+
+    #[test]
+    fn emits_on_one_side() {
+        let light = simple_light();
+        // u x v points down
+        let below = light.sample_at(&Point::origin(), 0.5, 0.5);
+        assert_eq!(below.direction, Vector::y_axis());
+        assert_eq!(below.distance, 2.);
+        assert_eq!(below.radiance, LinearColor::new(1., 1., 1.));
+        // 1 / (distance² / (cos * area))
+        assert!((below.pdf - 1.).abs() < 1e-6);
+        let above = light.sample_at(&Point::new(0., 4., 0.), 0.5, 0.5);
+        assert_eq!(above.radiance, LinearColor::black());
+    }
+
+    #[test]
+    fn pdf_matches_samples() {
+        let light = simple_light();
+        let point = Point::new(0.5, 0., 0.);
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..10 {
+            let sample = light.sample_li(&point, &mut rng);
+            let pdf = light.pdf_li(&point, &sample.direction);
+            assert!((pdf - sample.pdf).abs() < 1e-4 * sample.pdf);
+        }
+        // Directions missing the rectangle are never sampled
+        assert_eq!(light.pdf_li(&point, &-Vector::y_axis()), 0.);
+        assert_eq!(light.pdf_li(&point, &Vector::x_axis()), 0.);
+    }
+
+    #[test]
+    fn gobo_is_stretched_over_light() {
+        use crate::texture::ImageTexture;
+        use image::{Rgb, RgbImage};
+
+        // Only the half of the light closest to its corner along `u` emits light
+        let mut image = RgbImage::new(2, 1);
+        image.put_pixel(0, 0, Rgb([255, 255, 255]));
+        let light = simple_light().with_gobo(ImageTexture::new(image).into());
+        let point = Point::origin();
+        let lit = light.sample_at(&point, 0.25, 0.5);
+        assert_eq!(lit.radiance, LinearColor::new(1., 1., 1.));
+        let dark = light.sample_at(&point, 0.75, 0.5);
+        assert_eq!(dark.radiance, LinearColor::black());
+    }
+
+    #[test]
+    fn stratified_samples_are_unbiased() {
+        // The irradiance under the center of a square light, compared with a brute force estimate
+        let light = simple_light();
+        let point = Point::origin();
+        let irradiance = |samples: &[LightSample]| {
+            let total: Float = (samples.iter())
+                .map(|sample| sample.radiance.r * sample.direction.y / sample.pdf)
+                .sum();
+            total / samples.len() as Float
+        };
+        let mut rng = StdRng::seed_from_u64(42);
+        let n = 200;
+        let expected: Float = (0..n)
+            .flat_map(|i| (0..n).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let (s, t) = (
+                    (i as Float + 0.5) / n as Float,
+                    (j as Float + 0.5) / n as Float,
+                );
+                irradiance(&[light.sample_at(&point, s, t)])
+            })
+            .sum::<Float>()
+            / (n * n) as Float;
+        for &sampler in &[Sampler::Random, Sampler::Cmj] {
+            let estimates: Vec<_> = (0..1000)
+                .map(|_| irradiance(&light.sample_li_stratified(&point, sampler, &mut rng)))
+                .collect();
+            let mean = estimates.iter().sum::<Float>() / estimates.len() as Float;
+            assert!((mean - expected).abs() < 0.01 * expected, "{:?}", sampler);
+        }
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            corner: [-1.0, 2.0, -1.0]
+            u: [2.0, 0.0, 0.0]
+            v: [0.0, 0.0, 2.0]
+            color: {r: 1.0, g: 1.0, b: 1.0}
+            samples: 16
+        "#;
+        let light: AreaLight = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(light, simple_light())
+    }
+}
@@ -1,15 +1,22 @@
-use super::{Light, SpatialLight};
+use super::{outline, Light, LightLink, SpatialLight};
 use crate::core::LinearColor;
-use crate::{Point, Vector};
+use crate::{Float, Point, Vector};
 use nalgebra::Unit;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// Represent a light emanating from a far away source, with parallel rays on all points.
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct DirectionalLight {
     #[serde(deserialize_with = "crate::serialize::vector_normalizer")]
     direction: Unit<Vector>,
+    #[serde(deserialize_with = "crate::serialize::light_color")]
     color: LinearColor,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default, flatten)]
+    link: LightLink,
 }
 
 impl DirectionalLight {
@@ -28,7 +35,31 @@ impl DirectionalLight {
     /// );
     /// ```
     pub fn new(direction: Unit<Vector>, color: LinearColor) -> Self {
-        DirectionalLight { direction, color }
+        DirectionalLight {
+            direction,
+            color,
+            name: None,
+            group: None,
+            link: LightLink::default(),
+        }
+    }
+
+    /// Give a name to the light, identifying its contribution to the rendered image.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Add the light to a group, whose contributions to the rendered image are output together.
+    pub fn with_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// Restrict the objects lit by the light.
+    pub fn with_link(mut self, link: LightLink) -> Self {
+        self.link = link;
+        self
     }
 }
 
@@ -36,11 +67,35 @@ impl Light for DirectionalLight {
     fn illumination(&self, _: &Point) -> LinearColor {
         self.color.clone()
     }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    fn link(&self) -> Option<&LightLink> {
+        Some(&self.link)
+    }
 }
 
 impl SpatialLight for DirectionalLight {
-    fn to_source(&self, _: &Point) -> (Unit<Vector>, f32) {
-        (-self.direction, std::f32::INFINITY)
+    fn to_source(&self, _: &Point) -> (Unit<Vector>, Float) {
+        (-self.direction, Float::INFINITY)
+    }
+
+    fn position(&self) -> Option<Point> {
+        None
+    }
+
+    fn power(&self) -> Float {
+        self.color.luminance()
+    }
+
+    fn outline(&self, anchor: &Point, size: Float) -> Vec<[Point; 2]> {
+        outline::arrow(anchor, &self.direction, size)
     }
 }
 
@@ -53,7 +108,13 @@ mod test {
         let direction = Vector::x_axis();
         let color = LinearColor::new(1., 1., 1.);
         let light = DirectionalLight::new(direction, color.clone());
-        let res = DirectionalLight { direction, color };
+        let res = DirectionalLight {
+            direction,
+            color,
+            name: None,
+            group: None,
+            link: LightLink::default(),
+        };
         assert_eq!(light, res)
     }
 
@@ -76,7 +137,7 @@ mod test {
         let ans = light.to_source(&Point::new(1., 0., 0.));
         let expected = (
             Unit::new_normalize(Vector::new(-1., 0., 0.)),
-            std::f32::INFINITY,
+            Float::INFINITY,
         );
         assert_eq!(ans, expected)
     }
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+/// The objects lit by a light, given by their names: either only those it `affects`, or all of
+/// them but those it `excludes`.
+///
+/// Objects which are not lit by a light do not receive its light, nor the shadows it casts onto
+/// them. Unnamed objects are only lit by lights which do not restrict the objects they affect.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct LightLink {
+    /// The only objects lit by the light, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    affects: Option<Vec<String>>,
+    /// The objects which are not lit by the light.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    excludes: Vec<String>,
+}
+
+impl LightLink {
+    /// Creates a new `LightLink`, only lighting the given objects.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::light::LightLink;
+    /// #
+    /// let link = LightLink::affecting(&["hero"]);
+    /// assert!(link.affects(Some("hero")));
+    /// assert!(!link.affects(Some("floor")));
+    /// assert!(!link.affects(None));
+    /// ```
+    pub fn affecting(objects: &[&str]) -> Self {
+        LightLink {
+            affects: Some(objects.iter().map(|name| name.to_string()).collect()),
+            excludes: Vec::new(),
+        }
+    }
+
+    /// Creates a new `LightLink`, lighting every object but the given ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::light::LightLink;
+    /// #
+    /// let link = LightLink::excluding(&["floor"]);
+    /// assert!(link.affects(Some("hero")));
+    /// assert!(!link.affects(Some("floor")));
+    /// assert!(link.affects(None));
+    /// ```
+    pub fn excluding(objects: &[&str]) -> Self {
+        LightLink {
+            affects: None,
+            excludes: objects.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    /// Whether the light lights the object with the given name, if it has one.
+    pub fn affects(&self, object: Option<&str>) -> bool {
+        let listed =
+            |names: &[String]| object.is_some_and(|object| names.iter().any(|n| n == object));
+        let affected = match &self.affects {
+            Some(names) => listed(names),
+            None => true,
+        };
+        affected && !listed(&self.excludes)
+    }
+
+    /// Whether the light lights every object.
+    pub fn is_unrestricted(&self) -> bool {
+        self.affects.is_none() && self.excludes.is_empty()
+    }
+
+    /// Returns an iterator over the names of the objects listed by the link.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        (self.affects.iter().flatten())
+            .chain(self.excludes.iter())
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_affects_everything() {
+        let link = LightLink::default();
+        assert!(link.is_unrestricted());
+        assert!(link.affects(Some("hero")));
+        assert!(link.affects(None));
+    }
+
+    #[test]
+    fn excludes_take_precedence() {
+        let link = LightLink {
+            affects: Some(vec!["hero".into(), "floor".into()]),
+            excludes: vec!["floor".into()],
+        };
+        assert!(link.affects(Some("hero")));
+        assert!(!link.affects(Some("floor")));
+        assert_eq!(
+            link.names().collect::<Vec<_>>(),
+            vec!["hero", "floor", "floor"]
+        );
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = "excludes: [floor]";
+        let link: LightLink = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(link, LightLink::excluding(&["floor"]));
+        let link: LightLink = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(link, LightLink::default());
+    }
+}
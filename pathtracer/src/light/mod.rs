@@ -1,29 +1,166 @@
 //! Various light implementations
 
-use super::core::LinearColor;
+use super::core::{LinearColor, Sampler};
 use super::{Point, Vector};
+use crate::Float;
 use nalgebra::Unit;
+use rand::RngCore;
+
+/// All the existing `Light` implementations.
+#[allow(missing_docs)]
+#[enum_dispatch::enum_dispatch]
+#[derive(Debug, PartialEq)]
+pub enum LightEnum {
+    AmbientLight,
+    DirectionalLight,
+    PointLight,
+    SpotLight,
+    ProjectorLight,
+    AreaLight,
+    SphereLight,
+    SkyLight,
+}
 
 /// Represent a light in the scene being rendered.
+#[enum_dispatch::enum_dispatch(LightEnum)]
 pub trait Light: std::fmt::Debug {
     /// Get the illumination of that light on that point.
     fn illumination(&self, point: &Point) -> LinearColor;
+
+    /// Get the name identifying the light's contribution to the rendered image, if any.
+    ///
+    /// Lights sharing the same name have their contributions accumulated together.
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Get the group of lights whose contributions to the rendered image are output together,
+    /// if any.
+    fn group(&self) -> Option<&str> {
+        None
+    }
+
+    /// Get the objects lit by the light, or `None` if it lights every object.
+    fn link(&self) -> Option<&LightLink> {
+        None
+    }
+
+    /// Whether the light lights the object with the given name, if it has one.
+    fn affects(&self, object: Option<&str>) -> bool {
+        self.link().is_none_or(|link| link.affects(object))
+    }
 }
 
 /// Represent a light which has an abstract position in the scene being rendered.
 pub trait SpatialLight: Light {
     /// Get a unit vector from the origin to the position of the light, and its distance
-    fn to_source(&self, origin: &Point) -> (Unit<Vector>, f32);
+    fn to_source(&self, origin: &Point) -> (Unit<Vector>, Float);
+
+    /// Get the position of the light, or `None` if it is infinitely far away.
+    fn position(&self) -> Option<Point>;
+
+    /// Get an estimate of the light's emitted power, used to choose which lights to sample.
+    fn power(&self) -> Float;
+
+    /// Sample a direction from the point towards the light, and the light it receives from it.
+    ///
+    /// Lights which are infinitely small, like the point lights, only have one possible direction
+    /// to sample: the default implementation uses [`to_source`] and [`illumination`] with a PDF
+    /// of one.
+    ///
+    /// [`to_source`]: #tymethod.to_source
+    /// [`illumination`]: trait.Light.html#tymethod.illumination
+    fn sample_li(&self, point: &Point, _rng: &mut dyn RngCore) -> LightSample {
+        let (direction, distance) = self.to_source(point);
+        LightSample {
+            direction,
+            distance,
+            radiance: self.illumination(point),
+            pdf: 1.,
+        }
+    }
+
+    /// Sample the light with as many directions as it needs for a shading point, spread evenly
+    /// over its surface following the `sampler`'s pattern. The light received at the point is
+    /// the average of their contributions.
+    ///
+    /// The default implementation returns a single sample from [`sample_li`].
+    ///
+    /// [`sample_li`]: #method.sample_li
+    fn sample_li_stratified(
+        &self,
+        point: &Point,
+        _sampler: Sampler,
+        rng: &mut dyn RngCore,
+    ) -> Vec<LightSample> {
+        vec![self.sample_li(point, rng)]
+    }
+
+    /// Get the probability density with which [`sample_li`] samples the direction from the
+    /// point, e.g: to weight the directions sampled by materials when combining both strategies
+    /// with multiple importance sampling.
+    ///
+    /// Lights which are infinitely small cannot be hit by such directions: the default
+    /// implementation returns zero.
+    ///
+    /// [`sample_li`]: #method.sample_li
+    fn pdf_li(&self, _point: &Point, _direction: &Unit<Vector>) -> Float {
+        0.
+    }
+
+    /// Get the line segments outlining the light, e.g: its position and where it shines, to draw
+    /// them over a rendered image.
+    ///
+    /// Markers, such as arrows, are `size` long. Lights which are infinitely far away have no
+    /// position and are drawn at `anchor` instead.
+    ///
+    /// The default implementation marks the light's position, if it has one.
+    fn outline(&self, _anchor: &Point, size: Float) -> Vec<[Point; 2]> {
+        self.position()
+            .map_or_else(Vec::new, |position| outline::star(&position, size))
+    }
+}
+
+/// A direction sampled towards a light, as returned by [`SpatialLight::sample_li`].
+///
+/// [`SpatialLight::sample_li`]: trait.SpatialLight.html#method.sample_li
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightSample {
+    /// The unit vector from the sampled point towards the light.
+    pub direction: Unit<Vector>,
+    /// The distance to the light along `direction`, used to test for shadows.
+    pub distance: Float,
+    /// The light received from that direction.
+    pub radiance: LinearColor,
+    /// The probability density with which the direction was sampled.
+    pub pdf: Float,
 }
 
 mod ambient_light;
 pub use ambient_light::*;
 
+mod area_light;
+pub use area_light::*;
+
 mod directional_light;
 pub use directional_light::*;
 
+mod link;
+pub use link::*;
+
+mod outline;
+
 mod point_light;
 pub use point_light::*;
 
+mod projector_light;
+pub use projector_light::*;
+
+mod sky_light;
+pub use sky_light::*;
+
+mod sphere_light;
+pub use sphere_light::*;
+
 mod spot_light;
 pub use spot_light::*;
@@ -0,0 +1,82 @@
+//! Shapes used to outline lights, as line segments
+
+use crate::core::sampling;
+use crate::{Float, Point, Vector};
+use nalgebra::Unit;
+
+/// The number of segments approximating a circle.
+const CIRCLE_SEGMENTS: usize = 16;
+
+/// Three segments along each axis, crossing at `center`.
+pub(crate) fn star(center: &Point, size: Float) -> Vec<[Point; 2]> {
+    let half = size / 2.;
+    [Vector::x(), Vector::y(), Vector::z()]
+        .iter()
+        .map(|axis| [center - axis * half, center + axis * half])
+        .collect()
+}
+
+/// An arrow of the given length, going from `start` towards `direction`.
+pub(crate) fn arrow(start: &Point, direction: &Unit<Vector>, length: Float) -> Vec<[Point; 2]> {
+    let tip = start + direction.as_ref() * length;
+    let base = tip - direction.as_ref() * (length / 4.);
+    let (right, up) = sampling::orthonormal_basis(direction);
+    let barb = length / 8.;
+    let mut segments = vec![[*start, tip]];
+    segments.extend(
+        [right, -right, up, -up]
+            .iter()
+            .map(|side| [tip, base + side * barb]),
+    );
+    segments
+}
+
+/// A circle of the given radius around `center`, facing towards `normal`.
+pub(crate) fn circle(center: &Point, normal: &Unit<Vector>, radius: Float) -> Vec<[Point; 2]> {
+    let (right, up) = sampling::orthonormal_basis(normal);
+    let point = |i: usize| {
+        let angle = 2. * crate::consts::PI * i as Float / CIRCLE_SEGMENTS as Float;
+        center + (right * angle.cos() + up * angle.sin()) * radius
+    };
+    (0..CIRCLE_SEGMENTS)
+        .map(|i| [point(i), point(i + 1)])
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn star_works() {
+        let segments = star(&Point::new(1., 2., 3.), 2.);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(
+            segments[1],
+            [Point::new(1., 1., 3.), Point::new(1., 3., 3.)]
+        );
+    }
+
+    #[test]
+    fn arrow_works() {
+        let segments = arrow(&Point::origin(), &Vector::z_axis(), 4.);
+        assert_eq!(segments[0], [Point::origin(), Point::new(0., 0., 4.)]);
+        // The barbs go back from the tip
+        assert!(segments[1..]
+            .iter()
+            .all(|[tip, end]| *tip == Point::new(0., 0., 4.) && (end.z - 3.).abs() < 1e-6));
+    }
+
+    #[test]
+    fn circle_works() {
+        let center = Point::new(0., 1., 0.);
+        let segments = circle(&center, &Vector::y_axis(), 2.);
+        assert_eq!(segments.len(), CIRCLE_SEGMENTS);
+        assert!(segments
+            .iter()
+            .flatten()
+            .all(|point| ((point - center).norm() - 2.).abs() < 1e-5 && point.y == 1.));
+        // The circle is closed
+        assert!((segments[0][0] - segments[CIRCLE_SEGMENTS - 1][1]).norm() < 1e-5);
+    }
+}
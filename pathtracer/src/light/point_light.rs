@@ -1,14 +1,64 @@
-use super::{Light, SpatialLight};
+use super::{Light, LightLink, SpatialLight};
 use crate::core::LinearColor;
-use crate::{Point, Vector};
+use crate::{Float, Point, Vector};
 use nalgebra::Unit;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-/// Represent a light emanating from a point in space, following the square distance law.
-#[derive(Debug, PartialEq, Deserialize)]
+/// How the light of a [`PointLight`] decreases with the distance to it.
+///
+/// [`PointLight`]: struct.PointLight.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Falloff {
+    /// The light does not decrease with the distance.
+    Constant,
+    /// The light decreases linearly with the distance.
+    Linear,
+    /// The light decreases with the square of the distance, as it does physically.
+    #[default]
+    InverseSquare,
+}
+
+impl Falloff {
+    /// Get the factor by which the light is divided at the given distance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::light::Falloff;
+    /// #
+    /// assert_eq!(Falloff::Constant.attenuation(2.0), 1.0);
+    /// assert_eq!(Falloff::Linear.attenuation(2.0), 2.0);
+    /// assert_eq!(Falloff::InverseSquare.attenuation(2.0), 4.0);
+    /// ```
+    pub fn attenuation(self, distance: Float) -> Float {
+        match self {
+            Falloff::Constant => 1.,
+            Falloff::Linear => distance,
+            Falloff::InverseSquare => distance * distance,
+        }
+    }
+}
+
+/// Represent a light emanating from a point in space, following the square distance law by
+/// default.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct PointLight {
     position: Point,
+    #[serde(deserialize_with = "crate::serialize::light_color")]
     color: LinearColor,
+    /// A factor scaling the color of the light.
+    #[serde(default = "crate::serialize::default_identity")]
+    intensity: Float,
+    /// How the light decreases with the distance.
+    #[serde(default)]
+    falloff: Falloff,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default, flatten)]
+    link: LightLink,
 }
 
 impl PointLight {
@@ -27,35 +77,131 @@ impl PointLight {
     /// );
     /// ```
     pub fn new(position: Point, color: LinearColor) -> Self {
-        PointLight { position, color }
+        PointLight {
+            position,
+            color,
+            intensity: 1.,
+            falloff: Falloff::default(),
+            name: None,
+            group: None,
+            link: LightLink::default(),
+        }
+    }
+
+    /// Scale the color of the light by the given intensity.
+    pub fn with_intensity(mut self, intensity: Float) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Change how the light decreases with the distance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::light::{Falloff, Light, PointLight};
+    /// # use pathtracer::core::color::LinearColor;
+    /// # use pathtracer::Point;
+    /// #
+    /// let light = PointLight::new(Point::origin(), LinearColor::new(1.0, 1.0, 1.0))
+    ///     .with_falloff(Falloff::Linear);
+    /// let illumination = light.illumination(&Point::new(2.0, 0.0, 0.0));
+    /// assert_eq!(illumination, LinearColor::new(0.5, 0.5, 0.5));
+    /// ```
+    pub fn with_falloff(mut self, falloff: Falloff) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    /// Get how the light decreases with the distance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::light::{Falloff, PointLight};
+    /// # use pathtracer::core::color::LinearColor;
+    /// # use pathtracer::Point;
+    /// #
+    /// let light = PointLight::new(Point::origin(), LinearColor::new(1.0, 1.0, 1.0));
+    /// assert_eq!(light.falloff(), Falloff::InverseSquare);
+    /// ```
+    pub fn falloff(&self) -> Falloff {
+        self.falloff
+    }
+
+    /// Give a name to the light, identifying its contribution to the rendered image.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Add the light to a group, whose contributions to the rendered image are output together.
+    pub fn with_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// Restrict the objects lit by the light.
+    pub fn with_link(mut self, link: LightLink) -> Self {
+        self.link = link;
+        self
     }
 }
 
 impl Light for PointLight {
     fn illumination(&self, point: &Point) -> LinearColor {
         let dist = (self.position - point).norm();
-        self.color.clone() / dist
+        self.color.clone() * self.intensity / self.falloff.attenuation(dist)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    fn link(&self) -> Option<&LightLink> {
+        Some(&self.link)
     }
 }
 
 impl SpatialLight for PointLight {
-    fn to_source(&self, point: &Point) -> (Unit<Vector>, f32) {
+    fn to_source(&self, point: &Point) -> (Unit<Vector>, Float) {
         let delt = self.position - point;
         let dist = delt.norm();
         (Unit::new_normalize(delt), dist)
     }
+
+    fn position(&self) -> Option<Point> {
+        Some(self.position)
+    }
+
+    fn power(&self) -> Float {
+        self.color.luminance() * self.intensity
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::light::LightSample;
 
     #[test]
     fn new_works() {
         let position = Point::origin();
         let color = LinearColor::black();
         let light = PointLight::new(position, color.clone());
-        let res = PointLight { position, color };
+        let res = PointLight {
+            position,
+            color,
+            intensity: 1.,
+            falloff: Falloff::InverseSquare,
+            name: None,
+            group: None,
+            link: LightLink::default(),
+        };
         assert_eq!(light, res)
     }
 
@@ -80,6 +226,37 @@ mod test {
         assert_eq!(ans, expected);
     }
 
+    #[test]
+    fn sample_li_is_correct() {
+        let light = simple_light();
+        let mut rng = rand::thread_rng();
+        let sample = light.sample_li(&Point::new(2., 0., 0.), &mut rng);
+        assert_eq!(
+            sample,
+            LightSample {
+                direction: Unit::new_normalize(Vector::new(-1., 0., 0.)),
+                distance: 2.,
+                radiance: LinearColor::new(0.25, 0.25, 0.25),
+                pdf: 1.,
+            }
+        );
+    }
+
+    #[test]
+    fn falloff_is_applied() {
+        let color = LinearColor::new(1., 1., 1.);
+        let point = Point::new(0., 4., 0.);
+        let light = |falloff| {
+            PointLight::new(Point::origin(), color.clone())
+                .with_intensity(2.)
+                .with_falloff(falloff)
+        };
+        let illumination = |falloff| light(falloff).illumination(&point).r;
+        assert_eq!(illumination(Falloff::Constant), 2.);
+        assert_eq!(illumination(Falloff::Linear), 0.5);
+        assert_eq!(illumination(Falloff::InverseSquare), 0.125);
+    }
+
     #[test]
     fn deserialization_works() {
         let yaml = "{position: [1.0, 1.0, 1.0], color: {r: 1.0, g: 0.5, b: 0.2}}";
@@ -87,6 +264,30 @@ mod test {
         assert_eq!(
             light,
             PointLight::new(Point::new(1., 1., 1.), LinearColor::new(1., 0.5, 0.2))
+        );
+        let yaml = r#"
+            position: [1.0, 1.0, 1.0]
+            color: {r: 1.0, g: 0.5, b: 0.2}
+            intensity: 10.0
+            falloff: linear
+        "#;
+        let light: PointLight = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            light,
+            PointLight::new(Point::new(1., 1., 1.), LinearColor::new(1., 0.5, 0.2))
+                .with_intensity(10.)
+                .with_falloff(Falloff::Linear)
         )
     }
+
+    #[test]
+    fn temperature_deserialization_works() {
+        let yaml = "{position: [1.0, 1.0, 1.0], color: {temperature: 3200, intensity: 2.0}}";
+        let light: PointLight = serde_yaml::from_str(yaml).unwrap();
+        let color = LinearColor::from_temperature(3200.) * 2.;
+        assert_eq!(light, PointLight::new(Point::new(1., 1., 1.), color));
+        let yaml = "{position: [1.0, 1.0, 1.0], color: {temperature: 6500}}";
+        let light: PointLight = serde_yaml::from_str(yaml).unwrap();
+        assert!((light.color.luminance() - 1.).abs() < 1e-5);
+    }
 }
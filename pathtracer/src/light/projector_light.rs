@@ -0,0 +1,315 @@
+use super::{outline, Light, LightLink, SpatialLight};
+use crate::core::LinearColor;
+use crate::texture::{Texture, TextureEnum};
+use crate::{Float, Point, Point2D, Vector};
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// How far outside of its frustum a point may be while still being lit, to absorb rounding errors
+/// on its edges.
+const EDGE_EPSILON: Float = 1e-6;
+
+/// Represent a projector, shining an image through a rectangular frustum like a slide projector.
+///
+/// The image, or gobo, covers the whole frustum and multiplies the color of the light, casting
+/// the pattern of a window, of stained glass, or of a stage light onto the scene. No light is
+/// emitted outside of the frustum.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ProjectorLight {
+    position: Point,
+    #[serde(deserialize_with = "crate::serialize::vector_normalizer")]
+    direction: Unit<Vector>,
+    /// The direction towards the top of the image, made orthogonal to the light's direction.
+    #[serde(
+        default = "default_up",
+        deserialize_with = "crate::serialize::vector_normalizer"
+    )]
+    up: Unit<Vector>,
+    /// The horizontal field of view of the projection, in degrees.
+    fov: Float,
+    /// The ratio of the width of the projection over its height.
+    #[serde(default = "crate::serialize::default_identity")]
+    aspect: Float,
+    #[serde(deserialize_with = "crate::serialize::light_color")]
+    color: LinearColor,
+    /// The image projected by the light.
+    image: TextureEnum,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default, flatten)]
+    link: LightLink,
+}
+
+fn default_up() -> Unit<Vector> {
+    Vector::y_axis()
+}
+
+impl ProjectorLight {
+    /// Creates a new `ProjectorLight`, with the given horizontal FOV in degrees, projecting a
+    /// square image with the Y axis towards its top.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::light::ProjectorLight;
+    /// # use pathtracer::texture::CheckerTexture;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// // A window-like pattern shining down onto the floor
+    /// let projector = ProjectorLight::new(
+    ///     Point::new(0.0, 3.0, 0.0),
+    ///     -Vector::y_axis(),
+    ///     40.0,
+    ///     LinearColor::new(10.0, 10.0, 10.0),
+    ///     CheckerTexture::new(
+    ///         LinearColor::new(1.0, 1.0, 1.0),
+    ///         LinearColor::new(0.0, 0.0, 0.0),
+    ///         2.0,
+    ///     )
+    ///     .into(),
+    /// )
+    /// .with_up(Vector::z_axis());
+    /// ```
+    pub fn new(
+        position: Point,
+        direction: Unit<Vector>,
+        fov: Float,
+        color: LinearColor,
+        image: TextureEnum,
+    ) -> Self {
+        ProjectorLight {
+            position,
+            direction,
+            up: default_up(),
+            fov,
+            aspect: 1.,
+            color,
+            image,
+            name: None,
+            group: None,
+            link: LightLink::default(),
+        }
+    }
+
+    /// Orient the top of the image towards the given direction.
+    pub fn with_up(mut self, up: Unit<Vector>) -> Self {
+        self.up = up;
+        self
+    }
+
+    /// Set the ratio of the width of the projection over its height.
+    pub fn with_aspect(mut self, aspect: Float) -> Self {
+        self.aspect = aspect;
+        self
+    }
+
+    /// Give a name to the light, identifying its contribution to the rendered image.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Add the light to a group, whose contributions to the rendered image are output together.
+    pub fn with_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// Restrict the objects lit by the light.
+    pub fn with_link(mut self, link: LightLink) -> Self {
+        self.link = link;
+        self
+    }
+
+    /// Returns the unit vectors towards the right and the top of the image.
+    fn axes(&self) -> (Vector, Vector) {
+        let right = self.direction.cross(&self.up);
+        // Fall back on any orthogonal axis when `up` is parallel to the direction
+        let right = if right.norm_squared() > 1e-12 {
+            right.normalize()
+        } else {
+            let helper = if self.direction.x.abs() > 0.9 {
+                Vector::y()
+            } else {
+                Vector::x()
+            };
+            self.direction.cross(&helper).normalize()
+        };
+        (right, right.cross(&self.direction))
+    }
+
+    /// Returns the half-width and half-height of the image, on the plane at unit distance.
+    fn half_extent(&self) -> (Float, Float) {
+        let half_width = (self.fov.to_radians() / 2.).tan();
+        (half_width, half_width / self.aspect)
+    }
+
+    /// Get the texel coordinates of the image in the direction of `delt`, if it is in the
+    /// frustum.
+    fn texel(&self, delt: &Vector) -> Option<Point2D> {
+        let depth = delt.dot(&self.direction);
+        if depth <= 0. {
+            return None;
+        }
+        let (right, up) = self.axes();
+        let (half_width, half_height) = self.half_extent();
+        // Intersect with the plane at unit distance
+        let projected = delt / depth;
+        let u = (projected.dot(&right) / half_width + 1.) / 2.;
+        let v = (projected.dot(&up) / half_height + 1.) / 2.;
+        let inside = |c: Float| (-EDGE_EPSILON..=1. + EDGE_EPSILON).contains(&c);
+        if inside(u) && inside(v) {
+            Some(Point2D::new(u.clamp(0., 1.), v.clamp(0., 1.)))
+        } else {
+            None
+        }
+    }
+}
+
+impl Light for ProjectorLight {
+    fn illumination(&self, point: &Point) -> LinearColor {
+        let delt = point - self.position;
+        match self.texel(&delt) {
+            Some(texel) => self.color.clone() * self.image.texel_color(texel) / delt.norm_squared(),
+            None => LinearColor::black(),
+        }
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    fn link(&self) -> Option<&LightLink> {
+        Some(&self.link)
+    }
+}
+
+impl SpatialLight for ProjectorLight {
+    fn to_source(&self, point: &Point) -> (Unit<Vector>, Float) {
+        let delt = self.position - point;
+        let dist = delt.norm();
+        (Unit::new_normalize(delt), dist)
+    }
+
+    fn position(&self) -> Option<Point> {
+        Some(self.position)
+    }
+
+    fn power(&self) -> Float {
+        self.color.luminance()
+    }
+
+    fn outline(&self, _: &Point, size: Float) -> Vec<[Point; 2]> {
+        // A pyramid shining from the light's position, whose length is a few markers
+        let length = 4. * size;
+        let (right, up) = self.axes();
+        let (half_width, half_height) = self.half_extent();
+        let center = self.position + self.direction.as_ref() * length;
+        let corner = |x: Float, y: Float| {
+            center + right * (x * half_width * length) + up * (y * half_height * length)
+        };
+        let corners = [
+            corner(-1., -1.),
+            corner(1., -1.),
+            corner(1., 1.),
+            corner(-1., 1.),
+        ];
+        let mut segments = outline::star(&self.position, size);
+        for (i, &point) in corners.iter().enumerate() {
+            segments.push([self.position, point]);
+            segments.push([point, corners[(i + 1) % corners.len()]]);
+        }
+        // Mark the top of the image
+        segments.push([corners[2], center + up * (half_height * length * 1.5)]);
+        segments.push([corners[3], center + up * (half_height * length * 1.5)]);
+        segments
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::texture::UniformTexture;
+
+    fn simple_light(image: TextureEnum) -> ProjectorLight {
+        ProjectorLight::new(
+            Point::origin(),
+            Vector::x_axis(),
+            90.,
+            LinearColor::new(1., 1., 1.),
+            image,
+        )
+        .with_aspect(2.)
+    }
+
+    fn white() -> TextureEnum {
+        UniformTexture::new(LinearColor::new(1., 1., 1.)).into()
+    }
+
+    #[test]
+    fn illumination_is_limited_to_frustum() {
+        let light = simple_light(white());
+        assert_eq!(
+            light.illumination(&Point::new(2., 0., 0.)),
+            LinearColor::new(0.25, 0.25, 0.25)
+        );
+        // The frustum is twice as wide as it is high
+        assert_ne!(
+            light.illumination(&Point::new(1., 0., 0.9)),
+            LinearColor::black()
+        );
+        assert_eq!(
+            light.illumination(&Point::new(1., 0.9, 0.)),
+            LinearColor::black()
+        );
+        assert_eq!(
+            light.illumination(&Point::new(-1., 0., 0.)),
+            LinearColor::black()
+        );
+    }
+
+    #[test]
+    fn image_covers_frustum() {
+        let light = simple_light(white());
+        // Looking along X with Y up, the right of the image is towards Z
+        let texel = light.texel(&Vector::new(1., 0., 0.)).unwrap();
+        assert!((texel - Point2D::new(0.5, 0.5)).norm() < 1e-6);
+        let texel = light.texel(&Vector::new(1., 0.5, 1.)).unwrap();
+        assert!((texel - Point2D::new(1., 1.)).norm() < 1e-5);
+        let texel = light.texel(&Vector::new(2., -1., -2.)).unwrap();
+        assert!((texel - Point2D::new(0., 0.)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn image_tints_illumination() {
+        let image = UniformTexture::new(LinearColor::new(1., 0.5, 0.)).into();
+        let light = simple_light(image);
+        assert_eq!(
+            light.illumination(&Point::new(1., 0., 0.)),
+            LinearColor::new(1., 0.5, 0.)
+        );
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            position: [0.0, 0.0, 0.0]
+            direction: [1.0, 0.0, 0.0]
+            fov: 90.0
+            aspect: 2.0
+            color: {r: 1.0, g: 1.0, b: 1.0}
+            image:
+              type: uniform
+              color: {r: 1.0, g: 1.0, b: 1.0}
+        "#;
+        let light: ProjectorLight = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(light, simple_light(white()));
+    }
+}
@@ -0,0 +1,308 @@
+use super::{outline, Light, LightSample, SpatialLight};
+use crate::background::{Background, PhysicalSkyBackground};
+use crate::consts::PI;
+use crate::core::{sampling, LinearColor, Sampler};
+use crate::{Float, Point, Vector};
+use nalgebra::Unit;
+use rand::{Rng, RngCore};
+
+/// The number of rows of the grid over which the sky's brightness is tabulated, evenly spaced
+/// along the up direction.
+const ROWS: usize = 32;
+/// The number of columns of that grid, evenly spaced around the up direction.
+const COLUMNS: usize = 64;
+
+/// Represent the light received from a [`PhysicalSkyBackground`], sampled in proportion to its
+/// brightness.
+///
+/// The sky is tabulated over a grid of cells covering the same solid angle, and the sun's disk,
+/// too small to be seen on that grid, is sampled on its own. The scene adds it to its lights when
+/// using such a background.
+///
+/// [`PhysicalSkyBackground`]: ../../background/struct.PhysicalSkyBackground.html
+#[derive(Debug, PartialEq)]
+pub struct SkyLight {
+    sky: PhysicalSkyBackground,
+    /// Two unit vectors orthogonal to the sky's up direction, and to each other.
+    tangents: (Vector, Vector),
+    /// The cumulative distribution of the sky's luminance over the cells of the grid.
+    cdf: Vec<Float>,
+    /// The probability of sampling the sun's disk rather than the rest of the sky.
+    sun_probability: Float,
+}
+
+impl SkyLight {
+    /// Creates a new `SkyLight`, tabulating the brightness of the sky.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::background::PhysicalSkyBackground;
+    /// # use pathtracer::light::{SkyLight, SpatialLight};
+    /// # use pathtracer::Vector;
+    /// # use nalgebra::Unit;
+    /// #
+    /// let sky = PhysicalSkyBackground::new(Unit::new_normalize(Vector::new(1.0, 1.0, 0.0)), 3.0);
+    /// let sky_light = SkyLight::new(sky);
+    /// assert_eq!(sky_light.position(), None);
+    /// ```
+    pub fn new(sky: PhysicalSkyBackground) -> Self {
+        let tangents = sampling::orthonormal_basis(sky.up());
+        let mut ans = SkyLight {
+            sky,
+            tangents,
+            cdf: Vec::with_capacity(ROWS * COLUMNS),
+            sun_probability: 0.,
+        };
+        let mut total = 0.;
+        for row in 0..ROWS {
+            for column in 0..COLUMNS {
+                let direction = ans.direction_in(row, column, 0.5, 0.5);
+                total += ans.sky.sky_color(&direction).luminance();
+                ans.cdf.push(total);
+            }
+        }
+        // Every cell covers the same solid angle
+        let sky_power = total * 4. * PI / (ROWS * COLUMNS) as Float;
+        let sun_power = ans.sky.sun_radiance().luminance() * ans.sun_solid_angle();
+        if sky_power + sun_power > 0. {
+            ans.sun_probability = sun_power / (sky_power + sun_power);
+        }
+        ans
+    }
+
+    fn sun_solid_angle(&self) -> Float {
+        2. * PI * (1. - self.sky.sun_cos())
+    }
+
+    /// Get the direction at `(u, v)` in `[0, 1)²` of a cell of the grid.
+    fn direction_in(&self, row: usize, column: usize, u: Float, v: Float) -> Unit<Vector> {
+        let cos = 1. - 2. * (row as Float + u) / ROWS as Float;
+        let sin = (1. - cos * cos).max(0.).sqrt();
+        let phi = 2. * PI * (column as Float + v) / COLUMNS as Float;
+        let (tangent, bitangent) = &self.tangents;
+        Unit::new_normalize(
+            tangent * (sin * phi.cos())
+                + bitangent * (sin * phi.sin())
+                + self.sky.up().as_ref() * cos,
+        )
+    }
+
+    /// Get the index of the cell of the grid containing the direction.
+    fn cell_of(&self, direction: &Unit<Vector>) -> usize {
+        let cos = direction.dot(self.sky.up());
+        let (tangent, bitangent) = &self.tangents;
+        let phi = direction.dot(bitangent).atan2(direction.dot(tangent));
+        let phi = if phi < 0. { phi + 2. * PI } else { phi };
+        let row = (((1. - cos) / 2. * ROWS as Float) as usize).min(ROWS - 1);
+        let column = ((phi / (2. * PI) * COLUMNS as Float) as usize).min(COLUMNS - 1);
+        row * COLUMNS + column
+    }
+
+    /// Sample a direction towards the sun's disk, uniformly.
+    fn sample_sun(&self, u: Float, v: Float) -> Unit<Vector> {
+        let cone = sampling::uniform_cone(1. - self.sky.sun_cos(), u, v);
+        sampling::to_world(self.sky.sun_direction(), &cone)
+    }
+
+    /// Sample a direction in a cell of the grid, chosen in proportion to its brightness.
+    fn sample_sky(&self, u: Float, v: Float) -> Unit<Vector> {
+        let total = *self.cdf.last().unwrap();
+        let target = u * total;
+        let cell = self
+            .cdf
+            .partition_point(|&sum| sum <= target)
+            .min(self.cdf.len() - 1);
+        let start = if cell == 0 { 0. } else { self.cdf[cell - 1] };
+        let width = self.cdf[cell] - start;
+        // Re-use the position within the cell's share of `u`, never NaN as `width` is positive
+        let offset = if width > 0. {
+            ((target - start) / width).clamp(0., 1.)
+        } else {
+            0.5
+        };
+        self.direction_in(cell / COLUMNS, cell % COLUMNS, offset, v)
+    }
+
+    /// Sample the sky with the uniform random numbers `(u, v)`, in `[0, 1)²`.
+    fn sample_at(&self, u: Float, v: Float) -> LightSample {
+        let direction = if u < self.sun_probability {
+            self.sample_sun(u / self.sun_probability, v)
+        } else {
+            self.sample_sky((u - self.sun_probability) / (1. - self.sun_probability), v)
+        };
+        let pdf = self.pdf(&direction);
+        let radiance = if pdf > 0. {
+            self.sky.color(&direction)
+        } else {
+            LinearColor::black()
+        };
+        LightSample {
+            direction,
+            distance: Float::INFINITY,
+            radiance,
+            pdf: if pdf > 0. { pdf } else { 1. },
+        }
+    }
+
+    fn pdf(&self, direction: &Unit<Vector>) -> Float {
+        let total = *self.cdf.last().unwrap();
+        let sky = if total > 0. {
+            let cell = self.cell_of(direction);
+            let start = if cell == 0 { 0. } else { self.cdf[cell - 1] };
+            (self.cdf[cell] - start) / total * (ROWS * COLUMNS) as Float / (4. * PI)
+        } else {
+            0.
+        };
+        let sun = if self.sky.is_in_sun(direction) {
+            1. / self.sun_solid_angle()
+        } else {
+            0.
+        };
+        self.sun_probability * sun + (1. - self.sun_probability) * sky
+    }
+}
+
+impl Light for SkyLight {
+    /// The light received from the sun, as if its disk were a single point.
+    fn illumination(&self, _: &Point) -> LinearColor {
+        self.sky.sun_radiance() * self.sun_solid_angle()
+    }
+}
+
+impl SpatialLight for SkyLight {
+    fn to_source(&self, _: &Point) -> (Unit<Vector>, Float) {
+        (*self.sky.sun_direction(), Float::INFINITY)
+    }
+
+    fn position(&self) -> Option<Point> {
+        None
+    }
+
+    fn power(&self) -> Float {
+        let sky = self.cdf.last().unwrap() * 4. * PI / (ROWS * COLUMNS) as Float;
+        sky + self.sky.sun_radiance().luminance() * self.sun_solid_angle()
+    }
+
+    fn sample_li(&self, _: &Point, rng: &mut dyn RngCore) -> LightSample {
+        self.sample_at(rng.gen(), rng.gen())
+    }
+
+    fn sample_li_stratified(
+        &self,
+        _: &Point,
+        sampler: Sampler,
+        rng: &mut dyn RngCore,
+    ) -> Vec<LightSample> {
+        sampler
+            .light_samples(self.sky.samples().max(1), rng)
+            .into_iter()
+            .map(|(u, v)| self.sample_at(u, v))
+            .collect()
+    }
+
+    fn pdf_li(&self, _: &Point, direction: &Unit<Vector>) -> Float {
+        self.pdf(direction)
+    }
+
+    fn outline(&self, anchor: &Point, size: Float) -> Vec<[Point; 2]> {
+        outline::arrow(anchor, &-*self.sky.sun_direction(), size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn simple_light() -> SkyLight {
+        let sky = PhysicalSkyBackground::new(Unit::new_normalize(Vector::new(1., 1., 0.)), 3.)
+            .with_ground(LinearColor::new(0.1, 0.1, 0.1));
+        SkyLight::new(sky)
+    }
+
+    #[test]
+    fn cells_are_found() {
+        let light = simple_light();
+        for &(row, column) in &[(0, 0), (5, 17), (16, 63), (31, 32)] {
+            let direction = light.direction_in(row, column, 0.3, 0.7);
+            assert_eq!(light.cell_of(&direction), row * COLUMNS + column);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let light = simple_light();
+        // Integrate the sky's density over the grid, and the sun's over its disk
+        let (rows, columns) = (4 * ROWS, 4 * COLUMNS);
+        let mut total = 0.;
+        for i in 0..rows {
+            for j in 0..columns {
+                let cos = 1. - 2. * (i as Float + 0.5) / rows as Float;
+                let sin = (1. - cos * cos).sqrt();
+                let phi = 2. * PI * (j as Float + 0.5) / columns as Float;
+                let direction =
+                    Unit::new_normalize(Vector::new(sin * phi.cos(), cos, sin * phi.sin()));
+                if !light.sky.is_in_sun(&direction) {
+                    total += light.pdf(&direction) * 4. * PI / (rows * columns) as Float;
+                }
+            }
+        }
+        let sun = light.sun_probability;
+        assert!((total + sun - 1.).abs() < 0.01, "{}", total + sun);
+    }
+
+    #[test]
+    fn samples_match_pdf() {
+        let light = simple_light();
+        let mut rng = StdRng::seed_from_u64(42);
+        let samples = light.sample_li_stratified(&Point::origin(), Sampler::Random, &mut rng);
+        assert_eq!(samples.len(), 4);
+        for _ in 0..100 {
+            let sample = light.sample_li(&Point::origin(), &mut rng);
+            let pdf = light.pdf_li(&Point::origin(), &sample.direction);
+            assert!((sample.pdf - pdf).abs() <= 1e-3 * pdf);
+            assert_eq!(sample.radiance, light.sky.color(&sample.direction));
+            assert_eq!(sample.distance, Float::INFINITY);
+        }
+    }
+
+    #[test]
+    fn irradiance_is_unbiased() {
+        // The irradiance of a surface facing up, compared with a brute force estimate of the sky,
+        // with the sun's disk added analytically
+        let light = simple_light();
+        let sky = &light.sky;
+        let n = 400;
+        let mut expected = 0.;
+        for i in 0..n {
+            for j in 0..n {
+                let cos = (i as Float + 0.5) / n as Float;
+                let sin = (1. - cos * cos).sqrt();
+                let phi = 2. * PI * (j as Float + 0.5) / n as Float;
+                let direction =
+                    Unit::new_normalize(Vector::new(sin * phi.cos(), cos, sin * phi.sin()));
+                expected += sky.sky_color(&direction).g * cos * 2. * PI / (n * n) as Float;
+            }
+        }
+        expected += sky.sun_radiance().g * light.sun_solid_angle() * sky.sun_direction().y;
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let count = 20_000;
+        let estimate: Float = (0..count)
+            .map(|_| {
+                let sample = light.sample_li(&Point::origin(), &mut rng);
+                let cos = sample.direction.y.max(0.);
+                sample.radiance.g * cos / sample.pdf
+            })
+            .sum::<Float>()
+            / count as Float;
+        assert!(
+            (estimate - expected).abs() < 0.02 * expected,
+            "{} {}",
+            estimate,
+            expected
+        );
+    }
+}
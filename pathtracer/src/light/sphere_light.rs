@@ -0,0 +1,339 @@
+use super::{outline, Light, LightLink, LightSample, SpatialLight};
+use crate::consts::PI;
+use crate::core::{sampling, LinearColor, Sampler};
+use crate::{Float, Point, Vector};
+use nalgebra::Unit;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Represent a spherical light, emitting uniformly in every direction from its surface.
+///
+/// Its surface is sampled by the solid angle it covers as seen from the shading point, casting
+/// soft shadows whose penumbrae grow with its radius. The light itself is not visible to the
+/// camera.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct SphereLight {
+    /// The center of the sphere.
+    position: Point,
+    /// The radius of the sphere.
+    radius: Float,
+    /// The total power emitted by the sphere, spread evenly over its surface.
+    #[serde(deserialize_with = "crate::serialize::light_color")]
+    power: LinearColor,
+    /// The number of shadow rays used at each shading point.
+    #[serde(default = "default_samples")]
+    samples: u32,
+    /// The name identifying the light's contribution, if any.
+    #[serde(default)]
+    name: Option<String>,
+    /// The group of lights whose contributions are output together, if any.
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default, flatten)]
+    link: LightLink,
+}
+
+fn default_samples() -> u32 {
+    4
+}
+
+impl SphereLight {
+    /// Creates a new `SphereLight`, using the given number of shadow rays at each shading point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::light::SphereLight;
+    /// # use pathtracer::core::color::LinearColor;
+    /// # use pathtracer::Point;
+    /// #
+    /// // A light bulb hanging from the ceiling
+    /// let sphere_light = SphereLight::new(
+    ///     Point::new(0.0, 2.0, 0.0),
+    ///     0.1,
+    ///     LinearColor::new(10.0, 10.0, 10.0),
+    ///     16,
+    /// );
+    /// ```
+    pub fn new(position: Point, radius: Float, power: LinearColor, samples: u32) -> Self {
+        SphereLight {
+            position,
+            radius,
+            power,
+            samples,
+            name: None,
+            group: None,
+            link: LightLink::default(),
+        }
+    }
+
+    /// Give a name to the light, identifying its contribution to the rendered image.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Add the light to a group, whose contributions to the rendered image are output together.
+    pub fn with_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// Restrict the objects lit by the light.
+    pub fn with_link(mut self, link: LightLink) -> Self {
+        self.link = link;
+        self
+    }
+
+    /// The radiance emitted by each point of the sphere's surface.
+    fn radiance(&self) -> LinearColor {
+        self.power.clone() / (4. * PI * PI * self.radius * self.radius)
+    }
+
+    /// Returns the cosine of the half-angle of the cone of directions covering the sphere from
+    /// the point, along with `1 - cos`, or `None` if the point is inside the sphere.
+    fn cone(&self, point: &Point) -> Option<(Float, Float)> {
+        let dist2 = (self.position - point).norm_squared();
+        let sin2 = self.radius * self.radius / dist2;
+        if sin2 >= 1. {
+            return None;
+        }
+        let cos = (1. - sin2).sqrt();
+        // Avoid the cancellation of `1 - cos` for small or distant spheres
+        Some((cos, sin2 / (1. + cos)))
+    }
+
+    /// Get the distance from the point to the sphere's surface along the direction, if it is hit.
+    fn distance_along(&self, point: &Point, direction: &Unit<Vector>) -> Option<Float> {
+        let offset = point - self.position;
+        let b = offset.dot(direction);
+        let c = offset.norm_squared() - self.radius * self.radius;
+        let discriminant = b * b - c;
+        if discriminant < 0. {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        // From inside the sphere, the nearest intersection is behind the point
+        let distance = if c > 0. { -b - root } else { -b + root };
+        if distance < 0. {
+            None
+        } else {
+            Some(distance)
+        }
+    }
+
+    /// Sample the light with the uniform random numbers `(u, v)`, in `[0, 1)²`.
+    fn sample_at(&self, point: &Point, u: Float, v: Float) -> LightSample {
+        let delt = self.position - point;
+        let axis = Unit::new_normalize(delt);
+        let (local, pdf) = match self.cone(point) {
+            // Sample the cone of directions towards the sphere uniformly
+            Some((_, one_minus_cos)) => (
+                sampling::uniform_cone(one_minus_cos, u, v),
+                sampling::uniform_cone_pdf(one_minus_cos),
+            ),
+            // Every direction sees the sphere's surface from inside of it
+            None => (
+                sampling::uniform_sphere(u, v),
+                sampling::uniform_sphere_pdf(),
+            ),
+        };
+        let direction = sampling::to_world(&axis, &local);
+        // Directions grazing the sphere can miss it by a rounding error
+        let distance = self
+            .distance_along(point, &direction)
+            .unwrap_or_else(|| delt.dot(&direction).max(0.));
+        LightSample {
+            direction,
+            distance,
+            radiance: self.radiance(),
+            pdf,
+        }
+    }
+}
+
+impl Light for SphereLight {
+    /// The light received from the sphere, as if it were all coming from its center.
+    fn illumination(&self, point: &Point) -> LinearColor {
+        match self.cone(point) {
+            Some((_, one_minus_cos)) => self.radiance() * (2. * PI * one_minus_cos),
+            None => self.radiance() * (4. * PI),
+        }
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    fn link(&self) -> Option<&LightLink> {
+        Some(&self.link)
+    }
+}
+
+impl SpatialLight for SphereLight {
+    fn to_source(&self, point: &Point) -> (Unit<Vector>, Float) {
+        let delt = self.position - point;
+        let dist = delt.norm();
+        (Unit::new_normalize(delt), dist)
+    }
+
+    fn position(&self) -> Option<Point> {
+        Some(self.position)
+    }
+
+    fn power(&self) -> Float {
+        self.power.luminance()
+    }
+
+    fn sample_li(&self, point: &Point, rng: &mut dyn RngCore) -> LightSample {
+        self.sample_at(point, rng.gen(), rng.gen())
+    }
+
+    fn sample_li_stratified(
+        &self,
+        point: &Point,
+        sampler: Sampler,
+        rng: &mut dyn RngCore,
+    ) -> Vec<LightSample> {
+        sampler
+            .light_samples(self.samples.max(1), rng)
+            .into_iter()
+            .map(|(u, v)| self.sample_at(point, u, v))
+            .collect()
+    }
+
+    fn pdf_li(&self, point: &Point, direction: &Unit<Vector>) -> Float {
+        if self.distance_along(point, direction).is_none() {
+            return 0.;
+        }
+        match self.cone(point) {
+            Some((_, one_minus_cos)) => sampling::uniform_cone_pdf(one_minus_cos),
+            None => 1. / (4. * PI),
+        }
+    }
+
+    fn outline(&self, _: &Point, _: Float) -> Vec<[Point; 2]> {
+        [Vector::x_axis(), Vector::y_axis(), Vector::z_axis()]
+            .iter()
+            .flat_map(|axis| outline::circle(&self.position, axis, self.radius))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn simple_light() -> SphereLight {
+        SphereLight::new(
+            Point::new(0., 2., 0.),
+            0.5,
+            LinearColor::new(1., 1., 1.),
+            16,
+        )
+    }
+
+    #[test]
+    fn new_works() {
+        assert_eq!(
+            simple_light(),
+            SphereLight {
+                position: Point::new(0., 2., 0.),
+                radius: 0.5,
+                power: LinearColor::new(1., 1., 1.),
+                samples: 16,
+                name: None,
+                group: None,
+                link: LightLink::default(),
+            }
+        )
+    }
+
+    #[test]
+    fn samples_hit_the_sphere() {
+        let light = simple_light();
+        let point = Point::origin();
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let sample = light.sample_li(&point, &mut rng);
+            let hit = point + sample.direction.as_ref() * sample.distance;
+            assert!(((hit - light.position).norm() - 0.5).abs() < 1e-4);
+            // The near side of the sphere is seen from below
+            assert!(sample.distance <= 2.);
+            assert!((sample.pdf - light.pdf_li(&point, &sample.direction)).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        // Integrate the density over the unit sphere of directions, the sphere being seen along
+        // the vertical axis needs few azimuths but many elevations
+        let light = simple_light();
+        let (rows, columns) = (10_000, 8);
+        for point in &[Point::origin(), Point::new(0., 2.2, 0.)] {
+            let mut total = 0.;
+            for i in 0..rows {
+                let cos = 1. - 2. * (i as Float + 0.5) / rows as Float;
+                let sin = (1. - cos * cos).sqrt();
+                for j in 0..columns {
+                    let phi = 2. * PI * (j as Float + 0.5) / columns as Float;
+                    let direction =
+                        Unit::new_normalize(Vector::new(sin * phi.cos(), cos, sin * phi.sin()));
+                    total += light.pdf_li(point, &direction);
+                }
+            }
+            total *= 4. * PI / (rows * columns) as Float;
+            assert!((total - 1.).abs() < 0.02, "{}", total);
+        }
+    }
+
+    #[test]
+    fn stratified_samples_are_unbiased() {
+        // The irradiance under the sphere, compared with the analytic solution
+        let light = simple_light();
+        let point = Point::origin();
+        let irradiance = |samples: &[LightSample]| {
+            let total: Float = (samples.iter())
+                .map(|sample| sample.radiance.r * sample.direction.y / sample.pdf)
+                .sum();
+            total / samples.len() as Float
+        };
+        // A sphere directly overhead is seen as a disk of radiance L covering `π sin²θ`
+        let expected = light.radiance().r * PI * 0.25 / 4.;
+        let mut rng = StdRng::seed_from_u64(42);
+        for &sampler in &[Sampler::Random, Sampler::Cmj] {
+            let estimates: Vec<_> = (0..1000)
+                .map(|_| irradiance(&light.sample_li_stratified(&point, sampler, &mut rng)))
+                .collect();
+            let mean = estimates.iter().sum::<Float>() / estimates.len() as Float;
+            assert!((mean - expected).abs() < 0.01 * expected, "{:?}", sampler);
+        }
+    }
+
+    #[test]
+    fn small_spheres_match_point_lights() {
+        let light = SphereLight::new(Point::origin(), 1e-3, LinearColor::new(1., 1., 1.), 1);
+        let illumination = light.illumination(&Point::new(0., 0., 2.));
+        // The power is spread over `4π` steradians, at a distance of 2
+        let expected = 1. / (4. * PI * 4.);
+        assert!((illumination.r - expected).abs() < 1e-3 * expected);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            position: [0.0, 2.0, 0.0]
+            radius: 0.5
+            power: {r: 1.0, g: 1.0, b: 1.0}
+            samples: 16
+        "#;
+        let light: SphereLight = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(light, simple_light())
+    }
+}
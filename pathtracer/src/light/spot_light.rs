@@ -1,18 +1,30 @@
-use super::{Light, SpatialLight};
-use crate::core::LinearColor;
-use crate::{Point, Vector};
+use super::{outline, Light, LightLink, SpatialLight};
+use crate::core::{sampling, LinearColor};
+use crate::texture::{Texture, TextureEnum};
+use crate::{Float, Point, Point2D, Vector};
 use nalgebra::Unit;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// How far outside of its cone a point may be while still being lit, to absorb rounding errors on
+/// its edge.
+const EDGE_EPSILON: Float = 1e-6;
 
 /// Represent a light emanating from a directed light-source, outputting rays in a cone.
 ///
 /// The illumination cone cannot have an FOV over 180°.
+///
+/// A texture, or gobo, can be projected by the light to fake the shadows of a window frame or of
+/// foliage without modeling them. It covers the square inscribed in the cone's section.
 #[derive(Debug, PartialEq)]
 pub struct SpotLight {
     position: Point,
     direction: Unit<Vector>,
-    cosine_value: f32,
+    cosine_value: Float,
     color: LinearColor,
+    gobo: Option<TextureEnum>,
+    name: Option<String>,
+    group: Option<String>,
+    link: LightLink,
 }
 
 impl SpotLight {
@@ -20,7 +32,7 @@ impl SpotLight {
     pub fn radians_new(
         position: Point,
         direction: Unit<Vector>,
-        fov_rad: f32,
+        fov_rad: Float,
         color: LinearColor,
     ) -> Self {
         SpotLight {
@@ -28,6 +40,10 @@ impl SpotLight {
             direction,
             cosine_value: (fov_rad / 2.).cos(),
             color,
+            gobo: None,
+            name: None,
+            group: None,
+            link: LightLink::default(),
         }
     }
 
@@ -35,50 +51,171 @@ impl SpotLight {
     pub fn degrees_new(
         position: Point,
         direction: Unit<Vector>,
-        fov_deg: f32,
+        fov_deg: Float,
         color: LinearColor,
     ) -> Self {
         SpotLight::radians_new(
             position,
             direction,
-            std::f32::consts::PI * fov_deg / 180.,
+            crate::consts::PI * fov_deg / 180.,
             color,
         )
     }
+
+    /// Project a texture with the light, multiplying its color.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::light::SpotLight;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let spot = SpotLight::degrees_new(
+    ///     Point::origin(),
+    ///     Vector::x_axis(),
+    ///     60.0,
+    ///     LinearColor::new(1.0, 1.0, 1.0),
+    /// )
+    /// .with_gobo(UniformTexture::new(LinearColor::new(1.0, 0.0, 0.0)).into());
+    /// ```
+    pub fn with_gobo(mut self, gobo: TextureEnum) -> Self {
+        self.gobo = Some(gobo);
+        self
+    }
+
+    /// Give a name to the light, identifying its contribution to the rendered image.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Add the light to a group, whose contributions to the rendered image are output together.
+    pub fn with_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// Restrict the objects lit by the light.
+    pub fn with_link(mut self, link: LightLink) -> Self {
+        self.link = link;
+        self
+    }
+
+    /// Get the texel coordinates of the gobo in the direction of `delt`, which is in the cone.
+    fn gobo_texel(&self, delt: &Vector) -> Point2D {
+        let (right, down) = sampling::orthonormal_basis(&self.direction);
+        let up = -down;
+        // Intersect with the plane at unit distance, on which the cone is a circle of radius `tan`
+        let projected = delt / delt.dot(&self.direction);
+        let tan = (1. - self.cosine_value * self.cosine_value).sqrt() / self.cosine_value;
+        let to_texel = |axis: &Vector| (projected.dot(axis) / tan + 1.) / 2.;
+        Point2D::new(to_texel(&right), to_texel(&up))
+    }
 }
 
 impl Light for SpotLight {
     fn illumination(&self, point: &Point) -> LinearColor {
         let delt = point - self.position;
         let cos = self.direction.dot(&delt.normalize());
-        if cos >= self.cosine_value {
-            self.color.clone() / delt.norm_squared()
-        } else {
-            LinearColor::black()
+        if cos < self.cosine_value - EDGE_EPSILON {
+            return LinearColor::black();
+        }
+        let color = self.color.clone() / delt.norm_squared();
+        match &self.gobo {
+            Some(gobo) => color * gobo.texel_color(self.gobo_texel(&delt)),
+            None => color,
         }
     }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    fn link(&self) -> Option<&LightLink> {
+        Some(&self.link)
+    }
 }
 
 impl SpatialLight for SpotLight {
-    fn to_source(&self, point: &Point) -> (Unit<Vector>, f32) {
+    fn to_source(&self, point: &Point) -> (Unit<Vector>, Float) {
         let delt = self.position - point;
         let dist = delt.norm();
         (Unit::new_normalize(delt), dist)
     }
+
+    fn position(&self) -> Option<Point> {
+        Some(self.position)
+    }
+
+    fn power(&self) -> Float {
+        self.color.luminance()
+    }
+
+    fn outline(&self, _: &Point, size: Float) -> Vec<[Point; 2]> {
+        // A cone shining from the light's position, whose length is a few markers
+        let length = 4. * size;
+        let end = self.position + self.direction.as_ref() * length;
+        let tan = (1. - self.cosine_value * self.cosine_value).sqrt() / self.cosine_value;
+        let rim = outline::circle(&end, &self.direction, length * tan);
+        let edges = rim
+            .iter()
+            .step_by(rim.len() / 4)
+            .map(|[point, _]| [self.position, *point]);
+        let mut segments = outline::star(&self.position, size);
+        segments.extend(edges.collect::<Vec<_>>());
+        segments.extend(rim);
+        segments
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct SerializedSpotLight {
     position: Point,
     #[serde(deserialize_with = "crate::serialize::vector_normalizer")]
     direction: Unit<Vector>,
-    fov: f32,
+    fov: Float,
+    #[serde(deserialize_with = "crate::serialize::light_color")]
     color: LinearColor,
+    #[serde(default)]
+    gobo: Option<TextureEnum>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default, flatten)]
+    link: LightLink,
 }
 
 impl From<SerializedSpotLight> for SpotLight {
     fn from(light: SerializedSpotLight) -> Self {
-        SpotLight::degrees_new(light.position, light.direction, light.fov, light.color)
+        let mut spot =
+            SpotLight::degrees_new(light.position, light.direction, light.fov, light.color);
+        spot.gobo = light.gobo;
+        spot.name = light.name;
+        spot.group = light.group;
+        spot.link = light.link;
+        spot
+    }
+}
+
+impl From<&SpotLight> for SerializedSpotLight {
+    fn from(light: &SpotLight) -> Self {
+        SerializedSpotLight {
+            position: light.position,
+            direction: light.direction,
+            fov: 2. * light.cosine_value.acos().to_degrees(),
+            color: light.color.clone(),
+            gobo: light.gobo.clone(),
+            name: light.name.clone(),
+            group: light.group.clone(),
+            link: light.link.clone(),
+        }
     }
 }
 
@@ -92,6 +229,15 @@ impl<'de> Deserialize<'de> for SpotLight {
     }
 }
 
+impl Serialize for SpotLight {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        SerializedSpotLight::from(self).serialize(serializer)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -101,11 +247,11 @@ mod test {
         let light = SpotLight::radians_new(
             Point::origin(),
             Vector::x_axis(),
-            std::f32::consts::PI / 2.,
+            crate::consts::PI / 2.,
             LinearColor::new(1., 1., 1.),
         );
         // The FOV is 90°, therefore the angle to the direction is 45° [= PI / 4]
-        let calculated_cosine_value = (std::f32::consts::PI / 4.).cos();
+        let calculated_cosine_value = (crate::consts::PI / 4.).cos();
         assert_eq!(
             light,
             SpotLight {
@@ -113,10 +259,14 @@ mod test {
                 direction: Vector::x_axis(),
                 cosine_value: calculated_cosine_value,
                 color: LinearColor::new(1., 1., 1.),
+                gobo: None,
+                name: None,
+                group: None,
+                link: LightLink::default(),
             }
         );
         // Checking this way because of rounding issues...
-        assert!((calculated_cosine_value - f32::sqrt(2.) / 2.).abs() < 1e-5)
+        assert!((calculated_cosine_value - Float::sqrt(2.) / 2.).abs() < 1e-5)
     }
 
     #[test]
@@ -127,7 +277,7 @@ mod test {
             60.,
             LinearColor::new(1., 1., 1.),
         );
-        let calculated_cosine_value = (std::f32::consts::PI * 60. / 360.).cos();
+        let calculated_cosine_value = (crate::consts::PI * 60. / 360.).cos();
         assert_eq!(
             light,
             SpotLight {
@@ -135,10 +285,14 @@ mod test {
                 direction: Vector::x_axis(),
                 cosine_value: calculated_cosine_value,
                 color: LinearColor::new(1., 1., 1.),
+                gobo: None,
+                name: None,
+                group: None,
+                link: LightLink::default(),
             }
         );
         // Checking this way because of rounding issues...
-        assert!((calculated_cosine_value - f32::sqrt(3.) / 2.).abs() < 1e-5)
+        assert!((calculated_cosine_value - Float::sqrt(3.) / 2.).abs() < 1e-5)
     }
 
     fn simple_light() -> impl SpatialLight {
@@ -186,6 +340,68 @@ mod test {
         assert_eq!(ans, expected);
     }
 
+    #[test]
+    fn gobo_texel_covers_cone() {
+        let light = SpotLight::degrees_new(
+            Point::origin(),
+            Vector::x_axis(),
+            90.,
+            LinearColor::new(1., 1., 1.),
+        );
+        let center = light.gobo_texel(&Vector::new(2., 0., 0.));
+        assert!((center - Point2D::new(0.5, 0.5)).norm() < 1e-6);
+        // The border of the cone is at the middle of the texture's edges
+        for delt in &[Vector::new(1., 1., 0.), Vector::new(1., 0., -1.)] {
+            let texel = light.gobo_texel(delt);
+            let offset = texel - Point2D::new(0.5, 0.5);
+            assert!((offset.norm() - 0.5).abs() < 1e-5);
+            assert!(offset.x.abs() < 1e-5 || offset.y.abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn gobo_tints_illumination() {
+        use crate::texture::UniformTexture;
+
+        let light = SpotLight::degrees_new(
+            Point::origin(),
+            Vector::x_axis(),
+            90.,
+            LinearColor::new(1., 1., 1.),
+        )
+        .with_gobo(UniformTexture::new(LinearColor::new(1., 0.5, 0.)).into());
+        let lum = light.illumination(&Point::new(1., 0., 0.));
+        assert_eq!(lum, LinearColor::new(1., 0.5, 0.));
+        let lum = light.illumination(&Point::new(1., 1., 1.));
+        assert_eq!(lum, LinearColor::black());
+    }
+
+    #[test]
+    fn gobo_deserialization_works() {
+        use crate::texture::UniformTexture;
+
+        let yaml = r#"
+            position: [0.0, 0.0, 0.0]
+            direction: [1.0, 0.0, 0.0]
+            fov: 90.0
+            color: {r: 1.0, g: 0.5, b: 0.2}
+            gobo:
+              type: uniform
+              color: {r: 0.5, g: 0.5, b: 0.5}
+        "#;
+        let light: SpotLight = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            light,
+            SpotLight::degrees_new(
+                Point::origin(),
+                Vector::x_axis(),
+                90.,
+                LinearColor::new(1., 0.5, 0.2)
+            )
+            .with_gobo(UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5)).into())
+        )
+    }
+
     #[test]
     fn deserialization_works() {
         let yaml = r#"
@@ -205,4 +421,18 @@ mod test {
             )
         )
     }
+
+    #[test]
+    fn serialization_works() {
+        let light = SpotLight::degrees_new(
+            Point::origin(),
+            Vector::x_axis(),
+            90.,
+            LinearColor::new(1., 0.5, 0.2),
+        );
+        let yaml: serde_yaml::Value = serde_yaml::to_value(&light).unwrap();
+        assert!((yaml["fov"].as_f64().unwrap() - 90.).abs() < 1e-3);
+        let deserialized: SpotLight = serde_yaml::from_value(yaml).unwrap();
+        assert!((deserialized.cosine_value - light.cosine_value).abs() < 1e-6);
+    }
 }
@@ -1,24 +1,364 @@
-use pathtracer::render::Scene;
-use std::path::PathBuf;
+use image::RgbImage;
+use pathtracer::core::{is_pfm, BitDepth, Crop, FrameBuffer};
+use pathtracer::post::Denoiser;
+use pathtracer::render::{Preview, RenderLimits, Scene};
+use pathtracer::serialize::{self, BundleFormat, LookWatcher};
+use pathtracer::{Error, Float};
+use rand::{thread_rng, Rng};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use structopt::StructOpt;
 
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Pack a scene and the meshes and textures it uses into a single .tar or .zip bundle, which
+    /// can be rendered directly.
+    Pack {
+        /// The description of the scene to pack.
+        #[structopt(parse(from_os_str))]
+        scene: PathBuf,
+        /// The bundle to create.
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Check a scene description for mistakes, reporting where each of them was made.
+    Check {
+        /// The description of the scene to check.
+        #[structopt(parse(from_os_str))]
+        scene: PathBuf,
+    },
+}
+
 #[derive(StructOpt, Debug)]
 struct Options {
-    /// Input description for the scene to be rendered.
+    #[structopt(subcommand)]
+    command: Option<Command>,
+    /// Input description for the scene to be rendered, or a bundle created by `pack`.
     #[structopt(short, long, parse(from_os_str), default_value = "scene.yaml")]
     input: PathBuf,
     /// Output image for the rendered scene.
     #[structopt(short, long, parse(from_os_str), default_value = "scene.png")]
     output: PathBuf,
+    /// Also render the scene from each of its named cameras, suffixing the output with their name.
+    #[structopt(short, long)]
+    all_cameras: bool,
+    /// Count the intersection tests made against each object, reporting the most tested ones.
+    #[structopt(short, long)]
+    statistics: bool,
+    /// Also render the shadows cast onto the shadow catcher objects into this image, as black
+    /// with transparency.
+    #[structopt(long, parse(from_os_str))]
+    shadows: Option<PathBuf>,
+    /// Also render the albedo, normal, and depth of the surfaces seen by the camera, and the
+    /// contribution of each named light and of each group of lights, writing them along with the
+    /// image into this multi-layer EXR image.
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["lights", "time_limit"])]
+    aovs: Option<PathBuf>,
+    /// Also draw how noisy each pixel of the image still is into this image, from blue for
+    /// converged pixels to red for pixels whose relative error reaches the heatmap threshold.
+    #[structopt(long, parse(from_os_str), requires = "aovs")]
+    heatmap: Option<PathBuf>,
+    /// The relative error of the pixels drawn in red in the heatmap.
+    #[structopt(long, default_value = "0.05")]
+    heatmap_threshold: Float,
+    /// Also output the contribution of each named light, suffixing the output with its name.
+    #[structopt(short, long)]
+    lights: bool,
+    /// Only render a region of the image, given as 'x,y,width,height' in pixels, or as ratios of
+    /// the image's size when using decimal numbers, e.g: '0.25,0.25,0.5,0.5'.
+    #[structopt(short, long)]
+    crop: Option<Crop>,
+    /// Remove the noise of the rendered images with the given strength, between 0 and 1, guided
+    /// by the albedo and normal of the surfaces seen by the camera.
+    #[structopt(long)]
+    denoise: Option<Float>,
+    /// Draw the outline of the lights over the rendered image, to check the lighting setup.
+    #[structopt(long)]
+    show_lights: bool,
+    /// Stop rendering the image after this many seconds, saving the pixels rendered so far.
+    #[structopt(long, conflicts_with = "lights")]
+    time_limit: Option<f32>,
+    /// The number of bits per channel of the output image, either 8 or 16. Outputs with a '.pfm'
+    /// extension are saved as floating point colors instead, with their full range.
+    #[structopt(long, default_value = "8")]
+    bit_depth: BitDepth,
+    /// Render each frame of the scene's animation into this directory, as 'frame_0001.png', ...
+    #[structopt(long, parse(from_os_str), conflicts_with = "lights")]
+    animation: Option<PathBuf>,
+    /// Write a small preview of the image every given number of seconds while rendering, next to
+    /// the output with a '-preview' suffix.
+    #[structopt(long)]
+    preview: Option<f32>,
+    /// Render progressively until interrupted, saving the image after each pass, and restart
+    /// whenever the scene's named materials and textures, or the files they use, are edited.
+    #[structopt(long, conflicts_with_all = &["lights", "aovs", "animation", "time_limit"])]
+    watch: bool,
+    /// Render the image on the GPU, as a fast approximation of the scene for previews, falling
+    /// back to the CPU when no GPU can be used or the scene is not supported.
+    #[cfg(feature = "gpu")]
+    #[structopt(long, conflicts_with_all = &["lights", "time_limit"])]
+    gpu: bool,
+}
+
+/// The number of objects listed in the statistics report.
+const REPORTED_OBJECTS: usize = 10;
+
+/// Print the most tested objects of the scene.
+fn report_statistics(scene: &Scene) {
+    let stats = match scene.intersection_statistics() {
+        Some(stats) => stats,
+        None => return,
+    };
+    let total: u64 = stats.iter().map(|s| s.tests).sum();
+    eprintln!("{} intersection tests, most tested objects:", total);
+    for s in stats.iter().take(REPORTED_OBJECTS) {
+        let name = s
+            .name
+            .map_or_else(|| format!("#{}", s.index), str::to_string);
+        eprintln!(
+            "{:>20} ({} objects): {} tests ({:.1}%), {:.1}% hits",
+            name,
+            s.objects,
+            s.tests,
+            100. * s.tests as f32 / total.max(1) as f32,
+            100. * s.hit_ratio(),
+        );
+    }
+}
+
+/// Insert a camera's or light's name between the output's file stem and its extension.
+fn camera_output(output: &Path, name: &str) -> PathBuf {
+    let mut file_name = output.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(format!("-{}", name));
+    if let Some(extension) = output.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    output.with_file_name(file_name)
+}
+
+/// Render each frame of the scene's animation into a directory.
+fn render_animation(
+    scene: &mut Scene,
+    directory: &Path,
+    depth: BitDepth,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let frames = scene
+        .frames()
+        .ok_or("the scene does not define any frames")?;
+    std::fs::create_dir_all(directory)?;
+    for frame in frames {
+        scene.set_frame(frame);
+        let path = directory.join(format!("frame_{:04}.png", frame));
+        match depth {
+            BitDepth::Eight => scene.render().save(&path)?,
+            BitDepth::Sixteen => scene.render_buffer().save(&path, depth)?,
+        }
+        eprintln!("Rendered {}", path.display());
+    }
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let options = Options::from_args();
-    let f = std::fs::File::open(options.input)?;
+/// Render the scene progressively into `output`, reloading its looks whenever they are edited.
+fn watch_looks(
+    scene: &mut Scene,
+    input: &Path,
+    output: &Path,
+    depth: BitDepth,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if BundleFormat::from_path(input).is_some() {
+        return Err("bundled scenes cannot be watched".into());
+    }
+    let mut watcher = LookWatcher::new(input)?;
+    let mut image: Option<FrameBuffer> = None;
+    let mut passes = 0;
+    loop {
+        // An edit in progress is reported, and read again on the next pass
+        match watcher.poll() {
+            Ok(Some(looks)) => match scene.set_looks(&looks) {
+                Ok(()) => {
+                    eprintln!("Looks changed, restarting the render");
+                    passes = 0;
+                }
+                Err(err) => eprintln!("warning: {}", err),
+            },
+            Ok(None) => {}
+            Err(err) => eprintln!("warning: {}", err),
+        }
+        let (pass, _) = scene.render_buffer_with_limits(thread_rng().gen(), &RenderLimits::new());
+        passes += 1;
+        let image = match &mut image {
+            Some(image) if passes > 1 => {
+                image.blend(&pass, 1. / passes as Float)?;
+                image
+            }
+            _ => image.insert(pass),
+        };
+        image.save(output, depth)?;
+        eprintln!("Rendered pass {}", passes);
+    }
+}
+
+/// Render the scene on the GPU if asked to, falling back to the CPU when it cannot be used.
+#[cfg(feature = "gpu")]
+fn render_gpu(scene: &Scene, options: &Options) -> Option<RgbImage> {
+    if !options.gpu {
+        return None;
+    }
+    let (image, err) = scene.render_gpu_with_seed(thread_rng().gen());
+    if let Some(err) = err {
+        eprintln!(
+            "warning: {}, the image was rendered on the CPU instead",
+            err
+        );
+    }
+    Some(image)
+}
+
+#[cfg(not(feature = "gpu"))]
+fn render_gpu(_: &Scene, _: &Options) -> Option<RgbImage> {
+    None
+}
+
+/// Load the scene to render, extracting it first if it is bundled.
+fn load_scene(input: &Path) -> pathtracer::Result<Scene> {
+    let parse = |description: String| {
+        serde_yaml::from_str(&description).map_err(|err| Error::from(err).with_path(input))
+    };
+    if BundleFormat::from_path(input).is_none() {
+        let diagnostics = serialize::validate(input)?;
+        for diagnostic in &diagnostics {
+            eprintln!("error: {}", diagnostic);
+        }
+        if !diagnostics.is_empty() {
+            return Err(Error::Parse {
+                path: Some(input.to_path_buf()),
+                line: None,
+                message: format!("{} problem(s) found", diagnostics.len()),
+            });
+        }
+        return parse(serialize::load_description(input)?);
+    }
+    let directory = std::env::temp_dir().join(format!("pathtracer-{}", std::process::id()));
+    let scene = serialize::unpack(input, &directory).and_then(parse);
+    // Every file has been loaded along with the scene, they are not needed anymore
+    std::fs::remove_dir_all(&directory).ok();
+    scene
+}
+
+fn main() {
+    // Errors are shown as they are displayed, rather than their debug representation
+    if let Err(err) = run(Options::from_args()) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run(options: Options) -> Result<(), Box<dyn std::error::Error>> {
+    match &options.command {
+        Some(Command::Pack { scene, output }) => {
+            serialize::pack(scene, output)?;
+            return Ok(());
+        }
+        Some(Command::Check { scene }) => {
+            let diagnostics = serialize::validate(scene)?;
+            for diagnostic in &diagnostics {
+                println!("{}", diagnostic);
+            }
+            if diagnostics.is_empty() {
+                return Ok(());
+            }
+            return Err(format!("{} problem(s) found", diagnostics.len()).into());
+        }
+        None => {}
+    }
+
+    let mut scene = load_scene(&options.input)?;
+    if let Some(err) = scene.bvh_cache_error() {
+        eprintln!("warning: could not cache the BVH: {}", err);
+    }
+    if options.statistics {
+        scene.enable_statistics();
+    }
+    if let Some(strength) = options.denoise {
+        scene.set_denoiser(Some(Denoiser::new(strength)));
+    }
+    if options.crop.is_some() {
+        scene.set_crop(options.crop);
+    }
+    if let Some(seconds) = options.preview {
+        let path = camera_output(&options.output, "preview").with_extension("png");
+        scene.set_preview(Some(Preview::new(path, Duration::from_secs_f32(seconds))));
+    }
+    if options.watch {
+        return watch_looks(
+            &mut scene,
+            &options.input,
+            &options.output,
+            options.bit_depth,
+        );
+    }
+    if let Some(directory) = &options.animation {
+        render_animation(&mut scene, directory, options.bit_depth)?;
+        report_statistics(&scene);
+        return Ok(());
+    }
+    let limits = match options.time_limit {
+        Some(seconds) => RenderLimits::new().with_time_limit(Duration::from_secs_f32(seconds)),
+        None => RenderLimits::new(),
+    };
+    let partial = || eprintln!("Time limit reached, the image is only partially rendered");
+    if let Some(path) = &options.aovs {
+        if options.show_lights {
+            return Err("the lights can only be shown in 8-bit images".into());
+        }
+        let aovs = scene.render_aovs();
+        aovs.save_exr(path)?;
+        aovs.beauty.save(&options.output, options.bit_depth)?;
+        if let Some(heatmap) = &options.heatmap {
+            let image = aovs.heatmap(options.heatmap_threshold);
+            image.save(heatmap, options.bit_depth)?;
+        }
+    } else if options.bit_depth == BitDepth::Sixteen || is_pfm(&options.output) {
+        if options.lights || options.show_lights {
+            return Err("the lights can only be shown in 8-bit images".into());
+        }
+        let (buffer, complete) = scene.render_buffer_with_limits(thread_rng().gen(), &limits);
+        if !complete {
+            partial();
+        }
+        buffer.save(&options.output, options.bit_depth)?;
+    } else {
+        let mut image = if options.lights {
+            let (image, lights) = scene.render_lights();
+            for (name, image) in lights {
+                image.save(camera_output(&options.output, &format!("light-{}", name)))?;
+            }
+            image
+        } else if let Some(image) = render_gpu(&scene, &options) {
+            image
+        } else {
+            let (image, complete) = scene.render_with_limits(thread_rng().gen(), &limits);
+            if !complete {
+                partial();
+            }
+            image
+        };
+        if options.show_lights {
+            scene.draw_lights(&mut image);
+        }
+        image.save(&options.output)?;
+    }
 
-    let scene: Scene = serde_yaml::from_reader(f)?;
-    let image = scene.render();
+    if let Some(shadows) = &options.shadows {
+        scene.render_shadows().save(shadows)?;
+    }
 
-    image.save(options.output)?;
+    if options.all_cameras {
+        for (name, image) in scene.render_cameras() {
+            image.save(camera_output(&options.output, name))?;
+        }
+    }
+    report_statistics(&scene);
     Ok(())
 }
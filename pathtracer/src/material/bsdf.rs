@@ -0,0 +1,154 @@
+use super::{MixBsdf, PrincipledBsdf};
+use crate::core::{sampling, LinearColor};
+use crate::{Float, Vector};
+use nalgebra::Unit;
+
+/// All the existing `Bsdf` implementation.
+#[allow(missing_docs)]
+// Only grows past the lint's threshold with an `f64` `Float`, not worth an allocation per hit
+#[allow(clippy::large_enum_variant)]
+#[enum_dispatch::enum_dispatch]
+#[derive(Clone, Debug, PartialEq)]
+pub enum BsdfEnum {
+    MixBsdf,
+    PrincipledBsdf,
+}
+
+/// Represent how light is scattered at a point of a surface.
+///
+/// Directions point away from the surface: `outgoing` towards the viewer, `incoming` towards the
+/// light.
+#[enum_dispatch::enum_dispatch(BsdfEnum)]
+pub trait Bsdf: std::fmt::Debug {
+    /// Get the ratio of light coming from `incoming` which is scattered towards `outgoing`,
+    /// ignoring perfectly specular interactions.
+    fn eval(&self, outgoing: &Unit<Vector>, incoming: &Unit<Vector>) -> LinearColor;
+
+    /// Get the probability density with which `sample` chooses `incoming`, ignoring perfectly
+    /// specular interactions.
+    fn pdf(&self, outgoing: &Unit<Vector>, incoming: &Unit<Vector>) -> Float;
+
+    /// Choose an incoming direction from three uniform random numbers in `[0, 1)`.
+    fn sample(&self, outgoing: &Unit<Vector>, u: [Float; 3]) -> Option<BsdfSample>;
+
+    /// Blur the glossy lobes so that their roughness is at least `min_roughness`, trading some
+    /// bias for less noise. Perfectly specular interactions are left untouched.
+    fn regularize(&mut self, _min_roughness: Float) {}
+}
+
+/// A direction sampled by a [`Bsdf`].
+///
+/// [`Bsdf`]: trait.Bsdf.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct BsdfSample {
+    /// The sampled direction, pointing away from the surface.
+    pub incoming: Unit<Vector>,
+    /// The value of the BSDF for that direction.
+    pub value: LinearColor,
+    /// The probability density with which the direction was sampled.
+    pub pdf: Float,
+    /// Whether the direction was chosen by a perfectly specular interaction, for which `value`
+    /// and `pdf` are not densities.
+    pub is_delta: bool,
+}
+
+impl BsdfSample {
+    /// The factor by which the light coming from the sampled direction is scaled, taking into
+    /// account the probability of sampling it.
+    pub fn weight(&self, normal: &Unit<Vector>) -> LinearColor {
+        if self.pdf <= 0. {
+            return LinearColor::black();
+        }
+        self.value.clone() * (self.incoming.dot(normal).abs() / self.pdf)
+    }
+}
+
+/// An orthonormal basis around a normal, used to express directions in the shading space.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Frame {
+    tangent: Vector,
+    bitangent: Vector,
+    normal: Unit<Vector>,
+}
+
+impl Frame {
+    pub(crate) fn new(normal: Unit<Vector>) -> Self {
+        let (tangent, bitangent) = sampling::orthonormal_basis(&normal);
+        Frame {
+            tangent,
+            bitangent,
+            normal,
+        }
+    }
+
+    /// Creates a frame around a normal, aligned with the projection of a tangent on its plane.
+    pub(crate) fn with_tangent(normal: Unit<Vector>, tangent: Vector) -> Self {
+        let tangent = tangent - normal.as_ref() * tangent.dot(&normal);
+        let norm = tangent.norm();
+        if norm < 1e-6 {
+            return Frame::new(normal);
+        }
+        let tangent = tangent / norm;
+        Frame {
+            tangent,
+            bitangent: normal.cross(&tangent),
+            normal,
+        }
+    }
+
+    pub(crate) fn to_local(&self, world: &Vector) -> Vector {
+        Vector::new(
+            world.dot(&self.tangent),
+            world.dot(&self.bitangent),
+            world.dot(&self.normal),
+        )
+    }
+
+    pub(crate) fn to_world(&self, local: &Vector) -> Unit<Vector> {
+        Unit::new_normalize(
+            self.tangent * local.x + self.bitangent * local.y + self.normal.as_ref() * local.z,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn frame_works() {
+        let normal = Unit::new_normalize(Vector::new(1., 2., 3.));
+        let frame = Frame::new(normal);
+        assert!((frame.to_world(&Vector::z()).into_inner() - normal.into_inner()).norm() < 1e-6);
+        assert!(frame.to_world(&Vector::x()).dot(&normal).abs() < 1e-6);
+        assert!(frame.to_world(&Vector::y()).dot(&normal).abs() < 1e-6);
+    }
+
+    #[test]
+    fn frame_with_tangent_works() {
+        let normal = Vector::z_axis();
+        let frame = Frame::with_tangent(normal, Vector::new(1., 1., 5.));
+        // The tangent is projected on the plane of the normal
+        let tangent = frame.to_world(&Vector::x());
+        assert!((tangent.into_inner() - Vector::new(1., 1., 0.).normalize()).norm() < 1e-6);
+        let local = frame.to_local(&Vector::new(0., 2., 3.));
+        assert!(
+            (frame.to_world(&local).into_inner() - Vector::new(0., 2., 3.).normalize()).norm()
+                < 1e-6
+        );
+    }
+
+    #[test]
+    fn weight_works() {
+        let sample = BsdfSample {
+            incoming: Vector::y_axis(),
+            value: LinearColor::new(1., 0.5, 0.),
+            pdf: 0.5,
+            is_delta: false,
+        };
+        assert_eq!(
+            sample.weight(&Vector::y_axis()),
+            LinearColor::new(2., 1., 0.)
+        );
+    }
+}
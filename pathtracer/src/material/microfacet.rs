@@ -0,0 +1,167 @@
+//! Microfacet distributions and Fresnel terms shared by physically based materials.
+
+use crate::consts::PI;
+use crate::core::sampling::spherical;
+use crate::core::LinearColor;
+use crate::{Float, Vector};
+
+/// The smallest roughness used, to avoid numerical issues with perfectly smooth distributions.
+pub(crate) const MIN_ALPHA: Float = 1e-3;
+
+/// The Smith masking term of the GGX distribution, for a cosine with the surface normal.
+pub(crate) fn smith_g1(cos: Float, alpha: Float) -> Float {
+    let a2 = alpha * alpha;
+    let cos = cos.abs();
+    2. * cos / (cos + (a2 + (1. - a2) * cos * cos).sqrt())
+}
+
+/// The Smith masking term of the anisotropic GGX distribution, for a direction in local space.
+pub(crate) fn smith_g1_anisotropic(direction: &Vector, alpha_x: Float, alpha_y: Float) -> Float {
+    let sin2 = direction.x * direction.x + direction.y * direction.y;
+    if sin2 <= 0. {
+        return smith_g1(direction.z, alpha_x);
+    }
+    // The roughness of the distribution projected along the direction's azimuth
+    let (x, y) = (direction.x * alpha_x, direction.y * alpha_y);
+    smith_g1(direction.z, ((x * x + y * y) / sin2).sqrt())
+}
+
+/// The "Charlie" sheen distribution of Estevez and Kulla, for a cosine with the surface normal,
+/// whose fibers lying along the surface reflect the most light at grazing angles.
+pub(crate) fn charlie_d(cos_h: Float, alpha: Float) -> Float {
+    let inverse = 1. / alpha;
+    let sin2 = (1. - cos_h * cos_h).max(0.);
+    (2. + inverse) * sin2.powf(inverse / 2.) / (2. * PI)
+}
+
+/// Neubelt and Pettineo's visibility term of the sheen distribution, which replaces both the
+/// masking term and the `4 * cos_o * cos_i` denominator of the microfacet BRDF.
+pub(crate) fn charlie_visibility(cos_o: Float, cos_i: Float) -> Float {
+    1. / (4. * (cos_i + cos_o - cos_i * cos_o))
+}
+
+/// The GTR1 (Berry) distribution used by the Disney clearcoat.
+pub(crate) fn gtr1_d(cos_h: Float, alpha: Float) -> Float {
+    let a2 = alpha * alpha;
+    (a2 - 1.) / (PI * a2.ln() * (1. + (a2 - 1.) * cos_h * cos_h))
+}
+
+/// Sample a half-vector in local space, proportionally to `gtr1_d(cos_h) * cos_h`.
+pub(crate) fn gtr1_sample(alpha: Float, u: Float, v: Float) -> Vector {
+    let a2 = alpha * alpha;
+    let cos2 = (1. - a2.powf(1. - u)) / (1. - a2);
+    spherical(cos2.max(0.).sqrt(), 2. * PI * v)
+}
+
+/// Schlick's weight `(1 - cos)^5`.
+pub(crate) fn schlick_weight(cos: Float) -> Float {
+    let m = (1. - cos).clamp(0., 1.);
+    // NaN cosines, e.g: from degenerate half vectors, give no weight
+    let m = if m.is_nan() { 0. } else { m };
+    let m2 = m * m;
+    m2 * m2 * m
+}
+
+/// Schlick's approximation of the Fresnel reflectance.
+pub(crate) fn schlick(f0: &LinearColor, cos: Float) -> LinearColor {
+    let w = schlick_weight(cos);
+    f0.clone() * (1. - w) + LinearColor::new(w, w, w)
+}
+
+/// The exact Fresnel reflectance of an unpolarized dielectric interface, given the ratio of
+/// indices of refraction `eta = n_incident / n_transmitted`.
+pub(crate) fn fresnel_dielectric(cos_i: Float, eta: Float) -> Float {
+    let cos_i = cos_i.abs().min(1.);
+    let sin2_t = eta * eta * (1. - cos_i * cos_i);
+    if sin2_t >= 1. {
+        // Total internal reflection
+        return 1.;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    let r_s = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_p = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_s * r_s + r_p * r_p) / 2.
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::sampling::{ggx_d, ggx_half_vector};
+
+    /// Integrate `f(cos) * cos` over the hemisphere, with a midpoint rule on the cosine.
+    fn integrate(f: impl Fn(Float) -> Float) -> Float {
+        let steps = 100_000;
+        (0..steps)
+            .map(|i| {
+                let cos = (i as Float + 0.5) / steps as Float;
+                f(cos) * cos * 2. * PI / steps as Float
+            })
+            .sum()
+    }
+
+    /// A local direction from its cosine with the Z axis, in the XZ plane.
+    fn from_cos(cos: Float) -> Vector {
+        spherical(cos, 0.)
+    }
+
+    #[test]
+    fn ggx_is_normalized() {
+        for &alpha in &[0.1, 0.5, 1.] {
+            assert!((integrate(|cos| ggx_d(&from_cos(cos), alpha, alpha)) - 1.).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn charlie_is_normalized() {
+        for &alpha in &[0.1, 0.5, 1.] {
+            assert!((integrate(|cos| charlie_d(cos, alpha)) - 1.).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn anisotropic_ggx_is_normalized() {
+        // Integrate over the azimuth too, with a midpoint rule
+        let steps = 64;
+        let total: Float = (0..steps)
+            .map(|i| {
+                let phi = 2. * PI * (i as Float + 0.5) / steps as Float;
+                let d = |cos| ggx_d(&spherical(cos, phi), 0.2, 0.6);
+                integrate(d) / steps as Float
+            })
+            .sum();
+        assert!((total - 1.).abs() < 1e-2, "{}", total);
+    }
+
+    #[test]
+    fn anisotropic_masking_works() {
+        let direction = spherical(0.5, 0.);
+        // Only the roughness along the direction's azimuth matters
+        let g1 = smith_g1_anisotropic(&direction, 0.3, 0.9);
+        assert!((g1 - smith_g1(0.5, 0.3)).abs() < 1e-6);
+        let across = spherical(0.5, PI / 2.);
+        assert!((smith_g1_anisotropic(&across, 0.3, 0.9) - smith_g1(0.5, 0.9)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn gtr1_is_normalized() {
+        for &alpha in &[0.1, 0.5, 0.9] {
+            assert!((integrate(|cos| gtr1_d(cos, alpha)) - 1.).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn samples_are_normalized() {
+        for &(u, v) in &[(0., 0.), (0.5, 0.25), (0.99, 0.9)] {
+            assert!((ggx_half_vector(0.3, 0.1, u, v).norm() - 1.).abs() < 1e-5);
+            assert!((gtr1_sample(0.3, u, v).norm() - 1.).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn fresnel_dielectric_works() {
+        // 4% reflectance at normal incidence for glass
+        assert!((fresnel_dielectric(1., 1. / 1.5) - 0.04).abs() < 1e-5);
+        assert_eq!(fresnel_dielectric(0.1, 1.5), 1.);
+        assert!((schlick(&LinearColor::black(), 0.).r - 1.).abs() < 1e-6);
+    }
+}
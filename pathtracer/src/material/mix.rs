@@ -0,0 +1,442 @@
+use super::bsdf::{Bsdf, BsdfEnum, BsdfSample};
+use super::{Material, MaterialEnum, SurfaceInput, SurfaceProbe, SurfaceSignals};
+use crate::core::{LightProperties, LinearColor};
+use crate::texture::{Texture, TextureEnum};
+use crate::{Float, Point2D, Vector};
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// The weight of the second material of a [`MixMaterial`].
+///
+/// [`MixMaterial`]: struct.MixMaterial.html
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum MixFactor {
+    /// The same weight everywhere.
+    Constant(Float),
+    /// A weight given by the luminance of a mask texture.
+    Mask(TextureEnum),
+    /// A weight computed from the geometry around the shading point.
+    Surface(SurfaceInput),
+}
+
+impl MixFactor {
+    /// Get the weight at a given texel coordinate.
+    ///
+    /// A surface input must have been replaced by its value beforehand, see [`with_surface`], it
+    /// is otherwise treated as an open, flat, surface.
+    ///
+    /// [`with_surface`]: ../trait.Material.html#method.with_surface
+    pub fn at(&self, point: Point2D) -> Float {
+        match self {
+            MixFactor::Constant(factor) => *factor,
+            MixFactor::Mask(mask) => mask.texel_value(point),
+            MixFactor::Surface(input) => input.at(&SurfaceSignals::open(Vector::z_axis())),
+        }
+    }
+}
+
+/// A material interpolating between two others, e.g: to paint patches of rust on a metal.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct MixMaterial {
+    /// The material used where the factor is 0.0.
+    first: Box<MaterialEnum>,
+    /// The material used where the factor is 1.0.
+    second: Box<MaterialEnum>,
+    /// The weight of the second material.
+    factor: MixFactor,
+}
+
+impl MixMaterial {
+    /// Creates a new `MixMaterial`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{LightProperties, LinearColor};
+    /// # use pathtracer::material::{MixFactor, MixMaterial, UniformMaterial};
+    /// #
+    /// let matte = UniformMaterial::new(
+    ///     LightProperties::new(
+    ///         LinearColor::new(1.0, 0.0, 0.0), // diffuse component
+    ///         LinearColor::new(0.0, 0.0, 0.0), // specular component
+    ///         None,
+    ///     ),
+    /// );
+    /// let shiny = UniformMaterial::new(
+    ///     LightProperties::new(
+    ///         LinearColor::new(0.0, 0.0, 0.0), // diffuse component
+    ///         LinearColor::new(1.0, 1.0, 1.0), // specular component
+    ///         None,
+    ///     ),
+    /// );
+    /// let mix = MixMaterial::new(matte.into(), shiny.into(), MixFactor::Constant(0.25));
+    /// ```
+    pub fn new(first: MaterialEnum, second: MaterialEnum, factor: MixFactor) -> Self {
+        MixMaterial {
+            first: Box::new(first),
+            second: Box::new(second),
+            factor,
+        }
+    }
+}
+
+impl Material for MixMaterial {
+    fn properties(&self, point: Point2D) -> LightProperties {
+        let factor = self.factor.at(point);
+        let first = self.first.properties(point);
+        first.mix(&self.second.properties(point), factor)
+    }
+
+    fn light_response(&self, point: Point2D, diffuse: Float, specular: Float) -> (Float, Float) {
+        let factor = self.factor.at(point);
+        let first = self.first.light_response(point, diffuse, specular);
+        let second = self.second.light_response(point, diffuse, specular);
+        (
+            first.0 * (1. - factor) + second.0 * factor,
+            first.1 * (1. - factor) + second.1 * factor,
+        )
+    }
+
+    /// Only returns a BSDF if both materials have one.
+    fn bsdf(&self, point: Point2D, normal: &Unit<Vector>, color: &LinearColor) -> Option<BsdfEnum> {
+        let factor = self.factor.at(point).clamp(0., 1.);
+        // NaN factors keep the first material, as factors below 0 do
+        let factor = if factor.is_nan() { 0. } else { factor };
+        let first = self.first.bsdf(point, normal, color)?;
+        let second = self.second.bsdf(point, normal, color)?;
+        Some(MixBsdf::new(first, second, factor).into())
+    }
+
+    fn translucency(&self, point: Point2D) -> Option<LinearColor> {
+        let first = self.first.translucency(point);
+        let second = self.second.translucency(point);
+        if first.is_none() && second.is_none() {
+            return None;
+        }
+        // Opaque surfaces do not let anything through
+        let factor = self.factor.at(point);
+        let first = first.unwrap_or_else(LinearColor::black);
+        let second = second.unwrap_or_else(LinearColor::black);
+        Some(first * (1. - factor) + second * factor)
+    }
+
+    /// Each material is chosen proportionally to its weight, only the chosen one being cut out.
+    fn is_cut_out(&self, point: Point2D, u: Float) -> bool {
+        // Choose a material, and remap the random number used to do so back into [0, 1)
+        let factor = self.factor.at(point).clamp(0., 1.);
+        // NaN factors keep the first material, as factors below 0 do
+        let factor = if factor.is_nan() { 0. } else { factor };
+        if u < factor {
+            self.second.is_cut_out(point, u / factor)
+        } else {
+            self.first.is_cut_out(point, (u - factor) / (1. - factor))
+        }
+    }
+
+    fn emission(&self, point: Point2D) -> LinearColor {
+        let factor = self.factor.at(point);
+        self.first.emission(point) * (1. - factor) + self.second.emission(point) * factor
+    }
+
+    fn absorption(&self, point: Point2D) -> Option<LinearColor> {
+        let first = self.first.absorption(point);
+        let second = self.second.absorption(point);
+        if first.is_none() && second.is_none() {
+            return None;
+        }
+        // A clear medium does not absorb anything
+        let factor = self.factor.at(point);
+        let first = first.unwrap_or_else(LinearColor::black);
+        let second = second.unwrap_or_else(LinearColor::black);
+        Some(first * (1. - factor) + second * factor)
+    }
+
+    fn surface_probe(&self) -> Option<SurfaceProbe> {
+        let factor = match &self.factor {
+            MixFactor::Surface(input) => Some(input.probe()),
+            _ => None,
+        };
+        [
+            factor,
+            self.first.surface_probe(),
+            self.second.surface_probe(),
+        ]
+        .iter()
+        .flatten()
+        .copied()
+        .fold(None, |acc, probe| match acc {
+            Some(acc) => Some(probe.merge(acc)),
+            None => Some(probe),
+        })
+    }
+
+    fn with_surface(&self, signals: &SurfaceSignals) -> Option<MaterialEnum> {
+        self.surface_probe()?;
+        let factor = match &self.factor {
+            MixFactor::Surface(input) => MixFactor::Constant(input.at(signals)),
+            factor => factor.clone(),
+        };
+        let resolve = |material: &MaterialEnum| {
+            material
+                .with_surface(signals)
+                .unwrap_or_else(|| material.clone())
+        };
+        Some(MixMaterial::new(resolve(&self.first), resolve(&self.second), factor).into())
+    }
+}
+
+/// The BSDF of a [`MixMaterial`], a weighted sum of two BSDFs.
+///
+/// [`MixMaterial`]: struct.MixMaterial.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct MixBsdf {
+    first: Box<BsdfEnum>,
+    second: Box<BsdfEnum>,
+    factor: Float,
+}
+
+impl MixBsdf {
+    /// Creates a new `MixBsdf`, `factor` being the weight of the second BSDF.
+    pub fn new(first: BsdfEnum, second: BsdfEnum, factor: Float) -> Self {
+        MixBsdf {
+            first: Box::new(first),
+            second: Box::new(second),
+            factor,
+        }
+    }
+}
+
+impl Bsdf for MixBsdf {
+    fn eval(&self, outgoing: &Unit<Vector>, incoming: &Unit<Vector>) -> LinearColor {
+        self.first.eval(outgoing, incoming) * (1. - self.factor)
+            + self.second.eval(outgoing, incoming) * self.factor
+    }
+
+    fn pdf(&self, outgoing: &Unit<Vector>, incoming: &Unit<Vector>) -> Float {
+        self.first.pdf(outgoing, incoming) * (1. - self.factor)
+            + self.second.pdf(outgoing, incoming) * self.factor
+    }
+
+    fn sample(&self, outgoing: &Unit<Vector>, u: [Float; 3]) -> Option<BsdfSample> {
+        // Choose a BSDF, and remap the random number used to do so back into [0, 1)
+        let (chosen, probability, u0) = if u[0] < self.factor {
+            (&self.second, self.factor, u[0] / self.factor)
+        } else {
+            (
+                &self.first,
+                1. - self.factor,
+                (u[0] - self.factor) / (1. - self.factor),
+            )
+        };
+        let sample = chosen.sample(outgoing, [u0.min(0.99999), u[1], u[2]])?;
+        if sample.is_delta {
+            // The other BSDF cannot have chosen the same direction
+            return Some(BsdfSample {
+                value: sample.value * probability,
+                pdf: sample.pdf * probability,
+                ..sample
+            });
+        }
+        let pdf = self.pdf(outgoing, &sample.incoming);
+        if pdf <= 0. {
+            return None;
+        }
+        Some(BsdfSample {
+            value: self.eval(outgoing, &sample.incoming),
+            pdf,
+            ..sample
+        })
+    }
+
+    fn regularize(&mut self, min_roughness: Float) {
+        self.first.regularize(min_roughness);
+        self.second.regularize(min_roughness);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::{PrincipledMaterial, SurfaceSignal, UniformMaterial};
+    use crate::texture::UniformTexture;
+
+    fn uniform(diffuse: Float) -> MaterialEnum {
+        let diffuse = LinearColor::new(diffuse, diffuse, diffuse);
+        UniformMaterial::new(LightProperties::new(diffuse, LinearColor::black(), None)).into()
+    }
+
+    #[test]
+    fn new_works() {
+        let mix = MixMaterial::new(uniform(0.), uniform(1.), MixFactor::Constant(0.5));
+        assert_eq!(
+            mix,
+            MixMaterial {
+                first: Box::new(uniform(0.)),
+                second: Box::new(uniform(1.)),
+                factor: MixFactor::Constant(0.5),
+            }
+        )
+    }
+
+    #[test]
+    fn properties_works() {
+        let mask = UniformTexture::new(LinearColor::new(0.25, 0.25, 0.25));
+        let mix = MixMaterial::new(uniform(0.), uniform(1.), MixFactor::Mask(mask.into()));
+        assert_eq!(
+            mix.properties(Point2D::origin()),
+            LightProperties::new(
+                LinearColor::new(0.25, 0.25, 0.25),
+                LinearColor::black(),
+                None
+            )
+        )
+    }
+
+    #[test]
+    fn bsdf_needs_both_materials() {
+        let principled = PrincipledMaterial::new(LinearColor::new(1., 1., 1.));
+        let white = LinearColor::new(1., 1., 1.);
+        let normal = Vector::z_axis();
+        let mix = MixMaterial::new(
+            uniform(0.),
+            principled.clone().into(),
+            MixFactor::Constant(0.5),
+        );
+        assert!(mix.bsdf(Point2D::origin(), &normal, &white).is_none());
+        let mix = MixMaterial::new(
+            principled.clone().into(),
+            principled.into(),
+            MixFactor::Constant(0.5),
+        );
+        assert!(mix.bsdf(Point2D::origin(), &normal, &white).is_some());
+    }
+
+    #[test]
+    fn bsdf_sample_matches_pdf() {
+        let white = LinearColor::new(1., 1., 1.);
+        let normal = Vector::z_axis();
+        let matte = PrincipledMaterial {
+            roughness: 1.,
+            ..PrincipledMaterial::new(LinearColor::new(1., 0., 0.))
+        };
+        let metal = PrincipledMaterial {
+            metallic: 1.,
+            roughness: 0.2,
+            ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+        };
+        let first = matte.bsdf(Point2D::origin(), &normal, &white).unwrap();
+        let second = metal.bsdf(Point2D::origin(), &normal, &white).unwrap();
+        let bsdf = MixBsdf::new(first, second, 0.3);
+        let outgoing = Unit::new_normalize(Vector::new(0.2, -0.1, 1.));
+        for i in 0..10 {
+            let u = [i as Float / 10., 0.35, 0.8];
+            if let Some(sample) = bsdf.sample(&outgoing, u) {
+                let pdf = bsdf.pdf(&outgoing, &sample.incoming);
+                assert!((sample.pdf - pdf).abs() <= 1e-3 * pdf);
+                assert_eq!(sample.value, bsdf.eval(&outgoing, &sample.incoming));
+            }
+        }
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            first:
+              type: uniform
+              diffuse: {r: 0.0, g: 0.0, b: 0.0}
+              specular: {r: 0.0, g: 0.0, b: 0.0}
+            second:
+              type: uniform
+              diffuse: {r: 1.0, g: 1.0, b: 1.0}
+              specular: {r: 0.0, g: 0.0, b: 0.0}
+            factor: 0.5
+        "#;
+        let mix: MixMaterial = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            mix,
+            MixMaterial::new(uniform(0.), uniform(1.), MixFactor::Constant(0.5))
+        )
+    }
+
+    #[test]
+    fn mask_deserialization_works() {
+        let yaml = r#"
+            first:
+              type: uniform
+              diffuse: {r: 0.0, g: 0.0, b: 0.0}
+              specular: {r: 0.0, g: 0.0, b: 0.0}
+            second:
+              type: uniform
+              diffuse: {r: 1.0, g: 1.0, b: 1.0}
+              specular: {r: 0.0, g: 0.0, b: 0.0}
+            factor:
+              type: uniform
+              color: {r: 0.5, g: 0.5, b: 0.5}
+        "#;
+        let mix: MixMaterial = serde_yaml::from_str(yaml).unwrap();
+        let mask = UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5));
+        assert_eq!(
+            mix,
+            MixMaterial::new(uniform(0.), uniform(1.), MixFactor::Mask(mask.into()))
+        )
+    }
+
+    #[test]
+    fn with_surface_works() {
+        let inner = MixMaterial::new(
+            uniform(0.),
+            uniform(1.),
+            MixFactor::Surface(SurfaceInput::new(SurfaceSignal::Occlusion, 0.5)),
+        );
+        let mix = MixMaterial::new(
+            inner.into(),
+            uniform(1.),
+            MixFactor::Surface(SurfaceInput::new(SurfaceSignal::Curvature, 0.1).with_samples(32)),
+        );
+        assert_eq!(
+            mix.surface_probe(),
+            Some(SurfaceProbe {
+                radius: 0.5,
+                samples: 32,
+            })
+        );
+        let signals = SurfaceSignals {
+            occlusion: 0.25,
+            curvature: 0.75,
+            bent_normal: Vector::z_axis(),
+        };
+        let inner = MixMaterial::new(uniform(0.), uniform(1.), MixFactor::Constant(0.25));
+        assert_eq!(
+            mix.with_surface(&signals),
+            Some(MixMaterial::new(inner.into(), uniform(1.), MixFactor::Constant(0.75)).into())
+        );
+        // Nothing to resolve without surface inputs
+        let plain = MixMaterial::new(uniform(0.), uniform(1.), MixFactor::Constant(0.5));
+        assert_eq!(plain.surface_probe(), None);
+        assert_eq!(plain.with_surface(&signals), None);
+    }
+
+    #[test]
+    fn surface_deserialization_works() {
+        let yaml = r#"
+            first:
+              type: uniform
+              diffuse: {r: 0.0, g: 0.0, b: 0.0}
+              specular: {r: 0.0, g: 0.0, b: 0.0}
+            second:
+              type: uniform
+              diffuse: {r: 1.0, g: 1.0, b: 1.0}
+              specular: {r: 0.0, g: 0.0, b: 0.0}
+            factor:
+              type: occlusion
+              radius: 0.5
+        "#;
+        let mix: MixMaterial = serde_yaml::from_str(yaml).unwrap();
+        let input = SurfaceInput::new(SurfaceSignal::Occlusion, 0.5);
+        assert_eq!(
+            mix,
+            MixMaterial::new(uniform(0.), uniform(1.), MixFactor::Surface(input))
+        )
+    }
+}
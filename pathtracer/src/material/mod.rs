@@ -1,18 +1,31 @@
 //! Various material implementations
 
-use super::core::LightProperties;
-use super::Point2D;
-use serde::Deserialize;
+use super::core::{LightProperties, LinearColor};
+use super::{Point2D, Vector};
+use crate::Float;
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
 
 /// All the existing `Material` implementation.
 #[serde(tag = "type")]
 #[serde(rename_all = "lowercase")]
 #[allow(missing_docs)]
 #[enum_dispatch::enum_dispatch]
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum MaterialEnum {
     #[serde(rename = "uniform")]
     UniformMaterial,
+    #[serde(rename = "principled")]
+    PrincipledMaterial,
+    #[serde(rename = "mix")]
+    MixMaterial,
+    #[serde(rename = "toon")]
+    ToonMaterial,
+    /// Given by name in the scene description, see [`SharedMaterial`]
+    ///
+    /// [`SharedMaterial`]: struct.SharedMaterial.html
+    #[serde(skip)]
+    SharedMaterial,
 }
 
 /// Represent the physical light properties of an object in the scene;
@@ -20,7 +33,85 @@ pub enum MaterialEnum {
 pub trait Material: std::fmt::Debug {
     /// Get the physical properties at a point.
     fn properties(&self, point: Point2D) -> LightProperties;
+
+    /// Get the fraction of the light coming from a direction which is scattered by the diffuse and
+    /// specular components of the material's [`properties`], given the cosine of that direction
+    /// with the normal and with the reflected ray, e.g: to quantize the shading of stylized
+    /// materials. Only used by materials without a BSDF.
+    ///
+    /// [`properties`]: #tymethod.properties
+    fn light_response(&self, _point: Point2D, diffuse: Float, specular: Float) -> (Float, Float) {
+        (diffuse, specular)
+    }
+
+    /// Get the BSDF at a point, given its normal and the object's texture color, for materials
+    /// which are physically based.
+    fn bsdf(
+        &self,
+        _point: Point2D,
+        _normal: &Unit<Vector>,
+        _color: &LinearColor,
+    ) -> Option<BsdfEnum> {
+        None
+    }
+
+    /// Get the color by which a surface attenuates the shadow rays going through it, or `None` if
+    /// it fully blocks them, e.g: the color of a thin translucent surface, or the transmission of
+    /// a transparent one, which casts tinted shadows.
+    fn translucency(&self, _point: Point2D) -> Option<LinearColor> {
+        None
+    }
+
+    /// Return whether the surface is cut out at a point, rays going through it as if it was not
+    /// there, e.g: around a leaf drawn on a simple quad. `u` is a uniform random number, for
+    /// surfaces which are cut out stochastically.
+    fn is_cut_out(&self, _point: Point2D, _u: Float) -> bool {
+        false
+    }
+
+    /// Get the light emitted by the surface at a point, towards every direction in front of it.
+    fn emission(&self, _point: Point2D) -> LinearColor {
+        LinearColor::black()
+    }
+
+    /// Get the absorption coefficient, per unit of distance, of the medium enclosed by a
+    /// transparent object at the point where a ray enters it, or `None` if the medium is clear.
+    fn absorption(&self, _point: Point2D) -> Option<LinearColor> {
+        None
+    }
+
+    /// Get how the geometry around a shading point must be probed for the material's surface
+    /// inputs, or `None` if it does not use any.
+    fn surface_probe(&self) -> Option<SurfaceProbe> {
+        None
+    }
+
+    /// Get the material at a shading point, with its surface inputs replaced by their value given
+    /// the `signals` measured there, or `None` if it does not use any.
+    fn with_surface(&self, _signals: &SurfaceSignals) -> Option<MaterialEnum> {
+        None
+    }
 }
 
 mod uniform;
 pub use uniform::*;
+
+mod bsdf;
+pub use bsdf::*;
+
+mod microfacet;
+
+mod mix;
+pub use mix::*;
+
+mod principled;
+pub use principled::*;
+
+mod shared;
+pub use shared::*;
+
+mod toon;
+pub use toon::*;
+
+mod surface;
+pub use surface::*;
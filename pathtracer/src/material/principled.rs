@@ -0,0 +1,1041 @@
+use super::bsdf::{Bsdf, BsdfEnum, BsdfSample, Frame};
+use super::microfacet::*;
+use super::Material;
+use crate::consts::PI;
+use crate::core::{sampling, LightProperties, LinearColor, ReflTransEnum};
+use crate::texture::{Texture, TextureEnum};
+use crate::{Float, Point2D, Vector};
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// A material following the Disney principled parameterization, as exported by most modern
+/// content creation tools.
+///
+/// All parameters except `base_color` and `ior` are expected to be between 0.0 and 1.0. The base
+/// color is multiplied by the object's texture.
+///
+/// Like glTF's metallic-roughness model, the base color, metallic, roughness, emission strength
+/// and opacity parameters can each be multiplied by a texture, the [`texel_value`] of grayscale
+/// textures being used for scalar parameters.
+///
+/// [`texel_value`]: ../texture/trait.Texture.html#method.texel_value
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PrincipledMaterial {
+    /// The color of the diffuse reflection, or of the specular reflection for metals.
+    pub base_color: LinearColor,
+    /// Blend between a dielectric (0.0) and a metallic (1.0) surface.
+    #[serde(default)]
+    pub metallic: Float,
+    /// The roughness of the specular reflections and transmissions.
+    #[serde(default = "default_half")]
+    pub roughness: Float,
+    /// The strength of the specular reflection of dielectrics, 0.5 being a 4% reflectance.
+    #[serde(default = "default_half")]
+    pub specular: Float,
+    /// The strength of the retro-reflective sheen at grazing angles, e.g: for cloth or velvet.
+    #[serde(default)]
+    pub sheen: Float,
+    /// The color of the sheen.
+    #[serde(default = "white")]
+    pub sheen_color: LinearColor,
+    /// The roughness of the sheen's fibers, lower values giving a sheen closer to grazing angles.
+    #[serde(default = "default_half")]
+    pub sheen_roughness: Float,
+    /// The strength of a second, white, specular layer on top of the material.
+    #[serde(default)]
+    pub clearcoat: Float,
+    /// The glossiness of the clearcoat layer.
+    #[serde(default = "crate::serialize::default_identity")]
+    pub clearcoat_gloss: Float,
+    /// Blend between an opaque (0.0) and a fully transmissive (1.0) dielectric.
+    #[serde(default)]
+    pub transmission: Float,
+    /// The index of refraction used for transmission.
+    #[serde(default = "default_ior")]
+    pub ior: Float,
+    /// The fraction of light absorbed per unit of distance travelled inside a transmissive
+    /// material, if any.
+    #[serde(default)]
+    pub absorption: Option<LinearColor>,
+    /// The texture multiplied with the base color, if any.
+    #[serde(default)]
+    pub base_color_map: Option<Box<TextureEnum>>,
+    /// The texture multiplied with the metallic parameter, if any.
+    #[serde(default)]
+    pub metallic_map: Option<Box<TextureEnum>>,
+    /// The texture multiplied with the roughness parameter, if any.
+    #[serde(default)]
+    pub roughness_map: Option<Box<TextureEnum>>,
+    /// The roughness across the surface's tangent, `roughness` then only applying along it,
+    /// stretching the specular highlights e.g: for brushed metal or hair. Isotropic if unset.
+    #[serde(default)]
+    pub bitangent_roughness: Option<Float>,
+    /// The rotation of the tangent around the normal, in degrees.
+    ///
+    /// The tangent is aligned with the Y axis projected on the surface, or with the X axis where
+    /// the surface faces up or down.
+    #[serde(default)]
+    pub tangent_rotation: Float,
+    /// The texture giving the direction of the tangent, if any, its red and green channels
+    /// mapping from `[0, 1]` to `[-1, 1]` along the rotated tangent and bitangent. Image
+    /// textures should be read with a `linear` color space.
+    #[serde(default)]
+    pub tangent_map: Option<Box<TextureEnum>>,
+    /// The color of the light emitted by the surface.
+    #[serde(default)]
+    pub emission: LinearColor,
+    /// The intensity of the emitted light.
+    #[serde(default = "crate::serialize::default_identity")]
+    pub emission_strength: Float,
+    /// The texture multiplied with the emission strength, if any.
+    #[serde(default)]
+    pub emission_map: Option<Box<TextureEnum>>,
+    /// Blend between a surface letting light straight through it (0.0) and an opaque one (1.0),
+    /// e.g: for the transparent parts of a leaf.
+    #[serde(default = "crate::serialize::default_identity")]
+    pub opacity: Float,
+    /// The texture multiplied with the opacity parameter, if any.
+    #[serde(default)]
+    pub opacity_map: Option<Box<TextureEnum>>,
+    /// Whether transparent parts of the surface let light through when shading it, or cut out
+    /// its geometry for every ray, e.g: for leaves or fences.
+    #[serde(default)]
+    pub alpha_mode: AlphaMode,
+    /// The opacity under which the surface is cut out, in the `mask` alpha mode.
+    #[serde(default = "default_half")]
+    pub alpha_cutoff: Float,
+}
+
+/// How the opacity of a [`PrincipledMaterial`] is used, following glTF's alpha modes.
+///
+/// [`PrincipledMaterial`]: struct.PrincipledMaterial.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlphaMode {
+    /// Light goes straight through the surface proportionally to its transparency.
+    #[default]
+    Blend,
+    /// The surface is cut out wherever its opacity is below the alpha cutoff, and opaque
+    /// elsewhere.
+    Mask,
+    /// The surface is cut out randomly, proportionally to its transparency, and opaque elsewhere.
+    Stochastic,
+}
+
+fn white() -> LinearColor {
+    LinearColor::new(1., 1., 1.)
+}
+
+fn default_half() -> Float {
+    0.5
+}
+
+fn default_ior() -> Float {
+    1.5
+}
+
+impl PrincipledMaterial {
+    /// Creates a new `PrincipledMaterial` with the given base color, using the default value for
+    /// every other parameter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::material::PrincipledMaterial;
+    /// # use pathtracer::core::LinearColor;
+    /// #
+    /// let gold = PrincipledMaterial {
+    ///     metallic: 1.0,
+    ///     roughness: 0.3,
+    ///     ..PrincipledMaterial::new(LinearColor::new(1.0, 0.78, 0.34))
+    /// };
+    /// ```
+    pub fn new(base_color: LinearColor) -> Self {
+        PrincipledMaterial {
+            base_color,
+            metallic: 0.,
+            roughness: default_half(),
+            specular: default_half(),
+            sheen: 0.,
+            sheen_color: white(),
+            sheen_roughness: default_half(),
+            clearcoat: 0.,
+            clearcoat_gloss: 1.,
+            transmission: 0.,
+            ior: default_ior(),
+            absorption: None,
+            base_color_map: None,
+            metallic_map: None,
+            roughness_map: None,
+            bitangent_roughness: None,
+            tangent_rotation: 0.,
+            tangent_map: None,
+            emission: LinearColor::black(),
+            emission_strength: 1.,
+            emission_map: None,
+            opacity: 1.,
+            opacity_map: None,
+            alpha_mode: AlphaMode::Blend,
+            alpha_cutoff: default_half(),
+        }
+    }
+
+    /// Get the opacity at a texel.
+    fn opacity_at(&self, point: Point2D) -> Float {
+        match &self.opacity_map {
+            Some(map) => self.opacity * map.texel_value(point),
+            None => self.opacity,
+        }
+    }
+
+    /// Get the rotation of the tangent at a texel, in degrees.
+    fn tangent_rotation_at(&self, point: Point2D) -> Float {
+        let map = match &self.tangent_map {
+            Some(map) => map,
+            None => return self.tangent_rotation,
+        };
+        let color = map.texel_color(point);
+        let (x, y) = (2. * color.r - 1., 2. * color.g - 1.);
+        // Keep the rotated tangent where the map gives no direction
+        if x * x + y * y < 1e-6 {
+            return self.tangent_rotation;
+        }
+        self.tangent_rotation + y.atan2(x).to_degrees()
+    }
+
+    /// Resolve the texture maps at a texel, returning a material without any of them.
+    fn at(&self, point: Point2D) -> Self {
+        let scalar = |map: &Option<Box<TextureEnum>>, value: Float| match map {
+            Some(map) => value * map.texel_value(point),
+            None => value,
+        };
+        let base_color = match &self.base_color_map {
+            Some(map) => self.base_color.clone() * map.texel_color(point),
+            None => self.base_color.clone(),
+        };
+        PrincipledMaterial {
+            base_color,
+            metallic: scalar(&self.metallic_map, self.metallic),
+            sheen_color: self.sheen_color.clone(),
+            roughness: scalar(&self.roughness_map, self.roughness),
+            absorption: self.absorption.clone(),
+            base_color_map: None,
+            metallic_map: None,
+            roughness_map: None,
+            bitangent_roughness: self
+                .bitangent_roughness
+                .map(|roughness| scalar(&self.roughness_map, roughness)),
+            tangent_rotation: self.tangent_rotation_at(point),
+            tangent_map: None,
+            emission: self.emission.clone(),
+            emission_strength: scalar(&self.emission_map, self.emission_strength),
+            emission_map: None,
+            // What is left of a cut out surface is opaque
+            opacity: match self.alpha_mode {
+                AlphaMode::Blend => self.opacity_at(point),
+                AlphaMode::Mask | AlphaMode::Stochastic => 1.,
+            },
+            opacity_map: None,
+            ..*self
+        }
+    }
+
+    /// The reflectance at normal incidence of the specular reflection.
+    fn f0(&self) -> LinearColor {
+        reflectance(&self.base_color, self.specular, self.metallic)
+    }
+}
+
+/// The tangent of the surface before its rotation, following the Y axis, or the X axis where the
+/// normal is close to it.
+fn reference_tangent(normal: &Unit<Vector>) -> Vector {
+    let axis = if normal.y.abs() > 0.99 {
+        Vector::x()
+    } else {
+        Vector::y()
+    };
+    (axis - normal.as_ref() * axis.dot(normal)).normalize()
+}
+
+/// The reflectance at normal incidence of a principled material's specular reflection.
+fn reflectance(base_color: &LinearColor, specular: Float, metallic: Float) -> LinearColor {
+    let dielectric = 0.08 * specular;
+    let dielectric = LinearColor::new(dielectric, dielectric, dielectric);
+    dielectric * (1. - metallic) + base_color.clone() * metallic
+}
+
+impl Material for PrincipledMaterial {
+    /// Approximate the material for the ray tracer, which does not use its BSDF.
+    fn properties(&self, point: Point2D) -> LightProperties {
+        let material = self.at(point);
+        let dielectric = (1. - material.metallic) * (1. - material.transmission);
+        let refl_trans = if material.transmission > 0. {
+            Some(ReflTransEnum::Transparency {
+                coef: material.transmission * (1. - material.metallic),
+                index: material.ior,
+            })
+        } else if material.metallic > 0. {
+            Some(ReflTransEnum::Reflectivity {
+                coef: material.metallic * (1. - material.roughness),
+            })
+        } else {
+            None
+        };
+        LightProperties::new(
+            material.base_color.clone() * dielectric,
+            material.f0() * (1. - material.roughness),
+            refl_trans,
+        )
+    }
+
+    fn bsdf(&self, point: Point2D, normal: &Unit<Vector>, color: &LinearColor) -> Option<BsdfEnum> {
+        Some(PrincipledBsdf::new(&self.at(point), *normal, color).into())
+    }
+
+    /// Transmissive materials let shadow rays through, tinted by their base color, and
+    /// translucent ones let them straight through.
+    fn translucency(&self, point: Point2D) -> Option<LinearColor> {
+        let blended = self.opacity < 1. || self.opacity_map.is_some();
+        if self.transmission <= 0. && !(blended && self.alpha_mode == AlphaMode::Blend) {
+            return None;
+        }
+        let material = self.at(point);
+        let opacity = material.opacity.clamp(0., 1.);
+        // NaN opacities let everything through, as opacities below 0 do
+        let opacity = if opacity.is_nan() { 0. } else { opacity };
+        let transmission = material.transmission * (1. - material.metallic) * opacity;
+        let passed = 1. - opacity;
+        Some(material.base_color * transmission + LinearColor::new(passed, passed, passed))
+    }
+
+    fn is_cut_out(&self, point: Point2D, u: Float) -> bool {
+        match self.alpha_mode {
+            AlphaMode::Blend => false,
+            AlphaMode::Mask => self.opacity_at(point) < self.alpha_cutoff,
+            AlphaMode::Stochastic => u >= self.opacity_at(point),
+        }
+    }
+
+    fn emission(&self, point: Point2D) -> LinearColor {
+        if self.emission == LinearColor::black() {
+            return LinearColor::black();
+        }
+        let material = self.at(point);
+        material.emission * material.emission_strength
+    }
+
+    fn absorption(&self, _: Point2D) -> Option<LinearColor> {
+        self.absorption.clone()
+    }
+}
+
+/// The BSDF of a [`PrincipledMaterial`] at a point.
+///
+/// [`PrincipledMaterial`]: struct.PrincipledMaterial.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrincipledBsdf {
+    normal: Unit<Vector>,
+    /// The direction along which the roughness is `alpha`, orthogonal to the normal.
+    tangent: Vector,
+    base_color: LinearColor,
+    f0: LinearColor,
+    alpha: Float,
+    /// The roughness across the tangent.
+    alpha_bitangent: Float,
+    clearcoat_alpha: Float,
+    roughness: Float,
+    metallic: Float,
+    /// The color of the sheen, scaled by its strength.
+    sheen: LinearColor,
+    sheen_alpha: Float,
+    clearcoat: Float,
+    transmission: Float,
+    ior: Float,
+    /// The fraction of light interacting with the surface, the rest going straight through it.
+    opacity: Float,
+    /// The probability of sampling the diffuse, specular, clearcoat and transmission lobes.
+    lobes: [Float; 4],
+}
+
+impl PrincipledBsdf {
+    /// Creates the BSDF of a [`PrincipledMaterial`], around a normal and tinted by the given
+    /// color. The material's texture maps are not taken into account.
+    ///
+    /// [`PrincipledMaterial`]: struct.PrincipledMaterial.html
+    pub fn new(material: &PrincipledMaterial, normal: Unit<Vector>, color: &LinearColor) -> Self {
+        let base_color = material.base_color.clone() * color.clone();
+        let metallic = material.metallic;
+        let transmission = material.transmission * (1. - metallic);
+        let weights = [
+            (1. - metallic) * (1. - material.transmission),
+            0.25 + 0.75 * metallic,
+            0.25 * material.clearcoat,
+            transmission,
+        ];
+        let total: Float = weights.iter().sum();
+        let mut lobes = [0.; 4];
+        for (lobe, weight) in lobes.iter_mut().zip(weights.iter()) {
+            *lobe = weight / total;
+        }
+        // Keep the parameters in [0, 1], NaNs giving 0 as values below it do
+        let unit = |value: Float| {
+            if value.is_nan() {
+                0.
+            } else {
+                value.clamp(0., 1.)
+            }
+        };
+        let roughness = unit(material.roughness);
+        let bitangent_roughness = material.bitangent_roughness.map_or(roughness, unit);
+        let sheen_roughness = unit(material.sheen_roughness);
+        let gloss = unit(material.clearcoat_gloss);
+        let (sin, cos) = material.tangent_rotation.to_radians().sin_cos();
+        let tangent = reference_tangent(&normal);
+        PrincipledBsdf {
+            normal,
+            tangent: tangent * cos + normal.cross(&tangent) * sin,
+            f0: reflectance(&base_color, material.specular, material.metallic),
+            base_color,
+            alpha: (roughness * roughness).max(MIN_ALPHA),
+            alpha_bitangent: (bitangent_roughness * bitangent_roughness).max(MIN_ALPHA),
+            clearcoat_alpha: 0.1 * (1. - gloss) + 0.001 * gloss,
+            roughness,
+            metallic,
+            sheen: material.sheen_color.clone() * material.sheen,
+            sheen_alpha: (sheen_roughness * sheen_roughness).max(MIN_ALPHA),
+            clearcoat: material.clearcoat,
+            transmission,
+            ior: material.ior,
+            opacity: unit(material.opacity),
+            lobes,
+        }
+    }
+
+    /// The normal on the same side as the given direction.
+    fn facing(&self, direction: &Unit<Vector>) -> Unit<Vector> {
+        if direction.dot(&self.normal) < 0. {
+            -self.normal
+        } else {
+            self.normal
+        }
+    }
+
+    /// The shading frame on the same side as the given direction, aligned with the tangent.
+    fn frame(&self, direction: &Unit<Vector>) -> Frame {
+        Frame::with_tangent(self.facing(direction), self.tangent)
+    }
+
+    fn sample_transmission(&self, outgoing: &Unit<Vector>, u: Float) -> Option<BsdfSample> {
+        let entering = outgoing.dot(&self.normal) > 0.;
+        let eta = if entering { 1. / self.ior } else { self.ior };
+        let normal = self.facing(outgoing);
+        let cos_o = outgoing.dot(&normal);
+        let fresnel = fresnel_dielectric(cos_o, eta);
+        let p = self.lobes[3];
+        let (incoming, tint, probability) = if u < fresnel {
+            let reflected = normal.as_ref() * (2. * cos_o) - outgoing.as_ref();
+            let white = LinearColor::new(1., 1., 1.);
+            (Unit::new_normalize(reflected), white, fresnel)
+        } else {
+            let cos_t = (1. - eta * eta * (1. - cos_o * cos_o)).sqrt();
+            let refracted = -outgoing.as_ref() * eta + normal.as_ref() * (eta * cos_o - cos_t);
+            (
+                Unit::new_normalize(refracted),
+                self.base_color.clone(),
+                1. - fresnel,
+            )
+        };
+        let cos_i = incoming.dot(&normal).abs().max(1e-6);
+        Some(BsdfSample {
+            incoming,
+            value: tint * (self.opacity * self.transmission * probability / cos_i),
+            pdf: self.opacity * p * probability,
+            is_delta: true,
+        })
+    }
+
+    /// Let the light go straight through the translucent part of the surface.
+    fn sample_pass_through(&self, outgoing: &Unit<Vector>) -> BsdfSample {
+        let passed = 1. - self.opacity;
+        let cos_i = outgoing.dot(&self.normal).abs().max(1e-6);
+        BsdfSample {
+            incoming: -*outgoing,
+            value: LinearColor::new(1., 1., 1.) * (passed / cos_i),
+            pdf: passed,
+            is_delta: true,
+        }
+    }
+}
+
+impl Bsdf for PrincipledBsdf {
+    fn eval(&self, outgoing: &Unit<Vector>, incoming: &Unit<Vector>) -> LinearColor {
+        let normal = self.facing(outgoing);
+        let (cos_o, cos_i) = (outgoing.dot(&normal), incoming.dot(&normal));
+        if cos_o <= 0. || cos_i <= 0. {
+            return LinearColor::black();
+        }
+        let half = Unit::new_normalize(outgoing.as_ref() + incoming.as_ref());
+        let cos_h = half.dot(&normal);
+        let cos_d = incoming.dot(&half);
+        let frame = self.frame(outgoing);
+        let (alpha_x, alpha_y) = (self.alpha, self.alpha_bitangent);
+
+        // Burley's diffuse, with a retro-reflection at grazing angles for rough surfaces
+        let fd90 = 0.5 + 2. * self.roughness * cos_d * cos_d;
+        let fd =
+            (1. + (fd90 - 1.) * schlick_weight(cos_i)) * (1. + (fd90 - 1.) * schlick_weight(cos_o));
+        let dielectric = (1. - self.metallic) * (1. - self.transmission);
+        let diffuse = self.base_color.clone() * (fd / PI * dielectric);
+        // The Charlie sheen, from fibers lying on top of the dielectric base
+        let sheen = charlie_d(cos_h, self.sheen_alpha) * charlie_visibility(cos_o, cos_i);
+        let sheen = self.sheen.clone() * (sheen * (1. - self.metallic));
+
+        let g = smith_g1_anisotropic(&frame.to_local(outgoing), alpha_x, alpha_y)
+            * smith_g1_anisotropic(&frame.to_local(incoming), alpha_x, alpha_y);
+        let d = sampling::ggx_d(&frame.to_local(&half), alpha_x, alpha_y);
+        let specular = schlick(&self.f0, cos_d) * (d * g / (4. * cos_o * cos_i));
+
+        let clearcoat = if self.clearcoat > 0. {
+            let g = smith_g1(cos_o, 0.25) * smith_g1(cos_i, 0.25);
+            let f = 0.04 + 0.96 * schlick_weight(cos_d);
+            let d = gtr1_d(cos_h, self.clearcoat_alpha);
+            0.25 * self.clearcoat * d * f * g / (4. * cos_o * cos_i)
+        } else {
+            0.
+        };
+        let clearcoat = LinearColor::new(clearcoat, clearcoat, clearcoat);
+
+        (diffuse + sheen + specular + clearcoat) * self.opacity
+    }
+
+    fn pdf(&self, outgoing: &Unit<Vector>, incoming: &Unit<Vector>) -> Float {
+        let normal = self.facing(outgoing);
+        let (cos_o, cos_i) = (outgoing.dot(&normal), incoming.dot(&normal));
+        if cos_o <= 0. || cos_i <= 0. {
+            return 0.;
+        }
+        let half = Unit::new_normalize(outgoing.as_ref() + incoming.as_ref());
+        let cos_h = half.dot(&normal);
+        // Jacobian of the reflection around the half-vector
+        let jacobian = 4. * outgoing.dot(&half).max(1e-6);
+        let [diffuse, specular, clearcoat, _] = self.lobes;
+        let local_half = self.frame(outgoing).to_local(&half);
+        let pdf = diffuse * sampling::cosine_hemisphere_pdf(cos_i)
+            + specular
+                * sampling::ggx_half_vector_pdf(&local_half, self.alpha, self.alpha_bitangent)
+                / jacobian
+            + clearcoat * gtr1_d(cos_h, self.clearcoat_alpha) * cos_h / jacobian;
+        pdf * self.opacity
+    }
+
+    fn sample(&self, outgoing: &Unit<Vector>, u: [Float; 3]) -> Option<BsdfSample> {
+        // Choose whether the light goes through, and remap the random number back into [0, 1)
+        let passed = 1. - self.opacity;
+        if u[0] < passed {
+            return Some(self.sample_pass_through(outgoing));
+        }
+        let u = [(u[0] - passed) / self.opacity, u[1], u[2]];
+        let [diffuse, specular, clearcoat, _] = self.lobes;
+        let frame = self.frame(outgoing);
+        let incoming = if u[0] < diffuse {
+            frame.to_world(&sampling::cosine_hemisphere(u[1], u[2]))
+        } else if u[0] < diffuse + specular + clearcoat {
+            let half = if u[0] < diffuse + specular {
+                frame.to_world(&sampling::ggx_half_vector(
+                    self.alpha,
+                    self.alpha_bitangent,
+                    u[1],
+                    u[2],
+                ))
+            } else {
+                frame.to_world(&gtr1_sample(self.clearcoat_alpha, u[1], u[2]))
+            };
+            let reflected = half.as_ref() * (2. * outgoing.dot(&half)) - outgoing.as_ref();
+            Unit::new_normalize(reflected)
+        } else {
+            return self.sample_transmission(outgoing, u[1]);
+        };
+        let pdf = self.pdf(outgoing, &incoming);
+        if pdf <= 0. {
+            return None;
+        }
+        Some(BsdfSample {
+            incoming,
+            value: self.eval(outgoing, &incoming),
+            pdf,
+            is_delta: false,
+        })
+    }
+
+    fn regularize(&mut self, min_roughness: Float) {
+        let min_alpha = min_roughness * min_roughness;
+        self.alpha = self.alpha.max(min_alpha);
+        self.alpha_bitangent = self.alpha_bitangent.max(min_alpha);
+        self.clearcoat_alpha = self.clearcoat_alpha.max(min_alpha);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::texture::UniformTexture;
+
+    fn simple_bsdf(material: &PrincipledMaterial) -> PrincipledBsdf {
+        PrincipledBsdf::new(material, Vector::z_axis(), &LinearColor::new(1., 1., 1.))
+    }
+
+    #[test]
+    fn new_works() {
+        let material = PrincipledMaterial::new(LinearColor::new(0.5, 0.5, 0.5));
+        assert_eq!(
+            material,
+            PrincipledMaterial {
+                base_color: LinearColor::new(0.5, 0.5, 0.5),
+                metallic: 0.,
+                roughness: 0.5,
+                specular: 0.5,
+                sheen: 0.,
+                sheen_color: LinearColor::new(1., 1., 1.),
+                sheen_roughness: 0.5,
+                clearcoat: 0.,
+                clearcoat_gloss: 1.,
+                transmission: 0.,
+                ior: 1.5,
+                absorption: None,
+                base_color_map: None,
+                metallic_map: None,
+                roughness_map: None,
+                bitangent_roughness: None,
+                tangent_rotation: 0.,
+                tangent_map: None,
+                emission: LinearColor::black(),
+                emission_strength: 1.,
+                emission_map: None,
+                opacity: 1.,
+                opacity_map: None,
+                alpha_mode: AlphaMode::Blend,
+                alpha_cutoff: 0.5,
+            }
+        )
+    }
+
+    #[test]
+    fn regularize_blurs_highlights() {
+        let mirror = PrincipledMaterial {
+            metallic: 1.,
+            roughness: 0.,
+            ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+        };
+        let mut bsdf = simple_bsdf(&mirror);
+        let outgoing = Unit::new_normalize(Vector::new(0.5, 0., 1.));
+        let specular = Unit::new_normalize(Vector::new(-0.5, 0., 1.));
+        let nearby = Unit::new_normalize(Vector::new(-0.4, 0.1, 1.));
+        let sharp = bsdf.eval(&outgoing, &nearby).r;
+        bsdf.regularize(0.3);
+        assert!(bsdf.eval(&outgoing, &nearby).r > 100. * sharp);
+        assert!(bsdf.eval(&outgoing, &specular).r > bsdf.eval(&outgoing, &nearby).r);
+        // Rougher lobes are not affected
+        let rough = bsdf.clone();
+        bsdf.regularize(0.1);
+        assert_eq!(bsdf, rough);
+    }
+
+    #[test]
+    fn transmission_lets_shadows_through() {
+        let opaque = PrincipledMaterial::new(LinearColor::new(1., 0.5, 0.));
+        assert_eq!(opaque.translucency(Point2D::origin()), None);
+        let glass = PrincipledMaterial {
+            transmission: 0.5,
+            ..opaque
+        };
+        assert_eq!(
+            glass.translucency(Point2D::origin()),
+            Some(LinearColor::new(0.5, 0.25, 0.))
+        );
+    }
+
+    #[test]
+    fn sheen_shines_at_grazing_angles() {
+        let black = PrincipledMaterial {
+            specular: 0.,
+            ..PrincipledMaterial::new(LinearColor::black())
+        };
+        let velvet = PrincipledMaterial {
+            sheen: 1.,
+            sheen_color: LinearColor::new(1., 0.5, 0.),
+            sheen_roughness: 0.3,
+            ..black.clone()
+        };
+        let (black, velvet) = (simple_bsdf(&black), simple_bsdf(&velvet));
+        let light = Unit::new_normalize(Vector::new(1., 0., 0.3));
+        // Only keep the sheen, the specular reflection of a black dielectric is white
+        let sheen = |outgoing: Unit<Vector>| {
+            let (with, without) = (
+                velvet.eval(&outgoing, &light),
+                black.eval(&outgoing, &light),
+            );
+            [with.r - without.r, with.g - without.g, with.b - without.b]
+        };
+        // Seen from near the light, at a grazing angle
+        let grazing = sheen(Unit::new_normalize(Vector::new(1., 0.1, 0.2)));
+        let normal = sheen(Vector::z_axis());
+        assert!(grazing[0] > 2. * normal[0], "{:?} <= {:?}", grazing, normal);
+        // The sheen is tinted by its color
+        assert!((grazing[1] - grazing[0] / 2.).abs() < 1e-5 * grazing[0]);
+        assert_eq!(grazing[2], 0.);
+    }
+
+    #[test]
+    fn sheen_deserialization_works() {
+        let yaml = r#"
+            base_color: {r: 0.5, g: 0.0, b: 0.0}
+            sheen: 0.8
+            sheen_color: {r: 1.0, g: 0.5, b: 0.5}
+            sheen_roughness: 0.25
+        "#;
+        let material: PrincipledMaterial = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            material,
+            PrincipledMaterial {
+                sheen: 0.8,
+                sheen_color: LinearColor::new(1., 0.5, 0.5),
+                sheen_roughness: 0.25,
+                ..PrincipledMaterial::new(LinearColor::new(0.5, 0., 0.))
+            }
+        );
+    }
+
+    #[test]
+    fn emission_works() {
+        let material = PrincipledMaterial {
+            emission: LinearColor::new(1., 0.5, 0.),
+            emission_strength: 4.,
+            emission_map: Some(Box::new(
+                UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5)).into(),
+            )),
+            ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+        };
+        assert_eq!(
+            material.emission(Point2D::origin()),
+            LinearColor::new(2., 1., 0.)
+        );
+        let dark = PrincipledMaterial::new(LinearColor::new(1., 1., 1.));
+        assert_eq!(dark.emission(Point2D::origin()), LinearColor::black());
+    }
+
+    #[test]
+    fn opacity_lets_light_through() {
+        let material = PrincipledMaterial {
+            opacity: 0.25,
+            ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+        };
+        let bsdf = simple_bsdf(&material);
+        let outgoing = Unit::new_normalize(Vector::new(0.5, 0., 1.));
+        // Three quarters of the samples go straight through, the rest are reflected
+        let sample = bsdf.sample(&outgoing, [0.1, 0.5, 0.5]).unwrap();
+        assert!(sample.is_delta);
+        assert_eq!(sample.incoming, -outgoing);
+        let weight = sample.weight(&Vector::z_axis());
+        assert!((weight - LinearColor::new(1., 1., 1.)).r.abs() < 1e-5);
+        assert!(!bsdf.sample(&outgoing, [0.9, 0.5, 0.5]).unwrap().is_delta);
+        // Only the opaque part reflects light
+        let incoming = Unit::new_normalize(Vector::new(-0.2, 0.3, 1.));
+        let opaque = simple_bsdf(&PrincipledMaterial::new(LinearColor::new(1., 1., 1.)));
+        let ratio = bsdf.eval(&outgoing, &incoming).g / opaque.eval(&outgoing, &incoming).g;
+        assert!((ratio - 0.25).abs() < 1e-5);
+        assert_eq!(
+            material.translucency(Point2D::origin()),
+            Some(LinearColor::new(0.75, 0.75, 0.75))
+        );
+    }
+
+    #[test]
+    fn scalar_maps_use_texel_value() {
+        use crate::texture::{Channel, ChannelTexture};
+
+        let packed: TextureEnum = UniformTexture::new(LinearColor::new(0., 0.25, 0.5)).into();
+        let channel = |channel| {
+            Some(Box::new(
+                ChannelTexture::new(packed.clone(), channel).into(),
+            ))
+        };
+        let material = PrincipledMaterial {
+            metallic: 1.,
+            metallic_map: channel(Channel::Blue),
+            roughness_map: channel(Channel::Green),
+            opacity_map: channel(Channel::Blue),
+            ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+        };
+        let resolved = material.at(Point2D::origin());
+        assert_eq!(resolved.metallic, 0.5);
+        assert_eq!(resolved.roughness, 0.125);
+        assert_eq!(resolved.opacity, 0.5);
+    }
+
+    #[test]
+    fn maps_deserialization_works() {
+        let yaml = r#"
+            base_color: {r: 1.0, g: 1.0, b: 1.0}
+            base_color_map:
+              type: uniform
+              color: {r: 0.5, g: 0.25, b: 0.125}
+            roughness_map:
+              type: uniform
+              color: {r: 0.5, g: 0.5, b: 0.5}
+        "#;
+        let material: PrincipledMaterial = serde_yaml::from_str(yaml).unwrap();
+        let texture = |r, g, b| {
+            Some(Box::new(
+                UniformTexture::new(LinearColor::new(r, g, b)).into(),
+            ))
+        };
+        assert_eq!(
+            material,
+            PrincipledMaterial {
+                base_color_map: texture(0.5, 0.25, 0.125),
+                roughness_map: texture(0.5, 0.5, 0.5),
+                ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+            }
+        )
+    }
+
+    #[test]
+    fn maps_are_resolved_per_texel() {
+        let texture = |r, g, b| {
+            Some(Box::new(
+                UniformTexture::new(LinearColor::new(r, g, b)).into(),
+            ))
+        };
+        let material = PrincipledMaterial {
+            metallic: 1.,
+            base_color_map: texture(0.5, 0.25, 0.125),
+            metallic_map: texture(0.5, 0.5, 0.5),
+            roughness_map: texture(0., 0., 0.),
+            ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+        };
+        let resolved = material.at(Point2D::origin());
+        assert_eq!(
+            resolved,
+            PrincipledMaterial {
+                metallic: 0.5,
+                roughness: 0.,
+                ..PrincipledMaterial::new(LinearColor::new(0.5, 0.25, 0.125))
+            }
+        );
+        assert_eq!(
+            material.properties(Point2D::origin()),
+            resolved.properties(Point2D::origin())
+        );
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            base_color: {r: 1.0, g: 0.78, b: 0.34}
+            metallic: 1.0
+            roughness: 0.3
+        "#;
+        let material: PrincipledMaterial = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            material,
+            PrincipledMaterial {
+                metallic: 1.,
+                roughness: 0.3,
+                ..PrincipledMaterial::new(LinearColor::new(1., 0.78, 0.34))
+            }
+        )
+    }
+
+    #[test]
+    fn sample_matches_pdf() {
+        let material = PrincipledMaterial {
+            clearcoat: 0.5,
+            ..PrincipledMaterial::new(LinearColor::new(0.8, 0.2, 0.2))
+        };
+        let bsdf = simple_bsdf(&material);
+        let outgoing = Unit::new_normalize(Vector::new(0.3, 0.1, 1.));
+        for i in 0..10 {
+            for j in 0..10 {
+                let u = [i as Float / 10., (j as Float + 0.5) / 10., 0.3];
+                if let Some(sample) = bsdf.sample(&outgoing, u) {
+                    assert!(!sample.is_delta);
+                    assert!(sample.incoming.z > 0.);
+                    let pdf = bsdf.pdf(&outgoing, &sample.incoming);
+                    assert!((sample.pdf - pdf).abs() <= 1e-3 * pdf);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn is_energy_conserving() {
+        // Estimate the albedo of a white rough dielectric with importance sampling
+        let material = PrincipledMaterial::new(LinearColor::new(1., 1., 1.));
+        let bsdf = simple_bsdf(&material);
+        let outgoing = Unit::new_normalize(Vector::new(0.5, 0., 1.));
+        let n = 64;
+        let mut total = 0.;
+        for i in 0..n {
+            for j in 0..n {
+                for &lobe in &[0.1, 0.9] {
+                    let u = [
+                        lobe,
+                        (i as Float + 0.5) / n as Float,
+                        (j as Float + 0.5) / n as Float,
+                    ];
+                    if let Some(sample) = bsdf.sample(&outgoing, u) {
+                        total += sample.weight(&Vector::z_axis()).g;
+                    }
+                }
+            }
+        }
+        let albedo = total / (2 * n * n) as Float;
+        assert!(albedo > 0.8 && albedo < 1.1, "albedo: {}", albedo);
+    }
+
+    #[test]
+    fn anisotropic_sample_matches_pdf() {
+        let material = PrincipledMaterial {
+            metallic: 1.,
+            roughness: 0.2,
+            bitangent_roughness: Some(0.7),
+            tangent_rotation: 30.,
+            ..PrincipledMaterial::new(LinearColor::new(0.9, 0.9, 0.9))
+        };
+        let bsdf = simple_bsdf(&material);
+        let outgoing = Unit::new_normalize(Vector::new(0.3, -0.2, 1.));
+        for i in 0..10 {
+            for j in 0..10 {
+                let u = [0.5, (i as Float + 0.5) / 10., (j as Float + 0.5) / 10.];
+                if let Some(sample) = bsdf.sample(&outgoing, u) {
+                    let pdf = bsdf.pdf(&outgoing, &sample.incoming);
+                    assert!((sample.pdf - pdf).abs() <= 1e-3 * pdf);
+                    let value = bsdf.eval(&outgoing, &sample.incoming);
+                    assert_eq!(sample.value, value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn highlights_are_stretched_across_the_tangent() {
+        let material = PrincipledMaterial {
+            metallic: 1.,
+            roughness: 0.1,
+            bitangent_roughness: Some(0.6),
+            ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+        };
+        // The tangent follows the Y axis on a surface facing Z
+        let bsdf = simple_bsdf(&material);
+        let outgoing = Vector::z_axis();
+        let tilted = |x, y| Unit::new_normalize(Vector::new(x, y, 1.));
+        let along = bsdf.eval(&outgoing, &tilted(0., 0.5)).g;
+        let across = bsdf.eval(&outgoing, &tilted(0.5, 0.)).g;
+        assert!(across > 10. * along, "{} <= {}", across, along);
+        // Rotating the tangent swaps both directions
+        let rotated = simple_bsdf(&PrincipledMaterial {
+            tangent_rotation: 90.,
+            ..material
+        });
+        let along = rotated.eval(&outgoing, &tilted(0.5, 0.)).g;
+        let across = rotated.eval(&outgoing, &tilted(0., 0.5)).g;
+        assert!(across > 10. * along, "{} <= {}", across, along);
+    }
+
+    #[test]
+    fn tangent_map_rotates_tangent() {
+        let material = PrincipledMaterial {
+            tangent_rotation: 10.,
+            // Points along the reference bitangent
+            tangent_map: Some(Box::new(
+                UniformTexture::new(LinearColor::new(0.5, 1., 0.5)).into(),
+            )),
+            ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+        };
+        let resolved = material.at(Point2D::origin());
+        assert!((resolved.tangent_rotation - 100.).abs() < 1e-4);
+        assert_eq!(resolved.tangent_map, None);
+    }
+
+    #[test]
+    fn anisotropy_deserialization_works() {
+        let yaml = r#"
+            base_color: {r: 1.0, g: 1.0, b: 1.0}
+            roughness: 0.2
+            bitangent_roughness: 0.8
+            tangent_rotation: 45.0
+        "#;
+        let material: PrincipledMaterial = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            material,
+            PrincipledMaterial {
+                roughness: 0.2,
+                bitangent_roughness: Some(0.8),
+                tangent_rotation: 45.,
+                ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+            }
+        );
+    }
+
+    #[test]
+    fn transmission_is_delta() {
+        let material = PrincipledMaterial {
+            transmission: 1.,
+            ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+        };
+        let bsdf = simple_bsdf(&material);
+        let outgoing = Vector::z_axis();
+        let sample = bsdf.sample(&outgoing, [0.99, 0.5, 0.5]).unwrap();
+        assert!(sample.is_delta);
+        // Goes straight through at normal incidence
+        assert!((sample.incoming.z + 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn mask_cuts_out_below_cutoff() {
+        use crate::texture::CheckerTexture;
+        let (black, white) = (LinearColor::black(), LinearColor::new(1., 1., 1.));
+        let material = PrincipledMaterial {
+            opacity_map: Some(Box::new(CheckerTexture::new(black, white, 2.).into())),
+            alpha_mode: AlphaMode::Mask,
+            ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+        };
+        // The random number is ignored
+        assert!(material.is_cut_out(Point2D::new(0.25, 0.25), 0.));
+        assert!(!material.is_cut_out(Point2D::new(0.75, 0.25), 0.99));
+        // What is left is opaque
+        assert_eq!(material.translucency(Point2D::new(0.75, 0.25)), None);
+        let blended = PrincipledMaterial {
+            alpha_mode: AlphaMode::Blend,
+            ..material
+        };
+        assert!(!blended.is_cut_out(Point2D::new(0.25, 0.25), 0.));
+    }
+
+    #[test]
+    fn stochastic_cuts_out_proportionally() {
+        let material = PrincipledMaterial {
+            opacity: 0.25,
+            alpha_mode: AlphaMode::Stochastic,
+            ..PrincipledMaterial::new(LinearColor::new(1., 1., 1.))
+        };
+        let cut = (0..100)
+            .filter(|&i| material.is_cut_out(Point2D::origin(), i as Float / 100.))
+            .count();
+        assert_eq!(cut, 75);
+    }
+
+    #[test]
+    fn alpha_mode_deserialization_works() {
+        let yaml = r#"
+            base_color: {r: 1.0, g: 1.0, b: 1.0}
+            opacity: 0.5
+            alpha_mode: mask
+            alpha_cutoff: 0.25
+        "#;
+        let material: PrincipledMaterial = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(material.alpha_mode, AlphaMode::Mask);
+        assert_eq!(material.alpha_cutoff, 0.25);
+        assert!(!material.is_cut_out(Point2D::origin(), 0.));
+    }
+}
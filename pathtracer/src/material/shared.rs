@@ -0,0 +1,176 @@
+use super::{BsdfEnum, Material, MaterialEnum, SurfaceProbe, SurfaceSignals, UniformMaterial};
+use crate::core::{LightProperties, LinearColor};
+use crate::{Error, Result};
+use crate::{Float, Point2D, Vector};
+use nalgebra::Unit;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+
+/// A material defined once in the scene, and shared by name between the objects using it.
+///
+/// When deserializing a scene, objects refer to the materials of its `materials` dictionary by
+/// name, and are given the shared material once the whole scene has been read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SharedMaterial {
+    name: String,
+    material: Option<Arc<MaterialEnum>>,
+}
+
+impl SharedMaterial {
+    /// Creates a new `SharedMaterial`, giving a name to the material it shares.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{LightProperties, LinearColor};
+    /// # use pathtracer::material::{Material, SharedMaterial, UniformMaterial};
+    /// # use pathtracer::Point2D;
+    /// use std::sync::Arc;
+    ///
+    /// let red = Arc::new(
+    ///     UniformMaterial::new(LightProperties::new(
+    ///         LinearColor::new(1.0, 0.0, 0.0), // diffuse component
+    ///         LinearColor::new(0.0, 0.0, 0.0), // specular component
+    ///         None,
+    ///     ))
+    ///     .into(),
+    /// );
+    /// let first = SharedMaterial::new("red", Arc::clone(&red));
+    /// let second = SharedMaterial::new("red", red);
+    /// assert_eq!(first.name(), "red");
+    /// assert_eq!(
+    ///     first.properties(Point2D::origin()),
+    ///     second.properties(Point2D::origin())
+    /// );
+    /// ```
+    pub fn new<S: Into<String>>(name: S, material: Arc<MaterialEnum>) -> Self {
+        SharedMaterial {
+            name: name.into(),
+            material: Some(material),
+        }
+    }
+
+    /// Refer to a material by name, before it has been defined.
+    pub(crate) fn named(name: String) -> Self {
+        SharedMaterial {
+            name,
+            material: None,
+        }
+    }
+
+    /// Get the name of the shared material.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Look up the shared material by name among the scene's `materials`.
+    pub(crate) fn resolve(
+        &mut self,
+        materials: &BTreeMap<String, Arc<MaterialEnum>>,
+    ) -> Result<()> {
+        let material = (materials.get(&self.name))
+            .ok_or_else(|| Error::unknown_name("material", &self.name))?;
+        self.material = Some(Arc::clone(material));
+        Ok(())
+    }
+
+    /// Get the shared material, or an [`Error::UnknownName`] if it was not resolved.
+    ///
+    /// [`Error::UnknownName`]: ../enum.Error.html#variant.UnknownName
+    pub(crate) fn material(&self) -> Result<&MaterialEnum> {
+        (self.material.as_deref()).ok_or_else(|| Error::unknown_name("material", &self.name))
+    }
+
+    /// Get the shared material, rendering it black if it was not resolved, which never happens
+    /// to the materials of a loaded scene.
+    fn shared(&self) -> &MaterialEnum {
+        static UNRESOLVED: OnceLock<MaterialEnum> = OnceLock::new();
+        self.material().unwrap_or_else(|_| {
+            UNRESOLVED.get_or_init(|| {
+                let black = LinearColor::black();
+                UniformMaterial::new(LightProperties::new(black.clone(), black, None)).into()
+            })
+        })
+    }
+}
+
+impl Material for SharedMaterial {
+    fn properties(&self, point: Point2D) -> LightProperties {
+        self.shared().properties(point)
+    }
+
+    fn light_response(&self, point: Point2D, diffuse: Float, specular: Float) -> (Float, Float) {
+        self.shared().light_response(point, diffuse, specular)
+    }
+
+    fn bsdf(&self, point: Point2D, normal: &Unit<Vector>, color: &LinearColor) -> Option<BsdfEnum> {
+        self.shared().bsdf(point, normal, color)
+    }
+
+    fn translucency(&self, point: Point2D) -> Option<LinearColor> {
+        self.shared().translucency(point)
+    }
+
+    fn is_cut_out(&self, point: Point2D, u: Float) -> bool {
+        self.shared().is_cut_out(point, u)
+    }
+
+    fn emission(&self, point: Point2D) -> LinearColor {
+        self.shared().emission(point)
+    }
+
+    fn absorption(&self, point: Point2D) -> Option<LinearColor> {
+        self.shared().absorption(point)
+    }
+
+    fn surface_probe(&self) -> Option<SurfaceProbe> {
+        self.shared().surface_probe()
+    }
+
+    fn with_surface(&self, signals: &SurfaceSignals) -> Option<MaterialEnum> {
+        self.shared().with_surface(signals)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn materials() -> BTreeMap<String, Arc<MaterialEnum>> {
+        let grey = LinearColor::new(0.5, 0.5, 0.5);
+        let material = UniformMaterial::new(LightProperties::new(grey, LinearColor::black(), None));
+        let mut materials = BTreeMap::new();
+        materials.insert("grey".to_string(), Arc::new(material.into()));
+        materials
+    }
+
+    #[test]
+    fn resolve_works() {
+        let materials = materials();
+        let mut shared = SharedMaterial::named("grey".to_string());
+        shared.resolve(&materials).unwrap();
+        assert_eq!(
+            shared,
+            SharedMaterial::new("grey", materials["grey"].clone())
+        );
+        assert_eq!(
+            shared.properties(Point2D::origin()),
+            materials["grey"].properties(Point2D::origin())
+        );
+    }
+
+    #[test]
+    fn resolve_unknown_fails() {
+        let mut shared = SharedMaterial::named("gray".to_string());
+        assert!(shared.material().is_err());
+        let err = shared.resolve(&materials()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::UnknownName {
+                kind: "material",
+                ..
+            }
+        ));
+        assert_eq!(err.to_string(), "unknown material 'gray'");
+    }
+}
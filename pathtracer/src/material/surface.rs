@@ -0,0 +1,249 @@
+use crate::{Float, Vector};
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// The geometric signals measured around a shading point, by casting rays over the hemispheres
+/// on both sides of the surface.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SurfaceSignals {
+    /// The fraction of the hemisphere above the surface which is not occluded by nearby geometry:
+    /// 0.0 at the bottom of a cavity, 1.0 on an open surface.
+    pub occlusion: Float,
+    /// An approximation of the surface's curvature, in `[-1, 1]`: positive on convex edges, whose
+    /// inside is thin, and negative in concave creases.
+    pub curvature: Float,
+    /// The average unoccluded direction above the surface.
+    pub bent_normal: Unit<Vector>,
+}
+
+impl SurfaceSignals {
+    /// Get the signals of a flat surface with nothing around it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::material::SurfaceSignals;
+    /// # use pathtracer::Vector;
+    /// #
+    /// let signals = SurfaceSignals::open(Vector::y_axis());
+    /// assert_eq!(signals.occlusion, 1.0);
+    /// assert_eq!(signals.curvature, 0.0);
+    /// ```
+    pub fn open(normal: Unit<Vector>) -> Self {
+        SurfaceSignals {
+            occlusion: 1.,
+            curvature: 0.,
+            bent_normal: normal,
+        }
+    }
+}
+
+/// How far around a shading point, and with how many rays, the geometry must be probed to
+/// compute its [`SurfaceSignals`].
+///
+/// [`SurfaceSignals`]: struct.SurfaceSignals.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfaceProbe {
+    /// The distance up to which geometry is considered close to the shading point.
+    pub radius: Float,
+    /// The number of rays cast on each side of the surface.
+    pub samples: u32,
+}
+
+impl SurfaceProbe {
+    /// Get the probe satisfying both `self` and `other`.
+    pub fn merge(self, other: Self) -> Self {
+        SurfaceProbe {
+            radius: self.radius.max(other.radius),
+            samples: self.samples.max(other.samples),
+        }
+    }
+}
+
+/// The signal read by a [`SurfaceInput`].
+///
+/// [`SurfaceInput`]: struct.SurfaceInput.html
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum SurfaceSignal {
+    /// The ambient occlusion, e.g: to put dirt in cavities.
+    Occlusion,
+    /// The approximate curvature, e.g: to wear the paint off edges.
+    Curvature,
+    /// How much the bent normal points towards a direction, e.g: for dust settling from above.
+    Exposure {
+        /// The direction the surface should be exposed to.
+        #[serde(deserialize_with = "crate::serialize::vector_normalizer")]
+        direction: Unit<Vector>,
+    },
+}
+
+/// A procedural input computed from the geometry around the shading point, instead of a baked
+/// texture.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SurfaceInput {
+    /// The signal which is read.
+    #[serde(flatten)]
+    signal: SurfaceSignal,
+    /// The distance up to which geometry is considered close to the shading point.
+    radius: Float,
+    /// The number of rays cast on each side of the surface.
+    #[serde(default = "default_samples")]
+    samples: u32,
+    /// The values of the signal mapped to 0.0 and 1.0, the input being clamped between them.
+    #[serde(default = "default_range")]
+    range: [Float; 2],
+}
+
+fn default_samples() -> u32 {
+    16
+}
+
+fn default_range() -> [Float; 2] {
+    [0., 1.]
+}
+
+impl SurfaceInput {
+    /// Creates a new `SurfaceInput`, reading `signal` in the given `radius`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::material::{SurfaceInput, SurfaceSignal, SurfaceSignals};
+    /// # use pathtracer::Vector;
+    /// #
+    /// // Only the sharpest edges are worn off
+    /// let wear = SurfaceInput::new(SurfaceSignal::Curvature, 0.1).with_range(0.2, 0.6);
+    /// let mut signals = SurfaceSignals::open(Vector::y_axis());
+    /// assert_eq!(wear.at(&signals), 0.0);
+    /// signals.curvature = 0.5;
+    /// assert!((wear.at(&signals) - 0.75).abs() < 1e-6);
+    /// ```
+    pub fn new(signal: SurfaceSignal, radius: Float) -> Self {
+        SurfaceInput {
+            signal,
+            radius,
+            samples: default_samples(),
+            range: default_range(),
+        }
+    }
+
+    /// Set the number of rays cast on each side of the surface.
+    pub fn with_samples(mut self, samples: u32) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Set the values of the signal mapped to 0.0 and 1.0.
+    pub fn with_range(mut self, low: Float, high: Float) -> Self {
+        self.range = [low, high];
+        self
+    }
+
+    /// Get the probe needed to compute the signals read by this input.
+    pub fn probe(&self) -> SurfaceProbe {
+        SurfaceProbe {
+            radius: self.radius,
+            samples: self.samples,
+        }
+    }
+
+    /// Get the value of the input, in `[0, 1]`, given the signals measured at a shading point.
+    pub fn at(&self, signals: &SurfaceSignals) -> Float {
+        let value = match &self.signal {
+            SurfaceSignal::Occlusion => signals.occlusion,
+            SurfaceSignal::Curvature => signals.curvature,
+            SurfaceSignal::Exposure { direction } => signals.bent_normal.dot(direction),
+        };
+        let [low, high] = self.range;
+        if high == low {
+            return if value < low { 0. } else { 1. };
+        }
+        ((value - low) / (high - low)).clamp(0., 1.)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn new_works() {
+        let input = SurfaceInput::new(SurfaceSignal::Occlusion, 0.5);
+        assert_eq!(
+            input,
+            SurfaceInput {
+                signal: SurfaceSignal::Occlusion,
+                radius: 0.5,
+                samples: 16,
+                range: [0., 1.],
+            }
+        )
+    }
+
+    #[test]
+    fn at_works() {
+        let signals = SurfaceSignals {
+            occlusion: 0.25,
+            curvature: -0.5,
+            bent_normal: Vector::x_axis(),
+        };
+        let occlusion = SurfaceInput::new(SurfaceSignal::Occlusion, 1.);
+        assert_eq!(occlusion.at(&signals), 0.25);
+        // Concave creases are below the default range
+        let curvature = SurfaceInput::new(SurfaceSignal::Curvature, 1.);
+        assert_eq!(curvature.at(&signals), 0.);
+        let cavity = curvature.with_range(0., -1.);
+        assert_eq!(cavity.at(&signals), 0.5);
+        let exposure = SurfaceInput::new(
+            SurfaceSignal::Exposure {
+                direction: Vector::x_axis(),
+            },
+            1.,
+        );
+        assert_eq!(exposure.at(&signals), 1.);
+    }
+
+    #[test]
+    fn merge_works() {
+        let first = SurfaceProbe {
+            radius: 1.,
+            samples: 4,
+        };
+        let second = SurfaceProbe {
+            radius: 0.5,
+            samples: 8,
+        };
+        assert_eq!(
+            first.merge(second),
+            SurfaceProbe {
+                radius: 1.,
+                samples: 8,
+            }
+        )
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            type: exposure
+            direction: [0.0, 2.0, 0.0]
+            radius: 0.5
+            samples: 8
+            range: [0.5, 1.0]
+        "#;
+        let input: SurfaceInput = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            input,
+            SurfaceInput::new(
+                SurfaceSignal::Exposure {
+                    direction: Vector::y_axis(),
+                },
+                0.5
+            )
+            .with_samples(8)
+            .with_range(0.5, 1.)
+        )
+    }
+}
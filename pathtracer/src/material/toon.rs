@@ -0,0 +1,152 @@
+use super::Material;
+use crate::core::{LightProperties, LinearColor};
+use crate::{Float, Point2D};
+use serde::{Deserialize, Serialize};
+
+/// A stylized material, shading surfaces with a few flat bands of color instead of smooth
+/// gradients, and giving them sharp highlights, e.g: for cartoon-like renders along with an
+/// [`Outline`].
+///
+/// The color is multiplied by the object's texture. Parts of the surface which do not face any
+/// light only receive the ambient lighting.
+///
+/// [`Outline`]: ../post/struct.Outline.html
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ToonMaterial {
+    /// The color of the fully lit parts of the surface.
+    color: LinearColor,
+    /// The number of shades of the lit parts of the surface.
+    #[serde(default = "default_bands")]
+    bands: u32,
+    /// The color of the highlights.
+    #[serde(default)]
+    highlight: LinearColor,
+    /// The size of the highlights, from 0 for none, to 1 for the whole lit side of the surface.
+    #[serde(default = "default_highlight_size")]
+    highlight_size: Float,
+}
+
+fn default_bands() -> u32 {
+    3
+}
+
+fn default_highlight_size() -> Float {
+    0.05
+}
+
+impl ToonMaterial {
+    /// Creates a new `ToonMaterial` of the given color, with three bands and no highlights.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::material::{Material, ToonMaterial};
+    /// # use pathtracer::Point2D;
+    /// #
+    /// let toon = ToonMaterial::new(LinearColor::new(0.8, 0.2, 0.2)).with_bands(2);
+    /// // Surfaces facing the light at slightly different angles get the same shade
+    /// let shade = |cos| toon.light_response(Point2D::origin(), cos, 0.).0;
+    /// assert_eq!(shade(0.6), shade(0.9));
+    /// assert!(shade(0.4) < shade(0.6));
+    /// assert_eq!(shade(-0.5), 0.);
+    /// ```
+    pub fn new(color: LinearColor) -> Self {
+        ToonMaterial {
+            color,
+            bands: default_bands(),
+            highlight: LinearColor::black(),
+            highlight_size: default_highlight_size(),
+        }
+    }
+
+    /// Set the number of shades of the lit parts of the surface.
+    pub fn with_bands(mut self, bands: u32) -> Self {
+        self.bands = bands;
+        self
+    }
+
+    /// Add highlights of the given color and size, between 0 and 1.
+    pub fn with_highlight(mut self, highlight: LinearColor, size: Float) -> Self {
+        self.highlight = highlight;
+        self.highlight_size = size;
+        self
+    }
+}
+
+impl Material for ToonMaterial {
+    fn properties(&self, _: Point2D) -> LightProperties {
+        LightProperties::new(self.color.clone(), self.highlight.clone(), None)
+    }
+
+    /// Quantize the diffuse shading into bands, and only keep the specular shading closest to
+    /// the reflection as a flat highlight.
+    fn light_response(&self, _: Point2D, diffuse: Float, specular: Float) -> (Float, Float) {
+        let bands = self.bands.max(1) as Float;
+        let diffuse = if diffuse > 0. {
+            (diffuse * bands).ceil().min(bands) / bands
+        } else {
+            0.
+        };
+        let specular = if diffuse > 0. && specular > 1. - self.highlight_size {
+            1.
+        } else {
+            0.
+        };
+        (diffuse, specular)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bands_work() {
+        let toon = ToonMaterial::new(LinearColor::new(1., 1., 1.)).with_bands(4);
+        let shade = |cos| toon.light_response(Point2D::origin(), cos, 0.).0;
+        assert_eq!(shade(0.1), 0.25);
+        assert_eq!(shade(0.3), 0.5);
+        assert_eq!(shade(0.6), 0.75);
+        assert_eq!(shade(0.8), 1.);
+        assert_eq!(shade(1.), 1.);
+        assert_eq!(shade(0.), 0.);
+    }
+
+    #[test]
+    fn highlights_are_flat() {
+        let white = LinearColor::new(1., 1., 1.);
+        let toon = ToonMaterial::new(white.clone()).with_highlight(white, 0.1);
+        let highlight =
+            |diffuse, specular| toon.light_response(Point2D::origin(), diffuse, specular).1;
+        assert_eq!(highlight(0.5, 0.95), 1.);
+        assert_eq!(highlight(0.5, 0.99), 1.);
+        assert_eq!(highlight(0.5, 0.85), 0.);
+        // Unlit parts of the surface never get highlights
+        assert_eq!(highlight(-0.5, 0.95), 0.);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            color: {r: 1.0, g: 0.5, b: 0.25}
+            bands: 2
+            highlight: {r: 1.0, g: 1.0, b: 1.0}
+            highlight_size: 0.1
+        "#;
+        let material: ToonMaterial = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            material,
+            ToonMaterial::new(LinearColor::new(1., 0.5, 0.25))
+                .with_bands(2)
+                .with_highlight(LinearColor::new(1., 1., 1.), 0.1)
+        );
+    }
+
+    #[test]
+    fn default_deserialization_works() {
+        let yaml = "color: {r: 1.0, g: 0.5, b: 0.25}";
+        let material: ToonMaterial = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(material, ToonMaterial::new(LinearColor::new(1., 0.5, 0.25)));
+    }
+}
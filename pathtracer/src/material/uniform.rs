@@ -1,13 +1,17 @@
 use super::Material;
-use crate::core::LightProperties;
+use crate::core::{LightProperties, LinearColor, ReflTransEnum};
 use crate::Point2D;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// A material with the same characteristics on all points.
-#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct UniformMaterial {
     #[serde(flatten)]
     properties: LightProperties,
+    #[serde(default)]
+    translucency: Option<LinearColor>,
+    #[serde(default)]
+    absorption: Option<LinearColor>,
 }
 
 impl UniformMaterial {
@@ -28,7 +32,62 @@ impl UniformMaterial {
     /// );
     /// ```
     pub fn new(properties: LightProperties) -> Self {
-        UniformMaterial { properties }
+        UniformMaterial {
+            properties,
+            translucency: None,
+            absorption: None,
+        }
+    }
+
+    /// Make the material a thin translucent surface, e.g: leaves or fabric, which lets shadow rays
+    /// through while tinting them by the given color.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::material::{Material, UniformMaterial};
+    /// # use pathtracer::core::{LightProperties, LinearColor};
+    /// # use pathtracer::Point2D;
+    /// #
+    /// let leaf = UniformMaterial::new(
+    ///     LightProperties::new(
+    ///         LinearColor::new(0.1, 0.5, 0.1), // diffuse component
+    ///         LinearColor::new(0.0, 0.0, 0.0), // specular component
+    ///         None,
+    ///     ),
+    /// )
+    /// .with_translucency(LinearColor::new(0.2, 0.5, 0.1));
+    /// assert!(leaf.translucency(Point2D::origin()).is_some());
+    /// ```
+    pub fn with_translucency(mut self, translucency: LinearColor) -> Self {
+        self.translucency = Some(translucency);
+        self
+    }
+
+    /// Make the inside of a transparent material absorb light, e.g: for colored glass or liquids.
+    /// Light going through it is attenuated exponentially by the distance it travels, each
+    /// channel of `absorption` being the fraction absorbed per unit of distance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::material::{Material, UniformMaterial};
+    /// # use pathtracer::core::{LightProperties, LinearColor, ReflTransEnum};
+    /// # use pathtracer::Point2D;
+    /// #
+    /// let green_glass = UniformMaterial::new(
+    ///     LightProperties::new(
+    ///         LinearColor::new(0.0, 0.0, 0.0), // diffuse component
+    ///         LinearColor::new(0.0, 0.0, 0.0), // specular component
+    ///         Some(ReflTransEnum::Transparency { coef: 1.0, index: 1.5 }),
+    ///     ),
+    /// )
+    /// .with_absorption(LinearColor::new(0.8, 0.1, 0.8));
+    /// assert!(green_glass.absorption(Point2D::origin()).is_some());
+    /// ```
+    pub fn with_absorption(mut self, absorption: LinearColor) -> Self {
+        self.absorption = Some(absorption);
+        self
     }
 }
 
@@ -36,6 +95,22 @@ impl Material for UniformMaterial {
     fn properties(&self, _: Point2D) -> LightProperties {
         self.properties.clone()
     }
+
+    /// Transparent materials let shadow rays through in proportion to their transparency, unless
+    /// a translucency is given.
+    fn translucency(&self, _: Point2D) -> Option<LinearColor> {
+        match (&self.translucency, &self.properties.refl_trans) {
+            (Some(translucency), _) => Some(translucency.clone()),
+            (None, Some(ReflTransEnum::Transparency { coef, .. })) => {
+                Some(LinearColor::new(*coef, *coef, *coef))
+            }
+            (None, _) => None,
+        }
+    }
+
+    fn absorption(&self, _: Point2D) -> Option<LinearColor> {
+        self.absorption.clone()
+    }
 }
 
 #[cfg(test)]
@@ -52,7 +127,14 @@ mod test {
             refl_trans: None,
         };
         let mat = UniformMaterial::new(properties.clone());
-        assert_eq!(mat, UniformMaterial { properties })
+        assert_eq!(
+            mat,
+            UniformMaterial {
+                properties,
+                translucency: None,
+                absorption: None,
+            }
+        )
     }
 
     #[test]
@@ -83,4 +165,76 @@ mod test {
             ))
         )
     }
+
+    #[test]
+    fn transparency_lets_shadows_through() {
+        let transparency = Some(ReflTransEnum::Transparency {
+            coef: 0.8,
+            index: 1.5,
+        });
+        let properties = LightProperties::new(
+            LinearColor::new(0.1, 0.5, 0.1),
+            LinearColor::black(),
+            transparency,
+        );
+        let glass = UniformMaterial::new(properties.clone());
+        assert_eq!(
+            glass.translucency(Point2D::origin()),
+            Some(LinearColor::new(0.8, 0.8, 0.8))
+        );
+        let tinted = glass.with_translucency(LinearColor::new(0.2, 0.5, 0.1));
+        assert_eq!(
+            tinted.translucency(Point2D::origin()),
+            Some(LinearColor::new(0.2, 0.5, 0.1))
+        );
+        let mirror = UniformMaterial::new(LightProperties::new(
+            LinearColor::black(),
+            LinearColor::black(),
+            Some(ReflTransEnum::Reflectivity { coef: 1. }),
+        ));
+        assert_eq!(mirror.translucency(Point2D::origin()), None);
+    }
+
+    #[test]
+    fn translucency_deserialization_works() {
+        let yaml = r#"
+            diffuse: {r: 0.1, g: 0.5, b: 0.1}
+            specular: {r: 0.0, g: 0.0, b: 0.0}
+            translucency: {r: 0.2, g: 0.5, b: 0.1}
+        "#;
+        let material: UniformMaterial = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            material,
+            UniformMaterial::new(LightProperties::new(
+                LinearColor::new(0.1, 0.5, 0.1),
+                LinearColor::black(),
+                None,
+            ))
+            .with_translucency(LinearColor::new(0.2, 0.5, 0.1))
+        )
+    }
+
+    #[test]
+    fn absorption_deserialization_works() {
+        let yaml = r#"
+            diffuse: {r: 0.0, g: 0.0, b: 0.0}
+            specular: {r: 0.0, g: 0.0, b: 0.0}
+            transparency: 1.0
+            index: 1.5
+            absorption: {r: 0.8, g: 0.1, b: 0.8}
+        "#;
+        let material: UniformMaterial = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            material,
+            UniformMaterial::new(LightProperties::new(
+                LinearColor::black(),
+                LinearColor::black(),
+                Some(ReflTransEnum::Transparency {
+                    coef: 1.,
+                    index: 1.5
+                }),
+            ))
+            .with_absorption(LinearColor::new(0.8, 0.1, 0.8))
+        )
+    }
 }
@@ -0,0 +1,584 @@
+//! Triangle meshes, loaded and processed before being turned into the scene's objects
+
+use crate::material::MaterialEnum;
+use crate::shape::Triangle;
+use crate::{Float, Result};
+use crate::{Point, Point2D, Vector};
+use nalgebra::{Affine3, Matrix3, Unit, U3};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+
+mod mtl;
+pub use mtl::*;
+
+mod obj;
+pub use obj::*;
+
+mod ply;
+pub use ply::*;
+
+mod point_set;
+pub use point_set::*;
+
+mod stl;
+pub use stl::*;
+
+mod subdivision;
+pub use subdivision::*;
+
+/// A triangle mesh, whose faces share their vertices.
+///
+/// Its faces can also have their own texture coordinates and materials, e.g: when loaded from a
+/// file along with its material libraries.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mesh {
+    vertices: Vec<Point>,
+    faces: Vec<[usize; 3]>,
+    texcoords: Option<Vec<[Point2D; 3]>>,
+    materials: Vec<MaterialEnum>,
+    /// The index of each face's material, if it has one, empty when no face has one.
+    face_materials: Vec<Option<usize>>,
+    /// The largest angle between faces whose normals are smoothed together, in degrees.
+    smoothing_angle: Option<Float>,
+}
+
+impl Mesh {
+    /// Creates a new `Mesh` from its vertices and the indices of each face's corners.
+    ///
+    /// Returns `None` if a face refers to a vertex which does not exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::mesh::Mesh;
+    /// # use pathtracer::Point;
+    /// #
+    /// let quad = Mesh::new(
+    ///     vec![
+    ///         Point::new(0.0, 0.0, 0.0),
+    ///         Point::new(1.0, 0.0, 0.0),
+    ///         Point::new(1.0, 1.0, 0.0),
+    ///         Point::new(0.0, 1.0, 0.0),
+    ///     ],
+    ///     vec![[0, 1, 2], [0, 2, 3]],
+    /// )
+    /// .unwrap();
+    /// assert!(Mesh::new(vec![Point::origin()], vec![[0, 1, 2]]).is_none());
+    /// ```
+    pub fn new(vertices: Vec<Point>, faces: Vec<[usize; 3]>) -> Option<Self> {
+        if faces.iter().flatten().any(|&i| i >= vertices.len()) {
+            return None;
+        }
+        Some(Mesh {
+            vertices,
+            faces,
+            texcoords: None,
+            materials: Vec::new(),
+            face_materials: Vec::new(),
+            smoothing_angle: None,
+        })
+    }
+
+    /// Set the texture coordinates of each face's corners.
+    ///
+    /// Returns `None` if there are not as many of them as there are faces.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::mesh::Mesh;
+    /// # use pathtracer::{Point, Point2D};
+    /// #
+    /// let triangle = Mesh::new(
+    ///     vec![
+    ///         Point::new(0.0, 0.0, 0.0),
+    ///         Point::new(1.0, 0.0, 0.0),
+    ///         Point::new(0.0, 1.0, 0.0),
+    ///     ],
+    ///     vec![[0, 1, 2]],
+    /// )
+    /// .unwrap()
+    /// .with_texcoords(vec![[
+    ///     Point2D::new(0.0, 0.0),
+    ///     Point2D::new(1.0, 0.0),
+    ///     Point2D::new(0.0, 1.0),
+    /// ]])
+    /// .unwrap();
+    /// assert_eq!(triangle.texcoords().unwrap().len(), 1);
+    /// ```
+    pub fn with_texcoords(mut self, texcoords: Vec<[Point2D; 3]>) -> Option<Self> {
+        if texcoords.len() != self.faces.len() {
+            return None;
+        }
+        self.texcoords = Some(texcoords);
+        Some(self)
+    }
+
+    /// Set the materials used by the `Mesh`, and the index of each face's material, faces without
+    /// one using the material of the object they belong to.
+    ///
+    /// Returns `None` if there is not an entry for each face, or if one refers to a material
+    /// which does not exist.
+    pub fn with_materials(
+        mut self,
+        materials: Vec<MaterialEnum>,
+        face_materials: Vec<Option<usize>>,
+    ) -> Option<Self> {
+        if face_materials.len() != self.faces.len()
+            || face_materials
+                .iter()
+                .flatten()
+                .any(|&i| i >= materials.len())
+        {
+            return None;
+        }
+        self.materials = materials;
+        self.face_materials = face_materials;
+        Some(self)
+    }
+
+    /// Shade the `Mesh` smoothly, interpolating normals over the faces which share a vertex, unless
+    /// they meet at an angle greater than `angle`, in degrees, to keep hard edges sharp.
+    ///
+    /// The normals are computed when turning the `Mesh` into [`Triangle`]s, after it has been
+    /// transformed.
+    ///
+    /// [`Triangle`]: ../shape/struct.Triangle.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::mesh::Mesh;
+    /// # use pathtracer::Point;
+    /// #
+    /// let mesh = Mesh::new(
+    ///     vec![
+    ///         Point::new(0.0, 0.0, 0.0),
+    ///         Point::new(1.0, 0.0, 0.0),
+    ///         Point::new(0.0, 1.0, 0.0),
+    ///     ],
+    ///     vec![[0, 1, 2]],
+    /// )
+    /// .unwrap()
+    /// .with_smoothing(30.0);
+    /// assert_eq!(mesh.smoothing_angle(), Some(30.0));
+    /// ```
+    pub fn with_smoothing(mut self, angle: Float) -> Self {
+        self.smoothing_angle = Some(angle);
+        self
+    }
+
+    /// Get the angle under which faces are shaded smoothly, if the `Mesh` is not flat-shaded.
+    pub fn smoothing_angle(&self) -> Option<Float> {
+        self.smoothing_angle
+    }
+
+    /// Get the `Mesh`'s vertices.
+    pub fn vertices(&self) -> &[Point] {
+        &self.vertices
+    }
+
+    /// Get the `Mesh`'s vertices, to modify their positions.
+    pub fn vertices_mut(&mut self) -> &mut [Point] {
+        &mut self.vertices
+    }
+
+    /// Get the indices of the corners of each of the `Mesh`'s faces.
+    pub fn faces(&self) -> &[[usize; 3]] {
+        &self.faces
+    }
+
+    /// Get the texture coordinates of each face's corners, if the `Mesh` has any.
+    pub fn texcoords(&self) -> Option<&[[Point2D; 3]]> {
+        self.texcoords.as_deref()
+    }
+
+    /// Get the material of a face, if it has its own.
+    pub fn face_material(&self, face: usize) -> Option<&MaterialEnum> {
+        let index = (*self.face_materials.get(face)?)?;
+        Some(&self.materials[index])
+    }
+
+    /// Compute the normal at each vertex, as the average of the normals of the faces around it
+    /// weighted by their area.
+    ///
+    /// Vertices which are not part of any face are given the Y-axis as their normal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::mesh::Mesh;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let triangle = Mesh::new(
+    ///     vec![
+    ///         Point::new(0.0, 0.0, 0.0),
+    ///         Point::new(1.0, 0.0, 0.0),
+    ///         Point::new(0.0, 1.0, 0.0),
+    ///     ],
+    ///     vec![[0, 1, 2]],
+    /// )
+    /// .unwrap();
+    /// assert!(triangle.vertex_normals().iter().all(|n| *n == Vector::z_axis()));
+    /// ```
+    pub fn vertex_normals(&self) -> Vec<Unit<Vector>> {
+        let mut normals = vec![Vector::zeros(); self.vertices.len()];
+        for face in &self.faces {
+            let [a, b, c] = *face;
+            // The cross product's norm is twice the face's area
+            let normal =
+                (self.vertices[b] - self.vertices[a]).cross(&(self.vertices[c] - self.vertices[a]));
+            for &i in face {
+                normals[i] += normal;
+            }
+        }
+        normals
+            .into_iter()
+            .map(|n| Unit::try_new(n, 0.).unwrap_or_else(Vector::y_axis))
+            .collect()
+    }
+
+    /// Apply a linear transformation to each of the `Mesh`'s vertices.
+    ///
+    /// When the transformation mirrors the mesh, the faces' winding order is reversed to keep
+    /// their orientation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::mesh::Mesh;
+    /// # use pathtracer::Point;
+    /// use nalgebra::Matrix3;
+    ///
+    /// let mut mesh = Mesh::new(
+    ///     vec![
+    ///         Point::new(0.0, 0.0, 0.0),
+    ///         Point::new(1.0, 0.0, 0.0),
+    ///         Point::new(0.0, 1.0, 0.0),
+    ///     ],
+    ///     vec![[0, 1, 2]],
+    /// )
+    /// .unwrap();
+    /// mesh.transform(&Matrix3::new(-1., 0., 0., 0., 1., 0., 0., 0., 1.));
+    /// assert_eq!(mesh.vertices()[1], Point::new(-1.0, 0.0, 0.0));
+    /// assert_eq!(mesh.faces(), &[[0, 2, 1]]);
+    /// ```
+    pub fn transform(&mut self, matrix: &Matrix3<Float>) {
+        for vertex in self.vertices.iter_mut() {
+            *vertex = Point::from(matrix * vertex.coords);
+        }
+        if matrix.determinant() < 0. {
+            self.reverse_winding();
+        }
+    }
+
+    /// Apply an affine transformation to each of the `Mesh`'s vertices.
+    ///
+    /// When the transformation mirrors the mesh, the faces' winding order is reversed to keep
+    /// their orientation.
+    pub fn transform_affine(&mut self, affine: &Affine3<Float>) {
+        for vertex in self.vertices.iter_mut() {
+            *vertex = affine * *vertex;
+        }
+        let linear = affine.matrix().fixed_slice::<U3, U3>(0, 0).into_owned();
+        if linear.determinant() < 0. {
+            self.reverse_winding();
+        }
+    }
+
+    /// Compute the normal at each face's corners, as the area-weighted average of the normals of
+    /// the faces around the corner's vertex meeting the face at no more than `angle` degrees.
+    fn smooth_normals(&self, angle: Float) -> Vec<[Unit<Vector>; 3]> {
+        // The cross product's norm is twice the face's area
+        let face_normals: Vec<_> = (self.faces.iter())
+            .map(|&[a, b, c]| {
+                (self.vertices[b] - self.vertices[a]).cross(&(self.vertices[c] - self.vertices[a]))
+            })
+            .collect();
+        let mut neighbours = vec![Vec::new(); self.vertices.len()];
+        for (i, face) in self.faces.iter().enumerate() {
+            for &vertex in face {
+                neighbours[vertex].push(i);
+            }
+        }
+        let min_cos = angle.to_radians().cos();
+        let unit = |normal: &Vector| Unit::try_new(*normal, 0.).unwrap_or_else(Vector::y_axis);
+        (self.faces.iter().zip(face_normals.iter()))
+            .map(|(face, normal)| {
+                let corner = |vertex: usize| {
+                    let sum: Vector = (neighbours[vertex].iter())
+                        .map(|&i| &face_normals[i])
+                        .filter(|other| unit(normal).dot(&unit(other)) >= min_cos)
+                        .sum();
+                    unit(&sum)
+                };
+                [corner(face[0]), corner(face[1]), corner(face[2])]
+            })
+            .collect()
+    }
+
+    fn reverse_winding(&mut self) {
+        for face in self.faces.iter_mut() {
+            face.swap(1, 2);
+        }
+        for texcoords in self.texcoords.iter_mut().flatten() {
+            texcoords.swap(1, 2);
+        }
+    }
+
+    /// Return the [`Triangle`] corresponding to each face of the `Mesh`.
+    ///
+    /// [`Triangle`]: ../shape/struct.Triangle.html
+    pub fn triangles(&self) -> impl Iterator<Item = Triangle> + '_ {
+        let normals = self.smoothing_angle.map(|angle| self.smooth_normals(angle));
+        self.faces.iter().enumerate().map(move |(i, &[a, b, c])| {
+            let mut triangle = Triangle::new(self.vertices[a], self.vertices[b], self.vertices[c]);
+            if let Some(texcoords) = &self.texcoords {
+                triangle = triangle.with_texcoords(texcoords[i]);
+            }
+            if let Some(normals) = &normals {
+                triangle = triangle.with_normals(normals[i]);
+            }
+            triangle
+        })
+    }
+}
+
+/// Load a [`Mesh`] from a file, its format being guessed from its extension: STL files are loaded
+/// with [`load_stl`], any other file with [`load_obj`].
+///
+/// [`Mesh`]: struct.Mesh.html
+/// [`load_stl`]: fn.load_stl.html
+/// [`load_obj`]: fn.load_obj.html
+pub fn load_mesh<P: AsRef<Path>>(path: P) -> Result<Mesh> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    match extension.map(str::to_lowercase).as_deref() {
+        Some("stl") => load_stl(path),
+        _ => load_obj(path),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SerializedMesh {
+    File {
+        file: PathBuf,
+        #[serde(default)]
+        smoothing_angle: Option<Float>,
+    },
+    Inline {
+        vertices: Vec<Point>,
+        faces: Vec<[usize; 3]>,
+        #[serde(default)]
+        smoothing_angle: Option<Float>,
+    },
+}
+
+impl<'de> Deserialize<'de> for Mesh {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (mesh, smoothing_angle) = match Deserialize::deserialize(deserializer)? {
+            SerializedMesh::File {
+                file,
+                smoothing_angle,
+            } => {
+                let mesh = load_mesh(&file).map_err(D::Error::custom)?;
+                (mesh, smoothing_angle)
+            }
+            SerializedMesh::Inline {
+                vertices,
+                faces,
+                smoothing_angle,
+            } => {
+                let mesh = Mesh::new(vertices, faces)
+                    .ok_or_else(|| D::Error::custom("face refers to a non-existent vertex"))?;
+                (mesh, smoothing_angle)
+            }
+        };
+        Ok(match smoothing_angle {
+            Some(angle) => mesh.with_smoothing(angle),
+            None => mesh,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn simple_quad() -> Mesh {
+        Mesh::new(
+            vec![
+                Point::new(0., 0., 0.),
+                Point::new(1., 0., 0.),
+                Point::new(1., 0., 1.),
+                Point::new(0., 0., 1.),
+            ],
+            vec![[0, 2, 1], [0, 3, 2]],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn new_checks_indices() {
+        assert!(Mesh::new(vec![Point::origin(); 3], vec![[0, 1, 3]]).is_none())
+    }
+
+    #[test]
+    fn vertex_normals_work() {
+        let mesh = simple_quad();
+        assert!(mesh
+            .vertex_normals()
+            .iter()
+            .all(|n| (n.as_ref() - Vector::y()).norm() < 1e-5))
+    }
+
+    #[test]
+    fn vertex_normals_are_area_weighted() {
+        // Two faces folded at a right angle along the Z-axis, the first one being bigger
+        let mesh = Mesh::new(
+            vec![
+                Point::new(0., 0., 0.),
+                Point::new(0., 0., 1.),
+                Point::new(2., 0., 0.),
+                Point::new(0., 1., 0.),
+            ],
+            vec![[0, 1, 2], [0, 3, 1]],
+        )
+        .unwrap();
+        let normal = mesh.vertex_normals()[0];
+        assert!((normal.as_ref() - Vector::new(1., 2., 0.).normalize()).norm() < 1e-5)
+    }
+
+    #[test]
+    fn smoothing_keeps_hard_edges() {
+        use crate::shape::Shape;
+
+        // Two faces folded at a right angle along the Z-axis
+        let mesh = Mesh::new(
+            vec![
+                Point::new(0., 0., 0.),
+                Point::new(0., 0., 1.),
+                Point::new(1., 0., 0.),
+                Point::new(0., 1., 0.),
+            ],
+            vec![[0, 1, 2], [0, 3, 1]],
+        )
+        .unwrap();
+        let corner_normal = |mesh: &Mesh| mesh.triangles().next().unwrap().normal(&Point::origin());
+        let flat = Vector::y();
+        assert!((corner_normal(&mesh).as_ref() - flat).norm() < 1e-5);
+        let sharp = mesh.clone().with_smoothing(60.);
+        assert!((corner_normal(&sharp).as_ref() - flat).norm() < 1e-5);
+        let smooth = mesh.with_smoothing(120.);
+        let expected = Vector::new(1., 1., 0.).normalize();
+        assert!((corner_normal(&smooth).as_ref() - expected).norm() < 1e-5);
+    }
+
+    #[test]
+    fn triangles_work() {
+        let mesh = simple_quad();
+        let triangles: Vec<_> = mesh.triangles().collect();
+        assert_eq!(
+            triangles,
+            vec![
+                Triangle::new(
+                    Point::new(0., 0., 0.),
+                    Point::new(1., 0., 1.),
+                    Point::new(1., 0., 0.)
+                ),
+                Triangle::new(
+                    Point::new(0., 0., 0.),
+                    Point::new(0., 0., 1.),
+                    Point::new(1., 0., 1.)
+                ),
+            ]
+        )
+    }
+
+    #[test]
+    fn mirroring_keeps_texcoords() {
+        use crate::shape::Shape;
+
+        let texcoords = [
+            Point2D::new(0., 0.),
+            Point2D::new(1., 0.),
+            Point2D::new(0., 1.),
+        ];
+        let mut mesh = simple_quad().with_texcoords(vec![texcoords; 2]).unwrap();
+        let before: Vec<_> = mesh.triangles().collect();
+        mesh.transform(&Matrix3::new(1., 0., 0., 0., 1., 0., 0., 0., -1.));
+        let after: Vec<_> = mesh.triangles().collect();
+        // Each corner keeps its texture coordinates
+        let corner = Point::new(1., 0., 1.);
+        let mirrored = Point::new(1., 0., -1.);
+        assert_eq!(
+            before[0].project_texel(&corner),
+            after[0].project_texel(&mirrored)
+        );
+    }
+
+    #[test]
+    fn face_materials_work() {
+        use crate::core::LinearColor;
+        use crate::material::PrincipledMaterial;
+
+        let material: MaterialEnum = PrincipledMaterial::new(LinearColor::black()).into();
+        let mesh = simple_quad()
+            .with_materials(vec![material.clone()], vec![None, Some(0)])
+            .unwrap();
+        assert_eq!(mesh.face_material(0), None);
+        assert_eq!(mesh.face_material(1), Some(&material));
+        assert!(simple_quad()
+            .with_materials(vec![material], vec![Some(1), None])
+            .is_none());
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            vertices:
+              - [0.0, 0.0, 0.0]
+              - [1.0, 0.0, 0.0]
+              - [1.0, 0.0, 1.0]
+              - [0.0, 0.0, 1.0]
+            faces:
+              - [0, 2, 1]
+              - [0, 3, 2]
+        "#;
+        let mesh: Mesh = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(mesh, simple_quad())
+    }
+
+    #[test]
+    fn smoothing_deserialization_works() {
+        let yaml = r#"
+            vertices:
+              - [0.0, 0.0, 0.0]
+              - [1.0, 0.0, 0.0]
+              - [1.0, 0.0, 1.0]
+              - [0.0, 0.0, 1.0]
+            faces:
+              - [0, 2, 1]
+              - [0, 3, 2]
+            smoothing_angle: 30.0
+        "#;
+        let mesh: Mesh = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(mesh, simple_quad().with_smoothing(30.))
+    }
+
+    #[test]
+    fn deserialization_checks_indices() {
+        let yaml = r#"
+            vertices:
+              - [0.0, 0.0, 0.0]
+            faces:
+              - [0, 1, 2]
+        "#;
+        assert!(serde_yaml::from_str::<Mesh>(yaml).is_err())
+    }
+}
@@ -0,0 +1,257 @@
+use crate::core::LinearColor;
+use crate::material::PrincipledMaterial;
+use crate::texture::ImageTexture;
+use crate::{Error, Float, Result};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// A material described in a Wavefront MTL library.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MtlMaterial {
+    /// The diffuse color, given by `Kd`.
+    pub diffuse: LinearColor,
+    /// The specular color, given by `Ks`.
+    pub specular: LinearColor,
+    /// The specular exponent, given by `Ns`, if any.
+    pub shininess: Option<Float>,
+    /// The index of refraction, given by `Ni`, if any.
+    pub ior: Option<Float>,
+    /// The image multiplied with the diffuse color, given by `map_Kd`, if any.
+    pub diffuse_map: Option<PathBuf>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: LinearColor::new(0.8, 0.8, 0.8),
+            specular: LinearColor::black(),
+            shininess: None,
+            ior: None,
+            diffuse_map: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    /// Convert to the closest [`PrincipledMaterial`], loading its diffuse map if any.
+    ///
+    /// The diffuse color becomes the base color, the specular exponent is converted to a
+    /// roughness, and the luminance of the specular color sets the strength of the specular
+    /// reflection, a specular color of 0.08 or more being the strongest.
+    ///
+    /// [`PrincipledMaterial`]: ../material/struct.PrincipledMaterial.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::mesh::MtlMaterial;
+    /// #
+    /// let mtl = MtlMaterial {
+    ///     diffuse: LinearColor::new(1.0, 0.0, 0.0),
+    ///     shininess: Some(0.0),
+    ///     ..Default::default()
+    /// };
+    /// let material = mtl.to_material().unwrap();
+    /// assert_eq!(material.base_color, LinearColor::new(1.0, 0.0, 0.0));
+    /// assert_eq!(material.roughness, 1.0);
+    /// assert_eq!(material.specular, 0.0);
+    /// ```
+    pub fn to_material(&self) -> Result<PrincipledMaterial> {
+        let mut material = PrincipledMaterial::new(self.diffuse.clone());
+        material.specular = (self.specular.luminance() / 0.08).clamp(0., 1.);
+        if let Some(shininess) = self.shininess {
+            // The Blinn-Phong exponent matching a microfacet distribution's width
+            let alpha = (2. / (shininess.max(0.) + 2.)).sqrt();
+            material.roughness = alpha.sqrt();
+        }
+        if let Some(ior) = self.ior {
+            material.ior = ior;
+        }
+        if let Some(path) = &self.diffuse_map {
+            material.base_color_map = Some(Box::new(ImageTexture::open(path)?.into()));
+        }
+        Ok(material)
+    }
+}
+
+/// Parse the named materials of a Wavefront MTL library, only keeping their `Kd`, `Ks`, `Ns`, `Ni`
+/// and `map_Kd` statements.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::core::LinearColor;
+/// # use pathtracer::mesh::parse_mtl;
+/// #
+/// let mtl = "
+/// newmtl red
+/// Kd 1.0 0.0 0.0
+/// Ns 100
+/// map_Kd bricks.png
+/// ";
+/// let materials = parse_mtl(mtl.as_bytes()).unwrap();
+/// assert_eq!(materials["red"].diffuse, LinearColor::new(1.0, 0.0, 0.0));
+/// assert_eq!(materials["red"].shininess, Some(100.0));
+/// assert_eq!(materials["red"].diffuse_map.as_ref().unwrap().to_str(), Some("bricks.png"));
+/// ```
+pub fn parse_mtl<R: BufRead>(reader: R) -> Result<BTreeMap<String, MtlMaterial>> {
+    let mut materials = BTreeMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = line_number + 1;
+        let mut tokens = line.split_whitespace();
+        let statement = match tokens.next() {
+            Some(statement) => statement,
+            None => continue,
+        };
+        if statement == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            materials.extend(current.replace((name, MtlMaterial::default())));
+            continue;
+        }
+        let material = match &mut current {
+            Some((_, material)) => material,
+            // Statements outside of a material are ignored
+            None => continue,
+        };
+        let values = || {
+            tokens
+                .clone()
+                .map(str::parse)
+                .collect::<Result<Vec<Float>, _>>()
+                .map_err(|_| Error::parse(line_number, "invalid number"))
+        };
+        let color = || match values()?.as_slice() {
+            [r, g, b] => Ok(LinearColor::new(*r, *g, *b)),
+            // A single value is used for all three channels
+            [value] => Ok(LinearColor::new(*value, *value, *value)),
+            _ => Err(Error::parse(line_number, "expected a color")),
+        };
+        let scalar = || match values()?.as_slice() {
+            [value] => Ok(*value),
+            _ => Err(Error::parse(line_number, "expected a single value")),
+        };
+        match statement {
+            "Kd" => material.diffuse = color()?,
+            "Ks" => material.specular = color()?,
+            "Ns" => material.shininess = Some(scalar()?),
+            "Ni" => material.ior = Some(scalar()?),
+            // Texture options come before the file name
+            "map_Kd" => {
+                let file = tokens
+                    .last()
+                    .ok_or_else(|| Error::parse(line_number, "expected a file name"))?;
+                material.diffuse_map = Some(PathBuf::from(file));
+            }
+            // Ignore any other kind of statement
+            _ => {}
+        }
+    }
+    materials.extend(current);
+    Ok(materials)
+}
+
+/// Load the named materials of a Wavefront MTL library, as described in [`parse_mtl`], the paths
+/// of their images being relative to the library's directory.
+///
+/// [`parse_mtl`]: fn.parse_mtl.html
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<BTreeMap<String, MtlMaterial>> {
+    let path = path.as_ref();
+    let mut materials = (File::open(path).map_err(Error::from))
+        .and_then(|file| parse_mtl(BufReader::new(file)))
+        .map_err(|err| err.with_path(path))?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    for material in materials.values_mut() {
+        if let Some(map) = &mut material.diffuse_map {
+            *map = directory.join(&map);
+        }
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_works() {
+        let mtl = r#"
+            # Two materials
+            newmtl first
+            Kd 0.5 0.25 1.0
+            Ks 0.1
+            illum 2
+
+            newmtl second
+            Ni 1.33
+        "#;
+        let materials = parse_mtl(mtl.as_bytes()).unwrap();
+        assert_eq!(materials.len(), 2);
+        assert_eq!(
+            materials["first"],
+            MtlMaterial {
+                diffuse: LinearColor::new(0.5, 0.25, 1.),
+                specular: LinearColor::new(0.1, 0.1, 0.1),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            materials["second"],
+            MtlMaterial {
+                ior: Some(1.33),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn map_options_are_skipped() {
+        let mtl = "newmtl textured\nmap_Kd -s 2 2 1 -blendu on wood.png\n";
+        let materials = parse_mtl(mtl.as_bytes()).unwrap();
+        assert_eq!(
+            materials["textured"].diffuse_map,
+            Some(PathBuf::from("wood.png"))
+        );
+    }
+
+    #[test]
+    fn invalid_color_fails() {
+        let mtl = "newmtl broken\nKd 1.0 red 0.0\n";
+        let err = parse_mtl(mtl.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Parse { line: Some(2), .. }))
+    }
+
+    #[test]
+    fn shininess_sets_roughness() {
+        let shiny = MtlMaterial {
+            shininess: Some(1000.),
+            specular: LinearColor::new(1., 1., 1.),
+            ..Default::default()
+        };
+        let material = shiny.to_material().unwrap();
+        assert!(material.roughness < 0.25);
+        assert_eq!(material.specular, 1.);
+    }
+
+    #[test]
+    fn load_works() {
+        let directory = std::env::temp_dir().join(format!("mtl-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let library = directory.join("materials.mtl");
+        std::fs::write(&library, "newmtl textured\nmap_Kd texture.png\n").unwrap();
+        image::RgbImage::new(1, 1)
+            .save(directory.join("texture.png"))
+            .unwrap();
+
+        let materials = load_mtl(&library).unwrap();
+        let map = materials["textured"].diffuse_map.as_ref().unwrap();
+        assert_eq!(map, &directory.join("texture.png"));
+        let material = materials["textured"].to_material().unwrap();
+        assert!(material.base_color_map.is_some());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
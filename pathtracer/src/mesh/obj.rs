@@ -0,0 +1,376 @@
+use super::{load_mtl, Mesh};
+use crate::material::MaterialEnum;
+use crate::{Error, Float, Result};
+use crate::{Point, Point2D};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Resolve an index into a list of `count` elements.
+///
+/// Indices start at 1, negative ones are relative to the end of the current list.
+fn parse_index(index: &str, count: usize, line: usize) -> Result<usize> {
+    let index: i64 = index
+        .parse()
+        .map_err(|_| Error::parse(line, "invalid face index"))?;
+    let index = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if index < 0 || index as usize >= count {
+        return Err(Error::parse(line, "face refers to a non-existent element"));
+    }
+    Ok(index as usize)
+}
+
+/// Parse the vertex index of a face's corner, and its texture coordinates index if any, ignoring
+/// its normal index.
+fn parse_corner(
+    corner: &str,
+    vertex_count: usize,
+    texcoord_count: usize,
+    line: usize,
+) -> Result<(usize, Option<usize>)> {
+    let mut indices = corner.split('/');
+    let vertex = parse_index(indices.next().unwrap_or_default(), vertex_count, line)?;
+    let texcoord = match indices.next() {
+        Some(index) if !index.is_empty() => Some(parse_index(index, texcoord_count, line)?),
+        _ => None,
+    };
+    Ok((vertex, texcoord))
+}
+
+/// The contents of an OBJ file, before loading its material libraries.
+struct ObjFile {
+    mesh: Mesh,
+    /// The material libraries referred to by `mtllib` statements.
+    libraries: Vec<String>,
+    /// The names of the materials used by `usemtl` statements.
+    material_names: Vec<String>,
+    /// The index of the name of each face's material, if any.
+    face_materials: Vec<Option<usize>>,
+}
+
+fn parse<R: BufRead>(reader: R) -> Result<ObjFile> {
+    let mut vertices = Vec::new();
+    let mut texcoords = Vec::new();
+    let mut faces = Vec::new();
+    let mut face_texcoords = Vec::new();
+    let mut has_texcoords = false;
+    let mut libraries = Vec::new();
+    let mut material_names: Vec<String> = Vec::new();
+    let mut face_materials = Vec::new();
+    let mut material = None;
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = line_number + 1;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let coords = tokens
+                    .take(3)
+                    .map(str::parse)
+                    .collect::<Result<Vec<Float>, _>>()
+                    .map_err(|_| Error::parse(line_number, "invalid vertex coordinate"))?;
+                if coords.len() != 3 {
+                    return Err(Error::parse(line_number, "expected 3 vertex coordinates"));
+                }
+                vertices.push(Point::new(coords[0], coords[1], coords[2]));
+            }
+            Some("vt") => {
+                let coords = tokens
+                    .take(2)
+                    .map(str::parse)
+                    .collect::<Result<Vec<Float>, _>>()
+                    .map_err(|_| Error::parse(line_number, "invalid texture coordinate"))?;
+                // The second coordinate is optional
+                let v = coords.get(1).copied().unwrap_or_default();
+                match coords.first() {
+                    Some(&u) => texcoords.push(Point2D::new(u, v)),
+                    None => return Err(Error::parse(line_number, "expected texture coordinates")),
+                }
+            }
+            Some("f") => {
+                let corners = tokens
+                    .map(|corner| {
+                        parse_corner(corner, vertices.len(), texcoords.len(), line_number)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if corners.len() < 3 {
+                    return Err(Error::parse(
+                        line_number,
+                        "expected at least 3 face corners",
+                    ));
+                }
+                // Faces without texture coordinates use their barycentric coordinates instead
+                let uv = corners
+                    .iter()
+                    .map(|(_, texcoord)| texcoord.map(|i| texcoords[i]))
+                    .collect::<Option<Vec<_>>>();
+                has_texcoords |= uv.is_some();
+                for i in 1..corners.len() - 1 {
+                    faces.push([corners[0].0, corners[i].0, corners[i + 1].0]);
+                    face_texcoords.push(match &uv {
+                        Some(uv) => [uv[0], uv[i], uv[i + 1]],
+                        None => [
+                            Point2D::new(0., 0.),
+                            Point2D::new(1., 0.),
+                            Point2D::new(0., 1.),
+                        ],
+                    });
+                    face_materials.push(material);
+                }
+            }
+            Some("mtllib") => libraries.extend(tokens.map(String::from)),
+            Some("usemtl") => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                material = match material_names.iter().position(|n| *n == name) {
+                    Some(index) => Some(index),
+                    None => {
+                        material_names.push(name);
+                        Some(material_names.len() - 1)
+                    }
+                };
+            }
+            // Ignore any other kind of statement
+            _ => {}
+        }
+    }
+    let mesh = Mesh::new(vertices, faces)
+        .ok_or_else(|| Error::invalid_data("faces refer to missing vertices"))?;
+    let mesh = if has_texcoords {
+        (mesh.with_texcoords(face_texcoords))
+            .ok_or_else(|| Error::invalid_data("faces refer to missing texture coordinates"))?
+    } else {
+        mesh
+    };
+    Ok(ObjFile {
+        mesh,
+        libraries,
+        material_names,
+        face_materials,
+    })
+}
+
+/// Parse a [`Mesh`] in the Wavefront OBJ format, only keeping its vertices, texture coordinates
+/// and faces. Its materials are only read by [`load_obj`], which can find its material libraries.
+///
+/// Faces with more than three corners are split into a fan of triangles.
+///
+/// [`Mesh`]: struct.Mesh.html
+/// [`load_obj`]: fn.load_obj.html
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::mesh::parse_obj;
+/// #
+/// let obj = "
+/// v 0.0 0.0 0.0
+/// v 1.0 0.0 0.0
+/// v 1.0 1.0 0.0
+/// v 0.0 1.0 0.0
+/// f 1 2 3 4
+/// ";
+/// let mesh = parse_obj(obj.as_bytes()).unwrap();
+/// assert_eq!(mesh.vertices().len(), 4);
+/// assert_eq!(mesh.faces(), &[[0, 1, 2], [0, 2, 3]]);
+/// ```
+pub fn parse_obj<R: BufRead>(reader: R) -> Result<Mesh> {
+    Ok(parse(reader)?.mesh)
+}
+
+/// Load a [`Mesh`] from a Wavefront OBJ file, as described in [`parse_obj`], along with the
+/// materials of its faces.
+///
+/// The material libraries are read from the file's directory, and each of their materials is
+/// converted as described in [`MtlMaterial::to_material`]. Faces whose material cannot be found
+/// in any of the libraries are left without one.
+///
+/// [`Mesh`]: struct.Mesh.html
+/// [`parse_obj`]: fn.parse_obj.html
+/// [`MtlMaterial::to_material`]: struct.MtlMaterial.html#method.to_material
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Mesh> {
+    let path = path.as_ref();
+    let obj = (File::open(path).map_err(Error::from))
+        .and_then(|file| parse(BufReader::new(file)))
+        .map_err(|err| err.with_path(path))?;
+    if obj.material_names.is_empty() {
+        return Ok(obj.mesh);
+    }
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut library = BTreeMap::new();
+    for name in &obj.libraries {
+        let path = directory.join(name);
+        library.extend(load_mtl(&path)?);
+    }
+    // Only keep the materials which were found, renumbering the faces' materials accordingly
+    let mut materials = Vec::new();
+    let mut indices = Vec::new();
+    for name in &obj.material_names {
+        indices.push(match library.get(name) {
+            Some(mtl) => {
+                materials.push(MaterialEnum::from(mtl.to_material()?));
+                Some(materials.len() - 1)
+            }
+            None => None,
+        });
+    }
+    let face_materials = (obj.face_materials.iter())
+        .map(|material| material.and_then(|i| indices[i]))
+        .collect();
+    (obj.mesh.with_materials(materials, face_materials))
+        .ok_or_else(|| Error::invalid_data("faces refer to missing materials").with_path(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::LinearColor;
+
+    #[test]
+    fn parse_works() {
+        let obj = r#"
+            # A single triangle, with texture coordinates and normals
+            o triangle
+            v 0.0 0.0 0.0
+            v 1.0 0.0 0.0
+            v 0.0 1.0 0.0
+            vt 0.0 0.0
+            vn 0.0 0.0 1.0
+            s off
+            f 1/1/1 2/1/1 3/1/1
+        "#;
+        let mesh = parse_obj(obj.as_bytes()).unwrap();
+        assert_eq!(
+            mesh,
+            Mesh::new(
+                vec![
+                    Point::new(0., 0., 0.),
+                    Point::new(1., 0., 0.),
+                    Point::new(0., 1., 0.)
+                ],
+                vec![[0, 1, 2]]
+            )
+            .unwrap()
+            .with_texcoords(vec![[Point2D::origin(); 3]])
+            .unwrap()
+        )
+    }
+
+    #[test]
+    fn texcoords_work() {
+        let obj = r#"
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0.0 0.0
+            vt 0.5 0.0
+            vt 0.5 0.5
+            vt 0.0 0.5
+            f 1/1 2/2 3/3 4/4
+            f 1 2 3
+        "#;
+        let mesh = parse_obj(obj.as_bytes()).unwrap();
+        let texcoords = mesh.texcoords().unwrap();
+        assert_eq!(
+            texcoords[1],
+            [
+                Point2D::new(0., 0.),
+                Point2D::new(0.5, 0.5),
+                Point2D::new(0., 0.5)
+            ]
+        );
+        // Faces without texture coordinates keep their barycentric coordinates
+        assert_eq!(
+            texcoords[2],
+            [
+                Point2D::new(0., 0.),
+                Point2D::new(1., 0.),
+                Point2D::new(0., 1.)
+            ]
+        );
+    }
+
+    #[test]
+    fn load_with_materials_works() {
+        let directory = std::env::temp_dir().join(format!("obj-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join("colors.mtl"),
+            "newmtl red\nKd 1 0 0\nnewmtl green\nKd 0 1 0\n",
+        )
+        .unwrap();
+        let obj = r#"
+            mtllib colors.mtl
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            f 1 2 3
+            usemtl green
+            f 1 2 3
+            usemtl missing
+            f 1 2 3
+            usemtl red
+            f 1 2 3
+            usemtl green
+            f 1 2 3
+        "#;
+        let path = directory.join("mesh.obj");
+        std::fs::write(&path, obj).unwrap();
+        let mesh = load_obj(&path).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let base_color = |face| match mesh.face_material(face) {
+            Some(MaterialEnum::PrincipledMaterial(material)) => Some(material.base_color.clone()),
+            _ => None,
+        };
+        let (red, green) = (LinearColor::new(1., 0., 0.), LinearColor::new(0., 1., 0.));
+        assert_eq!(base_color(0), None);
+        assert_eq!(base_color(1), Some(green.clone()));
+        assert_eq!(base_color(2), None);
+        assert_eq!(base_color(3), Some(red));
+        assert_eq!(base_color(4), Some(green));
+    }
+
+    #[test]
+    fn missing_library_fails() {
+        let directory = std::env::temp_dir().join(format!("obj-missing-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("mesh.obj");
+        std::fs::write(&path, "mtllib nowhere.mtl\nv 0 0 0\nusemtl red\n").unwrap();
+        let result = load_obj(&path);
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(result.is_err())
+    }
+
+    #[test]
+    fn negative_indices_work() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3//1 -2//1 -1//1\n";
+        let mesh = parse_obj(obj.as_bytes()).unwrap();
+        assert_eq!(mesh.faces(), &[[0, 1, 2]])
+    }
+
+    #[test]
+    fn polygons_are_triangulated() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf 1 2 3 4 5\n";
+        let mesh = parse_obj(obj.as_bytes()).unwrap();
+        assert_eq!(mesh.faces(), &[[0, 1, 2], [0, 2, 3], [0, 3, 4]])
+    }
+
+    #[test]
+    fn invalid_index_fails() {
+        let obj = "v 0 0 0\nv 1 0 0\nf 1 2 3\n";
+        let err = parse_obj(obj.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Parse { line: Some(3), .. }))
+    }
+
+    #[test]
+    fn invalid_vertex_fails() {
+        let obj = "v 0 zero 0\n";
+        assert!(parse_obj(obj.as_bytes()).is_err())
+    }
+}
@@ -0,0 +1,407 @@
+use super::PointSet;
+use crate::core::{srgb_to_linear, LinearColor};
+use crate::{Error, Float, Result};
+use crate::{Point, Vector};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// How the values of a PLY file are stored after its header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// The type of a property's values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Scalar::Char,
+            "uchar" | "uint8" => Scalar::UChar,
+            "short" | "int16" => Scalar::Short,
+            "ushort" | "uint16" => Scalar::UShort,
+            "int" | "int32" => Scalar::Int,
+            "uint" | "uint32" => Scalar::UInt,
+            "float" | "float32" => Scalar::Float,
+            "double" | "float64" => Scalar::Double,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::Char | Scalar::UChar => 1,
+            Scalar::Short | Scalar::UShort => 2,
+            Scalar::Int | Scalar::UInt | Scalar::Float => 4,
+            Scalar::Double => 8,
+        }
+    }
+
+    /// The value standing for a full intensity, for integer color channels.
+    fn max(self) -> Option<Float> {
+        match self {
+            Scalar::UChar => Some(u8::MAX as Float),
+            Scalar::UShort => Some(u16::MAX as Float),
+            _ => None,
+        }
+    }
+
+    fn decode(self, bytes: &[u8], format: Format) -> f64 {
+        macro_rules! decode {
+            ($type:ty) => {{
+                let mut buffer = [0; std::mem::size_of::<$type>()];
+                buffer.copy_from_slice(bytes);
+                match format {
+                    Format::BinaryBigEndian => <$type>::from_be_bytes(buffer),
+                    _ => <$type>::from_le_bytes(buffer),
+                }
+            }};
+        }
+        match self {
+            Scalar::Char => decode!(i8) as f64,
+            Scalar::UChar => decode!(u8) as f64,
+            Scalar::Short => decode!(i16) as f64,
+            Scalar::UShort => decode!(u16) as f64,
+            Scalar::Int => decode!(i32) as f64,
+            Scalar::UInt => decode!(u32) as f64,
+            Scalar::Float => decode!(f32) as f64,
+            Scalar::Double => decode!(f64),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: Scalar,
+    /// The type of the number of values, for list properties.
+    count: Option<Scalar>,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Parse the header, returning the format and elements it describes along with the size of the
+/// header in bytes.
+fn parse_header(content: &[u8]) -> Result<(Format, Vec<Element>, usize)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut offset = 0;
+    for line_number in 1.. {
+        let end = (content[offset..].iter().position(|&b| b == b'\n'))
+            .ok_or_else(|| Error::invalid_data("the header does not end"))?;
+        let line = String::from_utf8_lossy(&content[offset..offset + end]);
+        offset += end + 1;
+        let error = |msg: &str| Error::parse(line_number, msg);
+        let tokens: Vec<_> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["ply"] if line_number == 1 => {}
+            _ if line_number == 1 => return Err(error("not a PLY file")),
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(error("unknown format")),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| error("invalid element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("property outside of an element"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: Scalar::parse(kind).ok_or_else(|| error("unknown property type"))?,
+                    count: Some(Scalar::parse(count).ok_or_else(|| error("unknown count type"))?),
+                })
+            }
+            ["property", kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("property outside of an element"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: Scalar::parse(kind).ok_or_else(|| error("unknown property type"))?,
+                    count: None,
+                })
+            }
+            ["end_header"] => break,
+            // Ignore comments and other informations
+            _ => {}
+        }
+    }
+    let format = format.ok_or_else(|| Error::invalid_data("the header has no format"))?;
+    Ok((format, elements, offset))
+}
+
+/// The values stored after the header, read in order.
+enum Values<'a> {
+    Ascii(std::str::SplitWhitespace<'a>),
+    Binary(&'a [u8], Format),
+}
+
+impl Values<'_> {
+    fn next(&mut self, kind: Scalar) -> Result<f64> {
+        let truncated = || Error::invalid_data("the file ends before all of its values");
+        match self {
+            Values::Ascii(tokens) => (tokens.next().ok_or_else(truncated)?)
+                .parse()
+                .map_err(|_| Error::invalid_data("invalid value")),
+            Values::Binary(bytes, format) => {
+                if bytes.len() < kind.size() {
+                    return Err(truncated());
+                }
+                let (value, rest) = bytes.split_at(kind.size());
+                *bytes = rest;
+                Ok(kind.decode(value, *format))
+            }
+        }
+    }
+}
+
+/// Parse a [`PointSet`] from the vertices of a PLY file, in either its ASCII or binary formats.
+///
+/// The vertices' `x`, `y`, `z` properties give their position. Their normal, radius, and color
+/// are read from the `nx`, `ny`, `nz`, the `radius`, and the `red`, `green`, `blue` properties
+/// when present. Integer colors are taken to be sRGB encoded, floating point ones linear. The
+/// other elements, e.g: faces, are ignored.
+///
+/// [`PointSet`]: struct.PointSet.html
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::mesh::parse_ply;
+/// #
+/// let ply = "\
+/// ply
+/// format ascii 1.0
+/// element vertex 2
+/// property float x
+/// property float y
+/// property float z
+/// property uchar red
+/// property uchar green
+/// property uchar blue
+/// end_header
+/// 0 0 0 255 0 0
+/// 1 0 0 0 0 255
+/// ";
+/// let points = parse_ply(ply.as_bytes()).unwrap();
+/// assert_eq!(points.positions().len(), 2);
+/// assert_eq!(points.colors().unwrap()[1].b, 1.0);
+/// ```
+pub fn parse_ply<R: Read>(mut reader: R) -> Result<PointSet> {
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+    let (format, elements, offset) = parse_header(&content)?;
+    let mut values = match format {
+        Format::Ascii => Values::Ascii(
+            std::str::from_utf8(&content[offset..])
+                .map_err(|_| Error::invalid_data("invalid ASCII values"))?
+                .split_whitespace(),
+        ),
+        _ => Values::Binary(&content[offset..], format),
+    };
+
+    for element in &elements {
+        if element.name != "vertex" {
+            // Skip the element's values to get to the vertices
+            for _ in 0..element.count {
+                for property in &element.properties {
+                    let count = match property.count {
+                        Some(kind) => values.next(kind)? as usize,
+                        None => 1,
+                    };
+                    for _ in 0..count {
+                        values.next(property.kind)?;
+                    }
+                }
+            }
+            continue;
+        }
+
+        let index = |name: &str| (element.properties.iter()).position(|p| p.name == name);
+        let indices = |names: [&str; 3]| {
+            let [x, y, z] = names.map(index);
+            Some([x?, y?, z?])
+        };
+        let position = indices(["x", "y", "z"])
+            .ok_or_else(|| Error::invalid_data("the vertices have no position"))?;
+        let normal = indices(["nx", "ny", "nz"]);
+        let color = indices(["red", "green", "blue"]);
+        let radius = index("radius");
+
+        // Do not trust the count to allocate the points, in case the file is truncated
+        let mut positions = Vec::new();
+        let (mut normals, mut radii, mut colors) = (Vec::new(), Vec::new(), Vec::new());
+        let mut row = vec![0.; element.properties.len()];
+        for _ in 0..element.count {
+            for (value, property) in row.iter_mut().zip(&element.properties) {
+                if let Some(kind) = property.count {
+                    return Err(Error::invalid_data(format!(
+                        "unexpected list property '{}' for vertices of type {:?}",
+                        property.name, kind
+                    )));
+                }
+                *value = values.next(property.kind)? as Float;
+            }
+            let [x, y, z] = position.map(|i| row[i]);
+            positions.push(Point::new(x, y, z));
+            if let Some([x, y, z]) = normal.map(|indices| indices.map(|i| row[i])) {
+                normals.push(Vector::new(x, y, z));
+            }
+            if let Some(i) = radius {
+                radii.push(row[i]);
+            }
+            if let Some(indices) = color {
+                let [r, g, b] = indices.map(|i| match element.properties[i].kind.max() {
+                    Some(max) => srgb_to_linear(row[i] / max),
+                    None => row[i],
+                });
+                colors.push(LinearColor::new(r, g, b));
+            }
+        }
+
+        // All the attributes have a value for each point by construction
+        let mut points = PointSet::new(positions);
+        if normal.is_some() {
+            points = points.with_normals(normals).unwrap();
+        }
+        if radius.is_some() {
+            points = points.with_radii(radii).unwrap();
+        }
+        if color.is_some() {
+            points = points.with_colors(colors).unwrap();
+        }
+        return Ok(points);
+    }
+    Err(Error::invalid_data("the file has no vertices"))
+}
+
+/// Load a [`PointSet`] from a PLY file, as described in [`parse_ply`].
+///
+/// [`PointSet`]: struct.PointSet.html
+/// [`parse_ply`]: fn.parse_ply.html
+pub fn load_ply<P: AsRef<Path>>(path: P) -> Result<PointSet> {
+    let path = path.as_ref();
+    (File::open(path).map_err(Error::from))
+        .and_then(|file| parse_ply(BufReader::new(file)))
+        .map_err(|err| err.with_path(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn binary_ply(format: &str, big_endian: bool) -> Vec<u8> {
+        let header = format!(
+            "ply\nformat {} 1.0\ncomment a list comes first\nelement face 1\n\
+             property list uchar int vertex_indices\nelement vertex 2\nproperty double x\n\
+             property double y\nproperty double z\nproperty float nx\nproperty float ny\n\
+             property float nz\nproperty float radius\nend_header\n",
+            format
+        );
+        let mut content = header.into_bytes();
+        content.push(3);
+        for index in &[0_i32, 1, 0] {
+            let bytes = if big_endian {
+                index.to_be_bytes()
+            } else {
+                index.to_le_bytes()
+            };
+            content.extend_from_slice(&bytes);
+        }
+        for vertex in &[[0., 1., 2.], [3., 4., 5.]] {
+            for coord in vertex {
+                let coord: f64 = *coord;
+                let bytes = if big_endian {
+                    coord.to_be_bytes()
+                } else {
+                    coord.to_le_bytes()
+                };
+                content.extend_from_slice(&bytes);
+            }
+            for value in &[0_f32, 0., 2., 0.25] {
+                let bytes = if big_endian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                };
+                content.extend_from_slice(&bytes);
+            }
+        }
+        content
+    }
+
+    #[test]
+    fn binary_works() {
+        for (format, big_endian) in &[("binary_little_endian", false), ("binary_big_endian", true)]
+        {
+            let points = parse_ply(binary_ply(format, *big_endian).as_slice()).unwrap();
+            assert_eq!(
+                points.positions(),
+                &[Point::new(0., 1., 2.), Point::new(3., 4., 5.)]
+            );
+            assert_eq!(points.normals().unwrap(), &[Vector::z_axis(); 2]);
+            assert_eq!(points.radii().unwrap(), &[0.25; 2]);
+            assert!(points.colors().is_none());
+        }
+    }
+
+    #[test]
+    fn ascii_colors_work() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\n\
+                   property float z\nproperty float red\nproperty float green\n\
+                   property float blue\nend_header\n0 0 0 0.5 0.25 1\n";
+        let points = parse_ply(ply.as_bytes()).unwrap();
+        // Floating point colors are already linear
+        assert_eq!(points.colors().unwrap(), &[LinearColor::new(0.5, 0.25, 1.)]);
+    }
+
+    #[test]
+    fn truncated_binary_fails() {
+        let mut content = binary_ply("binary_little_endian", false);
+        content.truncate(content.len() - 1);
+        let err = parse_ply(content.as_slice()).unwrap_err();
+        assert!(matches!(err, Error::Parse { line: None, .. }))
+    }
+
+    #[test]
+    fn invalid_header_fails() {
+        let err = parse_ply("ply\nformat ascii 1.0\nproperty float x\nend_header\n".as_bytes())
+            .unwrap_err();
+        assert!(matches!(err, Error::Parse { line: Some(3), .. }));
+        let err = parse_ply("solid\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Parse { line: Some(1), .. }));
+    }
+
+    #[test]
+    fn missing_positions_fail() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nend_header\n0\n";
+        assert!(parse_ply(ply.as_bytes()).is_err());
+    }
+}
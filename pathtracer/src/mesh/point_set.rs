@@ -0,0 +1,271 @@
+use super::load_ply;
+use crate::core::LinearColor;
+use crate::{Float, Point, Vector};
+use nalgebra::{Affine3, Matrix3, Unit, U3};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::path::PathBuf;
+
+/// A set of points sampled on a surface, e.g: from a 3D scan.
+///
+/// Each point can also have its own normal, radius, and color.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PointSet {
+    positions: Vec<Point>,
+    normals: Option<Vec<Unit<Vector>>>,
+    radii: Option<Vec<Float>>,
+    colors: Option<Vec<LinearColor>>,
+}
+
+impl PointSet {
+    /// Creates a new `PointSet` from the points' positions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::mesh::PointSet;
+    /// # use pathtracer::Point;
+    /// #
+    /// let points = PointSet::new(vec![Point::origin(), Point::new(1.0, 0.0, 0.0)]);
+    /// assert_eq!(points.positions().len(), 2);
+    /// ```
+    pub fn new(positions: Vec<Point>) -> Self {
+        PointSet {
+            positions,
+            normals: None,
+            radii: None,
+            colors: None,
+        }
+    }
+
+    /// Set the normal of each point.
+    ///
+    /// Returns `None` if there is not a normal for each point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::mesh::PointSet;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let points = PointSet::new(vec![Point::origin()])
+    ///     .with_normals(vec![Vector::new(0.0, 2.0, 0.0)])
+    ///     .unwrap();
+    /// assert_eq!(points.normals().unwrap(), &[Vector::y_axis()]);
+    /// ```
+    pub fn with_normals(mut self, normals: Vec<Vector>) -> Option<Self> {
+        if normals.len() != self.positions.len() {
+            return None;
+        }
+        self.normals = Some(normals.into_iter().map(Unit::new_normalize).collect());
+        Some(self)
+    }
+
+    /// Set the radius of each point.
+    ///
+    /// Returns `None` if there is not a radius for each point.
+    pub fn with_radii(mut self, radii: Vec<Float>) -> Option<Self> {
+        if radii.len() != self.positions.len() {
+            return None;
+        }
+        self.radii = Some(radii);
+        Some(self)
+    }
+
+    /// Set the color of each point.
+    ///
+    /// Returns `None` if there is not a color for each point.
+    pub fn with_colors(mut self, colors: Vec<LinearColor>) -> Option<Self> {
+        if colors.len() != self.positions.len() {
+            return None;
+        }
+        self.colors = Some(colors);
+        Some(self)
+    }
+
+    /// Get the position of each point.
+    pub fn positions(&self) -> &[Point] {
+        &self.positions
+    }
+
+    /// Get the normal of each point, if known.
+    pub fn normals(&self) -> Option<&[Unit<Vector>]> {
+        self.normals.as_deref()
+    }
+
+    /// Get the radius of each point, if known.
+    pub fn radii(&self) -> Option<&[Float]> {
+        self.radii.as_deref()
+    }
+
+    /// Get the color of each point, if known.
+    pub fn colors(&self) -> Option<&[LinearColor]> {
+        self.colors.as_deref()
+    }
+
+    /// Apply a linear transformation to each of the points.
+    ///
+    /// The normals are kept orthogonal to the surface, and the radii are scaled by the average
+    /// scaling of the transformation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::mesh::PointSet;
+    /// # use pathtracer::Point;
+    /// use nalgebra::Matrix3;
+    ///
+    /// let mut points = PointSet::new(vec![Point::new(1.0, 0.0, 0.0)])
+    ///     .with_radii(vec![0.5])
+    ///     .unwrap();
+    /// points.transform(&(Matrix3::identity() * 2.));
+    /// assert_eq!(points.positions(), &[Point::new(2.0, 0.0, 0.0)]);
+    /// assert_eq!(points.radii().unwrap(), &[1.0]);
+    /// ```
+    pub fn transform(&mut self, matrix: &Matrix3<Float>) {
+        for position in self.positions.iter_mut() {
+            *position = Point::from(matrix * position.coords);
+        }
+        self.transform_attributes(matrix);
+    }
+
+    /// Apply an affine transformation to each of the points, as described in [`transform`].
+    ///
+    /// [`transform`]: #method.transform
+    pub fn transform_affine(&mut self, affine: &Affine3<Float>) {
+        for position in self.positions.iter_mut() {
+            *position = affine * *position;
+        }
+        let linear = affine.matrix().fixed_slice::<U3, U3>(0, 0).into_owned();
+        self.transform_attributes(&linear);
+    }
+
+    fn transform_attributes(&mut self, linear: &Matrix3<Float>) {
+        if let Some(normals) = self.normals.as_mut() {
+            // The inverse transpose keeps the normals orthogonal to the transformed surface
+            let matrix = linear
+                .try_inverse()
+                .map_or(*linear, |inverse| inverse.transpose());
+            for normal in normals.iter_mut() {
+                *normal = Unit::try_new(matrix * normal.as_ref(), 0.).unwrap_or(*normal);
+            }
+        }
+        if let Some(radii) = self.radii.as_mut() {
+            let scale = linear.determinant().abs().cbrt();
+            for radius in radii.iter_mut() {
+                *radius *= scale;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SerializedPointSet {
+    File {
+        file: PathBuf,
+    },
+    Inline {
+        positions: Vec<Point>,
+        #[serde(default)]
+        normals: Option<Vec<Vector>>,
+        #[serde(default)]
+        radii: Option<Vec<Float>>,
+        #[serde(default)]
+        colors: Option<Vec<LinearColor>>,
+    },
+}
+
+impl<'de> Deserialize<'de> for PointSet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Deserialize::deserialize(deserializer)? {
+            SerializedPointSet::File { file } => load_ply(&file).map_err(D::Error::custom),
+            SerializedPointSet::Inline {
+                positions,
+                normals,
+                radii,
+                colors,
+            } => {
+                let mut points = Some(PointSet::new(positions));
+                if let Some(normals) = normals {
+                    points = points.and_then(|points| points.with_normals(normals));
+                }
+                if let Some(radii) = radii {
+                    points = points.and_then(|points| points.with_radii(radii));
+                }
+                if let Some(colors) = colors {
+                    points = points.and_then(|points| points.with_colors(colors));
+                }
+                points.ok_or_else(|| D::Error::custom("expected an attribute for each point"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn attributes_need_one_value_per_point() {
+        let points = || PointSet::new(vec![Point::origin(), Point::new(1., 0., 0.)]);
+        assert!(points().with_normals(vec![Vector::y()]).is_none());
+        assert!(points().with_radii(vec![1., 2., 3.]).is_none());
+        assert!(points().with_colors(vec![]).is_none());
+        assert!(points().with_radii(vec![1., 2.]).is_some());
+    }
+
+    #[test]
+    fn transform_affine_works() {
+        let mut points = PointSet::new(vec![Point::new(1., 0., 0.)])
+            .with_normals(vec![Vector::new(1., 1., 0.)])
+            .unwrap()
+            .with_radii(vec![1.])
+            .unwrap();
+        // Stretch along X, then move up
+        let mut matrix = nalgebra::Matrix4::new_nonuniform_scaling(&Vector::new(8., 1., 1.));
+        matrix[(1, 3)] = 1.;
+        points.transform_affine(&Affine3::from_matrix_unchecked(matrix));
+        assert_eq!(points.positions(), &[Point::new(8., 1., 0.)]);
+        assert!((points.radii().unwrap()[0] - 2.).abs() < 1e-5);
+        // The surface gets closer to facing up
+        let expected = Vector::new(1., 8., 0.).normalize();
+        assert!((points.normals().unwrap()[0].as_ref() - expected).norm() < 1e-5);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            positions:
+              - [0.0, 0.0, 0.0]
+              - [1.0, 0.0, 0.0]
+            radii: [0.5, 0.25]
+            colors:
+              - {r: 1.0, g: 0.0, b: 0.0}
+              - {r: 0.0, g: 1.0, b: 0.0}
+        "#;
+        let points: PointSet = serde_yaml::from_str(yaml).unwrap();
+        let expected = PointSet::new(vec![Point::origin(), Point::new(1., 0., 0.)])
+            .with_radii(vec![0.5, 0.25])
+            .unwrap()
+            .with_colors(vec![
+                LinearColor::new(1., 0., 0.),
+                LinearColor::new(0., 1., 0.),
+            ])
+            .unwrap();
+        assert_eq!(points, expected)
+    }
+
+    #[test]
+    fn deserialization_checks_attributes() {
+        let yaml = r#"
+            positions:
+              - [0.0, 0.0, 0.0]
+            radii: [0.5, 0.25]
+        "#;
+        assert!(serde_yaml::from_str::<PointSet>(yaml).is_err())
+    }
+}
@@ -0,0 +1,214 @@
+use super::Mesh;
+use crate::{Error, Result};
+use crate::{Float, Point};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Build a [`Mesh`] from each facet's corners, merging the corners at the same position into a
+/// single vertex, as STL files do not share vertices between facets.
+fn weld(facets: Vec<[Point; 3]>) -> Result<Mesh> {
+    let mut vertices = Vec::new();
+    let mut indices = HashMap::new();
+    let faces = (facets.into_iter())
+        .map(|corners| {
+            corners.map(|corner| {
+                // Adding zero turns negative zeroes into positive ones
+                let key = [corner.x, corner.y, corner.z].map(|c| (c + 0.).to_bits());
+                *indices.entry(key).or_insert_with(|| {
+                    vertices.push(corner);
+                    vertices.len() - 1
+                })
+            })
+        })
+        .collect();
+    Mesh::new(vertices, faces).ok_or_else(|| Error::invalid_data("faces refer to missing vertices"))
+}
+
+fn parse_binary(content: &[u8]) -> Vec<[Point; 3]> {
+    // An 80 bytes header and the number of facets come first, then 50 bytes per facet: its
+    // normal, its corners, and an unused attribute
+    let float =
+        |bytes: &[u8]| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as Float;
+    let point = |bytes: &[u8]| Point::new(float(bytes), float(&bytes[4..]), float(&bytes[8..]));
+    (content[84..].chunks_exact(50))
+        .map(|facet| {
+            [
+                point(&facet[12..]),
+                point(&facet[24..]),
+                point(&facet[36..]),
+            ]
+        })
+        .collect()
+}
+
+fn parse_ascii(content: &str) -> Result<Vec<[Point; 3]>> {
+    let mut facets = Vec::new();
+    let mut corners = Vec::new();
+    for (line_number, line) in content.lines().enumerate() {
+        let line_number = line_number + 1;
+        let error = |msg: &str| Error::parse(line_number, msg);
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("facet") => corners.clear(),
+            Some("vertex") => {
+                let coords = tokens
+                    .map(str::parse)
+                    .collect::<Result<Vec<Float>, _>>()
+                    .map_err(|_| error("invalid vertex coordinate"))?;
+                match coords.as_slice() {
+                    [x, y, z] => corners.push(Point::new(*x, *y, *z)),
+                    _ => return Err(error("expected 3 vertex coordinates")),
+                }
+            }
+            Some("endfacet") => match corners.as_slice() {
+                [a, b, c] => facets.push([*a, *b, *c]),
+                _ => return Err(error("a facet should have 3 vertices")),
+            },
+            // Ignore the normals, which are computed from the winding order
+            _ => {}
+        }
+    }
+    Ok(facets)
+}
+
+/// Parse a flat-shaded [`Mesh`] from an STL file, in either its binary or ASCII format.
+///
+/// The normals given in the file are ignored, each facet's orientation is given by its winding
+/// order. Corners at the same position are merged into a single vertex, which allows shading the
+/// mesh smoothly with [`Mesh::with_smoothing`].
+///
+/// [`Mesh`]: struct.Mesh.html
+/// [`Mesh::with_smoothing`]: struct.Mesh.html#method.with_smoothing
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::mesh::parse_stl;
+/// #
+/// let stl = "
+/// solid quad
+///   facet normal 0 0 1
+///     outer loop
+///       vertex 0 0 0
+///       vertex 1 0 0
+///       vertex 1 1 0
+///     endloop
+///   endfacet
+///   facet normal 0 0 1
+///     outer loop
+///       vertex 0 0 0
+///       vertex 1 1 0
+///       vertex 0 1 0
+///     endloop
+///   endfacet
+/// endsolid quad
+/// ";
+/// let mesh = parse_stl(stl.as_bytes()).unwrap();
+/// assert_eq!(mesh.vertices().len(), 4);
+/// assert_eq!(mesh.faces(), &[[0, 1, 2], [0, 2, 3]]);
+/// ```
+pub fn parse_stl<R: Read>(mut reader: R) -> Result<Mesh> {
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+    // ASCII files start with "solid", but so do some binary ones: rely on the binary size first
+    if content.len() >= 84 {
+        let count = u32::from_le_bytes([content[80], content[81], content[82], content[83]]);
+        if content.len() as u64 == 84 + 50 * count as u64 {
+            return weld(parse_binary(&content));
+        }
+    }
+    match std::str::from_utf8(&content) {
+        Ok(text) if text.trim_start().starts_with("solid") => weld(parse_ascii(text)?),
+        _ => Err(Error::invalid_data(
+            "neither an ASCII STL file, nor a binary one of the expected size",
+        )),
+    }
+}
+
+/// Load a [`Mesh`] from an STL file, as described in [`parse_stl`].
+///
+/// [`Mesh`]: struct.Mesh.html
+/// [`parse_stl`]: fn.parse_stl.html
+pub fn load_stl<P: AsRef<Path>>(path: P) -> Result<Mesh> {
+    let path = path.as_ref();
+    (File::open(path).map_err(Error::from))
+        .and_then(|file| parse_stl(BufReader::new(file)))
+        .map_err(|err| err.with_path(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn binary_stl(facets: &[[[f32; 3]; 3]]) -> Vec<u8> {
+        let mut content = b"solid but actually binary".to_vec();
+        content.resize(80, 0);
+        content.extend_from_slice(&(facets.len() as u32).to_le_bytes());
+        for facet in facets {
+            // The normal is ignored
+            content.extend_from_slice(&[0; 12]);
+            for coord in facet.iter().flatten() {
+                content.extend_from_slice(&coord.to_le_bytes());
+            }
+            content.extend_from_slice(&[0; 2]);
+        }
+        content
+    }
+
+    #[test]
+    fn binary_works() {
+        let content = binary_stl(&[
+            [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.]],
+            [[0., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+        ]);
+        let mesh = parse_stl(content.as_slice()).unwrap();
+        assert_eq!(
+            mesh.vertices(),
+            &[
+                Point::new(0., 0., 0.),
+                Point::new(1., 0., 0.),
+                Point::new(1., 1., 0.),
+                Point::new(0., 1., 0.),
+            ]
+        );
+        assert_eq!(mesh.faces(), &[[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn negative_zeroes_are_welded() {
+        let content = binary_stl(&[
+            [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]],
+            [[-0., 0., -0.], [0., 1., 0.], [0., 0., 1.]],
+        ]);
+        let mesh = parse_stl(content.as_slice()).unwrap();
+        assert_eq!(mesh.vertices().len(), 4);
+        assert_eq!(mesh.faces()[1][0], 0);
+    }
+
+    #[test]
+    fn truncated_binary_fails() {
+        let mut content = binary_stl(&[[[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]]]);
+        content.truncate(content.len() - 1);
+        let err = parse_stl(content.as_slice()).unwrap_err();
+        assert!(matches!(err, Error::Parse { line: None, .. }))
+    }
+
+    #[test]
+    fn invalid_ascii_fails() {
+        let stl = "solid broken\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\nendfacet\nendsolid broken\n";
+        let err = parse_stl(stl.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Parse { line: Some(7), .. }));
+    }
+
+    #[test]
+    fn load_works() {
+        let path = std::env::temp_dir().join(format!("stl-{}.STL", std::process::id()));
+        let content = binary_stl(&[[[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]]]);
+        std::fs::write(&path, content).unwrap();
+        let mesh = crate::mesh::load_mesh(&path).unwrap();
+        assert_eq!(mesh.faces(), &[[0, 1, 2]]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,568 @@
+use super::Mesh;
+use crate::{Float, Point, Point2D, Vector};
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How the faces of a [`Mesh`] are split when subdividing it, each level bringing it closer to a
+/// smooth surface.
+///
+/// [`Mesh`]: struct.Mesh.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubdivisionScheme {
+    /// Split each polygon into a quad per corner, e.g: for cages modelled with quads.
+    ///
+    /// The pairs of consecutive triangles `[a, b, c]` and `[a, c, d]`, which is how quads are
+    /// split when loading them, are subdivided as the quad they came from.
+    #[default]
+    CatmullClark,
+    /// Split each triangle into four, e.g: for cages modelled with triangles.
+    Loop,
+    /// Split each triangle into four at the middle of its edges without moving any vertex, e.g:
+    /// to add vertices to a displaced mesh without changing its shape.
+    Midpoint,
+}
+
+/// A face being subdivided, along with what its children inherit from it.
+struct Polygon {
+    corners: Vec<usize>,
+    texcoords: Option<Vec<Point2D>>,
+    material: Option<usize>,
+}
+
+/// The edges of a mesh, numbered in the order they are first found.
+struct Edges {
+    indices: HashMap<(usize, usize), usize>,
+    /// The vertices at both ends of each edge.
+    ends: Vec<(usize, usize)>,
+    /// The faces around each edge.
+    faces: Vec<Vec<usize>>,
+}
+
+impl Edges {
+    /// Collect the edges of the given polygons.
+    fn new<'a>(polygons: impl Iterator<Item = &'a [usize]>) -> Self {
+        let mut indices = HashMap::new();
+        let (mut ends, mut faces) = (Vec::new(), Vec::<Vec<usize>>::new());
+        for (face, corners) in polygons.enumerate() {
+            for (i, &a) in corners.iter().enumerate() {
+                let b = corners[(i + 1) % corners.len()];
+                let key = (a.min(b), a.max(b));
+                let index = *indices.entry(key).or_insert_with(|| {
+                    ends.push(key);
+                    faces.push(Vec::new());
+                    ends.len() - 1
+                });
+                faces[index].push(face);
+            }
+        }
+        Edges {
+            indices,
+            ends,
+            faces,
+        }
+    }
+
+    /// Get the index of the edge between two vertices.
+    fn index(&self, a: usize, b: usize) -> usize {
+        self.indices[&(a.min(b), a.max(b))]
+    }
+
+    /// Whether an edge is not shared by exactly two faces, and is kept sharp.
+    fn is_boundary(&self, edge: usize) -> bool {
+        self.faces[edge].len() != 2
+    }
+
+    /// Get the edges around each of the `count` vertices.
+    fn around_vertices(&self, count: usize) -> Vec<Vec<usize>> {
+        let mut around = vec![Vec::new(); count];
+        for (edge, &(a, b)) in self.ends.iter().enumerate() {
+            around[a].push(edge);
+            around[b].push(edge);
+        }
+        around
+    }
+
+    /// Get the position of a vertex along a boundary, where only its neighbours on the boundary
+    /// are taken into account, or `None` if it is not on a boundary. Corners, whose only edges
+    /// are on the boundary, and vertices where boundaries meet, stay in place.
+    fn boundary_vertex(
+        &self,
+        vertices: &[Point],
+        vertex: usize,
+        around: &[usize],
+    ) -> Option<Point> {
+        let boundary: Vec<_> = (around.iter().copied())
+            .filter(|&edge| self.is_boundary(edge))
+            .map(|edge| match self.ends[edge] {
+                (a, b) if a == vertex => vertices[b],
+                (a, _) => vertices[a],
+            })
+            .collect();
+        match boundary.len() {
+            0 => None,
+            2 if around.len() > 2 => Some(Point::from(
+                (vertices[vertex].coords * 6. + boundary[0].coords + boundary[1].coords) / 8.,
+            )),
+            _ => Some(vertices[vertex]),
+        }
+    }
+}
+
+impl Mesh {
+    /// Subdivide the `Mesh`'s faces `levels` times with the given scheme, e.g: to render a
+    /// low-polygon cage as a smooth surface.
+    ///
+    /// Edges which are not shared by exactly two faces are kept sharp, their vertices only
+    /// moving along them, and the corners between two of them stay in place. Texture coordinates
+    /// are interpolated linearly, and the faces keep the material of the face they were split
+    /// from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::mesh::{Mesh, SubdivisionScheme};
+    /// # use pathtracer::Point;
+    /// #
+    /// let quad = Mesh::new(
+    ///     vec![
+    ///         Point::new(0.0, 0.0, 0.0),
+    ///         Point::new(1.0, 0.0, 0.0),
+    ///         Point::new(1.0, 1.0, 0.0),
+    ///         Point::new(0.0, 1.0, 0.0),
+    ///     ],
+    ///     vec![[0, 1, 2], [0, 2, 3]],
+    /// )
+    /// .unwrap();
+    /// // The quad is split into 4 quads, themselves split into 2 triangles
+    /// let subdivided = quad.subdivided(SubdivisionScheme::CatmullClark, 1);
+    /// assert_eq!(subdivided.faces().len(), 8);
+    /// assert_eq!(subdivided.vertices().len(), 9);
+    /// // Each triangle is split into 4 triangles
+    /// let subdivided = quad.subdivided(SubdivisionScheme::Loop, 2);
+    /// assert_eq!(subdivided.faces().len(), 32);
+    /// ```
+    pub fn subdivided(&self, scheme: SubdivisionScheme, levels: u32) -> Mesh {
+        let mut mesh = self.clone();
+        for _ in 0..levels {
+            mesh = match scheme {
+                SubdivisionScheme::CatmullClark => mesh.catmull_clark(),
+                SubdivisionScheme::Loop => mesh.loop_subdivision(true),
+                SubdivisionScheme::Midpoint => mesh.loop_subdivision(false),
+            };
+        }
+        mesh
+    }
+
+    /// Group the faces into polygons, merging back the quads split into two triangles.
+    fn polygons(&self) -> Vec<Polygon> {
+        let texcoords = |face: usize| self.texcoords.as_ref().and_then(|t| t.get(face).copied());
+        let material = |face: usize| self.face_materials.get(face).copied().flatten();
+        let mut polygons = Vec::with_capacity(self.faces.len());
+        let mut face = 0;
+        while face < self.faces.len() {
+            let [a, b, c] = self.faces[face];
+            let (uv, next_uv) = (texcoords(face), texcoords(face + 1));
+            let is_quad = match self.faces.get(face + 1) {
+                Some(&[next_a, next_c, d]) => {
+                    next_a == a
+                        && next_c == c
+                        && d != b
+                        && material(face) == material(face + 1)
+                        && uv
+                            .zip(next_uv)
+                            .is_none_or(|(t, n)| n[0] == t[0] && n[1] == t[2])
+                }
+                None => false,
+            };
+            if is_quad {
+                let d = self.faces[face + 1][2];
+                polygons.push(Polygon {
+                    corners: vec![a, b, c, d],
+                    texcoords: uv.zip(next_uv).map(|(t, n)| vec![t[0], t[1], t[2], n[2]]),
+                    material: material(face),
+                });
+                face += 2;
+            } else {
+                polygons.push(Polygon {
+                    corners: vec![a, b, c],
+                    texcoords: uv.map(|t| t.to_vec()),
+                    material: material(face),
+                });
+                face += 1;
+            }
+        }
+        polygons
+    }
+
+    /// Build a `Mesh` like this one out of new vertices and polygons, splitting the polygons into
+    /// fans of triangles.
+    fn with_polygons(&self, vertices: Vec<Point>, polygons: Vec<Polygon>) -> Mesh {
+        let mut faces = Vec::new();
+        let mut texcoords = Vec::new();
+        let mut face_materials = Vec::new();
+        for polygon in polygons {
+            let corners = &polygon.corners;
+            for i in 1..corners.len() - 1 {
+                faces.push([corners[0], corners[i], corners[i + 1]]);
+                if let Some(uv) = &polygon.texcoords {
+                    texcoords.push([uv[0], uv[i], uv[i + 1]]);
+                }
+                face_materials.push(polygon.material);
+            }
+        }
+        let mut mesh = Mesh::new(vertices, faces).unwrap();
+        if self.texcoords.is_some() {
+            mesh = mesh.with_texcoords(texcoords).unwrap();
+        }
+        if !self.face_materials.is_empty() {
+            mesh = mesh
+                .with_materials(self.materials.clone(), face_materials)
+                .unwrap();
+        }
+        mesh.smoothing_angle = self.smoothing_angle;
+        mesh
+    }
+
+    /// Subdivide the `Mesh` once with the Catmull-Clark scheme.
+    fn catmull_clark(&self) -> Mesh {
+        let polygons = self.polygons();
+        let edges = Edges::new(polygons.iter().map(|p| p.corners.as_slice()));
+        let around = edges.around_vertices(self.vertices.len());
+        let position = |vertex: usize| self.vertices[vertex].coords;
+
+        let face_points: Vec<Vector> = (polygons.iter())
+            .map(|p| {
+                p.corners.iter().map(|&v| position(v)).sum::<Vector>() / p.corners.len() as Float
+            })
+            .collect();
+        let edge_points: Vec<Vector> = (edges.ends.iter().zip(edges.faces.iter()))
+            .map(|(&(a, b), faces)| match faces.as_slice() {
+                [lhs, rhs] => {
+                    (position(a) + position(b) + face_points[*lhs] + face_points[*rhs]) / 4.
+                }
+                _ => (position(a) + position(b)) / 2.,
+            })
+            .collect();
+        let vertex_points = (0..self.vertices.len()).map(|vertex| {
+            let around = &around[vertex];
+            if let Some(point) = edges.boundary_vertex(&self.vertices, vertex, around) {
+                return point;
+            }
+            if around.is_empty() {
+                return self.vertices[vertex];
+            }
+            let mut faces: Vec<_> = (around.iter())
+                .flat_map(|&edge| edges.faces[edge].iter().copied())
+                .collect();
+            faces.sort_unstable();
+            faces.dedup();
+            let valence = around.len() as Float;
+            let faces_average =
+                faces.iter().map(|&f| face_points[f]).sum::<Vector>() / faces.len() as Float;
+            let edges_average = (around.iter())
+                .map(|&edge| (position(edges.ends[edge].0) + position(edges.ends[edge].1)) / 2.)
+                .sum::<Vector>()
+                / valence;
+            Point::from(
+                (faces_average + edges_average * 2. + position(vertex) * (valence - 3.)) / valence,
+            )
+        });
+        let vertex_points: Vec<_> = vertex_points.collect();
+        // The updated vertices come first, then the edges' points, then the faces' points
+        let edge_offset = self.vertices.len();
+        let face_offset = edge_offset + edge_points.len();
+        let vertices = (vertex_points.into_iter())
+            .chain(edge_points.into_iter().map(Point::from))
+            .chain(face_points.into_iter().map(Point::from))
+            .collect();
+        let mut quads = Vec::new();
+        for (face, polygon) in polygons.iter().enumerate() {
+            let corners = &polygon.corners;
+            let count = corners.len();
+            let uv_center = (polygon.texcoords.as_ref()).map(|uv| {
+                Point2D::from(uv.iter().map(|t| t.coords).sum::<Vector2<Float>>() / count as Float)
+            });
+            for i in 0..count {
+                let (prev, next) = ((i + count - 1) % count, (i + 1) % count);
+                let edge_point =
+                    |other: usize| edge_offset + edges.index(corners[i], corners[other]);
+                quads.push(Polygon {
+                    corners: vec![
+                        corners[i],
+                        edge_point(next),
+                        face_offset + face,
+                        edge_point(prev),
+                    ],
+                    texcoords: (polygon.texcoords.as_ref())
+                        .zip(uv_center)
+                        .map(|(uv, center)| {
+                            vec![
+                                uv[i],
+                                Point2D::from((uv[i].coords + uv[next].coords) / 2.),
+                                center,
+                                Point2D::from((uv[i].coords + uv[prev].coords) / 2.),
+                            ]
+                        }),
+                    material: polygon.material,
+                });
+            }
+        }
+        self.with_polygons(vertices, quads)
+    }
+
+    /// Subdivide the `Mesh` once with the Loop scheme, or only split its faces when not `smooth`.
+    fn loop_subdivision(&self, smooth: bool) -> Mesh {
+        let edges = Edges::new(self.faces.iter().map(|face| &face[..]));
+        let around = edges.around_vertices(self.vertices.len());
+        let position = |vertex: usize| self.vertices[vertex].coords;
+        let opposite = |face: usize, (a, b): (usize, usize)| {
+            let corners = self.faces[face];
+            corners
+                .iter()
+                .copied()
+                .find(|&v| v != a && v != b)
+                .unwrap_or(a)
+        };
+
+        let edge_points = (edges.ends.iter().zip(edges.faces.iter())).map(|(&(a, b), faces)| {
+            Point::from(match faces.as_slice() {
+                [lhs, rhs] if smooth => {
+                    (position(a) + position(b)) * 3. / 8.
+                        + (position(opposite(*lhs, (a, b))) + position(opposite(*rhs, (a, b)))) / 8.
+                }
+                _ => (position(a) + position(b)) / 2.,
+            })
+        });
+        let vertex_points = (0..self.vertices.len()).map(|vertex| {
+            let around = &around[vertex];
+            if !smooth {
+                return self.vertices[vertex];
+            }
+            if let Some(point) = edges.boundary_vertex(&self.vertices, vertex, around) {
+                return point;
+            }
+            if around.is_empty() {
+                return self.vertices[vertex];
+            }
+            let valence = around.len() as Float;
+            let beta = if around.len() == 3 {
+                3. / 16.
+            } else {
+                3. / (8. * valence)
+            };
+            let neighbours = (around.iter())
+                .map(|&edge| match edges.ends[edge] {
+                    (a, b) if a == vertex => position(b),
+                    (a, _) => position(a),
+                })
+                .sum::<Vector>();
+            Point::from(position(vertex) * (1. - valence * beta) + neighbours * beta)
+        });
+
+        let edge_offset = self.vertices.len();
+        let vertices = vertex_points.chain(edge_points).collect();
+        let material = |face: usize| self.face_materials.get(face).copied().flatten();
+        let mut triangles = Vec::new();
+        for (face, &[a, b, c]) in self.faces.iter().enumerate() {
+            let (ab, bc, ca) = (
+                edge_offset + edges.index(a, b),
+                edge_offset + edges.index(b, c),
+                edge_offset + edges.index(c, a),
+            );
+            let uv = (self.texcoords.as_ref()).map(|t| {
+                let middle = |i: usize, j: usize| {
+                    Point2D::from((t[face][i].coords + t[face][j].coords) / 2.)
+                };
+                (t[face], [middle(0, 1), middle(1, 2), middle(2, 0)])
+            });
+            let children = [
+                ([a, ab, ca], [0, 3, 5]),
+                ([ab, b, bc], [3, 1, 4]),
+                ([ca, bc, c], [5, 4, 2]),
+                ([ab, bc, ca], [3, 4, 5]),
+            ];
+            for (corners, uv_indices) in children.iter() {
+                triangles.push(Polygon {
+                    corners: corners.to_vec(),
+                    // The face's corners, then the middles of its edges
+                    texcoords: uv.map(|(corners, middles)| {
+                        (uv_indices.iter())
+                            .map(|&i| if i < 3 { corners[i] } else { middles[i - 3] })
+                            .collect()
+                    }),
+                    material: material(face),
+                });
+            }
+        }
+        self.with_polygons(vertices, triangles)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A cube of side 2 centered on the origin, made of quads split into triangles.
+    fn cube() -> Mesh {
+        let vertices = (0..8)
+            .map(|i| {
+                let coordinate = |bit: usize| if i & bit == 0 { -1. } else { 1. };
+                Point::new(coordinate(1), coordinate(2), coordinate(4))
+            })
+            .collect();
+        let quads = [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ];
+        let faces = (quads.iter())
+            .flat_map(|&[a, b, c, d]| vec![[a, b, c], [a, c, d]])
+            .collect();
+        Mesh::new(vertices, faces).unwrap()
+    }
+
+    fn grid() -> Mesh {
+        Mesh::new(
+            vec![
+                Point::new(0., 0., 0.),
+                Point::new(1., 0., 0.),
+                Point::new(1., 0., 1.),
+                Point::new(0., 0., 1.),
+            ],
+            vec![[0, 2, 1], [0, 3, 2]],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn polygons_merge_quads() {
+        let polygons = cube().polygons();
+        assert_eq!(polygons.len(), 6);
+        assert_eq!(polygons[0].corners, vec![0, 2, 3, 1]);
+        // Triangles which do not share their first edge are left alone
+        let mesh = Mesh::new(vec![Point::origin(); 4], vec![[0, 1, 2], [1, 2, 3]]).unwrap();
+        assert_eq!(mesh.polygons().len(), 2);
+    }
+
+    #[test]
+    fn catmull_clark_cube_works() {
+        let subdivided = cube().subdivided(SubdivisionScheme::CatmullClark, 1);
+        // 8 corners, 12 edges and 6 faces, split into 24 quads
+        assert_eq!(subdivided.vertices().len(), 26);
+        assert_eq!(subdivided.faces().len(), 48);
+        // The corners are pulled in towards the center, by the known amount for a cube
+        let corner = subdivided.vertices()[7];
+        assert!((corner - Point::new(5. / 9., 5. / 9., 5. / 9.)).norm() < 1e-5);
+        // The faces' points stay in the middle of the faces
+        assert_eq!(subdivided.vertices()[20..].len(), 6);
+        assert!((subdivided.vertices()[20].coords.norm() - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn catmull_clark_converges_to_a_smooth_surface() {
+        let subdivided = cube().subdivided(SubdivisionScheme::CatmullClark, 3);
+        let distances: Vec<Float> = (subdivided.vertices().iter())
+            .map(|v| v.coords.norm())
+            .collect();
+        let (min, max) = distances
+            .iter()
+            .fold((Float::INFINITY, 0. as Float), |(min, max), &d| {
+                (min.min(d), max.max(d))
+            });
+        // The cube is rounded towards a sphere-like shape
+        assert!(max / min < 1.2, "{} {}", min, max);
+    }
+
+    #[test]
+    fn boundaries_stay_flat() {
+        let subdivided = grid().subdivided(SubdivisionScheme::CatmullClark, 2);
+        assert!(subdivided.vertices().iter().all(|v| v.y == 0.));
+        // The corners of the boundary, between only two edges, stay in place
+        assert_eq!(subdivided.vertices()[1], grid().vertices()[1]);
+        assert_eq!(subdivided.vertices()[3], grid().vertices()[3]);
+        let subdivided = grid().subdivided(SubdivisionScheme::Loop, 2);
+        assert!(subdivided.vertices().iter().all(|v| v.y == 0.));
+    }
+
+    #[test]
+    fn loop_works() {
+        let subdivided = cube().subdivided(SubdivisionScheme::Loop, 1);
+        // 8 vertices and 18 edges, each triangle split into 4
+        assert_eq!(subdivided.vertices().len(), 26);
+        assert_eq!(subdivided.faces().len(), 48);
+        let normals = subdivided.vertex_normals();
+        // The faces keep facing outwards
+        assert!(
+            (subdivided.vertices().iter().zip(normals.iter())).all(|(v, n)| v.coords.dot(n) > 0.)
+        );
+    }
+
+    #[test]
+    fn midpoint_keeps_the_shape() {
+        let mesh = cube();
+        let subdivided = mesh.subdivided(SubdivisionScheme::Midpoint, 2);
+        assert_eq!(subdivided.faces().len(), 12 * 16);
+        assert_eq!(&subdivided.vertices()[..8], mesh.vertices());
+        // All the new vertices stay on the cube's faces
+        assert!(subdivided.vertices().iter().all(|v| v.coords.amax() == 1.));
+    }
+
+    #[test]
+    fn attributes_are_kept() {
+        let material = crate::material::UniformMaterial::new(crate::core::LightProperties::new(
+            crate::core::LinearColor::black(),
+            crate::core::LinearColor::black(),
+            None,
+        ));
+        let mesh = grid()
+            .with_texcoords(vec![
+                [
+                    Point2D::new(0., 0.),
+                    Point2D::new(1., 1.),
+                    Point2D::new(1., 0.),
+                ],
+                [
+                    Point2D::new(0., 0.),
+                    Point2D::new(0., 1.),
+                    Point2D::new(1., 1.),
+                ],
+            ])
+            .unwrap()
+            .with_materials(vec![material.into()], vec![Some(0), Some(0)])
+            .unwrap()
+            .with_smoothing(30.);
+        for scheme in [SubdivisionScheme::CatmullClark, SubdivisionScheme::Loop].iter() {
+            let subdivided = mesh.subdivided(*scheme, 1);
+            assert_eq!(subdivided.smoothing_angle(), Some(30.));
+            assert_eq!(
+                subdivided.texcoords().unwrap().len(),
+                subdivided.faces().len()
+            );
+            assert!((0..subdivided.faces().len()).all(|f| subdivided.face_material(f).is_some()));
+            // The texel coordinates of the original vertices are kept
+            for (face, uv) in subdivided
+                .faces()
+                .iter()
+                .zip(subdivided.texcoords().unwrap())
+            {
+                for (&vertex, texel) in face.iter().zip(uv.iter()).filter(|(&v, _)| v < 4) {
+                    let point = grid().vertices()[vertex];
+                    assert_eq!((point.x, point.z), (texel.x, texel.y));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let scheme: SubdivisionScheme = serde_yaml::from_str("catmull_clark").unwrap();
+        assert_eq!(scheme, SubdivisionScheme::CatmullClark);
+        let scheme: SubdivisionScheme = serde_yaml::from_str("loop").unwrap();
+        assert_eq!(scheme, SubdivisionScheme::Loop);
+    }
+}
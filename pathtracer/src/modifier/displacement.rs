@@ -0,0 +1,156 @@
+use super::Modifier;
+use crate::mesh::{Mesh, SubdivisionScheme};
+use crate::texture::{SurfacePoint, Texture, TextureEnum};
+use crate::{Float, Point2D};
+use serde::Deserialize;
+
+/// Displace each vertex of a mesh along its normal by the height given by a texture, after
+/// splitting its faces to give the displacement enough vertices to show through in silhouettes.
+///
+/// The height at a vertex is the luminance of the texture's color, averaged over the faces around
+/// it when their texel coordinates differ, e.g: along texture seams. Vertices and edges shared by
+/// multiple faces are only displaced once, which keeps the mesh closed.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Displacement {
+    /// The height texture.
+    texture: TextureEnum,
+    /// The factor applied to the height.
+    #[serde(default = "crate::serialize::default_identity")]
+    strength: Float,
+    /// The number of times the faces are split in four before being displaced.
+    #[serde(default)]
+    levels: u32,
+}
+
+impl Displacement {
+    /// Creates a new `Displacement`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::modifier::Displacement;
+    /// # use pathtracer::texture::UniformTexture;
+    /// #
+    /// let displacement = Displacement::new(
+    ///     UniformTexture::new(LinearColor::black()).into(),
+    ///     0.1, // strength
+    ///     3,   // levels
+    /// );
+    /// ```
+    pub fn new(texture: TextureEnum, strength: Float, levels: u32) -> Self {
+        Displacement {
+            texture,
+            strength,
+            levels,
+        }
+    }
+}
+
+impl Modifier for Displacement {
+    fn apply(&self, mesh: &mut Mesh) {
+        *mesh = mesh.subdivided(SubdivisionScheme::Midpoint, self.levels);
+        let normals = mesh.vertex_normals();
+        // Faces without texel coordinates use their barycentric coordinates, as triangles do
+        let barycentric = [
+            Point2D::origin(),
+            Point2D::new(1., 0.),
+            Point2D::new(0., 1.),
+        ];
+        let mut heights = vec![(0., 0); mesh.vertices().len()];
+        for (face, corners) in mesh.faces().iter().enumerate() {
+            let texcoords = mesh.texcoords().map_or(&barycentric, |t| &t[face]);
+            for (&vertex, &texel) in corners.iter().zip(texcoords.iter()) {
+                let point = SurfacePoint::new(mesh.vertices()[vertex], normals[vertex], texel);
+                let (sum, count) = &mut heights[vertex];
+                *sum += self.texture.surface_color(&point).luminance();
+                *count += 1;
+            }
+        }
+        let vertices = mesh.vertices_mut().iter_mut();
+        for ((vertex, normal), (sum, count)) in vertices.zip(normals).zip(heights) {
+            if count > 0 {
+                *vertex += normal.as_ref() * (self.strength * sum / count as Float);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::LinearColor;
+    use crate::texture::{CheckerTexture, UniformTexture};
+    use crate::Point;
+
+    fn grid() -> Mesh {
+        // A 3x3 grid on the XZ-plane, facing up
+        let vertices = (0..9)
+            .map(|i| Point::new((i % 3) as Float, 0., (i / 3) as Float))
+            .collect();
+        let faces = (0..2)
+            .flat_map(|z| (0..2).map(move |x| z * 3 + x))
+            .flat_map(|i| vec![[i, i + 3, i + 1], [i + 1, i + 3, i + 4]])
+            .collect();
+        Mesh::new(vertices, faces).unwrap()
+    }
+
+    fn white() -> TextureEnum {
+        UniformTexture::new(LinearColor::new(1., 1., 1.)).into()
+    }
+
+    #[test]
+    fn new_works() {
+        assert_eq!(
+            Displacement::new(white(), 0.5, 2),
+            Displacement {
+                texture: white(),
+                strength: 0.5,
+                levels: 2,
+            }
+        )
+    }
+
+    #[test]
+    fn apply_works() {
+        let mut mesh = grid();
+        Displacement::new(white(), 0.5, 2).apply(&mut mesh);
+        assert_eq!(mesh.faces().len(), 8 * 16);
+        assert!(mesh.vertices().iter().all(|v| (v.y - 0.5).abs() < 1e-5));
+    }
+
+    #[test]
+    fn apply_averages_heights_across_seams() {
+        // Two faces sharing an edge, with a black and a white square as their texture
+        let mut mesh = Mesh::new(
+            vec![
+                Point::new(0., 0., 0.),
+                Point::new(0., 0., 1.),
+                Point::new(1., 0., 0.),
+                Point::new(1., 0., 1.),
+            ],
+            vec![[0, 1, 2], [2, 1, 3]],
+        )
+        .unwrap()
+        .with_texcoords(vec![
+            [Point2D::new(0.25, 0.25); 3],
+            [Point2D::new(0.75, 0.25); 3],
+        ])
+        .unwrap();
+        let checker = CheckerTexture::new(LinearColor::black(), LinearColor::new(1., 1., 1.), 2.);
+        Displacement::new(checker.into(), 2., 0).apply(&mut mesh);
+        let heights: Vec<_> = mesh.vertices().iter().map(|v| v.y).collect();
+        assert_eq!(heights, vec![0., 1., 1., 2.]);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            texture:
+              type: uniform
+              color: {r: 1.0, g: 1.0, b: 1.0}
+        "#;
+        let displacement: Displacement = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(displacement, Displacement::new(white(), 1., 0))
+    }
+}
@@ -0,0 +1,33 @@
+//! Various mesh modifier implementations, applied when loading the scene
+
+use super::mesh::Mesh;
+use serde::Deserialize;
+
+/// All the existing `Modifier` implementation.
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+#[allow(missing_docs)]
+#[enum_dispatch::enum_dispatch]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum ModifierEnum {
+    Displacement,
+    #[serde(rename = "noise")]
+    NoiseDisplacement,
+    Subdivision,
+}
+
+/// Represent a transformation applied to a mesh before rendering it.
+#[enum_dispatch::enum_dispatch(ModifierEnum)]
+pub trait Modifier: std::fmt::Debug {
+    /// Apply the modification to the mesh.
+    fn apply(&self, mesh: &mut Mesh);
+}
+
+mod displacement;
+pub use displacement::*;
+
+mod noise;
+pub use noise::*;
+
+mod subdivision;
+pub use subdivision::*;
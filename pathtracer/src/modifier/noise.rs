@@ -0,0 +1,130 @@
+use super::Modifier;
+use crate::core::Perlin;
+use crate::mesh::Mesh;
+use crate::Float;
+use serde::Deserialize;
+
+/// Displace each vertex of a mesh along its normal, by an amount given by a procedural noise.
+///
+/// Vertices shared by multiple faces are only displaced once, which keeps the mesh closed.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct NoiseDisplacement {
+    /// The maximum distance by which a vertex is displaced.
+    amplitude: Float,
+    /// The number of noise features per unit length.
+    frequency: Float,
+    /// The seed used to generate the noise.
+    #[serde(default)]
+    seed: u64,
+    /// The number of layers of noise, each one adding finer details.
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+}
+
+fn default_octaves() -> u32 {
+    1
+}
+
+impl NoiseDisplacement {
+    /// Creates a new `NoiseDisplacement`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::modifier::NoiseDisplacement;
+    /// #
+    /// let displacement = NoiseDisplacement::new(
+    ///     0.1, // amplitude
+    ///     2.0, // frequency
+    ///     42,  // seed
+    ///     3,   // octaves
+    /// );
+    /// ```
+    pub fn new(amplitude: Float, frequency: Float, seed: u64, octaves: u32) -> Self {
+        NoiseDisplacement {
+            amplitude,
+            frequency,
+            seed,
+            octaves,
+        }
+    }
+}
+
+impl Modifier for NoiseDisplacement {
+    fn apply(&self, mesh: &mut Mesh) {
+        let noise = Perlin::new(self.seed);
+        let normals = mesh.vertex_normals();
+        for (vertex, normal) in mesh.vertices_mut().iter_mut().zip(normals) {
+            let value = noise.fractal(&(*vertex * self.frequency), self.octaves);
+            *vertex += normal.as_ref() * (value * self.amplitude);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Point, Vector};
+
+    fn grid() -> Mesh {
+        // A 10x10 grid on the XZ-plane, facing up
+        let vertices = (0..100)
+            .map(|i| Point::new((i % 10) as Float * 0.37, 0., (i / 10) as Float * 0.37))
+            .collect();
+        let faces = (0..9)
+            .flat_map(|z| (0..9).map(move |x| z * 10 + x))
+            .flat_map(|i| vec![[i, i + 10, i + 1], [i + 1, i + 10, i + 11]])
+            .collect();
+        Mesh::new(vertices, faces).unwrap()
+    }
+
+    #[test]
+    fn new_works() {
+        assert_eq!(
+            NoiseDisplacement::new(0.1, 2., 42, 3),
+            NoiseDisplacement {
+                amplitude: 0.1,
+                frequency: 2.,
+                seed: 42,
+                octaves: 3,
+            }
+        )
+    }
+
+    #[test]
+    fn apply_displaces_along_normals() {
+        let mut mesh = grid();
+        NoiseDisplacement::new(0.5, 1., 0, 2).apply(&mut mesh);
+        let original = grid();
+        let moved = mesh
+            .vertices()
+            .iter()
+            .zip(original.vertices())
+            .map(|(new, old)| new - old)
+            .collect::<Vec<Vector>>();
+        // Only the height changes, by at most the amplitude
+        assert!(moved.iter().all(|d| d.x == 0. && d.z == 0.));
+        assert!(moved.iter().all(|d| d.y.abs() <= 0.5));
+        assert!(moved.iter().any(|d| d.y != 0.));
+    }
+
+    #[test]
+    fn apply_is_deterministic() {
+        let mut lhs = grid();
+        let mut rhs = grid();
+        NoiseDisplacement::new(0.5, 1., 7, 2).apply(&mut lhs);
+        NoiseDisplacement::new(0.5, 1., 7, 2).apply(&mut rhs);
+        assert_eq!(lhs, rhs);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            amplitude: 0.1
+            frequency: 2.0
+            seed: 42
+        "#;
+        let displacement: NoiseDisplacement = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(displacement, NoiseDisplacement::new(0.1, 2., 42, 1))
+    }
+}
@@ -0,0 +1,83 @@
+use super::Modifier;
+use crate::mesh::{Mesh, SubdivisionScheme};
+use serde::Deserialize;
+
+/// Subdivide a mesh, used as a control cage, into a smooth surface.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Subdivision {
+    /// How the faces are split at each level.
+    #[serde(default)]
+    scheme: SubdivisionScheme,
+    /// The number of times the faces are split.
+    levels: u32,
+}
+
+impl Subdivision {
+    /// Creates a new `Subdivision`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::mesh::SubdivisionScheme;
+    /// # use pathtracer::modifier::Subdivision;
+    /// #
+    /// let subdivision = Subdivision::new(SubdivisionScheme::CatmullClark, 2);
+    /// ```
+    pub fn new(scheme: SubdivisionScheme, levels: u32) -> Self {
+        Subdivision { scheme, levels }
+    }
+}
+
+impl Modifier for Subdivision {
+    fn apply(&self, mesh: &mut Mesh) {
+        *mesh = mesh.subdivided(self.scheme, self.levels);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Point;
+
+    fn tetrahedron() -> Mesh {
+        let vertices = vec![
+            Point::new(1., 1., 1.),
+            Point::new(1., -1., -1.),
+            Point::new(-1., 1., -1.),
+            Point::new(-1., -1., 1.),
+        ];
+        let faces = vec![[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]];
+        Mesh::new(vertices, faces).unwrap()
+    }
+
+    #[test]
+    fn new_works() {
+        assert_eq!(
+            Subdivision::new(SubdivisionScheme::Loop, 3),
+            Subdivision {
+                scheme: SubdivisionScheme::Loop,
+                levels: 3,
+            }
+        )
+    }
+
+    #[test]
+    fn apply_works() {
+        let mut mesh = tetrahedron();
+        Subdivision::new(SubdivisionScheme::Loop, 2).apply(&mut mesh);
+        assert_eq!(mesh, tetrahedron().subdivided(SubdivisionScheme::Loop, 2));
+        assert_eq!(mesh.faces().len(), 4 * 16);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            levels: 2
+        "#;
+        let subdivision: Subdivision = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            subdivision,
+            Subdivision::new(SubdivisionScheme::CatmullClark, 2)
+        )
+    }
+}
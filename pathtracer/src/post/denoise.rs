@@ -0,0 +1,196 @@
+use crate::core::{FrameBuffer, LinearColor};
+use crate::Float;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The standard deviation of the albedo differences across which pixels are still blended.
+const ALBEDO_SIGMA: Float = 0.1;
+/// The standard deviation of the normal differences across which pixels are still blended.
+const NORMAL_SIGMA: Float = 0.25;
+/// The largest spatial standard deviation of the filter, in pixels, at full strength.
+const MAX_SPATIAL_SIGMA: Float = 4.;
+
+/// Remove the noise of a rendered image with a joint bilateral filter, without any external
+/// library.
+///
+/// Each pixel is averaged with its neighbours, weighted by their distance, and by how similar
+/// the albedo and normal of the surfaces they show are. The edges of the objects and of their
+/// textures, which the albedo and normal show without noise, are kept sharp, while the noise of
+/// their lighting is smoothed away.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Denoiser {
+    /// How much the image is smoothed, from 0 for not at all, to 1.
+    strength: Float,
+}
+
+impl Denoiser {
+    /// Creates a new `Denoiser`, smoothing the image more with a higher `strength`, between 0
+    /// and 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::post::Denoiser;
+    /// #
+    /// let denoiser = Denoiser::new(0.5);
+    /// ```
+    pub fn new(strength: Float) -> Self {
+        Denoiser { strength }
+    }
+
+    /// Get the denoised image, using the albedo and normal of the surfaces seen in each pixel to
+    /// preserve their edges. All three buffers must have the same dimensions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{FrameBuffer, LinearColor, Precision};
+    /// # use pathtracer::post::Denoiser;
+    /// #
+    /// let mut image = FrameBuffer::new(8, 8, Precision::Single);
+    /// image.set(4, 4, &LinearColor::new(1.0, 1.0, 1.0));
+    /// let mut albedo = FrameBuffer::new(8, 8, Precision::Single);
+    /// let normal = FrameBuffer::new(8, 8, Precision::Single);
+    /// // The right half of the image shows another surface
+    /// for y in 0..8 {
+    ///     for x in 4..8 {
+    ///         albedo.set(x, y, &LinearColor::new(1.0, 0.0, 0.0));
+    ///     }
+    /// }
+    ///
+    /// let denoised = Denoiser::new(0.5).apply(&image, &albedo, &normal);
+    /// // The noisy pixel is spread over its surface, without bleeding onto the other one
+    /// assert!(denoised.get(4, 4).r < 0.5);
+    /// assert!(denoised.get(5, 4).r > 0.0);
+    /// assert!(denoised.get(3, 4).r < 1e-6);
+    /// ```
+    pub fn apply(
+        &self,
+        image: &FrameBuffer,
+        albedo: &FrameBuffer,
+        normal: &FrameBuffer,
+    ) -> FrameBuffer {
+        let (width, height) = image.dimensions();
+        assert_eq!(albedo.dimensions(), (width, height), "different dimensions");
+        assert_eq!(normal.dimensions(), (width, height), "different dimensions");
+        let strength = self.strength.clamp(0., 1.);
+        // A NaN strength leaves the image as is, as a strength of 0 does
+        let strength = if strength.is_nan() { 0. } else { strength };
+        if strength == 0. {
+            return image.clone();
+        }
+        let spatial_sigma = MAX_SPATIAL_SIGMA * strength;
+        let radius = (2. * spatial_sigma).ceil() as i64;
+        let weight = |sigma: Float, distance2: Float| (-distance2 / (2. * sigma * sigma)).exp();
+        let neighbours = |center: u32, size: u32| {
+            let center = center as i64;
+            (center - radius).max(0) as u32..=(center + radius).min(size as i64 - 1) as u32
+        };
+        let pixel = |x: u32, y: u32| {
+            let (surface, normal_at) = (albedo.get(x, y), normal.get(x, y));
+            let (mut total, mut total_weight) = (LinearColor::black(), 0.);
+            for ny in neighbours(y, height) {
+                for nx in neighbours(x, width) {
+                    let other = image.get(nx, ny);
+                    let (dx, dy) = (nx as Float - x as Float, ny as Float - y as Float);
+                    let w = weight(spatial_sigma, dx * dx + dy * dy)
+                        * weight(ALBEDO_SIGMA, distance2(&albedo.get(nx, ny), &surface))
+                        * weight(NORMAL_SIGMA, distance2(&normal.get(nx, ny), &normal_at));
+                    total += other * w;
+                    total_weight += w;
+                }
+            }
+            // The pixel itself always has a weight of 1
+            total / total_weight
+        };
+
+        #[cfg(feature = "parallel")]
+        let ys = (0..height).into_par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let ys = 0..height;
+        let rows: Vec<Vec<_>> = ys
+            .map(|y| (0..width).map(|x| pixel(x, y)).collect())
+            .collect();
+        let mut denoised = FrameBuffer::new(width, height, image.precision());
+        for (y, row) in (0..height).zip(rows) {
+            for (x, color) in (0..width).zip(row) {
+                denoised.set(x, y, &color);
+            }
+        }
+        denoised
+    }
+}
+
+fn distance2(lhs: &LinearColor, rhs: &LinearColor) -> Float {
+    (lhs.r - rhs.r).powi(2) + (lhs.g - rhs.g).powi(2) + (lhs.b - rhs.b).powi(2)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::Precision;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn uniform(value: Float) -> FrameBuffer {
+        let mut buffer = FrameBuffer::new(16, 16, Precision::Single);
+        for y in 0..16 {
+            for x in 0..16 {
+                buffer.set(x, y, &LinearColor::new(value, value, value));
+            }
+        }
+        buffer
+    }
+
+    fn variance(buffer: &FrameBuffer) -> Float {
+        let values: Vec<_> = (0..16)
+            .flat_map(|y| (0..16).map(move |x| (x, y)))
+            .map(|(x, y)| buffer.get(x, y).r)
+            .collect();
+        let mean = values.iter().sum::<Float>() / values.len() as Float;
+        values.iter().map(|v| (v - mean).powi(2)).sum::<Float>() / values.len() as Float
+    }
+
+    #[test]
+    fn zero_strength_keeps_image() {
+        let mut image = uniform(0.5);
+        image.set(3, 3, &LinearColor::new(2., 0., 1.));
+        let denoised = Denoiser::new(0.).apply(&image, &uniform(1.), &uniform(0.));
+        assert_eq!(denoised, image);
+    }
+
+    #[test]
+    fn noise_is_reduced() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut image = uniform(0.);
+        for y in 0..16 {
+            for x in 0..16 {
+                let value = 0.5 + 0.2 * (rng.gen::<Float>() - 0.5);
+                image.set(x, y, &LinearColor::new(value, value, value));
+            }
+        }
+        let denoised = Denoiser::new(0.5).apply(&image, &uniform(1.), &uniform(0.));
+        assert!(variance(&denoised) < variance(&image) / 4.);
+        let strong = Denoiser::new(1.).apply(&image, &uniform(1.), &uniform(0.));
+        assert!(variance(&strong) < variance(&denoised));
+    }
+
+    #[test]
+    fn uniform_image_is_unchanged() {
+        let image = uniform(0.3);
+        let denoised = Denoiser::new(1.).apply(&image, &uniform(1.), &uniform(0.));
+        for y in 0..16 {
+            for x in 0..16 {
+                assert!((denoised.get(x, y).r - 0.3).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = "{strength: 0.5}";
+        let denoiser: Denoiser = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(denoiser, Denoiser::new(0.5))
+    }
+}
@@ -0,0 +1,150 @@
+use crate::Float;
+use image::RgbImage;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Simulate the grain of photographic film, to match rendered elements with grainy footage.
+///
+/// The grain is applied to the final image. It is monochromatic, and stronger in the midtones
+/// than in the shadows and highlights.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct FilmGrain {
+    /// The standard deviation of the grain in the midtones, as a fraction of the full range.
+    strength: Float,
+    /// The seed of the grain's pattern, if it should not depend on the rendering's seed.
+    #[serde(default)]
+    seed: Option<u64>,
+}
+
+impl FilmGrain {
+    /// Creates a new `FilmGrain`, whose pattern depends on the seed used for rendering.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::post::FilmGrain;
+    /// #
+    /// let grain = FilmGrain::new(0.05);
+    /// ```
+    pub fn new(strength: Float) -> Self {
+        FilmGrain {
+            strength,
+            seed: None,
+        }
+    }
+
+    /// Always use the same grain pattern, whatever the seed used for rendering.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Add grain to an image, using the given seed unless the grain was given its own.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use image::{Rgb, RgbImage};
+    /// # use pathtracer::post::FilmGrain;
+    /// #
+    /// let mut image = RgbImage::from_pixel(16, 16, Rgb([128, 128, 128]));
+    /// FilmGrain::new(0.05).apply(&mut image, 42);
+    /// assert!(image.pixels().any(|pixel| *pixel != Rgb([128, 128, 128])));
+    /// ```
+    pub fn apply(&self, image: &mut RgbImage, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(self.seed.unwrap_or(seed));
+        for pixel in image.pixels_mut() {
+            let [r, g, b] = pixel.0;
+            let luminance =
+                (0.2126 * r as Float + 0.7152 * g as Float + 0.0722 * b as Float) / 255.;
+            // Peaks at 1.0 for midtones, vanishes for pure black and white
+            let response = 2. * (luminance * (1. - luminance)).max(0.).sqrt();
+            let offset = self.strength * response * gaussian(&mut rng) * 255.;
+            for channel in pixel.0.iter_mut() {
+                // Casting saturates to the channel's range, and turns NaNs into 0
+                *channel = (*channel as Float + offset).round() as u8;
+            }
+        }
+    }
+}
+
+/// Draw a number from the standard normal distribution, using the Box-Muller transform.
+fn gaussian(rng: &mut impl Rng) -> Float {
+    let u = 1. - rng.gen::<Float>();
+    let v = rng.gen::<Float>();
+    (-2. * u.ln()).sqrt() * (2. * crate::consts::PI * v).cos()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::Rgb;
+
+    fn grey(value: u8) -> RgbImage {
+        RgbImage::from_pixel(64, 64, Rgb([value, value, value]))
+    }
+
+    #[test]
+    fn new_works() {
+        assert_eq!(
+            FilmGrain::new(0.1),
+            FilmGrain {
+                strength: 0.1,
+                seed: None
+            }
+        )
+    }
+
+    #[test]
+    fn grain_is_monochromatic_and_unbiased() {
+        let mut image = grey(128);
+        FilmGrain::new(0.05).apply(&mut image, 42);
+        assert!(image.pixels().all(|Rgb([r, g, b])| r == g && g == b));
+        let mean = image
+            .pixels()
+            .map(|pixel| pixel.0[0] as Float)
+            .sum::<Float>()
+            / (64. * 64.);
+        assert!((mean - 128.).abs() < 1.);
+        let variance = image
+            .pixels()
+            .map(|pixel| (pixel.0[0] as Float - mean).powi(2))
+            .sum::<Float>()
+            / (64. * 64.);
+        // A standard deviation of 5% of the range
+        assert!((variance.sqrt() - 0.05 * 255.).abs() < 1.);
+    }
+
+    #[test]
+    fn grain_depends_on_luminance() {
+        let mut black = grey(0);
+        let mut white = grey(255);
+        let grain = FilmGrain::new(0.05);
+        grain.apply(&mut black, 42);
+        grain.apply(&mut white, 42);
+        assert_eq!(black, grey(0));
+        assert_eq!(white, grey(255));
+    }
+
+    #[test]
+    fn grain_is_seedable() {
+        let render = |grain: &FilmGrain, seed| {
+            let mut image = grey(100);
+            grain.apply(&mut image, seed);
+            image
+        };
+        let grain = FilmGrain::new(0.05);
+        assert_eq!(render(&grain, 1), render(&grain, 1));
+        assert_ne!(render(&grain, 1), render(&grain, 2));
+        let grain = grain.with_seed(7);
+        assert_eq!(render(&grain, 1), render(&grain, 2));
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = "{strength: 0.05, seed: 7}";
+        let grain: FilmGrain = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(grain, FilmGrain::new(0.05).with_seed(7))
+    }
+}
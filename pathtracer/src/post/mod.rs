@@ -0,0 +1,10 @@
+//! Post-processing passes applied to rendered images
+
+mod denoise;
+pub use denoise::*;
+
+mod grain;
+pub use grain::*;
+
+mod outline;
+pub use outline::*;
@@ -0,0 +1,248 @@
+use crate::core::{FrameBuffer, LinearColor};
+use crate::Float;
+use serde::{Deserialize, Serialize};
+
+/// Draw lines along the silhouettes and creases of the objects, e.g: for cartoon-like renders
+/// along with [`ToonMaterial`].
+///
+/// A pixel is part of an outline when the depth or the normal of the surface it shows changes
+/// sharply with one of its neighbours. Silhouettes against the background are always outlined.
+///
+/// [`ToonMaterial`]: ../material/struct.ToonMaterial.html
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Outline {
+    /// The color of the lines.
+    #[serde(default)]
+    color: LinearColor,
+    /// The distance, in pixels, up to which neighbours are compared, widening the lines.
+    #[serde(default = "default_width")]
+    width: u32,
+    /// The relative difference in depth above which neighbours are separated by a line.
+    #[serde(default = "default_depth_threshold")]
+    depth_threshold: Float,
+    /// The distance between normals above which neighbours are separated by a line.
+    #[serde(default = "default_normal_threshold")]
+    normal_threshold: Float,
+}
+
+fn default_width() -> u32 {
+    1
+}
+
+fn default_depth_threshold() -> Float {
+    0.1
+}
+
+fn default_normal_threshold() -> Float {
+    0.5
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Outline {
+            color: LinearColor::black(),
+            width: default_width(),
+            depth_threshold: default_depth_threshold(),
+            normal_threshold: default_normal_threshold(),
+        }
+    }
+}
+
+impl Outline {
+    /// Creates a new black `Outline`, one pixel wide.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::post::Outline;
+    /// #
+    /// let outline = Outline::new()
+    ///     .with_color(LinearColor::new(0.1, 0.0, 0.2))
+    ///     .with_width(2);
+    /// ```
+    pub fn new() -> Self {
+        Outline::default()
+    }
+
+    /// Set the color of the lines.
+    pub fn with_color(mut self, color: LinearColor) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set how far apart compared neighbours are, in pixels.
+    pub fn with_width(mut self, width: u32) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Set the relative depth and normal differences above which lines are drawn.
+    pub fn with_thresholds(mut self, depth: Float, normal: Float) -> Self {
+        self.depth_threshold = depth;
+        self.normal_threshold = normal;
+        self
+    }
+
+    /// Draw the outlines over an image, using the normal and depth of the surfaces seen in each
+    /// pixel to find their edges. All three buffers must have the same dimensions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{FrameBuffer, LinearColor, Precision};
+    /// # use pathtracer::post::Outline;
+    /// # use pathtracer::Float;
+    /// #
+    /// let mut image = FrameBuffer::new(8, 8, Precision::Single);
+    /// let normal = FrameBuffer::new(8, 8, Precision::Single);
+    /// let mut depth = FrameBuffer::new(8, 8, Precision::Single);
+    /// // The left half shows a surface in front of the background
+    /// for y in 0..8 {
+    ///     for x in 0..8 {
+    ///         image.set(x, y, &LinearColor::new(1.0, 1.0, 1.0));
+    ///         let distance = if x < 4 { 1.0 } else { Float::INFINITY };
+    ///         depth.set(x, y, &LinearColor::new(distance, 0.0, 0.0));
+    ///     }
+    /// }
+    ///
+    /// let outlined = Outline::new().apply(&image, &normal, &depth);
+    /// // The pixels on either side of the silhouette are outlined
+    /// assert_eq!(outlined.get(3, 0), LinearColor::black());
+    /// assert_eq!(outlined.get(4, 0), LinearColor::black());
+    /// assert_eq!(outlined.get(2, 0), LinearColor::new(1.0, 1.0, 1.0));
+    /// ```
+    pub fn apply(
+        &self,
+        image: &FrameBuffer,
+        normal: &FrameBuffer,
+        depth: &FrameBuffer,
+    ) -> FrameBuffer {
+        let (width, height) = image.dimensions();
+        assert_eq!(normal.dimensions(), (width, height), "different dimensions");
+        assert_eq!(depth.dimensions(), (width, height), "different dimensions");
+        let radius = self.width.max(1) as i64;
+        let is_edge = |x: u32, y: u32| {
+            let (normal_at, depth_at) = (normal.get(x, y), depth.get(x, y).r);
+            let neighbours = [(radius, 0), (-radius, 0), (0, radius), (0, -radius)];
+            neighbours.iter().any(|&(dx, dy)| {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                    return false;
+                }
+                let (nx, ny) = (nx as u32, ny as u32);
+                self.separates(
+                    &normal_at,
+                    depth_at,
+                    &normal.get(nx, ny),
+                    depth.get(nx, ny).r,
+                )
+            })
+        };
+
+        let mut outlined = image.clone();
+        for y in 0..height {
+            for x in 0..width {
+                if is_edge(x, y) {
+                    outlined.set(x, y, &self.color);
+                }
+            }
+        }
+        outlined
+    }
+
+    /// Whether a line separates two neighbouring surfaces.
+    fn separates(
+        &self,
+        normal: &LinearColor,
+        depth: Float,
+        other: &LinearColor,
+        other_depth: Float,
+    ) -> bool {
+        // The background is infinitely far away, and never separated from itself
+        if depth.is_infinite() || other_depth.is_infinite() {
+            return depth != other_depth;
+        }
+        let relative = (depth - other_depth).abs() / depth.min(other_depth).max(Float::EPSILON);
+        if relative > self.depth_threshold {
+            return true;
+        }
+        let distance2 = (normal.r - other.r).powi(2)
+            + (normal.g - other.g).powi(2)
+            + (normal.b - other.b).powi(2);
+        distance2 > self.normal_threshold * self.normal_threshold
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::Precision;
+
+    fn buffers(depth: impl Fn(u32, u32) -> Float) -> (FrameBuffer, FrameBuffer, FrameBuffer) {
+        let mut image = FrameBuffer::new(8, 8, Precision::Single);
+        let mut normal = FrameBuffer::new(8, 8, Precision::Single);
+        let mut depths = FrameBuffer::new(8, 8, Precision::Single);
+        for y in 0..8 {
+            for x in 0..8 {
+                image.set(x, y, &LinearColor::new(1., 1., 1.));
+                normal.set(x, y, &LinearColor::new(0., 0., 1.));
+                depths.set(x, y, &LinearColor::new(depth(x, y), 0., 0.));
+            }
+        }
+        (image, normal, depths)
+    }
+
+    fn outlined_count(image: &FrameBuffer) -> usize {
+        let (width, height) = image.dimensions();
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| image.get(x, y) == LinearColor::black())
+            .count()
+    }
+
+    #[test]
+    fn smooth_surfaces_are_not_outlined() {
+        // A slanted plane, whose depth changes slowly
+        let (image, normal, depth) = buffers(|x, _| 10. + 0.1 * x as Float);
+        let outlined = Outline::new().apply(&image, &normal, &depth);
+        assert_eq!(outlined, image);
+    }
+
+    #[test]
+    fn depth_discontinuities_are_outlined() {
+        let (image, normal, depth) = buffers(|_, y| if y < 4 { 1. } else { 2. });
+        let outlined = Outline::new().apply(&image, &normal, &depth);
+        // Both rows along the discontinuity
+        assert_eq!(outlined_count(&outlined), 16);
+        let wide = Outline::new().with_width(2).apply(&image, &normal, &depth);
+        assert_eq!(outlined_count(&wide), 32);
+    }
+
+    #[test]
+    fn creases_are_outlined() {
+        let (image, mut normal, depth) = buffers(|_, _| 1.);
+        for y in 0..8 {
+            for x in 4..8 {
+                normal.set(x, y, &LinearColor::new(1., 0., 0.));
+            }
+        }
+        let outlined = Outline::new().apply(&image, &normal, &depth);
+        assert_eq!(outlined_count(&outlined), 16);
+        // A looser threshold ignores the crease
+        let loose = Outline::new().with_thresholds(0.1, 2.);
+        assert_eq!(loose.apply(&image, &normal, &depth), image);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = "{color: {r: 1.0, g: 0.0, b: 0.0}, width: 2}";
+        let outline: Outline = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            outline,
+            Outline::new()
+                .with_color(LinearColor::new(1., 0., 0.))
+                .with_width(2)
+        );
+    }
+}
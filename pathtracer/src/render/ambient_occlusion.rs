@@ -0,0 +1,72 @@
+//! Contact shadows for the ambient lights
+
+use crate::Float;
+use serde::{Deserialize, Serialize};
+
+/// Darken the light received from ambient lights in the creases and corners of the scene, by
+/// casting short-range rays around each shading point.
+///
+/// The ambient light is scaled by the fraction of those rays which do not hit anything closer
+/// than `distance`, giving contact shadows without computing the full global illumination.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct AmbientOcclusion {
+    /// The distance up to which geometry occludes the ambient light.
+    distance: Float,
+    /// The number of rays cast from each shading point.
+    #[serde(default = "default_samples")]
+    samples: u32,
+}
+
+fn default_samples() -> u32 {
+    16
+}
+
+impl AmbientOcclusion {
+    /// Creates a new `AmbientOcclusion`, casting 16 rays from each shading point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::render::AmbientOcclusion;
+    /// #
+    /// let occlusion = AmbientOcclusion::new(0.5).with_samples(64);
+    /// assert_eq!(occlusion.samples(), 64);
+    /// ```
+    pub fn new(distance: Float) -> Self {
+        AmbientOcclusion {
+            distance,
+            samples: default_samples(),
+        }
+    }
+
+    /// Set the number of rays cast from each shading point, at least one.
+    pub fn with_samples(mut self, samples: u32) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Get the distance up to which geometry occludes the ambient light.
+    pub fn distance(&self) -> Float {
+        self.distance
+    }
+
+    /// Get the number of rays cast from each shading point.
+    pub fn samples(&self) -> u32 {
+        self.samples.max(1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = "distance: 0.5";
+        let occlusion: AmbientOcclusion = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(occlusion, AmbientOcclusion::new(0.5));
+        let yaml = "{distance: 2.0, samples: 4}";
+        let occlusion: AmbientOcclusion = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(occlusion, AmbientOcclusion::new(2.).with_samples(4));
+    }
+}
@@ -0,0 +1,163 @@
+//! Arbitrary output variables, rendered alongside the image
+
+use super::utils::heat;
+use crate::core::{write_exr, ExrLayer, FrameBuffer};
+use crate::{Float, Result};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The image of a scene, along with arbitrary output variables (AOVs) describing what the camera
+/// sees, which compositing tools use to rework the image.
+///
+/// The variables are averaged over the samples of each pixel, except for the depth, which is the
+/// distance to the closest surface seen through it, and the variance, which measures how much
+/// these samples disagree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aovs {
+    /// The rendered image, denoised if the scene has a denoiser.
+    pub beauty: FrameBuffer,
+    /// The color of the surfaces seen by the camera, before being lit.
+    pub albedo: FrameBuffer,
+    /// The normal of these surfaces, its coordinates stored in the red, green and blue channels.
+    pub normal: FrameBuffer,
+    /// The distance from the camera's origin to these surfaces, stored in the red channel, and
+    /// infinite for the pixels which do not see any of them.
+    pub depth: FrameBuffer,
+    /// The variance of the luminance of each pixel of the image, before it is denoised, stored in
+    /// the red channel. It is estimated from the anti-aliasing samples, and is zero when a single
+    /// sample is taken per pixel.
+    pub variance: FrameBuffer,
+    /// The contribution of each named light to the image, sorted by name. Lights sharing the same
+    /// name are rendered together.
+    pub lights: Vec<(String, FrameBuffer)>,
+    /// The contribution of each group of lights to the image, sorted by name.
+    pub groups: Vec<(String, FrameBuffer)>,
+}
+
+/// The luminance below which the error of a pixel is measured relatively to this value instead,
+/// so that the darkest pixels do not look noisy.
+const MIN_LUMINANCE: Float = 1e-2;
+
+impl Aovs {
+    /// Draw how noisy each pixel of the image still is, from blue for converged pixels, through
+    /// green, to red for pixels whose relative error is at least `threshold`.
+    ///
+    /// The relative error of a pixel is the standard deviation of its luminance, divided by its
+    /// luminance, e.g: a threshold of 0.05 shows the pixels which are off by more than 5%.
+    pub fn heatmap(&self, threshold: Float) -> FrameBuffer {
+        let (width, height) = self.variance.dimensions();
+        let mut heatmap = FrameBuffer::new(width, height, self.variance.precision());
+        for y in 0..height {
+            for x in 0..width {
+                let deviation = self.variance.get(x, y).r.max(0.).sqrt();
+                let luminance = self.beauty.get(x, y).luminance().max(MIN_LUMINANCE);
+                heatmap.set(x, y, &heat(deviation / luminance / threshold));
+            }
+        }
+        heatmap
+    }
+
+    /// Write every variable into a single multi-layer OpenEXR image.
+    ///
+    /// The image is stored in the `R`, `G`, and `B` channels, the other variables in the `albedo`,
+    /// `normal` (as `X`, `Y`, and `Z`), `depth` (as `Z`), and `variance` (as `Y`) layers, the
+    /// contribution of each
+    /// light in a `light-<name>` layer, and of each group of lights in a `group-<name>` layer.
+    pub fn write_exr<W: Write>(&self, writer: W) -> io::Result<()> {
+        const RGB: &[&str] = &["R", "G", "B"];
+        let names: Vec<_> = (self.lights.iter())
+            .map(|(name, _)| format!("light-{}", name))
+            .chain(
+                self.groups
+                    .iter()
+                    .map(|(name, _)| format!("group-{}", name)),
+            )
+            .collect();
+        let mut layers = vec![
+            ExrLayer::new("", RGB, &self.beauty),
+            ExrLayer::new("albedo", RGB, &self.albedo),
+            ExrLayer::new("normal", &["X", "Y", "Z"], &self.normal),
+            ExrLayer::new("depth", &["Z"], &self.depth),
+            ExrLayer::new("variance", &["Y"], &self.variance),
+        ];
+        for (name, (_, buffer)) in names.iter().zip(self.lights.iter().chain(&self.groups)) {
+            layers.push(ExrLayer::new(name, RGB, buffer));
+        }
+        write_exr(writer, &layers)
+    }
+
+    /// Save every variable into a single multi-layer OpenEXR file, as described in
+    /// [`write_exr`].
+    ///
+    /// [`write_exr`]: #method.write_exr
+    pub fn save_exr<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        File::create(path)
+            .and_then(|file| self.write_exr(BufWriter::new(file)))
+            .map_err(|err| crate::Error::from(err).with_path(path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{LinearColor, Precision};
+
+    #[test]
+    fn write_exr_works() {
+        let buffer = || FrameBuffer::new(2, 2, Precision::Half);
+        let mut depth = buffer();
+        depth.set(0, 0, &LinearColor::new(Float::INFINITY, 0., 0.));
+        let aovs = Aovs {
+            beauty: buffer(),
+            albedo: buffer(),
+            normal: buffer(),
+            depth,
+            variance: buffer(),
+            lights: vec![("key".to_string(), buffer())],
+            groups: vec![("practicals".to_string(), buffer())],
+        };
+        let mut exr = Vec::new();
+        aovs.write_exr(&mut exr).unwrap();
+        let content = String::from_utf8_lossy(&exr);
+        for channel in &[
+            "\0R\0",
+            "albedo.G\0",
+            "normal.X\0",
+            "depth.Z\0",
+            "variance.Y\0",
+            "light-key.B\0",
+            "group-practicals.R\0",
+        ] {
+            assert!(content.contains(channel), "missing {:?}", channel);
+        }
+        assert!(!content.contains("depth.X"));
+    }
+
+    #[test]
+    fn heatmap_works() {
+        let buffer = |color: LinearColor| {
+            let mut buffer = FrameBuffer::new(3, 1, Precision::Single);
+            (0..3).for_each(|x| buffer.set(x, 0, &color));
+            buffer
+        };
+        let mut variance = buffer(LinearColor::black());
+        // A 5% error, then a 20% one, of a pixel whose luminance is 1
+        variance.set(1, 0, &LinearColor::new(0.05 * 0.05, 0., 0.));
+        variance.set(2, 0, &LinearColor::new(0.2 * 0.2, 0., 0.));
+        let aovs = Aovs {
+            beauty: buffer(LinearColor::new(1., 1., 1.)),
+            albedo: buffer(LinearColor::black()),
+            normal: buffer(LinearColor::black()),
+            depth: buffer(LinearColor::black()),
+            variance,
+            lights: Vec::new(),
+            groups: Vec::new(),
+        };
+        let heatmap = aovs.heatmap(0.1);
+        assert_eq!(heatmap.get(0, 0), LinearColor::new(0., 0., 1.));
+        assert!((heatmap.get(1, 0).g - 1.).abs() < 1e-5);
+        assert_eq!(heatmap.get(2, 0), LinearColor::new(1., 0., 0.));
+    }
+}
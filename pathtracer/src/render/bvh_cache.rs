@@ -0,0 +1,176 @@
+//! Caching the BVH of a scene on disk, to skip building it on subsequent renders
+
+use super::object::Object;
+use super::scene::BvhBuilder;
+use crate::{Error, Result};
+use beevee::aabb::Bounded;
+use beevee::bvh::BVH4;
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+/// The first bytes of a cache, followed by the objects' fingerprint, the checksum of the rest of
+/// the cache, the order in which the objects are stored, and the BVH.
+const MAGIC: &[u8; 8] = b"PTBVH\0\0\x03";
+
+/// Compute a checksum of the builder and of the bounds of the objects, in the order given: the BVH
+/// only depends on them, it can be used for any objects with the same bounds.
+fn fingerprint(objects: &[Object], builder: BvhBuilder) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    match builder {
+        BvhBuilder::Sah => hasher.update(&[0]),
+        BvhBuilder::Linear => hasher.update(&[1]),
+        BvhBuilder::Spatial { budget } => {
+            hasher.update(&[2]);
+            hasher.update(&budget.to_le_bytes());
+        }
+    }
+    hasher.update(&(objects.len() as u64).to_le_bytes());
+    for object in objects {
+        let (aabb, centroid) = (object.aabb(), object.centroid());
+        for point in [aabb.low, aabb.high, centroid].iter() {
+            for coordinate in point.iter() {
+                hasher.update(&coordinate.to_le_bytes());
+            }
+        }
+    }
+    hasher.finalize()
+}
+
+/// Load the BVH cached in `path` for the given bounded objects and builder, with the order in which
+/// they must be stored to walk it, as given by [`BVH::build_order`].
+///
+/// Return `None` if there is no usable cache, e.g: if it was made for other objects or by another
+/// builder, or if it was truncated or corrupted.
+///
+/// [`BVH::build_order`]: ../../../beevee/bvh/struct.BVH.html#method.build_order
+pub fn load(path: &Path, objects: &[Object], builder: BvhBuilder) -> Option<(BVH4, Vec<usize>)> {
+    let bytes = fs::read(path).ok()?;
+    let (magic, bytes) = split(&bytes, MAGIC.len())?;
+    let (checksum, bytes) = split(bytes, 4)?;
+    if magic != MAGIC || checksum != fingerprint(objects, builder).to_le_bytes() {
+        return None;
+    }
+    let (checksum, bytes) = split(bytes, 4)?;
+    if checksum != crc32fast::hash(bytes).to_le_bytes() {
+        return None;
+    }
+    let (order, bytes) = split(bytes, 4 * objects.len())?;
+    let order: Vec<_> = order
+        .chunks_exact(4)
+        .map(|index| u32::from_le_bytes(index.try_into().unwrap()) as usize)
+        .collect();
+    // Each object must appear exactly once
+    let mut seen = vec![false; objects.len()];
+    for &index in order.iter() {
+        if std::mem::replace(seen.get_mut(index)?, true) {
+            return None;
+        }
+    }
+    let bvh = BVH4::read(bytes, objects.len()).ok()?;
+    Some((bvh, order))
+}
+
+/// Write the BVH built by `builder` for the given bounded objects and the order in which they are
+/// stored to `path`, to be loaded again by [`load`].
+///
+/// [`load`]: fn.load.html
+pub fn save(
+    path: &Path,
+    objects: &[Object],
+    builder: BvhBuilder,
+    bvh: &BVH4,
+    order: &[usize],
+) -> Result<()> {
+    let mut payload = Vec::new();
+    for &index in order {
+        payload.extend_from_slice(&(index as u32).to_le_bytes());
+    }
+    bvh.write(&mut payload)?;
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&fingerprint(objects, builder).to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    fs::write(path, bytes).map_err(|err| Error::from(err).with_path(path))
+}
+
+fn split(bytes: &[u8], len: usize) -> Option<(&[u8], &[u8])> {
+    if bytes.len() < len {
+        None
+    } else {
+        Some(bytes.split_at(len))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{LightProperties, LinearColor};
+    use crate::material::UniformMaterial;
+    use crate::shape::Sphere;
+    use crate::texture::UniformTexture;
+    use crate::{Float, Point};
+    use beevee::bvh::BVH;
+
+    fn spheres(offset: Float) -> Vec<Object> {
+        (0..50)
+            .map(|i| {
+                Object::new(
+                    Sphere::new(Point::new(i as Float + offset, 0., 0.), 0.25).into(),
+                    UniformMaterial::new(LightProperties::new(
+                        LinearColor::new(1., 1., 1.),
+                        LinearColor::black(),
+                        None,
+                    ))
+                    .into(),
+                    UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cache_round_trip_works() {
+        let path = std::env::temp_dir().join(format!("bvh-{}.bvh", std::process::id()));
+        let objects = spheres(0.);
+        let (bvh, order) = BVH::build_order(&objects);
+        let bvh = BVH4::collapse(&bvh);
+        save(&path, &objects, BvhBuilder::Sah, &bvh, &order).unwrap();
+        assert_eq!(
+            load(&path, &objects, BvhBuilder::Sah),
+            Some((bvh.clone(), order.clone()))
+        );
+        // Objects with other bounds need another BVH
+        assert_eq!(load(&path, &spheres(0.5), BvhBuilder::Sah), None);
+        assert_eq!(load(&path, &objects[1..], BvhBuilder::Sah), None);
+        // As do other builders
+        assert_eq!(load(&path, &objects, BvhBuilder::Linear), None);
+        let spatial = |budget| BvhBuilder::Spatial { budget };
+        save(&path, &objects, spatial(0.3), &bvh, &order).unwrap();
+        assert!(load(&path, &objects, spatial(0.3)).is_some());
+        assert_eq!(load(&path, &objects, spatial(0.5)), None);
+        assert_eq!(load(&path, &objects, BvhBuilder::Sah), None);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(load(&path, &objects, BvhBuilder::Sah), None);
+    }
+
+    #[test]
+    fn corrupted_caches_are_rejected() {
+        let path = std::env::temp_dir().join(format!("bvh-corrupt-{}.bvh", std::process::id()));
+        let objects = spheres(0.);
+        let (bvh, order) = BVH::build_order(&objects);
+        let bvh = BVH4::collapse(&bvh);
+        save(&path, &objects, BvhBuilder::Sah, &bvh, &order).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        // Truncated
+        std::fs::write(&path, &bytes[..bytes.len() - 8]).unwrap();
+        assert_eq!(load(&path, &objects, BvhBuilder::Sah), None);
+        // Flipped bits in the BVH
+        let mut corrupted = bytes.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+        std::fs::write(&path, &corrupted).unwrap();
+        assert_eq!(load(&path, &objects, BvhBuilder::Sah), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,388 @@
+use super::{GpuError, GpuScene};
+use crate::core::{FrameBuffer, LinearColor, Precision};
+use crate::Float;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+use wgpu::util::DeviceExt;
+
+/// The source of the wavefront kernels.
+const KERNEL: &str = include_str!("wavefront.wgsl");
+
+/// The width and height of the workgroups of the kernels, in pixels.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// The size of a path's state, in bytes.
+const PATH_SIZE: u64 = 5 * 16;
+
+/// The size of a pixel of the accumulated image, in bytes.
+const PIXEL_SIZE: u64 = 16;
+
+/// The GPU used to render scenes.
+pub(crate) struct Device {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+}
+
+/// The pipelines running each stage of the paths, sharing the same bindings.
+struct Kernels {
+    generate: wgpu::ComputePipeline,
+    extend: wgpu::ComputePipeline,
+    shade: wgpu::ComputePipeline,
+    accumulate: wgpu::ComputePipeline,
+}
+
+impl Device {
+    /// Open the most powerful GPU available.
+    pub(crate) fn new() -> Result<Self, GpuError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .ok_or_else(|| GpuError::Unavailable("no adapter was found".into()))?;
+        let descriptor = wgpu::DeviceDescriptor {
+            label: None,
+            required_features: wgpu::Features::empty(),
+            required_limits: adapter.limits(),
+        };
+        let (device, queue) = block_on(adapter.request_device(&descriptor, None))
+            .map_err(|err| GpuError::Unavailable(err.to_string()))?;
+        Ok(Device { device, queue })
+    }
+
+    /// Render the flattened scene, averaging its samples into a buffer of the given precision.
+    pub(crate) fn render(
+        &self,
+        scene: &GpuScene,
+        precision: Precision,
+    ) -> Result<FrameBuffer, GpuError> {
+        let pixels = scene.width as u64 * scene.height as u64;
+        let limit = self.device.limits().max_storage_buffer_binding_size as u64;
+        if pixels * PATH_SIZE > limit {
+            return Err(GpuError::Unavailable("the image is too large".into()));
+        }
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let image = self.trace(scene, pixels);
+        if let Some(err) = block_on(self.device.pop_error_scope()) {
+            return Err(GpuError::Unavailable(err.to_string()));
+        }
+
+        let mut buffer = FrameBuffer::new(scene.width, scene.height, precision);
+        let samples = scene.samples as Float;
+        for (i, pixel) in (0..).zip(image?.chunks(4)) {
+            let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|channel| channel as Float);
+            let color = LinearColor::new(r, g, b) / samples;
+            buffer.set(i % scene.width, i / scene.width, &color);
+        }
+        Ok(buffer)
+    }
+
+    /// Run the kernels over every sample of the scene, returning the sum of the samples of each
+    /// pixel.
+    fn trace(&self, scene: &GpuScene, pixels: u64) -> Result<Vec<f32>, GpuError> {
+        let storage = wgpu::BufferUsages::STORAGE;
+        let params = self.upload(&scene.params(0), wgpu::BufferUsages::UNIFORM);
+        let tables = [
+            &scene.nodes,
+            &scene.indices,
+            &scene.primitives,
+            &scene.materials,
+            &scene.lights,
+            &scene.background,
+        ];
+        let tables: Vec<_> = tables.iter().map(|t| self.upload(t, storage)).collect();
+        let paths = self.buffer(pixels * PATH_SIZE, storage);
+        let output = self.buffer(pixels * PIXEL_SIZE, storage | wgpu::BufferUsages::COPY_SRC);
+
+        let (layout, kernels) = self.kernels();
+        let buffers = std::iter::once(&params)
+            .chain(tables.iter())
+            .chain([&paths, &output]);
+        let entries: Vec<_> = (0..)
+            .zip(buffers)
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        let bindings = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &entries,
+        });
+
+        let workgroups = |size: u32| size.div_ceil(WORKGROUP_SIZE);
+        let (x, y) = (workgroups(scene.width), workgroups(scene.height));
+        // Each path is generated, then extended and shaded once per bounce, then accumulated
+        let bounces = (0..=scene.reflection_limit).flat_map(|_| [&kernels.extend, &kernels.shade]);
+        let stages: Vec<_> = std::iter::once(&kernels.generate)
+            .chain(bounces)
+            .chain(std::iter::once(&kernels.accumulate))
+            .collect();
+        for sample in 0..scene.samples {
+            let params_bytes = to_bytes(&scene.params(sample));
+            self.queue.write_buffer(&params, 0, &params_bytes);
+            let mut encoder = self.device.create_command_encoder(&Default::default());
+            {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                pass.set_bind_group(0, &bindings, &[]);
+                for kernel in stages.iter() {
+                    pass.set_pipeline(kernel);
+                    pass.dispatch_workgroups(x, y, 1);
+                }
+            }
+            self.queue.submit([encoder.finish()]);
+        }
+
+        self.read(&output, pixels * PIXEL_SIZE)
+    }
+
+    /// Compile the kernels, along with the layout of their bindings.
+    fn kernels(&self) -> (wgpu::BindGroupLayout, Kernels) {
+        let module = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("wavefront"),
+                source: wgpu::ShaderSource::Wgsl(KERNEL.into()),
+            });
+        // The parameters, the read-only tables of the scene, then the paths and the image
+        let entries: Vec<_> = (0..9)
+            .map(|binding| {
+                let ty = match binding {
+                    0 => wgpu::BufferBindingType::Uniform,
+                    1..=6 => wgpu::BufferBindingType::Storage { read_only: true },
+                    _ => wgpu::BufferBindingType::Storage { read_only: false },
+                };
+                wgpu::BindGroupLayoutEntry {
+                    binding,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            })
+            .collect();
+        let layout = (self.device).create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &entries,
+        });
+        let pipeline_layout =
+            (self.device).create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = |entry_point| {
+            (self.device).create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point,
+            })
+        };
+        let kernels = Kernels {
+            generate: pipeline("generate"),
+            extend: pipeline("extend"),
+            shade: pipeline("shade"),
+            accumulate: pipeline("accumulate"),
+        };
+        (layout, kernels)
+    }
+
+    /// Create a buffer holding the given words.
+    fn upload(&self, words: &[u32], usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        // Bindings cannot be empty, pad the empty tables
+        let padded = [0; 4];
+        let words = if words.is_empty() { &padded } else { words };
+        (self.device).create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: &to_bytes(words),
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+        })
+    }
+
+    /// Create a zeroed buffer of the given size, in bytes.
+    fn buffer(&self, size: u64, usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Copy the content of a buffer of the given size back from the GPU.
+    fn read(&self, buffer: &wgpu::Buffer, size: u64) -> Result<Vec<f32>, GpuError> {
+        let usage = wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST;
+        let staging = self.buffer(size, usage);
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, size);
+        self.queue.submit([encoder.finish()]);
+
+        let slice = staging.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            // The receiver waits for the result, it cannot be gone yet
+            sender.send(result).unwrap();
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|err| GpuError::Unavailable(err.to_string()))?
+            .map_err(|err| GpuError::Unavailable(err.to_string()))?;
+        let floats = (slice.get_mapped_range().chunks_exact(4))
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        staging.unmap();
+        Ok(floats)
+    }
+}
+
+/// Convert words into the bytes of a buffer.
+fn to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+/// Wake the thread waiting for a future.
+struct Unparker(Thread);
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Wait for a future to complete on the current thread.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Arc::new(Unparker(thread::current())).into();
+    let mut context = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wgpu::naga;
+
+    #[test]
+    fn kernels_are_valid() {
+        let module = naga::front::wgsl::parse_str(KERNEL)
+            .unwrap_or_else(|err| panic!("{}", err.emit_to_string(KERNEL)));
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap_or_else(|err| panic!("{}", err.emit_to_string(KERNEL)));
+        for name in &["generate", "extend", "shade", "accumulate"] {
+            let entry = (module.entry_points.iter())
+                .find(|entry| entry.name == *name)
+                .unwrap_or_else(|| panic!("missing kernel '{}'", name));
+            assert_eq!(entry.stage, naga::ShaderStage::Compute);
+            assert_eq!(entry.workgroup_size, [WORKGROUP_SIZE, WORKGROUP_SIZE, 1]);
+        }
+    }
+
+    #[test]
+    fn gpu_matches_cpu() {
+        use crate::consts::FRAC_PI_2;
+        use crate::core::{Camera, LightProperties};
+        use crate::light::PointLight;
+        use crate::material::UniformMaterial;
+        use crate::render::{LightAggregate, Object, Scene};
+        use crate::shape::{Plane, Sphere, Triangle};
+        use crate::texture::UniformTexture;
+        use crate::{Point, Vector};
+
+        if let Err(err) = Device::new() {
+            eprintln!("skipping the comparison to the CPU: {}", err);
+            return;
+        }
+
+        let object = |shape, color| {
+            Object::new(
+                shape,
+                UniformMaterial::new(LightProperties::new(color, LinearColor::black(), None))
+                    .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )
+        };
+        let light = PointLight::new(Point::new(0., 3., 0.), LinearColor::new(1., 1., 1.));
+        let scene = Scene::new(
+            Camera::new(
+                Point::origin(),
+                Vector::z(),
+                Vector::y(),
+                FRAC_PI_2,
+                1.,
+                32,
+                32,
+            ),
+            LightAggregate::new(vec![], vec![], vec![light], vec![]),
+            vec![
+                object(
+                    Sphere::new(Point::new(-1., 0., 4.), 1.).into(),
+                    LinearColor::new(0.8, 0.2, 0.2),
+                ),
+                object(
+                    Triangle::new(
+                        Point::new(0.5, -1., 4.),
+                        Point::new(2.5, -1., 4.),
+                        Point::new(1.5, 1., 4.),
+                    )
+                    .into(),
+                    LinearColor::new(0.2, 0.8, 0.2),
+                ),
+                object(
+                    Plane::new(Point::new(0., -1., 0.), Vector::y()).into(),
+                    LinearColor::new(0.5, 0.5, 0.8),
+                ),
+            ],
+            LinearColor::new(0.2, 0.2, 0.2).into(),
+            16,
+            0,
+            1.,
+        );
+
+        let (gpu, err) = scene.render_gpu_with_seed(42);
+        assert_eq!(err, None);
+        let cpu = scene.render_with_seed(42);
+        // The renders only differ by their noise, compare the average of each block of pixels
+        let block = |image: &image::RgbImage, x: u32, y: u32| {
+            let mut sum = [0.; 3];
+            for (dx, dy) in (0..4).flat_map(|dy| (0..4).map(move |dx| (dx, dy))) {
+                let pixel = image.get_pixel(x * 4 + dx, y * 4 + dy);
+                for (sum, channel) in sum.iter_mut().zip(pixel.0.iter()) {
+                    *sum += *channel as Float / 16.;
+                }
+            }
+            sum
+        };
+        for (x, y) in (0..8).flat_map(|y| (0..8).map(move |x| (x, y))) {
+            let (gpu, cpu) = (block(&gpu, x, y), block(&cpu, x, y));
+            for (g, c) in gpu.iter().zip(cpu.iter()) {
+                assert!(
+                    (g - c).abs() < 24.,
+                    "block ({}, {}): {:?} != {:?}",
+                    x,
+                    y,
+                    gpu,
+                    cpu
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use super::GpuError;
+use crate::background::Background;
+use crate::consts::PI;
+use crate::core::{Camera, LinearColor, ReflTransEnum};
+use crate::light::{Falloff, Light, SpatialLight};
+use crate::material::Material;
+use crate::render::{Object, Scene, Sides};
+use crate::shape::{Shape, ShapeEnum};
+use crate::texture::Texture;
+use crate::{Float, Point, Point2D, Vector};
+use beevee::bvh::WideChild;
+use nalgebra::Unit;
+use std::collections::HashMap;
+
+/// The number of columns of the table sampling the background over every direction.
+pub(crate) const BACKGROUND_WIDTH: u32 = 64;
+
+/// The number of rows of the table sampling the background over every direction.
+pub(crate) const BACKGROUND_HEIGHT: u32 = 32;
+
+/// The count marking the children of the BVH's nodes which are nodes themselves, not leaves.
+const INTERNAL: u32 = u32::MAX;
+
+/// The kinds of primitives, as told apart by the kernels.
+const SPHERE: u32 = 0;
+const TRIANGLE: u32 = 1;
+const PLANE: u32 = 2;
+
+/// The flag of spheres which are seen from the inside.
+const INVERTED: u32 = 1;
+
+/// The flag of primitives which emit light from both of their sides.
+const EMITS_BOTH_SIDES: u32 = 2;
+
+/// The kinds of lights, as told apart by the kernels.
+const POINT_LIGHT: Float = 0.;
+const DIRECTIONAL_LIGHT: Float = 1.;
+
+/// A scene flattened into the buffers read by the kernels.
+///
+/// Every buffer is made of 32-bit words, grouped in vectors of four to follow the alignment rules
+/// of WGSL: each of its elements is a struct of `vec4` fields declared in the kernels.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct GpuScene {
+    /// The width of the rendered image.
+    pub width: u32,
+    /// The height of the rendered image.
+    pub height: u32,
+    /// The number of paths traced through each pixel.
+    pub samples: u32,
+    /// The number of bounces of each path.
+    pub reflection_limit: u32,
+    /// The nodes of the scene's BVH, the root first.
+    pub nodes: Vec<u32>,
+    /// The index of the primitive found at each position of the BVH's leaves.
+    pub indices: Vec<u32>,
+    /// The primitives, the ones which are part of the BVH first.
+    pub primitives: Vec<u32>,
+    /// The materials of the primitives, shared by the primitives looking alike.
+    pub materials: Vec<u32>,
+    /// The point and directional lights.
+    pub lights: Vec<u32>,
+    /// The color of the background over a grid of directions, by latitude and longitude.
+    pub background: Vec<u32>,
+    /// The parameters which do not change from one sample to the next, see `params`.
+    params: Vec<u32>,
+}
+
+impl GpuScene {
+    /// Flatten the scene, to render the `(x, y, width, height)` window of the camera's image
+    /// drawing every random sample from the given seed.
+    pub(crate) fn new(
+        scene: &Scene,
+        camera: &Camera,
+        window: (u32, u32, u32, u32),
+        seed: u64,
+    ) -> Result<Self, GpuError> {
+        let (left, top, width, height) = window;
+        let mut primitives = Vec::new();
+        let mut materials = Vec::new();
+        let mut known = HashMap::new();
+        for object in scene.objects.iter() {
+            let material = material(object);
+            let next = known.len() as u32;
+            let index = *known.entry(material.clone()).or_insert_with(|| {
+                materials.extend_from_slice(&material);
+                next
+            });
+            primitives.extend_from_slice(&primitive(object, index)?);
+        }
+
+        let (directionals, points) = scene.lights.simple_lights().ok_or_else(|| {
+            GpuError::Unsupported("spot, area, sphere, projector, and sky lights".into())
+        })?;
+        let mut lights = Vec::new();
+        for light in directionals {
+            let (direction, _) = light.to_source(&Point::origin());
+            let color = light.illumination(&Point::origin());
+            push(
+                &mut lights,
+                &[direction.x, direction.y, direction.z, DIRECTIONAL_LIGHT],
+            );
+            push(&mut lights, &[color.r, color.g, color.b, 0.]);
+        }
+        for light in points {
+            let position = light.position().unwrap();
+            // The light received at a unit distance is the light's color, whatever its falloff
+            let color = light.illumination(&(position + Vector::x()));
+            let exponent = match light.falloff() {
+                Falloff::Constant => 0.,
+                Falloff::Linear => 1.,
+                Falloff::InverseSquare => 2.,
+            };
+            push(
+                &mut lights,
+                &[position.x, position.y, position.z, POINT_LIGHT],
+            );
+            push(&mut lights, &[color.r, color.g, color.b, exponent]);
+        }
+        let ambient = (scene.lights.ambient_lights_iter())
+            .map(|light| light.illumination(&Point::origin()))
+            .fold(LinearColor::black(), |sum, color| sum + color);
+
+        let film = camera.film();
+        let corner = film.pixel_at_coord(left, top);
+        let right = film.pixel_at_coord(left + 1, top) - corner;
+        let down = film.pixel_at_coord(left, top + 1) - corner;
+        let origin = camera.origin();
+        let light_count = (lights.len() / 8) as u32;
+        let jittered = (scene.aliasing_limit > 0) as u32;
+        let mut params = vec![width, height, 0, (seed ^ (seed >> 32)) as u32];
+        params.extend_from_slice(&[
+            scene.reflection_limit,
+            scene.bvh.nodes().len() as u32,
+            scene.bounded_count as u32,
+            scene.objects.len() as u32,
+        ]);
+        params.extend_from_slice(&[light_count, jittered, 0, 0]);
+        push(&mut params, &[origin.x, origin.y, origin.z, 0.]);
+        push(&mut params, &[corner.x, corner.y, corner.z, 0.]);
+        push(&mut params, &[right.x, right.y, right.z, 0.]);
+        push(&mut params, &[down.x, down.y, down.z, 0.]);
+        let (r, g, b) = (ambient.r, ambient.g, ambient.b);
+        push(&mut params, &[r, g, b, scene.diffraction_index]);
+
+        let (nodes, indices) = flatten_bvh(scene);
+        Ok(GpuScene {
+            width,
+            height,
+            samples: scene.aliasing_limit.max(1),
+            reflection_limit: scene.reflection_limit,
+            nodes,
+            indices,
+            primitives,
+            materials,
+            lights,
+            background: sample_background(scene),
+            params,
+        })
+    }
+
+    /// Get the parameters of the kernels for the given sample of each pixel.
+    pub(crate) fn params(&self, sample: u32) -> Vec<u32> {
+        let mut params = self.params.clone();
+        params[2] = sample;
+        params
+    }
+}
+
+/// Write floats into a buffer of words, as the `f32` the kernels work with.
+// The cast is a no-op unless `Float` is `f64`
+#[allow(clippy::unnecessary_cast)]
+fn push(words: &mut Vec<u32>, floats: &[Float]) {
+    words.extend(floats.iter().map(|&float| (float as f32).to_bits()));
+}
+
+/// Flatten the scene's BVH into its nodes, and the index of the primitive found at each
+/// position of its leaves.
+fn flatten_bvh(scene: &Scene) -> (Vec<u32>, Vec<u32>) {
+    let mut nodes = Vec::new();
+    let mut positions = 0;
+    for node in scene.bvh.nodes() {
+        let used = |lane: usize| lane < node.count;
+        for coordinates in node.low.iter().chain(node.high.iter()) {
+            // The bounds of unused children are infinite, which the kernels cannot handle
+            let bounds = (0..4).map(|lane| if used(lane) { coordinates[lane] } else { 0. });
+            push(&mut nodes, &bounds.collect::<Vec<_>>());
+        }
+        let children = (0..4).map(|lane| match node.children[lane] {
+            _ if !used(lane) => (0, 0),
+            WideChild::Node(index) => (index as u32, INTERNAL),
+            WideChild::Leaf { begin, end } => {
+                positions = positions.max(end);
+                (begin as u32, (end - begin) as u32)
+            }
+        });
+        let (first, counts): (Vec<_>, Vec<_>) = children.unzip();
+        nodes.extend(first.into_iter().chain(counts));
+    }
+    let indices = (0..positions)
+        .map(|position| scene.bvh.object_index(position) as u32)
+        .collect();
+    (nodes, indices)
+}
+
+/// Flatten an object into a primitive using the material of the given index.
+fn primitive(object: &Object, material: u32) -> Result<Vec<u32>, GpuError> {
+    let mut flags = 0;
+    if object.sides == Sides::BothEmitting {
+        flags |= EMITS_BOTH_SIDES;
+    }
+    let mut words = Vec::with_capacity(16);
+    let points = match &object.shape {
+        ShapeEnum::Sphere(sphere) => {
+            if sphere.is_inverted() {
+                flags |= INVERTED;
+            }
+            words.extend_from_slice(&[SPHERE, material, flags, 0]);
+            let center = sphere.center();
+            [center.x, center.y, center.z, sphere.radius()]
+                .iter()
+                .chain(&[0.; 8])
+                .copied()
+                .collect::<Vec<_>>()
+        }
+        ShapeEnum::Triangle(triangle) => {
+            words.extend_from_slice(&[TRIANGLE, material, flags, 0]);
+            (triangle.corners().iter())
+                .flat_map(|corner| vec![corner.x, corner.y, corner.z, 0.])
+                .collect()
+        }
+        ShapeEnum::Plane(plane) => {
+            words.extend_from_slice(&[PLANE, material, flags, 0]);
+            let (origin, normal) = (plane.origin(), plane.normal(plane.origin()));
+            vec![origin.x, origin.y, origin.z, 0.]
+                .into_iter()
+                .chain(vec![normal.x, normal.y, normal.z, 0.])
+                .chain(vec![0.; 4])
+                .collect()
+        }
+        ShapeEnum::Csg(_) => return Err(GpuError::Unsupported("CSG shapes".into())),
+        ShapeEnum::Sdf(_) => return Err(GpuError::Unsupported("SDF shapes".into())),
+        ShapeEnum::Disk(_) => return Err(GpuError::Unsupported("disks".into())),
+        ShapeEnum::Heightfield(_) => return Err(GpuError::Unsupported("heightfields".into())),
+        ShapeEnum::Instance(_) => return Err(GpuError::Unsupported("instances".into())),
+    };
+    push(&mut words, &points);
+    Ok(words)
+}
+
+/// Flatten the material and texture of an object, as seen in the middle of its texel
+/// coordinates.
+fn material(object: &Object) -> Vec<u32> {
+    let texel = Point2D::new(0.5, 0.5);
+    let properties = object.material.properties(texel);
+    let diffuse = properties.diffuse * object.texture.texel_color(texel);
+    let emission = object.material.emission(texel);
+    // Refracting materials have a diffraction index, the reflecting ones do not
+    let (coef, index) = match properties.refl_trans {
+        Some(ReflTransEnum::Transparency { coef, index }) => (coef, index),
+        Some(ReflTransEnum::Reflectivity { coef }) => (coef, 0.),
+        None => (0., 0.),
+    };
+    let mut words = Vec::with_capacity(8);
+    push(&mut words, &[diffuse.r, diffuse.g, diffuse.b, coef]);
+    push(&mut words, &[emission.r, emission.g, emission.b, index]);
+    words
+}
+
+/// Sample the background's color at the middle of each cell of a grid of latitudes and
+/// longitudes, the poles being along the Y axis.
+fn sample_background(scene: &Scene) -> Vec<u32> {
+    let mut words = Vec::new();
+    for row in 0..BACKGROUND_HEIGHT {
+        let theta = (row as Float + 0.5) / BACKGROUND_HEIGHT as Float * PI;
+        for column in 0..BACKGROUND_WIDTH {
+            let phi = (column as Float + 0.5) / BACKGROUND_WIDTH as Float * 2. * PI;
+            let direction = Vector::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            let color = scene.background.color(&Unit::new_normalize(direction));
+            push(&mut words, &[color.r, color.g, color.b, 0.]);
+        }
+    }
+    words
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::LightProperties;
+    use crate::light::{PointLight, SpotLight};
+    use crate::material::UniformMaterial;
+    use crate::render::LightAggregate;
+    use crate::shape::{Plane, Sphere};
+    use crate::texture::UniformTexture;
+
+    fn object(shape: ShapeEnum, color: LinearColor) -> Object {
+        Object::new(
+            shape,
+            UniformMaterial::new(LightProperties::new(color, LinearColor::black(), None)).into(),
+            UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+        )
+    }
+
+    fn scene(lights: LightAggregate) -> Scene {
+        let red = LinearColor::new(1., 0., 0.);
+        Scene::new(
+            Camera::default(),
+            lights,
+            vec![
+                object(Plane::new(Point::origin(), Vector::y()).into(), red.clone()),
+                object(Sphere::new(Point::new(0., 1., 0.), 1.).into(), red.clone()),
+                object(Sphere::new(Point::new(0., 1., 3.), 1.).into(), red),
+                object(
+                    Sphere::new(Point::new(3., 1., 0.), 1.).into(),
+                    LinearColor::new(0., 0., 1.),
+                ),
+            ],
+            LinearColor::new(0.5, 0.5, 0.5).into(),
+            4,
+            3,
+            1.,
+        )
+    }
+
+    #[test]
+    fn flattening_works() {
+        let light = PointLight::new(Point::new(0., 5., 0.), LinearColor::new(1., 1., 1.));
+        let scene = scene(LightAggregate::new(vec![], vec![], vec![light], vec![]));
+        let flat = GpuScene::new(&scene, &Camera::default(), (0, 0, 1080, 1080), 42).unwrap();
+        assert_eq!((flat.width, flat.height, flat.samples), (1080, 1080, 4));
+        // The plane is not part of the BVH, and comes last
+        assert_eq!(flat.primitives.len(), 4 * 16);
+        assert_eq!(flat.primitives[3 * 16], PLANE);
+        let mut indices = flat.indices.clone();
+        indices.sort_unstable();
+        assert_eq!(indices, vec![0, 1, 2]);
+        // The red objects share their material
+        assert_eq!(flat.materials.len(), 2 * 8);
+        assert_eq!(flat.primitives[1], flat.primitives[16 + 1]);
+        assert_eq!(flat.lights.len(), 8);
+        assert_eq!(f32::from_bits(flat.lights[4 + 3]), 2.);
+        let size = BACKGROUND_WIDTH * BACKGROUND_HEIGHT * 4;
+        assert_eq!(flat.background.len(), size as usize);
+        assert_eq!(f32::from_bits(flat.background[0]), 0.5);
+    }
+
+    #[test]
+    fn nodes_are_finite() {
+        let scene = scene(LightAggregate::empty());
+        let flat = GpuScene::new(&scene, &Camera::default(), (0, 0, 10, 10), 42).unwrap();
+        assert_eq!(flat.nodes.len(), scene.bvh.nodes().len() * 32);
+        for node in flat.nodes.chunks(32) {
+            assert!(node[..24]
+                .iter()
+                .all(|&word| f32::from_bits(word).is_finite()));
+            // Unused children are empty leaves
+            for lane in 0..4 {
+                if node[24 + lane] == 0 && node[28 + lane] == 0 {
+                    continue;
+                }
+                assert!(node[28 + lane] == INTERNAL || node[28 + lane] > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn params_change_with_the_sample() {
+        let scene = scene(LightAggregate::empty());
+        let flat = GpuScene::new(&scene, &Camera::default(), (0, 0, 10, 10), 42).unwrap();
+        let (first, second) = (flat.params(0), flat.params(1));
+        assert_eq!(first.len(), 8 * 4);
+        assert_eq!((first[2], second[2]), (0, 1));
+        assert_eq!(first[3], second[3]);
+        // The bounce limit, the nodes, and the primitives
+        assert_eq!(first[4], 3);
+        assert_eq!(&first[6..8], &[3, 4]);
+    }
+
+    #[test]
+    fn unsupported_lights_are_rejected() {
+        let spot = SpotLight::degrees_new(
+            Point::origin(),
+            Vector::y_axis(),
+            90.,
+            LinearColor::new(1., 1., 1.),
+        );
+        let scene = scene(LightAggregate::new(vec![], vec![], vec![], vec![spot]));
+        let err = GpuScene::new(&scene, &Camera::default(), (0, 0, 10, 10), 42).unwrap_err();
+        assert!(matches!(err, GpuError::Unsupported(_)));
+    }
+}
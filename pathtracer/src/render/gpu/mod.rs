@@ -0,0 +1,46 @@
+//! An experimental path tracer running on the GPU, for fast previews
+//!
+//! The scene is flattened into plain buffers: the nodes of its BVH, its primitives, a table of
+//! materials, its lights, and a table sampling its background. They are uploaded once, then a
+//! wavefront path tracer runs over them: each stage of the paths' lives (generating the camera
+//! rays, extending them to their next hit, shading that hit) is a kernel run for every path at
+//! once, keeping their state in a buffer between stages.
+//!
+//! Only a subset of the scenes can be rendered this way, as an approximation of what is
+//! rendered on the CPU:
+//! * spheres, triangles, and planes, whose normals are not interpolated nor bumped,
+//! * materials and textures with a single color over their whole surface, taken at the middle
+//!   of their texel coordinates, and which either reflect or refract the specular part of the
+//!   light,
+//! * ambient, directional, and point lights, which light every object.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+mod device;
+pub(crate) use device::*;
+
+mod layout;
+pub(crate) use layout::*;
+
+/// Why a scene could not be rendered on the GPU.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GpuError {
+    /// No GPU could be used, or it failed while rendering.
+    Unavailable(String),
+    /// The scene uses a feature which cannot be rendered on the GPU.
+    Unsupported(String),
+}
+
+impl Display for GpuError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GpuError::Unavailable(reason) => write!(f, "no GPU can be used: {}", reason),
+            GpuError::Unsupported(feature) => {
+                write!(f, "the GPU renderer does not support {}", feature)
+            }
+        }
+    }
+}
+
+impl Error for GpuError {}
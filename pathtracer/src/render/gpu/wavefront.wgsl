@@ -0,0 +1,420 @@
+// A wavefront path tracer: each entry point is a stage of the paths' lives, run for every pixel's
+// path at once, the state of the paths being kept in a buffer between stages.
+//
+// The layout of the buffers is written by `layout.rs`, both must be kept in sync.
+
+struct Params {
+    // The width and height of the image, the index of the sample, and the seed
+    size: vec4<u32>,
+    // The bounce limit, the number of nodes, of bounded primitives, and of primitives
+    counts: vec4<u32>,
+    // The number of lights, and whether the pixels are anti-aliased
+    settings: vec4<u32>,
+    // The origin of the camera
+    origin: vec4<f32>,
+    // The point of the film at the top left corner of the image
+    corner: vec4<f32>,
+    // The steps between two pixels of the film, towards the right and the bottom of the image
+    right: vec4<f32>,
+    down: vec4<f32>,
+    // The ambient light, and the diffraction index of the scene
+    ambient: vec4<f32>,
+}
+
+struct Node {
+    // The bounds of the four children of the node
+    low_x: vec4<f32>,
+    low_y: vec4<f32>,
+    low_z: vec4<f32>,
+    high_x: vec4<f32>,
+    high_y: vec4<f32>,
+    high_z: vec4<f32>,
+    // For each child, the index of a node, or the first position of a leaf
+    first: vec4<u32>,
+    // For each child, INTERNAL for a node, or the number of primitives of a leaf
+    count: vec4<u32>,
+}
+
+struct Primitive {
+    // The kind of the primitive, its material, and its flags
+    header: vec4<u32>,
+    // The center and radius of a sphere, the corners of a triangle, or the point and normal of
+    // a plane
+    a: vec4<f32>,
+    b: vec4<f32>,
+    c: vec4<f32>,
+}
+
+struct Material {
+    // The diffuse color, and the part of the light which is reflected or refracted instead
+    diffuse: vec4<f32>,
+    // The emitted light, and the diffraction index of refracting materials
+    emission: vec4<f32>,
+}
+
+struct Light {
+    // The position of a point light, or the direction towards a directional light, and its kind
+    position: vec4<f32>,
+    // The light received at a unit distance, and the exponent of its falloff
+    color: vec4<f32>,
+}
+
+struct Path {
+    // The origin of the path's current ray, and the distance to its next hit
+    origin: vec4<f32>,
+    direction: vec4<f32>,
+    throughput: vec4<f32>,
+    radiance: vec4<f32>,
+    // The random state, whether the path is alive, its depth, and the primitive it hits next
+    state: vec4<u32>,
+}
+
+struct Hit {
+    distance: f32,
+    primitive: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> nodes: array<Node>;
+@group(0) @binding(2) var<storage, read> indices: array<u32>;
+@group(0) @binding(3) var<storage, read> primitives: array<Primitive>;
+@group(0) @binding(4) var<storage, read> materials: array<Material>;
+@group(0) @binding(5) var<storage, read> lights: array<Light>;
+@group(0) @binding(6) var<storage, read> background: array<vec4<f32>>;
+@group(0) @binding(7) var<storage, read_write> paths: array<Path>;
+@group(0) @binding(8) var<storage, read_write> output: array<vec4<f32>>;
+
+const INTERNAL: u32 = 0xffffffffu;
+const NONE: u32 = 0xffffffffu;
+
+const SPHERE: u32 = 0u;
+const TRIANGLE: u32 = 1u;
+const PLANE: u32 = 2u;
+
+const INVERTED: u32 = 1u;
+const EMITS_BOTH_SIDES: u32 = 2u;
+
+const POINT_LIGHT: f32 = 0.0;
+
+const BACKGROUND_WIDTH: u32 = 64u;
+const BACKGROUND_HEIGHT: u32 = 32u;
+
+// The depth of the stack of nodes left to visit, which is enough for any balanced tree
+const STACK_SIZE: u32 = 64u;
+
+const FAR: f32 = 3.0e38;
+const EPSILON: f32 = 1e-4;
+const PI: f32 = 3.14159265;
+
+// Hash a word, with the PCG hash.
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Get a random float in [0, 1), updating the random state.
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = hash(*state);
+    return f32(*state >> 8u) / 16777216.0;
+}
+
+// Get the index of the path of the invocation's pixel, or NONE if it is outside of the image.
+fn path_index(id: vec3<u32>) -> u32 {
+    if id.x >= params.size.x || id.y >= params.size.y {
+        return NONE;
+    }
+    return id.y * params.size.x + id.x;
+}
+
+// Get the distance along the ray to the primitive, or FAR if it is missed.
+fn intersect(index: u32, origin: vec3<f32>, direction: vec3<f32>) -> f32 {
+    let primitive = primitives[index];
+    let a = primitive.a.xyz;
+    switch primitive.header.x {
+        case SPHERE: {
+            let delta = a - origin;
+            let tca = dot(direction, delta);
+            let d2 = dot(delta, delta) - tca * tca;
+            let r2 = primitive.a.w * primitive.a.w;
+            if d2 > r2 {
+                return FAR;
+            }
+            let thc = sqrt(r2 - d2);
+            if tca - thc > EPSILON {
+                return tca - thc;
+            }
+            if tca + thc > EPSILON {
+                return tca + thc;
+            }
+            return FAR;
+        }
+        case TRIANGLE: {
+            let ab = primitive.b.xyz - a;
+            let ac = primitive.c.xyz - a;
+            let p = cross(direction, ac);
+            let det = dot(ab, p);
+            if abs(det) < 1e-12 {
+                return FAR;
+            }
+            let t = origin - a;
+            let u = dot(t, p) / det;
+            let q = cross(t, ab);
+            let v = dot(direction, q) / det;
+            let distance = dot(ac, q) / det;
+            if u < 0.0 || v < 0.0 || u + v > 1.0 || distance <= EPSILON {
+                return FAR;
+            }
+            return distance;
+        }
+        case PLANE: {
+            let normal = primitive.b.xyz;
+            let cosine = dot(normal, direction);
+            if abs(cosine) < 1e-12 {
+                return FAR;
+            }
+            let distance = dot(a - origin, normal) / cosine;
+            return select(FAR, distance, distance > EPSILON);
+        }
+        default: {
+            return FAR;
+        }
+    }
+}
+
+// Keep the primitive as the closest hit if the ray hits it before the current one.
+fn test(index: u32, origin: vec3<f32>, direction: vec3<f32>, hit: ptr<function, Hit>) {
+    let distance = intersect(index, origin, direction);
+    if distance < (*hit).distance {
+        *hit = Hit(distance, index);
+    }
+}
+
+// Find the closest primitive hit by the ray before `max_distance`, walking the BVH.
+fn closest_hit(origin: vec3<f32>, direction: vec3<f32>, max_distance: f32) -> Hit {
+    var hit = Hit(max_distance, NONE);
+    // Avoid dividing by zero for directions parallel to an axis
+    let safe = select(direction, vec3<f32>(1e-12), abs(direction) < vec3<f32>(1e-12));
+    let inv = 1.0 / safe;
+    var stack: array<u32, STACK_SIZE>;
+    var top = 0u;
+    if params.counts.y > 0u {
+        stack[0] = 0u;
+        top = 1u;
+    }
+    while top > 0u {
+        top -= 1u;
+        let node = nodes[stack[top]];
+        let x0 = (node.low_x - origin.x) * inv.x;
+        let x1 = (node.high_x - origin.x) * inv.x;
+        let y0 = (node.low_y - origin.y) * inv.y;
+        let y1 = (node.high_y - origin.y) * inv.y;
+        let z0 = (node.low_z - origin.z) * inv.z;
+        let z1 = (node.high_z - origin.z) * inv.z;
+        let near = max(max(min(x0, x1), min(y0, y1)), max(min(z0, z1), vec4<f32>(0.0)));
+        let far = min(min(max(x0, x1), max(y0, y1)), min(max(z0, z1), vec4<f32>(hit.distance)));
+        for (var lane = 0u; lane < 4u; lane++) {
+            if near[lane] > far[lane] {
+                continue;
+            }
+            let first = node.first[lane];
+            let count = node.count[lane];
+            if count == INTERNAL {
+                if top < STACK_SIZE {
+                    stack[top] = first;
+                    top += 1u;
+                }
+                continue;
+            }
+            for (var position = first; position < first + count; position++) {
+                test(indices[position], origin, direction, &hit);
+            }
+        }
+    }
+    // The primitives which are not bounded are not part of the BVH
+    for (var index = params.counts.z; index < params.counts.w; index++) {
+        test(index, origin, direction, &hit);
+    }
+    return hit;
+}
+
+// Get the normal of the primitive's front at the given point.
+fn normal_at(index: u32, point: vec3<f32>) -> vec3<f32> {
+    let primitive = primitives[index];
+    switch primitive.header.x {
+        case SPHERE: {
+            let normal = normalize(point - primitive.a.xyz);
+            return select(normal, -normal, (primitive.header.z & INVERTED) != 0u);
+        }
+        case TRIANGLE: {
+            let a = primitive.a.xyz;
+            return normalize(cross(primitive.b.xyz - a, primitive.c.xyz - a));
+        }
+        default: {
+            return primitive.b.xyz;
+        }
+    }
+}
+
+// Get the color of the background in the given direction.
+fn background_color(direction: vec3<f32>) -> vec3<f32> {
+    let theta = acos(clamp(direction.y, -1.0, 1.0));
+    var phi = atan2(direction.z, direction.x);
+    if phi < 0.0 {
+        phi += 2.0 * PI;
+    }
+    let row = min(u32(theta / PI * f32(BACKGROUND_HEIGHT)), BACKGROUND_HEIGHT - 1u);
+    let column = min(u32(phi / (2.0 * PI) * f32(BACKGROUND_WIDTH)), BACKGROUND_WIDTH - 1u);
+    return background[row * BACKGROUND_WIDTH + column].xyz;
+}
+
+// Sample a direction around the normal, with a density proportional to its cosine.
+fn sample_cosine(normal: vec3<f32>, u: f32, v: f32) -> vec3<f32> {
+    let helper = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(normal.x) > 0.9);
+    let tangent = normalize(cross(normal, helper));
+    let bitangent = cross(normal, tangent);
+    let radius = sqrt(u);
+    let angle = 2.0 * PI * v;
+    let height = sqrt(max(0.0, 1.0 - u));
+    return normalize((tangent * cos(angle) + bitangent * sin(angle)) * radius + normal * height);
+}
+
+// Start the path of each pixel from the camera.
+@compute @workgroup_size(8, 8)
+fn generate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = path_index(id);
+    if index == NONE {
+        return;
+    }
+    var state = hash(index ^ hash(params.size.z ^ hash(params.size.w)));
+    var offset = vec2<f32>(0.5);
+    if params.settings.y != 0u {
+        offset = vec2<f32>(random(&state), random(&state));
+    }
+    let x = f32(id.x) + offset.x;
+    let y = f32(id.y) + offset.y;
+    let pixel = params.corner.xyz + params.right.xyz * x + params.down.xyz * y;
+    let direction = normalize(pixel - params.origin.xyz);
+    paths[index] = Path(
+        vec4<f32>(pixel, 0.0),
+        vec4<f32>(direction, 0.0),
+        vec4<f32>(1.0),
+        vec4<f32>(0.0),
+        vec4<u32>(state, 1u, 0u, NONE),
+    );
+}
+
+// Find the next hit of each path which is still alive.
+@compute @workgroup_size(8, 8)
+fn extend(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = path_index(id);
+    if index == NONE || paths[index].state.y == 0u {
+        return;
+    }
+    var path = paths[index];
+    let hit = closest_hit(path.origin.xyz, path.direction.xyz, FAR);
+    path.origin.w = hit.distance;
+    path.state.w = hit.primitive;
+    paths[index] = path;
+}
+
+// Gather the light reaching each path at its hit, then bounce it off the surface.
+@compute @workgroup_size(8, 8)
+fn shade(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = path_index(id);
+    if index == NONE || paths[index].state.y == 0u {
+        return;
+    }
+    var path = paths[index];
+    var state = path.state.x;
+    let direction = path.direction.xyz;
+    let throughput = path.throughput.xyz;
+    if path.state.w == NONE {
+        path.radiance += vec4<f32>(throughput * background_color(direction), 0.0);
+        path.state.y = 0u;
+        paths[index] = path;
+        return;
+    }
+
+    let primitive = primitives[path.state.w];
+    let material = materials[primitive.header.y];
+    let point = path.origin.xyz + direction * path.origin.w;
+    let front = normal_at(path.state.w, point);
+    let back_face = dot(direction, front) > 0.0;
+    // The side of the surface facing the ray is the one being shaded
+    let normal = select(front, -front, back_face);
+    var radiance = vec3<f32>(0.0);
+    if !back_face || (primitive.header.z & EMITS_BOTH_SIDES) != 0u {
+        radiance += material.emission.xyz;
+    }
+
+    // The direct lighting is only received by the diffuse part of the material
+    let coef = material.diffuse.w;
+    let albedo = material.diffuse.xyz;
+    var received = params.ambient.xyz;
+    let start = point + normal * EPSILON;
+    for (var i = 0u; i < params.settings.x; i++) {
+        let light = lights[i];
+        var towards = light.position.xyz;
+        var distance = FAR;
+        var color = light.color.xyz;
+        if light.position.w == POINT_LIGHT {
+            let delta = light.position.xyz - point;
+            distance = length(delta);
+            towards = delta / distance;
+            color /= pow(distance, light.color.w);
+        }
+        let cosine = dot(normal, towards);
+        if cosine > 0.0 && closest_hit(start, towards, distance).primitive == NONE {
+            received += color * cosine;
+        }
+    }
+    radiance += albedo * received * (1.0 - coef);
+    path.radiance += vec4<f32>(throughput * radiance, 0.0);
+
+    if path.state.z >= params.counts.x {
+        path.state.y = 0u;
+        paths[index] = path;
+        return;
+    }
+    // Choose between the specular and diffuse parts proportionally to their weight
+    var next = reflect(direction, normal);
+    var weight = vec3<f32>(1.0);
+    if random(&state) < coef {
+        let diffraction_index = material.emission.w;
+        if diffraction_index > 0.0 {
+            let ratio = select(
+                params.ambient.w / diffraction_index,
+                diffraction_index / params.ambient.w,
+                back_face,
+            );
+            // Light is reflected instead of refracted past the critical angle
+            let refracted = refract(direction, normal, ratio);
+            if dot(refracted, refracted) > 0.0 {
+                next = refracted;
+            }
+        }
+    } else {
+        let u = random(&state);
+        let v = random(&state);
+        next = sample_cosine(normal, u, v);
+        weight = albedo;
+    }
+    let side = select(normal, -normal, dot(next, normal) < 0.0);
+    path.origin = vec4<f32>(point + side * EPSILON, 0.0);
+    path.direction = vec4<f32>(next, 0.0);
+    path.throughput = vec4<f32>(throughput * weight, 0.0);
+    path.state.x = state;
+    path.state.z += 1u;
+    paths[index] = path;
+}
+
+// Add the light gathered by each path to its pixel, clamped like the samples of the CPU renderer.
+@compute @workgroup_size(8, 8)
+fn accumulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = path_index(id);
+    if index == NONE {
+        return;
+    }
+    output[index] += min(paths[index].radiance, vec4<f32>(1.0));
+}
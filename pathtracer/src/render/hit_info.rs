@@ -0,0 +1,21 @@
+//! Information about a ray cast into the scene
+
+use crate::{Float, Point, Point2D, Vector};
+use nalgebra::Unit;
+
+/// The information about the closest object hit by a ray cast into a [`Scene`].
+///
+/// [`Scene`]: ../scene/struct.Scene.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct HitInfo<'a> {
+    /// The name of the object which was hit, if it has one.
+    pub name: Option<&'a str>,
+    /// The distance along the ray at which the object was hit.
+    pub distance: Float,
+    /// The point at which the object was hit.
+    pub point: Point,
+    /// The normal of the object's surface at that point.
+    pub normal: Unit<Vector>,
+    /// The texel coordinates of that point on the object's surface.
+    pub uv: Point2D,
+}
@@ -0,0 +1,199 @@
+use super::Integrator;
+use crate::core::LinearColor;
+use crate::render::utils::heat;
+use crate::render::{LightContributions, Object, Scene};
+use crate::shape::Hit;
+use crate::{Float, Vector};
+use beevee::ray::Ray;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+
+fn default_max_distance() -> Float {
+    10.
+}
+
+fn default_max_tests() -> u32 {
+    64
+}
+
+/// The quantity shown by a [`DebugIntegrator`].
+///
+/// [`DebugIntegrator`]: struct.DebugIntegrator.html
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum DebugMode {
+    /// The surface normal, each coordinate mapped from `[-1, 1]` to a color channel.
+    Normal,
+    /// The texel coordinates, in the red and green channels.
+    Uv,
+    /// The distance to the camera, from white up close to black at `max_distance`.
+    Depth {
+        /// The distance shown in black.
+        #[serde(default = "default_max_distance")]
+        max_distance: Float,
+    },
+    /// The number of ray-object intersection tests made to find the hit, from blue to red at
+    /// `max_tests`, showing the efficiency of the BVH.
+    Cost {
+        /// The number of tests shown in red.
+        #[serde(default = "default_max_tests")]
+        max_tests: u32,
+    },
+}
+
+/// An integrator shading each pixel with a false color, showing a property of the surface seen
+/// by the camera to help track down geometry, projection or acceleration issues.
+///
+/// Rays which do not hit anything are black, except in the `Cost` mode.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DebugIntegrator {
+    #[serde(flatten)]
+    mode: DebugMode,
+}
+
+impl DebugIntegrator {
+    /// Creates a new `DebugIntegrator`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::render::integrator::{DebugIntegrator, DebugMode};
+    /// #
+    /// let normals = DebugIntegrator::new(DebugMode::Normal);
+    /// let depth = DebugIntegrator::new(DebugMode::Depth { max_distance: 20.0 });
+    /// ```
+    pub fn new(mode: DebugMode) -> Self {
+        DebugIntegrator { mode }
+    }
+}
+
+impl Integrator for DebugIntegrator {
+    fn radiance_from_hit(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        hit: Option<(Hit, &Object)>,
+        _: &mut dyn RngCore,
+        _: &mut LightContributions,
+    ) -> LinearColor {
+        match (&self.mode, hit.map(|(hit, _)| hit)) {
+            (DebugMode::Cost { max_tests }, _) => {
+                // The tests are only counted when casting the ray on its own, not in a packet
+                let tests = Cell::new(0);
+                scene.cast_ray_counting(ray, 0, &tests);
+                heat(tests.get() as Float / (*max_tests).max(1) as Float)
+            }
+            (_, None) => LinearColor::black(),
+            (DebugMode::Normal, Some(hit)) => {
+                let color = (hit.normal.into_inner() + Vector::new(1., 1., 1.)) / 2.;
+                LinearColor::new(color.x, color.y, color.z)
+            }
+            (DebugMode::Uv, Some(hit)) => LinearColor::new(hit.uv.x, hit.uv.y, 0.),
+            (DebugMode::Depth { max_distance }, Some(hit)) => {
+                let value = (1. - hit.distance / max_distance).max(0.);
+                LinearColor::new(value, value, value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{Camera, LightProperties};
+    use crate::material::UniformMaterial;
+    use crate::render::{LightAggregate, Object};
+    use crate::shape::Sphere;
+    use crate::texture::UniformTexture;
+    use crate::Point;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn simple_scene() -> Scene {
+        Scene::new(
+            Camera::default(),
+            LightAggregate::empty(),
+            vec![Object::new(
+                Sphere::new(Point::origin(), 1.).into(),
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(0.5, 0.5, 0.5),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )],
+            LinearColor::new(1., 1., 1.).into(),
+            0,
+            0,
+            1.,
+        )
+    }
+
+    fn radiance(mode: DebugMode, ray: Ray) -> LinearColor {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut lights = LightContributions::untracked();
+        DebugIntegrator::new(mode).radiance(&simple_scene(), ray, &mut rng, &mut lights)
+    }
+
+    #[test]
+    fn new_works() {
+        let integrator = DebugIntegrator::new(DebugMode::Normal);
+        assert_eq!(
+            integrator,
+            DebugIntegrator {
+                mode: DebugMode::Normal
+            }
+        )
+    }
+
+    #[test]
+    fn normal_works() {
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        assert_eq!(
+            radiance(DebugMode::Normal, ray),
+            LinearColor::new(0., 0.5, 0.5)
+        );
+        let miss = Ray::new(Point::new(-5., 5., 0.), Vector::x_axis());
+        assert_eq!(radiance(DebugMode::Normal, miss), LinearColor::black());
+    }
+
+    #[test]
+    fn depth_works() {
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        let depth = radiance(DebugMode::Depth { max_distance: 8. }, ray);
+        assert_eq!(depth, LinearColor::new(0.5, 0.5, 0.5));
+        let depth = radiance(DebugMode::Depth { max_distance: 2. }, ray);
+        assert_eq!(depth, LinearColor::black());
+    }
+
+    #[test]
+    fn cost_works() {
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        // A single test against the only sphere
+        let cost = radiance(DebugMode::Cost { max_tests: 2 }, ray);
+        assert_eq!(cost, LinearColor::new(0., 1., 0.));
+        let cost = radiance(DebugMode::Cost { max_tests: 1 }, ray);
+        assert_eq!(cost, LinearColor::new(1., 0., 0.));
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = "mode: uv";
+        let integrator: DebugIntegrator = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(integrator, DebugIntegrator::new(DebugMode::Uv));
+        let yaml = "mode: depth";
+        let integrator: DebugIntegrator = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            integrator,
+            DebugIntegrator::new(DebugMode::Depth { max_distance: 10. })
+        );
+        let yaml = "{mode: cost, max_tests: 16}";
+        let integrator: DebugIntegrator = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            integrator,
+            DebugIntegrator::new(DebugMode::Cost { max_tests: 16 })
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Various integrator implementations
+
+use super::{LightContributions, Object, Scene};
+use crate::core::LinearColor;
+use crate::shape::Hit;
+use beevee::ray::Ray;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+/// All the existing `Integrator` implementation.
+#[serde(tag = "type")]
+#[allow(missing_docs)]
+#[enum_dispatch::enum_dispatch]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum IntegratorEnum {
+    #[serde(rename = "raytrace")]
+    Raytracer,
+    #[serde(rename = "pathtrace")]
+    Pathtracer,
+    #[serde(rename = "debug")]
+    DebugIntegrator,
+}
+
+/// Represent the algorithm used to compute the light travelling back along a camera ray.
+#[enum_dispatch::enum_dispatch(IntegratorEnum)]
+pub trait Integrator: std::fmt::Debug {
+    /// Get the color of the light reaching the ray's origin, coming from its direction.
+    ///
+    /// The part of it coming from each of the scene's named lights is added to `lights`.
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        let hit = scene.cast_ray(ray, 0);
+        self.radiance_from_hit(scene, ray, hit, rng, lights)
+    }
+
+    /// Like `radiance`, given the ray's closest hit as found by `Scene::cast_ray`, e.g: when the
+    /// ray was cast in a packet with the other samples of its pixel.
+    fn radiance_from_hit(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        hit: Option<(Hit, &Object)>,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor;
+}
+
+impl Default for IntegratorEnum {
+    fn default() -> Self {
+        Raytracer::new().into()
+    }
+}
+
+mod debug;
+pub use debug::*;
+
+mod pathtracer;
+pub use self::pathtracer::*;
+
+mod raytracer;
+pub use raytracer::*;
@@ -0,0 +1,481 @@
+use super::Integrator;
+use crate::background::Background;
+use crate::core::{LinearColor, ReflTransEnum};
+use crate::material::{Bsdf, Material};
+use crate::render::utils::{
+    offset_origin, reflected, refracted, sample_hemisphere, RefractionInfo,
+};
+use crate::render::{LightContributions, Object, Scene};
+use crate::shape::Hit;
+use crate::texture::Texture;
+use crate::{Float, Vector};
+use beevee::ray::Ray;
+use nalgebra::Unit;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// A unidirectional path tracer, sampling a single bounce direction at each intersection.
+///
+/// Paths are terminated at random with Russian roulette once they reach `roulette_depth`
+/// bounces, with a survival probability depending on the light they can still carry. The scene's
+/// reflection limit is kept as a hard limit on the length of a path.
+///
+/// Caustic paths, reaching a light through near-specular surfaces after a diffuse bounce, are
+/// rarely found and produce a lot of noise. They can be regularized by giving a minimum
+/// roughness to the physically based materials hit after the first glossy or diffuse bounce,
+/// slightly blurring their highlights.
+///
+/// The remaining bright speckles can be suppressed by clamping the light gathered after the first
+/// bounce, so that none of its channels exceed `indirect_clamp`, at the cost of darkening the
+/// brightest indirect lighting.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Pathtracer {
+    #[serde(default = "default_roulette_depth")]
+    roulette_depth: u32,
+    #[serde(default)]
+    regularization: Option<Float>,
+    #[serde(default)]
+    indirect_clamp: Option<Float>,
+}
+
+fn default_roulette_depth() -> u32 {
+    3
+}
+
+impl Pathtracer {
+    /// Creates a new `Pathtracer`, starting Russian roulette after the given number of bounces.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::render::integrator::Pathtracer;
+    /// #
+    /// let pathtracer = Pathtracer::new(3);
+    /// ```
+    pub fn new(roulette_depth: u32) -> Self {
+        Pathtracer {
+            roulette_depth,
+            regularization: None,
+            indirect_clamp: None,
+        }
+    }
+
+    /// Regularize indirect bounces, using at least the given roughness for their BSDFs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::render::integrator::Pathtracer;
+    /// #
+    /// let pathtracer = Pathtracer::new(3).with_regularization(0.3);
+    /// ```
+    pub fn with_regularization(mut self, min_roughness: Float) -> Self {
+        self.regularization = Some(min_roughness);
+        self
+    }
+
+    /// Clamp the light gathered after the first bounce, so that none of its channels exceed
+    /// `max`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::render::integrator::Pathtracer;
+    /// #
+    /// let pathtracer = Pathtracer::new(3).with_indirect_clamp(10.0);
+    /// ```
+    pub fn with_indirect_clamp(mut self, max: Float) -> Self {
+        self.indirect_clamp = Some(max);
+        self
+    }
+
+    /// Get the factor by which light gathered at the given depth is scaled down to be clamped.
+    fn clamp_factor(&self, contribution: &LinearColor, depth: u32) -> Float {
+        let brightest = contribution.r.max(contribution.g).max(contribution.b);
+        match self.indirect_clamp.filter(|_| depth > 0) {
+            Some(max) if brightest > max => max / brightest,
+            _ => 1.,
+        }
+    }
+}
+
+impl Default for Pathtracer {
+    fn default() -> Self {
+        Self::new(default_roulette_depth())
+    }
+}
+
+impl Integrator for Pathtracer {
+    fn radiance_from_hit(
+        &self,
+        scene: &Scene,
+        mut ray: Ray,
+        hit: Option<(Hit, &Object)>,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        let mut first_hit = Some(hit);
+        let mut indices = RefractionInfo::with_index(scene.diffraction_index);
+        let mut throughput = LinearColor::new(1., 1., 1.);
+        let mut radiance = LinearColor::black();
+        // Whether the path was scattered by a non-specular interaction
+        let mut scattered = false;
+        // Whether the last bounce was a non-specular one, whose direct lighting was sampled
+        let mut sampled_lighting = false;
+
+        for depth in 0..=scene.reflection_limit {
+            let closest = first_hit
+                .take()
+                .unwrap_or_else(|| scene.cast_ray(ray, depth));
+            let (hit, object) = match closest {
+                Some(res) => res,
+                // A sky sampled as a light was already accounted for by the direct lighting
+                None if sampled_lighting && scene.lights.sky_light().is_some() => break,
+                None => {
+                    let background = throughput * scene.background.color(&ray.direction);
+                    radiance += background.clone() * self.clamp_factor(&background, depth);
+                    break;
+                }
+            };
+            // Light is absorbed along the way when travelling inside of a medium
+            throughput *= indices.attenuation(hit.distance);
+            let point = ray.origin + ray.direction.as_ref() * hit.distance;
+            let surface = object.surface_point(&ray, &hit);
+            let object_color = object.texture.surface_color(&surface);
+            let material = scene.material_at(object, &ray, &hit, depth, rng);
+            if object.sides.emits_towards(&hit) {
+                let emitted = throughput.clone() * material.emission(hit.uv);
+                radiance += emitted.clone() * self.clamp_factor(&emitted, depth);
+            }
+            let direction = match material.bsdf(hit.uv, &hit.normal, &object_color) {
+                Some(mut bsdf) => {
+                    if let Some(min_roughness) = self.regularization.filter(|_| scattered) {
+                        bsdf.regularize(min_roughness);
+                    }
+                    let outgoing = -ray.direction;
+                    let contribution = |lum: LinearColor, incoming: &Unit<Vector>| {
+                        lum * bsdf.eval(&outgoing, incoming) * hit.normal.dot(incoming).abs()
+                    };
+                    let mut bounce_lights = lights.empty_like();
+                    let ambient = scene.illuminate_ambient(
+                        object,
+                        &ray,
+                        object_color,
+                        &hit,
+                        depth,
+                        rng,
+                        &mut bounce_lights,
+                    );
+                    let direct = scene.direct_lighting(
+                        object,
+                        &ray,
+                        &hit,
+                        depth,
+                        rng,
+                        &contribution,
+                        &mut bounce_lights,
+                    );
+                    let gathered = throughput.clone() * (ambient + direct);
+                    let factor = self.clamp_factor(&gathered, depth);
+                    *lights += bounce_lights * (throughput.clone() * factor);
+                    radiance += gathered * factor;
+
+                    if depth == scene.reflection_limit {
+                        break;
+                    }
+
+                    match bsdf.sample(&outgoing, rng.gen()) {
+                        Some(sample) => {
+                            throughput *= sample.weight(&hit.normal);
+                            scattered |= !sample.is_delta;
+                            sampled_lighting = !sample.is_delta;
+                            let cos_o = outgoing.dot(&hit.normal);
+                            if cos_o * sample.incoming.dot(&hit.normal) < 0. {
+                                // Transmitted through the surface, entering or leaving the medium
+                                indices.absorption = if cos_o > 0. {
+                                    material.absorption(hit.uv)
+                                } else {
+                                    None
+                                };
+                            }
+                            sample.incoming
+                        }
+                        None => break,
+                    }
+                }
+                None => {
+                    let properties = material.properties(hit.uv);
+                    let reflected_ray = reflected(ray.direction, hit.normal);
+
+                    // The direct lighting is only received by the diffuse part of the material
+                    let coef = match properties.refl_trans {
+                        Some(ReflTransEnum::Transparency { coef, .. }) => coef,
+                        Some(ReflTransEnum::Reflectivity { coef }) => coef,
+                        None => 0.,
+                    };
+                    let mut bounce_lights = lights.empty_like();
+                    let lighting = scene.illuminate(
+                        object,
+                        &ray,
+                        object_color.clone(),
+                        &material,
+                        &properties,
+                        &hit,
+                        depth,
+                        rng,
+                        &mut bounce_lights,
+                    );
+                    let gathered = throughput.clone() * lighting * (1. - coef);
+                    let factor = self.clamp_factor(&gathered, depth);
+                    *lights += bounce_lights * throughput.clone() * ((1. - coef) * factor);
+                    radiance += gathered * factor;
+
+                    if depth == scene.reflection_limit {
+                        break;
+                    }
+
+                    // Choose between the specular and diffuse parts proportionally to their weight
+                    sampled_lighting = false;
+                    if rng.gen::<Float>() < coef {
+                        match properties.refl_trans {
+                            Some(ReflTransEnum::Transparency { index, .. }) => {
+                                let mut new_indices = indices.clone();
+                                match refracted(ray.direction, hit.normal, &mut new_indices, index)
+                                {
+                                    Some((refracted, refl_t)) if rng.gen::<Float>() >= refl_t => {
+                                        let entering = ray.direction.dot(&hit.normal) < 0.;
+                                        indices = new_indices;
+                                        indices.absorption = if entering {
+                                            material.absorption(hit.uv)
+                                        } else {
+                                            None
+                                        };
+                                        refracted
+                                    }
+                                    _ => reflected_ray,
+                                }
+                            }
+                            _ => reflected_ray,
+                        }
+                    } else {
+                        let normal = if ray.direction.dot(&hit.normal) > 0. {
+                            -hit.normal
+                        } else {
+                            hit.normal
+                        };
+                        throughput *= object_color * properties.diffuse;
+                        scattered = true;
+                        sampled_lighting = true;
+                        sample_hemisphere(&normal, rng.gen(), rng.gen())
+                    }
+                }
+            };
+
+            if depth + 1 >= self.roulette_depth {
+                let survival = throughput.r.max(throughput.g).max(throughput.b).min(1.);
+                if rng.gen::<Float>() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+
+            let start = offset_origin(&point, &hit.normal, &direction, hit.distance);
+            ray = ray.bounced(start, direction, hit.distance);
+        }
+
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::background::{BackgroundEnum, PhysicalSkyBackground};
+    use crate::core::{Camera, LightProperties};
+    use crate::material::{MaterialEnum, PrincipledMaterial, UniformMaterial};
+    use crate::render::{LightAggregate, Object};
+    use crate::shape::Sphere;
+    use crate::texture::UniformTexture;
+    use crate::Point;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn new_works() {
+        let pathtracer = Pathtracer::new(5);
+        assert_eq!(
+            pathtracer,
+            Pathtracer {
+                roulette_depth: 5,
+                regularization: None,
+                indirect_clamp: None,
+            }
+        )
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = "roulette_depth: 5";
+        let pathtracer: Pathtracer = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(pathtracer, Pathtracer::new(5))
+    }
+
+    #[test]
+    fn regularization_deserialization_works() {
+        let yaml = "regularization: 0.3";
+        let pathtracer: Pathtracer = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(pathtracer, Pathtracer::default().with_regularization(0.3))
+    }
+
+    #[test]
+    fn indirect_clamp_deserialization_works() {
+        let yaml = "indirect_clamp: 10.0";
+        let pathtracer: Pathtracer = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(pathtracer, Pathtracer::default().with_indirect_clamp(10.))
+    }
+
+    #[test]
+    fn default_deserialization_works() {
+        let yaml = "{}";
+        let pathtracer: Pathtracer = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(pathtracer, Pathtracer::default())
+    }
+
+    /// A grey sphere lit by a white background: every path bounces once then escapes.
+    fn furnace_scene() -> Scene {
+        let material = UniformMaterial::new(LightProperties::new(
+            LinearColor::new(0.5, 0.5, 0.5),
+            LinearColor::black(),
+            None,
+        ));
+        sphere_scene(material.into(), LinearColor::new(1., 1., 1.).into())
+    }
+
+    fn sphere_scene(material: MaterialEnum, background: BackgroundEnum) -> Scene {
+        Scene::new(
+            Camera::default(),
+            LightAggregate::empty(),
+            vec![Object::new(
+                Sphere::new(Point::origin(), 1.).into(),
+                material,
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )],
+            background,
+            0,
+            5,
+            1.,
+        )
+    }
+
+    fn average_radiance(pathtracer: &Pathtracer, samples: u32) -> Float {
+        scene_radiance(&furnace_scene(), pathtracer, samples)
+    }
+
+    fn scene_radiance(scene: &Scene, pathtracer: &Pathtracer, samples: u32) -> Float {
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        let mut rng = StdRng::seed_from_u64(42);
+        let total: Float = (0..samples)
+            .map(|_| {
+                let mut lights = LightContributions::untracked();
+                pathtracer.radiance(scene, ray, &mut rng, &mut lights).g
+            })
+            .sum();
+        total / samples as Float
+    }
+
+    #[test]
+    fn without_roulette_works() {
+        let pathtracer = Pathtracer::new(u32::MAX);
+        assert!((average_radiance(&pathtracer, 16) - 0.5).abs() < 1e-5)
+    }
+
+    #[test]
+    fn roulette_is_unbiased() {
+        let pathtracer = Pathtracer::new(0);
+        assert!((average_radiance(&pathtracer, 10_000) - 0.5).abs() < 0.02)
+    }
+
+    #[test]
+    fn indirect_clamp_works() {
+        // Every path escapes to the background after a single bounce, bringing back 0.5
+        let pathtracer = Pathtracer::new(u32::MAX).with_indirect_clamp(0.1);
+        assert!((average_radiance(&pathtracer, 16) - 0.1).abs() < 1e-5)
+    }
+
+    #[test]
+    fn emission_is_added() {
+        let material = PrincipledMaterial {
+            emission: LinearColor::new(0.25, 0.5, 1.),
+            emission_strength: 2.,
+            ..PrincipledMaterial::new(LinearColor::black())
+        };
+        let scene = sphere_scene(material.into(), LinearColor::black().into());
+        let radiance = scene_radiance(&scene, &Pathtracer::new(u32::MAX), 16);
+        assert!((radiance - 1.).abs() < 1e-5)
+    }
+
+    #[test]
+    fn back_emits_when_two_sided() {
+        use crate::render::Sides;
+        use crate::shape::Plane;
+
+        let material = PrincipledMaterial {
+            emission: LinearColor::new(1., 1., 1.),
+            ..PrincipledMaterial::new(LinearColor::black())
+        };
+        // The ray hits the back of the panel
+        let panel = |sides| {
+            Scene::new(
+                Camera::default(),
+                LightAggregate::empty(),
+                vec![Object::new(
+                    Plane::new(Point::origin(), Vector::x()).into(),
+                    material.clone().into(),
+                    UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+                )
+                .with_sides(sides)],
+                LinearColor::black().into(),
+                0,
+                5,
+                1.,
+            )
+        };
+        let pathtracer = Pathtracer::new(u32::MAX);
+        assert_eq!(scene_radiance(&panel(Sides::Both), &pathtracer, 4), 0.);
+        let radiance = scene_radiance(&panel(Sides::BothEmitting), &pathtracer, 4);
+        assert!((radiance - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn transparent_surfaces_let_light_through() {
+        let material = PrincipledMaterial {
+            opacity: 0.,
+            ..PrincipledMaterial::new(LinearColor::new(0.5, 0.5, 0.5))
+        };
+        let scene = sphere_scene(material.into(), LinearColor::new(1., 1., 1.).into());
+        let radiance = scene_radiance(&scene, &Pathtracer::new(u32::MAX), 16);
+        assert!((radiance - 1.).abs() < 1e-5)
+    }
+
+    #[test]
+    fn sky_light_is_counted_once() {
+        // Once the sun has set, the sky is smooth enough to be found by the bounces alone
+        let sunset = Unit::new_normalize(Vector::new(1., -0.01, 0.));
+        let sky = PhysicalSkyBackground::new(sunset, 3.).with_intensity(10.);
+        let material = || PrincipledMaterial::new(LinearColor::new(0.5, 0.5, 0.5)).into();
+        let pathtracer = Pathtracer::new(u32::MAX);
+        let bounced = scene_radiance(
+            &sphere_scene(material(), sky.clone().with_samples(0).into()),
+            &pathtracer,
+            20_000,
+        );
+        let sampled = scene_radiance(&sphere_scene(material(), sky.into()), &pathtracer, 20_000);
+        assert!(bounced > 0.);
+        assert!(
+            (sampled - bounced).abs() < 0.03 * bounced,
+            "{} {}",
+            sampled,
+            bounced
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use super::Integrator;
+use crate::core::LinearColor;
+use crate::render::{LightContributions, Object, Scene};
+use crate::shape::Hit;
+use beevee::ray::Ray;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+/// A Whitted-style ray tracer, following perfect reflections and refractions up to the scene's
+/// reflection limit.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Raytracer {}
+
+impl Raytracer {
+    /// Creates a new `Raytracer`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::render::integrator::Raytracer;
+    /// #
+    /// let raytracer = Raytracer::new();
+    /// ```
+    pub fn new() -> Self {
+        Raytracer {}
+    }
+}
+
+impl Default for Raytracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Integrator for Raytracer {
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        scene.trace(ray, rng, lights)
+    }
+
+    fn radiance_from_hit(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        hit: Option<(Hit, &Object)>,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        scene.trace_from_hit(ray, hit, rng, lights)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = "{}";
+        let raytracer: Raytracer = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(raytracer, Raytracer::new())
+    }
+}
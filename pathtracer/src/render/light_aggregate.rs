@@ -1,20 +1,26 @@
 //! Utility module to compute overall illumination
 
+use super::light_tree::LightTree;
 use crate::light::*;
-use serde::Deserialize;
+use crate::{Float, Point};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::iter::Iterator;
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq)]
 /// A struct centralizing the light computation logic.
 pub struct LightAggregate {
-    #[serde(default)]
     ambients: Vec<AmbientLight>,
-    #[serde(default)]
     directionals: Vec<DirectionalLight>,
-    #[serde(default)]
     points: Vec<PointLight>,
-    #[serde(default)]
     spots: Vec<SpotLight>,
+    areas: Vec<AreaLight>,
+    spheres: Vec<SphereLight>,
+    projectors: Vec<ProjectorLight>,
+    sky: Option<SkyLight>,
+    tree: LightTree,
+    names: Vec<String>,
+    groups: Vec<String>,
 }
 
 impl LightAggregate {
@@ -57,12 +63,259 @@ impl LightAggregate {
         points: Vec<PointLight>,
         spots: Vec<SpotLight>,
     ) -> Self {
-        LightAggregate {
+        let mut ans = LightAggregate {
             ambients,
             directionals,
             points,
             spots,
+            areas: Vec::new(),
+            spheres: Vec::new(),
+            projectors: Vec::new(),
+            sky: None,
+            tree: LightTree::default(),
+            names: Vec::new(),
+            groups: Vec::new(),
+        };
+        ans.index_lights();
+        ans
+    }
+
+    /// Add [`AreaLight`]s to the aggregate.
+    ///
+    /// [`AreaLight`]: ../../light/area_light/struct.AreaLight.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::light::AreaLight;
+    /// # use pathtracer::render::LightAggregate;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let la = LightAggregate::empty().with_area_lights(vec![AreaLight::new(
+    ///     Point::new(0.0, 2.0, 0.0),
+    ///     Vector::new(1.0, 0.0, 0.0),
+    ///     Vector::new(0.0, 0.0, 1.0),
+    ///     LinearColor::new(1.0, 1.0, 1.0),
+    ///     16,
+    /// )]);
+    /// assert_eq!(la.spatial_lights_iter().count(), 1);
+    /// ```
+    pub fn with_area_lights(mut self, areas: Vec<AreaLight>) -> Self {
+        self.areas = areas;
+        self.index_lights();
+        self
+    }
+
+    /// Add [`SphereLight`]s to the aggregate.
+    ///
+    /// [`SphereLight`]: ../../light/sphere_light/struct.SphereLight.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::light::SphereLight;
+    /// # use pathtracer::render::LightAggregate;
+    /// # use pathtracer::Point;
+    /// #
+    /// let la = LightAggregate::empty().with_sphere_lights(vec![SphereLight::new(
+    ///     Point::new(0.0, 2.0, 0.0),
+    ///     0.1,
+    ///     LinearColor::new(10.0, 10.0, 10.0),
+    ///     16,
+    /// )]);
+    /// assert_eq!(la.spatial_lights_iter().count(), 1);
+    /// ```
+    pub fn with_sphere_lights(mut self, spheres: Vec<SphereLight>) -> Self {
+        self.spheres = spheres;
+        self.index_lights();
+        self
+    }
+
+    /// Add [`ProjectorLight`]s to the aggregate.
+    ///
+    /// [`ProjectorLight`]: ../../light/projector_light/struct.ProjectorLight.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::light::ProjectorLight;
+    /// # use pathtracer::render::LightAggregate;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let la = LightAggregate::empty().with_projector_lights(vec![ProjectorLight::new(
+    ///     Point::new(0.0, 2.0, 0.0),
+    ///     -Vector::y_axis(),
+    ///     45.0,
+    ///     LinearColor::new(1.0, 1.0, 1.0),
+    ///     UniformTexture::new(LinearColor::new(1.0, 0.5, 0.0)).into(),
+    /// )]);
+    /// assert_eq!(la.spatial_lights_iter().count(), 1);
+    /// ```
+    pub fn with_projector_lights(mut self, projectors: Vec<ProjectorLight>) -> Self {
+        self.projectors = projectors;
+        self.index_lights();
+        self
+    }
+
+    /// Add the [`SkyLight`] of a physically based sky to the aggregate, replacing the previous
+    /// one, if any.
+    ///
+    /// It is not serialized with the other lights, being part of the scene's background.
+    ///
+    /// [`SkyLight`]: ../../light/sky_light/struct.SkyLight.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::background::PhysicalSkyBackground;
+    /// # use pathtracer::light::SkyLight;
+    /// # use pathtracer::render::LightAggregate;
+    /// # use pathtracer::Vector;
+    /// #
+    /// let sky = PhysicalSkyBackground::new(Vector::y_axis(), 3.0);
+    /// let la = LightAggregate::empty().with_sky_light(SkyLight::new(sky));
+    /// assert_eq!(la.spatial_lights_iter().count(), 1);
+    /// assert!(la.sky_light().is_some());
+    /// ```
+    pub fn with_sky_light(mut self, sky: SkyLight) -> Self {
+        self.sky = Some(sky);
+        self
+    }
+
+    /// Returns the aggregate's [`SkyLight`], if any.
+    ///
+    /// [`SkyLight`]: ../../light/sky_light/struct.SkyLight.html
+    pub fn sky_light(&self) -> Option<&SkyLight> {
+        self.sky.as_ref()
+    }
+
+    /// Returns the aggregate's [`DirectionalLight`]s and [`PointLight`]s, if they are its only
+    /// spatial lights and every light of the aggregate lights every object.
+    ///
+    /// [`DirectionalLight`]: ../../light/directional_light/struct.DirectionalLight.html
+    /// [`PointLight`]: ../../light/point_light/struct.PointLight.html
+    #[cfg(feature = "gpu")]
+    pub(crate) fn simple_lights(&self) -> Option<(&[DirectionalLight], &[PointLight])> {
+        let others = self.spots.len() + self.areas.len() + self.spheres.len();
+        if others + self.projectors.len() > 0 || self.sky.is_some() {
+            return None;
+        }
+        let unrestricted = |l: &dyn Light| l.link().is_none_or(LightLink::is_unrestricted);
+        let mut lights = (self.ambient_lights_iter())
+            .chain(self.directionals.iter().map(|l| l as &dyn Light))
+            .chain(self.points.iter().map(|l| l as &dyn Light));
+        if !lights.all(unrestricted) {
+            return None;
         }
+        Some((&self.directionals, &self.points))
+    }
+
+    fn index_lights(&mut self) {
+        let lights: Vec<_> = self
+            .local_lights_iter()
+            .filter_map(|l| l.position().map(|position| (position, l.power())))
+            .collect();
+        self.tree = LightTree::new(&lights);
+
+        let ambients = self.ambient_lights_iter().filter_map(Light::name);
+        let spatials = self.spatial_lights_iter().filter_map(|l| l.name());
+        self.names = sorted_names(ambients.chain(spatials));
+        let ambients = self.ambient_lights_iter().filter_map(Light::group);
+        let spatials = self.spatial_lights_iter().filter_map(|l| l.group());
+        self.groups = sorted_names(ambients.chain(spatials));
+    }
+
+    /// Returns the names given to the aggregate's lights, sorted and without duplicates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::light::{AmbientLight, PointLight};
+    /// # use pathtracer::render::LightAggregate;
+    /// # use pathtracer::Point;
+    /// #
+    /// let white = LinearColor::new(1.0, 1.0, 1.0);
+    /// let la = LightAggregate::new(
+    ///     vec![AmbientLight::new(white.clone())],
+    ///     Vec::new(),
+    ///     vec![
+    ///         PointLight::new(Point::new(0.0, 1.0, 0.0), white.clone()).with_name("key"),
+    ///         PointLight::new(Point::new(0.0, 1.0, 1.0), white.clone()).with_name("key"),
+    ///         PointLight::new(Point::new(1.0, 1.0, 0.0), white).with_name("fill"),
+    ///     ],
+    ///     Vec::new(),
+    /// );
+    /// assert_eq!(la.light_names(), &["fill", "key"]);
+    /// ```
+    pub fn light_names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Returns the index of a light's name in [`light_names`], if it has one.
+    ///
+    /// [`light_names`]: #method.light_names
+    pub fn light_index<L: Light + ?Sized>(&self, light: &L) -> Option<usize> {
+        let name = light.name()?;
+        self.names.binary_search_by(|n| n.as_str().cmp(name)).ok()
+    }
+
+    /// Returns the groups the aggregate's lights belong to, sorted and without duplicates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::light::{AmbientLight, PointLight};
+    /// # use pathtracer::render::LightAggregate;
+    /// # use pathtracer::Point;
+    /// #
+    /// let white = LinearColor::new(1.0, 1.0, 1.0);
+    /// let la = LightAggregate::new(
+    ///     vec![AmbientLight::new(white.clone()).with_group("fill")],
+    ///     Vec::new(),
+    ///     vec![
+    ///         PointLight::new(Point::new(0.0, 1.0, 0.0), white.clone()).with_group("practicals"),
+    ///         PointLight::new(Point::new(0.0, 1.0, 1.0), white).with_group("practicals"),
+    ///     ],
+    ///     Vec::new(),
+    /// );
+    /// assert_eq!(la.light_groups(), &["fill", "practicals"]);
+    /// ```
+    pub fn light_groups(&self) -> &[String] {
+        &self.groups
+    }
+
+    /// Returns the index of a light's group in [`light_groups`], if it has one.
+    ///
+    /// [`light_groups`]: #method.light_groups
+    pub fn group_index<L: Light + ?Sized>(&self, light: &L) -> Option<usize> {
+        let group = light.group()?;
+        self.groups.binary_search_by(|g| g.as_str().cmp(group)).ok()
+    }
+
+    /// Returns the number of contributions tracked when rendering the lights: one for each of
+    /// the [`light_names`], followed by one for each of the [`light_groups`].
+    ///
+    /// [`light_names`]: #method.light_names
+    /// [`light_groups`]: #method.light_groups
+    pub fn contributions_count(&self) -> usize {
+        self.names.len() + self.groups.len()
+    }
+
+    /// Returns the indices of the contributions a light adds to, as counted by
+    /// [`contributions_count`]: for its name, then for its group.
+    ///
+    /// [`contributions_count`]: #method.contributions_count
+    pub fn contribution_indices<L: Light + ?Sized>(&self, light: &L) -> [Option<usize>; 2] {
+        let group = self
+            .group_index(light)
+            .map(|index| self.names.len() + index);
+        [self.light_index(light), group]
     }
 
     /// Returns an iterator over the aggregate's [`AmbientLight`]s.
@@ -74,18 +327,94 @@ impl LightAggregate {
 
     /// Returns an iterator over the aggregate's [`SpatialLight`]s.
     ///
-    /// This simply merges iterators over [`DirectionalLight`], [`PointLight`] and [`SpotLight`].
+    /// This simply merges iterators over [`DirectionalLight`], [`SkyLight`], [`PointLight`],
+    /// [`SpotLight`], [`AreaLight`], [`SphereLight`] and [`ProjectorLight`].
     ///
     /// [`SpatialLight`]: ../../light/trait.SpatialLight.html
     /// [`DirectionalLight`]: ../../light/directional_light/struct.DirectionalLight.html
+    /// [`SkyLight`]: ../../light/sky_light/struct.SkyLight.html
     /// [`PointLight`]: ../../light/point_light/struct.PointLight.html
     /// [`Spotight`]: ../../light/spot_light/struct.Spotight.html
+    /// [`AreaLight`]: ../../light/area_light/struct.AreaLight.html
+    /// [`SphereLight`]: ../../light/sphere_light/struct.SphereLight.html
+    /// [`ProjectorLight`]: ../../light/projector_light/struct.ProjectorLight.html
     pub fn spatial_lights_iter(&self) -> impl Iterator<Item = &'_ dyn SpatialLight> {
-        self.directionals
+        self.distant_lights_iter().chain(self.local_lights_iter())
+    }
+
+    /// Returns an iterator over the aggregate's lights which are infinitely far away: its
+    /// [`DirectionalLight`]s, and its [`SkyLight`].
+    ///
+    /// [`DirectionalLight`]: ../../light/directional_light/struct.DirectionalLight.html
+    /// [`SkyLight`]: ../../light/sky_light/struct.SkyLight.html
+    pub fn distant_lights_iter(&self) -> impl Iterator<Item = &'_ dyn SpatialLight> {
+        self.directional_lights_iter()
+            .chain(self.sky.iter().map(|l| l as &dyn SpatialLight))
+    }
+
+    /// Returns an iterator over the aggregate's [`DirectionalLight`]s.
+    ///
+    /// [`DirectionalLight`]: ../../light/directional_light/struct.DirectionalLight.html
+    pub fn directional_lights_iter(&self) -> impl Iterator<Item = &'_ dyn SpatialLight> {
+        self.directionals.iter().map(|l| l as &dyn SpatialLight)
+    }
+
+    /// Returns an iterator over the aggregate's [`PointLight`]s, [`SpotLight`]s, [`AreaLight`]s,
+    /// [`SphereLight`]s and [`ProjectorLight`]s, which have a position in the scene.
+    ///
+    /// [`PointLight`]: ../../light/point_light/struct.PointLight.html
+    /// [`SpotLight`]: ../../light/spot_light/struct.SpotLight.html
+    /// [`AreaLight`]: ../../light/area_light/struct.AreaLight.html
+    /// [`SphereLight`]: ../../light/sphere_light/struct.SphereLight.html
+    /// [`ProjectorLight`]: ../../light/projector_light/struct.ProjectorLight.html
+    fn local_lights_iter(&self) -> impl Iterator<Item = &'_ dyn SpatialLight> {
+        self.points
             .iter()
             .map(|l| l as &dyn SpatialLight)
-            .chain(self.points.iter().map(|l| l as &dyn SpatialLight))
             .chain(self.spots.iter().map(|l| l as &dyn SpatialLight))
+            .chain(self.areas.iter().map(|l| l as &dyn SpatialLight))
+            .chain(self.spheres.iter().map(|l| l as &dyn SpatialLight))
+            .chain(self.projectors.iter().map(|l| l as &dyn SpatialLight))
+    }
+
+    /// Choose one of the lights which have a position in the scene, with a probability
+    /// proportional to its estimated contribution at the given point, using a uniform random
+    /// number in `[0, 1)`.
+    ///
+    /// Returns the chosen light and the probability with which it was chosen, or `None` if there
+    /// are no such lights.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::light::{PointLight, SpatialLight};
+    /// # use pathtracer::render::LightAggregate;
+    /// # use pathtracer::Point;
+    /// #
+    /// let la = LightAggregate::new(
+    ///     Vec::new(),
+    ///     Vec::new(),
+    ///     vec![
+    ///         PointLight::new(Point::new(0.0, 0.0, 0.0), LinearColor::new(1.0, 1.0, 1.0)),
+    ///         PointLight::new(Point::new(100.0, 0.0, 0.0), LinearColor::new(1.0, 1.0, 1.0)),
+    ///     ],
+    ///     Vec::new(),
+    /// );
+    /// let (light, pmf) = la.sample_local_light(&Point::new(1.0, 0.0, 0.0), 0.5).unwrap();
+    /// assert_eq!(light.position(), Some(Point::origin()));
+    /// assert!(pmf > 0.99);
+    /// ```
+    pub fn sample_local_light(
+        &self,
+        point: &Point,
+        u: Float,
+    ) -> Option<(&dyn SpatialLight, Float)> {
+        self.tree.sample(point, u).map(|(index, pmf)| {
+            // The tree's lights are in the same order as `local_lights_iter`
+            let light = self.local_lights_iter().nth(index).unwrap();
+            (light, pmf)
+        })
     }
 }
 
@@ -95,6 +424,116 @@ impl Default for LightAggregate {
     }
 }
 
+/// Gather lights of any kind into a `LightAggregate`.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::core::LinearColor;
+/// # use pathtracer::light::{AmbientLight, LightEnum, PointLight};
+/// # use pathtracer::render::LightAggregate;
+/// # use pathtracer::Point;
+/// #
+/// let white = LinearColor::new(1.0, 1.0, 1.0);
+/// let lights: Vec<LightEnum> = vec![
+///     AmbientLight::new(white.clone()).into(),
+///     PointLight::new(Point::new(0.0, 1.0, 0.0), white).into(),
+/// ];
+/// let la: LightAggregate = lights.into_iter().collect();
+/// assert_eq!(la.ambient_lights_iter().count(), 1);
+/// assert_eq!(la.spatial_lights_iter().count(), 1);
+/// ```
+impl std::iter::FromIterator<LightEnum> for LightAggregate {
+    fn from_iter<I: IntoIterator<Item = LightEnum>>(lights: I) -> Self {
+        let mut ans = SerializedLightAggregate::default();
+        for light in lights {
+            match light {
+                LightEnum::AmbientLight(light) => ans.ambients.push(light),
+                LightEnum::DirectionalLight(light) => ans.directionals.push(light),
+                LightEnum::PointLight(light) => ans.points.push(light),
+                LightEnum::SpotLight(light) => ans.spots.push(light),
+                LightEnum::AreaLight(light) => ans.areas.push(light),
+                LightEnum::SphereLight(light) => ans.spheres.push(light),
+                LightEnum::ProjectorLight(light) => ans.projectors.push(light),
+                LightEnum::SkyLight(light) => ans.sky = Some(light),
+            }
+        }
+        ans.into()
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+struct SerializedLightAggregate {
+    #[serde(default)]
+    ambients: Vec<AmbientLight>,
+    #[serde(default)]
+    directionals: Vec<DirectionalLight>,
+    #[serde(default)]
+    points: Vec<PointLight>,
+    #[serde(default)]
+    spots: Vec<SpotLight>,
+    #[serde(default)]
+    areas: Vec<AreaLight>,
+    #[serde(default)]
+    spheres: Vec<SphereLight>,
+    #[serde(default)]
+    projectors: Vec<ProjectorLight>,
+    #[serde(skip)]
+    sky: Option<SkyLight>,
+}
+
+impl From<SerializedLightAggregate> for LightAggregate {
+    fn from(lights: SerializedLightAggregate) -> Self {
+        let ans = LightAggregate::new(
+            lights.ambients,
+            lights.directionals,
+            lights.points,
+            lights.spots,
+        )
+        .with_area_lights(lights.areas)
+        .with_sphere_lights(lights.spheres)
+        .with_projector_lights(lights.projectors);
+        match lights.sky {
+            Some(sky) => ans.with_sky_light(sky),
+            None => ans,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LightAggregate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let lights: SerializedLightAggregate = Deserialize::deserialize(deserializer)?;
+        Ok(lights.into())
+    }
+}
+
+impl Serialize for LightAggregate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut lights = serializer.serialize_struct("LightAggregate", 7)?;
+        lights.serialize_field("ambients", &self.ambients)?;
+        lights.serialize_field("directionals", &self.directionals)?;
+        lights.serialize_field("points", &self.points)?;
+        lights.serialize_field("spots", &self.spots)?;
+        lights.serialize_field("areas", &self.areas)?;
+        lights.serialize_field("spheres", &self.spheres)?;
+        lights.serialize_field("projectors", &self.projectors)?;
+        lights.end()
+    }
+}
+
+fn sorted_names<'a>(names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut names: Vec<_> = names.map(str::to_string).collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -109,6 +548,13 @@ mod test {
                 directionals: vec![],
                 points: vec![],
                 spots: vec![],
+                areas: vec![],
+                spheres: vec![],
+                projectors: vec![],
+                sky: None,
+                tree: LightTree::default(),
+                names: vec![],
+                groups: vec![],
             }
         )
     }
@@ -121,6 +567,7 @@ mod test {
 
     #[test]
     fn deserialization_works() {
+        use crate::texture::UniformTexture;
         use crate::{core::LinearColor, Point, Vector};
 
         let yaml = r#"
@@ -137,6 +584,24 @@ mod test {
                 direction: [1.0, 0.0, 0.0]
                 fov: 90.0
                 color: {r: 1.0, g: 0.5, b: 0.2}
+            areas:
+              - corner: [0.0, 2.0, 0.0]
+                u: [1.0, 0.0, 0.0]
+                v: [0.0, 0.0, 1.0]
+                color: {r: 1.0, g: 0.5, b: 0.2}
+            spheres:
+              - position: [0.0, 2.0, 0.0]
+                radius: 0.1
+                power: {r: 1.0, g: 0.5, b: 0.2}
+            projectors:
+              - position: [0.0, 2.0, 0.0]
+                direction: [0.0, -1.0, 0.0]
+                up: [0.0, 0.0, 1.0]
+                fov: 45.0
+                color: {r: 1.0, g: 0.5, b: 0.2}
+                image:
+                  type: uniform
+                  color: {r: 1.0, g: 1.0, b: 1.0}
         "#;
         let expected = LightAggregate::new(
             vec![AmbientLight::new(LinearColor::new(1., 0.5, 0.2))],
@@ -154,8 +619,57 @@ mod test {
                 90.,
                 LinearColor::new(1., 0.5, 0.2),
             )],
-        );
+        )
+        .with_area_lights(vec![AreaLight::new(
+            Point::new(0., 2., 0.),
+            Vector::x(),
+            Vector::z(),
+            LinearColor::new(1., 0.5, 0.2),
+            4,
+        )])
+        .with_sphere_lights(vec![SphereLight::new(
+            Point::new(0., 2., 0.),
+            0.1,
+            LinearColor::new(1., 0.5, 0.2),
+            4,
+        )])
+        .with_projector_lights(vec![ProjectorLight::new(
+            Point::new(0., 2., 0.),
+            -Vector::y_axis(),
+            45.,
+            LinearColor::new(1., 0.5, 0.2),
+            UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+        )
+        .with_up(Vector::z_axis())]);
         let lights: LightAggregate = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(lights, expected)
     }
+
+    #[test]
+    fn named_lights_deserialization_works() {
+        let yaml = r#"
+            directionals:
+              - direction: [1.0, 0.0, 0.0]
+                color: {r: 1.0, g: 0.5, b: 0.2}
+                name: sun
+            spots:
+              - position: [0.0, 0.0, 0.0]
+                direction: [1.0, 0.0, 0.0]
+                fov: 90.0
+                color: {r: 1.0, g: 0.5, b: 0.2}
+                name: key
+            areas:
+              - corner: [-1.0, 2.0, -1.0]
+                u: [2.0, 0.0, 0.0]
+                v: [0.0, 0.0, 2.0]
+                color: {r: 1.0, g: 1.0, b: 1.0}
+                name: key
+        "#;
+        let lights: LightAggregate = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(lights.light_names(), &["key", "sun"]);
+        let indices: Vec<_> = (lights.spatial_lights_iter())
+            .map(|light| lights.light_index(light))
+            .collect();
+        assert_eq!(indices, vec![Some(1), Some(0), Some(0)]);
+    }
 }
@@ -0,0 +1,162 @@
+//! Tracking of the light received from each named light
+
+use crate::core::LinearColor;
+use crate::Float;
+use std::ops::{Add, AddAssign, Mul};
+
+/// The light carried back along a ray, split between the named lights which emitted it, and
+/// between the groups of lights they belong to.
+///
+/// The contributions are indexed like [`LightAggregate::contribution_indices`]. The light coming
+/// from unnamed and ungrouped lights or from the background is not tracked.
+///
+/// They are scaled and summed alongside the colors computed by the integrators, so that each of
+/// them ends up being the part of the pixel's color due to its light.
+///
+/// [`LightAggregate::contribution_indices`]: ../light_aggregate/struct.LightAggregate.html#method.contribution_indices
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LightContributions {
+    values: Vec<LinearColor>,
+}
+
+impl LightContributions {
+    /// Creates a new `LightContributions`, tracking `count` named lights.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::render::LightContributions;
+    /// #
+    /// let lights = LightContributions::new(2);
+    /// assert!(lights.is_tracking());
+    /// assert_eq!(lights.values(), &[LinearColor::black(), LinearColor::black()]);
+    /// ```
+    pub fn new(count: usize) -> Self {
+        LightContributions {
+            values: vec![LinearColor::black(); count],
+        }
+    }
+
+    /// Creates a new `LightContributions` which does not track anything, when only the total
+    /// radiance is needed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::render::LightContributions;
+    /// #
+    /// let lights = LightContributions::untracked();
+    /// assert!(!lights.is_tracking());
+    /// ```
+    pub fn untracked() -> Self {
+        Self::default()
+    }
+
+    /// Whether any light is being tracked.
+    pub fn is_tracking(&self) -> bool {
+        !self.values.is_empty()
+    }
+
+    /// Creates a new `LightContributions`, tracking the same lights without any contribution.
+    pub fn empty_like(&self) -> Self {
+        Self::new(self.values.len())
+    }
+
+    /// Get the contribution of each light.
+    pub fn values(&self) -> &[LinearColor] {
+        &self.values
+    }
+
+    /// Add some light to the contribution at `index`, if any.
+    pub fn record(&mut self, index: Option<usize>, color: LinearColor) {
+        if let Some(value) = index.and_then(|index| self.values.get_mut(index)) {
+            *value += color;
+        }
+    }
+}
+
+impl AddAssign for LightContributions {
+    fn add_assign(&mut self, other: Self) {
+        if self.values.is_empty() {
+            self.values = other.values;
+            return;
+        }
+        for (lhs, rhs) in self.values.iter_mut().zip(other.values) {
+            *lhs += rhs;
+        }
+    }
+}
+
+impl Add for LightContributions {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self::Output {
+        self += other;
+        self
+    }
+}
+
+impl Mul<Float> for LightContributions {
+    type Output = Self;
+
+    fn mul(mut self, factor: Float) -> Self::Output {
+        for value in self.values.iter_mut() {
+            *value *= factor;
+        }
+        self
+    }
+}
+
+impl Mul<LinearColor> for LightContributions {
+    type Output = Self;
+
+    fn mul(mut self, factor: LinearColor) -> Self::Output {
+        for value in self.values.iter_mut() {
+            *value = value.clone() * factor.clone();
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_works() {
+        let mut lights = LightContributions::new(2);
+        lights.record(Some(1), LinearColor::new(1., 0., 0.));
+        lights.record(Some(1), LinearColor::new(0., 1., 0.));
+        // Unnamed lights are not tracked
+        lights.record(None, LinearColor::new(1., 1., 1.));
+        assert_eq!(
+            lights.values(),
+            &[LinearColor::black(), LinearColor::new(1., 1., 0.)]
+        );
+    }
+
+    #[test]
+    fn untracked_ignores_everything() {
+        let mut lights = LightContributions::untracked();
+        lights.record(Some(0), LinearColor::new(1., 1., 1.));
+        lights += lights.empty_like() * 2.;
+        assert_eq!(lights, LightContributions::untracked());
+    }
+
+    #[test]
+    fn operations_work() {
+        let mut first = LightContributions::new(2);
+        first.record(Some(0), LinearColor::new(1., 1., 1.));
+        let mut second = first.empty_like();
+        second.record(Some(1), LinearColor::new(1., 1., 1.));
+        let total = first * 0.5 + second * LinearColor::new(1., 0.5, 0.);
+        assert_eq!(
+            total.values(),
+            &[
+                LinearColor::new(0.5, 0.5, 0.5),
+                LinearColor::new(1., 0.5, 0.)
+            ]
+        );
+    }
+}
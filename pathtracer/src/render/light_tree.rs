@@ -0,0 +1,259 @@
+//! Hierarchical importance sampling of lights
+
+use crate::{Float, Point};
+use beevee::aabb::AABB;
+use std::cmp::Ordering;
+
+/// The smallest squared distance used to estimate a light's contribution, to avoid dividing by
+/// zero when a point is right on top of a light.
+const MIN_SQUARED_DISTANCE: Float = 1e-4;
+
+/// A binary tree over lights which have a position, used to choose a light to sample at a point
+/// with a probability proportional to its estimated contribution, without looking at every light.
+///
+/// Each node stores the bounds and total power of the lights beneath it, the contribution of a
+/// node is estimated as its power divided by its squared distance to the point.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LightTree {
+    nodes: Vec<LightNode>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct LightNode {
+    bounds: AABB,
+    power: Float,
+    kind: NodeKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum NodeKind {
+    /// The index of the light, in the order given to `LightTree::new`.
+    Leaf(usize),
+    /// The left child is always the next node, only store the index of the right child.
+    Internal { right: usize },
+}
+
+impl LightTree {
+    /// Build a `LightTree` from the position and power of each light.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::render::LightTree;
+    /// # use pathtracer::Point;
+    /// #
+    /// let tree = LightTree::new(&[
+    ///     (Point::new(0.0, 0.0, 0.0), 1.0),
+    ///     (Point::new(10.0, 0.0, 0.0), 1.0),
+    /// ]);
+    /// assert_eq!(tree.len(), 2);
+    /// ```
+    pub fn new(lights: &[(Point, Float)]) -> Self {
+        let mut entries: Vec<_> = lights
+            .iter()
+            .enumerate()
+            .map(|(index, &(position, power))| (index, position, power.max(0.)))
+            .collect();
+        let mut tree = LightTree {
+            nodes: Vec::with_capacity(2 * entries.len()),
+        };
+        if !entries.is_empty() {
+            tree.build(&mut entries);
+        }
+        tree
+    }
+
+    /// Return the number of lights in the tree.
+    pub fn len(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| matches!(node.kind, NodeKind::Leaf(_)))
+            .count()
+    }
+
+    /// Return true if the tree does not contain any light.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn build(&mut self, entries: &mut [(usize, Point, Float)]) -> usize {
+        let bounds = entries
+            .iter()
+            .fold(AABB::empty(), |bounds, (_, position, _)| {
+                bounds.grow(position)
+            });
+        let power = entries.iter().map(|(_, _, power)| power).sum();
+        let index = self.nodes.len();
+        if let [(light, _, _)] = entries {
+            self.nodes.push(LightNode {
+                bounds,
+                power,
+                kind: NodeKind::Leaf(*light),
+            });
+            return index;
+        }
+        self.nodes.push(LightNode {
+            bounds,
+            power,
+            kind: NodeKind::Internal { right: 0 },
+        });
+        // Split the lights in two halves along the largest axis
+        let axis = bounds.largest_axis();
+        entries.sort_by(|(_, lhs, _), (_, rhs, _)| {
+            lhs[axis].partial_cmp(&rhs[axis]).unwrap_or(Ordering::Equal)
+        });
+        let (left, right) = entries.split_at_mut(entries.len() / 2);
+        self.build(left);
+        let right = self.build(right);
+        self.nodes[index].kind = NodeKind::Internal { right };
+        index
+    }
+
+    fn importance(&self, node: usize, point: &Point) -> Float {
+        let node = &self.nodes[node];
+        let radius_squared = node.bounds.diagonal().norm_squared() / 4.;
+        let distance_squared = (node.bounds.centroid() - point).norm_squared();
+        node.power
+            / distance_squared
+                .max(radius_squared)
+                .max(MIN_SQUARED_DISTANCE)
+    }
+
+    /// The probability of going down the left child of an internal node.
+    fn left_probability(&self, node: usize, right: usize, point: &Point) -> Float {
+        let left = self.importance(node + 1, point);
+        let right = self.importance(right, point);
+        if left + right > 0. {
+            left / (left + right)
+        } else {
+            0.5
+        }
+    }
+
+    /// Choose a light using a uniform random number in `[0, 1)`, returning its index and the
+    /// probability with which it was chosen.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::render::LightTree;
+    /// # use pathtracer::Point;
+    /// #
+    /// let tree = LightTree::new(&[
+    ///     (Point::new(0.0, 0.0, 0.0), 1.0),
+    ///     (Point::new(10.0, 0.0, 0.0), 1.0),
+    /// ]);
+    /// // The first light is much closer, it is chosen most of the time
+    /// let (light, pmf) = tree.sample(&Point::new(1.0, 0.0, 0.0), 0.5).unwrap();
+    /// assert_eq!(light, 0);
+    /// assert!(pmf > 0.9);
+    /// ```
+    pub fn sample(&self, point: &Point, mut u: Float) -> Option<(usize, Float)> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut node = 0;
+        let mut pmf = 1.;
+        loop {
+            match self.nodes[node].kind {
+                NodeKind::Leaf(light) => return Some((light, pmf)),
+                NodeKind::Internal { right } => {
+                    let p_left = self.left_probability(node, right, point);
+                    // Re-use the random number by rescaling it to the chosen interval
+                    if u < p_left {
+                        u /= p_left;
+                        pmf *= p_left;
+                        node += 1;
+                    } else {
+                        u = (u - p_left) / (1. - p_left);
+                        pmf *= 1. - p_left;
+                        node = right;
+                    }
+                    u = u.min(1. - Float::EPSILON);
+                }
+            }
+        }
+    }
+
+    /// Return the probability with which `sample` chooses the given light at that point.
+    pub fn pmf(&self, point: &Point, light: usize) -> Float {
+        if self.is_empty() {
+            return 0.;
+        }
+        self.pmf_helper(0, point, light).unwrap_or(0.)
+    }
+
+    fn pmf_helper(&self, node: usize, point: &Point, light: usize) -> Option<Float> {
+        match self.nodes[node].kind {
+            NodeKind::Leaf(index) if index == light => Some(1.),
+            NodeKind::Leaf(_) => None,
+            NodeKind::Internal { right } => {
+                let p_left = self.left_probability(node, right, point);
+                self.pmf_helper(node + 1, point, light)
+                    .map(|pmf| pmf * p_left)
+                    .or_else(|| {
+                        self.pmf_helper(right, point, light)
+                            .map(|pmf| pmf * (1. - p_left))
+                    })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn simple_tree() -> LightTree {
+        LightTree::new(&[
+            (Point::new(0., 0., 0.), 1.),
+            (Point::new(10., 0., 0.), 1.),
+            (Point::new(0., 10., 0.), 4.),
+            (Point::new(5., 5., 5.), 0.),
+            (Point::new(-3., 2., 1.), 2.),
+        ])
+    }
+
+    #[test]
+    fn new_works() {
+        let tree = simple_tree();
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.nodes.len(), 9);
+        assert_eq!(tree.nodes[0].power, 8.);
+    }
+
+    #[test]
+    fn empty_works() {
+        let tree = LightTree::new(&[]);
+        assert!(tree.is_empty());
+        assert_eq!(tree.sample(&Point::origin(), 0.5), None);
+    }
+
+    #[test]
+    fn pmf_sums_to_one() {
+        let tree = simple_tree();
+        let point = Point::new(1., 2., 3.);
+        let total: Float = (0..5).map(|light| tree.pmf(&point, light)).sum();
+        assert!((total - 1.).abs() < 1e-5);
+        // Lights without any power are never chosen
+        assert_eq!(tree.pmf(&point, 3), 0.);
+    }
+
+    #[test]
+    fn sample_matches_pmf() {
+        let tree = simple_tree();
+        let point = Point::new(1., 2., 3.);
+        for i in 0..100 {
+            let (light, pmf) = tree.sample(&point, i as Float / 100.).unwrap();
+            assert!((pmf - tree.pmf(&point, light)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn sample_prefers_close_lights() {
+        let tree = simple_tree();
+        let point = Point::new(9., 0., 0.);
+        assert!(tree.pmf(&point, 1) > tree.pmf(&point, 0));
+        assert!(tree.pmf(&point, 1) > tree.pmf(&point, 4));
+    }
+}
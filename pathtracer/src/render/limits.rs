@@ -0,0 +1,150 @@
+//! Stopping renders before they are done
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The conditions under which a render is stopped before it is done, keeping the pixels which
+/// were rendered so far.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::render::RenderLimits;
+/// use std::sync::atomic::AtomicBool;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// // Set the flag from another thread to stop the render
+/// let cancelled = Arc::new(AtomicBool::new(false));
+/// let limits = RenderLimits::new()
+///     .with_cancellation(cancelled.clone())
+///     .with_time_limit(Duration::from_secs(60));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RenderLimits {
+    cancelled: Option<Arc<AtomicBool>>,
+    time_limit: Option<Duration>,
+    pixel_budget: Option<u64>,
+}
+
+impl RenderLimits {
+    /// Creates new `RenderLimits`, which never stop the render.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop the render as soon as `cancelled` is set, e.g: by another thread.
+    pub fn with_cancellation(mut self, cancelled: Arc<AtomicBool>) -> Self {
+        self.cancelled = Some(cancelled);
+        self
+    }
+
+    /// Stop the render once it has run for `limit`.
+    ///
+    /// Time limits are ignored on WebAssembly, which has no clock to measure them with.
+    pub fn with_time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = Some(limit);
+        self
+    }
+
+    /// Stop the render once `pixels` pixels have been rendered. Pixels are rendered in parallel,
+    /// which ones are rendered before stopping depends on how the work was scheduled.
+    pub fn with_pixel_budget(mut self, pixels: u64) -> Self {
+        self.pixel_budget = Some(pixels);
+        self
+    }
+
+    /// Start tracking a render, from now on.
+    pub(crate) fn start(&self) -> LimitTracker<'_> {
+        LimitTracker {
+            limits: self,
+            deadline: (self.time_limit)
+                .filter(|_| !cfg!(target_arch = "wasm32"))
+                .map(|limit| Instant::now() + limit),
+            pixels: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+        }
+    }
+}
+
+/// The progress of a render towards its [`RenderLimits`].
+///
+/// [`RenderLimits`]: struct.RenderLimits.html
+#[derive(Debug)]
+pub(crate) struct LimitTracker<'a> {
+    limits: &'a RenderLimits,
+    deadline: Option<Instant>,
+    pixels: AtomicU64,
+    stopped: AtomicBool,
+}
+
+impl LimitTracker<'_> {
+    /// Whether the next pixel can be rendered, counting it as rendered if so.
+    pub fn next_pixel(&self) -> bool {
+        if self.stopped.load(Ordering::Relaxed) {
+            return false;
+        }
+        let cancelled = (self.limits.cancelled.as_ref()).is_some_and(|c| c.load(Ordering::Relaxed));
+        let late = self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        let spent = self.limits.pixel_budget.is_some_and(|budget| {
+            // Only count pixels when needed, to avoid contention between threads
+            self.pixels.fetch_add(1, Ordering::Relaxed) >= budget
+        });
+        if cancelled || late || spent {
+            self.stopped.store(true, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Whether the render was stopped before being done.
+    pub fn stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn no_limits_never_stop() {
+        let limits = RenderLimits::new();
+        let tracker = limits.start();
+        assert!((0..1000).all(|_| tracker.next_pixel()));
+        assert!(!tracker.stopped());
+    }
+
+    #[test]
+    fn pixel_budget_works() {
+        let limits = RenderLimits::new().with_pixel_budget(10);
+        let tracker = limits.start();
+        assert_eq!((0..100).filter(|_| tracker.next_pixel()).count(), 10);
+        assert!(tracker.stopped());
+    }
+
+    #[test]
+    fn cancellation_works() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let limits = RenderLimits::new().with_cancellation(cancelled.clone());
+        let tracker = limits.start();
+        assert!(tracker.next_pixel());
+        cancelled.store(true, Ordering::Relaxed);
+        assert!(!tracker.next_pixel());
+        // Stopping is final
+        cancelled.store(false, Ordering::Relaxed);
+        assert!(!tracker.next_pixel());
+        assert!(tracker.stopped());
+    }
+
+    #[test]
+    fn time_limit_works() {
+        let limits = RenderLimits::new().with_time_limit(Duration::from_secs(0));
+        assert!(!limits.start().next_pixel());
+        let limits = RenderLimits::new().with_time_limit(Duration::from_secs(3600));
+        assert!(limits.start().next_pixel());
+    }
+}
@@ -0,0 +1,360 @@
+//! Logic for the scene's meshes
+
+use super::{Object, Sides, Visibility};
+use crate::core::{
+    CoordinateSystem, Handedness, LightProperties, LinearColor, Motion, Transform, UpAxis,
+};
+use crate::material::{MaterialEnum, UniformMaterial};
+use crate::mesh::Mesh;
+use crate::modifier::{Modifier, ModifierEnum};
+use crate::texture::{BumpMap, TextureEnum, UniformTexture};
+use serde::Deserialize;
+
+/// A mesh being rendered in the scene, turned into an [`Object`] per face when loading the scene.
+///
+/// Faces which have their own material, e.g: read from the material libraries of an OBJ file,
+/// use it along with a white texture, instead of the `MeshObject`'s material and texture.
+///
+/// [`Object`]: ../object/struct.Object.html
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct MeshObject {
+    /// The name given to each of the mesh's `Object`s
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The `MeshObject`'s geometry
+    #[serde(flatten)]
+    pub mesh: Mesh,
+    /// The up axis used by the mesh, if different from the scene's
+    #[serde(default)]
+    pub up_axis: Option<UpAxis>,
+    /// The handedness of the mesh's coordinates, if different from the scene's
+    #[serde(default)]
+    pub handedness: Option<Handedness>,
+    /// The transformation placing the mesh in the scene, applied before its modifiers
+    #[serde(default)]
+    pub transform: Transform,
+    /// The modifiers applied in order to the mesh when loading it
+    #[serde(default)]
+    pub modifiers: Vec<ModifierEnum>,
+    /// The material shared by the mesh's faces without their own, a light grey one by default
+    #[serde(
+        default = "default_material",
+        deserialize_with = "crate::serialize::named_material"
+    )]
+    pub material: MaterialEnum,
+    /// The texture shared by the mesh's faces without their own material, white by default
+    #[serde(
+        default = "default_texture",
+        deserialize_with = "crate::serialize::named_texture"
+    )]
+    pub texture: TextureEnum,
+    /// The bump map shared by all of the mesh's faces, if any
+    #[serde(default)]
+    pub bump: Option<BumpMap>,
+    /// The ray depths at which the mesh can be seen
+    #[serde(default)]
+    pub visibility: Visibility,
+    /// Whether the mesh's faces are shadow catchers
+    #[serde(default)]
+    pub shadow_catcher: bool,
+    /// The mesh's movement over time, in the scene's coordinates, if it is not still
+    #[serde(default)]
+    pub motion: Option<Motion>,
+    /// How the back of the mesh's faces is hit and shaded
+    #[serde(default)]
+    pub sides: Sides,
+}
+
+pub(super) fn default_material() -> MaterialEnum {
+    let grey = LinearColor::new(0.8, 0.8, 0.8);
+    UniformMaterial::new(LightProperties::new(grey, LinearColor::black(), None)).into()
+}
+
+pub(super) fn default_texture() -> TextureEnum {
+    UniformTexture::new(LinearColor::new(1., 1., 1.)).into()
+}
+
+impl MeshObject {
+    /// Creates a new `MeshObject`, without any modifiers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{LightProperties, LinearColor};
+    /// # use pathtracer::material::UniformMaterial;
+    /// # use pathtracer::mesh::Mesh;
+    /// # use pathtracer::render::MeshObject;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::Point;
+    /// #
+    /// let mesh = MeshObject::new(
+    ///     Mesh::new(
+    ///         vec![
+    ///             Point::new(0.0, 0.0, 0.0),
+    ///             Point::new(1.0, 0.0, 0.0),
+    ///             Point::new(0.0, 1.0, 0.0),
+    ///         ],
+    ///         vec![[0, 1, 2]],
+    ///     )
+    ///     .unwrap(),
+    ///     UniformMaterial::new(
+    ///         LightProperties::new(
+    ///             LinearColor::new(1.0, 0.0, 0.0), // diffuse component
+    ///             LinearColor::new(0.0, 0.0, 0.0), // specular component
+    ///             None,
+    ///         ),
+    ///     ).into(),
+    ///     UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5)).into(),
+    /// );
+    /// assert_eq!(mesh.into_objects().len(), 1);
+    /// ```
+    pub fn new(mesh: Mesh, material: MaterialEnum, texture: TextureEnum) -> Self {
+        MeshObject {
+            name: None,
+            mesh,
+            up_axis: None,
+            handedness: None,
+            transform: Transform::default(),
+            modifiers: Vec::new(),
+            material,
+            texture,
+            bump: None,
+            visibility: Visibility::default(),
+            shadow_catcher: false,
+            motion: None,
+            sides: Sides::default(),
+        }
+    }
+
+    /// Convert the mesh from its own coordinate system into the scene's, then apply the
+    /// transformation and the modifiers to it, and return an [`Object`] for each of its faces.
+    ///
+    /// [`Object`]: ../object/struct.Object.html
+    pub fn into_scene_objects(mut self, scene: &CoordinateSystem) -> Vec<Object> {
+        let asset = CoordinateSystem::new(
+            self.up_axis.unwrap_or(scene.up_axis),
+            self.handedness.unwrap_or(scene.handedness),
+        );
+        if asset != *scene {
+            self.mesh.transform(&asset.conversion_to(scene));
+        }
+        self.into_objects()
+    }
+
+    /// Apply the transformation and the modifiers to the mesh, and return an [`Object`] for each of
+    /// its faces.
+    ///
+    /// [`Object`]: ../object/struct.Object.html
+    pub fn into_objects(self) -> Vec<Object> {
+        let mut mesh = self.mesh;
+        if !self.transform.is_identity() {
+            mesh.transform_affine(&self.transform.affine());
+        }
+        for modifier in &self.modifiers {
+            modifier.apply(&mut mesh);
+        }
+        let (name, material, texture) = (self.name, self.material, self.texture);
+        let (bump, visibility, shadow_catcher) = (self.bump, self.visibility, self.shadow_catcher);
+        let (motion, sides) = (self.motion, self.sides);
+        let white = default_texture();
+        mesh.triangles()
+            .enumerate()
+            .map(|(face, triangle)| {
+                let (material, texture) = match mesh.face_material(face) {
+                    Some(material) => (material, &white),
+                    None => (&material, &texture),
+                };
+                (triangle, material.clone(), texture.clone())
+            })
+            .map(|(triangle, material, texture)| Object {
+                name: name.clone(),
+                shape: triangle.into(),
+                material,
+                texture,
+                bump: bump.clone(),
+                visibility,
+                shadow_catcher,
+                motion: motion.clone(),
+                sides,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{LightProperties, LinearColor};
+    use crate::core::{Scale, TransformComponent};
+    use crate::material::UniformMaterial;
+    use crate::modifier::NoiseDisplacement;
+    use crate::shape::Triangle;
+    use crate::texture::UniformTexture;
+    use crate::{Point, Vector};
+
+    fn simple_mesh_object() -> MeshObject {
+        let mesh = Mesh::new(
+            vec![
+                Point::new(0., 0., 0.),
+                Point::new(1., 0., 0.),
+                Point::new(1., 0., 1.),
+                Point::new(0., 0., 1.),
+            ],
+            vec![[0, 2, 1], [0, 3, 2]],
+        )
+        .unwrap();
+        let material = UniformMaterial::new(LightProperties::new(
+            LinearColor::new(0.5, 0.5, 0.5),
+            LinearColor::new(1., 1., 1.),
+            None,
+        ));
+        let texture = UniformTexture::new(LinearColor::new(0.25, 0.5, 1.));
+        MeshObject::new(mesh, material.into(), texture.into())
+    }
+
+    #[test]
+    fn into_objects_works() {
+        let objects = simple_mesh_object().into_objects();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(
+            objects[1].shape,
+            Triangle::new(
+                Point::new(0., 0., 0.),
+                Point::new(0., 0., 1.),
+                Point::new(1., 0., 1.)
+            )
+            .into()
+        );
+    }
+
+    #[test]
+    fn into_objects_applies_modifiers() {
+        let mut mesh_object = simple_mesh_object();
+        mesh_object.modifiers = vec![NoiseDisplacement::new(1., 0.3, 0, 1).into()];
+        let expected = {
+            let mut mesh = mesh_object.mesh.clone();
+            mesh_object.modifiers[0].apply(&mut mesh);
+            mesh
+        };
+        let objects = mesh_object.into_objects();
+        let shapes: Vec<_> = objects.into_iter().map(|o| o.shape).collect();
+        let expected: Vec<_> = expected.triangles().map(Into::into).collect();
+        assert_eq!(shapes, expected);
+    }
+
+    #[test]
+    fn into_objects_applies_transform() {
+        let mut mesh_object = simple_mesh_object();
+        mesh_object.transform = Transform::new(vec![
+            TransformComponent::Scale(Scale::Uniform(2.)),
+            TransformComponent::Translate(Vector::new(0., 1., 0.)),
+        ]);
+        let objects = mesh_object.into_objects();
+        assert_eq!(
+            objects[1].shape,
+            Triangle::new(
+                Point::new(0., 1., 0.),
+                Point::new(0., 1., 2.),
+                Point::new(2., 1., 2.)
+            )
+            .into()
+        );
+    }
+
+    #[test]
+    fn into_scene_objects_converts_coordinates() {
+        let mut mesh_object = simple_mesh_object();
+        mesh_object.up_axis = Some(UpAxis::Z);
+        let objects = mesh_object.into_scene_objects(&CoordinateSystem::default());
+        // The floor is now a wall, facing the same way relative to its new up axis
+        assert_eq!(
+            objects[1].shape,
+            Triangle::new(
+                Point::new(0., 0., 0.),
+                Point::new(0., 1., 0.),
+                Point::new(1., 1., 0.)
+            )
+            .into()
+        );
+    }
+
+    #[test]
+    fn into_scene_objects_keeps_orientation() {
+        let mut mesh_object = simple_mesh_object();
+        mesh_object.handedness = Some(Handedness::Left);
+        let objects = mesh_object.into_scene_objects(&CoordinateSystem::default());
+        // Mirrored along Z, with the winding order reversed
+        assert_eq!(
+            objects[1].shape,
+            Triangle::new(
+                Point::new(0., 0., 0.),
+                Point::new(1., 0., -1.),
+                Point::new(0., 0., -1.)
+            )
+            .into()
+        );
+    }
+
+    #[test]
+    fn into_objects_uses_face_materials() {
+        use crate::material::PrincipledMaterial;
+
+        let own: MaterialEnum = PrincipledMaterial::new(LinearColor::new(1., 0., 0.)).into();
+        let mut mesh_object = simple_mesh_object();
+        mesh_object.mesh = (mesh_object.mesh)
+            .with_materials(vec![own.clone()], vec![Some(0), None])
+            .unwrap();
+        let objects = mesh_object.clone().into_objects();
+        assert_eq!(objects[0].material, own);
+        assert_eq!(objects[0].texture, default_texture());
+        assert_eq!(objects[1].material, mesh_object.material);
+        assert_eq!(objects[1].texture, mesh_object.texture);
+    }
+
+    #[test]
+    fn deserialization_defaults_work() {
+        let yaml = r#"
+            vertices: [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+            faces: [[0, 1, 2]]
+        "#;
+        let mesh_object: MeshObject = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(mesh_object.material, default_material());
+        assert_eq!(mesh_object.texture, default_texture());
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            name: floor
+            vertices:
+              - [0.0, 0.0, 0.0]
+              - [1.0, 0.0, 0.0]
+              - [1.0, 0.0, 1.0]
+              - [0.0, 0.0, 1.0]
+            faces:
+              - [0, 2, 1]
+              - [0, 3, 2]
+            up_axis: z
+            transform:
+              - translate: [0.0, 1.0, 0.0]
+            modifiers:
+              - type: noise
+                amplitude: 0.1
+                frequency: 2.0
+            material:
+              type: uniform
+              diffuse: {r: 0.5, g: 0.5, b: 0.5}
+              specular: {r: 1., g: 1., b: 1.}
+            texture:
+              type: uniform
+              color: {r: 0.25, g: 0.5, b: 1.}
+        "#;
+        let mesh_object: MeshObject = serde_yaml::from_str(yaml).unwrap();
+        let mut expected = simple_mesh_object();
+        expected.name = Some("floor".to_string());
+        expected.up_axis = Some(UpAxis::Z);
+        expected.transform = Transform::new(vec![TransformComponent::Translate(Vector::y())]);
+        expected.modifiers = vec![NoiseDisplacement::new(0.1, 2., 0, 1).into()];
+        assert_eq!(mesh_object, expected)
+    }
+}
@@ -1,12 +1,63 @@
 //! Rendering logic
 
+pub mod ambient_occlusion;
+pub use ambient_occlusion::*;
+
+pub mod aovs;
+pub use aovs::*;
+
+#[cfg(feature = "gpu")]
+pub mod gpu;
+
+pub mod hit_info;
+pub use hit_info::*;
+
+pub mod integrator;
+pub use integrator::*;
+
 pub mod light_aggregate;
 pub use light_aggregate::*;
 
+pub mod light_contributions;
+pub use light_contributions::*;
+
+pub mod light_tree;
+pub use light_tree::*;
+
+pub mod limits;
+pub use limits::*;
+
+pub mod mesh_object;
+pub use mesh_object::*;
+
 pub mod object;
 pub use object::*;
 
+pub mod point_cloud;
+pub use point_cloud::*;
+
+pub mod preview;
+pub use preview::*;
+
+pub mod scatter;
+pub use scatter::*;
+
 pub mod scene;
 pub use scene::*;
 
+pub mod scene_builder;
+pub use scene_builder::*;
+
+pub mod statistics;
+pub use statistics::*;
+
+pub mod tiles;
+pub use tiles::*;
+
+mod bvh_cache;
+
+mod overlay;
+
+mod splat;
+
 pub(crate) mod utils;
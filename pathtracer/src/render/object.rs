@@ -1,25 +1,132 @@
 //! Logic for the scene objects
 
+use crate::core::Motion;
 use crate::material::MaterialEnum;
-use crate::shape::{Shape, ShapeEnum};
-use crate::texture::TextureEnum;
-use crate::Point;
+use crate::shape::{Hit, Shape, ShapeEnum};
+use crate::texture::{BumpMap, SurfacePoint, TextureEnum};
+use crate::{Float, Point, Vector};
 use beevee::{
     aabb::{Bounded, AABB},
     bvh::Intersected,
     ray::Ray,
 };
-use serde::Deserialize;
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// The smallest cosine used to stretch footprints at grazing angles, to avoid blurring textures
+/// completely on the silhouette of objects.
+const MIN_FOOTPRINT_COS: Float = 0.1;
 
 /// An object being rendered in the scene.
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct Object {
+    /// The `Object`'s name, used to identify it outside of rendering
+    #[serde(default)]
+    pub name: Option<String>,
     /// The `Object`'s physical shape
     pub shape: ShapeEnum,
-    /// The `Object`'s material
+    /// The `Object`'s material, or the name of one of the scene's `materials`
+    #[serde(
+        deserialize_with = "crate::serialize::named_material",
+        serialize_with = "crate::serialize::serialize_named_material"
+    )]
     pub material: MaterialEnum,
-    /// The `Object`'s texture
+    /// The `Object`'s texture, or the name of one of the scene's `textures`
+    #[serde(
+        deserialize_with = "crate::serialize::named_texture",
+        serialize_with = "crate::serialize::serialize_named_texture"
+    )]
     pub texture: TextureEnum,
+    /// The `Object`'s bump map, if any
+    #[serde(default)]
+    pub bump: Option<BumpMap>,
+    /// The ray depths at which the `Object` can be seen
+    #[serde(default)]
+    pub visibility: Visibility,
+    /// Whether the shadows cast onto the `Object` are part of the shadow pass
+    #[serde(default)]
+    pub shadow_catcher: bool,
+    /// The `Object`'s movement over time, if it is not still
+    #[serde(default)]
+    pub motion: Option<Motion>,
+    /// How the back of the `Object`'s surface is hit and shaded
+    #[serde(default)]
+    pub sides: Sides,
+}
+
+/// How the sides of an object's surface are handled, its front being the side its shape's normal
+/// points towards.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sides {
+    /// Both sides are hit, and shaded with the normal of the front one, e.g: for closed
+    /// refractive objects, which tell their inside apart by the normal.
+    #[default]
+    Oriented,
+    /// Only the front is hit, rays going through the back, e.g: for the walls of a room seen from
+    /// the outside.
+    Front,
+    /// Both sides are hit and shaded alike, the normal being flipped towards the ray, e.g: for
+    /// thin surfaces such as leaves or sheets of paper.
+    Both,
+    /// Like `Both`, the back also emitting light, e.g: for a two-sided light panel.
+    BothEmitting,
+}
+
+impl Sides {
+    /// Whether rays go through the back of the surface.
+    pub fn culls_back(self) -> bool {
+        self == Sides::Front
+    }
+
+    /// Whether the normal is flipped towards the rays hitting the back of the surface.
+    pub fn flips_normal(self) -> bool {
+        matches!(self, Sides::Both | Sides::BothEmitting)
+    }
+
+    /// Whether a hit on the surface sees the light it emits.
+    pub fn emits_towards(self, hit: &Hit) -> bool {
+        !hit.back_face || self == Sides::BothEmitting
+    }
+}
+
+/// The range of ray depths at which an object is visible, the camera rays being at depth 0.
+///
+/// This allows for example a backdrop to only appear in reflections, or only to the camera.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Visibility {
+    /// The smallest depth at which the object is visible
+    #[serde(default)]
+    pub min_depth: u32,
+    /// The largest depth at which the object is visible, if any
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+}
+
+impl Visibility {
+    /// Creates a new `Visibility`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::render::Visibility;
+    /// #
+    /// // Only visible through reflections and refractions
+    /// let reflections_only = Visibility::new(1, None);
+    /// assert!(!reflections_only.is_visible_at(0));
+    /// assert!(reflections_only.is_visible_at(3));
+    /// ```
+    pub fn new(min_depth: u32, max_depth: Option<u32>) -> Self {
+        Visibility {
+            min_depth,
+            max_depth,
+        }
+    }
+
+    /// Whether a ray having bounced `depth` times can see the object.
+    pub fn is_visible_at(&self, depth: u32) -> bool {
+        depth >= self.min_depth && self.max_depth.is_none_or(|max| depth <= max)
+    }
 }
 
 impl Object {
@@ -49,26 +156,218 @@ impl Object {
     /// ```
     pub fn new(shape: ShapeEnum, material: MaterialEnum, texture: TextureEnum) -> Self {
         Object {
+            name: None,
             shape,
             material,
             texture,
+            bump: None,
+            visibility: Visibility::default(),
+            shadow_catcher: false,
+            motion: None,
+            sides: Sides::default(),
+        }
+    }
+
+    /// Give a name to the `Object`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{LightProperties, LinearColor};
+    /// # use pathtracer::material::UniformMaterial;
+    /// # use pathtracer::render::Object;
+    /// # use pathtracer::shape::Sphere;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::Point;
+    /// #
+    /// let obj = Object::new(
+    ///     Sphere::new(Point::origin(), 1.0).into(),
+    ///     UniformMaterial::new(
+    ///         LightProperties::new(
+    ///             LinearColor::new(1.0, 0.0, 0.0), // diffuse component
+    ///             LinearColor::new(0.0, 0.0, 0.0), // specular component
+    ///             None,
+    ///         ),
+    ///     ).into(),
+    ///     UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5)).into(),
+    /// )
+    /// .with_name("ball");
+    /// assert_eq!(obj.name.as_deref(), Some("ball"));
+    /// ```
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Perturb the `Object`'s shading normals with a bump map.
+    pub fn with_bump(mut self, bump: BumpMap) -> Self {
+        self.bump = Some(bump);
+        self
+    }
+
+    /// Restrict the ray depths at which the `Object` is visible.
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Mark the `Object` as a shadow catcher, whose received shadows are rendered by
+    /// [`Scene::render_shadows`].
+    ///
+    /// [`Scene::render_shadows`]: struct.Scene.html#method.render_shadows
+    pub fn as_shadow_catcher(mut self) -> Self {
+        self.shadow_catcher = true;
+        self
+    }
+
+    /// Move the `Object` over time, its shape being where it is at rest.
+    pub fn with_motion(mut self, motion: Motion) -> Self {
+        self.motion = Some(motion);
+        self
+    }
+
+    /// Set how the back of the `Object`'s surface is hit and shaded.
+    pub fn with_sides(mut self, sides: Sides) -> Self {
+        self.sides = sides;
+        self
+    }
+
+    /// Creates a new `Object` of the given shape, with the other properties of this one, e.g: for
+    /// each copy of an object.
+    pub(crate) fn reshaped(&self, shape: ShapeEnum) -> Self {
+        Object {
+            name: self.name.clone(),
+            shape,
+            material: self.material.clone(),
+            texture: self.texture.clone(),
+            bump: self.bump.clone(),
+            visibility: self.visibility,
+            shadow_catcher: self.shadow_catcher,
+            motion: self.motion.clone(),
+            sides: self.sides,
+        }
+    }
+
+    /// Intersect the `Object` where it is at the ray's time.
+    pub fn hit(&self, ray: &Ray) -> Option<Hit> {
+        let motion = match &self.motion {
+            Some(motion) => motion,
+            None => return self.shape.intersect(ray),
+        };
+        let (rest, scale) = motion.ray_to_rest(ray);
+        let hit = self.shape.intersect(&rest)?;
+        Some(Hit::new(
+            hit.distance * scale,
+            motion.normal_at(ray.time, &hit.normal),
+            hit.uv,
+        ))
+    }
+
+    /// Get the normal perturbed by the `Object`'s bump map, if any, where `ray` hit it.
+    pub fn shading_normal(&self, ray: &Ray, hit: &Hit) -> Unit<Vector> {
+        let bump = match &self.bump {
+            Some(bump) => bump,
+            None => return hit.normal,
+        };
+        let point = ray.origin + ray.direction.as_ref() * hit.distance;
+        let motion = match &self.motion {
+            Some(motion) => motion.at(ray.time),
+            None => return bump.perturb(&self.shape, &point, hit.normal),
+        };
+        // Bump maps are defined on the shape at rest
+        let inverse = motion.inverse();
+        let normal = inverse.isometry.rotation * hit.normal;
+        let perturbed = bump.perturb(&self.shape, &(inverse * point), normal);
+        motion.isometry.rotation * perturbed
+    }
+
+    /// Get the width, in texel space, of the footprint of `ray` where it hit the `Object`, used
+    /// to filter its texture.
+    pub fn texel_footprint(&self, ray: &Ray, hit: &Hit) -> Float {
+        let width = ray.footprint_at(hit.distance);
+        if width <= 0. {
+            return 0.;
+        }
+        // The footprint is stretched on surfaces seen at grazing angles
+        let cos = ray.direction.dot(&hit.normal).abs().max(MIN_FOOTPRINT_COS);
+        let point = ray.origin + ray.direction.as_ref() * hit.distance;
+        let scale = match &self.motion {
+            Some(motion) => {
+                // Texel coordinates are defined on the shape at rest
+                let inverse = motion.at(ray.time).inverse();
+                let normal = inverse.isometry.rotation * hit.normal;
+                self.shape.texel_scale(&(inverse * point), &normal) * inverse.scaling()
+            }
+            None => self.shape.texel_scale(&point, &hit.normal),
+        };
+        width / cos * scale
+    }
+
+    /// Get the [`SurfacePoint`] of the `Object` where `ray` hit it, to compute its texture.
+    ///
+    /// [`SurfacePoint`]: ../texture/struct.SurfacePoint.html
+    pub fn surface_point(&self, ray: &Ray, hit: &Hit) -> SurfacePoint {
+        let point = ray.origin + ray.direction.as_ref() * hit.distance;
+        let cos = ray.direction.dot(&hit.normal).abs().max(MIN_FOOTPRINT_COS);
+        let width = ray.footprint_at(hit.distance).max(0.) / cos;
+        let footprint = self.texel_footprint(ray, hit);
+        match &self.motion {
+            Some(motion) => {
+                // Textures are defined on the shape at rest
+                let inverse = motion.at(ray.time).inverse();
+                SurfacePoint {
+                    position: inverse * point,
+                    normal: inverse.isometry.rotation * hit.normal,
+                    texel: hit.uv,
+                    footprint,
+                    width: width * inverse.scaling(),
+                }
+            }
+            None => SurfacePoint {
+                position: point,
+                normal: hit.normal,
+                texel: hit.uv,
+                footprint,
+                width,
+            },
+        }
+    }
+
+    /// Get the bounds covering every position of the `Object` between `start` and `end`.
+    pub fn aabb_between(&self, start: Float, end: Float) -> AABB {
+        match &self.motion {
+            Some(motion) => motion.aabb_between(&self.shape.aabb(), start, end),
+            None => self.shape.aabb(),
         }
     }
 }
 
 impl Bounded for Object {
     fn aabb(&self) -> AABB {
-        self.shape.aabb()
+        match &self.motion {
+            Some(motion) => motion.aabb(&self.shape.aabb()),
+            None => self.shape.aabb(),
+        }
     }
 
     fn centroid(&self) -> Point {
-        self.shape.centroid()
+        match &self.motion {
+            Some(_) => self.aabb().centroid(),
+            None => self.shape.centroid(),
+        }
+    }
+
+    fn clipped_aabb(&self, clip: &AABB) -> AABB {
+        match &self.motion {
+            Some(_) => self.aabb().intersection(clip),
+            None => self.shape.clipped_aabb(clip),
+        }
     }
 }
 
 impl Intersected for Object {
-    fn intersect(&self, ray: &Ray) -> Option<f32> {
-        self.shape.intersect(ray)
+    fn intersect(&self, ray: &Ray) -> Option<Float> {
+        self.hit(ray).map(|hit| hit.distance)
     }
 }
 
@@ -78,7 +377,7 @@ mod test {
     use crate::core::color::LinearColor;
     use crate::core::LightProperties;
     use crate::material::UniformMaterial;
-    use crate::shape::Sphere;
+    use crate::shape::{Plane, Sphere};
     use crate::texture::UniformTexture;
 
     fn simple_object() -> Object {
@@ -104,13 +403,29 @@ mod test {
         assert_eq!(
             simple_object(),
             Object {
+                name: None,
                 shape: shape.into(),
                 material: material.into(),
                 texture: texture.into(),
+                bump: None,
+                visibility: Visibility::default(),
+                shadow_catcher: false,
+                motion: None,
+                sides: Sides::Oriented,
             }
         )
     }
 
+    #[test]
+    fn visibility_works() {
+        let visibility = Visibility::new(1, Some(2));
+        assert!(!visibility.is_visible_at(0));
+        assert!(visibility.is_visible_at(1));
+        assert!(visibility.is_visible_at(2));
+        assert!(!visibility.is_visible_at(3));
+        assert!(Visibility::default().is_visible_at(42));
+    }
+
     #[test]
     fn deserialization_works() {
         let yaml = r#"
@@ -131,4 +446,155 @@ mod test {
         let expected = simple_object();
         assert_eq!(object, expected)
     }
+
+    #[test]
+    fn named_deserialization_works() {
+        let yaml = r#"
+            name: ball
+            shape:
+              type: sphere
+              inverted: false
+              center: [5., 0.0, 0.0]
+              radius: 1.0
+            material:
+              type: uniform
+              diffuse: {r: 0.5, g: 0.5, b: 0.5}
+              specular: {r: 1., g: 1., b: 1.}
+            texture:
+              type: uniform
+              color: {r: 0.25, g: 0.5, b: 1.}
+        "#;
+        let object: Object = serde_yaml::from_str(yaml).unwrap();
+        let expected = simple_object().with_name("ball");
+        assert_eq!(object, expected)
+    }
+
+    #[test]
+    fn sides_deserialization_works() {
+        let yaml = r#"
+            shape:
+              type: sphere
+              inverted: false
+              center: [5., 0.0, 0.0]
+              radius: 1.0
+            material:
+              type: uniform
+              diffuse: {r: 0.5, g: 0.5, b: 0.5}
+              specular: {r: 1., g: 1., b: 1.}
+            texture:
+              type: uniform
+              color: {r: 0.25, g: 0.5, b: 1.}
+            sides: both_emitting
+        "#;
+        let object: Object = serde_yaml::from_str(yaml).unwrap();
+        let expected = simple_object().with_sides(Sides::BothEmitting);
+        assert_eq!(object, expected)
+    }
+
+    #[test]
+    fn visibility_deserialization_works() {
+        let yaml = r#"
+            shape:
+              type: sphere
+              inverted: false
+              center: [5., 0.0, 0.0]
+              radius: 1.0
+            material:
+              type: uniform
+              diffuse: {r: 0.5, g: 0.5, b: 0.5}
+              specular: {r: 1., g: 1., b: 1.}
+            texture:
+              type: uniform
+              color: {r: 0.25, g: 0.5, b: 1.}
+            visibility:
+              max_depth: 0
+        "#;
+        let object: Object = serde_yaml::from_str(yaml).unwrap();
+        let expected = simple_object().with_visibility(Visibility::new(0, Some(0)));
+        assert_eq!(object, expected)
+    }
+
+    #[test]
+    fn shadow_catcher_deserialization_works() {
+        let yaml = r#"
+            shape:
+              type: sphere
+              inverted: false
+              center: [5., 0.0, 0.0]
+              radius: 1.0
+            material:
+              type: uniform
+              diffuse: {r: 0.5, g: 0.5, b: 0.5}
+              specular: {r: 1., g: 1., b: 1.}
+            texture:
+              type: uniform
+              color: {r: 0.25, g: 0.5, b: 1.}
+            shadow_catcher: true
+        "#;
+        let object: Object = serde_yaml::from_str(yaml).unwrap();
+        let expected = simple_object().as_shadow_catcher();
+        assert_eq!(object, expected)
+    }
+
+    #[test]
+    fn moving_hit_works() {
+        use crate::core::Keyframe;
+
+        // Moves up by 2, while growing twice as big around its center
+        let motion = Motion::new(
+            Point::new(5., 0., 0.),
+            vec![
+                Keyframe::new(0., Vector::zeros()),
+                Keyframe::new(1., Vector::new(0., 2., 0.)).with_scale(2.),
+            ],
+        );
+        let object = simple_object().with_motion(motion);
+        let ray = Ray::new(Point::new(5., 10., 0.), -Vector::y_axis());
+        assert_eq!(object.hit(&ray).map(|hit| hit.distance), Some(9.));
+        let hit = object.hit(&ray.with_time(1.)).unwrap();
+        assert!((hit.distance - 6.).abs() < 1e-5);
+        assert!((hit.normal.into_inner() - Vector::y()).norm() < 1e-5);
+        // The bounds cover the whole motion
+        let aabb = object.aabb();
+        assert!(aabb.contains(&Point::new(5., -1., 0.)));
+        assert!(aabb.contains(&Point::new(5., 4., 0.)));
+    }
+
+    #[test]
+    fn surface_point_is_at_rest() {
+        use crate::core::Keyframe;
+
+        let motion = Motion::new(
+            Point::new(5., 0., 0.),
+            vec![
+                Keyframe::new(0., Vector::zeros()),
+                Keyframe::new(1., Vector::new(0., 2., 0.)).with_scale(2.),
+            ],
+        );
+        let object = simple_object().with_motion(motion);
+        let ray = Ray::new(Point::new(5., 10., 0.), -Vector::y_axis())
+            .with_time(1.)
+            .with_footprint(0.5, 0.);
+        let hit = object.hit(&ray).unwrap();
+        let surface = object.surface_point(&ray, &hit);
+        assert!((surface.position - Point::new(5., 1., 0.)).norm() < 1e-5);
+        assert!((surface.normal.into_inner() - Vector::y()).norm() < 1e-5);
+        assert!((surface.width - 0.25).abs() < 1e-5);
+        assert_eq!(surface.texel, hit.uv);
+    }
+
+    #[test]
+    fn texel_footprint_works() {
+        let ground = Object::new(
+            Plane::new(Point::origin(), Vector::y()).into(),
+            simple_object().material,
+            simple_object().texture,
+        );
+        let ray = Ray::new(Point::new(0.5, 1., 0.5), -Vector::y_axis());
+        let hit = ground.hit(&ray).unwrap();
+        assert_eq!(ground.texel_footprint(&ray, &hit), 0.);
+        let ray = ray.with_footprint(0.05, 0.05);
+        let footprint = ground.texel_footprint(&ray, &hit);
+        assert!((footprint - 0.1).abs() < 1e-3);
+    }
 }
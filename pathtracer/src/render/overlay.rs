@@ -0,0 +1,145 @@
+//! Drawing wireframe overlays over rendered images
+
+use crate::core::Camera;
+use crate::{Float, Point};
+use image::{Rgb, RgbImage};
+
+/// The depth in front of the camera at which segments going behind it are cut.
+const NEAR_DEPTH: Float = 1e-3;
+
+/// Draw a segment of the scene over the image seen by `camera`, whose top-left pixel is at
+/// `(left, top)` in the camera's image.
+pub(crate) fn draw_segment(
+    image: &mut RgbImage,
+    camera: &Camera,
+    (left, top): (u32, u32),
+    [start, end]: [Point; 2],
+    color: Rgb<u8>,
+) {
+    // Only keep the part of the segment in front of the camera
+    let (start_depth, end_depth) = (camera.depth(&start), camera.depth(&end));
+    if start_depth < NEAR_DEPTH && end_depth < NEAR_DEPTH {
+        return;
+    }
+    let cut = |behind: Point, behind_depth: Float, front: Point, front_depth: Float| {
+        let t = (NEAR_DEPTH - behind_depth) / (front_depth - behind_depth);
+        behind + (front - behind) * t
+    };
+    let (start, end) = if start_depth < NEAR_DEPTH {
+        (cut(start, start_depth, end, end_depth), end)
+    } else if end_depth < NEAR_DEPTH {
+        (start, cut(end, end_depth, start, start_depth))
+    } else {
+        (start, end)
+    };
+    // Both points are in front of the camera after cutting the segment
+    let (x0, y0) = camera.project(&start).unwrap();
+    let (x1, y1) = camera.project(&end).unwrap();
+    let (x0, y0, x1, y1) = (
+        x0 - left as Float,
+        y0 - top as Float,
+        x1 - left as Float,
+        y1 - top as Float,
+    );
+    let (width, height) = image.dimensions();
+    let (t0, t1) = match clip(
+        (x0, y0),
+        (x1 - x0, y1 - y0),
+        (width as Float - 0.5, height as Float - 0.5),
+    ) {
+        Some(range) => range,
+        None => return,
+    };
+    // Step by at most one pixel along the visible part of the segment
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let steps = ((t1 - t0) * dx.abs().max(dy.abs())).ceil().max(1.) as u32;
+    for i in 0..=steps {
+        let t = t0 + (t1 - t0) * i as Float / steps as Float;
+        let (x, y) = ((x0 + dx * t).round(), (y0 + dy * t).round());
+        if x >= 0. && y >= 0. && (x as u32) < width && (y as u32) < height {
+            image.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+/// Clip the segment going from `start` to `start + delta` to the rectangle from `(-0.5, -0.5)`
+/// to `high`, returning the range of its parameter inside of it, if any.
+fn clip(
+    start: (Float, Float),
+    delta: (Float, Float),
+    high: (Float, Float),
+) -> Option<(Float, Float)> {
+    let (mut t0, mut t1): (Float, Float) = (0., 1.);
+    for &(p, q) in &[
+        (-delta.0, start.0 + 0.5),
+        (delta.0, high.0 - start.0),
+        (-delta.1, start.1 + 0.5),
+        (delta.1, high.1 - start.1),
+    ] {
+        if p == 0. {
+            if q < 0. {
+                return None;
+            }
+        } else if p < 0. {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    if t0 <= t1 {
+        Some((t0, t1))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Vector;
+
+    fn simple_camera() -> Camera {
+        Camera::new(
+            Point::origin(),
+            Vector::z(),
+            Vector::y(),
+            crate::consts::FRAC_PI_2,
+            1.,
+            10,
+            10,
+        )
+    }
+
+    #[test]
+    fn clip_works() {
+        assert_eq!(clip((1., 1.), (2., 2.), (9.5, 9.5)), Some((0., 1.)));
+        assert_eq!(clip((-10.5, 0.), (20., 0.), (9.5, 9.5)), Some((0.5, 1.)));
+        assert_eq!(clip((-10., -10.), (1., 1.), (9.5, 9.5)), None);
+    }
+
+    #[test]
+    fn draw_segment_works() {
+        let white = Rgb([255, 255, 255]);
+        let mut image = RgbImage::new(10, 10);
+        // A horizontal line from the middle of the image, going behind the camera
+        let segment = [Point::new(0., 0., 1.), Point::new(-4., 0., -1.)];
+        draw_segment(&mut image, &simple_camera(), (0, 0), segment, white);
+        assert!((5..10).all(|x| image.get_pixel(x, 5) == &white));
+        assert_ne!(image.get_pixel(4, 5), &white);
+        assert!((0..10).all(|x| image.get_pixel(x, 4) != &white));
+    }
+
+    #[test]
+    fn draw_segment_behind_does_nothing() {
+        let mut image = RgbImage::new(10, 10);
+        let segment = [Point::new(-1., 0., -1.), Point::new(1., 0., -1.)];
+        draw_segment(
+            &mut image,
+            &simple_camera(),
+            (0, 0),
+            segment,
+            Rgb([255, 0, 0]),
+        );
+        assert!(image.pixels().all(|pixel| pixel == &Rgb([0, 0, 0])));
+    }
+}
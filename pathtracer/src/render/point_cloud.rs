@@ -0,0 +1,290 @@
+//! Logic for the scene's point clouds
+
+use super::mesh_object::{default_material, default_texture};
+use super::{Object, Sides, Visibility};
+use crate::core::{CoordinateSystem, Handedness, Motion, Transform, UpAxis};
+use crate::material::MaterialEnum;
+use crate::mesh::PointSet;
+use crate::shape::{Disk, ShapeEnum, Sphere};
+use crate::texture::{TextureEnum, UniformTexture};
+use crate::Float;
+use serde::Deserialize;
+
+/// How each point of a [`PointCloud`] is drawn.
+///
+/// [`PointCloud`]: struct.PointCloud.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Splat {
+    /// A disk facing along the point's normal, e.g: for scans of surfaces. Points without a
+    /// normal are drawn as spheres.
+    #[default]
+    Disk,
+    /// A sphere, seen the same from every direction.
+    Sphere,
+}
+
+/// Points rendered directly in the scene, e.g: to visualize scan data, turned into an
+/// [`Object`] per point when loading the scene.
+///
+/// Points with their own color use it as their texture, instead of the `PointCloud`'s texture.
+///
+/// [`Object`]: ../object/struct.Object.html
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct PointCloud {
+    /// The name given to each of the points' `Object`s
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The `PointCloud`'s points
+    #[serde(flatten)]
+    pub points: PointSet,
+    /// The radius of the points without their own
+    #[serde(default = "default_radius")]
+    pub radius: Float,
+    /// How the points are drawn
+    #[serde(default)]
+    pub splat: Splat,
+    /// The up axis used by the points, if different from the scene's
+    #[serde(default)]
+    pub up_axis: Option<UpAxis>,
+    /// The handedness of the points' coordinates, if different from the scene's
+    #[serde(default)]
+    pub handedness: Option<Handedness>,
+    /// The transformation placing the points in the scene
+    #[serde(default)]
+    pub transform: Transform,
+    /// The material shared by the points, a light grey one by default
+    #[serde(
+        default = "default_material",
+        deserialize_with = "crate::serialize::named_material"
+    )]
+    pub material: MaterialEnum,
+    /// The texture shared by the points without their own color, white by default
+    #[serde(
+        default = "default_texture",
+        deserialize_with = "crate::serialize::named_texture"
+    )]
+    pub texture: TextureEnum,
+    /// The ray depths at which the points can be seen
+    #[serde(default)]
+    pub visibility: Visibility,
+    /// Whether the points are shadow catchers
+    #[serde(default)]
+    pub shadow_catcher: bool,
+    /// The points' movement over time, in the scene's coordinates, if they are not still
+    #[serde(default)]
+    pub motion: Option<Motion>,
+}
+
+fn default_radius() -> Float {
+    0.01
+}
+
+impl PointCloud {
+    /// Creates a new `PointCloud`, drawing its points as disks of the default radius.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{LightProperties, LinearColor};
+    /// # use pathtracer::material::UniformMaterial;
+    /// # use pathtracer::mesh::PointSet;
+    /// # use pathtracer::render::PointCloud;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::Point;
+    /// #
+    /// let cloud = PointCloud::new(
+    ///     PointSet::new(vec![Point::origin(), Point::new(1.0, 0.0, 0.0)]),
+    ///     UniformMaterial::new(
+    ///         LightProperties::new(
+    ///             LinearColor::new(0.8, 0.8, 0.8), // diffuse component
+    ///             LinearColor::new(0.0, 0.0, 0.0), // specular component
+    ///             None,
+    ///         ),
+    ///     ).into(),
+    ///     UniformTexture::new(LinearColor::new(1.0, 1.0, 1.0)).into(),
+    /// );
+    /// assert_eq!(cloud.into_objects().len(), 2);
+    /// ```
+    pub fn new(points: PointSet, material: MaterialEnum, texture: TextureEnum) -> Self {
+        PointCloud {
+            name: None,
+            points,
+            radius: default_radius(),
+            splat: Splat::default(),
+            up_axis: None,
+            handedness: None,
+            transform: Transform::default(),
+            material,
+            texture,
+            visibility: Visibility::default(),
+            shadow_catcher: false,
+            motion: None,
+        }
+    }
+
+    /// Convert the points from their own coordinate system into the scene's, then apply the
+    /// transformation to them, and return an [`Object`] for each of them.
+    ///
+    /// [`Object`]: ../object/struct.Object.html
+    pub fn into_scene_objects(mut self, scene: &CoordinateSystem) -> Vec<Object> {
+        let asset = CoordinateSystem::new(
+            self.up_axis.unwrap_or(scene.up_axis),
+            self.handedness.unwrap_or(scene.handedness),
+        );
+        if asset != *scene {
+            self.points.transform(&asset.conversion_to(scene));
+        }
+        self.into_objects()
+    }
+
+    /// Apply the transformation to the points, and return an [`Object`] for each of them.
+    ///
+    /// [`Object`]: ../object/struct.Object.html
+    pub fn into_objects(self) -> Vec<Object> {
+        let (name, mut points, material, texture) =
+            (self.name, self.points, self.material, self.texture);
+        let (splat, visibility, shadow_catcher, motion) = (
+            self.splat,
+            self.visibility,
+            self.shadow_catcher,
+            self.motion,
+        );
+        if points.radii().is_none() {
+            // Scale the default radius along with the points' own
+            let radii = vec![self.radius; points.positions().len()];
+            points = points.with_radii(radii).unwrap();
+        }
+        if !self.transform.is_identity() {
+            points.transform_affine(&self.transform.affine());
+        }
+        let radii = points.radii().unwrap();
+        (0..points.positions().len())
+            .map(|i| {
+                let (position, radius) = (points.positions()[i], radii[i]);
+                let shape: ShapeEnum = match (splat, points.normals()) {
+                    (Splat::Disk, Some(normals)) => {
+                        Disk::new(position, normals[i].into_inner(), radius).into()
+                    }
+                    _ => Sphere::new(position, radius).into(),
+                };
+                let texture = match points.colors() {
+                    Some(colors) => UniformTexture::new(colors[i].clone()).into(),
+                    None => texture.clone(),
+                };
+                Object {
+                    name: name.clone(),
+                    shape,
+                    material: material.clone(),
+                    texture,
+                    bump: None,
+                    visibility,
+                    shadow_catcher,
+                    motion: motion.clone(),
+                    // Splats are seen alike from both sides
+                    sides: Sides::Both,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{LinearColor, Rotation, Scale, TransformComponent};
+    use crate::{Point, Vector};
+
+    fn simple_point_cloud() -> PointCloud {
+        let points = PointSet::new(vec![Point::origin(), Point::new(1., 0., 0.)])
+            .with_normals(vec![Vector::y(), Vector::x()])
+            .unwrap();
+        PointCloud::new(points, default_material(), default_texture())
+    }
+
+    #[test]
+    fn new_works() {
+        let cloud = simple_point_cloud();
+        assert_eq!(cloud.radius, 0.01);
+        assert_eq!(cloud.splat, Splat::Disk);
+        assert_eq!(cloud.transform, Transform::default());
+    }
+
+    #[test]
+    fn into_objects_works() {
+        let objects = simple_point_cloud().into_objects();
+        let shapes: Vec<_> = objects.into_iter().map(|o| o.shape).collect();
+        assert_eq!(
+            shapes,
+            vec![
+                Disk::new(Point::origin(), Vector::y(), 0.01).into(),
+                Disk::new(Point::new(1., 0., 0.), Vector::x(), 0.01).into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn into_objects_draws_spheres() {
+        let mut cloud = simple_point_cloud();
+        cloud.splat = Splat::Sphere;
+        let objects = cloud.into_objects();
+        assert_eq!(
+            objects[1].shape,
+            Sphere::new(Point::new(1., 0., 0.), 0.01).into()
+        );
+        // Disks need a normal
+        let cloud = PointCloud::new(
+            PointSet::new(vec![Point::origin()]),
+            default_material(),
+            default_texture(),
+        );
+        let objects = cloud.into_objects();
+        assert_eq!(objects[0].shape, Sphere::new(Point::origin(), 0.01).into());
+    }
+
+    #[test]
+    fn into_objects_uses_colors() {
+        let red = LinearColor::new(1., 0., 0.);
+        let mut cloud = simple_point_cloud();
+        cloud.points = (cloud.points)
+            .with_colors(vec![red.clone(), LinearColor::black()])
+            .unwrap();
+        let objects = cloud.into_objects();
+        assert_eq!(objects[0].texture, UniformTexture::new(red).into());
+    }
+
+    #[test]
+    fn into_objects_applies_transform() {
+        let mut cloud = simple_point_cloud();
+        cloud.transform = Transform::new(vec![
+            TransformComponent::Scale(Scale::Uniform(2.)),
+            TransformComponent::Rotate(Rotation::Euler([0., 0., 90.])),
+        ]);
+        let objects = cloud.into_objects();
+        let disk = match &objects[1].shape {
+            ShapeEnum::Disk(disk) => disk.clone(),
+            shape => panic!("unexpected shape {:?}", shape),
+        };
+        assert!((disk.center() - Point::new(0., 2., 0.)).norm() < 1e-5);
+        assert!((disk.radius() - 0.02).abs() < 1e-5);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            positions:
+              - [0.0, 0.0, 0.0]
+              - [1.0, 0.0, 0.0]
+            normals:
+              - [0.0, 1.0, 0.0]
+              - [1.0, 0.0, 0.0]
+            radius: 0.5
+            splat: sphere
+        "#;
+        let cloud: PointCloud = serde_yaml::from_str(yaml).unwrap();
+        let mut expected = simple_point_cloud();
+        expected.radius = 0.5;
+        expected.splat = Splat::Sphere;
+        assert_eq!(cloud, expected)
+    }
+}
@@ -0,0 +1,148 @@
+//! Monitoring long renders through a periodically written thumbnail
+
+use image::{ImageFormat, Rgb, RgbImage};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A small preview of the image being rendered, periodically written to a PNG file, so that a
+/// render can be monitored by just refreshing the file.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::render::Preview;
+/// use std::time::Duration;
+///
+/// let preview = Preview::new("scene-preview.png", Duration::from_secs(10)).with_max_size(128);
+/// assert_eq!(preview.path().to_str(), Some("scene-preview.png"));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preview {
+    path: PathBuf,
+    interval: Duration,
+    max_size: u32,
+}
+
+impl Preview {
+    /// Write the preview to `path` every `interval`, while rendering.
+    pub fn new<P: Into<PathBuf>>(path: P, interval: Duration) -> Self {
+        Preview {
+            path: path.into(),
+            interval,
+            max_size: 256,
+        }
+    }
+
+    /// Downscale the preview until neither its width nor its height are larger than `size`.
+    pub fn with_max_size(mut self, size: u32) -> Self {
+        self.max_size = size.max(1);
+        self
+    }
+
+    /// Get the path to which the preview is written.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Start writing the preview of an image being rendered, of the given size.
+    pub(crate) fn start(&self, width: u32, height: u32) -> PreviewWriter<'_> {
+        let step = width.max(height).div_ceil(self.max_size).max(1);
+        let size = |length: u32| length.div_ceil(step);
+        PreviewWriter {
+            preview: self,
+            step,
+            image: Mutex::new(RgbImage::new(size(width), size(height))),
+            last_write: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+/// Gather the parts of an image being rendered, writing its [`Preview`] when due.
+///
+/// [`Preview`]: struct.Preview.html
+#[derive(Debug)]
+pub(crate) struct PreviewWriter<'a> {
+    preview: &'a Preview,
+    /// Only one out of `step` pixels is kept along each axis.
+    step: u32,
+    image: Mutex<RgbImage>,
+    last_write: Mutex<Instant>,
+}
+
+impl PreviewWriter<'_> {
+    /// Record the rendered pixels of a row of the image, starting at `x`, writing the preview if
+    /// it is due.
+    pub fn record<I: IntoIterator<Item = Rgb<u8>>>(&self, x: u32, y: u32, row: I) {
+        if y.is_multiple_of(self.step) {
+            let mut image = self.image.lock().unwrap();
+            for (x, pixel) in (x..).zip(row) {
+                if x.is_multiple_of(self.step) {
+                    image.put_pixel(x / self.step, y / self.step, pixel);
+                }
+            }
+        }
+        // Other threads are already writing it when locked
+        if let Ok(mut last_write) = self.last_write.try_lock() {
+            if last_write.elapsed() >= self.preview.interval {
+                self.write();
+                *last_write = Instant::now();
+            }
+        }
+    }
+
+    /// Write the preview of the finished image.
+    pub fn finish(&self) {
+        let _lock = self.last_write.lock().unwrap();
+        self.write();
+    }
+
+    fn write(&self) {
+        let image = self.image.lock().unwrap().clone();
+        // Write to a temporary file first, so that the preview is never seen half-written. A
+        // preview failing to be written should not stop the render.
+        let temporary = self.preview.path.with_extension("tmp");
+        if image.save_with_format(&temporary, ImageFormat::Png).is_ok() {
+            std::fs::rename(&temporary, &self.preview.path).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn downscaling_works() {
+        let preview = Preview::new("preview.png", Duration::from_secs(1)).with_max_size(4);
+        let writer = preview.start(10, 5);
+        assert_eq!(writer.step, 3);
+        assert_eq!(writer.image.lock().unwrap().dimensions(), (4, 2));
+        for y in 0..5 {
+            writer.record(0, y, (0..5).map(|x| Rgb([x as u8, y as u8, 0])));
+            writer.record(5, y, (5..10).map(|x| Rgb([x as u8, y as u8, 0])));
+        }
+        let image = writer.image.lock().unwrap();
+        assert_eq!(image.get_pixel(3, 1), &Rgb([9, 3, 0]));
+        assert_eq!(image.get_pixel(1, 0), &Rgb([3, 0, 0]));
+    }
+
+    #[test]
+    fn small_images_are_not_upscaled() {
+        let preview = Preview::new("preview.png", Duration::from_secs(1));
+        let writer = preview.start(16, 9);
+        assert_eq!(writer.step, 1);
+        assert_eq!(writer.image.lock().unwrap().dimensions(), (16, 9));
+    }
+
+    #[test]
+    fn preview_is_written() {
+        let path = std::env::temp_dir().join(format!("preview-{}.png", std::process::id()));
+        let preview = Preview::new(&path, Duration::from_secs(0));
+        let writer = preview.start(2, 1);
+        writer.record(0, 0, vec![Rgb([255, 0, 0]), Rgb([0, 255, 0])]);
+        let image = image::open(&path).unwrap().to_rgb8();
+        assert_eq!(image.get_pixel(1, 0), &Rgb([0, 255, 0]));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,412 @@
+//! Logic for the scene's scattered copies of a mesh
+
+use super::{MeshObject, Object};
+use crate::core::{sampling, CoordinateSystem, Rotation, Scale, Transform, TransformComponent};
+use crate::mesh::Mesh;
+use crate::shape::{Instance, Prototype, ShapeEnum, Triangle};
+use crate::{Float, Point, Vector};
+use nalgebra::{Unit, UnitQuaternion};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// The surface over which a [`Scatter`] places its copies.
+///
+/// [`Scatter`]: struct.Scatter.html
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ScatterTarget {
+    /// The surface's geometry
+    #[serde(flatten)]
+    pub mesh: Mesh,
+    /// The transformation placing the surface in the scene
+    #[serde(default)]
+    pub transform: Transform,
+}
+
+/// Copies of a mesh scattered at random over a surface, e.g: to cover a terrain with rocks or
+/// trees, turned into the copies' [`Object`]s when loading the scene.
+///
+/// The prototype's transformation and modifiers are applied once, its triangles being shared by
+/// every copy as an [`Instance`] of them. Each copy is placed with its origin on the surface, after
+/// being scaled and rotated around the scene's up axis by random amounts. The target surface
+/// itself is not rendered.
+///
+/// [`Instance`]: ../../shape/struct.Instance.html
+/// [`Object`]: ../object/struct.Object.html
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Scatter {
+    /// The mesh being copied
+    pub prototype: MeshObject,
+    /// The surface over which the copies are placed
+    pub target: ScatterTarget,
+    /// The average number of copies per unit of area
+    pub density: Float,
+    /// The range of the uniform scaling applied to each copy
+    #[serde(default = "default_scale")]
+    pub scale: [Float; 2],
+    /// The range of the rotation around the up axis applied to each copy, in degrees
+    #[serde(default = "default_rotation")]
+    pub rotation: [Float; 2],
+    /// Whether the copies' up axis is aligned with the surface's normal, instead of staying
+    /// upright
+    #[serde(default)]
+    pub align: bool,
+    /// The seed used to place the copies
+    #[serde(default)]
+    pub seed: u64,
+}
+
+fn default_scale() -> [Float; 2] {
+    [1., 1.]
+}
+
+fn default_rotation() -> [Float; 2] {
+    [0., 360.]
+}
+
+impl Scatter {
+    /// Creates a new `Scatter`, with copies which are neither scaled nor aligned with the surface,
+    /// and rotated in any direction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{LightProperties, LinearColor, Transform};
+    /// # use pathtracer::material::UniformMaterial;
+    /// # use pathtracer::mesh::Mesh;
+    /// # use pathtracer::render::{MeshObject, Scatter, ScatterTarget};
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::Point;
+    /// #
+    /// let pebble = MeshObject::new(
+    ///     Mesh::new(
+    ///         vec![
+    ///             Point::new(0.0, 0.0, 0.0),
+    ///             Point::new(0.1, 0.0, 0.0),
+    ///             Point::new(0.0, 0.1, 0.0),
+    ///         ],
+    ///         vec![[0, 1, 2]],
+    ///     )
+    ///     .unwrap(),
+    ///     UniformMaterial::new(
+    ///         LightProperties::new(
+    ///             LinearColor::new(0.5, 0.5, 0.5), // diffuse component
+    ///             LinearColor::new(0.0, 0.0, 0.0), // specular component
+    ///             None,
+    ///         ),
+    ///     ).into(),
+    ///     UniformTexture::new(LinearColor::new(1.0, 1.0, 1.0)).into(),
+    /// );
+    /// let ground = ScatterTarget {
+    ///     mesh: Mesh::new(
+    ///         vec![
+    ///             Point::new(0.0, 0.0, 0.0),
+    ///             Point::new(0.0, 0.0, 10.0),
+    ///             Point::new(10.0, 0.0, 0.0),
+    ///         ],
+    ///         vec![[0, 1, 2]],
+    ///     )
+    ///     .unwrap(),
+    ///     transform: Transform::default(),
+    /// };
+    /// // About 2 pebbles per unit of area, over a surface of 50
+    /// let scatter = Scatter::new(pebble, ground, 2.0, 42);
+    /// assert_eq!(scatter.into_objects().len(), 100);
+    /// ```
+    pub fn new(prototype: MeshObject, target: ScatterTarget, density: Float, seed: u64) -> Self {
+        Scatter {
+            prototype,
+            target,
+            density,
+            scale: default_scale(),
+            rotation: default_rotation(),
+            align: false,
+            seed,
+        }
+    }
+
+    /// Compute the position of each copy, and the normal of the surface at that point.
+    fn placements(&self, rng: &mut StdRng) -> Vec<(Point, Unit<Vector>)> {
+        let mut surface = self.target.mesh.clone();
+        if !self.target.transform.is_identity() {
+            surface.transform_affine(&self.target.transform.affine());
+        }
+        let faces: Vec<_> = surface
+            .faces()
+            .iter()
+            .map(|&[a, b, c]| {
+                let vertices = surface.vertices();
+                (
+                    vertices[a],
+                    vertices[b] - vertices[a],
+                    vertices[c] - vertices[a],
+                )
+            })
+            .collect();
+        // The cross product's norm is twice the face's area
+        let cumulative: Vec<Float> = faces
+            .iter()
+            .scan(0., |total, (_, u, v)| {
+                *total += u.cross(v).norm() / 2.;
+                Some(*total)
+            })
+            .collect();
+        let area = cumulative.last().copied().unwrap_or(0.);
+        if area <= 0. {
+            return Vec::new();
+        }
+        let count = (self.density * area + rng.gen::<Float>()).floor() as usize;
+        (0..count)
+            .map(|_| {
+                let target = rng.gen::<Float>() * area;
+                let index = cumulative
+                    .partition_point(|&total| total <= target)
+                    .min(faces.len() - 1);
+                let (origin, u, v) = &faces[index];
+                let (s, t) = sampling::uniform_triangle(rng.gen(), rng.gen());
+                let normal = Unit::try_new(u.cross(v), 0.).unwrap_or_else(Vector::y_axis);
+                (origin + u * s + v * t, normal)
+            })
+            .collect()
+    }
+
+    /// Convert the prototype from its own coordinate system into the scene's, then return the
+    /// [`Object`]s of each of its copies.
+    ///
+    /// [`Object`]: ../object/struct.Object.html
+    pub fn into_scene_objects(mut self, scene: &CoordinateSystem) -> Vec<Object> {
+        let asset = CoordinateSystem::new(
+            self.prototype.up_axis.unwrap_or(scene.up_axis),
+            self.prototype.handedness.unwrap_or(scene.handedness),
+        );
+        if asset != *scene {
+            self.prototype.mesh.transform(&asset.conversion_to(scene));
+        }
+        self.prototype.up_axis = None;
+        self.prototype.handedness = None;
+        self.into_objects_with_up(scene.up())
+    }
+
+    /// Return the [`Object`]s of each of the copies, using the Y axis as the up axis.
+    ///
+    /// [`Object`]: ../object/struct.Object.html
+    pub fn into_objects(self) -> Vec<Object> {
+        self.into_objects_with_up(Vector::y_axis())
+    }
+
+    fn into_objects_with_up(self, up: Unit<Vector>) -> Vec<Object> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let placements = self.placements(&mut rng);
+        let [min_scale, max_scale] = self.scale;
+        let [min_angle, max_angle] = self.rotation;
+        let prototypes = prototypes(self.prototype);
+        let mut objects = Vec::new();
+        for (position, normal) in placements {
+            let scale = min_scale + (max_scale - min_scale) * rng.gen::<Float>();
+            let angle = min_angle + (max_angle - min_angle) * rng.gen::<Float>();
+            let mut rotation = UnitQuaternion::from_axis_angle(&up, angle.to_radians());
+            if self.align {
+                // Both possible up axes are orthogonal to X
+                let alignment =
+                    UnitQuaternion::rotation_between_axis(&up, &normal).unwrap_or_else(|| {
+                        UnitQuaternion::from_axis_angle(&Vector::x_axis(), crate::consts::PI)
+                    });
+                rotation = alignment * rotation;
+            }
+            let placement = Transform::default()
+                .then(TransformComponent::Scale(Scale::Uniform(scale)))
+                .then(TransformComponent::Rotate(Rotation::from(rotation)))
+                .then(TransformComponent::Translate(position.coords))
+                .affine();
+            for (template, prototype) in prototypes.iter() {
+                // Copies scaled down to nothing cannot be seen
+                if let Some(instance) = Instance::new(prototype.clone(), placement) {
+                    objects.push(template.reshaped(instance.into()));
+                }
+            }
+        }
+        objects
+    }
+}
+
+/// Apply the transformation and the modifiers of the mesh, then group its faces by how they are
+/// shaded, returning the shared [`Prototype`] of each group along with an [`Object`] holding the
+/// group's properties.
+///
+/// [`Object`]: ../object/struct.Object.html
+/// [`Prototype`]: ../../shape/struct.Prototype.html
+fn prototypes(mesh: MeshObject) -> Vec<(Object, Arc<Prototype>)> {
+    let mut groups: Vec<(Object, Vec<Triangle>)> = Vec::new();
+    for object in mesh.into_objects() {
+        // Meshes are only made of triangles
+        let triangle = match &object.shape {
+            ShapeEnum::Triangle(triangle) => triangle.clone(),
+            _ => continue,
+        };
+        let group = (groups.iter_mut()).find(|(template, _)| {
+            template.material == object.material && template.texture == object.texture
+        });
+        match group {
+            Some((_, triangles)) => triangles.push(triangle),
+            None => groups.push((object, vec![triangle])),
+        }
+    }
+    (groups.into_iter())
+        .map(|(template, triangles)| (template, Arc::new(Prototype::new(triangles))))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{LightProperties, LinearColor};
+    use crate::material::UniformMaterial;
+    use crate::shape::Shape;
+    use crate::texture::UniformTexture;
+
+    fn pole() -> MeshObject {
+        // A thin vertical triangle, with its base at the origin
+        let mesh = Mesh::new(
+            vec![
+                Point::new(0., 0., 0.),
+                Point::new(0.01, 0., 0.),
+                Point::new(0., 1., 0.),
+            ],
+            vec![[0, 1, 2]],
+        )
+        .unwrap();
+        let material = UniformMaterial::new(LightProperties::new(
+            LinearColor::new(0.5, 0.5, 0.5),
+            LinearColor::black(),
+            None,
+        ));
+        let texture = UniformTexture::new(LinearColor::new(1., 1., 1.));
+        MeshObject::new(mesh, material.into(), texture.into())
+    }
+
+    fn floor() -> ScatterTarget {
+        // A 2x2 square, facing up
+        let mesh = Mesh::new(
+            vec![
+                Point::new(-1., 0., -1.),
+                Point::new(1., 0., -1.),
+                Point::new(1., 0., 1.),
+                Point::new(-1., 0., 1.),
+            ],
+            vec![[0, 2, 1], [0, 3, 2]],
+        )
+        .unwrap();
+        ScatterTarget {
+            mesh,
+            transform: Transform::default(),
+        }
+    }
+
+    fn bases(objects: &[Object]) -> Vec<Point> {
+        objects
+            .iter()
+            .map(|object| object.shape.centroid())
+            .collect()
+    }
+
+    #[test]
+    fn new_works() {
+        assert_eq!(
+            Scatter::new(pole(), floor(), 10., 42),
+            Scatter {
+                prototype: pole(),
+                target: floor(),
+                density: 10.,
+                scale: [1., 1.],
+                rotation: [0., 360.],
+                align: false,
+                seed: 42,
+            }
+        )
+    }
+
+    #[test]
+    fn copies_cover_the_target() {
+        let objects = Scatter::new(pole(), floor(), 10., 42).into_objects();
+        assert_eq!(objects.len(), 40);
+        for point in bases(&objects) {
+            assert!(point.x.abs() <= 1.01 && point.z.abs() <= 1.01);
+            // The poles are kept upright, the center of their bounding box being halfway up
+            assert!((point.y - 0.5).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn copies_are_scaled_and_aligned() {
+        let mut target = floor();
+        // Turn the floor into a wall facing +X
+        target.transform = Transform::new(vec![TransformComponent::Rotate(Rotation::Euler([
+            0., 0., -90.,
+        ]))]);
+        let mut scatter = Scatter::new(pole(), target, 10., 42);
+        scatter.scale = [2., 2.];
+        scatter.align = true;
+        for point in bases(&scatter.into_objects()) {
+            assert!((point.x - 1.).abs() < 1e-4, "{}", point);
+        }
+    }
+
+    #[test]
+    fn copies_share_the_prototype() {
+        let objects = Scatter::new(pole(), floor(), 10., 42).into_objects();
+        let prototypes: Vec<_> = (objects.iter())
+            .map(|object| match &object.shape {
+                ShapeEnum::Instance(instance) => instance.prototype().clone(),
+                shape => panic!("{:?} is not an instance", shape),
+            })
+            .collect();
+        assert!(prototypes.iter().all(|p| Arc::ptr_eq(p, &prototypes[0])));
+        assert_eq!(prototypes[0].triangles().len(), 1);
+    }
+
+    #[test]
+    fn seed_is_reproducible() {
+        let objects = |seed| Scatter::new(pole(), floor(), 10., seed).into_objects();
+        assert_eq!(objects(1), objects(1));
+        assert_ne!(objects(1), objects(2));
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            prototype:
+              vertices:
+                - [0.0, 0.0, 0.0]
+                - [0.01, 0.0, 0.0]
+                - [0.0, 1.0, 0.0]
+              faces:
+                - [0, 1, 2]
+              material:
+                type: uniform
+                diffuse: {r: 0.5, g: 0.5, b: 0.5}
+                specular: {r: 0.0, g: 0.0, b: 0.0}
+              texture:
+                type: uniform
+                color: {r: 1.0, g: 1.0, b: 1.0}
+            target:
+              vertices:
+                - [-1.0, 0.0, -1.0]
+                - [1.0, 0.0, -1.0]
+                - [1.0, 0.0, 1.0]
+                - [-1.0, 0.0, 1.0]
+              faces:
+                - [0, 2, 1]
+                - [0, 3, 2]
+            density: 10.0
+            scale: [0.5, 2.0]
+            align: true
+            seed: 42
+        "#;
+        let scatter: Scatter = serde_yaml::from_str(yaml).unwrap();
+        let mut expected = Scatter::new(pole(), floor(), 10., 42);
+        expected.scale = [0.5, 2.];
+        expected.align = true;
+        assert_eq!(scatter, expected)
+    }
+}
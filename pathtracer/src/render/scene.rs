@@ -1,30 +1,184 @@
 //! Scene rendering logic
 
-use super::{light_aggregate::LightAggregate, object::Object, utils::*};
+#[cfg(feature = "gpu")]
+use super::gpu::{Device, GpuError, GpuScene};
+use super::{
+    ambient_occlusion::AmbientOcclusion,
+    aovs::Aovs,
+    bvh_cache,
+    hit_info::HitInfo,
+    integrator::{Integrator, IntegratorEnum},
+    light_aggregate::LightAggregate,
+    light_contributions::LightContributions,
+    limits::{LimitTracker, RenderLimits},
+    mesh_object::MeshObject,
+    object::Object,
+    overlay,
+    point_cloud::PointCloud,
+    preview::{Preview, PreviewWriter},
+    scatter::Scatter,
+    splat::{Film, Sample, SplatBuffer},
+    statistics::{self, IntersectionCounter, IntersectionStatistics},
+    tiles::{Tile, TileOrder},
+    utils::*,
+};
+use crate::Error;
+use crate::Float;
 use crate::{
-    core::{Camera, LightProperties, LinearColor, ReflTransEnum},
-    material::Material,
-    shape::Shape,
-    texture::Texture,
+    background::{Background, BackgroundEnum},
+    core::{
+        Camera, CoordinateSystem, Crop, Filter, FrameBuffer, Handedness, LightProperties,
+        LinearColor, Precision, ReflTransEnum, Sampler, UpAxis,
+    },
+    light::{LightLink, SkyLight, SpatialLight},
+    material::{Material, MaterialEnum, SurfaceProbe, SurfaceSignals},
+    post::{Denoiser, FilmGrain, Outline},
+    serialize::Looks,
+    shape::{Hit, Shape, ShapeEnum},
+    texture::{Texture, TextureEnum},
     {Point, Vector},
 };
-use beevee::{bvh::BVH, ray::Ray};
-use image::RgbImage;
+use beevee::{
+    bvh::{BVH, BVH4},
+    ray::Ray,
+};
+use image::{ImageBuffer, Pixel, Rgb, RgbImage, Rgba, RgbaImage};
 use nalgebra::Unit;
 use rand::prelude::thread_rng;
-use rand::Rng;
-use serde::{Deserialize, Deserializer};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::de::Error as _;
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::{Range, RangeInclusive};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The color in which the outline of the lights is drawn.
+const LIGHT_OUTLINE_COLOR: Rgb<u8> = Rgb([255, 255, 0]);
+
+/// The size of light markers, relative to their distance to the camera.
+const LIGHT_MARKER_SIZE: Float = 0.1;
+
+/// The height, as a ratio of the image's, at which lights without a position are drawn.
+const LIGHT_ANCHOR_HEIGHT: Float = 0.15;
+
+/// The most cut out parts of an object's surface a ray goes through before stopping on it.
+const MAX_CUTOUT_CROSSINGS: u32 = 16;
+
+/// The number of rays cast together by [`Scene::cast_packet`].
+///
+/// [`Scene::cast_packet`]: struct.Scene.html#method.cast_packet
+const PACKET_SIZE: usize = 4;
+
+/// Only keep the samples which are at most `factor` times as bright as their median, which is
+/// always kept when `factor` is at least 1.
+fn reject_outliers(samples: Vec<Sample>, factor: Float) -> Vec<Sample> {
+    let mut luminances: Vec<_> = samples
+        .iter()
+        .map(|sample| sample.layers[0].luminance())
+        .collect();
+    luminances.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let threshold = factor * luminances[luminances.len() / 2];
+    samples
+        .into_iter()
+        .filter(|sample| sample.layers[0].luminance() <= threshold)
+        .collect()
+}
+
+/// Estimate the variance of the mean luminance of `samples`, from their unbiased sample variance.
+///
+/// There is no estimate with fewer than two samples, which are given a variance of zero.
+fn mean_variance(samples: &[Sample]) -> Float {
+    if samples.len() < 2 {
+        return 0.;
+    }
+    let luminances: Vec<_> = samples.iter().map(|s| s.layers[0].luminance()).collect();
+    let count = luminances.len() as Float;
+    let mean = luminances.iter().sum::<Float>() / count;
+    let spread = luminances
+        .iter()
+        .map(|l| (l - mean) * (l - mean))
+        .sum::<Float>();
+    spread / (count * (count - 1.))
+}
+
+/// Build the sample of the image at `(x, y)`, made of its color followed by the contribution of
+/// each tracked light.
+fn sample(x: Float, y: Float, color: LinearColor, lights: LightContributions) -> Sample {
+    let layers = std::iter::once(color)
+        .chain(lights.values().iter().cloned())
+        .collect();
+    Sample { x, y, layers }
+}
+
+/// The buffers of the contributions of several lights, along with their names.
+type LightBuffers<'a> = Vec<(&'a str, FrameBuffer)>;
+
+/// How the BVH of a scene's objects is built.
+#[serde(rename_all = "lowercase")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum BvhBuilder {
+    /// Split the objects using the surface area heuristic, for the fastest renders.
+    #[default]
+    Sah,
+    /// Sort the objects along a Morton curve, building the BVH much faster at the cost of slower
+    /// renders, e.g: for quick previews of large scenes.
+    Linear,
+    /// Split the space as well as the objects, referencing objects straddling a split from both
+    /// sides, for faster renders of scenes with long thin triangles.
+    Spatial {
+        /// The fraction of additional references to the objects allowed, 0.3 by default.
+        #[serde(default = "default_split_budget")]
+        budget: Float,
+    },
+}
+
+fn default_split_budget() -> Float {
+    0.3
+}
+
+/// A function reporting the progress of a render, see [`Scene::set_progress`].
+///
+/// [`Scene::set_progress`]: struct.Scene.html#method.set_progress
+pub type Progress = Arc<dyn Fn(u64, u64) + Send + Sync>;
 
 /// Represent the scene being rendered.
 pub struct Scene {
     camera: Camera,
-    lights: LightAggregate,
-    objects: Vec<Object>,
-    bvh: BVH,
-    background: LinearColor,
-    aliasing_limit: u32,
-    reflection_limit: u32,
-    diffraction_index: f32,
+    cameras: BTreeMap<String, Camera>,
+    pub(crate) lights: LightAggregate,
+    pub(crate) objects: Vec<Object>,
+    pub(crate) bvh: BVH4,
+    pub(crate) bounded_count: usize,
+    light_samples: Option<u32>,
+    counters: Option<Vec<IntersectionCounter>>,
+    pub(crate) background: BackgroundEnum,
+    integrator: IntegratorEnum,
+    grain: Option<FilmGrain>,
+    denoiser: Option<Denoiser>,
+    outline: Option<Outline>,
+    crop: Option<Crop>,
+    precision: Precision,
+    preview: Option<Preview>,
+    progress: Option<Progress>,
+    filter: Filter,
+    sampler: Sampler,
+    tile_order: TileOrder,
+    outlier_rejection: Option<Float>,
+    ambient_occlusion: Option<AmbientOcclusion>,
+    bvh_builder: BvhBuilder,
+    bvh_cache: Option<PathBuf>,
+    bvh_cache_error: Option<Error>,
+    frames: Option<RangeInclusive<u32>>,
+    frame: u32,
+    pub(crate) aliasing_limit: u32,
+    pub(crate) reflection_limit: u32,
+    pub(crate) diffraction_index: Float,
 }
 
 impl Scene {
@@ -56,7 +210,7 @@ impl Scene {
     ///             UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5)).into(),
     ///         ),
     ///     ],
-    ///     LinearColor::black(), // Background color
+    ///     LinearColor::black().into(), // Background color
     ///     5,   // aliasing limit
     ///     3,   // reflection recursion limit
     ///     0.0, // diffraction index
@@ -66,301 +220,4612 @@ impl Scene {
         camera: Camera,
         lights: LightAggregate,
         mut objects: Vec<Object>,
-        background: LinearColor,
+        background: BackgroundEnum,
+        aliasing_limit: u32,
+        reflection_limit: u32,
+        diffraction_index: Float,
+    ) -> Self {
+        let bounded_count = partition_bounded(&mut objects);
+        let (bvh, order) = BVH::build_order(&objects[..bounded_count]);
+        apply_order(&mut objects, &order);
+        Self::with_bvh(
+            camera,
+            lights,
+            objects,
+            (BVH4::collapse(&bvh), bounded_count),
+            background,
+            aliasing_limit,
+            reflection_limit,
+            diffraction_index,
+        )
+    }
+
+    /// Creates a new `Scene` whose bounded objects are stored first, in the order of `bvh`.
+    #[allow(clippy::too_many_arguments)]
+    fn with_bvh(
+        camera: Camera,
+        lights: LightAggregate,
+        objects: Vec<Object>,
+        (bvh, bounded_count): (BVH4, usize),
+        background: BackgroundEnum,
         aliasing_limit: u32,
         reflection_limit: u32,
-        diffraction_index: f32,
+        diffraction_index: Float,
     ) -> Self {
-        // NOTE(Antoine): fun fact: BVH::build stack overflows when given an empty slice :)
-        let bvh = BVH::build(&mut objects);
+        // A physically based sky lights the scene, as well as being seen in the background
+        let lights = match &background {
+            BackgroundEnum::PhysicalSkyBackground(sky) if sky.samples() > 0 => {
+                lights.with_sky_light(SkyLight::new(sky.clone()))
+            }
+            _ => lights,
+        };
         Scene {
             camera,
+            cameras: BTreeMap::new(),
             lights,
             objects,
             bvh,
+            bounded_count,
+            light_samples: None,
+            counters: None,
             background,
+            integrator: IntegratorEnum::default(),
+            grain: None,
+            denoiser: None,
+            outline: None,
+            crop: None,
+            precision: Precision::default(),
+            preview: None,
+            progress: None,
+            filter: Filter::default(),
+            sampler: Sampler::default(),
+            tile_order: TileOrder::default(),
+            outlier_rejection: None,
+            ambient_occlusion: None,
+            bvh_builder: BvhBuilder::default(),
+            bvh_cache: None,
+            bvh_cache_error: None,
+            frames: None,
+            frame: 0,
             aliasing_limit,
             reflection_limit,
             diffraction_index,
         }
     }
 
-    /// Render the scene into an image.
-    pub fn render(&self) -> RgbImage {
-        let mut image = RgbImage::new(self.camera.film().width(), self.camera.film().height());
-
-        let total = (image.width() * image.height()) as u64;
-        let pb = indicatif::ProgressBar::new(total);
-        pb.set_draw_delta(total / 10000);
-        pb.set_style(indicatif::ProgressStyle::default_bar().template(
-            "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {percent:>3}%: {pos}/{len} pixels (ETA: {eta})",
-        ));
-
-        let pixel_func = if self.aliasing_limit > 0 {
-            Self::anti_alias_pixel
-        } else {
-            Self::pixel
-        };
+    /// Add a named [`Camera`] to the scene, which can be rendered alongside the main one.
+    ///
+    /// [`Camera`]: ../../core/camera/struct.Camera.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LightProperties, LinearColor};
+    /// # use pathtracer::material::UniformMaterial;
+    /// # use pathtracer::render::{LightAggregate, Object, Scene};
+    /// # use pathtracer::shape::Sphere;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::{Point, Vector};
+    /// # use pathtracer::Float;
+    /// #
+    /// # let mut scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![
+    /// #         Object::new(
+    /// #             Sphere::new(Point::new(5.0, 0.0, 0.0), 1.0).into(),
+    /// #             UniformMaterial::new(
+    /// #                 LightProperties::new(
+    /// #                     LinearColor::new(1.0, 0.0, 0.0), // diffuse component
+    /// #                     LinearColor::new(0.0, 0.0, 0.0), // specular component
+    /// #                     None,
+    /// #                 ),
+    /// #             ).into(),
+    /// #             UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5)).into(),
+    /// #         ),
+    /// #     ],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     0,   // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// scene.add_camera(
+    ///     "top",
+    ///     Camera::new(
+    ///         Point::new(5.0, 5.0, 0.0),
+    ///         Vector::new(0.0, -1.0, 0.0),
+    ///         Vector::new(1.0, 0.0, 0.0),
+    ///         2. * Float::atan(1.), /* 90° in radian */
+    ///         1.0,
+    ///         16,
+    ///         16,
+    ///     ),
+    /// );
+    /// let images = scene.render_cameras();
+    /// assert_eq!(images.len(), 1);
+    /// assert_eq!(images[0].0, "top");
+    /// assert_eq!(images[0].1.dimensions(), (16, 16));
+    /// ```
+    pub fn add_camera(&mut self, name: &str, camera: Camera) {
+        self.cameras.insert(name.to_string(), camera);
+    }
 
-        rayon::scope(|s| {
-            // FIXME(Bruno): it would go even faster to cut the image in blocks of rows, leading to
-            // better cache-line behaviour...
-            for (_, row) in image.enumerate_rows_mut() {
-                s.spawn(|_| {
-                    for (x, y, pixel) in row {
-                        *pixel = pixel_func(&self, x as f32, y as f32).into();
-                        pb.inc(1);
-                    }
-                })
-            }
-        });
+    /// Set the [`Integrator`] used to compute the color of each camera ray, ray tracing by default.
+    ///
+    /// [`Integrator`]: integrator/trait.Integrator.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LightProperties, LinearColor};
+    /// # use pathtracer::material::UniformMaterial;
+    /// # use pathtracer::render::{LightAggregate, Object, Pathtracer, Scene};
+    /// # use pathtracer::shape::Sphere;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::Point;
+    /// #
+    /// # let mut scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![
+    /// #         Object::new(
+    /// #             Sphere::new(Point::new(5.0, 0.0, 0.0), 1.0).into(),
+    /// #             UniformMaterial::new(
+    /// #                 LightProperties::new(
+    /// #                     LinearColor::new(1.0, 0.0, 0.0), // diffuse component
+    /// #                     LinearColor::new(0.0, 0.0, 0.0), // specular component
+    /// #                     None,
+    /// #                 ),
+    /// #             ).into(),
+    /// #             UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5)).into(),
+    /// #         ),
+    /// #     ],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     5,   // aliasing limit
+    /// #     8,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// // Start Russian roulette after 3 bounces
+    /// scene.set_integrator(Pathtracer::new(3).into());
+    /// ```
+    pub fn set_integrator(&mut self, integrator: IntegratorEnum) {
+        self.integrator = integrator;
+    }
 
-        pb.finish();
-        image
+    /// Start counting the intersection tests made against each object, to find out which ones are
+    /// the most expensive to render.
+    ///
+    /// Counting has a small cost on rendering speed, which is why it must be enabled explicitly.
+    /// Enabling it again resets the counters.
+    pub fn enable_statistics(&mut self) {
+        let counters = self.objects.iter().map(|_| Default::default()).collect();
+        self.counters = Some(counters);
     }
 
-    /// Get pixel color for (x, y) a pixel **coordinate**
-    fn pixel(&self, x: f32, y: f32) -> LinearColor {
-        let (x, y) = self.camera.film().pixel_ratio(x, y);
-        let pixel = self.camera.film().pixel_at_ratio(x, y);
-        let direction = Unit::new_normalize(pixel - self.camera.origin());
-        let indices = RefractionInfo::with_index(self.diffraction_index);
-        self.cast_ray(Ray::new(pixel, direction)).map_or_else(
-            || self.background.clone(),
-            |(t, obj)| {
-                self.color_at(
-                    pixel + direction.as_ref() * t,
-                    obj,
-                    direction,
-                    self.reflection_limit,
-                    indices,
-                )
-            },
-        )
+    /// Return the intersection tests made since statistics were enabled, grouping the objects which
+    /// share the same name, sorted from the most tested to the least tested.
+    ///
+    /// Returns `None` if statistics were not enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LightProperties, LinearColor};
+    /// # use pathtracer::material::UniformMaterial;
+    /// # use pathtracer::render::{LightAggregate, Object, Scene};
+    /// # use pathtracer::shape::Sphere;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// # let mut scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![
+    /// #         Object::new(
+    /// #             Sphere::new(Point::origin(), 1.0).into(),
+    /// #             UniformMaterial::new(
+    /// #                 LightProperties::new(
+    /// #                     LinearColor::new(1.0, 0.0, 0.0), // diffuse component
+    /// #                     LinearColor::new(0.0, 0.0, 0.0), // specular component
+    /// #                     None,
+    /// #                 ),
+    /// #             ).into(),
+    /// #             UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5)).into(),
+    /// #         )
+    /// #         .with_name("ball"),
+    /// #     ],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     0,   // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// assert!(scene.intersection_statistics().is_none());
+    ///
+    /// scene.enable_statistics();
+    /// scene.raycast(Point::new(-5.0, 0.0, 0.0), Vector::x());
+    /// scene.raycast(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.1, 0.0));
+    ///
+    /// let stats = scene.intersection_statistics().unwrap();
+    /// assert_eq!(stats[0].name, Some("ball"));
+    /// assert_eq!(stats[0].tests, 2);
+    /// assert_eq!(stats[0].hits, 2);
+    /// ```
+    pub fn intersection_statistics(&self) -> Option<Vec<IntersectionStatistics<'_>>> {
+        self.counters
+            .as_ref()
+            .map(|counters| statistics::gather(&self.objects, counters))
     }
 
-    /// Get pixel color with anti-aliasing
-    fn anti_alias_pixel(&self, x: f32, y: f32) -> LinearColor {
-        let range = 0..self.aliasing_limit;
-        let mut rng = thread_rng();
-        let acc: LinearColor = range
-            .map(|_| {
-                let random_x: f32 = rng.gen();
-                let random_y: f32 = rng.gen();
-                self.pixel(x + random_x, y + random_y)
-            })
-            .map(LinearColor::clamp)
-            .sum();
-        acc / self.aliasing_limit as f32
+    /// Only sample this many of the lights which have a position at each shading point, choosing
+    /// them according to their estimated contribution, instead of looking at every light.
+    ///
+    /// Lights which are infinitely far away, and ambient lights, are always taken into account.
+    /// This trades noise for speed in scenes with many lights, use `None` to use every light.
+    pub fn set_light_samples(&mut self, samples: Option<u32>) {
+        self.light_samples = samples;
     }
 
-    fn cast_ray(&self, ray: Ray) -> Option<(f32, &Object)> {
-        self.bvh.walk(&ray, &self.objects)
+    /// Add film grain to the rendered images, or remove it with `None`.
+    pub fn set_grain(&mut self, grain: Option<FilmGrain>) {
+        self.grain = grain;
     }
 
-    fn color_at(
-        &self,
-        point: Point,
-        object: &Object,
-        incident_ray: Unit<Vector>,
-        reflection_limit: u32,
-        mut indices: RefractionInfo,
-    ) -> LinearColor {
-        let texel = object.shape.project_texel(&point);
-        let properties = object.material.properties(texel);
-        let object_color = object.texture.texel_color(texel);
+    /// Remove the noise of the rendered images, or keep it with `None`.
+    ///
+    /// The denoiser is guided by the albedo and normal of the surfaces seen by the camera, which
+    /// are rendered along with each image.
+    pub fn set_denoiser(&mut self, denoiser: Option<Denoiser>) {
+        self.denoiser = denoiser;
+    }
 
-        let normal = object.shape.normal(&point);
-        let reflected_ray = reflected(incident_ray, normal);
+    /// Draw outlines along the silhouettes and creases of the objects, or remove them with
+    /// `None`.
+    ///
+    /// Like the denoiser, the outlines are found from the normal and depth of the surfaces seen
+    /// by the camera, which are rendered along with each image, and drawn after denoising it.
+    pub fn set_outline(&mut self, outline: Option<Outline>) {
+        self.outline = outline;
+    }
 
-        let lighting = self.illuminate(point, object_color, &properties, normal, reflected_ray);
-        if properties.refl_trans.is_none() {
-            // Avoid calculating reflection when not needed
-            return lighting;
+    /// Replace the named materials and textures of the objects, e.g: when they are edited while
+    /// rendering with a [`LookWatcher`].
+    ///
+    /// The scene is left untouched if an object refers to a look which is missing from `looks`.
+    ///
+    /// [`LookWatcher`]: ../serialize/struct.LookWatcher.html
+    pub fn set_looks(&mut self, looks: &Looks) -> crate::Result<()> {
+        // Check every name first, so that the objects are never left with mismatched looks
+        for object in self.objects.iter() {
+            if let MaterialEnum::SharedMaterial(shared) = &object.material {
+                if !looks.materials.contains_key(shared.name()) {
+                    return Err(Error::unknown_name("material", shared.name()));
+                }
+            }
+            if let TextureEnum::SharedTexture(shared) = &object.texture {
+                if !looks.textures.contains_key(shared.name()) {
+                    return Err(Error::unknown_name("texture", shared.name()));
+                }
+            }
         }
-        let reflected = self.reflection(point, reflected_ray, reflection_limit, indices.clone());
-        // We can unwrap safely thanks to the check for None before
-        match properties.refl_trans.unwrap() {
-            ReflTransEnum::Transparency { coef, index } => {
-                // Calculate the refracted ray, if it was refracted, and mutate indices accordingly
-                refracted(incident_ray, normal, &mut indices, index).map_or_else(
-                    // Total reflection
-                    || reflected.clone(),
-                    // Refraction (refracted ray, amount of *reflection*)
-                    |(r, refl_t)| {
-                        let refracted = self.refraction(point, coef, r, reflection_limit, indices);
-                        let refr_light = refracted * (1. - refl_t) + reflected.clone() * refl_t;
-                        refr_light * coef + lighting * (1. - coef)
-                    },
-                )
+        for object in self.objects.iter_mut() {
+            if let MaterialEnum::SharedMaterial(shared) = &mut object.material {
+                shared.resolve(&looks.materials)?;
+            }
+            if let TextureEnum::SharedTexture(shared) = &mut object.texture {
+                shared.resolve(&looks.textures)?;
             }
-            ReflTransEnum::Reflectivity { coef } => reflected * coef + lighting * (1. - coef),
         }
+        Ok(())
     }
 
-    fn refraction(
-        &self,
-        point: Point,
-        transparency: f32,
-        refracted: Unit<Vector>,
-        reflection_limit: u32,
-        indices: RefractionInfo,
-    ) -> LinearColor {
-        if transparency > 1e-5 && reflection_limit > 0 {
-            let refraction_start = point + refracted.as_ref() * 0.001;
-            if let Some((t, obj)) = self.cast_ray(Ray::new(refraction_start, refracted)) {
-                let resulting_position = refraction_start + refracted.as_ref() * t;
-                let refracted = self.color_at(
-                    resulting_position,
-                    obj,
-                    refracted,
-                    reflection_limit - 1,
-                    indices,
-                );
-                return refracted * transparency;
-            }
-        }
-        LinearColor::black()
+    /// Only render a region of the images, which are cropped to it, or render them whole with
+    /// `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, Crop, LinearColor};
+    /// # use pathtracer::render::{LightAggregate, Scene};
+    /// #
+    /// # let mut scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     0,   // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// // Only render the bottom-right quarter of the 1080x1080 image
+    /// scene.set_crop(Some(Crop::Ratios([0.5, 0.5, 0.5, 0.5])));
+    /// assert_eq!(scene.render().dimensions(), (540, 540));
+    /// ```
+    pub fn set_crop(&mut self, crop: Option<Crop>) {
+        self.crop = crop;
     }
 
-    fn reflection(
-        &self,
-        point: Point,
-        reflected: Unit<Vector>,
-        reflection_limit: u32,
-        indices: RefractionInfo,
-    ) -> LinearColor {
-        if reflection_limit > 0 {
-            let reflection_start = point + reflected.as_ref() * 0.001;
-            if let Some((t, obj)) = self.cast_ray(Ray::new(reflection_start, reflected)) {
-                let resulting_position = reflection_start + reflected.as_ref() * t;
-                let color = self.color_at(
-                    resulting_position,
-                    obj,
-                    reflected,
-                    reflection_limit - 1,
-                    indices,
-                );
-                return color;
+    /// Draw the outline of each light over an image rendered from the main camera, e.g: by
+    /// [`render`], to check where they are and where they shine.
+    ///
+    /// Lights which are infinitely far away are drawn as arrows along the top of the image.
+    ///
+    /// [`render`]: #method.render
+    pub fn draw_lights(&self, image: &mut RgbImage) {
+        let camera = &self.camera;
+        let (left, top, _, _) = self.window(camera);
+        let directionals = self.lights.distant_lights_iter().count();
+        let mut directional = 0;
+        for light in self.lights.spatial_lights_iter() {
+            let anchor = match light.position() {
+                Some(position) => position,
+                None => {
+                    directional += 1;
+                    let x = directional as Float / (directionals + 1) as Float;
+                    camera.film().pixel_at_ratio(x, LIGHT_ANCHOR_HEIGHT)
+                }
+            };
+            // Keep the markers the same size on screen, wherever the lights are
+            let size = LIGHT_MARKER_SIZE * camera.depth(&anchor).abs();
+            for segment in light.outline(&anchor, size) {
+                overlay::draw_segment(image, camera, (left, top), segment, LIGHT_OUTLINE_COLOR);
             }
-        };
-        LinearColor::black()
+        }
     }
 
-    fn illuminate(
-        &self,
-        point: Point,
-        object_color: LinearColor,
-        properties: &LightProperties,
-        normal: Unit<Vector>,
-        reflected: Unit<Vector>,
-    ) -> LinearColor {
-        let ambient = self.illuminate_ambient(object_color.clone());
-        let spatial = self.illuminate_spatial(point, properties, normal, reflected);
-        ambient + object_color * spatial
+    /// Store the floating point images computed while rendering, such as the contribution of each
+    /// light, at this precision.
+    ///
+    /// Colors are computed at single precision, and only rounded when stored, once the rows of
+    /// samples around them have been rendered: using [`Precision::Half`] halves the memory used by
+    /// renders with many lights, at the cost of about 3 significant digits.
+    ///
+    /// [`Precision::Half`]: ../../core/framebuffer/enum.Precision.html#variant.Half
+    pub fn set_precision(&mut self, precision: Precision) {
+        self.precision = precision;
     }
 
-    fn illuminate_ambient(&self, color: LinearColor) -> LinearColor {
-        self.lights
-            .ambient_lights_iter()
-            .map(|light| color.clone() * light.illumination(&Point::origin()))
-            .map(LinearColor::clamp)
-            .sum()
+    /// Set the filter weighting the anti-aliasing samples added to each pixel, a box covering the
+    /// pixel by default.
+    ///
+    /// Each sample is taken within a pixel, and added to every pixel whose filter covers it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, Filter, LinearColor};
+    /// # use pathtracer::render::{LightAggregate, Scene};
+    /// #
+    /// # let mut scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     16,  // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// scene.set_filter(Filter::mitchell(2.0));
+    /// ```
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
     }
 
-    fn illuminate_spatial(
-        &self,
-        point: Point,
-        properties: &LightProperties,
-        normal: Unit<Vector>,
-        reflected: Unit<Vector>,
-    ) -> LinearColor {
-        self.lights
-            .spatial_lights_iter()
-            .map(|light| {
-                let (direction, t) = light.to_source(&point);
-                let light_ray = Ray::new(point + 0.001 * direction.as_ref(), direction);
-                match self.cast_ray(light_ray) {
-                    // Take shadows into account
-                    Some((obstacle_t, _)) if obstacle_t < t => return LinearColor::black(),
-                    _ => {}
-                }
-                let lum = light.illumination(&point);
-                let diffused = properties.diffuse.clone() * normal.dot(&direction);
-                let specular = properties.specular.clone() * reflected.dot(&direction);
-                lum * (diffused + specular)
-            })
-            .map(LinearColor::clamp)
-            .sum()
+    /// Set how the samples of the pixels and of the lights are spread over them, independent
+    /// random points for the pixels and stratified ones for the lights by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LinearColor, Sampler};
+    /// # use pathtracer::render::{LightAggregate, Scene};
+    /// #
+    /// # let mut scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     16,  // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// scene.set_sampler(Sampler::Cmj);
+    /// ```
+    pub fn set_sampler(&mut self, sampler: Sampler) {
+        self.sampler = sampler;
     }
-}
 
-#[derive(Debug, PartialEq, Deserialize)]
-struct SerializedScene {
-    camera: Camera,
-    #[serde(default)]
-    lights: LightAggregate,
-    #[serde(default)]
-    objects: Vec<Object>,
-    #[serde(default)]
-    background: LinearColor,
-    #[serde(default)]
-    aliasing_limit: u32,
-    #[serde(default)]
-    reflection_limit: u32,
-    #[serde(default = "crate::serialize::default_identity")]
-    starting_diffraction: f32,
-}
+    /// Set the order in which the parts of the image are rendered, e.g: so that a preview shows
+    /// the center of the image first. The image is rendered row by row by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LinearColor};
+    /// # use pathtracer::render::{LightAggregate, Scene, TileOrder};
+    /// #
+    /// # let mut scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     0,   // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// scene.set_tile_order(TileOrder::Spiral);
+    /// ```
+    pub fn set_tile_order(&mut self, order: TileOrder) {
+        self.tile_order = order;
+    }
 
-impl From<SerializedScene> for Scene {
-    fn from(scene: SerializedScene) -> Self {
-        Scene::new(
-            scene.camera,
-            scene.lights,
-            scene.objects,
-            scene.background,
-            scene.aliasing_limit,
-            scene.reflection_limit,
-            scene.starting_diffraction,
-        )
+    /// Discard the anti-aliasing samples of a pixel which are more than `factor` times as bright
+    /// as their median, averaging the remaining ones, or keep every sample with `None`.
+    ///
+    /// This removes the bright speckles left by paths rarely reaching a light, at the cost of
+    /// darkening the image slightly. The factor is at least 1, so that the median is kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LinearColor};
+    /// # use pathtracer::render::{LightAggregate, Scene};
+    /// #
+    /// # let mut scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     16,  // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// scene.set_outlier_rejection(Some(4.0));
+    /// ```
+    pub fn set_outlier_rejection(&mut self, factor: Option<Float>) {
+        self.outlier_rejection = factor.map(|factor| factor.max(1.));
     }
-}
 
-impl<'de> Deserialize<'de> for Scene {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let cam: SerializedScene = Deserialize::deserialize(deserializer)?;
-        Ok(cam.into())
+    /// Darken the light received from ambient lights where the scene's geometry occludes it, or
+    /// light every point uniformly with `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LinearColor};
+    /// # use pathtracer::render::{AmbientOcclusion, LightAggregate, Scene};
+    /// #
+    /// # let mut scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     16,  // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// // Contact shadows within half a unit of the occluding geometry
+    /// scene.set_ambient_occlusion(Some(AmbientOcclusion::new(0.5)));
+    /// ```
+    pub fn set_ambient_occlusion(&mut self, occlusion: Option<AmbientOcclusion>) {
+        self.ambient_occlusion = occlusion;
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    /// Periodically write a small preview of the main camera's image while rendering it, or stop
+    /// doing so with `None`.
+    ///
+    /// Previews are ignored on WebAssembly, which has neither a clock nor files to write them to.
+    pub fn set_preview(&mut self, preview: Option<Preview>) {
+        self.preview = preview.filter(|_| !cfg!(target_arch = "wasm32"));
+    }
 
-    #[test]
-    fn deserialization_works() {
-        let yaml = std::include_str!("../../examples/scene.yaml");
-        let _: Scene = serde_yaml::from_str(yaml).unwrap();
+    /// Report the progress of each render to the given function, or stop doing so with `None`.
+    ///
+    /// The function is called from the rendering threads with the number of pixels rendered so
+    /// far, and the total number of pixels to render.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LinearColor};
+    /// # use pathtracer::render::{LightAggregate, Scene};
+    /// # use pathtracer::{Point, Vector};
+    /// # use std::sync::atomic::{AtomicU64, Ordering};
+    /// # use std::sync::Arc;
+    /// #
+    /// # let mut scene = Scene::new(
+    /// #     Camera::new(Point::origin(), Vector::z(), Vector::y(), 90., 1., 8, 6),
+    /// #     LightAggregate::empty(),
+    /// #     vec![],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     0,   // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// let rendered = Arc::new(AtomicU64::new(0));
+    /// let counter = rendered.clone();
+    /// scene.set_progress(Some(Arc::new(move |done, _| {
+    ///     counter.fetch_max(done, Ordering::Relaxed);
+    /// })));
+    /// scene.render_with_seed(0);
+    /// assert_eq!(rendered.load(Ordering::Relaxed), 8 * 6);
+    /// ```
+    pub fn set_progress(&mut self, progress: Option<Progress>) {
+        self.progress = progress;
+    }
+
+    /// Animate the scene over a range of frames, or stop animating it with `None`.
+    ///
+    /// The instants at which cameras and objects are placed by their [`Motion`] are counted in
+    /// frames: frame `n` is rendered at instant `n`, the cameras' shutter being relative to it.
+    ///
+    /// [`Motion`]: ../../core/motion/struct.Motion.html
+    pub fn set_frames(&mut self, frames: Option<RangeInclusive<u32>>) {
+        self.frames = frames;
+    }
+
+    /// Get the error which prevented writing the scene's BVH cache when loading it, if any.
+    ///
+    /// The scene can still be rendered, its BVH is only built again the next time it is loaded.
+    pub fn bvh_cache_error(&self) -> Option<&Error> {
+        self.bvh_cache_error.as_ref()
+    }
+
+    /// Get the range of frames over which the scene is animated, if any.
+    pub fn frames(&self) -> Option<RangeInclusive<u32>> {
+        self.frames.clone()
+    }
+
+    /// Move the scene to a frame, which is rendered by the next calls to [`render`] and the other
+    /// rendering methods.
+    ///
+    /// The bounding volume hierarchy is kept between frames, its bounds being refit to only cover
+    /// the movement of the objects while the cameras' shutters are open during the frame.
+    ///
+    /// [`render`]: #method.render
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, Keyframe, LightProperties, LinearColor, Motion};
+    /// # use pathtracer::material::UniformMaterial;
+    /// # use pathtracer::render::{LightAggregate, Object, Scene};
+    /// # use pathtracer::shape::Sphere;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// // A ball rolling along the X axis, one unit per frame
+    /// let motion = Motion::new(
+    ///     Point::origin(),
+    ///     vec![
+    ///         Keyframe::new(0.0, Vector::zeros()),
+    ///         Keyframe::new(10.0, Vector::new(10.0, 0.0, 0.0)),
+    ///     ],
+    /// );
+    /// let ball = Object::new(
+    ///     Sphere::new(Point::new(0.0, 0.0, 5.0), 0.5).into(),
+    ///     UniformMaterial::new(LightProperties::new(
+    ///         LinearColor::new(1.0, 0.0, 0.0), // diffuse component
+    ///         LinearColor::new(0.0, 0.0, 0.0), // specular component
+    ///         None,
+    ///     ))
+    ///     .into(),
+    ///     UniformTexture::new(LinearColor::new(1.0, 1.0, 1.0)).into(),
+    /// )
+    /// .with_motion(motion);
+    /// let mut scene = Scene::new(
+    ///     Camera::default(),
+    ///     LightAggregate::empty(),
+    ///     vec![ball],
+    ///     LinearColor::black().into(), // Background color
+    ///     0,   // aliasing limit
+    ///     0,   // reflection recursion limit
+    ///     1.0, // diffraction index
+    /// );
+    /// scene.set_frames(Some(1..=10));
+    ///
+    /// scene.set_frame(3);
+    /// assert!(scene.raycast(Point::new(3.0, 0.0, 0.0), Vector::z()).is_some());
+    /// assert!(scene.raycast(Point::origin(), Vector::z()).is_none());
+    /// ```
+    pub fn set_frame(&mut self, frame: u32) {
+        let cameras = std::iter::once(&self.camera).chain(self.cameras.values());
+        let (open, close) = cameras.map(Camera::shutter).fold(
+            (Float::INFINITY, Float::NEG_INFINITY),
+            |(open, close), shutter| (open.min(shutter[0]), close.max(shutter[1])),
+        );
+        let (start, end) = (frame as Float + open, frame as Float + close);
+        let objects = &self.objects[..self.bounded_count];
+        self.bvh
+            .refit_with(objects, |object| object.aabb_between(start, end));
+        self.frame = frame;
+    }
+
+    /// Get the frame being rendered, 0 unless [`set_frame`] was called.
+    ///
+    /// [`set_frame`]: #method.set_frame
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Render the scene into an image.
+    pub fn render(&self) -> RgbImage {
+        self.render_with_seed(thread_rng().gen())
+    }
+
+    /// Render the scene once for each named camera, sorted by name, sharing the scene's objects
+    /// and acceleration structure between each rendering.
+    pub fn render_cameras(&self) -> Vec<(&str, RgbImage)> {
+        let seed = thread_rng().gen();
+        self.cameras
+            .iter()
+            .map(|(name, camera)| {
+                let limits = RenderLimits::new();
+                let progress = self.progress.as_ref();
+                let (image, _) = self.render_camera(camera, seed, &limits, None, progress);
+                (name.as_str(), image)
+            })
+            .collect()
+    }
+
+    /// Render the scene into an image, drawing every random sample from the given seed.
+    ///
+    /// Rendering the same scene with the same seed always results in the same image, regardless
+    /// of how the work was scheduled across threads.
+    pub fn render_with_seed(&self, seed: u64) -> RgbImage {
+        self.render_with_limits(seed, &RenderLimits::new()).0
+    }
+
+    /// Render the scene into an image like [`render_with_seed`], reporting its progress to the
+    /// given function instead of the one given to [`set_progress`], e.g: to render the scene from
+    /// several threads at once, each of them following its own render.
+    ///
+    /// [`render_with_seed`]: #method.render_with_seed
+    /// [`set_progress`]: #method.set_progress
+    pub fn render_with_progress(&self, seed: u64, progress: &Progress) -> RgbImage {
+        let (camera, preview) = (&self.camera, self.preview.as_ref());
+        let limits = RenderLimits::new();
+        self.render_camera(camera, seed, &limits, preview, Some(progress))
+            .0
+    }
+
+    /// Render the scene into an image like [`render_with_seed`], stopping early once any of the
+    /// given limits is reached.
+    ///
+    /// Returns the image along with whether it was completely rendered, the pixels which were
+    /// not rendered being left black.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LinearColor};
+    /// # use pathtracer::render::{LightAggregate, RenderLimits, Scene};
+    /// #
+    /// # let scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     0,   // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// let (_, complete) = scene.render_with_limits(0, &RenderLimits::new());
+    /// assert!(complete);
+    /// let (_, complete) = scene.render_with_limits(0, &RenderLimits::new().with_pixel_budget(10));
+    /// assert!(!complete);
+    /// ```
+    ///
+    /// [`render_with_seed`]: #method.render_with_seed
+    pub fn render_with_limits(&self, seed: u64, limits: &RenderLimits) -> (RgbImage, bool) {
+        let (camera, preview) = (&self.camera, self.preview.as_ref());
+        self.render_camera(camera, seed, limits, preview, self.progress.as_ref())
+    }
+
+    /// Render the scene into a floating point image, keeping the colors as they were computed,
+    /// e.g: to save them with more than 8 bits per channel.
+    ///
+    /// The image is stored at the scene's [`Precision`], and does not get any film grain, which
+    /// is only added to 8-bit images.
+    ///
+    /// [`Precision`]: ../../core/framebuffer/enum.Precision.html
+    pub fn render_buffer(&self) -> FrameBuffer {
+        self.render_buffer_with_limits(thread_rng().gen(), &RenderLimits::new())
+            .0
+    }
+
+    /// Render the scene into a floating point image like [`render_buffer`], drawing every random
+    /// sample from the given seed, and stopping early once any of the given limits is reached.
+    ///
+    /// Returns the image along with whether it was completely rendered, the pixels which were
+    /// not rendered being left black.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LinearColor};
+    /// # use pathtracer::render::{LightAggregate, RenderLimits, Scene};
+    /// #
+    /// # let scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![],
+    /// #     LinearColor::new(2.0, 1.0, 0.5).into(), // Background color
+    /// #     0,   // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// let (buffer, complete) = scene.render_buffer_with_limits(0, &RenderLimits::new());
+    /// assert!(complete);
+    /// // Colors brighter than white are kept
+    /// assert_eq!(buffer.get(0, 0), LinearColor::new(2.0, 1.0, 0.5));
+    /// ```
+    ///
+    /// [`render_buffer`]: #method.render_buffer
+    pub fn render_buffer_with_limits(
+        &self,
+        seed: u64,
+        limits: &RenderLimits,
+    ) -> (FrameBuffer, bool) {
+        let (camera, preview) = (&self.camera, self.preview.as_ref());
+        self.render_camera_buffer(camera, seed, limits, preview, self.progress.as_ref())
+    }
+
+    /// Render the scene into an image on the GPU, drawing every random sample from the given
+    /// seed, as an approximation of [`render_with_seed`] for fast previews.
+    ///
+    /// Falls back to rendering on the CPU when no GPU can be used, or when the scene uses
+    /// features which the GPU renderer does not support, returning the reason along with the
+    /// image.
+    ///
+    /// [`render_with_seed`]: #method.render_with_seed
+    #[cfg(feature = "gpu")]
+    pub fn render_gpu_with_seed(&self, seed: u64) -> (RgbImage, Option<GpuError>) {
+        let camera = &self.camera;
+        let render = || {
+            let scene = GpuScene::new(self, camera, self.window(camera), seed)?;
+            Device::new()?.render(&scene, self.precision)
+        };
+        match render() {
+            Ok(buffer) => {
+                let progress = self.progress.as_ref();
+                let mut image = self.post_process(camera, seed, buffer, progress).to_image();
+                if let Some(grain) = &self.grain {
+                    grain.apply(&mut image, seed);
+                }
+                (image, None)
+            }
+            Err(err) => (self.render_with_seed(seed), Some(err)),
+        }
+    }
+
+    /// Render the shadows cast onto the shadow catcher objects, ready to be composited over a
+    /// background plate.
+    ///
+    /// Each pixel is black, its opacity being the fraction of the light which is blocked before
+    /// reaching a shadow catcher. Pixels showing any other object, or the background, are fully
+    /// transparent.
+    pub fn render_shadows(&self) -> RgbaImage {
+        self.render_shadows_with_seed(thread_rng().gen())
+    }
+
+    /// Render the shadow pass, drawing every random sample from the given seed.
+    pub fn render_shadows_with_seed(&self, seed: u64) -> RgbaImage {
+        let limits = RenderLimits::new();
+        let tracker = limits.start();
+        let progress = self.progress.as_ref();
+        self.render_pixels(&self.camera, seed, &tracker, None, progress, |x, y, rng| {
+            let alpha = self.shadow_pixel(&self.camera, x, y, rng);
+            // Casting saturates to the channel's range, and turns NaNs into 0
+            Rgba([0, 0, 0, (alpha * 255.).round() as u8])
+        })
+    }
+
+    /// Render the scene into an image, along with the contribution of each named light to it,
+    /// sorted by name.
+    ///
+    /// The contributions add up to the rendered image, except for the light coming from unnamed
+    /// lights or from the background, and for the clamping of overly bright samples. Lights
+    /// sharing the same name are rendered together.
+    pub fn render_lights(&self) -> (RgbImage, Vec<(&str, RgbImage)>) {
+        self.render_lights_with_seed(thread_rng().gen())
+    }
+
+    /// Render the scene and the contribution of each named light, drawing every random sample
+    /// from the given seed.
+    ///
+    /// The rendered image is the same as the one returned by [`render_with_seed`].
+    ///
+    /// [`render_with_seed`]: #method.render_with_seed
+    pub fn render_lights_with_seed(&self, seed: u64) -> (RgbImage, Vec<(&str, RgbImage)>) {
+        let (beauty, _, lights, _) = self.render_light_buffers(seed);
+        let progress = self.progress.as_ref();
+        let mut image = self
+            .post_process(&self.camera, seed, beauty, progress)
+            .to_image();
+        if let Some(grain) = &self.grain {
+            grain.apply(&mut image, seed);
+        }
+        let lights = (lights.into_iter())
+            .map(|(name, buffer)| (name, buffer.to_image()))
+            .collect();
+        (image, lights)
+    }
+
+    /// Render the scene into a floating point image, along with its arbitrary output variables:
+    /// the albedo, normal, and depth of the surfaces seen by the camera, the variance of each
+    /// pixel, and the contribution of each named light.
+    pub fn render_aovs(&self) -> Aovs {
+        self.render_aovs_with_seed(thread_rng().gen())
+    }
+
+    /// Render the scene and its arbitrary output variables, drawing every random sample from the
+    /// given seed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LightProperties, LinearColor};
+    /// # use pathtracer::material::UniformMaterial;
+    /// # use pathtracer::render::{LightAggregate, Object, Scene};
+    /// # use pathtracer::shape::Sphere;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::Point;
+    /// # use pathtracer::Float;
+    /// #
+    /// # let scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![Object::new(
+    /// #         Sphere::new(Point::new(2.0, 0.0, 0.0), 1.0).into(),
+    /// #         UniformMaterial::new(LightProperties::new(
+    /// #             LinearColor::new(1.0, 0.5, 0.0), // diffuse component
+    /// #             LinearColor::new(0.0, 0.0, 0.0), // specular component
+    /// #             None,
+    /// #         ))
+    /// #         .into(),
+    /// #         UniformTexture::new(LinearColor::new(1.0, 1.0, 1.0)).into(),
+    /// #     )],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     0,   // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     1.0, // diffraction index
+    /// # );
+    /// // The camera looks at a sphere from the origin
+    /// let aovs = scene.render_aovs_with_seed(0);
+    /// let (width, height) = aovs.beauty.dimensions();
+    /// let center = (width / 2, height / 2);
+    /// assert_eq!(aovs.albedo.get(center.0, center.1), LinearColor::new(1.0, 0.5, 0.0));
+    /// assert!((aovs.depth.get(center.0, center.1).r - 1.0).abs() < 1e-2);
+    /// assert_eq!(aovs.depth.get(0, 0).r, Float::INFINITY);
+    /// ```
+    pub fn render_aovs_with_seed(&self, seed: u64) -> Aovs {
+        let (beauty, variance, lights, groups) = self.render_light_buffers(seed);
+        let (albedo, normal, depth) =
+            self.render_surfaces(&self.camera, seed, self.progress.as_ref());
+        let beauty = match &self.denoiser {
+            Some(denoiser) => denoiser.apply(&beauty, &albedo, &normal),
+            None => beauty,
+        };
+        let beauty = match &self.outline {
+            Some(outline) => outline.apply(&beauty, &normal, &depth),
+            None => beauty,
+        };
+        Aovs {
+            beauty,
+            albedo,
+            normal,
+            depth,
+            variance,
+            lights: (lights.into_iter())
+                .map(|(name, buffer)| (name.to_string(), buffer))
+                .collect(),
+            groups: (groups.into_iter())
+                .map(|(name, buffer)| (name.to_string(), buffer))
+                .collect(),
+        }
+    }
+
+    /// Render the albedo, normal, and depth of the surfaces seen by the camera, reporting the
+    /// progress of the render to `progress`.
+    fn render_surfaces(
+        &self,
+        camera: &Camera,
+        seed: u64,
+        progress: Option<&Progress>,
+    ) -> (FrameBuffer, FrameBuffer, FrameBuffer) {
+        let (_, _, width, height) = self.window(camera);
+        // The surfaces are sampled separately, so that the image is the same as without them
+        let pixels = self.render_tiles(camera, !seed, progress, |y, xs, rng| {
+            xs.map(|x| {
+                let (albedo, normal, depth) =
+                    self.surface_pixel(camera, x as Float, y as Float, rng);
+                vec![
+                    albedo,
+                    LinearColor::new(normal.x, normal.y, normal.z),
+                    LinearColor::new(depth, 0., 0.),
+                ]
+            })
+            .collect()
+        });
+        let mut surfaces = self.split_layers(width, height, 3, pixels);
+        let depth = surfaces.pop().unwrap();
+        let normal = surfaces.pop().unwrap();
+        (surfaces.pop().unwrap(), normal, depth)
+    }
+
+    /// Remove the noise of an image rendered from the camera, then draw the outlines over it,
+    /// if a denoiser or outlines are set. The progress of rendering the surfaces they need is
+    /// reported to `progress`.
+    fn post_process(
+        &self,
+        camera: &Camera,
+        seed: u64,
+        image: FrameBuffer,
+        progress: Option<&Progress>,
+    ) -> FrameBuffer {
+        if self.denoiser.is_none() && self.outline.is_none() {
+            return image;
+        }
+        let (albedo, normal, depth) = self.render_surfaces(camera, seed, progress);
+        let image = match &self.denoiser {
+            Some(denoiser) => denoiser.apply(&image, &albedo, &normal),
+            None => image,
+        };
+        match &self.outline {
+            Some(outline) => outline.apply(&image, &normal, &depth),
+            None => image,
+        }
+    }
+
+    /// Render the scene's main camera into a floating point image, along with the variance of each
+    /// of its pixels, and the contribution of each named light, and of each group of lights.
+    fn render_light_buffers(
+        &self,
+        seed: u64,
+    ) -> (FrameBuffer, FrameBuffer, LightBuffers<'_>, LightBuffers<'_>) {
+        let camera = &self.camera;
+        let count = self.lights.contributions_count();
+        let (_, _, width, height) = self.window(camera);
+        let preview = self.preview.as_ref().map(|p| p.start(width, height));
+        let limits = RenderLimits::new();
+        let (mut buffers, variance) = self.render_samples(
+            camera,
+            seed,
+            count,
+            &limits.start(),
+            preview.as_ref(),
+            self.progress.as_ref(),
+        );
+        if let Some(preview) = &preview {
+            preview.finish();
+        }
+        // The rendered image, followed by the contribution of each light and of each group
+        let mut lights = buffers.split_off(1);
+        let groups = lights.split_off(self.lights.light_names().len());
+        let names = self.lights.light_names().iter().map(String::as_str);
+        let group_names = self.lights.light_groups().iter().map(String::as_str);
+        (
+            buffers.pop().unwrap(),
+            variance,
+            names.zip(lights).collect(),
+            group_names.zip(groups).collect(),
+        )
+    }
+
+    fn render_camera(
+        &self,
+        camera: &Camera,
+        seed: u64,
+        limits: &RenderLimits,
+        preview: Option<&Preview>,
+        progress: Option<&Progress>,
+    ) -> (RgbImage, bool) {
+        let (buffer, complete) = self.render_camera_buffer(camera, seed, limits, preview, progress);
+        let mut image = buffer.to_image();
+        if let Some(grain) = &self.grain {
+            grain.apply(&mut image, seed);
+        }
+        (image, complete)
+    }
+
+    fn render_camera_buffer(
+        &self,
+        camera: &Camera,
+        seed: u64,
+        limits: &RenderLimits,
+        preview: Option<&Preview>,
+        progress: Option<&Progress>,
+    ) -> (FrameBuffer, bool) {
+        let tracker = limits.start();
+        let (_, _, width, height) = self.window(camera);
+        let preview = preview.map(|preview| preview.start(width, height));
+        let (mut buffers, _) =
+            self.render_samples(camera, seed, 0, &tracker, preview.as_ref(), progress);
+        if let Some(preview) = &preview {
+            preview.finish();
+        }
+        let buffer = buffers.swap_remove(0);
+        (
+            self.post_process(camera, seed, buffer, progress),
+            !tracker.stopped(),
+        )
+    }
+
+    /// Render the rendered region of the camera's image, returning a buffer per layer: its color
+    /// followed by the contribution of each of the `lights` tracked lights, along with the
+    /// variance of the luminance of each pixel.
+    ///
+    /// The samples of each pixel are splatted onto the pixels around it through the scene's
+    /// filter, and each row is only normalized once all of the rows around it have been rendered,
+    /// before being stored at the scene's precision. Pixels which the `tracker` skipped take no
+    /// samples, each row is recorded into the `preview`, and the progress of the render is
+    /// reported to `progress`.
+    fn render_samples(
+        &self,
+        camera: &Camera,
+        seed: u64,
+        lights: usize,
+        tracker: &LimitTracker,
+        preview: Option<&PreviewWriter>,
+        progress: Option<&Progress>,
+    ) -> (Vec<FrameBuffer>, FrameBuffer) {
+        let window = self.window(camera);
+        let (left, top, _, _) = window;
+        // Without anti-aliasing, the single sample at the center of a pixel is kept to itself
+        let filter = if self.aliasing_limit > 0 {
+            self.filter
+        } else {
+            Filter::default()
+        };
+        let film = Mutex::new(Film::new(window, &filter, lights + 1, self.precision));
+        self.render_rows(camera, seed, progress, |y, xs, rng| {
+            let start = xs.start;
+            let mut splats = SplatBuffer::around(xs.clone(), y..y + 1, window, &filter, lights + 1);
+            let mut colors = Vec::with_capacity(xs.len());
+            let mut variances = Vec::with_capacity(xs.len());
+            for x in xs.clone() {
+                let (samples, variance) = if tracker.next_pixel() {
+                    self.sample_pixel(camera, x as Float, y as Float, lights, rng)
+                } else {
+                    (Vec::new(), 0.)
+                };
+                for sample in samples.iter() {
+                    splats.splat(&filter, sample);
+                }
+                // The preview cannot wait for the samples of the next rows
+                let total: LinearColor = samples.iter().map(|s| s.layers[0].clone()).sum();
+                colors.push(total / samples.len().max(1) as Float);
+                variances.push(variance);
+            }
+            if let Some(preview) = preview {
+                preview.record(start - left, y - top, colors.into_iter().map(Into::into));
+            }
+            film.lock().unwrap().add(y, xs, &splats, &variances);
+        });
+        film.into_inner().unwrap().finish()
+    }
+
+    /// Compute each pixel of the camera's image in parallel, leaving the ones which the
+    /// `tracker` skipped black, recording each row into the `preview`, and reporting the progress
+    /// of the render to `progress`.
+    fn render_pixels<P>(
+        &self,
+        camera: &Camera,
+        seed: u64,
+        tracker: &LimitTracker,
+        preview: Option<&PreviewWriter>,
+        progress: Option<&Progress>,
+        pixel: impl Fn(Float, Float, &mut StdRng) -> P + Sync,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8> + Send + 'static,
+    {
+        let (left, top, width, height) = self.window(camera);
+        let pixels = self.render_tiles(camera, seed, progress, |y, xs, rng| {
+            let start = xs.start;
+            let row = xs
+                .map(|x| {
+                    tracker
+                        .next_pixel()
+                        .then(|| pixel(x as Float, y as Float, rng))
+                })
+                .collect::<Vec<_>>();
+            if let Some(preview) = preview {
+                let black = Rgb([0, 0, 0]);
+                let colors = row.iter().map(|p| p.map_or(black, |p| p.to_rgb()));
+                preview.record(start - left, y - top, colors);
+            }
+            row
+        });
+        let mut image = ImageBuffer::new(width, height);
+        for (i, value) in (0..).zip(pixels) {
+            if let Some(value) = value {
+                image.put_pixel(i % width, i / width, value);
+            }
+        }
+        image
+    }
+
+    /// Get the `(x, y, width, height)` of the region of the camera's image which is rendered.
+    fn window(&self, camera: &Camera) -> (u32, u32, u32, u32) {
+        let (width, height) = (camera.film().width(), camera.film().height());
+        match &self.crop {
+            Some(crop) => crop.window(width, height),
+            None => (0, 0, width, height),
+        }
+    }
+
+    /// Compute each pixel of the rendered region of the camera's image in parallel, tile by tile
+    /// in the scene's tile order, returning them row by row. Each row of a tile is computed given
+    /// its ordinate, the abscissas of its pixels, and a generator of its own, reporting the
+    /// progress of the render to `progress`.
+    fn render_tiles<P: Send>(
+        &self,
+        camera: &Camera,
+        seed: u64,
+        progress: Option<&Progress>,
+        row: impl Fn(u32, Range<u32>, &mut StdRng) -> Vec<P> + Sync,
+    ) -> Vec<P> {
+        // The rows of the tiles cover the region, and are sorted from its top left corner
+        (self.render_rows(camera, seed, progress, row).into_iter())
+            .flat_map(|(_, _, row)| row)
+            .collect()
+    }
+
+    /// Compute each row of the tiles of the rendered region of the camera's image in parallel,
+    /// tile by tile in the scene's tile order, returning them along with their ordinate and
+    /// abscissas, sorted from the top left corner of the region. Each row of a tile is computed
+    /// given its ordinate, the abscissas of its pixels, and a generator of its own. The progress
+    /// of the render is reported to `progress`.
+    fn render_rows<R: Send>(
+        &self,
+        camera: &Camera,
+        seed: u64,
+        progress: Option<&Progress>,
+        row: impl Fn(u32, Range<u32>, &mut StdRng) -> R + Sync,
+    ) -> Vec<(u32, Range<u32>, R)> {
+        let window = self.window(camera);
+        let (left, _, width, height) = window;
+        let tiles = self.tile_order.tiles(window);
+        let mut rows: Vec<Vec<R>> = tiles.iter().map(|_| Vec::new()).collect();
+
+        let total = (width * height) as u64;
+        #[cfg(feature = "progress")]
+        let pb = {
+            let pb = indicatif::ProgressBar::new(total);
+            pb.set_draw_delta(total / 10000);
+            pb.set_style(indicatif::ProgressStyle::default_bar().template(
+                "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {percent:>3}%: {pos}/{len} pixels (ETA: {eta})",
+            ));
+            pb
+        };
+        let done = AtomicU64::new(0);
+        let report = |pixels: u64| {
+            #[cfg(feature = "progress")]
+            pb.inc(pixels);
+            if let Some(progress) = progress {
+                progress(done.fetch_add(pixels, Ordering::Relaxed) + pixels, total);
+            }
+        };
+        let render_tile = |tile: &Tile, value: &mut Vec<R>| {
+            for y in tile.ys.clone() {
+                // Each row of a tile gets its own generator to be independent of the scheduling
+                // order
+                let offset = (tile.xs.start - left) as u64;
+                let rng_seed = seed ^ (y as u64).rotate_left(32) ^ offset;
+                let mut rng = StdRng::seed_from_u64(rng_seed);
+                value.push(row(y, tile.xs.clone(), &mut rng));
+                report(tile.xs.len() as u64);
+            }
+        };
+
+        // Start the tiles in order, so that the first ones are the first to be finished
+        #[cfg(feature = "parallel")]
+        rayon::scope_fifo(|s| {
+            for (tile, value) in tiles.iter().zip(rows.iter_mut()) {
+                let render_tile = &render_tile;
+                s.spawn_fifo(move |_| render_tile(tile, value))
+            }
+        });
+        #[cfg(not(feature = "parallel"))]
+        for (tile, value) in tiles.iter().zip(rows.iter_mut()) {
+            render_tile(tile, value);
+        }
+
+        #[cfg(feature = "progress")]
+        pb.finish();
+        // Every tile has been computed once the scope is over
+        let mut rows: Vec<_> = (tiles.iter().zip(rows))
+            .flat_map(|(tile, tile_rows)| {
+                let xs = tile.xs.clone();
+                tile.ys
+                    .clone()
+                    .zip(tile_rows)
+                    .map(move |(y, row)| (y, xs.clone(), row))
+            })
+            .collect();
+        rows.sort_by_key(|(y, xs, _)| (*y, xs.start));
+        rows
+    }
+
+    /// Split the pixels of the rendered region, row by row, each made of a color per layer, into
+    /// a buffer per layer.
+    fn split_layers(
+        &self,
+        width: u32,
+        height: u32,
+        layers: usize,
+        pixels: Vec<Vec<LinearColor>>,
+    ) -> Vec<FrameBuffer> {
+        let mut buffers: Vec<_> = (0..layers)
+            .map(|_| FrameBuffer::new(width, height, self.precision))
+            .collect();
+        for (i, pixel) in (0..).zip(pixels) {
+            for (buffer, color) in buffers.iter_mut().zip(pixel) {
+                buffer.set(i % width, i / width, &color);
+            }
+        }
+        buffers
+    }
+
+    /// Cast a ray into the scene, returning information about the closest object it hits.
+    ///
+    /// The direction does not need to be normalized, distances are expressed in the scene's unit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::{Camera, LightProperties, LinearColor};
+    /// # use pathtracer::material::UniformMaterial;
+    /// # use pathtracer::render::{LightAggregate, Object, Scene};
+    /// # use pathtracer::shape::Sphere;
+    /// # use pathtracer::texture::UniformTexture;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// # let scene = Scene::new(
+    /// #     Camera::default(),
+    /// #     LightAggregate::empty(),
+    /// #     vec![
+    /// #         Object::new(
+    /// #             Sphere::new(Point::origin(), 1.0).into(),
+    /// #             UniformMaterial::new(
+    /// #                 LightProperties::new(
+    /// #                     LinearColor::new(1.0, 0.0, 0.0), // diffuse component
+    /// #                     LinearColor::new(0.0, 0.0, 0.0), // specular component
+    /// #                     None,
+    /// #                 ),
+    /// #             ).into(),
+    /// #             UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5)).into(),
+    /// #         )
+    /// #         .with_name("ball"),
+    /// #     ],
+    /// #     LinearColor::black().into(), // Background color
+    /// #     5,   // aliasing limit
+    /// #     3,   // reflection recursion limit
+    /// #     0.0, // diffraction index
+    /// # );
+    /// // The scene contains a unit sphere named "ball" at the origin
+    /// let info = scene
+    ///     .raycast(Point::new(-5.0, 0.0, 0.0), Vector::new(2.0, 0.0, 0.0))
+    ///     .unwrap();
+    /// assert_eq!(info.name, Some("ball"));
+    /// assert_eq!(info.distance, 4.0);
+    /// assert_eq!(info.point, Point::new(-1.0, 0.0, 0.0));
+    /// assert_eq!(info.normal.into_inner(), Vector::new(-1.0, 0.0, 0.0));
+    ///
+    /// assert!(scene.raycast(Point::new(-5.0, 0.0, 0.0), Vector::y()).is_none());
+    /// ```
+    pub fn raycast(&self, origin: Point, direction: Vector) -> Option<HitInfo<'_>> {
+        let direction = Unit::new_normalize(direction);
+        let ray = Ray::new(origin, direction).with_time(self.frame as Float);
+        self.cast_ray(ray, 0).map(|(hit, obj)| HitInfo {
+            name: obj.name.as_deref(),
+            distance: hit.distance,
+            point: origin + direction.as_ref() * hit.distance,
+            normal: hit.normal,
+            uv: hit.uv,
+        })
+    }
+
+    /// Take the samples of the pixel at (x, y) a pixel **coordinate**, spread over its area if
+    /// anti-aliasing is enabled or a single one at its center otherwise, tracking the
+    /// contributions of `lights` lights, along with the variance of their luminance.
+    fn sample_pixel(
+        &self,
+        camera: &Camera,
+        x: Float,
+        y: Float,
+        lights: usize,
+        rng: &mut impl Rng,
+    ) -> (Vec<Sample>, Float) {
+        if self.aliasing_limit > 0 {
+            return self.anti_alias_pixel(camera, x, y, lights, rng);
+        }
+        let mut contributions = LightContributions::new(lights);
+        let (x, y) = (x + 0.5, y + 0.5);
+        let color = self.pixel(camera, x, y, rng, &mut contributions);
+        (vec![sample(x, y, color, contributions)], 0.)
+    }
+
+    /// Get pixel color for (x, y) a pixel **coordinate**
+    fn pixel(
+        &self,
+        camera: &Camera,
+        x: Float,
+        y: Float,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        let ray = camera_ray(camera, self.frame, x, y, rng);
+        self.integrator.radiance(self, ray, rng, lights)
+    }
+
+    /// Take the anti-aliasing samples of the pixel at (x, y) a pixel **coordinate**, spread over
+    /// its area, along with the variance of the mean of their luminance.
+    fn anti_alias_pixel(
+        &self,
+        camera: &Camera,
+        x: Float,
+        y: Float,
+        lights: usize,
+        rng: &mut impl Rng,
+    ) -> (Vec<Sample>, Float) {
+        // The rays of a pixel are coherent, intersect them together
+        let cast = |rays: &[Ray]| self.cast_rays(rays, 0);
+        self.anti_alias_pixel_with(camera, x, y, lights, rng, cast)
+    }
+
+    /// Like `anti_alias_pixel`, finding the closest hits of the camera rays with `cast`.
+    fn anti_alias_pixel_with<'a>(
+        &'a self,
+        camera: &Camera,
+        x: Float,
+        y: Float,
+        lights: usize,
+        rng: &mut impl Rng,
+        cast: impl Fn(&[Ray]) -> Vec<Option<(Hit, &'a Object)>>,
+    ) -> (Vec<Sample>, Float) {
+        let points = self.sampler.pixel_samples(self.aliasing_limit, rng);
+        let positions: Vec<_> = (points.into_iter()).map(|(u, v)| (x + u, y + v)).collect();
+        let rays: Vec<_> = (positions.iter())
+            .map(|&(x, y)| camera_ray(camera, self.frame, x, y, rng))
+            .collect();
+        let hits = cast(&rays);
+        let samples: Vec<_> = (rays.into_iter().zip(hits).zip(positions))
+            .map(|((ray, hit), (x, y))| {
+                let mut contributions = LightContributions::new(lights);
+                let color =
+                    (self.integrator).radiance_from_hit(self, ray, hit, rng, &mut contributions);
+                sample(x, y, color, contributions)
+            })
+            .collect();
+        let samples = match self.outlier_rejection {
+            Some(factor) => reject_outliers(samples, factor),
+            None => samples,
+        };
+        let variance = mean_variance(&samples);
+        (samples, variance)
+    }
+
+    /// Get the opacity of the shadow pass for (x, y) a pixel **coordinate**, using the same
+    /// number of samples as the anti-aliasing.
+    fn shadow_pixel(&self, camera: &Camera, x: Float, y: Float, rng: &mut impl Rng) -> Float {
+        let offsets = self.surface_offsets(rng);
+        let samples = offsets.len();
+        let total: Float = (offsets.into_iter())
+            .map(|(dx, dy)| {
+                let ray = camera_ray(camera, self.frame, x + dx, y + dy, rng);
+                self.shadow(ray, rng)
+            })
+            .sum();
+        total / samples as Float
+    }
+
+    /// Get the offsets within a pixel of the rays used by the shadow and surface passes, placed
+    /// like the anti-aliasing samples, or in the center of the pixel without anti-aliasing.
+    fn surface_offsets(&self, rng: &mut impl Rng) -> Vec<(Float, Float)> {
+        if self.aliasing_limit > 0 {
+            self.sampler.pixel_samples(self.aliasing_limit, rng)
+        } else {
+            vec![(0.5, 0.5)]
+        }
+    }
+
+    /// Get the albedo, normal, and depth of the surfaces seen through (x, y) a pixel
+    /// **coordinate**, using the same number of samples as the anti-aliasing.
+    fn surface_pixel(
+        &self,
+        camera: &Camera,
+        x: Float,
+        y: Float,
+        rng: &mut impl Rng,
+    ) -> (LinearColor, Vector, Float) {
+        let offsets = self.surface_offsets(rng);
+        let samples = offsets.len();
+        let rays: Vec<_> = (offsets.into_iter())
+            .map(|(dx, dy)| camera_ray(camera, self.frame, x + dx, y + dy, rng))
+            .collect();
+        // The rays of a pixel are coherent, intersect them together
+        let hits = rays.iter().copied().zip(self.cast_rays(&rays, 0));
+        let (mut albedo, mut normal, mut depth) =
+            (LinearColor::black(), Vector::zeros(), Float::INFINITY);
+        for (ray, hit) in hits {
+            if let Some((hit, object)) = hit {
+                let material = self.material_at(object, &ray, &hit, 0, rng);
+                let surface = object.surface_point(&ray, &hit);
+                let color = object.texture.surface_color(&surface);
+                albedo += material.properties(hit.uv).diffuse * color;
+                normal += hit.normal.into_inner();
+                // Camera rays start on the film, the depth is measured from the camera itself
+                let origin = match camera.motion() {
+                    Some(motion) => motion.at(ray.time) * camera.origin(),
+                    None => *camera.origin(),
+                };
+                let point = ray.origin + ray.direction.as_ref() * hit.distance;
+                depth = depth.min((point - origin).norm());
+            }
+        }
+        (albedo / samples as Float, normal / samples as Float, depth)
+    }
+
+    /// Get the fraction of the direct light blocked before reaching the point where a camera ray
+    /// hits a shadow catcher, or 0 if it does not hit one.
+    pub(crate) fn shadow(&self, ray: Ray, rng: &mut dyn RngCore) -> Float {
+        let (hit, object) = match self.cast_ray(ray, 0) {
+            Some((hit, object)) if object.shadow_catcher => (hit, object),
+            _ => return 0.,
+        };
+        let point = ray.origin + ray.direction.as_ref() * hit.distance;
+        // Shadows are seen from the side of the catcher facing the camera
+        let normal = if ray.direction.dot(&hit.normal) > 0. {
+            -hit.normal
+        } else {
+            hit.normal
+        };
+        let (mut unoccluded, mut received) = (0., 0.);
+        let lights = (self.lights.spatial_lights_iter())
+            .filter(|light| light.affects(object.name.as_deref()));
+        for light in lights {
+            let samples = light.sample_li_stratified(&point, self.sampler, rng);
+            let count = samples.len() as Float;
+            for sample in samples {
+                let cos = normal.dot(&sample.direction);
+                if cos <= 0. || sample.pdf <= 0. {
+                    continue;
+                }
+                let irradiance = sample.radiance.luminance() * cos / (sample.pdf * count);
+                let start = offset_origin(&point, &normal, &sample.direction, hit.distance);
+                let shadow_ray = Ray::new(start, sample.direction).with_time(ray.time);
+                let transmittance = self.transmittance(shadow_ray, sample.distance, 1);
+                unoccluded += irradiance;
+                received += irradiance * transmittance.luminance();
+            }
+        }
+        if unoccluded <= 0. {
+            return 0.;
+        }
+        1. - received / unoccluded
+    }
+
+    /// Follow a camera ray with Whitted-style ray tracing.
+    pub(crate) fn trace(
+        &self,
+        ray: Ray,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        self.trace_from_hit(ray, self.cast_ray(ray, 0), rng, lights)
+    }
+
+    /// Like `trace`, given the camera ray's closest hit.
+    pub(crate) fn trace_from_hit(
+        &self,
+        ray: Ray,
+        hit: Option<(Hit, &Object)>,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        let indices = RefractionInfo::with_index(self.diffraction_index);
+        match hit {
+            Some((hit, obj)) => {
+                self.color_at(&ray, &hit, obj, self.reflection_limit, indices, rng, lights)
+            }
+            None => self.background.color(&ray.direction),
+        }
+    }
+
+    /// Find the closest object hit by a ray, ignoring objects which are not visible at `depth`
+    /// bounces from the camera.
+    pub(crate) fn cast_ray(&self, ray: Ray, depth: u32) -> Option<(Hit, &Object)> {
+        self.cast_ray_counting(ray, depth, &Cell::new(0))
+    }
+
+    /// Like `cast_ray`, adding the number of ray-object intersection tests it made to `tests`.
+    pub(crate) fn cast_ray_counting(
+        &self,
+        ray: Ray,
+        depth: u32,
+        tests: &Cell<u32>,
+    ) -> Option<(Hit, &Object)> {
+        let intersect = |obj: &Object| {
+            self.hit_visible(obj, &ray, depth, tests)
+                .map(|hit| (hit.distance, hit))
+        };
+        let (bounded, unbounded) = self.objects.split_at(self.bounded_count);
+        let closest = self.bvh.walk_with(&ray, bounded, intersect);
+        self.closest_hit(&ray, depth, closest, unbounded, tests)
+    }
+
+    /// Like `cast_ray`, for any number of rays which walk the BVH together in packets.
+    pub(crate) fn cast_rays(&self, rays: &[Ray], depth: u32) -> Vec<Option<(Hit, &Object)>> {
+        (rays.chunks(PACKET_SIZE))
+            .flat_map(|chunk| {
+                // Pad the last packet by repeating its last ray, whose hits are then ignored
+                let packet = std::array::from_fn(|lane| chunk[lane.min(chunk.len() - 1)]);
+                let hits = self.cast_packet(&packet, depth);
+                IntoIterator::into_iter(hits).take(chunk.len())
+            })
+            .collect()
+    }
+
+    /// Like `cast_ray`, for a packet of rays which walk the BVH together.
+    ///
+    /// This is faster than casting each ray on its own when they are coherent, e.g: the rays
+    /// sampling a single pixel.
+    pub(crate) fn cast_packet(
+        &self,
+        rays: &[Ray; PACKET_SIZE],
+        depth: u32,
+    ) -> [Option<(Hit, &Object)>; PACKET_SIZE] {
+        let tests = Cell::new(0);
+        let intersect = |lane: usize, obj: &Object| {
+            self.hit_visible(obj, &rays[lane], depth, &tests)
+                .map(|hit| (hit.distance, hit))
+        };
+        let (bounded, unbounded) = self.objects.split_at(self.bounded_count);
+        let closest = self.bvh.walk_packet_with(rays, bounded, intersect);
+        let mut lanes = rays.iter().zip(closest);
+        [(); PACKET_SIZE].map(|_| {
+            let (ray, closest) = lanes.next().unwrap();
+            self.closest_hit(ray, depth, closest, unbounded, &tests)
+        })
+    }
+
+    /// Intersect an object if it is visible at `depth` bounces from the camera, adding the test
+    /// to `tests` and to the statistics of the object.
+    fn hit_visible(&self, obj: &Object, ray: &Ray, depth: u32, tests: &Cell<u32>) -> Option<Hit> {
+        if !obj.visibility.is_visible_at(depth) {
+            return None;
+        }
+        tests.set(tests.get() + 1);
+        let hit = hit_uncut(obj, ray);
+        if let Some(counters) = &self.counters {
+            // The BVH only gives us a reference to the object, recover its index from it
+            let offset = obj as *const Object as usize - self.objects.as_ptr() as usize;
+            counters[offset / std::mem::size_of::<Object>()].record(hit.is_some());
+        }
+        hit
+    }
+
+    /// Keep the closest of the hit found in the BVH and those of the unbounded objects, and
+    /// orient its normal for shading.
+    fn closest_hit<'a>(
+        &self,
+        ray: &Ray,
+        depth: u32,
+        closest: Option<(Hit, &'a Object)>,
+        unbounded: &'a [Object],
+        tests: &Cell<u32>,
+    ) -> Option<(Hit, &'a Object)> {
+        let (mut hit, obj) = unbounded
+            .iter()
+            .filter_map(|obj| {
+                self.hit_visible(obj, ray, depth, tests)
+                    .map(|hit| (hit, obj))
+            })
+            .chain(closest)
+            .min_by(|(lhs, _), (rhs, _)| lhs.distance.total_cmp(&rhs.distance))?;
+        hit.back_face = ray.direction.dot(&hit.normal) > 0.;
+        hit.normal = obj.shading_normal(ray, &hit);
+        if hit.back_face && obj.sides.flips_normal() {
+            hit.normal = -hit.normal;
+        }
+        Some((hit, obj))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn color_at(
+        &self,
+        ray: &Ray,
+        hit: &Hit,
+        object: &Object,
+        reflection_limit: u32,
+        mut indices: RefractionInfo,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        let point = ray.origin + ray.direction.as_ref() * hit.distance;
+        let incident_ray = ray.direction;
+        let texel = hit.uv;
+        let depth = self.reflection_limit - reflection_limit;
+        let material = self.material_at(object, ray, hit, depth, rng);
+        let properties = material.properties(texel);
+        let emission = if object.sides.emits_towards(hit) {
+            material.emission(texel)
+        } else {
+            LinearColor::black()
+        };
+        let surface = object.surface_point(ray, hit);
+        let object_color = object.texture.surface_color(&surface);
+
+        let normal = hit.normal;
+        let reflected_ray = reflected(incident_ray, normal);
+
+        let mut lighting_lights = lights.empty_like();
+        let lighting = self.illuminate(
+            object,
+            ray,
+            object_color,
+            &material,
+            &properties,
+            hit,
+            depth,
+            rng,
+            &mut lighting_lights,
+        );
+        if properties.refl_trans.is_none() {
+            // Avoid calculating reflection when not needed
+            *lights += lighting_lights;
+            return lighting + emission;
+        }
+        let reflection_start = offset_origin(&point, &normal, &reflected_ray, hit.distance);
+        let mut reflected_lights = lights.empty_like();
+        let reflected = self.reflection(
+            ray.bounced(reflection_start, reflected_ray, hit.distance),
+            reflection_limit,
+            indices.clone(),
+            rng,
+            &mut reflected_lights,
+        );
+        // We can unwrap safely thanks to the check for None before
+        let color = match properties.refl_trans.unwrap() {
+            ReflTransEnum::Transparency { coef, index } => {
+                let entering = incident_ray.dot(&normal) < 0.;
+                // Calculate the refracted ray, if it was refracted, and mutate indices accordingly
+                match refracted(incident_ray, normal, &mut indices, index) {
+                    // Total reflection
+                    None => {
+                        *lights += reflected_lights;
+                        reflected
+                    }
+                    // Refraction (refracted ray, amount of *reflection*)
+                    Some((r, refl_t)) => {
+                        indices.absorption = if entering {
+                            material.absorption(texel)
+                        } else {
+                            None
+                        };
+                        let start = offset_origin(&point, &normal, &r, hit.distance);
+                        let mut refracted_lights = lights.empty_like();
+                        let refracted = self.refraction(
+                            ray.bounced(start, r, hit.distance),
+                            coef,
+                            reflection_limit,
+                            indices,
+                            rng,
+                            &mut refracted_lights,
+                        );
+                        *lights += (refracted_lights * (1. - refl_t) + reflected_lights * refl_t)
+                            * coef
+                            + lighting_lights * (1. - coef);
+                        let refr_light = refracted * (1. - refl_t) + reflected * refl_t;
+                        refr_light * coef + lighting * (1. - coef)
+                    }
+                }
+            }
+            ReflTransEnum::Reflectivity { coef } => {
+                *lights += reflected_lights * coef + lighting_lights * (1. - coef);
+                reflected * coef + lighting * (1. - coef)
+            }
+        };
+        color + emission
+    }
+
+    fn refraction(
+        &self,
+        ray: Ray,
+        transparency: Float,
+        reflection_limit: u32,
+        indices: RefractionInfo,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        if transparency > 1e-5 && reflection_limit > 0 {
+            let depth = self.reflection_limit - reflection_limit + 1;
+            if let Some((hit, obj)) = self.cast_ray(ray, depth) {
+                let attenuation = indices.attenuation(hit.distance);
+                let mut refracted_lights = lights.empty_like();
+                let refracted = self.color_at(
+                    &ray,
+                    &hit,
+                    obj,
+                    reflection_limit - 1,
+                    indices,
+                    rng,
+                    &mut refracted_lights,
+                );
+                *lights += refracted_lights * attenuation.clone() * transparency;
+                return refracted * attenuation * transparency;
+            }
+        }
+        LinearColor::black()
+    }
+
+    fn reflection(
+        &self,
+        ray: Ray,
+        reflection_limit: u32,
+        indices: RefractionInfo,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        if reflection_limit > 0 {
+            let depth = self.reflection_limit - reflection_limit + 1;
+            if let Some((hit, obj)) = self.cast_ray(ray, depth) {
+                let attenuation = indices.attenuation(hit.distance);
+                let mut reflected_lights = lights.empty_like();
+                let color = self.color_at(
+                    &ray,
+                    &hit,
+                    obj,
+                    reflection_limit - 1,
+                    indices,
+                    rng,
+                    &mut reflected_lights,
+                );
+                *lights += reflected_lights * attenuation.clone();
+                return color * attenuation;
+            }
+        };
+        LinearColor::black()
+    }
+
+    /// Compute the light received at the point where `ray` hit an object, `depth` bounces away
+    /// from the camera, and scattered by its `material` given its `properties` there.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn illuminate(
+        &self,
+        object: &Object,
+        ray: &Ray,
+        object_color: LinearColor,
+        material: &MaterialEnum,
+        properties: &LightProperties,
+        hit: &Hit,
+        depth: u32,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        let ambient =
+            self.illuminate_ambient(object, ray, object_color.clone(), hit, depth, rng, lights);
+        let mut spatial_lights = lights.empty_like();
+        let spatial = self.illuminate_spatial(
+            object,
+            ray,
+            material,
+            properties,
+            hit,
+            depth,
+            rng,
+            &mut spatial_lights,
+        );
+        *lights += spatial_lights * object_color.clone();
+        ambient + object_color * spatial
+    }
+
+    /// Compute the light received from ambient lights at the point where `ray` hit an object,
+    /// darkened by the surrounding geometry if ambient occlusion is enabled.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn illuminate_ambient(
+        &self,
+        object: &Object,
+        ray: &Ray,
+        color: LinearColor,
+        hit: &Hit,
+        depth: u32,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        let mut ambient_lights = (self.lights.ambient_lights_iter())
+            .filter(|light| light.affects(object.name.as_deref()))
+            .peekable();
+        // Only cast occlusion rays when there is some ambient light to occlude
+        let occlusion = match self.ambient_occlusion {
+            Some(occlusion) if ambient_lights.peek().is_some() => {
+                self.ambient_occlusion(ray, hit, occlusion, depth, rng)
+            }
+            _ => 1.,
+        };
+        ambient_lights
+            .map(|light| {
+                let ambient =
+                    (color.clone() * light.illumination(&Point::origin()) * occlusion).clamp();
+                if lights.is_tracking() {
+                    for index in &self.lights.contribution_indices(light) {
+                        lights.record(*index, ambient.clone());
+                    }
+                }
+                ambient
+            })
+            .sum()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn illuminate_spatial(
+        &self,
+        object: &Object,
+        ray: &Ray,
+        material: &MaterialEnum,
+        properties: &LightProperties,
+        hit: &Hit,
+        depth: u32,
+        rng: &mut dyn RngCore,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        let reflected = reflected(ray.direction, hit.normal);
+        let contribution = |lum: LinearColor, direction: &Unit<Vector>| {
+            let (diffuse, specular) = material.light_response(
+                hit.uv,
+                hit.normal.dot(direction),
+                reflected.dot(direction),
+            );
+            let diffused = properties.diffuse.clone() * diffuse;
+            let specular = properties.specular.clone() * specular;
+            (lum * (diffused + specular)).clamp()
+        };
+        self.direct_lighting(object, ray, hit, depth, rng, &contribution, lights)
+    }
+
+    /// Sum the light received at the point where `ray` hit an object from the spatial lights, as
+    /// scattered by `contribution` given the (unoccluded) radiance of a light divided by its
+    /// sampling PDF, and its direction.
+    ///
+    /// Shadow rays only consider the objects visible one bounce further than `depth`, and lights
+    /// which do not light the `object` are skipped.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn direct_lighting(
+        &self,
+        object: &Object,
+        ray: &Ray,
+        hit: &Hit,
+        depth: u32,
+        rng: &mut dyn RngCore,
+        contribution: &dyn Fn(LinearColor, &Unit<Vector>) -> LinearColor,
+        lights: &mut LightContributions,
+    ) -> LinearColor {
+        let point = ray.origin + ray.direction.as_ref() * hit.distance;
+        let mut shade = |light: &dyn SpatialLight, weight: Float, rng: &mut dyn RngCore| {
+            if !light.affects(object.name.as_deref()) {
+                return LinearColor::black();
+            }
+            let samples = light.sample_li_stratified(&point, self.sampler, rng);
+            let count = samples.len() as Float;
+            let total: LinearColor = (samples.into_iter())
+                .map(|sample| {
+                    let direction = sample.direction;
+                    // Take shadows into account
+                    let start = offset_origin(&point, &hit.normal, &direction, hit.distance);
+                    let shadow_ray = Ray::new(start, direction).with_time(ray.time);
+                    let transmittance = self.transmittance(shadow_ray, sample.distance, depth + 1);
+                    if transmittance == LinearColor::black() {
+                        return transmittance;
+                    }
+                    contribution(sample.radiance / sample.pdf, &direction) * transmittance
+                })
+                .sum();
+            let shaded = total * weight / count;
+            if lights.is_tracking() {
+                for index in &self.lights.contribution_indices(light) {
+                    lights.record(*index, shaded.clone());
+                }
+            }
+            shaded
+        };
+        let samples = match self.light_samples {
+            Some(samples) => samples,
+            None => {
+                return (self.lights.spatial_lights_iter())
+                    .map(|light| shade(light, 1., rng))
+                    .sum()
+            }
+        };
+        // Lights infinitely far away cannot be part of the light tree, always take them into account
+        let distant: LinearColor = (self.lights.distant_lights_iter())
+            .map(|light| shade(light, 1., rng))
+            .sum();
+        let local: LinearColor = (0..samples)
+            .filter_map(|_| {
+                let u = rng.gen();
+                let (light, pmf) = self.lights.sample_local_light(&point, u)?;
+                Some(shade(light, 1. / (pmf * samples as Float), rng))
+            })
+            .sum();
+        distant + local
+    }
+
+    /// Get the fraction of light going along a ray up to a given distance: opaque objects block
+    /// it entirely, translucent and transparent ones only attenuate it, as does the medium they
+    /// enclose.
+    fn transmittance(&self, mut ray: Ray, mut distance: Float, depth: u32) -> LinearColor {
+        let mut transmittance = LinearColor::new(1., 1., 1.);
+        // The medium enclosed by the transparent object the ray is going through, if any
+        let mut medium = RefractionInfo::with_index(1.);
+        while let Some((obstacle, object)) = self.cast_ray(ray, depth) {
+            if obstacle.distance >= distance {
+                break;
+            }
+            match object.material.translucency(obstacle.uv) {
+                Some(color) => transmittance *= color * medium.attenuation(obstacle.distance),
+                None => return LinearColor::black(),
+            }
+            medium.absorption = if ray.direction.dot(&obstacle.normal) < 0. {
+                object.material.absorption(obstacle.uv)
+            } else {
+                None
+            };
+            if transmittance.r.max(transmittance.g).max(transmittance.b) <= 0. {
+                return LinearColor::black();
+            }
+            // Go through the surface, and keep looking for obstacles behind it
+            let crossed = ray.origin + ray.direction.as_ref() * obstacle.distance;
+            let start = offset_origin(
+                &crossed,
+                &obstacle.normal,
+                &ray.direction,
+                obstacle.distance,
+            );
+            ray = Ray::new(start, ray.direction).with_time(ray.time);
+            distance -= obstacle.distance;
+        }
+        transmittance
+    }
+
+    /// Get the material of `object` at the point hit by `ray`, with the surface inputs it uses,
+    /// if any, computed from the geometry around that point.
+    pub(crate) fn material_at<'a>(
+        &self,
+        object: &'a Object,
+        ray: &Ray,
+        hit: &Hit,
+        depth: u32,
+        rng: &mut dyn RngCore,
+    ) -> Cow<'a, MaterialEnum> {
+        let probe = match object.material.surface_probe() {
+            Some(probe) => probe,
+            None => return Cow::Borrowed(&object.material),
+        };
+        let signals = self.surface_signals(ray, hit, probe, depth, rng);
+        match object.material.with_surface(&signals) {
+            Some(material) => Cow::Owned(material),
+            None => Cow::Borrowed(&object.material),
+        }
+    }
+
+    /// Compute the fraction of the ambient light reaching the point where `ray` hit an object,
+    /// which is not blocked by geometry closer than the occlusion distance.
+    fn ambient_occlusion(
+        &self,
+        ray: &Ray,
+        hit: &Hit,
+        occlusion: AmbientOcclusion,
+        depth: u32,
+        rng: &mut dyn RngCore,
+    ) -> Float {
+        let point = ray.origin + ray.direction.as_ref() * hit.distance;
+        // Light comes from the side of the surface the ray is coming from
+        let normal = if ray.direction.dot(&hit.normal) > 0. {
+            -hit.normal
+        } else {
+            hit.normal
+        };
+        let samples = occlusion.samples();
+        let open = (0..samples)
+            .filter(|_| {
+                let (u, v) = (rng.gen(), rng.gen());
+                let direction = sample_hemisphere(&normal, u, v);
+                let start = offset_origin(&point, &normal, &direction, hit.distance);
+                let ray = Ray::new(start, direction).with_time(ray.time);
+                !matches!(
+                    self.cast_ray(ray, depth + 1),
+                    Some((obstacle, _)) if obstacle.distance < occlusion.distance()
+                )
+            })
+            .count();
+        open as Float / samples as Float
+    }
+
+    /// Measure the geometric signals around the point where `ray` hit an object, by casting rays
+    /// over the hemisphere above the surface, and the one below it.
+    ///
+    /// Rays going out of the surface are blocked in cavities, those going into it escape the
+    /// object quickly on thin convex edges: their difference approximates the curvature.
+    pub(crate) fn surface_signals(
+        &self,
+        ray: &Ray,
+        hit: &Hit,
+        probe: SurfaceProbe,
+        depth: u32,
+        rng: &mut dyn RngCore,
+    ) -> SurfaceSignals {
+        let point = ray.origin + ray.direction.as_ref() * hit.distance;
+        let samples = probe.samples.max(1);
+        let blocked = |direction: &Unit<Vector>| {
+            let start = offset_origin(&point, &hit.normal, direction, hit.distance);
+            matches!(
+                self.cast_ray(Ray::new(start, *direction).with_time(ray.time), depth + 1),
+                Some((obstacle, _)) if obstacle.distance < probe.radius
+            )
+        };
+        let (mut open, mut inside) = (0, 0);
+        let mut bent_normal = Vector::zeros();
+        for _ in 0..samples {
+            let (u, v) = (rng.gen(), rng.gen());
+            let outwards = sample_hemisphere(&hit.normal, u, v);
+            if blocked(&outwards) {
+                continue;
+            }
+            open += 1;
+            bent_normal += outwards.as_ref();
+        }
+        for _ in 0..samples {
+            let (u, v) = (rng.gen(), rng.gen());
+            if blocked(&sample_hemisphere(&-hit.normal, u, v)) {
+                inside += 1;
+            }
+        }
+        let samples = samples as Float;
+        SurfaceSignals {
+            occlusion: open as Float / samples,
+            curvature: (inside as Float - (samples - open as Float)) / samples,
+            bent_normal: Unit::try_new(bent_normal, 1e-6).unwrap_or(hit.normal),
+        }
+    }
+}
+
+/// Intersect an object, going through the parts of its surface which are cut out by its material,
+/// and through its back if it is culled.
+fn hit_uncut(obj: &Object, ray: &Ray) -> Option<Hit> {
+    let mut hit = obj.hit(ray)?;
+    let (mut current, mut travelled) = (*ray, 0.);
+    for _ in 0..MAX_CUTOUT_CROSSINGS {
+        let culled = obj.sides.culls_back() && ray.direction.dot(&hit.normal) > 0.;
+        if !culled
+            && !obj
+                .material
+                .is_cut_out(hit.uv, hit_noise(&current, hit.distance))
+        {
+            hit.distance += travelled;
+            return Some(hit);
+        }
+        // Start again from just behind the surface
+        let point = current.origin + current.direction.as_ref() * hit.distance;
+        let origin = offset_origin(&point, &hit.normal, &ray.direction, hit.distance);
+        travelled = (origin - ray.origin).dot(&ray.direction);
+        current = ray.bounced(origin, ray.direction, travelled);
+        hit = obj.hit(&current)?;
+    }
+    None
+}
+
+/// Get a pseudo-random number in `[0, 1)` for a hit, used to cut out surfaces stochastically.
+///
+/// It only depends on the ray and the hit, to keep renders reproducible with a given seed.
+// The bits of an `f64` need no conversion to be hashed as a `u64`
+#[allow(clippy::useless_conversion)]
+fn hit_noise(ray: &Ray, distance: Float) -> Float {
+    let coordinates = ray.origin.iter().chain(ray.direction.iter());
+    let mut hash = coordinates
+        .chain(std::iter::once(&distance))
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, x| {
+            (hash ^ u64::from(x.to_bits())).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    // Mix the bits of the hash, so that close rays give unrelated numbers
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    // Keep the 24 highest bits, which are exactly representable by an `Float`
+    (hash >> 40) as Float / (1u64 << 24) as Float
+}
+
+/// Move the unbounded objects, which cannot be part of the BVH, at the end to be checked
+/// separately. Return the number of bounded objects.
+fn partition_bounded(objects: &mut [Object]) -> usize {
+    objects.sort_by_key(|obj| !obj.shape.is_bounded());
+    objects.iter().filter(|obj| obj.shape.is_bounded()).count()
+}
+
+/// Sort the first objects in the given order, as returned by [`BVH::build_order`].
+///
+/// [`BVH::build_order`]: ../../../beevee/bvh/struct.BVH.html#method.build_order
+fn apply_order(objects: &mut Vec<Object>, order: &[usize]) {
+    let mut slots: Vec<_> = objects.drain(..order.len()).map(Some).collect();
+    let sorted: Vec<_> = (order.iter())
+        .map(|&index| slots[index].take().unwrap())
+        .collect();
+    objects.splice(0..0, sorted);
+}
+
+/// Get the ray going from the camera through (x, y) a pixel **coordinate**, at a random instant
+/// while its shutter is open during the frame.
+fn camera_ray(camera: &Camera, frame: u32, x: Float, y: Float, rng: &mut dyn RngCore) -> Ray {
+    let (x, y) = camera.film().pixel_ratio(x, y);
+    let [open, close] = camera.shutter();
+    let time = frame as Float
+        + if close > open {
+            open + (close - open) * rng.gen::<Float>()
+        } else {
+            open
+        };
+    let (origin, pixel, scale) = match camera.motion() {
+        Some(motion) => {
+            let motion = motion.at(time);
+            let pixel = motion * camera.film().pixel_at_ratio(x, y);
+            (motion * camera.origin(), pixel, motion.scaling())
+        }
+        None => (*camera.origin(), camera.film().pixel_at_ratio(x, y), 1.),
+    };
+    let direction = Unit::new_normalize(pixel - origin);
+    // The ray stands for every ray going through the pixel, to filter the textures it hits
+    let size = camera.film().pixel_size() * scale;
+    let spread = size / (pixel - origin).norm();
+    Ray::new(pixel, direction)
+        .with_time(time)
+        .with_footprint(size, spread)
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct SerializedScene {
+    camera: Camera,
+    #[serde(default)]
+    cameras: BTreeMap<String, Camera>,
+    #[serde(default)]
+    lights: LightAggregate,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialEnum>,
+    #[serde(default)]
+    textures: BTreeMap<String, TextureEnum>,
+    #[serde(default)]
+    pub(crate) objects: Vec<Object>,
+    #[serde(default)]
+    meshes: Vec<MeshObject>,
+    #[serde(default)]
+    scatters: Vec<Scatter>,
+    #[serde(default)]
+    point_clouds: Vec<PointCloud>,
+    #[serde(default)]
+    up_axis: UpAxis,
+    #[serde(default)]
+    handedness: Handedness,
+    #[serde(default)]
+    background: BackgroundEnum,
+    #[serde(default)]
+    integrator: IntegratorEnum,
+    #[serde(default)]
+    light_samples: Option<u32>,
+    #[serde(default)]
+    grain: Option<FilmGrain>,
+    #[serde(default)]
+    denoise: Option<Denoiser>,
+    #[serde(default)]
+    outline: Option<Outline>,
+    #[serde(default)]
+    crop: Option<Crop>,
+    #[serde(default)]
+    precision: Precision,
+    #[serde(default)]
+    filter: Filter,
+    #[serde(default)]
+    sampler: Sampler,
+    #[serde(default)]
+    tile_order: TileOrder,
+    #[serde(default)]
+    outlier_rejection: Option<Float>,
+    #[serde(default)]
+    ambient_occlusion: Option<AmbientOcclusion>,
+    #[serde(default)]
+    bvh_builder: BvhBuilder,
+    #[serde(default)]
+    bvh_cache: Option<PathBuf>,
+    #[serde(default)]
+    frames: Option<[u32; 2]>,
+    #[serde(default)]
+    pub(crate) aliasing_limit: u32,
+    #[serde(default)]
+    reflection_limit: u32,
+    #[serde(default = "crate::serialize::default_identity")]
+    starting_diffraction: Float,
+}
+
+impl SerializedScene {
+    /// Give their shared material and texture to the objects which refer to them by name.
+    fn resolve_names(&mut self) -> crate::Result<()> {
+        let materials: BTreeMap<_, _> = std::mem::take(&mut self.materials)
+            .into_iter()
+            .map(|(name, material)| (name, Arc::new(material)))
+            .collect();
+        let textures: BTreeMap<_, _> = std::mem::take(&mut self.textures)
+            .into_iter()
+            .map(|(name, texture)| (name, Arc::new(texture)))
+            .collect();
+        let resolve = |material: &mut MaterialEnum, texture: &mut TextureEnum| {
+            if let MaterialEnum::SharedMaterial(shared) = material {
+                shared.resolve(&materials)?;
+            }
+            if let TextureEnum::SharedTexture(shared) = texture {
+                shared.resolve(&textures)?;
+            }
+            Ok::<_, Error>(())
+        };
+        for object in self.objects.iter_mut() {
+            resolve(&mut object.material, &mut object.texture)?;
+        }
+        for mesh in self.meshes.iter_mut() {
+            resolve(&mut mesh.material, &mut mesh.texture)?;
+        }
+        for scatter in self.scatters.iter_mut() {
+            let prototype = &mut scatter.prototype;
+            resolve(&mut prototype.material, &mut prototype.texture)?;
+        }
+        for cloud in self.point_clouds.iter_mut() {
+            resolve(&mut cloud.material, &mut cloud.texture)?;
+        }
+        let objects: BTreeSet<_> = (self.objects.iter().map(|object| &object.name))
+            .chain(self.meshes.iter().map(|mesh| &mesh.name))
+            .chain(self.scatters.iter().map(|scatter| &scatter.prototype.name))
+            .chain(self.point_clouds.iter().map(|cloud| &cloud.name))
+            .filter_map(|name| name.as_deref())
+            .collect();
+        let links = (self
+            .lights
+            .ambient_lights_iter()
+            .filter_map(|light| light.link()))
+        .chain(
+            self.lights
+                .spatial_lights_iter()
+                .filter_map(|light| light.link()),
+        );
+        for name in links.flat_map(LightLink::names) {
+            if !objects.contains(name) {
+                return Err(Error::unknown_name("object", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the scene, loading its BVH from its cache if it was built for the same objects, and
+    /// writing it there otherwise.
+    fn into_scene(self) -> crate::Result<Scene> {
+        let mut scene = self;
+        let system = CoordinateSystem::new(scene.up_axis, scene.handedness);
+        let meshes = scene.meshes.into_iter();
+        scene
+            .objects
+            .extend(meshes.flat_map(|mesh| mesh.into_scene_objects(&system)));
+        let scatters = scene.scatters.into_iter();
+        scene
+            .objects
+            .extend(scatters.flat_map(|scatter| scatter.into_scene_objects(&system)));
+        let clouds = scene.point_clouds.into_iter();
+        scene
+            .objects
+            .extend(clouds.flat_map(|cloud| cloud.into_scene_objects(&system)));
+        // Our cameras are right-handed, mirror them to see left-handed scenes the right way round
+        if system.is_left_handed() {
+            scene.camera = scene.camera.mirrored();
+            scene.cameras = (scene.cameras.into_iter())
+                .map(|(name, camera)| (name, camera.mirrored()))
+                .collect();
+        }
+        let background = match scene.background {
+            BackgroundEnum::SkyBackground(sky) => sky.with_up(system.up()).into(),
+            BackgroundEnum::PhysicalSkyBackground(sky) => sky.with_up(system.up()).into(),
+            background => background,
+        };
+        let bounded_count = partition_bounded(&mut scene.objects);
+        let bounded = &scene.objects[..bounded_count];
+        let builder = scene.bvh_builder;
+        let cached =
+            (scene.bvh_cache.as_ref()).and_then(|path| bvh_cache::load(path, bounded, builder));
+        let mut cache_error = None;
+        let (bvh, order) = match cached {
+            Some(cached) => cached,
+            None => {
+                let (bvh, order) = match builder {
+                    BvhBuilder::Sah => BVH::build_order(bounded),
+                    BvhBuilder::Linear => BVH::build_linear_order(bounded),
+                    // The objects are referenced from the leaves, and keep their order
+                    BvhBuilder::Spatial { budget } => (
+                        BVH::build_spatial(bounded, budget),
+                        (0..bounded.len()).collect(),
+                    ),
+                };
+                let bvh = BVH4::collapse(&bvh);
+                if let Some(path) = &scene.bvh_cache {
+                    // The cache only speeds up the next renders, this one can go on without it
+                    cache_error = bvh_cache::save(path, bounded, builder, &bvh, &order).err();
+                }
+                (bvh, order)
+            }
+        };
+        apply_order(&mut scene.objects, &order);
+        let mut ans = Scene::with_bvh(
+            scene.camera,
+            scene.lights,
+            scene.objects,
+            (bvh, bounded_count),
+            background,
+            scene.aliasing_limit,
+            scene.reflection_limit,
+            scene.starting_diffraction,
+        );
+        ans.cameras = scene.cameras;
+        ans.integrator = scene.integrator;
+        ans.light_samples = scene.light_samples;
+        ans.grain = scene.grain;
+        ans.denoiser = scene.denoise;
+        ans.outline = scene.outline;
+        ans.crop = scene.crop;
+        ans.precision = scene.precision;
+        ans.filter = scene.filter;
+        ans.sampler = scene.sampler;
+        ans.tile_order = scene.tile_order;
+        ans.set_outlier_rejection(scene.outlier_rejection);
+        ans.ambient_occlusion = scene.ambient_occlusion;
+        ans.bvh_builder = builder;
+        ans.bvh_cache = scene.bvh_cache;
+        ans.bvh_cache_error = cache_error;
+        ans.frames = scene.frames.map(|[first, last]| first..=last);
+        Ok(ans)
+    }
+}
+
+/// A scene written back as a description, its meshes being written as the triangles they are made
+/// of.
+#[derive(Serialize)]
+struct SceneDescription<'a> {
+    camera: &'a Camera,
+    cameras: &'a BTreeMap<String, Camera>,
+    lights: &'a LightAggregate,
+    materials: BTreeMap<&'a str, &'a MaterialEnum>,
+    textures: BTreeMap<&'a str, &'a TextureEnum>,
+    objects: ObjectsDescription<'a>,
+    up_axis: UpAxis,
+    handedness: Handedness,
+    background: &'a BackgroundEnum,
+    integrator: &'a IntegratorEnum,
+    light_samples: Option<u32>,
+    grain: &'a Option<FilmGrain>,
+    denoise: &'a Option<Denoiser>,
+    outline: &'a Option<Outline>,
+    crop: Option<Crop>,
+    precision: Precision,
+    filter: Filter,
+    sampler: Sampler,
+    tile_order: TileOrder,
+    outlier_rejection: Option<Float>,
+    ambient_occlusion: Option<AmbientOcclusion>,
+    bvh_builder: BvhBuilder,
+    bvh_cache: &'a Option<PathBuf>,
+    frames: Option<[u32; 2]>,
+    pub(crate) aliasing_limit: u32,
+    reflection_limit: u32,
+    starting_diffraction: Float,
+}
+
+impl<'a> From<&'a Scene> for SceneDescription<'a> {
+    fn from(scene: &'a Scene) -> Self {
+        let mut materials = BTreeMap::new();
+        let mut textures = BTreeMap::new();
+        for object in scene.objects.iter() {
+            if let MaterialEnum::SharedMaterial(shared) = &object.material {
+                if let Ok(material) = shared.material() {
+                    materials.insert(shared.name(), material);
+                }
+            }
+            if let TextureEnum::SharedTexture(shared) = &object.texture {
+                if let Ok(texture) = shared.texture() {
+                    textures.insert(shared.name(), texture);
+                }
+            }
+        }
+        // Only the sky depends on the up axis, the meshes having already been converted
+        let up_axis = match &scene.background {
+            BackgroundEnum::SkyBackground(sky) if *sky.up() == Vector::z_axis() => UpAxis::Z,
+            BackgroundEnum::PhysicalSkyBackground(sky) if *sky.up() == Vector::z_axis() => {
+                UpAxis::Z
+            }
+            _ => UpAxis::Y,
+        };
+        let handedness = if scene.camera.is_mirrored() {
+            Handedness::Left
+        } else {
+            Handedness::Right
+        };
+        SceneDescription {
+            camera: &scene.camera,
+            cameras: &scene.cameras,
+            lights: &scene.lights,
+            materials,
+            textures,
+            objects: ObjectsDescription(&scene.objects),
+            up_axis,
+            handedness,
+            background: &scene.background,
+            integrator: &scene.integrator,
+            light_samples: scene.light_samples,
+            grain: &scene.grain,
+            denoise: &scene.denoiser,
+            outline: &scene.outline,
+            crop: scene.crop,
+            precision: scene.precision,
+            filter: scene.filter,
+            sampler: scene.sampler,
+            tile_order: scene.tile_order,
+            outlier_rejection: scene.outlier_rejection,
+            ambient_occlusion: scene.ambient_occlusion,
+            bvh_builder: scene.bvh_builder,
+            bvh_cache: &scene.bvh_cache,
+            frames: (scene.frames.as_ref()).map(|frames| [*frames.start(), *frames.end()]),
+            aliasing_limit: scene.aliasing_limit,
+            reflection_limit: scene.reflection_limit,
+            starting_diffraction: scene.diffraction_index,
+        }
+    }
+}
+
+/// The objects of a scene, the instances of a shared prototype being written as the triangles
+/// they are made of.
+struct ObjectsDescription<'a>(&'a [Object]);
+
+impl Serialize for ObjectsDescription<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(None)?;
+        for object in self.0 {
+            match &object.shape {
+                ShapeEnum::Instance(instance) => {
+                    for triangle in instance.triangles() {
+                        seq.serialize_element(&object.reshaped(triangle.into()))?;
+                    }
+                }
+                _ => seq.serialize_element(object)?,
+            }
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Scene {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut scene: SerializedScene = Deserialize::deserialize(deserializer)?;
+        scene.resolve_names().map_err(D::Error::custom)?;
+        scene.into_scene().map_err(D::Error::custom)
+    }
+}
+
+/// The scene is written as a description which loads back into the same scene, e.g: to save a
+/// scene built programmatically as a test fixture.
+///
+/// Shared materials and textures are written in the `materials` and `textures` dictionaries, and
+/// the objects refer to them by name. The meshes, and the copies of scattered meshes, are written
+/// as the triangles they are made of.
+impl Serialize for Scene {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        SceneDescription::from(self).serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::Pathtracer;
+    use std::path::Path;
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = std::include_str!("../../examples/scene.yaml");
+        let _: Scene = serde_yaml::from_str(yaml).unwrap();
         // FIXME: actually test the equality ?
     }
 
     #[test]
-    #[ignore] // stack overflow because of BVH :(
-    fn bvh_fails() {
+    fn named_materials_deserialization_works() {
+        let yaml = r#"
+            camera:
+              origin: [-1.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 8
+              y: 8
+            materials:
+              red:
+                type: uniform
+                diffuse: {r: 1.0, g: 0.0, b: 0.0}
+                specular: {r: 0.0, g: 0.0, b: 0.0}
+            textures:
+              white:
+                type: uniform
+                color: {r: 1.0, g: 1.0, b: 1.0}
+            objects:
+              - shape:
+                  type: sphere
+                  inverted: false
+                  center: [0.5, 0.0, 0.0]
+                  radius: 1.0
+                material: red
+                texture: white
+              - shape:
+                  type: sphere
+                  inverted: false
+                  center: [2.0, 0.0, 0.0]
+                  radius: 1.0
+                material: red
+                texture:
+                  type: uniform
+                  color: {r: 0.5, g: 0.5, b: 0.5}
+        "#;
+        let scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        let shared = |object: &Object| match &object.material {
+            MaterialEnum::SharedMaterial(shared) => shared.clone(),
+            material => panic!("unexpected material: {:?}", material),
+        };
+        assert_eq!(shared(&scene.objects[0]).name(), "red");
+        assert_eq!(shared(&scene.objects[0]), shared(&scene.objects[1]));
+        assert_eq!(
+            scene.objects[0]
+                .texture
+                .texel_color(crate::Point2D::origin()),
+            LinearColor::new(1., 1., 1.)
+        );
+    }
+
+    #[test]
+    fn set_looks_works() {
+        let yaml = r#"
+            camera:
+              origin: [-1.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              x: 8
+              y: 8
+            materials:
+              red:
+                type: uniform
+                diffuse: {r: 1.0, g: 0.0, b: 0.0}
+                specular: {r: 0.0, g: 0.0, b: 0.0}
+            textures:
+              white:
+                type: uniform
+                color: {r: 1.0, g: 1.0, b: 1.0}
+            objects:
+              - shape: {type: sphere, center: [2.0, 0.0, 0.0], radius: 1.0}
+                material: red
+                texture: white
+        "#;
+        let mut scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        let color = |scene: &Scene| {
+            let object = &scene.objects[0];
+            let texel = crate::Point2D::origin();
+            object.material.properties(texel).diffuse * object.texture.texel_color(texel)
+        };
+        assert_eq!(color(&scene), LinearColor::new(1., 0., 0.));
+
+        let looks = |material: &str, texture: &str| {
+            let green = crate::material::UniformMaterial::new(LightProperties::new(
+                LinearColor::new(0., 1., 0.),
+                LinearColor::black(),
+                None,
+            ));
+            let grey = crate::texture::UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5));
+            Looks {
+                materials: std::iter::once((material.to_string(), Arc::new(green.into())))
+                    .collect(),
+                textures: std::iter::once((texture.to_string(), Arc::new(grey.into()))).collect(),
+            }
+        };
+        scene.set_looks(&looks("red", "white")).unwrap();
+        assert_eq!(color(&scene), LinearColor::new(0., 0.5, 0.));
+        // Missing looks leave the scene untouched
+        assert!(matches!(
+            scene.set_looks(&looks("red", "black")),
+            Err(Error::UnknownName {
+                kind: "texture",
+                ..
+            })
+        ));
+        assert!(matches!(
+            scene.set_looks(&looks("blue", "white")),
+            Err(Error::UnknownName {
+                kind: "material",
+                ..
+            })
+        ));
+        assert_eq!(color(&scene), LinearColor::new(0., 0.5, 0.));
+    }
+
+    #[test]
+    fn unknown_material_fails() {
+        let yaml = r#"
+            camera:
+              origin: [-1.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 8
+              y: 8
+            objects:
+              - shape:
+                  type: sphere
+                  inverted: false
+                  center: [0.5, 0.0, 0.0]
+                  radius: 1.0
+                material: red
+                texture:
+                  type: uniform
+                  color: {r: 1.0, g: 1.0, b: 1.0}
+        "#;
+        match serde_yaml::from_str::<Scene>(yaml) {
+            Err(err) => assert!(err.to_string().contains("unknown material 'red'")),
+            Ok(_) => panic!("unknown material should fail"),
+        }
+    }
+
+    /// Compare two descriptions, up to rounding errors.
+    fn assert_descriptions_eq(lhs: &serde_yaml::Value, rhs: &serde_yaml::Value, path: &str) {
+        use serde_yaml::Value;
+
+        match (lhs, rhs) {
+            (Value::Number(lhs), Value::Number(rhs)) => {
+                let (lhs, rhs) = (lhs.as_f64().unwrap(), rhs.as_f64().unwrap());
+                assert!((lhs - rhs).abs() < 1e-5, "{}: {} != {}", path, lhs, rhs);
+            }
+            (Value::Sequence(lhs), Value::Sequence(rhs)) => {
+                assert_eq!(lhs.len(), rhs.len(), "{}", path);
+                for (i, (lhs, rhs)) in lhs.iter().zip(rhs).enumerate() {
+                    assert_descriptions_eq(lhs, rhs, &format!("{}[{}]", path, i));
+                }
+            }
+            (Value::Mapping(lhs), Value::Mapping(rhs)) => {
+                assert_eq!(lhs.len(), rhs.len(), "{}", path);
+                for (key, lhs) in lhs {
+                    let name = format!("{}.{}", path, key.as_str().unwrap_or("?"));
+                    assert_descriptions_eq(lhs, &rhs[key], &name);
+                }
+            }
+            (lhs, rhs) => assert_eq!(lhs, rhs, "{}", path),
+        }
+    }
+
+    #[test]
+    fn serialization_round_trips() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("yaml") {
+                continue;
+            }
+            let description = crate::serialize::load_description(&path).unwrap();
+            let scene: Scene = serde_yaml::from_str(&description).unwrap();
+            let saved = serde_yaml::to_value(&scene).unwrap();
+            let reloaded: Scene = serde_yaml::from_value(saved.clone()).unwrap();
+            let resaved = serde_yaml::to_value(&reloaded).unwrap();
+            assert_descriptions_eq(&resaved, &saved, &path.display().to_string());
+        }
+    }
+
+    #[test]
+    fn serialization_keeps_names() {
+        let yaml = r#"
+            camera:
+              origin: [-1.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              x: 8
+              y: 8
+            handedness: left
+            materials:
+              red:
+                type: uniform
+                diffuse: {r: 1.0, g: 0.0, b: 0.0}
+                specular: {r: 0.0, g: 0.0, b: 0.0}
+            objects:
+              - shape:
+                  type: sphere
+                  center: [0.5, 0.0, 0.0]
+                  radius: 1.0
+                material: red
+                texture:
+                  type: uniform
+                  color: {r: 1.0, g: 1.0, b: 1.0}
+        "#;
+        let scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        let saved: serde_yaml::Value = serde_yaml::to_value(&scene).unwrap();
+        assert_eq!(saved["objects"][0]["material"].as_str(), Some("red"));
+        assert_eq!(saved["materials"]["red"]["type"].as_str(), Some("uniform"));
+        assert_eq!(saved["handedness"].as_str(), Some("left"));
+        assert_eq!(saved["camera"]["forward"][0].as_f64(), Some(1.));
+        let reloaded: Scene = serde_yaml::from_value(saved).unwrap();
+        assert!(reloaded.camera.is_mirrored());
+    }
+
+    #[test]
+    fn serialization_writes_scattered_triangles() {
+        let yaml = r#"
+            camera:
+              origin: [0.0, 5.0, 0.0]
+              forward: [0.0, -1.0, 0.0]
+              up: [0.0, 0.0, 1.0]
+              fov: 90.0
+              x: 8
+              y: 8
+            scatters:
+              - prototype:
+                  vertices:
+                    - [0.0, 0.0, 0.0]
+                    - [0.1, 0.0, 0.0]
+                    - [0.0, 0.1, 0.0]
+                    - [0.0, 0.0, 0.1]
+                  faces:
+                    - [0, 1, 2]
+                    - [0, 3, 1]
+                target:
+                  vertices:
+                    - [-1.0, 0.0, -1.0]
+                    - [1.0, 0.0, -1.0]
+                    - [1.0, 0.0, 1.0]
+                  faces:
+                    - [0, 2, 1]
+                density: 5.0
+                seed: 42
+        "#;
+        let scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        let copies = scene.objects.len();
+        assert!(copies > 0);
+        let saved = serde_yaml::to_value(&scene).unwrap();
+        let objects = saved["objects"].as_sequence().unwrap();
+        assert_eq!(objects.len(), 2 * copies);
+        assert!(objects
+            .iter()
+            .all(|object| object["shape"]["type"].as_str() == Some("triangle")));
+        // The copies are rendered the same once written as triangles
+        let reloaded: Scene = serde_yaml::from_value(saved).unwrap();
+        assert_eq!(reloaded.render_with_seed(42), scene.render_with_seed(42));
+    }
+
+    #[test]
+    fn cameras_deserialization_works() {
+        let yaml = r#"
+            camera:
+              origin: [-1.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 8
+              y: 8
+            cameras:
+              side:
+                origin: [5.0, 0.0, -5.0]
+                forward: [0.0, 0.0, 1.0]
+                up: [0.0, 1.0, 0.0]
+                fov: 90.0
+                distance_to_image: 1.0
+                x: 4
+                y: 2
+              front:
+                origin: [0.0, 0.0, 0.0]
+                forward: [1.0, 0.0, 0.0]
+                up: [0.0, 1.0, 0.0]
+                fov: 90.0
+                distance_to_image: 1.0
+                x: 2
+                y: 4
+            objects:
+              - shape:
+                  type: sphere
+                  center: [5.0, 0.0, 0.0]
+                  radius: 1.0
+                material:
+                  type: uniform
+                  diffuse: {r: 0.5, g: 0.5, b: 0.5}
+                  specular: {r: 1., g: 1., b: 1.}
+                texture:
+                  type: uniform
+                  color: {r: 0.25, g: 0.5, b: 1.}
+        "#;
+        let scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        let images = scene.render_cameras();
+        let names: Vec<_> = images.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["front", "side"]);
+        assert_eq!(images[0].1.dimensions(), (2, 4));
+        assert_eq!(images[1].1.dimensions(), (4, 2));
+        assert_eq!(scene.render().dimensions(), (8, 8));
+    }
+
+    #[test]
+    fn integrator_deserialization_works() {
+        let yaml = r#"
+            camera:
+              origin: [-1.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 4
+              y: 4
+            integrator:
+              type: pathtrace
+              roulette_depth: 2
+            objects:
+              - shape:
+                  type: sphere
+                  center: [5.0, 0.0, 0.0]
+                  radius: 1.0
+                material:
+                  type: uniform
+                  diffuse: {r: 0.5, g: 0.5, b: 0.5}
+                  specular: {r: 1., g: 1., b: 1.}
+                texture:
+                  type: uniform
+                  color: {r: 0.25, g: 0.5, b: 1.}
+        "#;
+        let scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(scene.integrator, Pathtracer::new(2).into());
+        assert_eq!(scene.render().dimensions(), (4, 4));
+    }
+
+    #[test]
+    fn grain_deserialization_works() {
+        let yaml = r#"
+            camera:
+              origin: [-1.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 4
+              y: 4
+            background: {r: 0.5, g: 0.5, b: 0.5}
+            grain:
+              strength: 0.1
+              seed: 7
+        "#;
+        let scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(scene.grain, Some(FilmGrain::new(0.1).with_seed(7)));
+        // The grain does not depend on the rendering's seed
+        assert_eq!(scene.render_with_seed(1), scene.render_with_seed(2));
+    }
+
+    #[test]
+    fn toon_outline_works() {
+        let yaml = r#"
+            camera:
+              origin: [0.0, 0.0, 0.0]
+              forward: [0.0, 0.0, 1.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 60.0
+              x: 32
+              y: 32
+            lights:
+              directionals:
+                - direction: [0.0, 0.0, 1.0]
+                  color: {r: 1.0, g: 1.0, b: 1.0}
+            background: {r: 1.0, g: 1.0, b: 1.0}
+            outline:
+              color: {r: 1.0, g: 0.0, b: 0.0}
+            objects:
+              - shape:
+                  type: sphere
+                  center: [0.0, 0.0, 5.0]
+                  radius: 1.0
+                material:
+                  type: toon
+                  color: {r: 0.0, g: 0.0, b: 1.0}
+                  bands: 2
+                texture:
+                  type: uniform
+                  color: {r: 1.0, g: 1.0, b: 1.0}
+        "#;
+        let mut scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        let red = Rgb([255, 0, 0]);
+        let image = scene.render_with_seed(0);
+        // The silhouette of the sphere is outlined, not its inside nor the background
+        let row: Vec<_> = (0..32).map(|x| *image.get_pixel(x, 16)).collect();
+        assert!(row.contains(&red));
+        assert_eq!(row[0], Rgb([255, 255, 255]));
+        assert_eq!(row[16], Rgb([0, 0, 255]));
+        // The sphere is shaded with only two flat bands
+        let mut blues: Vec<_> = row.iter().filter(|p| **p != red).map(|p| p[2]).collect();
+        blues.sort_unstable();
+        blues.dedup();
+        assert!(blues.len() <= 3, "{:?}", blues);
+        scene.set_outline(None);
+        let image = scene.render_with_seed(0);
+        assert!(image.pixels().all(|pixel| *pixel != red));
+    }
+
+    #[test]
+    fn denoise_works() {
+        let yaml = r#"
+            camera:
+              origin: [0.0, 1.0, 0.0]
+              forward: [0.0, -1.0, 0.0]
+              up: [0.0, 0.0, 1.0]
+              fov: 90.0
+              x: 16
+              y: 16
+            lights:
+              areas:
+                - corner: [-1.0, 2.0, -1.0]
+                  u: [2.0, 0.0, 0.0]
+                  v: [0.0, 0.0, 2.0]
+                  color: {r: 1.0, g: 1.0, b: 1.0}
+            denoise:
+              strength: 0.5
+            objects:
+              - shape:
+                  type: plane
+                  origin: [0.0, 0.0, 0.0]
+                  normal: [0.0, 1.0, 0.0]
+                material:
+                  type: uniform
+                  diffuse: {r: 0.5, g: 0.5, b: 0.5}
+                  specular: {r: 0.0, g: 0.0, b: 0.0}
+                texture:
+                  type: uniform
+                  color: {r: 1.0, g: 1.0, b: 1.0}
+        "#;
+        let mut scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(scene.denoiser, Some(Denoiser::new(0.5)));
+        let variance = |buffer: &FrameBuffer| {
+            let values: Vec<_> = (0..16)
+                .flat_map(|y| (0..16).map(move |x| (x, y)))
+                .map(|(x, y)| buffer.get(x, y).g)
+                .collect();
+            let mean = values.iter().sum::<Float>() / values.len() as Float;
+            values.iter().map(|v| (v - mean).powi(2)).sum::<Float>() / values.len() as Float
+        };
+        let (denoised, _) = scene.render_buffer_with_limits(42, &RenderLimits::new());
+        scene.set_denoiser(None);
+        let (noisy, _) = scene.render_buffer_with_limits(42, &RenderLimits::new());
+        assert!(variance(&noisy) > 0.);
+        assert!(variance(&denoised) < variance(&noisy) / 2.);
+    }
+
+    #[test]
+    fn coordinates_deserialization_works() {
+        let yaml = r#"
+            up_axis: z
+            handedness: left
+            camera:
+              origin: [-1.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 0.0, 1.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 4
+              y: 4
+            background:
+              zenith: {r: 0.0, g: 0.0, b: 1.0}
+              horizon: {r: 1.0, g: 1.0, b: 1.0}
+              ground: {r: 0.5, g: 0.25, b: 0.0}
+        "#;
+        let scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        let expected = Camera::new(
+            Point::new(-1., 0., 0.),
+            Vector::new(1., 0., 0.),
+            Vector::new(0., 0., 1.),
+            crate::consts::PI / 2.,
+            1.,
+            4,
+            4,
+        );
+        assert_eq!(scene.camera, expected.mirrored());
+        assert_eq!(
+            scene.background.color(&Vector::z_axis()),
+            LinearColor::new(0., 0., 1.)
+        );
+    }
+
+    #[test]
+    fn physical_sky_lights_scene() {
+        let yaml = r#"
+            camera:
+              origin: [-1.0, 0.0, 0.0]
+              look_at: [0.0, 0.0, 0.0]
+              fov: 90.0
+              x: 4
+              y: 4
+            up_axis: z
+            background:
+              sun_direction: [1.0, 0.0, 1.0]
+        "#;
+        let scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        let sky = scene.lights.sky_light().unwrap();
+        assert!((sky.to_source(&Point::origin()).0.z - Float::sqrt(0.5)).abs() < 1e-6);
+        // The sky light is not written back, being part of the background
+        let description = serde_yaml::to_string(&scene).unwrap();
+        assert!(!description.contains("sky"));
+        let scene: Scene = serde_yaml::from_str(&description).unwrap();
+        assert!(scene.lights.sky_light().is_some());
+        // Looking down the up axis shows the ground
+        assert_eq!(
+            scene.background.color(&-Vector::z_axis()),
+            LinearColor::black()
+        );
+    }
+
+    #[test]
+    fn light_samples_are_unbiased() {
+        use crate::light::PointLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let lights = (0..50)
+            .map(|i| {
+                let angle = i as Float / 50. * crate::consts::PI;
+                let position = Point::new(-3. * angle.sin(), 3. * angle.cos(), (i % 5) as Float);
+                PointLight::new(position, LinearColor::new(0.1, 0.1, 0.1))
+            })
+            .collect();
+        let mut scene = Scene::new(
+            Camera::default(),
+            LightAggregate::new(vec![], vec![], lights, vec![]),
+            vec![Object::new(
+                Sphere::new(Point::origin(), 1.).into(),
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(1., 1., 1.),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let ray = Ray::new(Point::new(-5., 0.5, 0.), Vector::x_axis());
+        let mut rng = StdRng::seed_from_u64(42);
+        let expected = scene
+            .trace(ray, &mut rng, &mut LightContributions::untracked())
+            .r;
+        assert!(expected > 0.);
+
+        scene.set_light_samples(Some(2));
+        let total: Float = (0..10_000)
+            .map(|_| {
+                scene
+                    .trace(ray, &mut rng, &mut LightContributions::untracked())
+                    .r
+            })
+            .sum();
+        assert!((total / 10_000. - expected).abs() < 0.02 * expected);
+    }
+
+    #[test]
+    fn visibility_by_depth_works() {
+        use crate::light::AmbientLight;
+        use crate::material::UniformMaterial;
+        use crate::render::Visibility;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let material = |refl_trans| {
+            UniformMaterial::new(LightProperties::new(
+                LinearColor::black(),
+                LinearColor::black(),
+                refl_trans,
+            ))
+            .into()
+        };
+        let ambient = AmbientLight::new(LinearColor::new(1., 1., 1.));
+        let scene = Scene::new(
+            Camera::default(),
+            LightAggregate::new(vec![ambient], vec![], vec![], vec![]),
+            vec![
+                Object::new(
+                    Sphere::new(Point::origin(), 1.).into(),
+                    material(Some(ReflTransEnum::Reflectivity { coef: 1. })),
+                    UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+                )
+                .with_name("mirror"),
+                // In front of the mirror, but only visible in its reflection
+                Object::new(
+                    Sphere::new(Point::new(-3., 0., 0.), 0.5).into(),
+                    material(None),
+                    UniformTexture::new(LinearColor::new(1., 0., 0.)).into(),
+                )
+                .with_name("backdrop")
+                .with_visibility(Visibility::new(1, None)),
+            ],
+            LinearColor::black().into(),
+            0,
+            1,
+            1.,
+        );
+        let info = scene.raycast(Point::new(-5., 0., 0.), Vector::x()).unwrap();
+        assert_eq!(info.name, Some("mirror"));
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        let mut rng = StdRng::seed_from_u64(42);
+        assert_eq!(
+            scene.trace(ray, &mut rng, &mut LightContributions::untracked()),
+            LinearColor::new(1., 0., 0.)
+        );
+    }
+
+    #[test]
+    fn light_linking_works() {
+        use crate::light::{AmbientLight, LightLink, PointLight};
+        use crate::material::UniformMaterial;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let object = |center, name| {
+            Object::new(
+                Sphere::new(center, 1.).into(),
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(1., 1., 1.),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )
+            .with_name(name)
+        };
+        let ambient = AmbientLight::new(LinearColor::new(0.25, 0.25, 0.25))
+            .with_link(LightLink::affecting(&["hero"]));
+        let point = PointLight::new(Point::new(-5., 2.5, 0.), LinearColor::new(1., 1., 1.))
+            .with_link(LightLink::excluding(&["hero"]));
+        let scene = Scene::new(
+            Camera::default(),
+            LightAggregate::new(vec![ambient], vec![], vec![point], vec![]),
+            vec![
+                object(Point::origin(), "hero"),
+                object(Point::new(0., 5., 0.), "floor"),
+            ],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut trace = |origin| {
+            let ray = Ray::new(origin, Vector::x_axis());
+            scene.trace(ray, &mut rng, &mut LightContributions::untracked())
+        };
+        // The hero only receives the ambient light, the floor only the point light
+        assert_eq!(
+            trace(Point::new(-5., 0., 0.)),
+            LinearColor::new(0.25, 0.25, 0.25)
+        );
+        let floor = trace(Point::new(-5., 5., 0.));
+        assert!(floor.r > 0. && floor.r != 0.25);
+    }
+
+    #[test]
+    fn unknown_linked_object_fails() {
+        let yaml = r#"
+            camera:
+              origin: [-1.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 8
+              y: 8
+            lights:
+              points:
+                - position: [0.0, 1.0, 0.0]
+                  color: {r: 1.0, g: 1.0, b: 1.0}
+                  affects: [hero]
+        "#;
+        match serde_yaml::from_str::<Scene>(yaml) {
+            Err(err) => assert!(err.to_string().contains("unknown object 'hero'")),
+            Ok(_) => panic!("unknown object should fail"),
+        }
+    }
+
+    #[test]
+    fn translucent_shadows_work() {
+        use crate::material::UniformMaterial;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let material = UniformMaterial::new(LightProperties::new(
+            LinearColor::new(1., 1., 1.),
+            LinearColor::black(),
+            None,
+        ));
+        let object = |center, material: &UniformMaterial| {
+            Object::new(
+                Sphere::new(center, 1.).into(),
+                material.clone().into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )
+        };
+        let leaf = material
+            .clone()
+            .with_translucency(LinearColor::new(0.5, 0.5, 1.));
+        let scene = Scene::new(
+            Camera::default(),
+            LightAggregate::empty(),
+            vec![
+                object(Point::origin(), &leaf),
+                object(Point::new(5., 0., 0.), &material),
+            ],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        // Both sides of the translucent sphere attenuate the light
+        assert_eq!(
+            scene.transmittance(ray, 5., 0),
+            LinearColor::new(0.5, 0.5, 1.)
+        );
+        assert_eq!(
+            scene.transmittance(ray, 7., 0),
+            LinearColor::new(0.25, 0.25, 1.)
+        );
+        assert_eq!(scene.transmittance(ray, 10., 0), LinearColor::black());
+    }
+
+    #[test]
+    fn cut_out_surfaces_are_skipped() {
+        use crate::material::{AlphaMode, PrincipledMaterial};
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let solid = PrincipledMaterial::new(LinearColor::new(1., 1., 1.));
+        let cut_out = PrincipledMaterial {
+            opacity: 0.25,
+            alpha_mode: AlphaMode::Mask,
+            ..solid.clone()
+        };
+        let object = |center, material: &PrincipledMaterial| {
+            Object::new(
+                Sphere::new(center, 1.).into(),
+                material.clone().into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )
+        };
+        let scene = Scene::new(
+            Camera::default(),
+            LightAggregate::empty(),
+            vec![
+                object(Point::origin(), &cut_out),
+                object(Point::new(5., 0., 0.), &solid),
+            ],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        let (hit, _) = scene.cast_ray(ray, 0).unwrap();
+        assert!((hit.distance - 9.).abs() < 1e-3);
+        // Shadows go through the cut out sphere unattenuated
+        assert_eq!(
+            scene.transmittance(ray, 7., 0),
+            LinearColor::new(1., 1., 1.)
+        );
+    }
+
+    #[test]
+    fn sides_work() {
+        use crate::material::UniformMaterial;
+        use crate::render::Sides;
+        use crate::shape::Plane;
+        use crate::texture::UniformTexture;
+
+        let scene = |sides| {
+            let wall = Object::new(
+                Plane::new(Point::origin(), Vector::x()).into(),
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(1., 1., 1.),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            );
+            Scene::new(
+                Camera::default(),
+                LightAggregate::empty(),
+                vec![wall.with_sides(sides)],
+                LinearColor::black().into(),
+                0,
+                0,
+                1.,
+            )
+        };
+        // The ray hits the back of the wall
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        let (hit, _) = scene(Sides::Oriented).cast_ray(ray, 0).unwrap();
+        assert!(hit.back_face);
+        assert_eq!(hit.normal, Vector::x_axis());
+        let (hit, _) = scene(Sides::Both).cast_ray(ray, 0).unwrap();
+        assert!(hit.back_face);
+        assert_eq!(hit.normal, -Vector::x_axis());
+        assert!(scene(Sides::Front).cast_ray(ray, 0).is_none());
+        let (hit, _) = (scene(Sides::Front)
+            .cast_ray(Ray::new(Point::new(5., 0., 0.), -Vector::x_axis()), 0))
+        .unwrap();
+        assert!(!hit.back_face);
+    }
+
+    #[test]
+    fn transparent_shadows_are_tinted() {
+        use crate::material::{PrincipledMaterial, UniformMaterial};
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let glass = UniformMaterial::new(LightProperties::new(
+            LinearColor::black(),
+            LinearColor::black(),
+            Some(ReflTransEnum::Transparency {
+                coef: 0.5,
+                index: 1.5,
+            }),
+        ))
+        .with_absorption(LinearColor::new(0., 0., 1.));
+        let tinted = PrincipledMaterial {
+            transmission: 1.,
+            ..PrincipledMaterial::new(LinearColor::new(1., 0.5, 0.25))
+        };
+        let object = |center, material: MaterialEnum| {
+            Object::new(
+                Sphere::new(center, 1.).into(),
+                material,
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )
+        };
+        let scene = Scene::new(
+            Camera::default(),
+            LightAggregate::empty(),
+            vec![
+                object(Point::origin(), glass.into()),
+                object(Point::new(5., 0., 0.), tinted.into()),
+            ],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        // Both sides of the glass let half of the light through, its inside absorbs the blue
+        let through_glass = scene.transmittance(ray, 7., 0);
+        let expected = LinearColor::new(0.25, 0.25, 0.25 * Float::exp(-2.));
+        assert!(
+            (through_glass.r - expected.r).abs() < 1e-4,
+            "{:?}",
+            through_glass
+        );
+        assert!(
+            (through_glass.b - expected.b).abs() < 1e-4,
+            "{:?}",
+            through_glass
+        );
+        // The principled material is tinted by its base color on each side
+        let through_both = scene.transmittance(ray, 20., 0);
+        assert!(
+            (through_both.r - expected.r).abs() < 1e-4,
+            "{:?}",
+            through_both
+        );
+        assert!(
+            (through_both.g - expected.g / 4.).abs() < 1e-4,
+            "{:?}",
+            through_both
+        );
+    }
+
+    #[test]
+    fn absorption_works() {
+        use crate::light::AmbientLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let glass = |absorption| {
+            let material = UniformMaterial::new(LightProperties::new(
+                LinearColor::black(),
+                LinearColor::black(),
+                // Do not bend nor reflect rays
+                Some(ReflTransEnum::Transparency {
+                    coef: 1.,
+                    index: 1.,
+                }),
+            ));
+            match absorption {
+                Some(absorption) => material.with_absorption(absorption),
+                None => material,
+            }
+        };
+        let scene = |glass: UniformMaterial| {
+            let white = || UniformTexture::new(LinearColor::new(1., 1., 1.)).into();
+            let backdrop = UniformMaterial::new(LightProperties::new(
+                LinearColor::new(1., 1., 1.),
+                LinearColor::black(),
+                None,
+            ));
+            Scene::new(
+                Camera::default(),
+                LightAggregate::new(
+                    vec![AmbientLight::new(LinearColor::new(1., 1., 1.))],
+                    vec![],
+                    vec![],
+                    vec![],
+                ),
+                vec![
+                    Object::new(
+                        Sphere::new(Point::origin(), 1.).into(),
+                        glass.into(),
+                        white(),
+                    ),
+                    Object::new(
+                        Sphere::new(Point::new(5., 0., 0.), 1.).into(),
+                        backdrop.into(),
+                        white(),
+                    ),
+                ],
+                LinearColor::black().into(),
+                0,
+                3,
+                1.,
+            )
+        };
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        let mut rng = StdRng::seed_from_u64(42);
+        let clear = scene(glass(None)).trace(ray, &mut rng, &mut LightContributions::untracked());
+        assert!(clear.r > 0.);
+        // The ray goes through 2 units of absorbing medium
+        let tinted = scene(glass(Some(LinearColor::new(0.5, 0., 0.)))).trace(
+            ray,
+            &mut rng,
+            &mut LightContributions::untracked(),
+        );
+        assert!((tinted.r - clear.r * Float::exp(-1.)).abs() < 1e-3);
+        assert!((tinted.g - clear.g).abs() < 1e-5);
+    }
+
+    #[test]
+    fn area_lights_cast_soft_shadows() {
+        use crate::light::AreaLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::{Plane, ShapeEnum, Sphere};
+        use crate::texture::UniformTexture;
+
+        let object = |shape: ShapeEnum| {
+            Object::new(
+                shape,
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(1., 1., 1.),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )
+        };
+        let scene = |objects| {
+            let light = AreaLight::new(
+                Point::new(-1., 4., -1.),
+                Vector::new(2., 0., 0.),
+                Vector::new(0., 0., 2.),
+                LinearColor::new(1., 1., 1.),
+                16,
+            );
+            Scene::new(
+                Camera::default(),
+                LightAggregate::empty().with_area_lights(vec![light]),
+                objects,
+                LinearColor::black().into(),
+                0,
+                0,
+                1.,
+            )
+        };
+        let ground = || object(Plane::new(Point::origin(), Vector::y()).into());
+        let lit = scene(vec![ground()]);
+        // The blocker only hides part of the light from the ground below it
+        let shadowed = scene(vec![
+            ground(),
+            object(Sphere::new(Point::new(0., 2., 0.), 0.3).into()),
+        ]);
+        let ray = Ray::new(Point::new(0., 1., 0.), -Vector::y_axis());
+        let mut rng = StdRng::seed_from_u64(42);
+        let full = lit
+            .trace(ray, &mut rng, &mut LightContributions::untracked())
+            .r;
+        let penumbra = shadowed
+            .trace(ray, &mut rng, &mut LightContributions::untracked())
+            .r;
+        assert!(full > 0.);
+        assert!(penumbra > 0.);
+        assert!(penumbra < full);
+    }
+
+    #[test]
+    fn ambient_occlusion_darkens_creases() {
+        use crate::light::AmbientLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::{Plane, ShapeEnum, Sphere};
+        use crate::texture::UniformTexture;
+
+        let object = |shape: ShapeEnum| {
+            Object::new(
+                shape,
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(1., 1., 1.),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )
+        };
+        // A ball resting on the ground, lit uniformly
+        let mut scene = Scene::new(
+            Camera::default(),
+            LightAggregate::new(
+                vec![AmbientLight::new(LinearColor::new(0.5, 0.5, 0.5))],
+                vec![],
+                vec![],
+                vec![],
+            ),
+            vec![
+                object(Plane::new(Point::origin(), Vector::y()).into()),
+                object(Sphere::new(Point::new(0., 1., 0.), 1.).into()),
+            ],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let ambient_at = |scene: &Scene, point: Point| {
+            let ray = Ray::new(point + Vector::y() * 0.01, -Vector::y_axis());
+            let (hit, object) = scene.cast_ray(ray, 0).unwrap();
+            let mut rng = StdRng::seed_from_u64(42);
+            let color = LinearColor::new(1., 1., 1.);
+            let mut lights = LightContributions::untracked();
+            scene.illuminate_ambient(object, &ray, color, &hit, 0, &mut rng, &mut lights)
+        };
+        let crease = Point::new(0.3, 0., 0.);
+        let far = Point::new(5., 0., 0.);
+        assert_eq!(ambient_at(&scene, crease), LinearColor::new(0.5, 0.5, 0.5));
+        scene.set_ambient_occlusion(Some(AmbientOcclusion::new(3.).with_samples(64)));
+        // Far from the ball, nothing occludes the ground
+        assert_eq!(ambient_at(&scene, far), LinearColor::new(0.5, 0.5, 0.5));
+        let occluded = ambient_at(&scene, crease);
+        assert!(occluded.r > 0.);
+        assert!(occluded.r < 0.35);
+        // Geometry further than the occlusion distance casts no contact shadow
+        scene.set_ambient_occlusion(Some(AmbientOcclusion::new(0.01).with_samples(64)));
+        assert_eq!(
+            ambient_at(&scene, Point::new(1.5, 0., 0.)),
+            LinearColor::new(0.5, 0.5, 0.5)
+        );
+    }
+
+    #[test]
+    fn surface_signals_work() {
+        use crate::material::UniformMaterial;
+        use crate::shape::{Plane, ShapeEnum, Sphere};
+        use crate::texture::UniformTexture;
+
+        let object = |shape: ShapeEnum| {
+            Object::new(
+                shape,
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(1., 1., 1.),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )
+        };
+        // A ball resting on the ground
+        let scene = Scene::new(
+            Camera::default(),
+            LightAggregate::empty(),
+            vec![
+                object(Plane::new(Point::origin(), Vector::y()).into()),
+                object(Sphere::new(Point::new(0., 1., 0.), 1.).into()),
+            ],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let probe = SurfaceProbe {
+            radius: 3.,
+            samples: 64,
+        };
+        let signals_at = |point: Point| {
+            let ray = Ray::new(point + Vector::y() * 0.01, -Vector::y_axis());
+            let (hit, _) = scene.cast_ray(ray, 0).unwrap();
+            let mut rng = StdRng::seed_from_u64(42);
+            scene.surface_signals(&ray, &hit, probe, 0, &mut rng)
+        };
+        // The top of the ball is a convex, open, surface
+        let top = signals_at(Point::new(0., 2., 0.));
+        assert_eq!(top.occlusion, 1.);
+        assert_eq!(top.curvature, 1.);
+        // The ground far from the ball is flat
+        let far = signals_at(Point::new(5., 0., 0.));
+        assert_eq!(far.occlusion, 1.);
+        assert_eq!(far.curvature, 0.);
+        // Under the ball is a concave crease, opening away from it
+        let crease = signals_at(Point::new(0.3, 0., 0.));
+        assert!(crease.occlusion < 0.7);
+        assert!(crease.curvature < 0.);
+        assert!(crease.bent_normal.x > 0.);
+    }
+
+    #[test]
+    fn motion_blur_works() {
+        use crate::core::{Keyframe, Motion};
+        use crate::light::AmbientLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let white = LinearColor::new(1., 1., 1.);
+        // The ball only stays in front of the camera for the first quarter of the motion
+        let motion = Motion::new(
+            Point::origin(),
+            vec![
+                Keyframe::new(0., Vector::zeros()),
+                Keyframe::new(1., Vector::new(0., 4., 0.)),
+            ],
+        );
+        let scene = |camera: Camera| {
+            Scene::new(
+                camera,
+                LightAggregate::new(
+                    vec![AmbientLight::new(white.clone())],
+                    vec![],
+                    vec![],
+                    vec![],
+                ),
+                vec![Object::new(
+                    Sphere::new(Point::new(0., 0., 10.), 1.).into(),
+                    UniformMaterial::new(LightProperties::new(
+                        white.clone(),
+                        LinearColor::black(),
+                        None,
+                    ))
+                    .into(),
+                    UniformTexture::new(white.clone()).into(),
+                )
+                .with_motion(motion.clone())],
+                LinearColor::black().into(),
+                64,
+                0,
+                1.,
+            )
+        };
+        let camera = || Camera::new(Point::origin(), Vector::z(), Vector::y(), 0.01, 1., 1, 1);
+        let still = scene(camera()).render_with_seed(42);
+        assert_eq!(still.get_pixel(0, 0).0, [255, 255, 255]);
+        let blurred = scene(camera().with_shutter(0., 1.)).render_with_seed(42);
+        let [r, _, _] = blurred.get_pixel(0, 0).0;
+        assert!(96 < r && r < 176);
+    }
+
+    #[test]
+    fn shadow_pass_works() {
+        use crate::light::DirectionalLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::{Plane, ShapeEnum, Sphere};
+        use crate::texture::UniformTexture;
+
+        let object = |shape: ShapeEnum| {
+            Object::new(
+                shape,
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(1., 1., 1.),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )
+        };
+        let ground = object(Plane::new(Point::origin(), Vector::y()).into()).as_shadow_catcher();
+        let blocker = object(Sphere::new(Point::new(0., 2., 0.), 0.5).into());
+        let light = DirectionalLight::new(-Vector::y_axis(), LinearColor::new(1., 1., 1.));
+        let scene = Scene::new(
+            Camera::default(),
+            LightAggregate::new(vec![], vec![light], vec![], vec![]),
+            vec![ground, blocker],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let mut rng = StdRng::seed_from_u64(42);
+        let down = -Vector::y_axis();
+        // Below the blocker, no light reaches the ground
+        let shadowed = Ray::new(Point::new(0., 1., 0.), down);
+        assert_eq!(scene.shadow(shadowed, &mut rng), 1.);
+        let lit = Ray::new(Point::new(2., 1., 0.), down);
+        assert_eq!(scene.shadow(lit, &mut rng), 0.);
+        // Objects other than the catcher are transparent
+        let blocker = Ray::new(Point::new(0., 5., 0.), down);
+        assert_eq!(scene.shadow(blocker, &mut rng), 0.);
+    }
+
+    #[test]
+    fn light_contributions_add_up() {
+        use crate::core::ReflTransEnum;
+        use crate::light::{AmbientLight, DirectionalLight, PointLight};
+        use crate::material::UniformMaterial;
+        use crate::shape::{Plane, ShapeEnum, Sphere};
+        use crate::texture::UniformTexture;
+
+        let object = |shape: ShapeEnum, refl_trans| {
+            Object::new(
+                shape,
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(0.5, 0.5, 0.5),
+                    LinearColor::black(),
+                    refl_trans,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 0.5, 0.25)).into(),
+            )
+        };
+        let white = LinearColor::new(1., 1., 1.);
+        let lights = LightAggregate::new(
+            vec![AmbientLight::new(white.clone() * 0.1).with_name("fill")],
+            vec![DirectionalLight::new(-Vector::y_axis(), white.clone()).with_name("sun")],
+            vec![PointLight::new(Point::new(1., 3., 0.), white).with_name("fill")],
+            vec![],
+        );
+        assert_eq!(lights.light_names(), &["fill", "sun"]);
+        let mut scene = Scene::new(
+            Camera::default(),
+            lights,
+            vec![
+                object(Plane::new(Point::origin(), Vector::y()).into(), None),
+                object(
+                    Sphere::new(Point::new(0., 1., 0.), 0.5).into(),
+                    Some(ReflTransEnum::Reflectivity { coef: 0.5 }),
+                ),
+            ],
+            LinearColor::black().into(),
+            0,
+            3,
+            1.,
+        );
+        let check = |scene: &Scene| {
+            let mut rng = StdRng::seed_from_u64(42);
+            for &origin in &[Point::new(0., 5., 0.), Point::new(0.3, 5., 0.2)] {
+                let ray = Ray::new(origin, -Vector::y_axis());
+                let mut lights = LightContributions::new(2);
+                let total = (scene.integrator).radiance(scene, ray, &mut rng, &mut lights);
+                let sum: LinearColor = lights.values().iter().cloned().sum();
+                assert!((total.r - sum.r).abs() < 1e-5, "{:?} != {:?}", total, sum);
+                assert!((total.g - sum.g).abs() < 1e-5, "{:?} != {:?}", total, sum);
+                assert!(lights.values().iter().all(|v| v.luminance() > 0.));
+            }
+        };
+        check(&scene);
+        scene.set_integrator(Pathtracer::new(3).into());
+        check(&scene);
+    }
+
+    #[test]
+    fn render_lights_matches_render() {
+        use crate::light::PointLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let light = PointLight::new(Point::new(0., 2., 0.), LinearColor::new(1., 1., 1.));
+        let scene = Scene::new(
+            Camera::new(Point::origin(), Vector::z(), Vector::y(), 90., 1., 8, 8),
+            LightAggregate::new(vec![], vec![], vec![light.with_name("key")], vec![]),
+            vec![Object::new(
+                Sphere::new(Point::new(0., 0., 3.), 1.).into(),
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(1., 1., 1.),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )],
+            LinearColor::black().into(),
+            2,
+            0,
+            1.,
+        );
+        let (image, lights) = scene.render_lights_with_seed(42);
+        assert_eq!(image, scene.render_with_seed(42));
+        // The only light is responsible for the whole image
+        assert_eq!(lights, vec![("key", image)]);
+    }
+
+    #[test]
+    fn half_precision_works() {
+        use crate::light::PointLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let light = PointLight::new(Point::new(0., 2., 0.), LinearColor::new(1., 1., 1.));
+        let mut scene = Scene::new(
+            Camera::new(Point::origin(), Vector::z(), Vector::y(), 90., 1., 8, 8),
+            LightAggregate::new(vec![], vec![], vec![light.with_name("key")], vec![]),
+            vec![Object::new(
+                Sphere::new(Point::new(0., 0., 3.), 1.).into(),
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(0.7, 0.3, 0.1),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )],
+            LinearColor::black().into(),
+            2,
+            0,
+            1.,
+        );
+        let (single, lights) = scene.render_lights_with_seed(42);
+        let single_light = lights[0].1.clone();
+        scene.set_precision(Precision::Half);
+        let (half, half_lights) = scene.render_lights_with_seed(42);
+        // Rounding the colors to half precision barely changes the 8-bit images
+        let close = |lhs: &RgbImage, rhs: &RgbImage| {
+            (lhs.pixels().zip(rhs.pixels()))
+                .all(|(lhs, rhs)| (lhs.0.iter().zip(&rhs.0)).all(|(l, r)| l.max(r) - l.min(r) <= 1))
+        };
+        assert!(close(&single, &half));
+        assert!(close(&single_light, &half_lights[0].1));
+        // The resolved layers are stored at half precision
+        let (image, variance, lights, _) = scene.render_light_buffers(42);
+        assert_eq!(image.precision(), Precision::Half);
+        assert_eq!(variance.precision(), Precision::Half);
+        assert_eq!(lights[0].1.precision(), Precision::Half);
+    }
+
+    #[test]
+    fn render_aovs_works() {
+        use crate::consts::FRAC_PI_2;
+        use crate::light::PointLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let light = PointLight::new(Point::new(0., 2., 0.), LinearColor::new(1., 1., 1.));
+        let scene = Scene::new(
+            Camera::new(
+                Point::origin(),
+                Vector::z(),
+                Vector::y(),
+                FRAC_PI_2,
+                1.,
+                8,
+                8,
+            ),
+            LightAggregate::new(vec![], vec![], vec![light.with_name("key")], vec![]),
+            vec![Object::new(
+                Sphere::new(Point::new(0., 0., 3.), 1.).into(),
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(0.7, 0.3, 0.1),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )],
+            LinearColor::black().into(),
+            2,
+            0,
+            1.,
+        );
+        let aovs = scene.render_aovs_with_seed(42);
+        let (beauty, _) = scene.render_buffer_with_limits(42, &RenderLimits::new());
+        assert_eq!(aovs.beauty, beauty);
+        assert_eq!(aovs.lights.len(), 1);
+        assert_eq!(aovs.lights[0].0, "key");
+        // The center of the image sees the front of the sphere, facing the camera
+        let normal = aovs.normal.get(4, 4);
+        assert!(normal.b < -0.5, "normal: {:?}", normal);
+        assert!((aovs.depth.get(4, 4).r - 2.).abs() < 0.25);
+        // The albedo layer is stored as `f32`, whatever the renderer's floating point type
+        let albedo = aovs.albedo.get(4, 4);
+        let expected = [0.7, 0.3, 0.1];
+        for (channel, expected) in [albedo.r, albedo.g, albedo.b].iter().zip(expected.iter()) {
+            assert!((channel - expected).abs() < 1e-6, "albedo: {:?}", albedo);
+        }
+        assert_eq!(aovs.albedo.get(0, 0), LinearColor::black());
+        // Only the pixels straddling the sphere's silhouette are noisy
+        assert!(aovs.variance.get(0, 0).r < 1e-6);
+        let noisy = (0..8).any(|x| (0..8).any(|y| aovs.variance.get(x, y).r > 0.));
+        assert!(noisy);
+        assert_eq!(aovs.heatmap(0.1).get(0, 0), LinearColor::new(0., 0., 1.));
+    }
+
+    #[test]
+    fn light_groups_add_up() {
+        use crate::consts::FRAC_PI_2;
+        use crate::light::PointLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let white = LinearColor::new(1., 1., 1.);
+        let light = |x| PointLight::new(Point::new(x, 2., 0.), white.clone());
+        let lights = LightAggregate::new(
+            vec![],
+            vec![],
+            vec![
+                light(-1.).with_name("left").with_group("key"),
+                light(0.).with_group("key"),
+                light(1.).with_group("rim"),
+            ],
+            vec![],
+        );
+        assert_eq!(lights.light_groups(), &["key", "rim"]);
+        assert_eq!(lights.contributions_count(), 3);
+        let scene = Scene::new(
+            Camera::new(
+                Point::origin(),
+                Vector::z(),
+                Vector::y(),
+                FRAC_PI_2,
+                1.,
+                8,
+                8,
+            ),
+            lights,
+            vec![Object::new(
+                Sphere::new(Point::new(0., 0., 3.), 1.).into(),
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(0.2, 0.2, 0.2),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(white.clone()).into(),
+            )],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let aovs = scene.render_aovs_with_seed(42);
+        assert_eq!(aovs.lights.len(), 1);
+        let groups: Vec<_> = aovs.groups.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(groups, vec!["key", "rim"]);
+        // Every light belongs to a group, which add up to the image
+        let (key, rim) = (&aovs.groups[0].1, &aovs.groups[1].1);
+        let color = aovs.beauty.get(4, 4);
+        let sum = key.get(4, 4) + rim.get(4, 4);
+        assert!(color.luminance() > 0.);
+        assert!((color.r - sum.r).abs() < 1e-5, "{:?} != {:?}", color, sum);
+        // The named light is also part of its group
+        assert!(key.get(4, 4).r > aovs.lights[0].1.get(4, 4).r);
+    }
+
+    #[test]
+    fn reject_outliers_works() {
+        let samples: Vec<_> = [0.2, 0.1, 5., 0.3, 0.2]
+            .iter()
+            .map(|&value| {
+                let color = LinearColor::new(value, value, value);
+                sample(0.5, 0.5, color, LightContributions::untracked())
+            })
+            .collect();
+        let kept = reject_outliers(samples.clone(), 4.);
+        assert_eq!(kept.len(), 4);
+        assert!(kept.iter().all(|sample| sample.layers[0].r < 1.));
+        // The median is always kept
+        assert_eq!(reject_outliers(samples, 1.).len(), 3);
+    }
+
+    #[test]
+    fn mean_variance_is_unbiased() {
+        let samples = |values: &[Float]| -> Vec<_> {
+            values
+                .iter()
+                .map(|&value| {
+                    let color = LinearColor::new(value, value, value);
+                    sample(0.5, 0.5, color, LightContributions::untracked())
+                })
+                .collect()
+        };
+        assert_eq!(mean_variance(&samples(&[])), 0.);
+        assert_eq!(mean_variance(&samples(&[0.5])), 0.);
+        // A sample variance of 1/2, over the 2 samples of the mean
+        assert!((mean_variance(&samples(&[0., 1.])) - 0.25).abs() < 1e-6);
+        // A sample variance of 5/3, over the 4 samples of the mean
+        let variance = mean_variance(&samples(&[1., 2., 3., 4.]));
+        assert!((variance - 5. / 12.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn linear_bvh_works() {
+        let yaml = r#"
+            camera:
+              origin: [-5.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 8
+              y: 8
+            bvh_builder: linear
+            objects:
+              - shape: {type: sphere, center: [0.0, 0.0, 0.0], radius: 1.0}
+                material: {type: uniform, diffuse: {r: 1.0, g: 1.0, b: 1.0}, specular: {r: 0.0, g: 0.0, b: 0.0}}
+                texture: {type: uniform, color: {r: 1.0, g: 1.0, b: 1.0}}
+              - shape: {type: sphere, center: [3.0, 0.0, 0.0], radius: 1.0}
+                material: {type: uniform, diffuse: {r: 1.0, g: 1.0, b: 1.0}, specular: {r: 0.0, g: 0.0, b: 0.0}}
+                texture: {type: uniform, color: {r: 1.0, g: 1.0, b: 1.0}}
+        "#;
+        let scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        let ray = Ray::new(Point::new(5., 0., 0.), -Vector::x_axis());
+        let (hit, _) = scene.cast_ray(ray, 0).unwrap();
+        assert_eq!(hit.distance, 1.);
+    }
+
+    #[test]
+    fn spatial_bvh_works() {
+        let yaml = r#"
+            camera:
+              origin: [-5.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 8
+              y: 8
+            bvh_builder: {spatial: {budget: 0.5}}
+            objects:
+              - shape: {type: triangle, corners: [[-10.0, 0.0, -1.0], [10.0, 0.0, -1.0], [10.0, 0.0, 1.0]]}
+                material: {type: uniform, diffuse: {r: 1.0, g: 1.0, b: 1.0}, specular: {r: 0.0, g: 0.0, b: 0.0}}
+                texture: {type: uniform, color: {r: 1.0, g: 1.0, b: 1.0}}
+              - shape: {type: sphere, center: [3.0, 0.0, 0.0], radius: 1.0}
+                material: {type: uniform, diffuse: {r: 1.0, g: 1.0, b: 1.0}, specular: {r: 0.0, g: 0.0, b: 0.0}}
+                texture: {type: uniform, color: {r: 1.0, g: 1.0, b: 1.0}}
+        "#;
+        let scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        let ray = Ray::new(Point::new(5., 0., 0.), -Vector::x_axis());
+        let (hit, _) = scene.cast_ray(ray, 0).unwrap();
+        assert_eq!(hit.distance, 1.);
+        let ray = Ray::new(Point::new(-5., 1., -0.75), -Vector::y_axis());
+        let (hit, _) = scene.cast_ray(ray, 0).unwrap();
+        assert!((hit.distance - 1.).abs() < 1e-5);
+        let saved = serde_yaml::to_value(&scene).unwrap();
+        let reloaded: Scene = serde_yaml::from_value(saved).unwrap();
+        assert_eq!(reloaded.bvh_builder, BvhBuilder::Spatial { budget: 0.5 });
+    }
+
+    #[test]
+    fn bvh_cache_is_reused() {
+        let path = std::env::temp_dir().join(format!("scene-{}.bvh", std::process::id()));
+        let yaml = format!(
+            r#"
+            camera:
+              origin: [-5.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 8
+              y: 8
+            bvh_cache: {:?}
+            objects:
+              - shape: {{type: sphere, center: [0.0, 0.0, 0.0], radius: 1.0}}
+                material: {{type: uniform, diffuse: {{r: 1.0, g: 1.0, b: 1.0}}, specular: {{r: 0.0, g: 0.0, b: 0.0}}}}
+                texture: {{type: uniform, color: {{r: 1.0, g: 1.0, b: 1.0}}}}
+              - shape: {{type: sphere, center: [3.0, 0.0, 0.0], radius: 1.0}}
+                material: {{type: uniform, diffuse: {{r: 1.0, g: 1.0, b: 1.0}}, specular: {{r: 0.0, g: 0.0, b: 0.0}}}}
+                texture: {{type: uniform, color: {{r: 1.0, g: 1.0, b: 1.0}}}}
+            "#,
+            path
+        );
+        let built: Scene = serde_yaml::from_str(&yaml).unwrap();
+        assert!(built.bvh_cache_error().is_none());
+        let cache = std::fs::read(&path).unwrap();
+        let cached: Scene = serde_yaml::from_str(&yaml).unwrap();
+        // The cache is only written when it could not be used
+        assert_eq!(std::fs::read(&path).unwrap(), cache);
+        assert_eq!(cached.bvh, built.bvh);
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        let (hit, _) = cached.cast_ray(ray, 0).unwrap();
+        assert_eq!(hit.distance, 4.);
+        let saved = serde_yaml::to_value(&cached).unwrap();
+        assert_eq!(saved["bvh_cache"].as_str(), path.to_str());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn unwritable_bvh_cache_is_skipped() {
+        let path = std::env::temp_dir().join("pathtracer-missing-directory/scene.bvh");
+        let yaml = format!(
+            r#"
+            camera:
+              origin: [-5.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 8
+              y: 8
+            bvh_cache: {:?}
+            objects:
+              - shape: {{type: sphere, center: [0.0, 0.0, 0.0], radius: 1.0}}
+                material: {{type: uniform, diffuse: {{r: 1.0, g: 1.0, b: 1.0}}, specular: {{r: 0.0, g: 0.0, b: 0.0}}}}
+                texture: {{type: uniform, color: {{r: 1.0, g: 1.0, b: 1.0}}}}
+            "#,
+            path
+        );
+        let scene: Scene = serde_yaml::from_str(&yaml).unwrap();
+        assert!(!path.exists());
+        assert!(scene.bvh_cache_error().is_some());
+        let ray = Ray::new(Point::new(-5., 0., 0.), Vector::x_axis());
+        let (hit, _) = scene.cast_ray(ray, 0).unwrap();
+        assert_eq!(hit.distance, 4.);
+    }
+
+    #[test]
+    fn outlier_rejection_deserialization_works() {
+        let yaml = r#"
+            camera:
+              origin: [0.0, 0.0, 0.0]
+              forward: [0.0, 0.0, 1.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              distance_to_image: 1.0
+              x: 10
+              y: 10
+            outlier_rejection: 0.5
+        "#;
+        let scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(scene.outlier_rejection, Some(1.));
+    }
+
+    #[test]
+    fn filter_blurs_edges() {
+        let yaml = r#"
+            camera:
+              origin: [0.0, 0.0, 0.0]
+              forward: [0.0, 0.0, 1.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              x: 16
+              y: 16
+            background: {r: 1.0, g: 1.0, b: 1.0}
+            objects:
+              - shape: {type: sphere, center: [0.0, 0.0, 3.0], radius: 1.5}
+                material:
+                  type: uniform
+                  diffuse: {r: 0.0, g: 0.0, b: 0.0}
+                  specular: {r: 0.0, g: 0.0, b: 0.0}
+                texture: {type: uniform, color: {r: 0.0, g: 0.0, b: 0.0}}
+            aliasing_limit: 32
+            filter: {type: gaussian, radius: 2.0, sigma: 1.0}
+        "#;
+        let mut scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            scene.filter,
+            Filter::Gaussian {
+                radius: 2.,
+                sigma: 1.
+            }
+        );
+        let blurred_pixels = |image: &RgbImage| {
+            (image.pixels())
+                .filter(|pixel| 0 < pixel[0] && pixel[0] < 255)
+                .count()
+        };
+        let gaussian = blurred_pixels(&scene.render_with_seed(42));
+        scene.set_filter(Filter::default());
+        let sharp = blurred_pixels(&scene.render_with_seed(42));
+        assert!(gaussian > sharp, "{} <= {}", gaussian, sharp);
+    }
+
+    #[test]
+    fn cmj_sampler_reduces_noise() {
+        let yaml = r#"
+            camera:
+              origin: [0.0, 0.0, 0.0]
+              forward: [0.0, 0.0, 1.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              x: 8
+              y: 8
+            background: {r: 1.0, g: 1.0, b: 1.0}
+            objects:
+              - shape: {type: sphere, center: [0.0, 0.0, 3.0], radius: 1.5}
+                material:
+                  type: uniform
+                  diffuse: {r: 0.0, g: 0.0, b: 0.0}
+                  specular: {r: 0.0, g: 0.0, b: 0.0}
+                texture: {type: uniform, color: {r: 0.0, g: 0.0, b: 0.0}}
+            aliasing_limit: 16
+            sampler: cmj
+        "#;
+        let mut scene: Scene = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(scene.sampler, Sampler::Cmj);
+        // The squared difference of the pixels between renders with different seeds
+        let noise = |scene: &Scene| {
+            let render = |seed| {
+                scene
+                    .render_buffer_with_limits(seed, &RenderLimits::new())
+                    .0
+            };
+            let (first, second) = (render(1), render(2));
+            (0..8)
+                .flat_map(|y| (0..8).map(move |x| (x, y)))
+                .map(|(x, y)| (first.get(x, y).r - second.get(x, y).r).powi(2))
+                .sum::<Float>()
+        };
+        let cmj = noise(&scene);
+        scene.set_sampler(Sampler::Random);
+        let random = noise(&scene);
+        assert!(cmj < random, "{} >= {}", cmj, random);
+    }
+
+    #[test]
+    fn surface_passes_use_the_sampler() {
+        use crate::core::Camera;
+
+        let mut scene = Scene::new(
+            Camera::default(),
+            LightAggregate::empty(),
+            Vec::new(),
+            LinearColor::black().into(),
+            16,
+            0,
+            1.0,
+        );
+        scene.set_sampler(Sampler::Cmj);
+        let offsets = scene.surface_offsets(&mut StdRng::seed_from_u64(42));
+        let samples = Sampler::Cmj.pixel_samples(16, &mut StdRng::seed_from_u64(42));
+        assert_eq!(offsets, samples);
+        scene.aliasing_limit = 0;
+        let offsets = scene.surface_offsets(&mut StdRng::seed_from_u64(42));
+        assert_eq!(offsets, vec![(0.5, 0.5)]);
+    }
+
+    #[test]
+    fn crop_matches_render() {
+        use crate::light::PointLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let light = PointLight::new(Point::new(0., 2., 0.), LinearColor::new(1., 1., 1.));
+        let mut scene = Scene::new(
+            Camera::new(Point::origin(), Vector::z(), Vector::y(), 90., 1., 8, 6),
+            LightAggregate::new(vec![], vec![], vec![light], vec![]),
+            vec![Object::new(
+                Sphere::new(Point::new(0., 0., 3.), 1.).into(),
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(1., 1., 1.),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let image = scene.render_with_seed(42);
+        scene.set_crop(Some(Crop::Pixels([2, 1, 4, 3])));
+        let cropped = scene.render_with_seed(42);
+        assert_eq!(cropped.dimensions(), (4, 3));
+        for (x, y, pixel) in cropped.enumerate_pixels() {
+            assert_eq!(pixel, image.get_pixel(x + 2, y + 1));
+        }
+    }
+
+    fn tile_scene() -> Scene {
+        use crate::consts::FRAC_PI_2;
+        use crate::light::PointLight;
+        use crate::material::UniformMaterial;
+        use crate::shape::Sphere;
+        use crate::texture::UniformTexture;
+
+        let light = PointLight::new(Point::new(0., 2., 0.), LinearColor::new(1., 1., 1.));
+        Scene::new(
+            Camera::new(
+                Point::origin(),
+                Vector::z(),
+                Vector::y(),
+                FRAC_PI_2,
+                1.,
+                40,
+                40,
+            ),
+            LightAggregate::new(vec![], vec![], vec![light], vec![]),
+            vec![Object::new(
+                Sphere::new(Point::new(0., 0., 3.), 1.).into(),
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(0.7, 0.3, 0.1),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )],
+            LinearColor::new(1., 1., 1.).into(),
+            4,
+            0,
+            1.,
+        )
+    }
+
+    #[test]
+    fn tile_orders_render_the_same_image() {
+        let mut scene = tile_scene();
+        scene.set_tile_order(TileOrder::Spiral);
+        let image = scene.render_with_seed(42);
+        for &order in &[TileOrder::Hilbert, TileOrder::CenterOut] {
+            scene.set_tile_order(order);
+            assert_eq!(scene.render_with_seed(42), image, "{:?}", order);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn center_out_renders_center_first() {
+        let mut scene = tile_scene();
+        scene.set_tile_order(TileOrder::CenterOut);
+        // A single thread renders the tiles in their exact order
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build();
+        let limits = RenderLimits::new().with_pixel_budget(16 * 16);
+        let (image, _) = pool
+            .unwrap()
+            .install(|| scene.render_with_limits(42, &limits));
+        assert_ne!(image.get_pixel(20, 20), &image::Rgb([0, 0, 0]));
+        assert_eq!(image.get_pixel(0, 0), &image::Rgb([0, 0, 0]));
+        assert_eq!(image.get_pixel(39, 39), &image::Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn cast_packet_matches_cast_ray() {
+        let scene = tile_scene();
+        let ray = |x: Float, y: Float| {
+            Ray::new(Point::origin(), Unit::new_normalize(Vector::new(x, y, 3.)))
+        };
+        // Rays hitting the sphere at different distances, and one missing it
+        let rays = [ray(0., 0.), ray(0.5, 0.2), ray(-0.8, 0.1), ray(3., 3.)];
+        let hits = scene.cast_packet(&rays, 0);
+        for (ray, hit) in rays.iter().zip(hits.iter()) {
+            let expected = scene.cast_ray(*ray, 0);
+            assert_eq!(hit.is_some(), expected.is_some());
+            if let (Some((hit, _)), Some((expected, _))) = (hit, expected) {
+                assert_eq!(hit.distance, expected.distance);
+                assert_eq!(hit.normal, expected.normal);
+            }
+        }
+        assert!(hits[3].is_none());
+    }
+
+    #[test]
+    fn packet_anti_aliasing_matches_single_rays() {
+        fn render(scene: &Scene, packets: bool) -> Vec<Vec<Sample>> {
+            let mut rng = StdRng::seed_from_u64(42);
+            let cast = |rays: &[Ray]| {
+                if packets {
+                    scene.cast_rays(rays, 0)
+                } else {
+                    rays.iter().map(|ray| scene.cast_ray(*ray, 0)).collect()
+                }
+            };
+            let pixels = (0..40).flat_map(|y| (0..40).map(move |x| (x as Float, y as Float)));
+            pixels
+                .map(|(x, y)| {
+                    let camera = &scene.camera;
+                    scene
+                        .anti_alias_pixel_with(camera, x, y, 0, &mut rng, cast)
+                        .0
+                })
+                .collect()
+        }
+
+        let mut scene = tile_scene();
+        assert_eq!(render(&scene, true), render(&scene, false));
+        // The path tracer draws its bounces from the same generator as the camera rays
+        scene.set_integrator(Pathtracer::new(2).into());
+        assert_eq!(render(&scene, true), render(&scene, false));
+    }
+
+    #[cfg(feature = "gpu")]
+    #[test]
+    fn render_gpu_falls_back_to_the_cpu() {
+        use crate::light::{LightLink, PointLight};
+
+        // Linked lights cannot be rendered on the GPU
+        let mut scene = tile_scene();
+        let light = PointLight::new(Point::new(0., 2., 0.), LinearColor::new(1., 1., 1.))
+            .with_link(LightLink::excluding(&["floor"]));
+        scene.lights = LightAggregate::new(vec![], vec![], vec![light], vec![]);
+        let (image, err) = scene.render_gpu_with_seed(42);
+        assert!(matches!(err, Some(GpuError::Unsupported(_))));
+        assert_eq!(image, scene.render_with_seed(42));
+    }
+
+    #[test]
+    fn render_with_limits_keeps_partial_image() {
+        use std::sync::atomic::AtomicBool;
+        use std::sync::Arc;
+
+        // Every rendered pixel is gray
+        let scene = Scene::new(
+            Camera::new(Point::origin(), Vector::z(), Vector::y(), 90., 1., 8, 6),
+            LightAggregate::empty(),
+            vec![],
+            LinearColor::new(0.5, 0.5, 0.5).into(),
+            0,
+            0,
+            1.,
+        );
+        let image = scene.render_with_seed(42);
+        let (partial, complete) =
+            scene.render_with_limits(42, &RenderLimits::new().with_pixel_budget(10));
+        assert!(!complete);
+        let black = Rgb([0, 0, 0]);
+        let rendered = partial.enumerate_pixels().filter(|(x, y, pixel)| {
+            assert!(**pixel == black || *pixel == image.get_pixel(*x, *y));
+            **pixel != black
+        });
+        assert_eq!(rendered.count(), 10);
+
+        let cancelled = Arc::new(AtomicBool::new(true));
+        let limits = RenderLimits::new().with_cancellation(cancelled);
+        let (partial, complete) = scene.render_with_limits(42, &limits);
+        assert!(!complete);
+        assert!(partial.pixels().all(|pixel| *pixel == black));
+    }
+
+    #[test]
+    fn draw_lights_works() {
+        use crate::light::{DirectionalLight, PointLight};
+
+        let white = LinearColor::new(1., 1., 1.);
+        let lights = LightAggregate::new(
+            vec![],
+            vec![DirectionalLight::new(Vector::x_axis(), white.clone())],
+            vec![
+                PointLight::new(Point::new(0., 0., 3.), white.clone()),
+                // Behind the camera
+                PointLight::new(Point::new(0., 0., -3.), white),
+            ],
+            vec![],
+        );
+        let mut scene = Scene::new(
+            Camera::new(Point::origin(), Vector::z(), Vector::y(), 90., 1., 100, 100),
+            lights,
+            vec![],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let mut image = RgbImage::new(100, 100);
+        scene.draw_lights(&mut image);
+        // The point light in the middle, and the directional light's arrow at the top
+        assert_eq!(image.get_pixel(50, 50), &LIGHT_OUTLINE_COLOR);
+        assert_eq!(image.get_pixel(50, 15), &LIGHT_OUTLINE_COLOR);
+        let drawn = image
+            .pixels()
+            .filter(|p| *p == &LIGHT_OUTLINE_COLOR)
+            .count();
+        assert!(drawn < 100);
+        // The overlay follows the cropped region
+        scene.set_crop(Some(Crop::Pixels([40, 40, 20, 20])));
+        let mut image = RgbImage::new(20, 20);
+        scene.draw_lights(&mut image);
+        assert_eq!(image.get_pixel(10, 10), &LIGHT_OUTLINE_COLOR);
+    }
+
+    #[test]
+    fn unbounded_objects_work() {
+        use crate::material::UniformMaterial;
+        use crate::shape::{Plane, ShapeEnum, Sphere};
+        use crate::texture::UniformTexture;
+
+        let object = |shape: ShapeEnum, name| {
+            Object::new(
+                shape,
+                UniformMaterial::new(LightProperties::new(
+                    LinearColor::new(1., 1., 1.),
+                    LinearColor::black(),
+                    None,
+                ))
+                .into(),
+                UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+            )
+            .with_name(name)
+        };
+        let ground = || object(Plane::new(Point::origin(), Vector::y()).into(), "ground");
+        let scene = Scene::new(
+            Camera::default(),
+            LightAggregate::empty(),
+            vec![
+                ground(),
+                object(Sphere::new(Point::new(0., 1., 0.), 1.).into(), "ball"),
+            ],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let down = -Vector::y();
+        let hit = |origin| scene.raycast(origin, down).and_then(|info| info.name);
+        assert_eq!(hit(Point::new(0., 5., 0.)), Some("ball"));
+        assert_eq!(hit(Point::new(1000., 5., 0.)), Some("ground"));
+        assert_eq!(hit(Point::new(1000., -5., 0.)), None);
+
+        // A scene with only unbounded objects has an empty BVH
+        let scene = Scene::new(
+            Camera::default(),
+            LightAggregate::empty(),
+            vec![ground()],
+            LinearColor::black().into(),
+            0,
+            0,
+            1.,
+        );
+        let info = scene.raycast(Point::new(0., 5., 0.), down).unwrap();
+        assert_eq!(info.distance, 5.);
+    }
+
+    #[test]
+    fn empty_scene_works() {
         use crate::core::Camera;
         use crate::render::{LightAggregate, Scene};
 
-        let _scene = Scene::new(
+        let scene = Scene::new(
             Camera::default(),
             LightAggregate::empty(),
-            Vec::new(),           // Objects list
-            LinearColor::black(), // Background color
-            5,                    // aliasing limit
-            3,                    // reflection recursion limit
-            0.0,                  // diffraction index
+            Vec::new(),                  // Objects list
+            LinearColor::black().into(), // Background color
+            5,                           // aliasing limit
+            3,                           // reflection recursion limit
+            0.0,                         // diffraction index
         );
+        assert!(scene.raycast(Point::origin(), Vector::x()).is_none());
     }
 }
@@ -0,0 +1,343 @@
+//! Building scenes programmatically
+
+use super::{IntegratorEnum, LightAggregate, MeshObject, Object, Scene};
+use crate::background::BackgroundEnum;
+use crate::core::Camera;
+use crate::light::LightEnum;
+use crate::shape::Shape;
+use crate::{Error, Float, Result};
+use std::ops::RangeInclusive;
+
+/// A builder to create a [`Scene`] in Rust, instead of writing its description.
+///
+/// Everything which is not set uses the same default as a scene description: the default
+/// [`Camera`], no lights, a black background, and ray tracing without anti-aliasing nor
+/// reflections.
+///
+/// [`Scene`]: ../scene/struct.Scene.html
+/// [`Camera`]: ../../core/camera/struct.Camera.html
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::core::{Camera, LightProperties, LinearColor};
+/// # use pathtracer::light::PointLight;
+/// # use pathtracer::material::UniformMaterial;
+/// # use pathtracer::render::{Object, Pathtracer, SceneBuilder};
+/// # use pathtracer::shape::Sphere;
+/// # use pathtracer::texture::UniformTexture;
+/// # use pathtracer::{Point, Vector};
+/// # use pathtracer::Float;
+/// #
+/// let scene = SceneBuilder::new()
+///     .set_camera(Camera::look_at(
+///         Point::new(-5.0, 0.0, 0.0),
+///         Point::origin(),
+///         Vector::y(),
+///         2. * Float::atan(1.), /* 90° in radian */
+///         1.0,
+///         16,
+///         16,
+///     ))
+///     .add_object(Object::new(
+///         Sphere::new(Point::origin(), 1.0).into(),
+///         UniformMaterial::new(LightProperties::new(
+///             LinearColor::new(1.0, 0.0, 0.0), // diffuse component
+///             LinearColor::new(0.0, 0.0, 0.0), // specular component
+///             None,
+///         ))
+///         .into(),
+///         UniformTexture::new(LinearColor::new(1.0, 1.0, 1.0)).into(),
+///     ))
+///     .add_light(PointLight::new(
+///         Point::new(-2.0, 2.0, 0.0),
+///         LinearColor::new(1.0, 1.0, 1.0),
+///     ))
+///     .set_integrator(Pathtracer::new(2))
+///     .build()
+///     .unwrap();
+/// assert_eq!(scene.render_with_seed(0).dimensions(), (16, 16));
+/// ```
+#[derive(Debug)]
+pub struct SceneBuilder {
+    camera: Camera,
+    cameras: Vec<(String, Camera)>,
+    lights: Vec<LightEnum>,
+    objects: Vec<Object>,
+    background: BackgroundEnum,
+    integrator: IntegratorEnum,
+    aliasing_limit: u32,
+    reflection_limit: u32,
+    diffraction_index: Float,
+    frames: Option<RangeInclusive<u32>>,
+}
+
+impl SceneBuilder {
+    /// Creates a new `SceneBuilder`, for an empty scene.
+    pub fn new() -> Self {
+        SceneBuilder {
+            camera: Camera::default(),
+            cameras: Vec::new(),
+            lights: Vec::new(),
+            objects: Vec::new(),
+            background: BackgroundEnum::default(),
+            integrator: IntegratorEnum::default(),
+            aliasing_limit: 0,
+            reflection_limit: 0,
+            diffraction_index: 1.,
+            frames: None,
+        }
+    }
+
+    /// Set the main camera, used to render the scene.
+    pub fn set_camera(mut self, camera: Camera) -> Self {
+        self.camera = camera;
+        self
+    }
+
+    /// Add a named camera, which can be rendered alongside the main one.
+    pub fn add_camera<S: Into<String>>(mut self, name: S, camera: Camera) -> Self {
+        self.cameras.push((name.into(), camera));
+        self
+    }
+
+    /// Add a light of any kind.
+    pub fn add_light<L: Into<LightEnum>>(mut self, light: L) -> Self {
+        self.lights.push(light.into());
+        self
+    }
+
+    /// Add an object.
+    pub fn add_object(mut self, object: Object) -> Self {
+        self.objects.push(object);
+        self
+    }
+
+    /// Add a mesh, as an object per face.
+    pub fn add_mesh(mut self, mesh: MeshObject) -> Self {
+        self.objects.extend(mesh.into_objects());
+        self
+    }
+
+    /// Set what is seen by the rays which do not hit any object.
+    pub fn set_background<B: Into<BackgroundEnum>>(mut self, background: B) -> Self {
+        self.background = background.into();
+        self
+    }
+
+    /// Set the integrator used to compute the color of each camera ray.
+    pub fn set_integrator<I: Into<IntegratorEnum>>(mut self, integrator: I) -> Self {
+        self.integrator = integrator.into();
+        self
+    }
+
+    /// Set the number of rays cast per pixel, for anti-aliasing.
+    pub fn set_aliasing_limit(mut self, limit: u32) -> Self {
+        self.aliasing_limit = limit;
+        self
+    }
+
+    /// Set the number of times rays can be reflected or refracted.
+    pub fn set_reflection_limit(mut self, limit: u32) -> Self {
+        self.reflection_limit = limit;
+        self
+    }
+
+    /// Set the refraction index of the medium the cameras are in.
+    pub fn set_diffraction_index(mut self, index: Float) -> Self {
+        self.diffraction_index = index;
+        self
+    }
+
+    /// Set the frames of the scene's animation, each of them being rendered at its own instant.
+    pub fn set_frames(mut self, frames: Option<RangeInclusive<u32>>) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    /// Check the scene's settings, then build it.
+    ///
+    /// The bounding boxes of its objects must be finite. Each camera's film must have pixels, and
+    /// its named cameras must have different names. Otherwise, an [`Error::InvalidParameter`] is
+    /// returned.
+    ///
+    /// [`Error::InvalidParameter`]: ../../error/enum.Error.html#variant.InvalidParameter
+    pub fn build(self) -> Result<Scene> {
+        let invalid = |message: String| Err(Error::InvalidParameter(message));
+        let bounded = (self.objects.iter()).filter(|object| object.shape.is_bounded());
+        for (index, object) in bounded.enumerate() {
+            let aabb = object.shape.aabb();
+            let finite = |point: &crate::Point| point.iter().all(|c| c.is_finite());
+            if !finite(&aabb.low) || !finite(&aabb.high) {
+                let name = object.name.clone().unwrap_or_else(|| index.to_string());
+                return invalid(format!("object '{}' has a non-finite bounding box", name));
+            }
+        }
+        let cameras = std::iter::once(("main", &self.camera))
+            .chain((self.cameras.iter()).map(|(name, camera)| (name.as_str(), camera)));
+        for (name, camera) in cameras {
+            if camera.film().width() == 0 || camera.film().height() == 0 {
+                return invalid(format!("camera '{}' has an empty film", name));
+            }
+        }
+        for (index, (name, _)) in self.cameras.iter().enumerate() {
+            if self.cameras[..index].iter().any(|(other, _)| other == name) {
+                return invalid(format!("camera '{}' is defined twice", name));
+            }
+        }
+        if self.diffraction_index.is_nan() || self.diffraction_index <= 0. {
+            return invalid(format!(
+                "the diffraction index must be positive, not {}",
+                self.diffraction_index
+            ));
+        }
+
+        let mut scene = Scene::new(
+            self.camera,
+            self.lights.into_iter().collect::<LightAggregate>(),
+            self.objects,
+            self.background,
+            self.aliasing_limit,
+            self.reflection_limit,
+            self.diffraction_index,
+        );
+        for (name, camera) in self.cameras {
+            scene.add_camera(&name, camera);
+        }
+        scene.set_integrator(self.integrator);
+        scene.set_frames(self.frames);
+        Ok(scene)
+    }
+}
+
+impl Default for SceneBuilder {
+    fn default() -> Self {
+        SceneBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{LightProperties, LinearColor};
+    use crate::light::{AmbientLight, PointLight};
+    use crate::material::UniformMaterial;
+    use crate::shape::{Plane, Sphere};
+    use crate::texture::UniformTexture;
+    use crate::{Point, Vector};
+
+    fn sphere() -> Object {
+        Object::new(
+            Sphere::new(Point::new(5., 0., 0.), 1.).into(),
+            UniformMaterial::new(LightProperties::new(
+                LinearColor::new(1., 0., 0.),
+                LinearColor::black(),
+                None,
+            ))
+            .into(),
+            UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+        )
+    }
+
+    fn build_err(builder: SceneBuilder) -> String {
+        match builder.build() {
+            Err(Error::InvalidParameter(message)) => message,
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("the scene should be invalid"),
+        }
+    }
+
+    #[test]
+    fn build_matches_description() {
+        let scene = SceneBuilder::new()
+            .add_object(sphere())
+            .add_light(AmbientLight::new(LinearColor::new(0.1, 0.1, 0.1)))
+            .add_light(PointLight::new(
+                Point::new(0., 2., 0.),
+                LinearColor::new(1., 1., 1.),
+            ))
+            .build()
+            .unwrap();
+        let yaml = r#"
+            camera:
+              origin: [0.0, 0.0, 0.0]
+              forward: [1.0, 0.0, 0.0]
+              up: [0.0, 1.0, 0.0]
+              fov: 90.0
+              x: 1080
+              y: 1080
+            lights:
+              ambients:
+                - color: {r: 0.1, g: 0.1, b: 0.1}
+              points:
+                - position: [0.0, 2.0, 0.0]
+                  color: {r: 1.0, g: 1.0, b: 1.0}
+            objects:
+              - shape:
+                  type: sphere
+                  center: [5.0, 0.0, 0.0]
+                  radius: 1.0
+                material:
+                  type: uniform
+                  diffuse: {r: 1.0, g: 0.0, b: 0.0}
+                  specular: {r: 0.0, g: 0.0, b: 0.0}
+                texture:
+                  type: uniform
+                  color: {r: 1.0, g: 1.0, b: 1.0}
+        "#;
+        let expected: Scene = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            serde_yaml::to_string(&scene).unwrap(),
+            serde_yaml::to_string(&expected).unwrap()
+        );
+    }
+
+    #[test]
+    fn build_checks_objects() {
+        assert!(SceneBuilder::new().build().is_ok());
+        let plane = Object::new(
+            Plane::new(Point::origin(), Vector::y()).into(),
+            sphere().material,
+            sphere().texture,
+        );
+        assert!(SceneBuilder::new().add_object(plane).build().is_ok());
+        let far = Object::new(
+            Sphere::new(Point::new(Float::INFINITY, 0., 0.), 1.).into(),
+            sphere().material,
+            sphere().texture,
+        );
+        assert_eq!(
+            build_err(SceneBuilder::new().add_object(far)),
+            "object '0' has a non-finite bounding box"
+        );
+    }
+
+    #[test]
+    fn build_checks_cameras() {
+        let empty = Camera::new(Point::origin(), Vector::x(), Vector::y(), 1., 1., 0, 16);
+        assert_eq!(
+            build_err(
+                SceneBuilder::new()
+                    .add_object(sphere())
+                    .add_camera("top", empty)
+            ),
+            "camera 'top' has an empty film"
+        );
+        let builder = SceneBuilder::new()
+            .add_object(sphere())
+            .add_camera("top", Camera::default())
+            .add_camera("top", Camera::default());
+        assert_eq!(build_err(builder), "camera 'top' is defined twice");
+    }
+
+    #[test]
+    fn build_checks_diffraction_index() {
+        let builder = SceneBuilder::new()
+            .add_object(sphere())
+            .set_diffraction_index(0.);
+        assert_eq!(
+            build_err(builder),
+            "the diffraction index must be positive, not 0"
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! Reconstruction of the image from the samples of its pixels, weighted by the filter
+
+use crate::core::{Filter, FrameBuffer, LinearColor, Precision};
+use crate::Float;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// A sample of the image at `(x, y)`, in pixel coordinates, made of its color followed by the
+/// contribution of each tracked light.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Sample {
+    pub x: Float,
+    pub y: Float,
+    pub layers: Vec<LinearColor>,
+}
+
+/// The sums of the samples splatted onto a rectangular region of the image.
+///
+/// Each sample is added to every pixel whose filter covers it, weighted by the filter at its
+/// offset from the pixel's center. Pixels are only normalized by the sum of their weights once
+/// every sample has been added, so that a sample is shared with the neighbouring pixels instead
+/// of only counting for the pixel which took it.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SplatBuffer {
+    xs: Range<u32>,
+    ys: Range<u32>,
+    layers: usize,
+    /// The weighted sum of the layers of the samples, `layers` per pixel.
+    sums: Vec<LinearColor>,
+    /// The sum of the weights of the samples, for each pixel.
+    weights: Vec<Float>,
+    /// The unweighted sum of the layers of the samples each pixel took itself, `layers` per pixel.
+    own_sums: Vec<LinearColor>,
+    /// The number of samples each pixel took itself.
+    own_counts: Vec<u32>,
+}
+
+impl SplatBuffer {
+    /// Creates a new empty `SplatBuffer` covering the pixels at `xs` and `ys`, each of them made
+    /// of `layers` colors.
+    pub fn new(xs: Range<u32>, ys: Range<u32>, layers: usize) -> Self {
+        let pixels = xs.len() * ys.len();
+        SplatBuffer {
+            xs,
+            ys,
+            layers,
+            sums: vec![LinearColor::black(); pixels * layers],
+            weights: vec![0.; pixels],
+            own_sums: vec![LinearColor::black(); pixels * layers],
+            own_counts: vec![0; pixels],
+        }
+    }
+
+    /// Creates a new empty `SplatBuffer` for the samples taken at `xs` and `ys`, covering every
+    /// pixel of the `(x, y, width, height)` window they can reach through `filter`.
+    pub fn around(
+        xs: Range<u32>,
+        ys: Range<u32>,
+        window: (u32, u32, u32, u32),
+        filter: &Filter,
+        layers: usize,
+    ) -> Self {
+        let (left, top, width, height) = window;
+        let reach = filter.radius().ceil() as u32;
+        let widen = |range: Range<u32>, start: u32, end: u32| {
+            range.start.saturating_sub(reach).max(start)..(range.end + reach).min(end)
+        };
+        SplatBuffer::new(
+            widen(xs, left, left + width),
+            widen(ys, top, top + height),
+            layers,
+        )
+    }
+
+    /// Get the index of the pixel at `(x, y)`, if it is part of the buffer.
+    fn index(&self, x: i64, y: i64) -> Option<usize> {
+        let (xs, ys) = (&self.xs, &self.ys);
+        if x < xs.start as i64 || x >= xs.end as i64 || y < ys.start as i64 || y >= ys.end as i64 {
+            return None;
+        }
+        let (x, y) = (
+            (x - xs.start as i64) as usize,
+            (y - ys.start as i64) as usize,
+        );
+        Some(y * xs.len() + x)
+    }
+
+    /// Add a sample to each pixel whose filter covers it.
+    pub fn splat(&mut self, filter: &Filter, sample: &Sample) {
+        let radius = filter.radius();
+        // The pixels whose center is within the filter's radius of the sample
+        let first = |coordinate: Float| (coordinate - 0.5 - radius).ceil() as i64;
+        let last = |coordinate: Float| (coordinate - 0.5 + radius).floor() as i64;
+        for y in first(sample.y)..=last(sample.y) {
+            for x in first(sample.x)..=last(sample.x) {
+                let index = match self.index(x, y) {
+                    Some(index) => index,
+                    None => continue,
+                };
+                let dx = sample.x - (x as Float + 0.5);
+                let dy = sample.y - (y as Float + 0.5);
+                let weight = filter.weight(dx, dy);
+                self.weights[index] += weight;
+                let sums = &mut self.sums[index * self.layers..(index + 1) * self.layers];
+                for (sum, layer) in sums.iter_mut().zip(&sample.layers) {
+                    *sum += layer.clone() * weight;
+                }
+            }
+        }
+        let (x, y) = (sample.x.floor() as i64, sample.y.floor() as i64);
+        if let Some(index) = self.index(x, y) {
+            self.own_counts[index] += 1;
+            let sums = &mut self.own_sums[index * self.layers..(index + 1) * self.layers];
+            for (sum, layer) in sums.iter_mut().zip(&sample.layers) {
+                *sum += layer.clone();
+            }
+        }
+    }
+
+    /// Add the sums of another `SplatBuffer` to the pixels they share.
+    pub fn add(&mut self, other: &SplatBuffer) {
+        for (i, y) in other.ys.clone().enumerate() {
+            for (j, x) in other.xs.clone().enumerate() {
+                let index = match self.index(x as i64, y as i64) {
+                    Some(index) => index,
+                    None => continue,
+                };
+                let other_index = i * other.xs.len() + j;
+                self.weights[index] += other.weights[other_index];
+                self.own_counts[index] += other.own_counts[other_index];
+                let layers = self.layers;
+                let range = index * layers..(index + 1) * layers;
+                let other_range = other_index * layers..(other_index + 1) * layers;
+                for (sum, value) in self.sums[range.clone()]
+                    .iter_mut()
+                    .zip(&other.sums[other_range.clone()])
+                {
+                    *sum += value.clone();
+                }
+                for (sum, value) in self.own_sums[range]
+                    .iter_mut()
+                    .zip(&other.own_sums[other_range])
+                {
+                    *sum += value.clone();
+                }
+            }
+        }
+    }
+
+    /// Normalize the sums of each pixel into its layers, one buffer per layer, at the same
+    /// coordinates relative to the buffers' top left corner.
+    pub fn resolve_into(&self, buffers: &mut [FrameBuffer]) {
+        let width = self.xs.len();
+        for index in 0..self.weights.len() {
+            let (x, y) = ((index % width) as u32, (index / width) as u32);
+            let layers = index * self.layers..(index + 1) * self.layers;
+            let (weight, count) = (self.weights[index], self.own_counts[index]);
+            let sums = self.sums[layers.clone()].iter().zip(&self.own_sums[layers]);
+            for (buffer, (sum, own_sum)) in buffers.iter_mut().zip(sums) {
+                let color = if weight > Float::EPSILON {
+                    sum.clone() / weight
+                } else if count > 0 {
+                    // Negative lobes can cancel out the few samples of a pixel, weigh the samples
+                    // it took equally instead
+                    own_sum.clone() / count as Float
+                } else {
+                    LinearColor::black()
+                };
+                buffer.set(x, y, &color);
+            }
+        }
+    }
+}
+
+/// The image reconstructed from the rows of samples as they are rendered, in any order.
+///
+/// The sums of a row of the image are only kept at single precision while the rows within the
+/// filter's reach of it are still being rendered. It is then resolved into a row of each layer,
+/// stored at the image's precision.
+#[derive(Debug)]
+pub(crate) struct Film {
+    window: (u32, u32, u32, u32),
+    reach: u32,
+    layers: usize,
+    precision: Precision,
+    /// The number of pixels of each row which were rendered.
+    rendered: Vec<u32>,
+    /// The sums of the rows which can still receive samples.
+    pending: BTreeMap<u32, SplatBuffer>,
+    /// The layers of each row, followed by its variance, once any of its pixels was rendered.
+    rows: Vec<Option<Vec<FrameBuffer>>>,
+}
+
+impl Film {
+    /// Creates a new empty `Film` for the `(x, y, width, height)` window of the image, whose
+    /// pixels are made of `layers` colors stored at `precision`.
+    pub fn new(
+        window: (u32, u32, u32, u32),
+        filter: &Filter,
+        layers: usize,
+        precision: Precision,
+    ) -> Self {
+        let (_, _, _, height) = window;
+        Film {
+            window,
+            reach: filter.radius().ceil() as u32,
+            layers,
+            precision,
+            rendered: vec![0; height as usize],
+            pending: BTreeMap::new(),
+            rows: (0..height).map(|_| None).collect(),
+        }
+    }
+
+    /// Add the samples of the pixels at `xs` on the row at `y`, splatted onto `splats`, along
+    /// with the variance of each of these pixels. Resolve the rows which can no longer receive
+    /// any samples.
+    pub fn add(&mut self, y: u32, xs: Range<u32>, splats: &SplatBuffer, variances: &[Float]) {
+        let (left, top, width, height) = self.window;
+        let layers = self.layers;
+        for row in splats.ys.clone() {
+            let pending = (self.pending.entry(row))
+                .or_insert_with(|| SplatBuffer::new(left..left + width, row..row + 1, layers));
+            pending.add(splats);
+        }
+        let variance = &mut self.row(y - top)[layers];
+        for (x, value) in xs.clone().zip(variances) {
+            variance.set(x - left, 0, &LinearColor::new(*value, 0., 0.));
+        }
+        self.rendered[(y - top) as usize] += xs.len() as u32;
+        // Only the rows within the filter's reach of this one can have been completed by it
+        let first = y.saturating_sub(self.reach).max(top);
+        let last = (y + self.reach).min(top + height - 1);
+        for row in first..=last {
+            if self.is_complete(row) {
+                self.resolve(row);
+            }
+        }
+    }
+
+    /// Get the buffers of the row at `index` from the top of the window, creating them if needed.
+    fn row(&mut self, index: u32) -> &mut Vec<FrameBuffer> {
+        let (_, _, width, _) = self.window;
+        let (layers, precision) = (self.layers, self.precision);
+        self.rows[index as usize].get_or_insert_with(|| {
+            (0..=layers)
+                .map(|_| FrameBuffer::new(width, 1, precision))
+                .collect()
+        })
+    }
+
+    /// Return true if every pixel of the rows within the filter's reach of `y` was rendered.
+    fn is_complete(&self, y: u32) -> bool {
+        let (_, top, width, height) = self.window;
+        let first = y.saturating_sub(self.reach).max(top);
+        let last = (y + self.reach).min(top + height - 1);
+        (first..=last).all(|row| self.rendered[(row - top) as usize] == width)
+    }
+
+    /// Normalize the sums of the row at `y` into its layers, and stop keeping them.
+    fn resolve(&mut self, y: u32) {
+        let (_, top, _, _) = self.window;
+        if let Some(pending) = self.pending.remove(&y) {
+            pending.resolve_into(self.row(y - top));
+        }
+    }
+
+    /// Return the image made of the resolved rows, one buffer per layer, followed by the
+    /// variance of each pixel. The rows which were not rendered are black.
+    pub fn finish(mut self) -> (Vec<FrameBuffer>, FrameBuffer) {
+        let (_, _, width, height) = self.window;
+        let mut buffers: Vec<_> = (0..=self.layers)
+            .map(|_| FrameBuffer::new(width, 0, self.precision))
+            .collect();
+        for index in 0..height {
+            for (buffer, row) in buffers.iter_mut().zip(self.row(index).drain(..)) {
+                // The rows have the width and the precision of the buffers
+                buffer.append(row).unwrap();
+            }
+        }
+        let variance = buffers.pop().unwrap();
+        (buffers, variance)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Resolve the single layer of the buffer, returning the layers of each pixel, row by row.
+    fn resolve(buffer: &SplatBuffer) -> Vec<Vec<LinearColor>> {
+        let (width, height) = (buffer.xs.len() as u32, buffer.ys.len() as u32);
+        let mut layer = [FrameBuffer::new(width, height, Precision::Single)];
+        buffer.resolve_into(&mut layer);
+        (0..width * height)
+            .map(|i| vec![layer[0].get(i % width, i / width)])
+            .collect()
+    }
+
+    fn sample(x: Float, y: Float, value: Float) -> Sample {
+        Sample {
+            x,
+            y,
+            layers: vec![LinearColor::new(value, value, value)],
+        }
+    }
+
+    #[test]
+    fn box_filter_keeps_samples_in_their_pixel() {
+        let filter = Filter::Box { radius: 0.5 };
+        let mut buffer = SplatBuffer::new(0..2, 0..1, 1);
+        buffer.splat(&filter, &sample(0.25, 0.5, 1.));
+        buffer.splat(&filter, &sample(0.75, 0.5, 3.));
+        buffer.splat(&filter, &sample(1.5, 0.5, 4.));
+        let pixels = resolve(&buffer);
+        assert_eq!(pixels[0], vec![LinearColor::new(2., 2., 2.)]);
+        assert_eq!(pixels[1], vec![LinearColor::new(4., 4., 4.)]);
+    }
+
+    #[test]
+    fn bright_sample_spreads_to_neighbours() {
+        let filter = Filter::Tent { radius: 1.5 };
+        let mut buffer = SplatBuffer::new(0..3, 0..3, 1);
+        for y in 0..3 {
+            for x in 0..3 {
+                buffer.splat(&filter, &sample(x as Float + 0.5, y as Float + 0.5, 0.));
+            }
+        }
+        // A single bright sample taken by the central pixel
+        buffer.splat(&filter, &sample(1.5, 1.5, 100.));
+        let pixels = resolve(&buffer);
+        let luminance = |x: usize, y: usize| pixels[y * 3 + x][0].luminance();
+        assert!(luminance(1, 1) > luminance(0, 1));
+        for &(x, y) in &[(0, 1), (2, 1), (1, 0), (1, 2), (0, 0), (2, 2)] {
+            assert!(luminance(x, y) > 0., "({}, {})", x, y);
+        }
+        // It is averaged with the dark samples around it
+        assert!(luminance(1, 1) < 100.);
+    }
+
+    #[test]
+    fn add_merges_overlapping_buffers() {
+        let filter = Filter::Tent { radius: 1. };
+        let mut whole = SplatBuffer::new(0..4, 0..1, 1);
+        let mut left = SplatBuffer::around(0..2, 0..1, (0, 0, 4, 1), &filter, 1);
+        let mut right = SplatBuffer::around(2..4, 0..1, (0, 0, 4, 1), &filter, 1);
+        for (x, value) in [(0.3, 1.), (1.9, 2.), (2.2, 5.), (3.6, 3.)].iter() {
+            let sample = sample(*x, 0.4, *value);
+            whole.splat(&filter, &sample);
+            let part = if *x < 2. { &mut left } else { &mut right };
+            part.splat(&filter, &sample);
+        }
+        let mut merged = SplatBuffer::new(0..4, 0..1, 1);
+        merged.add(&left);
+        merged.add(&right);
+        assert_eq!(merged, whole);
+    }
+
+    #[test]
+    fn cancelled_weights_fall_back_to_own_samples() {
+        let filter = Filter::mitchell(2.);
+        let mut buffer = SplatBuffer::new(0..1, 0..1, 1);
+        buffer.splat(&filter, &sample(0.25, 0.5, 2.));
+        buffer.splat(&filter, &sample(0.75, 0.5, 4.));
+        // As if the negative lobes of the neighbours' samples cancelled out the weights
+        buffer.weights[0] = 0.;
+        assert_eq!(resolve(&buffer)[0], vec![LinearColor::new(3., 3., 3.)]);
+        let empty = SplatBuffer::new(0..1, 0..1, 1);
+        assert_eq!(resolve(&empty)[0], vec![LinearColor::black()]);
+    }
+
+    #[test]
+    fn film_matches_whole_buffer() {
+        let filter = Filter::Tent { radius: 1.5 };
+        let window = (1, 2, 4, 5);
+        let mut whole = SplatBuffer::new(1..5, 2..7, 1);
+        let mut film = Film::new(window, &filter, 1, Precision::Single);
+        // Rows finish out of order, split in tiles
+        for &y in &[4, 2, 6, 3, 5] {
+            for xs in [1..3, 3..5].iter() {
+                let mut splats = SplatBuffer::around(xs.clone(), y..y + 1, window, &filter, 1);
+                for x in xs.clone() {
+                    let value = (x * 7 + y * 3) as Float;
+                    let sample = sample(x as Float + 0.3, y as Float + 0.6, value);
+                    splats.splat(&filter, &sample);
+                    whole.splat(&filter, &sample);
+                }
+                film.add(y, xs.clone(), &splats, &[1., 2.]);
+            }
+        }
+        assert!(film.pending.is_empty());
+        let (layers, variance) = film.finish();
+        let expected = resolve(&whole);
+        for (i, pixel) in (0..).zip(expected) {
+            let color = layers[0].get(i % 4, i / 4);
+            assert!((color - pixel[0].clone()).luminance().abs() < 1e-4, "{}", i);
+        }
+        assert_eq!(variance.get(2, 3), LinearColor::new(1., 0., 0.));
+        assert_eq!(variance.get(3, 3), LinearColor::new(2., 0., 0.));
+    }
+
+    #[test]
+    fn film_only_keeps_rows_around_the_rendered_ones() {
+        let filter = Filter::Tent { radius: 1.5 };
+        let (width, height) = (8, 32);
+        let window = (0, 0, width, height);
+        let mut film = Film::new(window, &filter, 3, Precision::Half);
+        for y in 0..height {
+            let mut splats = SplatBuffer::around(0..width, y..y + 1, window, &filter, 3);
+            for x in 0..width {
+                let mut sample = sample(x as Float + 0.5, y as Float + 0.5, 1.);
+                sample.layers = vec![sample.layers[0].clone(); 3];
+                splats.splat(&filter, &sample);
+            }
+            film.add(y, 0..width, &splats, &[0.; 8]);
+            // Only the rows which can still receive samples are kept at single precision
+            assert!(film.pending.len() <= 2 * film.reach as usize + 1);
+            for row in film.rows.iter().flatten() {
+                assert!(row.iter().all(|layer| layer.precision() == Precision::Half));
+            }
+        }
+        assert!(film.pending.is_empty());
+        let (layers, variance) = film.finish();
+        assert!(layers
+            .iter()
+            .all(|layer| layer.precision() == Precision::Half));
+        assert_eq!(variance.precision(), Precision::Half);
+        assert_eq!(layers[2].get(3, 17), LinearColor::new(1., 1., 1.));
+    }
+}
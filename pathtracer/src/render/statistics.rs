@@ -0,0 +1,167 @@
+//! Intersection statistics gathered while rendering
+
+use super::object::Object;
+use crate::Float;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Count the intersection tests made against a single `Object`, safe to share between threads.
+#[derive(Debug, Default)]
+pub(crate) struct IntersectionCounter {
+    tests: AtomicU64,
+    hits: AtomicU64,
+}
+
+impl IntersectionCounter {
+    /// Record an intersection test, and whether the object was hit.
+    pub(crate) fn record(&self, hit: bool) {
+        self.tests.fetch_add(1, Ordering::Relaxed);
+        if hit {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The intersection tests made against a group of objects during rendering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntersectionStatistics<'a> {
+    /// The name shared by the objects, `None` for a single unnamed object.
+    pub name: Option<&'a str>,
+    /// The index of the first object of the group, in the order they were given to the scene.
+    pub index: usize,
+    /// How many objects are part of the group, e.g: the triangles of a mesh.
+    pub objects: usize,
+    /// How many ray-object intersection tests were made.
+    pub tests: u64,
+    /// How many of those tests found an intersection, not necessarily the closest one.
+    pub hits: u64,
+}
+
+impl IntersectionStatistics<'_> {
+    /// The ratio of intersection tests which were successful, between 0.0 and 1.0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::render::IntersectionStatistics;
+    /// #
+    /// let stats = IntersectionStatistics {
+    ///     name: Some("rock"),
+    ///     index: 0,
+    ///     objects: 1280,
+    ///     tests: 400,
+    ///     hits: 100,
+    /// };
+    /// assert_eq!(stats.hit_ratio(), 0.25);
+    /// ```
+    pub fn hit_ratio(&self) -> Float {
+        if self.tests == 0 {
+            0.
+        } else {
+            self.hits as Float / self.tests as Float
+        }
+    }
+}
+
+/// Group the counters of objects sharing the same name, sorting by decreasing number of tests.
+pub(crate) fn gather<'a>(
+    objects: &'a [Object],
+    counters: &[IntersectionCounter],
+) -> Vec<IntersectionStatistics<'a>> {
+    let mut named: BTreeMap<&str, IntersectionStatistics> = BTreeMap::new();
+    let mut unnamed = Vec::new();
+    for (index, (object, counter)) in objects.iter().zip(counters).enumerate() {
+        let tests = counter.tests.load(Ordering::Relaxed);
+        let hits = counter.hits.load(Ordering::Relaxed);
+        match object.name.as_deref() {
+            Some(name) => {
+                let stats = named.entry(name).or_insert(IntersectionStatistics {
+                    name: Some(name),
+                    index,
+                    objects: 0,
+                    tests: 0,
+                    hits: 0,
+                });
+                stats.objects += 1;
+                stats.tests += tests;
+                stats.hits += hits;
+            }
+            None => unnamed.push(IntersectionStatistics {
+                name: None,
+                index,
+                objects: 1,
+                tests,
+                hits,
+            }),
+        }
+    }
+    let mut ans: Vec<_> = named.into_values().collect();
+    ans.extend(unnamed);
+    ans.sort_by(|lhs, rhs| rhs.tests.cmp(&lhs.tests).then(lhs.index.cmp(&rhs.index)));
+    ans
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{LightProperties, LinearColor};
+    use crate::material::UniformMaterial;
+    use crate::shape::Sphere;
+    use crate::texture::UniformTexture;
+    use crate::Point;
+
+    fn simple_object() -> Object {
+        Object::new(
+            Sphere::new(Point::origin(), 1.).into(),
+            UniformMaterial::new(LightProperties::new(
+                LinearColor::new(0.5, 0.5, 0.5),
+                LinearColor::black(),
+                None,
+            ))
+            .into(),
+            UniformTexture::new(LinearColor::new(1., 1., 1.)).into(),
+        )
+    }
+
+    #[test]
+    fn record_works() {
+        let counter = IntersectionCounter::default();
+        counter.record(true);
+        counter.record(false);
+        counter.record(false);
+        assert_eq!(counter.tests.load(Ordering::Relaxed), 3);
+        assert_eq!(counter.hits.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn gather_works() {
+        let objects = vec![
+            simple_object(),
+            simple_object().with_name("mesh"),
+            simple_object().with_name("mesh"),
+        ];
+        let counters: Vec<_> = (0..3).map(|_| IntersectionCounter::default()).collect();
+        counters[0].record(true);
+        counters[1].record(false);
+        counters[2].record(true);
+        assert_eq!(
+            gather(&objects, &counters),
+            vec![
+                IntersectionStatistics {
+                    name: Some("mesh"),
+                    index: 1,
+                    objects: 2,
+                    tests: 2,
+                    hits: 1,
+                },
+                IntersectionStatistics {
+                    name: None,
+                    index: 0,
+                    objects: 1,
+                    tests: 1,
+                    hits: 1,
+                },
+            ]
+        )
+    }
+}
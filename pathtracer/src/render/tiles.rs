@@ -0,0 +1,220 @@
+//! The order in which the parts of an image are rendered
+
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// The width and height, in pixels, of the square tiles an image is cut into.
+const TILE_SIZE: u32 = 16;
+
+/// The order in which the parts of an image are rendered, which is what a preview or an image
+/// rendered with a time limit shows first.
+///
+/// Except for scanlines, the image is cut into square tiles of the same size for every order, so
+/// that changing the order only changes which tiles are rendered first, not the rendered image.
+#[serde(rename_all = "snake_case")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TileOrder {
+    /// Each row of the image, from top to bottom.
+    #[default]
+    Scanline,
+    /// Tiles spiralling outwards from the center of the image.
+    Spiral,
+    /// Tiles along a Hilbert curve, which keeps consecutive tiles close to each other.
+    Hilbert,
+    /// Tiles from the closest to the center of the image to the farthest.
+    CenterOut,
+}
+
+/// A rectangular part of an image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Tile {
+    /// The abscissas of its pixels.
+    pub xs: Range<u32>,
+    /// The ordinates of its pixels.
+    pub ys: Range<u32>,
+}
+
+impl TileOrder {
+    /// Cut the `(x, y, width, height)` window of an image into tiles, sorted in this order.
+    pub(crate) fn tiles(self, window: (u32, u32, u32, u32)) -> Vec<Tile> {
+        let (left, top, width, height) = window;
+        let columns = width.div_ceil(TILE_SIZE);
+        let rows = height.div_ceil(TILE_SIZE);
+        let grid = || {
+            (0..rows)
+                .flat_map(|row| (0..columns).map(move |column| (column, row)))
+                .collect::<Vec<_>>()
+        };
+        let cells = match self {
+            TileOrder::Scanline => {
+                return (top..top + height)
+                    .map(|y| Tile {
+                        xs: left..left + width,
+                        ys: y..y + 1,
+                    })
+                    .collect()
+            }
+            TileOrder::Spiral => spiral(columns, rows),
+            TileOrder::Hilbert => {
+                let side = columns.max(rows).next_power_of_two();
+                let mut cells = grid();
+                cells.sort_by_key(|&(column, row)| hilbert_index(side, column, row));
+                cells
+            }
+            TileOrder::CenterOut => {
+                // Twice the distance to the center, in tiles, to stay on integers
+                let distance = |(column, row): (u32, u32)| {
+                    let dx = (2 * column + 1) as i64 - columns as i64;
+                    let dy = (2 * row + 1) as i64 - rows as i64;
+                    dx * dx + dy * dy
+                };
+                let mut cells = grid();
+                cells.sort_by_key(|&cell| distance(cell));
+                cells
+            }
+        };
+        let tile = |(column, row): (u32, u32)| {
+            let (x, y) = (left + column * TILE_SIZE, top + row * TILE_SIZE);
+            Tile {
+                xs: x..(x + TILE_SIZE).min(left + width),
+                ys: y..(y + TILE_SIZE).min(top + height),
+            }
+        };
+        cells.into_iter().map(tile).collect()
+    }
+}
+
+/// List the cells of a grid along a square spiral starting from its center.
+fn spiral(columns: u32, rows: u32) -> Vec<(u32, u32)> {
+    let total = (columns * rows) as usize;
+    let mut cells = Vec::with_capacity(total);
+    let (mut x, mut y) = (((columns as i64) - 1) / 2, ((rows as i64) - 1) / 2);
+    let visit = |x: i64, y: i64, cells: &mut Vec<_>| {
+        if (0..columns as i64).contains(&x) && (0..rows as i64).contains(&y) {
+            cells.push((x as u32, y as u32));
+        }
+    };
+    visit(x, y, &mut cells);
+    // Walk right, down, left, then up, each pair of legs one cell longer than the previous one
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut length = 1;
+    while cells.len() < total {
+        for (leg, &(dx, dy)) in directions.iter().enumerate() {
+            for _ in 0..length {
+                x += dx;
+                y += dy;
+                visit(x, y, &mut cells);
+            }
+            if leg % 2 == 1 {
+                length += 1;
+            }
+        }
+    }
+    cells
+}
+
+/// Get the position of a cell along the Hilbert curve covering a grid of `side` by `side` cells,
+/// `side` being a power of two.
+fn hilbert_index(side: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut index = 0;
+    let mut scale = side / 2;
+    while scale > 0 {
+        let rx = (x & scale > 0) as u32;
+        let ry = (y & scale > 0) as u32;
+        index += (scale as u64) * (scale as u64) * ((3 * rx) ^ ry) as u64;
+        // Rotate the quadrant so that the curve is continuous
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        scale /= 2;
+    }
+    index
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ORDERS: [TileOrder; 4] = [
+        TileOrder::Scanline,
+        TileOrder::Spiral,
+        TileOrder::Hilbert,
+        TileOrder::CenterOut,
+    ];
+
+    #[test]
+    fn tiles_cover_the_window_once() {
+        for &order in ORDERS.iter() {
+            for &window in &[(0, 0, 64, 64), (5, 3, 50, 37), (0, 0, 1, 100)] {
+                let (left, top, width, height) = window;
+                let mut covered = vec![0; (width * height) as usize];
+                for tile in order.tiles(window) {
+                    for y in tile.ys.clone() {
+                        for x in tile.xs.clone() {
+                            covered[((y - top) * width + x - left) as usize] += 1;
+                        }
+                    }
+                }
+                assert!(covered.iter().all(|&count| count == 1), "{:?}", order);
+            }
+        }
+    }
+
+    #[test]
+    fn orders_share_their_tiles() {
+        let window = (0, 0, 50, 37);
+        let mut tiles = TileOrder::Spiral.tiles(window);
+        tiles.sort_by_key(|tile| (tile.ys.start, tile.xs.start));
+        for &order in &[TileOrder::Hilbert, TileOrder::CenterOut] {
+            let mut others = order.tiles(window);
+            others.sort_by_key(|tile| (tile.ys.start, tile.xs.start));
+            assert_eq!(tiles, others);
+        }
+    }
+
+    #[test]
+    fn center_is_rendered_first() {
+        let center = Tile {
+            xs: 32..48,
+            ys: 32..48,
+        };
+        assert_eq!(TileOrder::Spiral.tiles((0, 0, 80, 80))[0], center);
+        assert_eq!(TileOrder::CenterOut.tiles((0, 0, 80, 80))[0], center);
+        // The corners come last
+        let last = TileOrder::CenterOut.tiles((0, 0, 80, 80)).pop().unwrap();
+        assert!(last.xs.start.is_multiple_of(64) && last.ys.start.is_multiple_of(64));
+    }
+
+    #[test]
+    fn spiral_steps_to_neighbours() {
+        let cells = spiral(5, 5);
+        assert_eq!(cells[0], (2, 2));
+        for pair in cells.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let step = (a.0 as i32 - b.0 as i32).abs() + (a.1 as i32 - b.1 as i32).abs();
+            assert_eq!(step, 1, "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn hilbert_steps_to_neighbours() {
+        let tiles = TileOrder::Hilbert.tiles((0, 0, 8 * TILE_SIZE, 8 * TILE_SIZE));
+        for pair in tiles.windows(2) {
+            let dx = (pair[0].xs.start as i32 - pair[1].xs.start as i32).abs();
+            let dy = (pair[0].ys.start as i32 - pair[1].ys.start as i32).abs();
+            assert_eq!(dx + dy, TILE_SIZE as i32, "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let order: TileOrder = serde_yaml::from_str("center_out").unwrap();
+        assert_eq!(order, TileOrder::CenterOut);
+        let order: TileOrder = serde_yaml::from_str("hilbert").unwrap();
+        assert_eq!(order, TileOrder::Hilbert);
+    }
+}
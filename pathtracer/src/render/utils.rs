@@ -1,4 +1,5 @@
-use crate::Vector;
+use crate::core::{sampling, LinearColor};
+use crate::{Float, Point, Vector};
 use nalgebra::Unit;
 
 pub fn reflected(incident: Unit<Vector>, normal: Unit<Vector>) -> Unit<Vector> {
@@ -7,13 +8,54 @@ pub fn reflected(incident: Unit<Vector>, normal: Unit<Vector>) -> Unit<Vector> {
     Unit::new_normalize(incident.as_ref() - delt)
 }
 
+/// The offset applied to secondary rays, relative to the magnitude of the values involved.
+const OFFSET_EPSILON: Float = 1e-4;
+/// The smallest offset applied to secondary rays, for points close to the scene's origin.
+const MIN_OFFSET: Float = 1e-6;
+
+/// Return the origin of a ray leaving the surface at `point` in `direction`, offset along the
+/// surface's normal by an amount proportional to the floating-point error of the hit point.
+pub fn offset_origin(
+    point: &Point,
+    normal: &Unit<Vector>,
+    direction: &Unit<Vector>,
+    hit_distance: Float,
+) -> Point {
+    // The error grows with the coordinates of the point and the distance travelled to reach it
+    let scale = point.coords.amax() + hit_distance.abs();
+    let offset = (OFFSET_EPSILON * scale).max(MIN_OFFSET);
+    // Move to the side of the surface the ray is leaving towards
+    let normal = if direction.dot(normal) < 0. {
+        -*normal
+    } else {
+        *normal
+    };
+    point + normal.as_ref() * offset
+}
+
+/// Map a value in `[0, 1]` to a color going from blue, through green, to red.
+pub fn heat(value: Float) -> LinearColor {
+    let value = value.clamp(0., 1.);
+    // NaNs are shown as 0
+    let value = if value.is_nan() { 0. } else { value };
+    let low = (1. - 2. * value).max(0.);
+    let high = (2. * value - 1.).max(0.);
+    LinearColor::new(high, 1. - low - high, low)
+}
+
+/// Sample a direction in the hemisphere around `normal`, with a cosine-weighted distribution,
+/// from two uniform random numbers in `[0, 1)`.
+pub fn sample_hemisphere(normal: &Unit<Vector>, u: Float, v: Float) -> Unit<Vector> {
+    sampling::to_world(normal, &sampling::cosine_hemisphere(u, v))
+}
+
 /// Returns None if the ray was totally reflected, Some(refracted_ray, reflected_amount) if not
 pub fn refracted(
     incident: Unit<Vector>,
     normal: Unit<Vector>,
     indices: &mut RefractionInfo,
-    new_index: f32,
-) -> Option<(Unit<Vector>, f32)> {
+    new_index: Float,
+) -> Option<(Unit<Vector>, Float)> {
     let cos1 = incident.dot(&normal);
     let normal = if cos1 < 0. {
         // Entering object, change the medium
@@ -42,26 +84,95 @@ pub fn refracted(
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct RefractionInfo {
-    pub old_index: f32,
-    pub new_index: f32,
+    pub old_index: Float,
+    pub new_index: Float,
+    /// The absorption coefficient of the medium the ray is travelling through, if any.
+    pub absorption: Option<LinearColor>,
 }
 
 impl RefractionInfo {
-    pub fn with_index(index: f32) -> Self {
+    pub fn with_index(index: Float) -> Self {
         RefractionInfo {
             old_index: index,
             new_index: index,
+            absorption: None,
         }
     }
 
-    pub fn enter_medium(&mut self, index: f32) {
+    pub fn enter_medium(&mut self, index: Float) {
         *self = RefractionInfo {
             old_index: self.new_index,
             new_index: index,
+            absorption: self.absorption.clone(),
         }
     }
 
     pub fn exit_medium(&mut self) {
         std::mem::swap(&mut self.old_index, &mut self.new_index)
     }
+
+    /// Get the fraction of light left after travelling `distance` through the current medium,
+    /// following the Beer-Lambert law.
+    pub fn attenuation(&self, distance: Float) -> LinearColor {
+        match &self.absorption {
+            Some(absorption) => LinearColor::new(
+                (-absorption.r * distance).exp(),
+                (-absorption.g * distance).exp(),
+                (-absorption.b * distance).exp(),
+            ),
+            None => LinearColor::new(1., 1., 1.),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn offset_origin_follows_direction() {
+        let point = Point::new(1., 0., 0.);
+        let normal = Vector::x_axis();
+        let outside = offset_origin(&point, &normal, &Vector::y_axis(), 1.);
+        assert!(outside.x > 1.);
+        let inside = offset_origin(&point, &normal, &-Vector::x_axis(), 1.);
+        assert!(inside.x < 1.);
+        // Only move along the normal
+        assert_eq!(outside.y, 0.);
+        assert_eq!(inside.y, 0.);
+    }
+
+    #[test]
+    fn sample_hemisphere_stays_above_surface() {
+        let normal = Unit::new_normalize(Vector::new(1., -2., 0.5));
+        for i in 0..10 {
+            for j in 0..10 {
+                let direction = sample_hemisphere(&normal, i as Float / 10., j as Float / 10.);
+                assert!(direction.dot(&normal) > 0.);
+            }
+        }
+        let top = sample_hemisphere(&normal, 0., 0.);
+        assert!((top.dot(&normal) - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn attenuation_works() {
+        let mut indices = RefractionInfo::with_index(1.);
+        assert_eq!(indices.attenuation(10.), LinearColor::new(1., 1., 1.));
+        indices.absorption = Some(LinearColor::new(0., 1., 2.));
+        let attenuation = indices.attenuation(0.5);
+        assert_eq!(attenuation.r, 1.);
+        assert!((attenuation.g - Float::exp(-0.5)).abs() < 1e-6);
+        assert!((attenuation.b - Float::exp(-1.)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn offset_origin_scales() {
+        let normal = Vector::y_axis();
+        let near = offset_origin(&Point::new(1., 0., 0.), &normal, &normal, 1.);
+        let far = offset_origin(&Point::new(1000., 0., 0.), &normal, &normal, 1000.);
+        assert!(far.y > 100. * near.y);
+        let origin = offset_origin(&Point::origin(), &normal, &normal, 0.);
+        assert!(origin.y > 0.);
+    }
 }
@@ -0,0 +1,351 @@
+//! Packing a scene and the files it refers to into a single archive
+
+use super::{read_description, tar, zip};
+use crate::{Error, Result};
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Component, Path, PathBuf};
+
+/// The name of the scene description inside of a bundle.
+const SCENE_ENTRY: &str = "scene.yaml";
+
+/// The directory holding the files referred to by the scene inside of a bundle.
+const ASSETS_DIRECTORY: &str = "assets";
+
+/// The kinds of archives a scene can be bundled into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BundleFormat {
+    /// An uncompressed tar archive.
+    Tar,
+    /// A zip archive, whose entries are deflated.
+    Zip,
+}
+
+impl BundleFormat {
+    /// Get the format of a bundle from its extension, if it is one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::serialize::BundleFormat;
+    /// # use std::path::Path;
+    /// #
+    /// assert_eq!(BundleFormat::from_path(Path::new("scene.zip")), Some(BundleFormat::Zip));
+    /// assert_eq!(BundleFormat::from_path(Path::new("scene.yaml")), None);
+    /// ```
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "tar" => Some(BundleFormat::Tar),
+            "zip" => Some(BundleFormat::Zip),
+            _ => None,
+        }
+    }
+}
+
+/// Replace the value of every `file` field in the scene, wherever it is nested.
+pub(super) fn rewrite_files(
+    value: &mut Value,
+    rewrite: &mut dyn FnMut(&str) -> Result<String>,
+) -> Result<()> {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                match (key.as_str(), &*value) {
+                    (Some("file"), Value::String(path)) => *value = Value::String(rewrite(path)?),
+                    _ => rewrite_files(value, rewrite)?,
+                }
+            }
+        }
+        Value::Sequence(values) => {
+            for value in values {
+                rewrite_files(value, rewrite)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// List the material libraries of an OBJ file, and the images they use, relative to the file's
+/// `directory`, refusing paths which lead outside of it.
+fn obj_dependencies(obj: &[u8], directory: &Path) -> Result<Vec<String>> {
+    let statements = |content: &str, statement: &str| -> Vec<String> {
+        (content.lines())
+            .filter_map(|line| {
+                let mut tokens = line.split_whitespace();
+                if tokens.next() != Some(statement) {
+                    return None;
+                }
+                match statement {
+                    "mtllib" => Some(tokens.map(String::from).collect::<Vec<_>>()),
+                    // Texture options come before the file name
+                    _ => tokens.last().map(|file| vec![file.to_string()]),
+                }
+            })
+            .flatten()
+            .collect()
+    };
+    let relative = |path: &Path| -> Result<String> {
+        if !(path.components()).all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(Error::invalid_data(format!(
+                "{}: outside of the mesh's directory",
+                path.display()
+            )));
+        }
+        Ok(path.to_string_lossy().replace('\\', "/"))
+    };
+
+    let mut dependencies = Vec::new();
+    for library in statements(&String::from_utf8_lossy(obj), "mtllib") {
+        let path = directory.join(&library);
+        let content =
+            std::fs::read_to_string(&path).map_err(|err| Error::from(err).with_path(&path))?;
+        let library = Path::new(&library);
+        let parent = library.parent().unwrap_or_else(|| Path::new(""));
+        for image in statements(&content, "map_Kd") {
+            let image = relative(&parent.join(image))?;
+            if !dependencies.contains(&image) {
+                dependencies.push(image);
+            }
+        }
+        dependencies.push(relative(library)?);
+    }
+    Ok(dependencies)
+}
+
+/// Pack the scene described at `scene`, along with the files it includes and the meshes and
+/// textures it refers to, into a single archive at `output`, whose format is given by its
+/// extension.
+///
+/// The included files are merged into the packed scene, as described in [`load_description`],
+/// and its paths are rewritten to point at the copies of those files, relative to the root of the
+/// archive.
+///
+/// [`load_description`]: fn.load_description.html
+pub fn pack(scene: &Path, output: &Path) -> Result<()> {
+    let format = BundleFormat::from_path(output).ok_or_else(|| not_a_bundle(output))?;
+    let mut value = read_description(scene)?;
+
+    let mut entries = Vec::new();
+    let mut packed: HashMap<String, String> = HashMap::new();
+    rewrite_files(&mut value, &mut |path| {
+        if let Some(name) = packed.get(path) {
+            return Ok(name.clone());
+        }
+        let read =
+            |path: &Path| std::fs::read(path).map_err(|err| Error::from(err).with_path(path));
+        let content = read(Path::new(path))?;
+        let extension = Path::new(path).extension().and_then(|ext| ext.to_str());
+        let name = if extension.map(str::to_lowercase).as_deref() == Some("obj") {
+            // Meshes refer to their material libraries by relative paths, keep them alongside
+            let directory = format!("{}/{}", ASSETS_DIRECTORY, packed.len());
+            let parent = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+            for dependency in obj_dependencies(&content, parent)? {
+                let content = read(&parent.join(&dependency))?;
+                entries.push((format!("{}/{}", directory, dependency), content));
+            }
+            format!("{}/mesh.obj", directory)
+        } else {
+            // Keep the extension, which is used to guess the file's format when loading it
+            match extension {
+                Some(extension) => format!("{}/{}.{}", ASSETS_DIRECTORY, packed.len(), extension),
+                None => format!("{}/{}", ASSETS_DIRECTORY, packed.len()),
+            }
+        };
+        packed.insert(path.to_string(), name.clone());
+        entries.push((name.clone(), content));
+        Ok(name)
+    })?;
+    let description =
+        serde_yaml::to_string(&value).map_err(|err| Error::from(err).with_path(scene))?;
+    entries.insert(0, (SCENE_ENTRY.to_string(), description.into_bytes()));
+
+    (File::create(output).map_err(Error::from))
+        .and_then(|file| {
+            let writer = BufWriter::new(file);
+            match format {
+                BundleFormat::Tar => tar::write_entries(writer, &entries),
+                BundleFormat::Zip => zip::write_entries(writer, &entries),
+            }
+        })
+        .map_err(|err| err.with_path(output))
+}
+
+/// Extract a bundle created by [`pack`] into `directory`, and return its scene description, with
+/// the paths it contains pointing into `directory`.
+///
+/// [`pack`]: fn.pack.html
+pub fn unpack(bundle: &Path, directory: &Path) -> Result<String> {
+    let format = BundleFormat::from_path(bundle).ok_or_else(|| not_a_bundle(bundle))?;
+    let entries = match format {
+        BundleFormat::Tar => File::open(bundle)
+            .map_err(Error::from)
+            .and_then(tar::read_entries),
+        BundleFormat::Zip => std::fs::read(bundle)
+            .map_err(Error::from)
+            .and_then(|data| zip::read_entries(&data)),
+    }
+    .map_err(|err| err.with_path(bundle))?;
+
+    let mut description = None;
+    for (name, content) in entries {
+        if name == SCENE_ENTRY {
+            description = Some(content);
+            continue;
+        }
+        let path = extracted_path(directory, &name)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| Error::from(err).with_path(parent))?;
+        }
+        std::fs::write(&path, content).map_err(|err| Error::from(err).with_path(&path))?;
+    }
+    let description = description
+        .ok_or_else(|| Error::invalid_data(format!("missing {}", SCENE_ENTRY)).with_path(bundle))?;
+
+    let mut value: Value =
+        serde_yaml::from_slice(&description).map_err(|err| Error::from(err).with_path(bundle))?;
+    rewrite_files(&mut value, &mut |path| {
+        let path = extracted_path(directory, path)?;
+        Ok(path.to_string_lossy().into_owned())
+    })?;
+    Ok(serde_yaml::to_string(&value)?)
+}
+
+fn not_a_bundle(path: &Path) -> Error {
+    Error::InvalidParameter(format!("{}: not a .tar or .zip bundle", path.display()))
+}
+
+/// Get where an entry of a bundle is extracted, refusing to write outside of `directory`.
+fn extracted_path(directory: &Path, name: &str) -> Result<PathBuf> {
+    let relative = Path::new(name);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(Error::invalid_data(format!(
+            "{}: invalid path in bundle",
+            name
+        )));
+    }
+    Ok(directory.join(relative))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::Scene;
+
+    /// A scene using both a mesh file and an image texture, written into `directory`.
+    fn write_scene(directory: &Path) -> PathBuf {
+        std::fs::create_dir_all(directory).unwrap();
+        let mesh = directory.join("triangle.obj");
+        std::fs::write(&mesh, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let image = directory.join("texture.png");
+        image::RgbImage::new(2, 2).save(&image).unwrap();
+        let scene = directory.join("scene.yaml");
+        let description = format!(
+            r#"
+            camera:
+              origin: [-1.0, 0.0, 0.0]
+              look_at: [0.0, 0.0, 0.0]
+              fov: 90.0
+              x: 8
+              y: 8
+            meshes:
+              - file: {mesh}
+                material:
+                  type: uniform
+                  diffuse: {{r: 1.0, g: 1.0, b: 1.0}}
+                  specular: {{r: 0.0, g: 0.0, b: 0.0}}
+                texture:
+                  type: image
+                  file: {image}
+            "#,
+            mesh = mesh.display(),
+            image = image.display(),
+        );
+        std::fs::write(&scene, description).unwrap();
+        scene
+    }
+
+    #[test]
+    fn from_path_works() {
+        let format = |path| BundleFormat::from_path(Path::new(path));
+        assert_eq!(format("scene.tar"), Some(BundleFormat::Tar));
+        assert_eq!(format("SCENE.ZIP"), Some(BundleFormat::Zip));
+        assert_eq!(format("scene.tar.gz"), None);
+        assert_eq!(format("scene"), None);
+    }
+
+    #[test]
+    fn round_trip_works() {
+        let root = std::env::temp_dir().join("pathtracer-bundle-round-trip");
+        let scene = write_scene(&root.join("original"));
+        for &extension in &["tar", "zip"] {
+            let bundle = root.join(format!("scene.{}", extension));
+            pack(&scene, &bundle).unwrap();
+            let extracted = root.join(format!("extracted-{}", extension));
+            let description = unpack(&bundle, &extracted).unwrap();
+            // The scene only refers to the extracted files
+            assert!(!description.contains("original"));
+            assert!(extracted.join("assets/0/mesh.obj").exists());
+            assert!(extracted.join("assets/1.png").exists());
+            serde_yaml::from_str::<Scene>(&description).unwrap();
+        }
+    }
+
+    #[test]
+    fn mesh_materials_are_packed() {
+        let root = std::env::temp_dir().join("pathtracer-bundle-materials");
+        let original = root.join("original");
+        let scene = write_scene(&original);
+        std::fs::create_dir_all(original.join("materials")).unwrap();
+        let mesh =
+            "mtllib materials/triangle.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n";
+        std::fs::write(original.join("triangle.obj"), mesh).unwrap();
+        let library = "newmtl red\nKd 1 0 0\nmap_Kd -bm 1 ../texture.png\n";
+        std::fs::write(original.join("materials/triangle.mtl"), library).unwrap();
+
+        // The texture is outside of the mesh's directory
+        std::fs::write(
+            original.join("triangle.obj"),
+            mesh.replace("materials/", "../"),
+        )
+        .unwrap();
+        assert!(pack(&scene, &root.join("scene.tar")).is_err());
+
+        let library = library.replace("../texture.png", "red.png");
+        std::fs::write(original.join("materials/triangle.mtl"), library).unwrap();
+        image::RgbImage::new(1, 1)
+            .save(original.join("materials/red.png"))
+            .unwrap();
+        std::fs::write(original.join("triangle.obj"), mesh).unwrap();
+        let bundle = root.join("scene.tar");
+        pack(&scene, &bundle).unwrap();
+        let extracted = root.join("extracted");
+        let description = unpack(&bundle, &extracted).unwrap();
+        assert!(extracted.join("assets/0/mesh.obj").exists());
+        assert!(extracted.join("assets/0/materials/triangle.mtl").exists());
+        assert!(extracted.join("assets/0/materials/red.png").exists());
+        serde_yaml::from_str::<Scene>(&description).unwrap();
+    }
+
+    #[test]
+    fn missing_file_fails() {
+        let root = std::env::temp_dir().join("pathtracer-bundle-missing");
+        let scene = write_scene(&root);
+        std::fs::remove_file(root.join("texture.png")).unwrap();
+        assert!(pack(&scene, &root.join("scene.tar")).is_err());
+    }
+
+    #[test]
+    fn escaping_paths_fail() {
+        let directory = Path::new("bundle");
+        assert!(extracted_path(directory, "assets/0.png").is_ok());
+        assert!(extracted_path(directory, "../0.png").is_err());
+        assert!(extracted_path(directory, "/etc/passwd").is_err());
+    }
+}
@@ -1,6 +1,8 @@
 //! Helper functions deserialize coefficients.
 
-/// Returns the identity for a f32, i.e. 1.0.
-pub fn default_identity() -> f32 {
+use crate::Float;
+
+/// Returns the identity for a Float, i.e. 1.0.
+pub fn default_identity() -> Float {
     1.
 }
@@ -0,0 +1,178 @@
+//! Splitting a scene description across several files
+
+use super::bundle::rewrite_files;
+use crate::{Error, Result};
+use serde_yaml::{Mapping, Value};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// The field listing the files included by a scene description.
+const INCLUDE_KEY: &str = "include";
+
+/// Merge `value` into `base`: mappings are merged field by field, lists are concatenated, and any
+/// other value replaces the one in `base`.
+fn merge(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Mapping(base), Value::Mapping(mapping)) => {
+            for (key, value) in mapping {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(values)) => base.extend(values),
+        (base, value) => *base = value,
+    }
+}
+
+/// Get the files listed by the `include` field of a description, removing it.
+pub(super) fn take_includes(value: &mut Value, path: &Path) -> Result<Vec<String>> {
+    let includes = match value {
+        Value::Mapping(mapping) => mapping.remove(&Value::String(INCLUDE_KEY.to_string())),
+        _ => None,
+    };
+    let invalid = || Error::invalid_data("expected a list of files to include").with_path(path);
+    match includes {
+        None => Ok(Vec::new()),
+        Some(Value::String(file)) => Ok(vec![file]),
+        Some(Value::Sequence(files)) => (files.into_iter())
+            .map(|file| match file {
+                Value::String(file) => Ok(file),
+                _ => Err(invalid()),
+            })
+            .collect(),
+        Some(_) => Err(invalid()),
+    }
+}
+
+/// Read the description at `path` along with the files it includes, `parents` being the files
+/// currently including it.
+fn read_with_includes(path: &Path, parents: &mut Vec<PathBuf>) -> Result<Value> {
+    let with_path = |err| Error::with_path(Error::from(err), path);
+    let canonical = path.canonicalize().map_err(with_path)?;
+    if parents.contains(&canonical) {
+        return Err(Error::invalid_data("included by itself").with_path(path));
+    }
+    let file = File::open(path).map_err(with_path)?;
+    let mut value: Value =
+        serde_yaml::from_reader(file).map_err(|err| Error::from(err).with_path(path))?;
+
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    rewrite_files(&mut value, &mut |file| {
+        Ok(directory.join(file).to_string_lossy().into_owned())
+    })?;
+    let includes = take_includes(&mut value, path)?;
+
+    parents.push(canonical);
+    let mut description = Value::Mapping(Mapping::new());
+    for include in includes {
+        merge(
+            &mut description,
+            read_with_includes(&directory.join(include), parents)?,
+        );
+    }
+    parents.pop();
+    merge(&mut description, value);
+    Ok(description)
+}
+
+/// Read the scene description at `path`, resolving its includes and the paths it contains.
+pub(crate) fn read_description(path: &Path) -> Result<Value> {
+    read_with_includes(path, &mut Vec::new())
+}
+
+/// Load the scene description at `path` as a single document, ready to be deserialized.
+///
+/// The description can list other files in its `include` field, to split a large scene across
+/// several files, e.g: to share materials between scenes. The included files are read in order,
+/// followed by the including file: their fields are merged with the same rules at any depth,
+/// lists being concatenated, and mappings being merged field by field, while any other value
+/// overrides the one read before it.
+///
+/// The included files and the meshes and textures each file refers to are found relative to
+/// the directory of that file, instead of the current one.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use pathtracer::render::Scene;
+/// # use pathtracer::serialize::load_description;
+/// # use std::path::Path;
+/// #
+/// let description = load_description(Path::new("scenes/kitchen.yaml")).unwrap();
+/// let scene: Scene = serde_yaml::from_str(&description).unwrap();
+/// ```
+pub fn load_description(path: &Path) -> Result<String> {
+    let value = read_description(path)?;
+    serde_yaml::to_string(&value).map_err(|err| Error::from(err).with_path(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn yaml(description: &str) -> Value {
+        serde_yaml::from_str(description).unwrap()
+    }
+
+    #[test]
+    fn merge_works() {
+        let mut base = yaml("{objects: [1, 2], lights: {points: [1]}, camera: {x: 8, y: 8}}");
+        let value = yaml("{objects: [3], lights: {points: [2], spots: [1]}, camera: {x: 16}}");
+        merge(&mut base, value);
+        assert_eq!(
+            base,
+            yaml(
+                "{objects: [1, 2, 3], lights: {points: [1, 2], spots: [1]}, camera: {x: 16, y: 8}}"
+            )
+        );
+    }
+
+    #[test]
+    fn includes_work() {
+        let root = std::env::temp_dir().join(format!("pathtracer-include-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("shared/meshes")).unwrap();
+        std::fs::write(
+            root.join("shared/common.yaml"),
+            "meshes: [{file: meshes/rock.obj}]\naliasing_limit: 2\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("scene.yaml"),
+            "include: shared/common.yaml\nmeshes: [{file: tree.obj}]\naliasing_limit: 4\n",
+        )
+        .unwrap();
+
+        let description = read_description(&root.join("scene.yaml")).unwrap();
+        let rock = root.join("shared/meshes/rock.obj");
+        let tree = root.join("tree.obj");
+        let expected = format!(
+            "{{meshes: [{{file: {}}}, {{file: {}}}], aliasing_limit: 4}}",
+            rock.display(),
+            tree.display()
+        );
+        assert_eq!(description, yaml(&expected));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn recursive_includes_fail() {
+        let root =
+            std::env::temp_dir().join(format!("pathtracer-recursive-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("first.yaml"), "include: [second.yaml]\n").unwrap();
+        std::fs::write(root.join("second.yaml"), "include: [first.yaml]\n").unwrap();
+        let err = read_description(&root.join("first.yaml")).unwrap_err();
+        assert!(matches!(err, Error::Parse { .. }));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn invalid_includes_fail() {
+        let mut value = yaml("{include: {file: scene.yaml}}");
+        assert!(take_includes(&mut value, Path::new("scene.yaml")).is_err());
+    }
+}
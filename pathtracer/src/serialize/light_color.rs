@@ -0,0 +1,35 @@
+//! Helper functions to deserialize the color of lights.
+
+use crate::core::LinearColor;
+use crate::Float;
+use serde::{Deserialize, Deserializer};
+
+/// The ways to describe the color of a light.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LightColor {
+    /// A linear RGB color.
+    Rgb(LinearColor),
+    /// The color of a black body at a temperature in Kelvin, scaled by an intensity.
+    Temperature {
+        temperature: Float,
+        #[serde(default = "crate::serialize::default_identity")]
+        intensity: Float,
+    },
+}
+
+/// Deserialize the color of a light, either given by its RGB components, or by a temperature in
+/// Kelvin and an intensity, e.g: `{temperature: 3200, intensity: 2}` for a tungsten light.
+pub fn light_color<'de, D>(deserializer: D) -> Result<LinearColor, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let color: LightColor = Deserialize::deserialize(deserializer)?;
+    Ok(match color {
+        LightColor::Rgb(color) => color,
+        LightColor::Temperature {
+            temperature,
+            intensity,
+        } => LinearColor::from_temperature(temperature) * intensity,
+    })
+}
@@ -0,0 +1,237 @@
+//! Reloading the materials and textures of a scene while it is being rendered
+
+use super::bundle::rewrite_files;
+use super::read_description;
+use crate::material::MaterialEnum;
+use crate::texture::TextureEnum;
+use crate::{Error, Result};
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// The sections of a scene description holding its looks.
+const SECTIONS: [&str; 2] = ["materials", "textures"];
+
+/// The named materials and textures of a scene, which objects refer to by name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Looks {
+    pub(crate) materials: BTreeMap<String, Arc<MaterialEnum>>,
+    pub(crate) textures: BTreeMap<String, Arc<TextureEnum>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SerializedLooks {
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialEnum>,
+    #[serde(default)]
+    textures: BTreeMap<String, TextureEnum>,
+}
+
+impl Looks {
+    /// Read the looks of the scene described by `value`, ignoring every other field.
+    fn from_value(value: Value) -> std::result::Result<Self, serde_yaml::Error> {
+        let looks: SerializedLooks = serde_yaml::from_value(value)?;
+        Ok(Looks {
+            materials: (looks.materials.into_iter())
+                .map(|(name, material)| (name, Arc::new(material)))
+                .collect(),
+            textures: (looks.textures.into_iter())
+                .map(|(name, texture)| (name, Arc::new(texture)))
+                .collect(),
+        })
+    }
+}
+
+/// Watch the `materials` and `textures` sections of a scene description, and the files they
+/// refer to, e.g: to update a scene while iterating on its look.
+///
+/// Materials and textures given directly to an object are not watched, only the named ones.
+#[derive(Debug)]
+pub struct LookWatcher {
+    path: PathBuf,
+    /// The sections as they were last read.
+    sections: Value,
+    /// The files referred to by the sections, and when they were last modified.
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+/// Get the looks' sections of a description.
+fn sections(description: &Value) -> Value {
+    let mut sections = serde_yaml::Mapping::new();
+    for &section in SECTIONS.iter() {
+        if let Some(value) = description.get(section) {
+            sections.insert(Value::String(section.to_string()), value.clone());
+        }
+    }
+    Value::Mapping(sections)
+}
+
+/// List the files referred to by the sections, along with their modification time.
+fn files(sections: &Value) -> Result<Vec<(PathBuf, Option<SystemTime>)>> {
+    let mut files = Vec::new();
+    let mut sections = sections.clone();
+    rewrite_files(&mut sections, &mut |file| {
+        let modified = std::fs::metadata(file).and_then(|m| m.modified()).ok();
+        files.push((PathBuf::from(file), modified));
+        Ok(file.to_string())
+    })?;
+    Ok(files)
+}
+
+impl LookWatcher {
+    /// Start watching the scene described at `path`.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let sections = sections(&read_description(&path)?);
+        Ok(LookWatcher {
+            files: files(&sections)?,
+            path,
+            sections,
+        })
+    }
+
+    /// Get the path of the watched scene description.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the new looks of the scene if they changed since it was last checked, reading the
+    /// files they refer to anew, or `None` if they did not.
+    ///
+    /// A description which cannot be read, e.g: because it is being written, is reported as an
+    /// error, and read again when next checked.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use pathtracer::render::Scene;
+    /// # use pathtracer::serialize::{load_description, LookWatcher};
+    /// # use std::path::Path;
+    /// #
+    /// let path = Path::new("scenes/kitchen.yaml");
+    /// let mut scene: Scene = serde_yaml::from_str(&load_description(path).unwrap()).unwrap();
+    /// let mut watcher = LookWatcher::new(path).unwrap();
+    /// loop {
+    ///     if let Some(looks) = watcher.poll().unwrap() {
+    ///         scene.set_looks(&looks).unwrap();
+    ///     }
+    ///     scene.render().save("kitchen.png").unwrap();
+    /// }
+    /// ```
+    pub fn poll(&mut self) -> Result<Option<Looks>> {
+        let sections = sections(&read_description(&self.path)?);
+        let files = files(&sections)?;
+        if sections == self.sections && files == self.files {
+            return Ok(None);
+        }
+        let looks = Looks::from_value(sections.clone())
+            .map_err(|err| Error::from(err).with_path(&self.path))?;
+        self.sections = sections;
+        self.files = files;
+        Ok(Some(looks))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Float;
+    use std::fs;
+
+    /// A scratch directory, removed once dropped.
+    struct Directory(PathBuf);
+
+    impl Directory {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "pathtracer-looks-{}-{}",
+                name,
+                std::process::id()
+            ));
+            fs::create_dir_all(&path).unwrap();
+            Directory(path)
+        }
+    }
+
+    impl Drop for Directory {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    const SCENE: &str = r#"
+        camera:
+          origin: [0.0, 0.0, 0.0]
+          forward: [0.0, 0.0, 1.0]
+          up: [0.0, 1.0, 0.0]
+          fov: 90.0
+          x: 4
+          y: 4
+        materials:
+          red:
+            type: uniform
+            diffuse: {r: RED, g: 0.0, b: 0.0}
+            specular: {r: 0.0, g: 0.0, b: 0.0}
+        textures:
+          grid:
+            type: image
+            file: grid.png
+    "#;
+
+    fn write_scene(directory: &Directory, red: Float, camera_x: u32) -> PathBuf {
+        let path = directory.0.join("scene.yaml");
+        let scene = SCENE
+            .replace("RED", &red.to_string())
+            .replace("x: 4", &format!("x: {}", camera_x));
+        fs::write(&path, scene).unwrap();
+        path
+    }
+
+    fn write_grid(directory: &Directory, value: u8) {
+        let image = image::RgbImage::from_pixel(2, 2, image::Rgb([value, value, value]));
+        image.save(directory.0.join("grid.png")).unwrap();
+    }
+
+    #[test]
+    fn unchanged_looks_are_not_reloaded() {
+        let directory = Directory::new("unchanged");
+        write_grid(&directory, 0);
+        let path = write_scene(&directory, 1., 4);
+        let mut watcher = LookWatcher::new(&path).unwrap();
+        assert_eq!(watcher.poll().unwrap(), None);
+        // Only the looks are watched
+        write_scene(&directory, 1., 8);
+        assert_eq!(watcher.poll().unwrap(), None);
+    }
+
+    #[test]
+    fn edited_materials_are_reloaded() {
+        let directory = Directory::new("materials");
+        write_grid(&directory, 0);
+        let path = write_scene(&directory, 1., 4);
+        let mut watcher = LookWatcher::new(&path).unwrap();
+        write_scene(&directory, 0.5, 4);
+        let looks = watcher.poll().unwrap().unwrap();
+        assert!(looks.materials.contains_key("red"));
+        assert!(looks.textures.contains_key("grid"));
+        assert_eq!(watcher.poll().unwrap(), None);
+    }
+
+    #[test]
+    fn edited_textures_are_reloaded() {
+        let directory = Directory::new("textures");
+        write_grid(&directory, 0);
+        let path = write_scene(&directory, 1., 4);
+        let mut watcher = LookWatcher::new(&path).unwrap();
+        // Make sure that the modification time changes
+        let modified = watcher.files[0].1;
+        while files(&watcher.sections).unwrap()[0].1 == modified {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            write_grid(&directory, 255);
+        }
+        assert!(watcher.poll().unwrap().is_some());
+    }
+}
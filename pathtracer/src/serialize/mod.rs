@@ -5,3 +5,25 @@ pub use vector::*;
 
 pub mod coefficient;
 pub use coefficient::*;
+
+pub mod light_color;
+pub use light_color::*;
+
+pub mod named;
+pub use named::*;
+
+pub mod include;
+pub use include::*;
+
+pub mod bundle;
+pub use bundle::*;
+
+pub mod looks;
+pub use looks::*;
+
+pub mod validation;
+pub use validation::*;
+
+mod tar;
+
+mod zip;
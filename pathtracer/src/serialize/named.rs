@@ -0,0 +1,69 @@
+//! Helper functions to (de)serialize materials and textures given by name.
+
+use crate::material::{MaterialEnum, SharedMaterial};
+use crate::texture::{SharedTexture, TextureEnum};
+use serde::de::{Deserialize, DeserializeOwned, Deserializer, Error};
+use serde::{Serialize, Serializer};
+use serde_yaml::Value;
+
+/// Deserialize either a value, or the name of one to be resolved later.
+fn value_or_name<'de, D, T, F>(deserializer: D, named: F) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+    F: FnOnce(String) -> T,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(name) => Ok(named(name)),
+        value => serde_yaml::from_value(value).map_err(D::Error::custom),
+    }
+}
+
+/// Deserialize a material, or the name of one of the scene's `materials`.
+///
+/// The named material is only known once the whole scene has been read, see [`SharedMaterial`].
+///
+/// [`SharedMaterial`]: ../material/struct.SharedMaterial.html
+pub fn named_material<'de, D>(deserializer: D) -> Result<MaterialEnum, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    value_or_name(deserializer, |name| SharedMaterial::named(name).into())
+}
+
+/// Deserialize a texture, or the name of one of the scene's `textures`.
+///
+/// The named texture is only known once the whole scene has been read, see [`SharedTexture`].
+///
+/// [`SharedTexture`]: ../texture/struct.SharedTexture.html
+pub fn named_texture<'de, D>(deserializer: D) -> Result<TextureEnum, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    value_or_name(deserializer, |name| SharedTexture::named(name).into())
+}
+
+/// Serialize a material, or the name of the shared material it refers to.
+pub fn serialize_named_material<S>(
+    material: &MaterialEnum,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match material {
+        MaterialEnum::SharedMaterial(shared) => serializer.serialize_str(shared.name()),
+        material => material.serialize(serializer),
+    }
+}
+
+/// Serialize a texture, or the name of the shared texture it refers to.
+pub fn serialize_named_texture<S>(texture: &TextureEnum, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match texture {
+        TextureEnum::SharedTexture(shared) => serializer.serialize_str(shared.name()),
+        texture => texture.serialize(serializer),
+    }
+}
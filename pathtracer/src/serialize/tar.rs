@@ -0,0 +1,134 @@
+//! Reading and writing the entries of uncompressed tar archives, in the ustar format
+
+use crate::{Error, Result};
+use std::io::{self, Read, Write};
+
+/// The size of headers, and the granularity of the data in the archive.
+const BLOCK: usize = 512;
+
+/// Write an octal number in a NUL-terminated field.
+fn write_octal(field: &mut [u8], value: u64) -> Result<()> {
+    let digits = format!("{:o}", value);
+    let width = field.len() - 1;
+    if digits.len() > width {
+        return Err(Error::invalid_data("value too large for a tar header"));
+    }
+    let padded = format!("{:0>width$}", digits, width = width);
+    field[..width].copy_from_slice(padded.as_bytes());
+    field[width] = 0;
+    Ok(())
+}
+
+fn read_octal(field: &[u8]) -> Result<u64> {
+    let text = std::str::from_utf8(field).map_err(|_| Error::invalid_data("invalid tar header"))?;
+    let text = text.trim_matches(|c| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(|_| Error::invalid_data("invalid tar header"))
+}
+
+fn checksum(header: &[u8; BLOCK]) -> u64 {
+    // The checksum field itself counts as if filled with spaces
+    (header.iter().enumerate())
+        .map(|(i, &byte)| if (148..156).contains(&i) { b' ' } else { byte } as u64)
+        .sum()
+}
+
+/// Write each `(name, content)` entry as a regular file into a tar archive.
+pub fn write_entries<W: Write>(mut writer: W, entries: &[(String, Vec<u8>)]) -> Result<()> {
+    for (name, content) in entries {
+        if name.len() > 100 {
+            return Err(Error::invalid_data("file name too long for a tar archive"));
+        }
+        let mut header = [0; BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        write_octal(&mut header[100..108], 0o644)?;
+        write_octal(&mut header[108..116], 0)?;
+        write_octal(&mut header[116..124], 0)?;
+        write_octal(&mut header[124..136], content.len() as u64)?;
+        write_octal(&mut header[136..148], 0)?;
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        let sum = checksum(&header);
+        write_octal(&mut header[148..155], sum)?;
+        header[155] = b' ';
+        writer.write_all(&header)?;
+        writer.write_all(content)?;
+        let padding = (BLOCK - content.len() % BLOCK) % BLOCK;
+        writer.write_all(&[0; BLOCK][..padding])?;
+    }
+    // The archive ends with two empty blocks
+    Ok(writer.write_all(&[0; 2 * BLOCK])?)
+}
+
+/// Read the `(name, content)` of each regular file in a tar archive.
+pub fn read_entries<R: Read>(mut reader: R) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut entries = Vec::new();
+    loop {
+        let mut header = [0; BLOCK];
+        if let Err(err) = reader.read_exact(&mut header) {
+            // Some writers omit the final empty blocks
+            if err.kind() == io::ErrorKind::UnexpectedEof && !entries.is_empty() {
+                break;
+            }
+            return Err(err.into());
+        }
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        if read_octal(&header[148..156])? != checksum(&header) {
+            return Err(Error::invalid_data("invalid tar header checksum"));
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let bytes = &header[range];
+            let end = bytes
+                .iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        };
+        let (name, prefix) = (field(0..100), field(345..500));
+        let name = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+        let size = read_octal(&header[124..136])? as usize;
+        let mut content = vec![0; size + (BLOCK - size % BLOCK) % BLOCK];
+        reader.read_exact(&mut content)?;
+        content.truncate(size);
+        // Only keep regular files, skipping directories, links, and extended headers
+        if header[156] == b'0' || header[156] == 0 {
+            entries.push((name, content));
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip_works() {
+        let entries = vec![
+            ("scene.yaml".to_string(), b"camera: {}".to_vec()),
+            ("assets/0.png".to_string(), vec![42; 1000]),
+            ("empty".to_string(), vec![]),
+        ];
+        let mut archive = Vec::new();
+        write_entries(&mut archive, &entries).unwrap();
+        assert_eq!(archive.len() % BLOCK, 0);
+        assert_eq!(read_entries(&archive[..]).unwrap(), entries);
+    }
+
+    #[test]
+    fn corrupted_header_fails() {
+        let mut archive = Vec::new();
+        write_entries(&mut archive, &[("file".to_string(), vec![1, 2, 3])]).unwrap();
+        archive[0] = b'g';
+        assert!(read_entries(&archive[..]).is_err());
+    }
+}
@@ -0,0 +1,1641 @@
+//! Checking scene descriptions for mistakes, reported along with where they were made
+
+use super::{read_description, take_includes};
+use crate::{Error, Result};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use std::path::{Path, PathBuf};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+/// A problem found in a scene description.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// The file in which the problem was found
+    pub file: PathBuf,
+    /// The line at which the problem was found, if known
+    pub line: Option<usize>,
+    /// The path to the faulty value in the description, e.g: `objects[2].shape.radius`
+    pub path: String,
+    /// The description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if !self.path.is_empty() {
+            write!(f, ": {}", self.path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The range of values a number can take.
+struct Range {
+    min: f64,
+    exclusive: bool,
+    max: f64,
+    /// Describes the range in diagnostics.
+    expected: &'static str,
+}
+
+/// A field of a mapping.
+struct Field {
+    name: &'static str,
+    schema: &'static Schema,
+    required: bool,
+}
+
+const fn field(name: &'static str, schema: &'static Schema) -> Field {
+    Field {
+        name,
+        schema,
+        required: false,
+    }
+}
+
+const fn required(name: &'static str, schema: &'static Schema) -> Field {
+    Field {
+        name,
+        schema,
+        required: true,
+    }
+}
+
+/// Checks a value beyond its schema, returning the path relative to the value and the message of
+/// each problem found.
+type Check = fn(&Value) -> Vec<(String, String)>;
+
+/// The expected form of a value in the scene description.
+enum Schema {
+    /// Any value, which is not checked further.
+    Any,
+    /// A number within a range.
+    Number(Range),
+    /// A path to an existing file, relative to the description.
+    File,
+    /// A mapping with the given fields.
+    Struct(&'static [Field]),
+    /// A mapping whose other fields are given by the value of its `type` field.
+    Tagged(&'static [(&'static str, &'static Schema)]),
+    /// A list of values.
+    List(&'static Schema),
+    /// A mapping of names to values.
+    Dictionary(&'static Schema),
+    /// Either a value, or the name of an entry in one of the scene's dictionaries, the kind of
+    /// its entries being given for diagnostics.
+    Named(&'static str, &'static str, &'static Schema),
+    /// The first of several forms which matches the value best, among those of its kind.
+    Either(&'static [&'static Schema]),
+    /// A value with additional checks, only done if it matches its schema.
+    Checked(&'static Schema, Check),
+}
+
+impl Schema {
+    /// Whether the value is of the kind expected by the schema, e.g: a mapping for a struct.
+    fn accepts_kind(&self, value: &Value) -> bool {
+        match self {
+            Schema::Any => true,
+            Schema::Number(_) => value.is_number(),
+            Schema::File => value.is_string(),
+            Schema::Struct(_) | Schema::Tagged(_) | Schema::Dictionary(_) => value.is_mapping(),
+            Schema::List(_) => value.is_sequence(),
+            Schema::Named(_, _, schema) => value.is_string() || schema.accepts_kind(value),
+            Schema::Either(schemas) => schemas.iter().any(|schema| schema.accepts_kind(value)),
+            Schema::Checked(schema, _) => schema.accepts_kind(value),
+        }
+    }
+}
+
+/// The field giving the kind of tagged values.
+const TAG: &str = "type";
+
+static NUMBER: Schema = Schema::Number(Range {
+    min: f64::NEG_INFINITY,
+    exclusive: false,
+    max: f64::INFINITY,
+    expected: "a number",
+});
+static POSITIVE: Schema = Schema::Number(Range {
+    min: 0.,
+    exclusive: true,
+    max: f64::INFINITY,
+    expected: "a positive number",
+});
+static NON_NEGATIVE: Schema = Schema::Number(Range {
+    min: 0.,
+    exclusive: false,
+    max: f64::INFINITY,
+    expected: "a non-negative number",
+});
+static UNIT: Schema = Schema::Number(Range {
+    min: 0.,
+    exclusive: false,
+    max: 1.,
+    expected: "a number between 0 and 1",
+});
+static COUNT: Schema = Schema::Number(Range {
+    min: 1.,
+    exclusive: false,
+    max: f64::INFINITY,
+    expected: "at least 1",
+});
+static FOV: Schema = Schema::Number(Range {
+    min: 0.,
+    exclusive: true,
+    max: 180.,
+    expected: "an angle between 0 and 180 degrees",
+});
+static SPOT_FOV: Schema = Schema::Number(Range {
+    min: 0.,
+    exclusive: true,
+    max: 360.,
+    expected: "an angle between 0 and 360 degrees",
+});
+static REJECTION: Schema = Schema::Number(Range {
+    min: 1.,
+    exclusive: false,
+    max: f64::INFINITY,
+    expected: "a factor of at least 1",
+});
+static TURBIDITY: Schema = Schema::Number(Range {
+    min: 2.,
+    exclusive: false,
+    max: 10.,
+    expected: "a turbidity between 2 and 10",
+});
+
+static POINT: Schema = Schema::List(&NUMBER);
+static DIRECTION: Schema = Schema::Checked(&POINT, non_zero_vector);
+static COLOR: Schema = Schema::Either(&[
+    &Schema::Struct(&[
+        required("r", &NON_NEGATIVE),
+        required("g", &NON_NEGATIVE),
+        required("b", &NON_NEGATIVE),
+    ]),
+    &Schema::Checked(&Schema::List(&NON_NEGATIVE), color_components),
+]);
+
+static LIGHT_COLOR: Schema = Schema::Either(&[
+    &COLOR,
+    &Schema::Struct(&[
+        required("temperature", &POSITIVE),
+        field("intensity", &NON_NEGATIVE),
+    ]),
+]);
+
+static TEXTURE: Schema = Schema::Tagged(&[
+    (
+        "checker",
+        &Schema::Struct(&[
+            required("even", &COLOR),
+            required("odd", &COLOR),
+            field("squares", &POSITIVE),
+        ]),
+    ),
+    ("uniform", &Schema::Struct(&[required("color", &COLOR)])),
+    (
+        "channel",
+        &Schema::Struct(&[
+            required("texture", &TEXTURE),
+            field("channel", &Schema::Any),
+        ]),
+    ),
+    (
+        "image",
+        &Schema::Struct(&[
+            required("file", &Schema::File),
+            field("color_space", &Schema::Any),
+            field("lod_bias", &NUMBER),
+        ]),
+    ),
+    (
+        "noise",
+        &Schema::Struct(&[
+            required("pattern", &Schema::Any),
+            field("frequency", &POSITIVE),
+            field("octaves", &COUNT),
+            field("seed", &Schema::Any),
+            field("ramp", &Schema::List(&COLOR_STOP)),
+        ]),
+    ),
+    (
+        "transformed",
+        &Schema::Struct(&[
+            required("texture", &TEXTURE),
+            field("scale", &POINT),
+            field("rotation", &NUMBER),
+            field("offset", &POINT),
+            field("wrap", &Schema::Any),
+        ]),
+    ),
+    (
+        "triplanar",
+        &Schema::Struct(&[
+            required("texture", &TEXTURE),
+            field("scale", &POSITIVE),
+            field("sharpness", &POSITIVE),
+        ]),
+    ),
+]);
+static COLOR_STOP: Schema =
+    Schema::Struct(&[required("position", &UNIT), required("color", &COLOR)]);
+static NAMED_TEXTURE: Schema = Schema::Named("textures", "texture", &TEXTURE);
+static BUMP: Schema = Schema::Struct(&[required("texture", &TEXTURE), field("strength", &NUMBER)]);
+
+static SURFACE_INPUT: Schema = Schema::Tagged(&[
+    ("occlusion", &SURFACE_PROBE),
+    ("curvature", &SURFACE_PROBE),
+    (
+        "exposure",
+        &Schema::Struct(&[
+            required("direction", &DIRECTION),
+            required("radius", &POSITIVE),
+            field("samples", &COUNT),
+            field("range", &POINT),
+        ]),
+    ),
+]);
+static SURFACE_PROBE: Schema = Schema::Struct(&[
+    required("radius", &POSITIVE),
+    field("samples", &COUNT),
+    field("range", &POINT),
+]);
+static MIX_FACTOR: Schema = Schema::Either(&[&UNIT, &TEXTURE, &SURFACE_INPUT]);
+
+static MATERIAL: Schema = Schema::Tagged(&[
+    (
+        "uniform",
+        &Schema::Struct(&[
+            required("diffuse", &COLOR),
+            required("specular", &COLOR),
+            field("reflectivity", &UNIT),
+            field("transparency", &UNIT),
+            field("index", &POSITIVE),
+            field("translucency", &COLOR),
+            field("absorption", &COLOR),
+        ]),
+    ),
+    (
+        "principled",
+        &Schema::Struct(&[
+            required("base_color", &COLOR),
+            field("metallic", &UNIT),
+            field("roughness", &UNIT),
+            field("specular", &UNIT),
+            field("sheen", &UNIT),
+            field("sheen_color", &COLOR),
+            field("sheen_roughness", &UNIT),
+            field("clearcoat", &UNIT),
+            field("clearcoat_gloss", &UNIT),
+            field("transmission", &UNIT),
+            field("ior", &POSITIVE),
+            field("absorption", &COLOR),
+            field("base_color_map", &TEXTURE),
+            field("metallic_map", &TEXTURE),
+            field("roughness_map", &TEXTURE),
+            field("bitangent_roughness", &UNIT),
+            field("tangent_rotation", &NUMBER),
+            field("tangent_map", &TEXTURE),
+            field("emission", &COLOR),
+            field("emission_strength", &NON_NEGATIVE),
+            field("emission_map", &TEXTURE),
+            field("opacity", &UNIT),
+            field("opacity_map", &TEXTURE),
+            field("alpha_mode", &Schema::Any),
+            field("alpha_cutoff", &UNIT),
+        ]),
+    ),
+    (
+        "toon",
+        &Schema::Struct(&[
+            required("color", &COLOR),
+            field("bands", &COUNT),
+            field("highlight", &COLOR),
+            field("highlight_size", &UNIT),
+        ]),
+    ),
+    (
+        "mix",
+        &Schema::Struct(&[
+            required("first", &MATERIAL),
+            required("second", &MATERIAL),
+            required("factor", &MIX_FACTOR),
+        ]),
+    ),
+]);
+static NAMED_MATERIAL: Schema = Schema::Named("materials", "material", &MATERIAL);
+
+static TRIANGLE: Schema = Schema::Checked(
+    &Schema::Struct(&[
+        required("corners", &Schema::List(&POINT)),
+        field("texcoords", &Schema::Any),
+        field("normals", &Schema::List(&DIRECTION)),
+    ]),
+    degenerate_triangle,
+);
+static SHAPE: Schema = Schema::Tagged(&[
+    (
+        "sphere",
+        &Schema::Struct(&[
+            field("inverted", &Schema::Any),
+            required("center", &POINT),
+            required("radius", &POSITIVE),
+            field("projection", &Schema::Any),
+        ]),
+    ),
+    (
+        "disk",
+        &Schema::Struct(&[
+            required("center", &POINT),
+            required("normal", &DIRECTION),
+            required("radius", &POSITIVE),
+        ]),
+    ),
+    (
+        "heightfield",
+        &Schema::Struct(&[
+            required("file", &Schema::File),
+            required("corner", &POINT),
+            required("size", &Schema::List(&POSITIVE)),
+        ]),
+    ),
+    (
+        "plane",
+        &Schema::Struct(&[required("origin", &POINT), required("normal", &DIRECTION)]),
+    ),
+    (
+        "csg",
+        &Schema::Struct(&[
+            required("operation", &Schema::Any),
+            required("left", &SHAPE),
+            required("right", &SHAPE),
+        ]),
+    ),
+    (
+        "sdf",
+        &Schema::Struct(&[
+            required("center", &POINT),
+            field("scale", &POSITIVE),
+            required("primitive", &Schema::Any),
+            field("radius", &POSITIVE),
+            field("half_extents", &POINT),
+            field("major_radius", &POSITIVE),
+            field("minor_radius", &POSITIVE),
+            field("frequency", &POSITIVE),
+            field("thickness", &POSITIVE),
+            field("power", &NUMBER),
+            field("iterations", &COUNT),
+        ]),
+    ),
+    ("triangle", &TRIANGLE),
+]);
+
+static VISIBILITY: Schema = Schema::Struct(&[
+    field("min_depth", &NON_NEGATIVE),
+    field("max_depth", &NON_NEGATIVE),
+]);
+static MOTION: Schema = Schema::Struct(&[
+    field("pivot", &POINT),
+    required(
+        "keyframes",
+        &Schema::List(&Schema::Struct(&[
+            required("time", &NUMBER),
+            field("translate", &POINT),
+            field("rotate", &Schema::Any),
+            field("scale", &POSITIVE),
+        ])),
+    ),
+]);
+
+static OBJECT: Schema = Schema::Struct(&[
+    field("name", &Schema::Any),
+    required("shape", &SHAPE),
+    required("material", &NAMED_MATERIAL),
+    required("texture", &NAMED_TEXTURE),
+    field("bump", &BUMP),
+    field("visibility", &VISIBILITY),
+    field("shadow_catcher", &Schema::Any),
+    field("motion", &MOTION),
+    field("sides", &Schema::Any),
+]);
+
+static MODIFIER: Schema = Schema::Tagged(&[
+    (
+        "displacement",
+        &Schema::Struct(&[
+            required("texture", &TEXTURE),
+            field("strength", &NUMBER),
+            field("levels", &NON_NEGATIVE),
+        ]),
+    ),
+    (
+        "noise",
+        &Schema::Struct(&[
+            required("amplitude", &NUMBER),
+            required("frequency", &POSITIVE),
+            field("seed", &Schema::Any),
+            field("octaves", &COUNT),
+        ]),
+    ),
+    (
+        "subdivision",
+        &Schema::Struct(&[field("scheme", &Schema::Any), required("levels", &COUNT)]),
+    ),
+]);
+
+static MESH_OBJECT: Schema = Schema::Checked(
+    &Schema::Struct(&[
+        field("name", &Schema::Any),
+        field("file", &Schema::File),
+        field("vertices", &Schema::List(&POINT)),
+        field("faces", &Schema::Any),
+        field("smoothing_angle", &NON_NEGATIVE),
+        field("up_axis", &Schema::Any),
+        field("handedness", &Schema::Any),
+        field("transform", &Schema::Any),
+        field("modifiers", &Schema::List(&MODIFIER)),
+        field("material", &NAMED_MATERIAL),
+        field("texture", &NAMED_TEXTURE),
+        field("bump", &BUMP),
+        field("visibility", &VISIBILITY),
+        field("shadow_catcher", &Schema::Any),
+        field("motion", &MOTION),
+        field("sides", &Schema::Any),
+    ]),
+    degenerate_faces,
+);
+static SCATTER: Schema = Schema::Struct(&[
+    required("prototype", &MESH_OBJECT),
+    required(
+        "target",
+        &Schema::Checked(
+            &Schema::Struct(&[
+                field("file", &Schema::File),
+                field("vertices", &Schema::List(&POINT)),
+                field("faces", &Schema::Any),
+                field("smoothing_angle", &NON_NEGATIVE),
+                field("transform", &Schema::Any),
+            ]),
+            degenerate_faces,
+        ),
+    ),
+    required("density", &NON_NEGATIVE),
+    field("scale", &Schema::List(&POSITIVE)),
+    field("rotation", &Schema::List(&NUMBER)),
+    field("align", &Schema::Any),
+    field("seed", &Schema::Any),
+]);
+static POINT_CLOUD: Schema = Schema::Struct(&[
+    field("name", &Schema::Any),
+    field("file", &Schema::File),
+    field("positions", &Schema::List(&POINT)),
+    field("normals", &Schema::List(&DIRECTION)),
+    field("radii", &Schema::List(&POSITIVE)),
+    field("colors", &Schema::List(&COLOR)),
+    field("radius", &POSITIVE),
+    field("splat", &Schema::Any),
+    field("up_axis", &Schema::Any),
+    field("handedness", &Schema::Any),
+    field("transform", &Schema::Any),
+    field("material", &NAMED_MATERIAL),
+    field("texture", &NAMED_TEXTURE),
+    field("visibility", &VISIBILITY),
+    field("shadow_catcher", &Schema::Any),
+    field("motion", &MOTION),
+]);
+
+static CAMERA: Schema = Schema::Checked(
+    &Schema::Struct(&[
+        required("origin", &POINT),
+        field("forward", &DIRECTION),
+        field("up", &DIRECTION),
+        field("look_at", &POINT),
+        required("fov", &FOV),
+        field("distance_to_image", &POSITIVE),
+        required("x", &COUNT),
+        required("y", &COUNT),
+        field("shutter", &Schema::List(&NUMBER)),
+        field("motion", &MOTION),
+    ]),
+    parallel_up,
+);
+
+static OBJECT_NAMES: Schema = Schema::List(&Schema::Named("objects", "object", &Schema::Any));
+static LIGHTS: Schema = Schema::Struct(&[
+    field(
+        "ambients",
+        &Schema::List(&Schema::Struct(&[
+            required("color", &LIGHT_COLOR),
+            field("name", &Schema::Any),
+            field("group", &Schema::Any),
+            field("affects", &OBJECT_NAMES),
+            field("excludes", &OBJECT_NAMES),
+        ])),
+    ),
+    field(
+        "directionals",
+        &Schema::List(&Schema::Struct(&[
+            required("direction", &DIRECTION),
+            required("color", &LIGHT_COLOR),
+            field("name", &Schema::Any),
+            field("group", &Schema::Any),
+            field("affects", &OBJECT_NAMES),
+            field("excludes", &OBJECT_NAMES),
+        ])),
+    ),
+    field(
+        "points",
+        &Schema::List(&Schema::Struct(&[
+            required("position", &POINT),
+            required("color", &LIGHT_COLOR),
+            field("intensity", &NON_NEGATIVE),
+            field("falloff", &Schema::Any),
+            field("name", &Schema::Any),
+            field("group", &Schema::Any),
+            field("affects", &OBJECT_NAMES),
+            field("excludes", &OBJECT_NAMES),
+        ])),
+    ),
+    field(
+        "spots",
+        &Schema::List(&Schema::Struct(&[
+            required("position", &POINT),
+            required("direction", &DIRECTION),
+            required("fov", &SPOT_FOV),
+            required("color", &LIGHT_COLOR),
+            field("gobo", &TEXTURE),
+            field("name", &Schema::Any),
+            field("group", &Schema::Any),
+            field("affects", &OBJECT_NAMES),
+            field("excludes", &OBJECT_NAMES),
+        ])),
+    ),
+    field(
+        "areas",
+        &Schema::List(&Schema::Checked(
+            &Schema::Struct(&[
+                required("corner", &POINT),
+                required("u", &POINT),
+                required("v", &POINT),
+                required("color", &LIGHT_COLOR),
+                field("samples", &COUNT),
+                field("gobo", &TEXTURE),
+                field("name", &Schema::Any),
+                field("group", &Schema::Any),
+                field("affects", &OBJECT_NAMES),
+                field("excludes", &OBJECT_NAMES),
+            ]),
+            degenerate_area,
+        )),
+    ),
+    field(
+        "spheres",
+        &Schema::List(&Schema::Struct(&[
+            required("position", &POINT),
+            required("radius", &POSITIVE),
+            required("power", &LIGHT_COLOR),
+            field("samples", &COUNT),
+            field("name", &Schema::Any),
+            field("group", &Schema::Any),
+            field("affects", &OBJECT_NAMES),
+            field("excludes", &OBJECT_NAMES),
+        ])),
+    ),
+    field(
+        "projectors",
+        &Schema::List(&Schema::Struct(&[
+            required("position", &POINT),
+            required("direction", &DIRECTION),
+            field("up", &DIRECTION),
+            required("fov", &FOV),
+            field("aspect", &POSITIVE),
+            required("color", &LIGHT_COLOR),
+            required("image", &TEXTURE),
+            field("name", &Schema::Any),
+            field("group", &Schema::Any),
+            field("affects", &OBJECT_NAMES),
+            field("excludes", &OBJECT_NAMES),
+        ])),
+    ),
+]);
+
+static BACKGROUND: Schema = Schema::Either(&[
+    &COLOR,
+    &Schema::Struct(&[
+        required("zenith", &COLOR),
+        required("horizon", &COLOR),
+        required("ground", &COLOR),
+        field(
+            "sun",
+            &Schema::Struct(&[
+                required("direction", &DIRECTION),
+                required("color", &COLOR),
+                field("size", &POSITIVE),
+            ]),
+        ),
+    ]),
+    &Schema::Struct(&[
+        required("sun_direction", &DIRECTION),
+        field("turbidity", &TURBIDITY),
+        field("intensity", &NON_NEGATIVE),
+        field("ground", &COLOR),
+        field("samples", &NON_NEGATIVE),
+    ]),
+]);
+
+static INTEGRATOR: Schema = Schema::Tagged(&[
+    ("raytrace", &Schema::Struct(&[])),
+    (
+        "pathtrace",
+        &Schema::Struct(&[
+            field("roulette_depth", &NON_NEGATIVE),
+            field("regularization", &NON_NEGATIVE),
+            field("indirect_clamp", &POSITIVE),
+        ]),
+    ),
+    (
+        "debug",
+        &Schema::Struct(&[
+            required("mode", &Schema::Any),
+            field("max_distance", &POSITIVE),
+            field("max_tests", &COUNT),
+        ]),
+    ),
+]);
+
+static FILTER: Schema = Schema::Tagged(&[
+    ("box", &Schema::Struct(&[field("radius", &POSITIVE)])),
+    ("tent", &Schema::Struct(&[field("radius", &POSITIVE)])),
+    (
+        "gaussian",
+        &Schema::Struct(&[field("radius", &POSITIVE), field("sigma", &POSITIVE)]),
+    ),
+    (
+        "mitchell",
+        &Schema::Struct(&[
+            field("radius", &POSITIVE),
+            field("b", &Schema::Any),
+            field("c", &Schema::Any),
+        ]),
+    ),
+]);
+
+static SCENE: Schema = Schema::Struct(&[
+    field("include", &Schema::Any),
+    required("camera", &CAMERA),
+    field("cameras", &Schema::Dictionary(&CAMERA)),
+    field("lights", &LIGHTS),
+    field("materials", &Schema::Dictionary(&MATERIAL)),
+    field("textures", &Schema::Dictionary(&TEXTURE)),
+    field("objects", &Schema::List(&OBJECT)),
+    field("meshes", &Schema::List(&MESH_OBJECT)),
+    field("scatters", &Schema::List(&SCATTER)),
+    field("point_clouds", &Schema::List(&POINT_CLOUD)),
+    field("up_axis", &Schema::Any),
+    field("handedness", &Schema::Any),
+    field("background", &BACKGROUND),
+    field("integrator", &INTEGRATOR),
+    field("light_samples", &COUNT),
+    field(
+        "grain",
+        &Schema::Struct(&[
+            required("strength", &NON_NEGATIVE),
+            field("seed", &Schema::Any),
+        ]),
+    ),
+    field("denoise", &Schema::Struct(&[required("strength", &UNIT)])),
+    field(
+        "outline",
+        &Schema::Struct(&[
+            field("color", &COLOR),
+            field("width", &COUNT),
+            field("depth_threshold", &NON_NEGATIVE),
+            field("normal_threshold", &NON_NEGATIVE),
+        ]),
+    ),
+    field("crop", &Schema::Any),
+    field("precision", &Schema::Any),
+    field("filter", &FILTER),
+    field("sampler", &Schema::Any),
+    field("tile_order", &Schema::Any),
+    field("outlier_rejection", &REJECTION),
+    field(
+        "ambient_occlusion",
+        &Schema::Struct(&[required("distance", &POSITIVE), field("samples", &COUNT)]),
+    ),
+    field("aliasing_limit", &NON_NEGATIVE),
+    field("reflection_limit", &NON_NEGATIVE),
+    field("starting_diffraction", &POSITIVE),
+    field("bvh_builder", &Schema::Any),
+    field("bvh_cache", &Schema::Any),
+    field(
+        "frames",
+        &Schema::Checked(&Schema::List(&NON_NEGATIVE), frame_range),
+    ),
+]);
+
+fn coordinates(value: &Value) -> Option<Vec<f64>> {
+    value.as_sequence()?.iter().map(Value::as_f64).collect()
+}
+
+fn cross(a: &[f64], b: &[f64]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn is_zero(vector: &[f64]) -> bool {
+    vector.iter().all(|c| c.abs() < 1e-9)
+}
+
+fn non_zero_vector(value: &Value) -> Vec<(String, String)> {
+    match coordinates(value) {
+        Some(vector) if is_zero(&vector) => vec![(String::new(), "zero-length vector".into())],
+        _ => Vec::new(),
+    }
+}
+
+fn color_components(value: &Value) -> Vec<(String, String)> {
+    match value.as_sequence() {
+        Some(components) if components.len() != 3 => {
+            vec![(String::new(), "expected the r, g and b components".into())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn parallel_up(value: &Value) -> Vec<(String, String)> {
+    let forward = value.get("forward").and_then(coordinates);
+    let up = value.get("up").and_then(coordinates);
+    match (forward, up) {
+        (Some(forward), Some(up))
+            if forward.len() == 3 && up.len() == 3 && is_zero(&cross(&forward, &up)) =>
+        {
+            vec![("up".into(), "parallel to the forward direction".into())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn frame_range(value: &Value) -> Vec<(String, String)> {
+    match coordinates(value).as_deref() {
+        Some([first, last]) if first > last => {
+            vec![(
+                String::new(),
+                "the first frame comes after the last one".into(),
+            )]
+        }
+        Some([_, _]) | None => Vec::new(),
+        Some(_) => vec![(String::new(), "expected the first and last frames".into())],
+    }
+}
+
+/// Check whether the triangle between three points has no area.
+fn is_degenerate(a: &[f64], b: &[f64], c: &[f64]) -> bool {
+    if a.len() != 3 || b.len() != 3 || c.len() != 3 {
+        return false;
+    }
+    let ab: Vec<_> = (0..3).map(|i| b[i] - a[i]).collect();
+    let ac: Vec<_> = (0..3).map(|i| c[i] - a[i]).collect();
+    is_zero(&cross(&ab, &ac))
+}
+
+fn degenerate_triangle(value: &Value) -> Vec<(String, String)> {
+    let corners: Option<Vec<_>> = (value.get("corners").and_then(Value::as_sequence))
+        .map(|corners| corners.iter().filter_map(coordinates).collect());
+    match corners.as_deref() {
+        Some([a, b, c]) if is_degenerate(a, b, c) => {
+            vec![("corners".into(), "zero-area triangle".into())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn degenerate_faces(value: &Value) -> Vec<(String, String)> {
+    let has = |key: &str| value.get(key).is_some();
+    if !(has("file") || has("vertices") && has("faces")) {
+        return vec![(
+            String::new(),
+            "expected either a file, or vertices and faces".into(),
+        )];
+    }
+    let vertices: Vec<_> = match value.get("vertices").and_then(Value::as_sequence) {
+        Some(vertices) => vertices.iter().map(coordinates).collect(),
+        None => return Vec::new(),
+    };
+    let faces = value.get("faces").and_then(Value::as_sequence);
+    let mut problems = Vec::new();
+    for (i, face) in faces.into_iter().flatten().enumerate() {
+        let path = format!("faces[{}]", i);
+        let indices: Option<Vec<_>> = (face.as_sequence()).and_then(|face| {
+            face.iter()
+                .map(|i| i.as_u64().map(|i| i as usize))
+                .collect()
+        });
+        let indices = match indices {
+            Some(indices) if indices.len() == 3 => indices,
+            _ => continue,
+        };
+        if let Some(&index) = indices.iter().find(|&&i| i >= vertices.len()) {
+            problems.push((path, format!("refers to a non-existent vertex {}", index)));
+            continue;
+        }
+        if let [Some(a), Some(b), Some(c)] = [0, 1, 2].map(|i| vertices[indices[i]].as_ref()) {
+            if is_degenerate(a, b, c) {
+                problems.push((path, "zero-area face".into()));
+            }
+        }
+    }
+    problems
+}
+
+fn degenerate_area(value: &Value) -> Vec<(String, String)> {
+    let u = value.get("u").and_then(coordinates);
+    let v = value.get("v").and_then(coordinates);
+    match (u, v) {
+        (Some(u), Some(v)) if u.len() == 3 && v.len() == 3 && is_zero(&cross(&u, &v)) => {
+            vec![(String::new(), "zero-area light".into())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The number of single-character edits needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<_> = b.chars().collect();
+    let mut previous: Vec<_> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Describe an unexpected name, suggesting the closest expected one when it looks like a typo.
+fn unexpected(kind: &str, name: &str, expected: &[&str]) -> String {
+    let closest = (expected.iter())
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .min();
+    match closest {
+        Some((distance, candidate)) if distance <= 2 => {
+            format!("unknown {} '{}', did you mean '{}'?", kind, name, candidate)
+        }
+        _ if expected.is_empty() => format!("unknown {} '{}'", kind, name),
+        _ => format!(
+            "unknown {} '{}', expected one of: {}",
+            kind,
+            name,
+            expected.join(", ")
+        ),
+    }
+}
+
+fn child(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Record the line of each value of a YAML document, indexed by its path.
+#[derive(Default)]
+struct LineIndex {
+    lines: HashMap<String, usize>,
+    /// The path of each mapping or list being read, and the key or index of its next value.
+    stack: Vec<(String, Option<String>, Option<usize>)>,
+}
+
+impl LineIndex {
+    fn new(description: &str) -> Self {
+        let mut index = LineIndex::default();
+        // Syntax errors are reported when reading the description
+        Parser::new(description.chars())
+            .load(&mut index, false)
+            .ok();
+        index
+    }
+
+    /// Get the path of the value starting at `mark`, or `None` if it is a key.
+    fn path(&mut self, scalar: Option<&str>, mark: Marker) -> Option<String> {
+        let path = match self.stack.last_mut() {
+            None => String::new(),
+            Some((path, key @ None, None)) => {
+                let name = scalar.unwrap_or("?").to_string();
+                let path = child(path, &name);
+                self.lines.insert(path, mark.line());
+                *key = Some(name);
+                return None;
+            }
+            Some((path, key @ Some(_), None)) => child(path, &key.take().unwrap()),
+            Some((path, _, Some(index))) => {
+                *index += 1;
+                format!("{}[{}]", path, *index - 1)
+            }
+        };
+        self.lines.entry(path.clone()).or_insert(mark.line());
+        Some(path)
+    }
+}
+
+impl MarkedEventReceiver for LineIndex {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, ..) => {
+                self.path(Some(&value), mark);
+            }
+            Event::Alias(_) => {
+                self.path(None, mark);
+            }
+            Event::MappingStart(_) => {
+                let path = self.path(None, mark).unwrap_or_else(|| "?".into());
+                self.stack.push((path, None, None));
+            }
+            Event::SequenceStart(_) => {
+                let path = self.path(None, mark).unwrap_or_else(|| "?".into());
+                self.stack.push((path, None, Some(0)));
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks one file of a scene description against the scene's schema.
+struct Validator<'a> {
+    file: &'a Path,
+    lines: LineIndex,
+    /// The names defined in each of the scene's dictionaries.
+    names: &'a HashMap<String, BTreeSet<String>>,
+    /// Whether the file is only part of the scene, its missing fields being given by others.
+    partial: bool,
+}
+
+impl Validator<'_> {
+    fn diagnostic(&self, path: &str, message: String) -> Diagnostic {
+        // Use the line of the closest enclosing value which is known
+        let mut line = None;
+        let mut prefix = path;
+        while line.is_none() {
+            line = self.lines.lines.get(prefix).copied();
+            match prefix.rfind(['.', '[']) {
+                Some(end) => prefix = &prefix[..end],
+                None if prefix.is_empty() => break,
+                None => prefix = "",
+            }
+        }
+        Diagnostic {
+            file: self.file.to_path_buf(),
+            line,
+            path: path.to_string(),
+            message,
+        }
+    }
+
+    fn check(&self, schema: &Schema, value: &Value, path: &str) -> Vec<Diagnostic> {
+        // Optional values left empty are allowed
+        if value.is_null() {
+            return Vec::new();
+        }
+        match schema {
+            Schema::Any => Vec::new(),
+            Schema::Number(range) => match value.as_f64() {
+                None => vec![self.diagnostic(path, format!("expected {}", range.expected))],
+                Some(number) => {
+                    let too_low = number < range.min || (range.exclusive && number == range.min);
+                    if too_low || number > range.max {
+                        let msg =
+                            format!("{} is out of range, expected {}", number, range.expected);
+                        vec![self.diagnostic(path, msg)]
+                    } else {
+                        Vec::new()
+                    }
+                }
+            },
+            Schema::File => match value.as_str() {
+                None => vec![self.diagnostic(path, "expected a file name".into())],
+                Some(file) => {
+                    let directory = self.file.parent().unwrap_or_else(|| Path::new(""));
+                    if directory.join(file).exists() {
+                        Vec::new()
+                    } else {
+                        let msg = format!("file '{}' does not exist", file);
+                        vec![self.diagnostic(path, msg)]
+                    }
+                }
+            },
+            Schema::Struct(fields) => self.check_fields(fields, value, path),
+            Schema::Tagged(variants) => {
+                let tag = value.get(TAG).and_then(Value::as_str);
+                let names: Vec<_> = variants.iter().map(|(name, _)| *name).collect();
+                match (tag, variants.iter().find(|(name, _)| Some(*name) == tag)) {
+                    (_, Some((_, schema))) => {
+                        let mut fields = value.clone();
+                        if let Value::Mapping(mapping) = &mut fields {
+                            mapping.remove(&Value::String(TAG.to_string()));
+                        }
+                        self.check(schema, &fields, path)
+                    }
+                    (Some(tag), None) => {
+                        let msg = unexpected(TAG, tag, &names);
+                        vec![self.diagnostic(&child(path, TAG), msg)]
+                    }
+                    (None, None) if self.partial => Vec::new(),
+                    (None, None) => {
+                        let msg = format!("missing field '{}', one of: {}", TAG, names.join(", "));
+                        vec![self.diagnostic(path, msg)]
+                    }
+                }
+            }
+            Schema::List(item) => match value.as_sequence() {
+                None => vec![self.diagnostic(path, "expected a list".into())],
+                Some(values) => (values.iter().enumerate())
+                    .flat_map(|(i, value)| self.check(item, value, &format!("{}[{}]", path, i)))
+                    .collect(),
+            },
+            Schema::Dictionary(entry) => match value.as_mapping() {
+                None => vec![self.diagnostic(path, "expected a mapping of names".into())],
+                Some(mapping) => (mapping.iter())
+                    .flat_map(|(name, value)| {
+                        let name = name.as_str().unwrap_or("?");
+                        self.check(entry, value, &child(path, name))
+                    })
+                    .collect(),
+            },
+            Schema::Named(dictionary, kind, schema) => match value.as_str() {
+                Some(name) => {
+                    let names = self.names.get(*dictionary);
+                    if names.is_some_and(|names| names.contains(name)) {
+                        Vec::new()
+                    } else {
+                        let names: Vec<_> =
+                            names.into_iter().flatten().map(String::as_str).collect();
+                        vec![self.diagnostic(path, unexpected(kind, name, &names))]
+                    }
+                }
+                None => self.check(schema, value, path),
+            },
+            Schema::Either(schemas) => {
+                // Only consider the forms of the same kind as the value, if there are any
+                let matching: Vec<_> = (schemas.iter())
+                    .filter(|schema| schema.accepts_kind(value))
+                    .collect();
+                let candidates = if matching.is_empty() {
+                    schemas.iter().collect()
+                } else {
+                    matching
+                };
+                (candidates.into_iter())
+                    .map(|schema| self.check(schema, value, path))
+                    .min_by_key(Vec::len)
+                    .unwrap_or_default()
+            }
+            Schema::Checked(schema, check) => {
+                let diagnostics = self.check(schema, value, path);
+                if !diagnostics.is_empty() {
+                    return diagnostics;
+                }
+                (check(value).into_iter())
+                    .map(|(relative, message)| {
+                        let path = match relative.as_str() {
+                            "" => path.to_string(),
+                            relative => child(path, relative),
+                        };
+                        self.diagnostic(&path, message)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn check_fields(&self, fields: &[Field], value: &Value, path: &str) -> Vec<Diagnostic> {
+        let mapping: &Mapping = match value.as_mapping() {
+            Some(mapping) => mapping,
+            None => return vec![self.diagnostic(path, "expected a mapping".into())],
+        };
+        let mut diagnostics = Vec::new();
+        let names: Vec<_> = fields.iter().map(|field| field.name).collect();
+        for (key, value) in mapping {
+            let key = key.as_str().unwrap_or("?");
+            match fields.iter().find(|field| field.name == key) {
+                Some(field) => {
+                    diagnostics.extend(self.check(field.schema, value, &child(path, key)))
+                }
+                None => {
+                    let msg = unexpected("field", key, &names);
+                    diagnostics.push(self.diagnostic(&child(path, key), msg));
+                }
+            }
+        }
+        if !self.partial {
+            for field in fields.iter().filter(|field| field.required) {
+                if !mapping.contains_key(&Value::String(field.name.to_string())) {
+                    let msg = format!("missing field '{}'", field.name);
+                    diagnostics.push(self.diagnostic(path, msg));
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+/// List the files making up a scene description, in the order they are read.
+fn scene_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if files.iter().any(|file| file == path) {
+        return Ok(());
+    }
+    files.push(path.to_path_buf());
+    let description =
+        std::fs::read_to_string(path).map_err(|err| Error::from(err).with_path(path))?;
+    let mut value: Value = serde_yaml::from_str(&description).unwrap_or_default();
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    for include in take_includes(&mut value, path)? {
+        scene_files(&directory.join(include), files)?;
+    }
+    Ok(())
+}
+
+/// Check a single file of a scene description.
+fn validate_file(
+    file: &Path,
+    description: &str,
+    names: &HashMap<String, BTreeSet<String>>,
+    partial: bool,
+) -> Vec<Diagnostic> {
+    let validator = Validator {
+        file,
+        lines: LineIndex::new(description),
+        names,
+        partial,
+    };
+    match serde_yaml::from_str::<Value>(description) {
+        Ok(value) => validator.check(&SCENE, &value, ""),
+        Err(err) => {
+            let line = err.location().map(|location| location.line());
+            vec![Diagnostic {
+                line,
+                ..validator.diagnostic("", err.to_string())
+            }]
+        }
+    }
+}
+
+/// Get the names defined in each of the scene's dictionaries.
+fn dictionary_names(description: &Value) -> HashMap<String, BTreeSet<String>> {
+    let mut names: HashMap<_, _> = ["materials", "textures"]
+        .iter()
+        .map(|&dictionary| {
+            let names = (description.get(dictionary).and_then(Value::as_mapping))
+                .into_iter()
+                .flat_map(|mapping| mapping.iter().filter_map(|(name, _)| name.as_str()))
+                .map(String::from)
+                .collect();
+            (dictionary.to_string(), names)
+        })
+        .collect();
+    // Objects are named by a field of their own, and can be referred to by the lights
+    let list = |field: &str| {
+        (description.get(field).and_then(Value::as_sequence))
+            .into_iter()
+            .flatten()
+    };
+    let prototypes = list("scatters").filter_map(|scatter| scatter.get("prototype"));
+    let objects = (list("objects").chain(list("meshes")).chain(prototypes))
+        .chain(list("point_clouds"))
+        .filter_map(|object| object.get("name").and_then(Value::as_str))
+        .map(String::from)
+        .collect();
+    names.insert("objects".to_string(), objects);
+    names
+}
+
+/// Check the scene description at `path`, and the files it includes, for mistakes which would
+/// either stop it from loading or silently give unexpected results.
+///
+/// These are reported with the file and line at which they were found:
+///
+/// * fields which are not part of the scene format, e.g: misspelled ones, and missing ones.
+/// * materials and textures referred to by a name which is not defined, and missing files.
+/// * degenerate geometry: zero-area triangles, faces and area lights, zero-length directions.
+/// * parameters outside of their valid range, e.g: a negative radius or color.
+///
+/// When the scene is split across several files, fields can be given by any of them: missing
+/// fields are only reported for scenes made of a single file.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use pathtracer::serialize::validate;
+/// # use std::path::Path;
+/// #
+/// for diagnostic in validate(Path::new("scene.yaml")).unwrap() {
+///     eprintln!("{}", diagnostic);
+/// }
+/// ```
+pub fn validate(path: &Path) -> Result<Vec<Diagnostic>> {
+    // Also checks that every included file can be read
+    let description = read_description(path)?;
+    let names = dictionary_names(&description);
+    let mut files = Vec::new();
+    scene_files(path, &mut files)?;
+    let partial = files.len() > 1;
+    let mut diagnostics = Vec::new();
+    for file in files {
+        let description =
+            std::fs::read_to_string(&file).map_err(|err| Error::from(err).with_path(&file))?;
+        diagnostics.extend(validate_file(&file, &description, &names, partial));
+    }
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn validate_str(description: &str) -> Vec<(Option<usize>, String, String)> {
+        let value = serde_yaml::from_str(description).unwrap_or_default();
+        let names = dictionary_names(&value);
+        validate_file(Path::new("scene.yaml"), description, &names, false)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.path, diagnostic.message))
+            .collect()
+    }
+
+    const CAMERA: &str = r#"
+camera:
+  origin: [-1.0, 0.0, 0.0]
+  look_at: [0.0, 0.0, 0.0]
+  fov: 90.0
+  x: 8
+  y: 8
+"#;
+
+    #[test]
+    fn examples_are_valid() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some("yaml") {
+                assert_eq!(validate(&path).unwrap(), Vec::new(), "{}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn saved_scenes_are_valid() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let saved =
+            std::env::temp_dir().join(format!("pathtracer-saved-{}.yaml", std::process::id()));
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("yaml") {
+                continue;
+            }
+            let description = crate::serialize::load_description(&path).unwrap();
+            let scene: crate::render::Scene = serde_yaml::from_str(&description).unwrap();
+            std::fs::write(&saved, serde_yaml::to_string(&scene).unwrap()).unwrap();
+            assert_eq!(validate(&saved).unwrap(), Vec::new(), "{}", path.display());
+        }
+        std::fs::remove_file(&saved).unwrap();
+    }
+
+    #[test]
+    fn unknown_fields_are_reported() {
+        let description = format!(
+            "{}{}",
+            CAMERA,
+            r#"
+objects:
+  - shape:
+      type: sphere
+      center: [0.0, 0.0, 0.0]
+      raduis: 1.0
+    material:
+      type: uniform
+      diffuse: {r: 1.0, g: 1.0, b: 1.0}
+      specular: {r: 0.0, g: 0.0, b: 0.0}
+    texture:
+      type: uniform
+      color: {r: 1.0, g: 1.0, b: 1.0}
+ligths: {}
+"#
+        );
+        assert_eq!(
+            validate_str(&description),
+            vec![
+                (
+                    Some(13),
+                    "objects[0].shape.raduis".into(),
+                    "unknown field 'raduis', did you mean 'radius'?".into()
+                ),
+                (
+                    Some(10),
+                    "objects[0].shape".into(),
+                    "missing field 'radius'".into()
+                ),
+                (
+                    Some(21),
+                    "ligths".into(),
+                    "unknown field 'ligths', did you mean 'lights'?".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn references_are_checked() {
+        let description = format!(
+            "{}{}",
+            CAMERA,
+            r#"
+materials:
+  red:
+    type: uniform
+    diffuse: {r: 1.0, g: 0.0, b: 0.0}
+    specular: {r: 0.0, g: 0.0, b: 0.0}
+meshes:
+  - file: missing.obj
+    material: rde
+"#
+        );
+        assert_eq!(
+            validate_str(&description),
+            vec![
+                (
+                    Some(15),
+                    "meshes[0].file".into(),
+                    "file 'missing.obj' does not exist".into()
+                ),
+                (
+                    Some(16),
+                    "meshes[0].material".into(),
+                    "unknown material 'rde', did you mean 'red'?".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn linked_objects_are_checked() {
+        let description = format!(
+            "{}{}",
+            CAMERA,
+            r#"
+lights:
+  points:
+    - position: [0.0, 1.0, 0.0]
+      color: {r: 1.0, g: 1.0, b: 1.0}
+      excludes: [flor]
+meshes:
+  - name: floor
+    vertices: [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]
+    faces: [[0, 1, 2]]
+"#
+        );
+        assert_eq!(
+            validate_str(&description),
+            vec![(
+                Some(13),
+                "lights.points[0].excludes[0]".into(),
+                "unknown object 'flor', did you mean 'floor'?".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn light_temperatures_are_checked() {
+        let description = format!(
+            "{}{}",
+            CAMERA,
+            r#"
+lights:
+  points:
+    - position: [0.0, 1.0, 0.0]
+      color: {temperature: 3200, intensity: 2.0}
+    - position: [0.0, 1.0, 0.0]
+      color: {temperature: -1.0}
+"#
+        );
+        assert_eq!(
+            validate_str(&description),
+            vec![(
+                Some(14),
+                "lights.points[1].color.temperature".into(),
+                "-1 is out of range, expected a positive number".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn degenerate_geometry_is_reported() {
+        let description = format!(
+            "{}{}",
+            CAMERA,
+            r#"
+lights:
+  areas:
+    - corner: [0.0, 0.0, 0.0]
+      u: [1.0, 0.0, 0.0]
+      v: [2.0, 0.0, 0.0]
+      color: {r: 1.0, g: 1.0, b: 1.0}
+meshes:
+  - vertices: [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+    faces: [[0, 1, 3], [0, 1, 2], [0, 1, 4]]
+"#
+        );
+        assert_eq!(
+            validate_str(&description),
+            vec![
+                (Some(11), "lights.areas[0]".into(), "zero-area light".into()),
+                (
+                    Some(17),
+                    "meshes[0].faces[1]".into(),
+                    "zero-area face".into()
+                ),
+                (
+                    Some(17),
+                    "meshes[0].faces[2]".into(),
+                    "refers to a non-existent vertex 4".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn degenerate_triangles_are_reported() {
+        let description = format!(
+            "{}{}",
+            CAMERA,
+            r#"
+objects:
+  - shape:
+      type: triangle
+      corners: [[0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [2.0, 2.0, 2.0]]
+    material:
+      type: uniform
+      diffuse: {r: 1.0, g: 1.0, b: 1.0}
+      specular: {r: 0.0, g: 0.0, b: 0.0}
+    texture:
+      type: uniform
+      color: {r: 1.0, g: 1.0, b: 1.0}
+"#
+        );
+        assert_eq!(
+            validate_str(&description),
+            vec![(
+                Some(12),
+                "objects[0].shape.corners".into(),
+                "zero-area triangle".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn ranges_are_checked() {
+        let description = r#"
+camera:
+  origin: [-1.0, 0.0, 0.0]
+  forward: [0.0, 0.0, 0.0]
+  fov: 190.0
+  x: 0
+  y: 8
+"#;
+        assert_eq!(
+            validate_str(description),
+            vec![
+                (
+                    Some(4),
+                    "camera.forward".into(),
+                    "zero-length vector".into()
+                ),
+                (
+                    Some(5),
+                    "camera.fov".into(),
+                    "190 is out of range, expected an angle between 0 and 180 degrees".into()
+                ),
+                (
+                    Some(6),
+                    "camera.x".into(),
+                    "0 is out of range, expected at least 1".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parallel_up_is_reported() {
+        let description = r#"
+camera:
+  origin: [-1.0, 0.0, 0.0]
+  forward: [0.0, 1.0, 0.0]
+  up: [0.0, -2.0, 0.0]
+  fov: 90.0
+  x: 8
+  y: 8
+"#;
+        assert_eq!(
+            validate_str(description),
+            vec![(
+                Some(5),
+                "camera.up".into(),
+                "parallel to the forward direction".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn color_lists_are_checked() {
+        let description = format!(
+            "{}{}",
+            CAMERA,
+            r#"
+background: [0.5, 0.6, 0.8]
+lights:
+  ambients:
+    - color: [0.1, 0.1]
+"#
+        );
+        assert_eq!(
+            validate_str(&description),
+            vec![(
+                Some(12),
+                "lights.ambients[0].color".into(),
+                "expected the r, g and b components".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn frames_are_checked() {
+        let description = format!("{}frames: [10, 1]\n", CAMERA);
+        assert_eq!(
+            validate_str(&description),
+            vec![(
+                Some(8),
+                "frames".into(),
+                "the first frame comes after the last one".into()
+            )]
+        );
+        let description = format!("{}frames: [1]\n", CAMERA);
+        assert_eq!(
+            validate_str(&description),
+            vec![(
+                Some(8),
+                "frames".into(),
+                "expected the first and last frames".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn physical_sky_is_checked() {
+        let description = format!(
+            "{}background: {{sun_direction: [1.0, 1.0, 0.0], turbidity: 20.0}}\n",
+            CAMERA
+        );
+        assert_eq!(
+            validate_str(&description),
+            vec![(
+                Some(8),
+                "background.turbidity".into(),
+                "20 is out of range, expected a turbidity between 2 and 10".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        let diagnostics = validate_str("camera: [1.0\nobjects: ]");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].0.is_some());
+    }
+
+    #[test]
+    fn display_works() {
+        let diagnostic = Diagnostic {
+            file: PathBuf::from("scene.yaml"),
+            line: Some(3),
+            path: "camera.fov".into(),
+            message: "missing field 'fov'".into(),
+        };
+        assert_eq!(
+            diagnostic.to_string(),
+            "scene.yaml:3: camera.fov: missing field 'fov'"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Reading and writing the entries of zip archives, either stored or deflated
+
+use crate::{Error, Result};
+use std::convert::TryInto;
+use std::io::Write;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// The date written for every entry: 1980-01-01, the earliest one the format can express.
+const DOS_DATE: u16 = 0x21;
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| Error::invalid_data("truncated zip archive"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| Error::invalid_data("truncated zip archive"))
+}
+
+/// The fields shared by the local and central headers of an entry.
+fn common_fields(method: u16, crc: u32, compressed: usize, size: usize, name: &str) -> Vec<u8> {
+    let mut fields = Vec::new();
+    fields.extend(&20u16.to_le_bytes()); // Version needed to extract
+    fields.extend(&0u16.to_le_bytes()); // Flags
+    fields.extend(&method.to_le_bytes());
+    fields.extend(&0u16.to_le_bytes()); // Modification time
+    fields.extend(&DOS_DATE.to_le_bytes());
+    fields.extend(&crc.to_le_bytes());
+    fields.extend(&(compressed as u32).to_le_bytes());
+    fields.extend(&(size as u32).to_le_bytes());
+    fields.extend(&(name.len() as u16).to_le_bytes());
+    fields.extend(&0u16.to_le_bytes()); // Extra field length
+    fields
+}
+
+/// Write each `(name, content)` entry as a deflated file into a zip archive.
+pub fn write_entries<W: Write>(mut writer: W, entries: &[(String, Vec<u8>)]) -> Result<()> {
+    let mut central = Vec::new();
+    let mut offset = 0;
+    for (name, content) in entries {
+        let crc = crc32fast::hash(content);
+        let compressed = miniz_oxide::deflate::compress_to_vec(content, 6);
+        // Already compressed files, such as images, may not shrink any further
+        let (method, data) = if compressed.len() < content.len() {
+            (DEFLATED, &compressed)
+        } else {
+            (STORED, content)
+        };
+        let fields = common_fields(method, crc, data.len(), content.len(), name);
+
+        let mut local = LOCAL_HEADER.to_le_bytes().to_vec();
+        local.extend(&fields);
+        local.extend(name.as_bytes());
+        writer.write_all(&local)?;
+        writer.write_all(data)?;
+
+        central.extend(&CENTRAL_HEADER.to_le_bytes());
+        central.extend(&20u16.to_le_bytes()); // Version made by
+        central.extend(&fields);
+        central.extend(&0u16.to_le_bytes()); // Comment length
+        central.extend(&0u16.to_le_bytes()); // Disk number
+        central.extend(&0u16.to_le_bytes()); // Internal attributes
+        central.extend(&0u32.to_le_bytes()); // External attributes
+        central.extend(&(offset as u32).to_le_bytes());
+        central.extend(name.as_bytes());
+        offset += local.len() + data.len();
+    }
+    if offset > u32::MAX as usize || entries.len() > u16::MAX as usize {
+        return Err(Error::invalid_data("too much data for a zip archive"));
+    }
+    let mut end = END_OF_CENTRAL_DIRECTORY.to_le_bytes().to_vec();
+    end.extend(&0u16.to_le_bytes()); // Disk number
+    end.extend(&0u16.to_le_bytes()); // Disk of the central directory
+    end.extend(&(entries.len() as u16).to_le_bytes());
+    end.extend(&(entries.len() as u16).to_le_bytes());
+    end.extend(&(central.len() as u32).to_le_bytes());
+    end.extend(&(offset as u32).to_le_bytes());
+    end.extend(&0u16.to_le_bytes()); // Comment length
+    writer.write_all(&central)?;
+    Ok(writer.write_all(&end)?)
+}
+
+/// Read the `(name, content)` of each file in a zip archive.
+pub fn read_entries(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    // The end of the central directory is followed by a comment of at most 64KiB
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .take(u16::MAX as usize + 1)
+        .find(|&offset| u32_at(data, offset).ok() == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(|| Error::invalid_data("not a zip archive"))?;
+    let count = u16_at(data, end + 10)?;
+    let mut offset = u32_at(data, end + 16)? as usize;
+
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        if u32_at(data, offset)? != CENTRAL_HEADER {
+            return Err(Error::invalid_data("invalid zip central directory"));
+        }
+        let (flags, method) = (u16_at(data, offset + 8)?, u16_at(data, offset + 10)?);
+        let crc = u32_at(data, offset + 16)?;
+        let compressed = u32_at(data, offset + 20)? as usize;
+        let name_len = u16_at(data, offset + 28)? as usize;
+        let extra_len = u16_at(data, offset + 30)? as usize;
+        let comment_len = u16_at(data, offset + 32)? as usize;
+        let local = u32_at(data, offset + 42)? as usize;
+        let name = data
+            .get(offset + 46..offset + 46 + name_len)
+            .ok_or_else(|| Error::invalid_data("truncated zip archive"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        offset += 46 + name_len + extra_len + comment_len;
+
+        if name.ends_with('/') {
+            continue;
+        }
+        if flags & 1 != 0 {
+            return Err(Error::invalid_data(
+                "encrypted zip entries are not supported",
+            ));
+        }
+        if u32_at(data, local)? != LOCAL_HEADER {
+            return Err(Error::invalid_data("invalid zip entry header"));
+        }
+        let start =
+            local + 30 + u16_at(data, local + 26)? as usize + u16_at(data, local + 28)? as usize;
+        let raw = data
+            .get(start..start + compressed)
+            .ok_or_else(|| Error::invalid_data("truncated zip archive"))?;
+        let content = match method {
+            STORED => raw.to_vec(),
+            DEFLATED => miniz_oxide::inflate::decompress_to_vec(raw)
+                .map_err(|_| Error::invalid_data("invalid deflated zip entry"))?,
+            _ => return Err(Error::invalid_data("unsupported zip compression method")),
+        };
+        if crc32fast::hash(&content) != crc {
+            return Err(Error::invalid_data("zip entry checksum mismatch"));
+        }
+        entries.push((name, content));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip_works() {
+        let entries = vec![
+            ("scene.yaml".to_string(), b"camera: {}".to_vec()),
+            // Compressible enough to be deflated
+            ("assets/0.obj".to_string(), b"v 0 0 0\n".repeat(100)),
+            ("empty".to_string(), vec![]),
+        ];
+        let mut archive = Vec::new();
+        write_entries(&mut archive, &entries).unwrap();
+        assert_eq!(read_entries(&archive).unwrap(), entries);
+    }
+
+    #[test]
+    fn corrupted_content_fails() {
+        let entries = vec![("file".to_string(), vec![1, 2, 3])];
+        let mut archive = Vec::new();
+        write_entries(&mut archive, &entries).unwrap();
+        // The stored content directly follows the 30 bytes header and the name
+        archive[30 + 4] = 42;
+        assert!(read_entries(&archive).is_err());
+        assert!(read_entries(b"not a zip").is_err());
+    }
+}
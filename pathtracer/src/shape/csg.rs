@@ -0,0 +1,373 @@
+use super::{Hit, Shape, ShapeEnum, Span};
+use crate::{Float, Point, Point2D, Vector};
+use beevee::aabb::AABB;
+use beevee::ray::Ray;
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// The boolean operation used to combine the children of a [`Csg`] node.
+///
+/// [`Csg`]: struct.Csg.html
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CsgOperation {
+    /// Keep every point which is inside either child.
+    Union,
+    /// Keep only the points which are inside both children.
+    Intersection,
+    /// Keep the points of the left child which are not inside the right child.
+    Difference,
+}
+
+/// Represent the combination of two shapes using constructive solid geometry.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct Csg {
+    operation: CsgOperation,
+    left: Box<ShapeEnum>,
+    right: Box<ShapeEnum>,
+}
+
+/// The distance used when probing a child's surface to find which one owns a point.
+const PROBE_DISTANCE: Float = 1e-3;
+
+impl Csg {
+    /// Creates a new `Csg` node from two [`ShapeEnum`]s.
+    ///
+    /// [`ShapeEnum`]: enum.ShapeEnum.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::{Csg, CsgOperation, Sphere};
+    /// # use pathtracer::Point;
+    /// #
+    /// // A sphere with a bite taken out of it
+    /// let csg = Csg::new(
+    ///     CsgOperation::Difference,
+    ///     Sphere::new(Point::origin(), 1.0).into(),
+    ///     Sphere::new(Point::new(1.0, 0.0, 0.0), 0.5).into(),
+    /// );
+    /// ```
+    pub fn new(operation: CsgOperation, left: ShapeEnum, right: ShapeEnum) -> Self {
+        Csg {
+            operation,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    /// Return the spans of the combination of both children's spans.
+    fn combine(&self, left: &[Span], right: &[Span]) -> Vec<Span> {
+        match self.operation {
+            CsgOperation::Union => union(left, right),
+            CsgOperation::Intersection => intersection(left, right),
+            CsgOperation::Difference => intersection(left, &complement(right)),
+        }
+    }
+
+    /// Return the child whose surface the point is on, and whether its normal must be flipped.
+    fn surface_owner(&self, point: &Point) -> (&ShapeEnum, bool) {
+        if surface_residual(&self.left, point) <= surface_residual(&self.right, point) {
+            (&self.left, false)
+        } else {
+            (&self.right, self.operation == CsgOperation::Difference)
+        }
+    }
+}
+
+/// Return how far the point is from lying on the shape's surface, by casting short rays through
+/// it along the shape's normal.
+fn surface_residual(shape: &ShapeEnum, point: &Point) -> Float {
+    let normal = shape.normal(point);
+    let probe = |direction: Unit<Vector>| {
+        let origin = point - direction.as_ref() * PROBE_DISTANCE;
+        shape
+            .intersect(&Ray::new(origin, direction))
+            .map_or(Float::INFINITY, |hit| (hit.distance - PROBE_DISTANCE).abs())
+    };
+    probe(normal).min(probe(-normal))
+}
+
+/// Merge two sorted lists of spans into the spans covered by either of them.
+fn union(lhs: &[Span], rhs: &[Span]) -> Vec<Span> {
+    let mut all: Vec<_> = lhs.iter().chain(rhs.iter()).cloned().collect();
+    all.sort_by(|a, b| a.enter.distance.total_cmp(&b.enter.distance));
+    let mut ans: Vec<Span> = Vec::with_capacity(all.len());
+    for span in all {
+        match ans.last_mut() {
+            Some(last) if span.enter.distance <= last.exit.distance => {
+                if span.exit.distance > last.exit.distance {
+                    last.exit = span.exit
+                }
+            }
+            _ => ans.push(span),
+        }
+    }
+    ans
+}
+
+/// Return the spans covered by both sorted lists of spans.
+fn intersection(lhs: &[Span], rhs: &[Span]) -> Vec<Span> {
+    let mut ans = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < lhs.len() && j < rhs.len() {
+        let (left, right) = (&lhs[i], &rhs[j]);
+        let enter = if left.enter.distance >= right.enter.distance {
+            &left.enter
+        } else {
+            &right.enter
+        };
+        let exit = if left.exit.distance <= right.exit.distance {
+            &left.exit
+        } else {
+            &right.exit
+        };
+        if enter.distance <= exit.distance {
+            ans.push(Span::new(enter.clone(), exit.clone()))
+        }
+        // Advance past the span which ends first
+        if left.exit.distance < right.exit.distance {
+            i += 1
+        } else {
+            j += 1
+        }
+    }
+    ans
+}
+
+/// Return the spans which are not covered by the sorted list of spans, whose boundaries are seen
+/// from the other side.
+fn complement(spans: &[Span]) -> Vec<Span> {
+    let mut ans = Vec::with_capacity(spans.len() + 1);
+    let mut start = Hit::unbounded(Float::NEG_INFINITY);
+    for span in spans {
+        if start.distance < span.enter.distance {
+            ans.push(Span::new(start, span.enter.clone().flipped()))
+        }
+        start = span.exit.clone().flipped();
+    }
+    if start.distance < Float::INFINITY {
+        ans.push(Span::new(start, Hit::unbounded(Float::INFINITY)))
+    }
+    ans
+}
+
+impl Shape for Csg {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        // The boundaries of the combination are the children's hits, keeping their normals
+        self.spans(ray)
+            .into_iter()
+            .flat_map(|span| vec![span.enter, span.exit])
+            .find(|hit| hit.distance >= 0. && hit.distance.is_finite())
+    }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        self.combine(&self.left.spans(ray), &self.right.spans(ray))
+    }
+
+    fn normal(&self, point: &Point) -> Unit<Vector> {
+        let (owner, flipped) = self.surface_owner(point);
+        let normal = owner.normal(point);
+        if flipped {
+            -normal
+        } else {
+            normal
+        }
+    }
+
+    fn project_texel(&self, point: &Point) -> Point2D {
+        self.surface_owner(point).0.project_texel(point)
+    }
+
+    fn aabb(&self) -> AABB {
+        let left = self.left.aabb();
+        match self.operation {
+            CsgOperation::Union => left.union(&self.right.aabb()),
+            CsgOperation::Intersection => {
+                let right = self.right.aabb();
+                let low = Point::new(
+                    left.low.x.max(right.low.x),
+                    left.low.y.max(right.low.y),
+                    left.low.z.max(right.low.z),
+                );
+                let high = Point::new(
+                    left.high.x.min(right.high.x),
+                    left.high.y.min(right.high.y),
+                    left.high.z.min(right.high.z),
+                );
+                // Disjoint children do not intersect anywhere
+                if low.x > high.x || low.y > high.y || low.z > high.z {
+                    AABB::empty()
+                } else {
+                    AABB::with_bounds(low, high)
+                }
+            }
+            CsgOperation::Difference => left,
+        }
+    }
+
+    fn centroid(&self) -> Point {
+        match self.operation {
+            CsgOperation::Difference => self.left.centroid(),
+            _ => self.aabb().centroid(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shape::{Sphere, Triangle};
+
+    fn ray_along_x() -> Ray {
+        Ray::new(Point::new(-5., 0., 0.), Vector::x_axis())
+    }
+
+    fn two_spheres(operation: CsgOperation) -> Csg {
+        Csg::new(
+            operation,
+            Sphere::new(Point::new(-0.5, 0., 0.), 1.).into(),
+            Sphere::new(Point::new(0.5, 0., 0.), 1.).into(),
+        )
+    }
+
+    fn distances(csg: &Csg, ray: &Ray) -> Vec<(Float, Float)> {
+        csg.spans(ray).iter().map(Span::distances).collect()
+    }
+
+    #[test]
+    fn union_works() {
+        let csg = two_spheres(CsgOperation::Union);
+        assert_eq!(csg.intersect(&ray_along_x()).unwrap().distance, 3.5);
+        assert_eq!(distances(&csg, &ray_along_x()), vec![(3.5, 6.5)]);
+    }
+
+    #[test]
+    fn intersection_works() {
+        let csg = two_spheres(CsgOperation::Intersection);
+        assert_eq!(csg.intersect(&ray_along_x()).unwrap().distance, 4.5);
+        assert_eq!(distances(&csg, &ray_along_x()), vec![(4.5, 5.5)]);
+    }
+
+    #[test]
+    fn difference_works() {
+        let csg = two_spheres(CsgOperation::Difference);
+        assert_eq!(csg.intersect(&ray_along_x()).unwrap().distance, 3.5);
+        assert_eq!(distances(&csg, &ray_along_x()), vec![(3.5, 4.5)]);
+    }
+
+    #[test]
+    fn difference_can_be_seen_through() {
+        // A sphere with a thin slab around the XY plane carved out of it
+        let csg = Csg::new(
+            CsgOperation::Difference,
+            Sphere::new(Point::origin(), 1.).into(),
+            Csg::new(
+                CsgOperation::Intersection,
+                Sphere::new(Point::new(0., 0., 10.), 10.1).into(),
+                Sphere::new(Point::new(0., 0., -10.), 10.1).into(),
+            )
+            .into(),
+        );
+        let ray = Ray::new(Point::new(0., -5., 0.), Vector::y_axis());
+        assert_eq!(csg.intersect(&ray), None);
+    }
+
+    #[test]
+    fn intersect_from_inside_works() {
+        let csg = two_spheres(CsgOperation::Union);
+        let ray = Ray::new(Point::origin(), Vector::x_axis());
+        assert_eq!(csg.intersect(&ray).unwrap().distance, 1.5);
+    }
+
+    #[test]
+    fn normal_works() {
+        let csg = two_spheres(CsgOperation::Union);
+        assert_eq!(
+            csg.normal(&Point::new(-1.5, 0., 0.)),
+            Unit::new_normalize(Vector::new(-1., 0., 0.))
+        );
+        assert_eq!(
+            csg.normal(&Point::new(1.5, 0., 0.)),
+            Unit::new_normalize(Vector::new(1., 0., 0.))
+        );
+    }
+
+    #[test]
+    fn difference_normal_is_flipped() {
+        let csg = two_spheres(CsgOperation::Difference);
+        // The carved-out surface faces towards the removed sphere's center
+        assert_eq!(
+            csg.normal(&Point::new(-0.5, 0., 0.)),
+            Unit::new_normalize(Vector::new(1., 0., 0.))
+        );
+    }
+
+    #[test]
+    fn intersect_hit_normal_works() {
+        let csg = two_spheres(CsgOperation::Difference);
+        let ray = Ray::new(Point::new(-1., 0., 0.), Vector::x_axis());
+        // The ray exits the shape through the carved-out surface, which faces the removed sphere
+        let hit = csg.intersect(&ray).unwrap();
+        assert_eq!(hit.distance, 0.5);
+        assert_eq!(hit.normal, Unit::new_normalize(Vector::new(1., 0., 0.)));
+    }
+
+    #[test]
+    fn intersect_keeps_the_child_hit() {
+        // A smoothly shaded triangle in front of a sphere, facing the ray
+        let triangle = Triangle::new(
+            Point::new(-3., -1., -1.),
+            Point::new(-3., -1., 1.),
+            Point::new(-3., 1., 0.),
+        )
+        .with_normals([
+            Unit::new_normalize(Vector::new(-1., 0.5, 0.)),
+            Unit::new_normalize(Vector::new(-1., 0., 0.5)),
+            Unit::new_normalize(Vector::new(-1., -0.5, -0.5)),
+        ]);
+        let expected = triangle.intersect(&ray_along_x()).unwrap();
+        let csg = Csg::new(
+            CsgOperation::Union,
+            triangle.into(),
+            Sphere::new(Point::origin(), 1.).into(),
+        );
+        assert_eq!(csg.intersect(&ray_along_x()), Some(expected));
+    }
+
+    #[test]
+    fn aabb_works() {
+        let union = two_spheres(CsgOperation::Union);
+        assert_eq!(
+            union.aabb(),
+            AABB::with_bounds(Point::new(-1.5, -1., -1.), Point::new(1.5, 1., 1.))
+        );
+        let intersection = two_spheres(CsgOperation::Intersection);
+        assert_eq!(
+            intersection.aabb(),
+            AABB::with_bounds(Point::new(-0.5, -1., -1.), Point::new(0.5, 1., 1.))
+        );
+        let difference = two_spheres(CsgOperation::Difference);
+        assert_eq!(
+            difference.aabb(),
+            AABB::with_bounds(Point::new(-1.5, -1., -1.), Point::new(0.5, 1., 1.))
+        );
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            operation: difference
+            left:
+              type: sphere
+              center: [-0.5, 0.0, 0.0]
+              radius: 1.0
+            right:
+              type: sphere
+              center: [0.5, 0.0, 0.0]
+              radius: 1.0
+        "#;
+        let csg: Csg = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(csg, two_spheres(CsgOperation::Difference))
+    }
+}
@@ -0,0 +1,194 @@
+use super::{Hit, Shape};
+use crate::core::sampling;
+use crate::{Float, Point, Point2D, Vector};
+use beevee::aabb::AABB;
+use beevee::ray::Ray;
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// Represent a flat disk inside the scene, e.g: a splat of a point cloud.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Disk {
+    /// The center of the disk.
+    center: Point,
+    /// The normal of the disk.
+    #[serde(deserialize_with = "crate::serialize::vector_normalizer")]
+    normal: Unit<Vector>,
+    /// The radius of the disk.
+    radius: Float,
+}
+
+impl Disk {
+    /// Creates a new `Disk` centered on `center`, facing towards `normal`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::Disk;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let splat = Disk::new(Point::origin(), Vector::new(0.0, 1.0, 0.0), 0.5);
+    /// ```
+    pub fn new(center: Point, normal: Vector, radius: Float) -> Self {
+        Disk {
+            center,
+            normal: Unit::new_normalize(normal),
+            radius,
+        }
+    }
+
+    /// Get the center of the `Disk`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::Disk;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let splat = Disk::new(Point::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), 0.5);
+    /// assert_eq!(splat.center(), &Point::new(1.0, 0.0, 0.0));
+    /// ```
+    pub fn center(&self) -> &Point {
+        &self.center
+    }
+
+    /// Get the radius of the `Disk`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::Disk;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let splat = Disk::new(Point::origin(), Vector::new(0.0, 1.0, 0.0), 0.5);
+    /// assert_eq!(splat.radius(), 0.5);
+    /// ```
+    pub fn radius(&self) -> Float {
+        self.radius
+    }
+
+    /// Return two unit vectors spanning the disk's plane.
+    fn tangents(&self) -> (Vector, Vector) {
+        sampling::orthonormal_basis(&self.normal)
+    }
+}
+
+impl Shape for Disk {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let cos = ray.direction.dot(&self.normal);
+        if cos.abs() < 1e-8 {
+            return None;
+        }
+        let distance = (self.center - ray.origin).dot(&self.normal) / cos;
+        if distance < 0. {
+            return None;
+        }
+        let point = ray.origin + ray.direction.as_ref() * distance;
+        if (point - self.center).norm_squared() > self.radius * self.radius {
+            return None;
+        }
+        Some(Hit::new(distance, self.normal, self.project_texel(&point)))
+    }
+
+    fn normal(&self, _: &Point) -> Unit<Vector> {
+        self.normal
+    }
+
+    fn project_texel(&self, point: &Point) -> Point2D {
+        // The square enclosing the disk is mapped to the whole texture
+        let (u, v) = self.tangents();
+        let delt = (point - self.center) / (2. * self.radius);
+        Point2D::new(0.5 + delt.dot(&u), 0.5 + delt.dot(&v))
+    }
+
+    fn aabb(&self) -> AABB {
+        // The disk's extent along an axis shrinks as its normal gets closer to it
+        let extent = self
+            .normal
+            .map(|n| self.radius * (1. - n * n).max(0.).sqrt());
+        AABB::with_bounds(self.center - extent, self.center + extent)
+    }
+
+    fn centroid(&self) -> Point {
+        self.center
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn simple_disk() -> Disk {
+        Disk::new(Point::origin(), Vector::new(0., 2., 0.), 1.)
+    }
+
+    #[test]
+    fn new_works() {
+        assert_eq!(
+            simple_disk(),
+            Disk {
+                center: Point::origin(),
+                normal: Vector::y_axis(),
+                radius: 1.,
+            }
+        )
+    }
+
+    #[test]
+    fn intersect_works() {
+        let disk = simple_disk();
+        let ray = Ray::new(
+            Point::new(0.5, 2., -0.5),
+            Unit::new_normalize(Vector::new(0., -1., 0.)),
+        );
+        let hit = disk.intersect(&ray).unwrap();
+        assert_eq!(hit.distance, 2.);
+        assert_eq!(hit.normal, Vector::y_axis());
+        // Seen from below
+        let ray = Ray::new(Point::new(0., -1., 0.), Vector::y_axis());
+        assert_eq!(disk.intersect(&ray).map(|hit| hit.distance), Some(1.));
+    }
+
+    #[test]
+    fn intersect_misses_outside_of_the_radius() {
+        let disk = simple_disk();
+        let ray = Ray::new(
+            Point::new(0.8, 1., 0.8),
+            Unit::new_normalize(Vector::new(0., -1., 0.)),
+        );
+        assert!(disk.intersect(&ray).is_none());
+        let ray = Ray::new(Point::new(0., 1., 0.), Vector::x_axis());
+        assert!(disk.intersect(&ray).is_none());
+    }
+
+    #[test]
+    fn project_texel_works() {
+        let disk = simple_disk();
+        assert_eq!(disk.project_texel(&Point::origin()), Point2D::new(0.5, 0.5));
+        let texel = disk.project_texel(&Point::new(0.5, 0., -0.5));
+        assert!(texel.x >= 0. && texel.x <= 1.);
+        assert!(texel.y >= 0. && texel.y <= 1.);
+    }
+
+    #[test]
+    fn aabb_works() {
+        let aabb = simple_disk().aabb();
+        assert_eq!(aabb.low, Point::new(-1., 0., -1.));
+        assert_eq!(aabb.high, Point::new(1., 0., 1.));
+        let tilted = Disk::new(Point::origin(), Vector::new(1., 1., 0.), 1.);
+        let aabb = tilted.aabb();
+        let extent = Float::sqrt(0.5);
+        assert!((aabb.high - Point::new(extent, extent, 1.)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            center: [0.0, 0.0, 0.0]
+            normal: [0.0, 2.0, 0.0]
+            radius: 1.0
+        "#;
+        let disk: Disk = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(disk, simple_disk())
+    }
+}
@@ -0,0 +1,474 @@
+use super::{bounds_interval, Hit, Shape};
+use crate::{Error, Float, Point, Point2D, Result, Vector};
+use beevee::aabb::AABB;
+use beevee::ray::Ray;
+use image::{DynamicImage, GenericImageView};
+use nalgebra::Unit;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The leeway added around the terrain's bounding box, to hit its flat parts from any side.
+const BOUNDS_EPSILON: Float = 1e-4;
+
+/// Represent a terrain whose heights are read from a grayscale image, its pixels being the
+/// vertices of a grid of triangles, which is traversed cell by cell along the rays instead of
+/// being split into as many shapes.
+///
+/// The terrain spans the XZ-plane from its corner, black pixels being at the corner's height and
+/// white ones rising up to the size's height. Seen from above, the top of the image is towards
+/// `-Z`, its texel coordinates matching those of an [`ImageTexture`] laid over it.
+///
+/// [`ImageTexture`]: ../texture/struct.ImageTexture.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heightfield {
+    /// The corner of the terrain with the lowest coordinates.
+    corner: Point,
+    /// The extent of the terrain along the X and Z axes, and the height of white pixels.
+    size: Vector,
+    /// The number of vertices along the X axis.
+    columns: usize,
+    /// The number of vertices along the Z axis.
+    rows: usize,
+    /// The height of each vertex in the scene, row by row from `-Z`.
+    heights: Arc<Vec<Float>>,
+    /// The smooth normal of each vertex, in the same order.
+    normals: Arc<Vec<Unit<Vector>>>,
+    /// The bounds of the terrain.
+    aabb: AABB,
+    /// The file the image was read from, if any.
+    file: Option<PathBuf>,
+}
+
+impl Heightfield {
+    /// Creates a new `Heightfield` from the luminance of an image, which must be at least 2
+    /// pixels wide and high.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use image::{DynamicImage, GrayImage, Luma};
+    /// # use pathtracer::shape::{Heightfield, Shape};
+    /// # use pathtracer::{Point, Vector};
+    /// # use beevee::ray::Ray;
+    /// #
+    /// let image = DynamicImage::ImageLuma8(GrayImage::from_pixel(4, 4, Luma([255])));
+    /// let terrain =
+    ///     Heightfield::new(&image, Point::origin(), Vector::new(10.0, 2.0, 10.0)).unwrap();
+    ///
+    /// let ray = Ray::new(Point::new(5.0, 10.0, 5.0), -Vector::y_axis());
+    /// let hit = terrain.intersect(&ray).unwrap();
+    /// assert!((hit.distance - 8.0).abs() < 1e-6);
+    /// assert_eq!(hit.normal, Vector::y_axis());
+    /// ```
+    pub fn new(image: &DynamicImage, corner: Point, size: Vector) -> Result<Self> {
+        let (columns, rows) = (image.width() as usize, image.height() as usize);
+        if columns < 2 || rows < 2 {
+            return Err(Error::InvalidParameter(format!(
+                "heightfield images must be at least 2x2 pixels, not {}x{}",
+                columns, rows
+            )));
+        }
+        let heights: Vec<Float> = luminance(image)
+            .into_iter()
+            .map(|value| corner.y + value * size.y)
+            .collect();
+        let (low, high) = heights
+            .iter()
+            .fold((Float::INFINITY, Float::NEG_INFINITY), |acc, h| {
+                (acc.0.min(*h), acc.1.max(*h))
+            });
+        let aabb = AABB::with_bounds(
+            Point::new(corner.x, low - BOUNDS_EPSILON, corner.z),
+            Point::new(corner.x + size.x, high + BOUNDS_EPSILON, corner.z + size.z),
+        );
+        let mut heightfield = Heightfield {
+            corner,
+            size,
+            columns,
+            rows,
+            heights: Arc::new(heights),
+            normals: Arc::new(Vec::new()),
+            aabb,
+            file: None,
+        };
+        heightfield.normals = Arc::new(heightfield.vertex_normals());
+        Ok(heightfield)
+    }
+
+    /// Creates a new `Heightfield` from the image at `path`, remembering it to write the shape
+    /// back into a scene description.
+    pub fn open<P: AsRef<Path>>(path: P, corner: Point, size: Vector) -> Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path).map_err(|err| Error::from(err).with_path(path))?;
+        Ok(Heightfield {
+            file: Some(path.to_path_buf()),
+            ..Heightfield::new(&image, corner, size).map_err(|err| err.with_path(path))?
+        })
+    }
+
+    /// Get the file the image was read from, if any.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Get the distance between two vertices along the X and Z axes.
+    fn spacing(&self) -> (Float, Float) {
+        (
+            self.size.x / (self.columns - 1) as Float,
+            self.size.z / (self.rows - 1) as Float,
+        )
+    }
+
+    /// Get the height of the vertex at the given column and row.
+    fn height(&self, column: usize, row: usize) -> Float {
+        self.heights[row * self.columns + column]
+    }
+
+    /// Get the position of the vertex at the given column and row.
+    fn vertex(&self, column: usize, row: usize) -> Point {
+        let (dx, dz) = self.spacing();
+        Point::new(
+            self.corner.x + column as Float * dx,
+            self.height(column, row),
+            self.corner.z + row as Float * dz,
+        )
+    }
+
+    /// Compute the normal of each vertex from the slope of the terrain around it.
+    fn vertex_normals(&self) -> Vec<Unit<Vector>> {
+        let (dx, dz) = self.spacing();
+        let mut normals = Vec::with_capacity(self.heights.len());
+        for row in 0..self.rows {
+            let (up, down) = (row.saturating_sub(1), (row + 1).min(self.rows - 1));
+            for column in 0..self.columns {
+                let (left, right) = (column.saturating_sub(1), (column + 1).min(self.columns - 1));
+                let slope_x = (self.height(right, row) - self.height(left, row))
+                    / ((right - left) as Float * dx);
+                let slope_z = (self.height(column, down) - self.height(column, up))
+                    / ((down - up) as Float * dz);
+                normals.push(Unit::new_normalize(Vector::new(-slope_x, 1., -slope_z)));
+            }
+        }
+        normals
+    }
+
+    /// Get the cell containing the given coordinates on the XZ-plane, clamped to the grid, along
+    /// with the position inside of that cell, between 0 and 1 along both axes.
+    fn cell(&self, x: Float, z: Float) -> ((usize, usize), (Float, Float)) {
+        let (dx, dz) = self.spacing();
+        let locate = |offset: Float, spacing: Float, count: usize| {
+            let position = offset / spacing;
+            let index = (position.floor().max(0.) as usize).min(count - 2);
+            (index, (position - index as Float).clamp(0., 1.))
+        };
+        let (column, fx) = locate(x - self.corner.x, dx, self.columns);
+        let (row, fz) = locate(z - self.corner.z, dz, self.rows);
+        ((column, row), (fx, fz))
+    }
+
+    /// Return the distance to the closest intersection with the two triangles of a cell, the
+    /// ray's part crossing the cell being given to skip the cells it passes above or below.
+    fn intersect_cell(
+        &self,
+        ray: &Ray,
+        cell: (usize, usize),
+        enter: Float,
+        exit: Float,
+    ) -> Option<Float> {
+        let (column, row) = cell;
+        let corners = [
+            self.vertex(column, row),
+            self.vertex(column + 1, row),
+            self.vertex(column + 1, row + 1),
+            self.vertex(column, row + 1),
+        ];
+        let (low, high) = corners
+            .iter()
+            .fold((Float::INFINITY, Float::NEG_INFINITY), |acc, c| {
+                (acc.0.min(c.y), acc.1.max(c.y))
+            });
+        let (y_enter, y_exit) = (
+            ray.origin.y + ray.direction.y * enter,
+            ray.origin.y + ray.direction.y * exit,
+        );
+        if y_enter.min(y_exit) > high + BOUNDS_EPSILON || y_enter.max(y_exit) < low - BOUNDS_EPSILON
+        {
+            return None;
+        }
+        // The cell is split along its diagonal from its first corner to its third one
+        [[0, 1, 2], [0, 2, 3]]
+            .iter()
+            .filter_map(|t| {
+                intersect_triangle(ray, [&corners[t[0]], &corners[t[1]], &corners[t[2]]])
+            })
+            .fold(None, |closest: Option<Float>, t| {
+                Some(closest.map_or(t, |c| c.min(t)))
+            })
+    }
+}
+
+/// Get the luminance of each pixel of an image, between 0 and 1, keeping the precision of 16-bit
+/// images to avoid terracing the terrain.
+fn luminance(image: &DynamicImage) -> Vec<Float> {
+    let color = image.color();
+    if color.bytes_per_pixel() > color.channel_count() {
+        let max = u16::MAX as Float;
+        (image.to_luma16().pixels())
+            .map(|p| p[0] as Float / max)
+            .collect()
+    } else {
+        let max = u8::MAX as Float;
+        (image.to_luma8().pixels())
+            .map(|p| p[0] as Float / max)
+            .collect()
+    }
+}
+
+/// Return the distance to the intersection of the ray with a triangle, if it is in front of it.
+fn intersect_triangle(ray: &Ray, corners: [&Point; 3]) -> Option<Float> {
+    let edge1 = corners[1] - corners[0];
+    let edge2 = corners[2] - corners[0];
+    let pvec = ray.direction.cross(&edge2);
+    let det = edge1.dot(&pvec);
+    if det.abs() < 1e-8 {
+        return None;
+    }
+    let inv_det = 1. / det;
+    let to_ray = ray.origin - corners[0];
+    let u = to_ray.dot(&pvec) * inv_det;
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+    let qvec = to_ray.cross(&edge1);
+    let v = ray.direction.dot(&qvec) * inv_det;
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+    let t = edge2.dot(&qvec) * inv_det;
+    if t < 0. {
+        None
+    } else {
+        Some(t)
+    }
+}
+
+impl Shape for Heightfield {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let (enter, exit) = bounds_interval(&self.aabb, ray)?;
+        let (enter, exit) = (enter.max(0.), exit);
+        if enter > exit {
+            return None;
+        }
+
+        // Walk through the cells crossed by the ray, in order, using a 2D DDA
+        let (dx, dz) = self.spacing();
+        let start = ray.origin + ray.direction.as_ref() * enter;
+        let ((mut column, mut row), _) = self.cell(start.x, start.z);
+        // The step between cells along each axis, the distance along the ray between crossings of
+        // the cells' borders, and the distance to the next crossing
+        let axis =
+            |direction: Float, origin: Float, corner: Float, index: usize, spacing: Float| {
+                if direction == 0. {
+                    return (0, Float::INFINITY, Float::INFINITY);
+                }
+                let step = if direction > 0. { 1 } else { -1 };
+                let border = corner + (index + (direction > 0.) as usize) as Float * spacing;
+                let delta = spacing / direction.abs();
+                (step, delta, (border - origin) / direction)
+            };
+        let (step_x, delta_x, mut next_x) =
+            axis(ray.direction.x, ray.origin.x, self.corner.x, column, dx);
+        let (step_z, delta_z, mut next_z) =
+            axis(ray.direction.z, ray.origin.z, self.corner.z, row, dz);
+
+        let mut cell_enter = enter;
+        loop {
+            let cell_exit = next_x.min(next_z).min(exit);
+            if let Some(t) = self.intersect_cell(ray, (column, row), cell_enter, cell_exit) {
+                let point = ray.origin + ray.direction.as_ref() * t;
+                return Some(Hit::new(t, self.normal(&point), self.project_texel(&point)));
+            }
+            if cell_exit >= exit {
+                return None;
+            }
+            let (index, step, count) = if next_x < next_z {
+                next_x += delta_x;
+                (&mut column, step_x, self.columns)
+            } else {
+                next_z += delta_z;
+                (&mut row, step_z, self.rows)
+            };
+            match index.checked_add_signed(step) {
+                Some(next) if next < count - 1 => *index = next,
+                _ => return None,
+            }
+            cell_enter = cell_exit;
+        }
+    }
+
+    fn normal(&self, point: &Point) -> Unit<Vector> {
+        let ((column, row), (fx, fz)) = self.cell(point.x, point.z);
+        let normal = |c: usize, r: usize| self.normals[r * self.columns + c].into_inner();
+        // Interpolate the vertices' normals over the triangle of the cell containing the point
+        let interpolated = if fx >= fz {
+            normal(column, row) * (1. - fx)
+                + normal(column + 1, row) * (fx - fz)
+                + normal(column + 1, row + 1) * fz
+        } else {
+            normal(column, row) * (1. - fz)
+                + normal(column + 1, row + 1) * fx
+                + normal(column, row + 1) * (fz - fx)
+        };
+        Unit::new_normalize(interpolated)
+    }
+
+    fn project_texel(&self, point: &Point) -> Point2D {
+        Point2D::new(
+            (point.x - self.corner.x) / self.size.x,
+            1. - (point.z - self.corner.z) / self.size.z,
+        )
+    }
+
+    fn aabb(&self) -> AABB {
+        self.aabb
+    }
+
+    fn centroid(&self) -> Point {
+        self.aabb.centroid()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SerializedHeightfield {
+    file: PathBuf,
+    corner: Point,
+    size: Vector,
+}
+
+impl<'de> Deserialize<'de> for Heightfield {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let heightfield: SerializedHeightfield = Deserialize::deserialize(deserializer)?;
+        Heightfield::open(&heightfield.file, heightfield.corner, heightfield.size)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Only heightfields read from a file can be serialized, referring to that file.
+impl Serialize for Heightfield {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::Error;
+
+        let file = (self.file.clone())
+            .ok_or_else(|| S::Error::custom("heightfield was not read from a file"))?;
+        SerializedHeightfield {
+            file,
+            corner: self.corner,
+            size: self.size,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    /// A 3x3 pyramid, rising from a flat border to a single peak in its middle.
+    fn pyramid() -> Heightfield {
+        let image = GrayImage::from_fn(3, 3, |x, y| Luma([if (x, y) == (1, 1) { 255 } else { 0 }]));
+        Heightfield::new(
+            &DynamicImage::ImageLuma8(image),
+            Point::origin(),
+            Vector::new(2., 1., 2.),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn small_images_are_rejected() {
+        let image = DynamicImage::ImageLuma8(GrayImage::new(1, 4));
+        assert!(Heightfield::new(&image, Point::origin(), Vector::repeat(1.)).is_err())
+    }
+
+    #[test]
+    fn aabb_works() {
+        let aabb = pyramid().aabb();
+        assert_eq!(aabb.low.x, 0.);
+        assert_eq!(aabb.high.z, 2.);
+        assert!((aabb.high.y - 1.).abs() < 1e-3);
+        assert!(aabb.low.y.abs() < 1e-3);
+    }
+
+    #[test]
+    fn intersect_peak_works() {
+        let ray = Ray::new(Point::new(1., 5., 1.), -Vector::y_axis());
+        let hit = pyramid().intersect(&ray).unwrap();
+        assert!((hit.distance - 4.).abs() < 1e-5);
+        assert!((hit.normal.into_inner() - Vector::y()).norm() < 1e-5);
+        assert!((hit.uv - Point2D::new(0.5, 0.5)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn intersect_slope_works() {
+        // Halfway up the slope towards -X, which rises by 1 over a distance of 1
+        let ray = Ray::new(Point::new(0.5, 5., 1.), -Vector::y_axis());
+        let hit = pyramid().intersect(&ray).unwrap();
+        assert!((hit.distance - 4.5).abs() < 1e-5);
+        assert!(hit.normal.x < 0. && hit.normal.y > 0.);
+    }
+
+    #[test]
+    fn intersect_across_cells_works() {
+        // Skimming over the terrain from -X, only hitting the peak's slope in the last cells
+        let ray = Ray::new(
+            Point::new(-1., 0.75, 0.9),
+            Unit::new_normalize(Vector::new(1., 0., 0.)),
+        );
+        let hit = pyramid().intersect(&ray).unwrap();
+        assert!((hit.distance - 1.75).abs() < 1e-5);
+        // Going past the peak's height
+        let ray = Ray::new(Point::new(-1., 1.5, 0.9), Vector::x_axis());
+        assert_eq!(pyramid().intersect(&ray), None);
+    }
+
+    #[test]
+    fn intersect_from_below_works() {
+        let ray = Ray::new(Point::new(1.25, -1., 0.5), Vector::y_axis());
+        let hit = pyramid().intersect(&ray).unwrap();
+        assert!((hit.distance - 1.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn intersect_behind_works() {
+        let ray = Ray::new(Point::new(1., 5., 1.), Vector::y_axis());
+        assert_eq!(pyramid().intersect(&ray), None);
+        let ray = Ray::new(Point::new(3., 5., 1.), -Vector::y_axis());
+        assert_eq!(pyramid().intersect(&ray), None);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let path = std::env::temp_dir().join(format!("heightfield-{}.png", std::process::id()));
+        GrayImage::from_pixel(2, 2, Luma([0])).save(&path).unwrap();
+        let yaml = format!(
+            "{{file: {}, corner: [1.0, 2.0, 3.0], size: [4.0, 5.0, 6.0]}}",
+            path.display()
+        );
+        let heightfield: Heightfield = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(heightfield.file(), Some(path.as_path()));
+        assert_eq!(heightfield.aabb().low.x, 1.);
+        assert_eq!(heightfield.aabb().high.z, 9.);
+        assert!(serde_yaml::to_string(&heightfield).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{Float, Point2D, Vector};
+use nalgebra::Unit;
+
+/// A structure holding the geometric information of a ray's intersection with a shape.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hit {
+    /// The distance along the ray at which the intersection happened.
+    pub distance: Float,
+    /// The normal of the shape's surface at the intersection.
+    pub normal: Unit<Vector>,
+    /// The texel coordinates of the intersection on the shape's surface.
+    pub uv: Point2D,
+    /// Whether the ray hit the back of the surface, which its shape's normal points away from.
+    ///
+    /// Shapes leave it unset, it is only known once the hit's object is found.
+    pub back_face: bool,
+}
+
+impl Hit {
+    /// Creates a new `Hit` struct.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::Hit;
+    /// # use pathtracer::{Point2D, Vector};
+    /// #
+    /// let hit = Hit::new(1.0, Vector::z_axis(), Point2D::new(0.5, 0.5));
+    /// ```
+    pub fn new(distance: Float, normal: Unit<Vector>, uv: Point2D) -> Self {
+        Hit {
+            distance,
+            normal,
+            uv,
+            back_face: false,
+        }
+    }
+
+    /// Creates the `Hit` bounding a [`Span`] which goes on forever along the ray's line, which is
+    /// never on a surface.
+    ///
+    /// [`Span`]: struct.Span.html
+    pub(crate) fn unbounded(distance: Float) -> Self {
+        Hit::new(distance, Vector::y_axis(), Point2D::origin())
+    }
+
+    /// Return the same `Hit` seen from the other side of its surface.
+    pub(crate) fn flipped(self) -> Self {
+        Hit {
+            normal: -self.normal,
+            ..self
+        }
+    }
+}
+
+/// A stretch of a ray's line inside of a shape, between the [`Hit`]s where it enters and exits it.
+///
+/// [`Hit`]: struct.Hit.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    /// Where the ray's line enters the shape.
+    pub enter: Hit,
+    /// Where the ray's line exits the shape.
+    pub exit: Hit,
+}
+
+impl Span {
+    /// Creates a new `Span` struct.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::{Hit, Span};
+    /// # use pathtracer::{Point2D, Vector};
+    /// #
+    /// let span = Span::new(
+    ///     Hit::new(1.0, -Vector::z_axis(), Point2D::new(0.5, 0.5)),
+    ///     Hit::new(3.0, Vector::z_axis(), Point2D::new(0.5, 0.5)),
+    /// );
+    /// assert_eq!(span.distances(), (1.0, 3.0));
+    /// ```
+    pub fn new(enter: Hit, exit: Hit) -> Self {
+        Span { enter, exit }
+    }
+
+    /// Creates the `Span` of a surface without an inside, entered and exited at the same [`Hit`].
+    ///
+    /// [`Hit`]: struct.Hit.html
+    pub fn surface(hit: Hit) -> Self {
+        Span::new(hit.clone(), hit)
+    }
+
+    /// Get the distances along the ray at which the `Span` is entered and exited.
+    pub fn distances(&self) -> (Float, Float) {
+        (self.enter.distance, self.exit.distance)
+    }
+}
@@ -0,0 +1,348 @@
+use super::{Hit, Shape, Triangle};
+use crate::{Float, Point, Point2D, Vector};
+use beevee::aabb::{Bounded, AABB};
+use beevee::bvh::{Intersected, BVH};
+use beevee::ray::Ray;
+use nalgebra::{Affine3, Matrix3, Unit, U3};
+use std::sync::Arc;
+
+/// The triangles shared by every [`Instance`] of them, along with their BVH.
+///
+/// [`Instance`]: struct.Instance.html
+#[derive(Debug, PartialEq)]
+pub struct Prototype {
+    faces: Vec<Face>,
+    bvh: BVH,
+    aabb: AABB,
+}
+
+impl Prototype {
+    /// Creates a new `Prototype` from its triangles, building their BVH.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::{Prototype, Triangle};
+    /// # use pathtracer::Point;
+    /// #
+    /// let prototype = Prototype::new(vec![Triangle::new(
+    ///     Point::new(0.0, 0.0, 0.0),
+    ///     Point::new(1.0, 0.0, 0.0),
+    ///     Point::new(0.0, 1.0, 0.0),
+    /// )]);
+    /// assert_eq!(prototype.triangles().len(), 1);
+    /// ```
+    pub fn new(triangles: Vec<Triangle>) -> Self {
+        let mut faces: Vec<_> = triangles.into_iter().map(Face).collect();
+        let bvh = BVH::build(&mut faces);
+        let aabb = (faces.iter()).fold(AABB::empty(), |aabb, face| aabb.union(&face.0.aabb()));
+        Prototype { faces, bvh, aabb }
+    }
+
+    /// Get the triangles of the `Prototype`, in the order in which its BVH stores them.
+    pub fn triangles(&self) -> impl ExactSizeIterator<Item = &Triangle> {
+        self.faces.iter().map(|face| &face.0)
+    }
+
+    /// Get the triangle on which a point of the `Prototype`'s surface lies.
+    fn face(&self, point: &Point) -> Option<&Triangle> {
+        (self.bvh.nearest(*point, &self.faces)).map(|(_, face)| &face.0)
+    }
+}
+
+/// A triangle of a [`Prototype`], stored in its BVH.
+///
+/// [`Prototype`]: struct.Prototype.html
+#[derive(Debug, PartialEq)]
+struct Face(Triangle);
+
+impl Bounded for Face {
+    fn aabb(&self) -> AABB {
+        self.0.aabb()
+    }
+
+    fn centroid(&self) -> Point {
+        self.0.centroid()
+    }
+
+    fn clipped_aabb(&self, clip: &AABB) -> AABB {
+        self.0.clipped_aabb(clip)
+    }
+
+    fn distance_to_point(&self, point: Point) -> Float {
+        self.0.distance_to_point(point)
+    }
+}
+
+impl Intersected for Face {
+    fn intersect(&self, ray: &Ray) -> Option<Float> {
+        self.0.intersect(ray).map(|hit| hit.distance)
+    }
+}
+
+/// A copy of a [`Prototype`] placed in the scene by an affine transformation, e.g: one of many
+/// rocks scattered over a terrain.
+///
+/// Every copy shares the triangles and the BVH of its prototype, instead of holding its own, and
+/// intersects rays by moving them into the prototype's coordinates.
+///
+/// [`Prototype`]: struct.Prototype.html
+#[derive(Debug, PartialEq)]
+pub struct Instance {
+    prototype: Arc<Prototype>,
+    transform: Affine3<Float>,
+    inverse: Affine3<Float>,
+    /// The inverse transpose of the transformation's linear part, which moves normals.
+    normals: Matrix3<Float>,
+    aabb: AABB,
+}
+
+impl Instance {
+    /// Creates a new `Instance` of the [`Prototype`], placed in the scene by `transform`, or
+    /// `None` if the transformation cannot be inverted, e.g: if it scales the prototype down to
+    /// nothing.
+    ///
+    /// [`Prototype`]: struct.Prototype.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nalgebra::{Affine3, Matrix4};
+    /// # use pathtracer::shape::{Instance, Prototype, Shape, Triangle};
+    /// # use pathtracer::{Point, Vector};
+    /// # use std::sync::Arc;
+    /// #
+    /// let prototype = Arc::new(Prototype::new(vec![Triangle::new(
+    ///     Point::new(0.0, 0.0, 0.0),
+    ///     Point::new(1.0, 0.0, 0.0),
+    ///     Point::new(0.0, 1.0, 0.0),
+    /// )]));
+    /// let moved = Affine3::from_matrix_unchecked(Matrix4::new_translation(&Vector::z()));
+    /// let instance = Instance::new(prototype.clone(), moved).unwrap();
+    /// assert_eq!(instance.aabb().low, Point::new(0.0, 0.0, 1.0));
+    ///
+    /// let flattened = Affine3::from_matrix_unchecked(Matrix4::new_scaling(0.0));
+    /// assert!(Instance::new(prototype, flattened).is_none());
+    /// ```
+    pub fn new(prototype: Arc<Prototype>, transform: Affine3<Float>) -> Option<Self> {
+        let inverse = transform.try_inverse()?;
+        let normals = linear(&inverse).transpose();
+        let corners = (0..8).map(|i| {
+            let pick = |bit, axis: usize| {
+                if i & bit == 0 {
+                    prototype.aabb.low[axis]
+                } else {
+                    prototype.aabb.high[axis]
+                }
+            };
+            transform * Point::new(pick(1, 0), pick(2, 1), pick(4, 2))
+        });
+        let aabb = if prototype.faces.is_empty() {
+            AABB::empty()
+        } else {
+            corners.fold(AABB::empty(), |aabb, corner| aabb.grow(&corner))
+        };
+        Some(Instance {
+            prototype,
+            transform,
+            inverse,
+            normals,
+            aabb,
+        })
+    }
+
+    /// Get the [`Prototype`] shared by the `Instance`.
+    ///
+    /// [`Prototype`]: struct.Prototype.html
+    pub fn prototype(&self) -> &Arc<Prototype> {
+        &self.prototype
+    }
+
+    /// Get the transformation placing the `Instance` in the scene.
+    pub fn transform(&self) -> &Affine3<Float> {
+        &self.transform
+    }
+
+    /// Return the triangles of the `Instance` where it is placed in the scene, e.g: to write it
+    /// back as a mesh.
+    pub fn triangles(&self) -> impl Iterator<Item = Triangle> + '_ {
+        // Mirroring the triangles turns their corners around, and their normal inside
+        let [a, b, c] = if linear(&self.transform).determinant() < 0. {
+            [0, 2, 1]
+        } else {
+            [0, 1, 2]
+        };
+        self.prototype.triangles().map(move |triangle| {
+            let corners = triangle.corners();
+            let mut moved = Triangle::new(
+                self.transform * corners[a],
+                self.transform * corners[b],
+                self.transform * corners[c],
+            );
+            if let Some(texcoords) = triangle.texcoords() {
+                moved = moved.with_texcoords([texcoords[a], texcoords[b], texcoords[c]]);
+            }
+            if let Some(normals) = triangle.normals() {
+                let normals = [normals[a], normals[b], normals[c]];
+                moved = moved.with_normals(normals.map(|normal| self.to_scene(&normal)));
+            }
+            moved
+        })
+    }
+
+    /// Move a normal of the prototype to where the `Instance` is placed in the scene.
+    fn to_scene(&self, normal: &Unit<Vector>) -> Unit<Vector> {
+        Unit::new_normalize(self.normals * normal.as_ref())
+    }
+}
+
+/// Get the linear part of an affine transformation.
+fn linear(transform: &Affine3<Float>) -> Matrix3<Float> {
+    transform.matrix().fixed_slice::<U3, U3>(0, 0).into_owned()
+}
+
+impl Shape for Instance {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let direction = self.inverse * ray.direction.as_ref();
+        // Distances along the prototype's ray are scaled by the transformation
+        let scale = direction.norm();
+        let local =
+            Ray::new(self.inverse * ray.origin, Unit::new_normalize(direction)).with_time(ray.time);
+        let faces = &self.prototype.faces;
+        let (hit, _) = self.prototype.bvh.walk_with(&local, faces, |face| {
+            face.0.intersect(&local).map(|hit| (hit.distance, hit))
+        })?;
+        Some(Hit::new(
+            hit.distance / scale,
+            self.to_scene(&hit.normal),
+            hit.uv,
+        ))
+    }
+
+    fn normal(&self, point: &Point) -> Unit<Vector> {
+        let local = self.inverse * point;
+        match self.prototype.face(&local) {
+            Some(face) => self.to_scene(&face.normal(&local)),
+            None => Vector::y_axis(),
+        }
+    }
+
+    fn project_texel(&self, point: &Point) -> Point2D {
+        let local = self.inverse * point;
+        match self.prototype.face(&local) {
+            Some(face) => face.project_texel(&local),
+            None => Point2D::origin(),
+        }
+    }
+
+    fn aabb(&self) -> AABB {
+        self.aabb
+    }
+
+    fn centroid(&self) -> Point {
+        self.aabb.centroid()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::{Rotation, Scale, Transform, TransformComponent};
+
+    fn square() -> Arc<Prototype> {
+        // A unit square in the XY plane, facing +Z
+        let corners = [
+            Point::new(0., 0., 0.),
+            Point::new(1., 0., 0.),
+            Point::new(1., 1., 0.),
+            Point::new(0., 1., 0.),
+        ];
+        Arc::new(Prototype::new(vec![
+            Triangle::new(corners[0], corners[1], corners[2]),
+            Triangle::new(corners[0], corners[2], corners[3]),
+        ]))
+    }
+
+    fn placed(components: Vec<TransformComponent>) -> Instance {
+        Instance::new(square(), Transform::new(components).affine()).unwrap()
+    }
+
+    #[test]
+    fn aabb_works() {
+        let instance = placed(vec![
+            TransformComponent::Scale(Scale::Uniform(2.)),
+            TransformComponent::Translate(Vector::new(1., 2., 3.)),
+        ]);
+        assert_eq!(
+            instance.aabb(),
+            AABB::with_bounds(Point::new(1., 2., 3.), Point::new(3., 4., 3.))
+        );
+        assert_eq!(instance.centroid(), Point::new(2., 3., 3.));
+    }
+
+    #[test]
+    fn intersect_matches_moved_triangles() {
+        let instance = placed(vec![
+            TransformComponent::Scale(Scale::Axes(Vector::new(2., 3., 1.))),
+            TransformComponent::Rotate(Rotation::Euler([0., 30., 0.])),
+            TransformComponent::Translate(Vector::new(0., 0., 5.)),
+        ]);
+        let triangles: Vec<_> = instance.triangles().collect();
+        for &(x, y) in &[(0.5, 0.5), (1.5, 2.), (0.1, 2.9), (3., 3.), (-0.5, 1.)] {
+            let ray = Ray::new(Point::new(x, y, 0.), Vector::z_axis());
+            let expected = (triangles.iter())
+                .filter_map(|triangle| Shape::intersect(triangle, &ray))
+                .min_by(|lhs, rhs| lhs.distance.total_cmp(&rhs.distance));
+            let hit = instance.intersect(&ray);
+            assert_eq!(hit.is_some(), expected.is_some(), "({}, {})", x, y);
+            if let (Some(hit), Some(expected)) = (hit, expected) {
+                assert!((hit.distance - expected.distance).abs() < 1e-4);
+                assert!((hit.normal.as_ref() - expected.normal.as_ref()).norm() < 1e-4);
+                assert!((hit.uv - expected.uv).norm() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn mirrored_normals_stay_outside() {
+        let mirror = Scale::Axes(Vector::new(1., 1., -1.));
+        let instance = placed(vec![TransformComponent::Scale(mirror)]);
+        // Like a mirrored mesh, the front of the square is mirrored to face -Z
+        let ray = Ray::new(Point::new(0.5, 0.25, 1.), -Vector::z_axis());
+        let hit = instance.intersect(&ray).unwrap();
+        assert!((hit.normal.as_ref() + Vector::z()).norm() < 1e-6);
+        for triangle in instance.triangles() {
+            assert!((triangle.normal(&Point::origin()).as_ref() + Vector::z()).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn normal_and_texel_use_the_face_under_the_point() {
+        let instance = placed(vec![
+            TransformComponent::Rotate(Rotation::Euler([90., 0., 0.])),
+            TransformComponent::Translate(Vector::new(0., 1., 0.)),
+        ]);
+        let ray = Ray::new(Point::new(0.25, 5., 0.75), -Vector::y_axis());
+        let hit = instance.intersect(&ray).unwrap();
+        let point = ray.origin + ray.direction.as_ref() * hit.distance;
+        assert!((instance.normal(&point).as_ref() - hit.normal.as_ref()).norm() < 1e-6);
+        assert!((instance.project_texel(&point) - hit.uv).norm() < 1e-6);
+    }
+
+    #[test]
+    fn copies_share_their_prototype() {
+        let prototype = square();
+        let copies: Vec<_> = (0..10)
+            .map(|i| {
+                let moved = Transform::new(vec![TransformComponent::Translate(Vector::new(
+                    i as Float, 0., 0.,
+                ))]);
+                Instance::new(prototype.clone(), moved.affine()).unwrap()
+            })
+            .collect();
+        assert_eq!(Arc::strong_count(&prototype), 11);
+        assert!(copies
+            .iter()
+            .all(|copy| Arc::ptr_eq(copy.prototype(), &prototype)));
+    }
+}
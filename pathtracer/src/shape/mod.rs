@@ -1,21 +1,32 @@
 //! Various shape implementations
 
 use super::{Point, Point2D, Vector};
+use crate::core::sampling;
+use crate::Float;
 use beevee::{
     aabb::{Bounded, AABB},
     bvh::Intersected,
     ray::Ray,
 };
 use nalgebra::Unit;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// All the existing `Shape` implementation.
 #[serde(tag = "type")]
 #[serde(rename_all = "lowercase")]
 #[allow(missing_docs)]
 #[enum_dispatch::enum_dispatch]
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub enum ShapeEnum {
+    Csg,
+    Disk,
+    Heightfield,
+    /// Instances share their prototype, they are only made when loading the scene, and are written
+    /// back as the triangles they are made of
+    #[serde(skip)]
+    Instance,
+    Plane,
+    Sdf,
     Sphere,
     Triangle,
 }
@@ -23,16 +34,56 @@ pub enum ShapeEnum {
 /// Represent an abstract shape inside the scene.
 #[enum_dispatch::enum_dispatch(ShapeEnum)]
 pub trait Shape: std::fmt::Debug {
-    /// Return the distance at which the object intersects with the ray, or None if it does not.
-    fn intersect(&self, ray: &Ray) -> Option<f32>;
+    /// Return the [`Hit`] describing the closest intersection of the object with the ray, or None
+    /// if it does not intersect.
+    ///
+    /// [`Hit`]: struct.Hit.html
+    fn intersect(&self, ray: &Ray) -> Option<Hit>;
+    /// Return the sorted [`Span`]s of the ray's line which are inside the shape, including behind
+    /// its origin, with the hits where it enters and exits them. Surfaces without an inside are
+    /// entered and exited at the same hit.
+    ///
+    /// [`Span`]: struct.Span.html
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        self.intersect(ray).into_iter().map(Span::surface).collect()
+    }
     /// Return the unit vector corresponding to the normal at this point of the shape.
+    ///
+    /// Prefer using the normal from the [`Hit`] when available, this is meant for points which
+    /// are known to be on the surface without having intersected it.
+    ///
+    /// [`Hit`]: struct.Hit.html
     fn normal(&self, point: &Point) -> Unit<Vector>;
     /// Project the point from the shape's surface to its texel coordinates.
     fn project_texel(&self, point: &Point) -> Point2D;
+    /// Return by how much the texel coordinates change when moving by a unit of distance along
+    /// the surface, in the direction where they change the most, around a point of the shape.
+    fn texel_scale(&self, point: &Point, normal: &Unit<Vector>) -> Float {
+        const EPSILON: Float = 1e-3;
+        let (tangent, bitangent) = sampling::orthonormal_basis(normal);
+        let uv = self.project_texel(point);
+        let scale = |direction: Vector| {
+            let delta = self.project_texel(&(point + direction * EPSILON)) - uv;
+            // Texel coordinates may wrap around, only keep the local variation
+            let (du, dv) = (delta.x - delta.x.round(), delta.y - delta.y.round());
+            (du * du + dv * dv).sqrt() / EPSILON
+        };
+        scale(tangent).max(scale(bitangent))
+    }
     /// Enclose the `Shape` in an axi-aligned bounding-box.
     fn aabb(&self) -> AABB;
     /// Return the centroid of the shape.
     fn centroid(&self) -> Point;
+    /// Return the axis-aligned bounding-box of the part of the shape which is inside `clip`, to
+    /// split it across nodes of a spatial BVH. Defaults to clipping the shape's bounding-box.
+    fn clipped_aabb(&self, clip: &AABB) -> AABB {
+        self.aabb().intersection(clip)
+    }
+    /// Return false for shapes which cannot be enclosed in a finite bounding-box, and must be
+    /// intersected outside of the BVH.
+    fn is_bounded(&self) -> bool {
+        true
+    }
 }
 
 impl Bounded for dyn Shape {
@@ -43,14 +94,60 @@ impl Bounded for dyn Shape {
     fn centroid(&self) -> Point {
         self.centroid()
     }
+
+    fn clipped_aabb(&self, clip: &AABB) -> AABB {
+        self.clipped_aabb(clip)
+    }
 }
 
 impl Intersected for dyn Shape {
-    fn intersect(&self, ray: &Ray) -> Option<f32> {
-        self.intersect(ray)
+    fn intersect(&self, ray: &Ray) -> Option<Float> {
+        self.intersect(ray).map(|hit| hit.distance)
     }
 }
 
+/// Return the distances along the ray's line at which it enters and exits the bounding box.
+fn bounds_interval(aabb: &AABB, ray: &Ray) -> Option<(Float, Float)> {
+    let mut t_min = Float::NEG_INFINITY;
+    let mut t_max = Float::INFINITY;
+    for i in 0..3 {
+        let a = (aabb.low[i] - ray.origin[i]) * ray.inv_direction[i];
+        let b = (aabb.high[i] - ray.origin[i]) * ray.inv_direction[i];
+        // NaNs happen when the ray is parallel to the slab and starts on its border
+        if a.is_nan() || b.is_nan() {
+            continue;
+        }
+        t_min = t_min.max(a.min(b));
+        t_max = t_max.min(a.max(b));
+    }
+    if t_min <= t_max {
+        Some((t_min, t_max))
+    } else {
+        None
+    }
+}
+
+mod csg;
+pub use csg::*;
+
+mod disk;
+pub use disk::*;
+
+mod heightfield;
+pub use heightfield::*;
+
+mod hit;
+pub use hit::*;
+
+mod instance;
+pub use instance::*;
+
+mod plane;
+pub use plane::*;
+
+mod sdf;
+pub use sdf::*;
+
 mod sphere;
 pub use sphere::*;
 
@@ -0,0 +1,171 @@
+use super::{Hit, Shape};
+use crate::core::sampling;
+use crate::{Float, Point, Point2D, Vector};
+use beevee::aabb::AABB;
+use beevee::ray::Ray;
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// Represent an infinite plane inside the scene, e.g: a ground or a backdrop.
+///
+/// A plane cannot be enclosed in a bounding box, it is kept outside of the scene's BVH.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Plane {
+    /// A point on the plane.
+    origin: Point,
+    /// The normal of the plane.
+    #[serde(deserialize_with = "crate::serialize::vector_normalizer")]
+    normal: Unit<Vector>,
+}
+
+impl Plane {
+    /// Creates a new `Plane` going through `origin`, facing towards `normal`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::Plane;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let ground = Plane::new(Point::origin(), Vector::new(0.0, 1.0, 0.0));
+    /// ```
+    pub fn new(origin: Point, normal: Vector) -> Self {
+        Plane {
+            origin,
+            normal: Unit::new_normalize(normal),
+        }
+    }
+
+    /// Get the point the `Plane` was created to go through.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::Plane;
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let ground = Plane::new(Point::new(0.0, -1.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+    /// assert_eq!(ground.origin(), &Point::new(0.0, -1.0, 0.0));
+    /// ```
+    pub fn origin(&self) -> &Point {
+        &self.origin
+    }
+
+    /// Return two unit vectors spanning the plane.
+    fn tangents(&self) -> (Vector, Vector) {
+        sampling::orthonormal_basis(&self.normal)
+    }
+}
+
+impl Shape for Plane {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let cos = ray.direction.dot(&self.normal);
+        if cos.abs() < 1e-8 {
+            return None;
+        }
+        let distance = (self.origin - ray.origin).dot(&self.normal) / cos;
+        if distance < 0. {
+            return None;
+        }
+        let point = ray.origin + ray.direction.as_ref() * distance;
+        Some(Hit::new(distance, self.normal, self.project_texel(&point)))
+    }
+
+    fn normal(&self, _: &Point) -> Unit<Vector> {
+        self.normal
+    }
+
+    fn project_texel(&self, point: &Point) -> Point2D {
+        // One texture repetition per unit of distance
+        let (u, v) = self.tangents();
+        let delt = point - self.origin;
+        Point2D::new(delt.dot(&u).rem_euclid(1.), delt.dot(&v).rem_euclid(1.))
+    }
+
+    fn aabb(&self) -> AABB {
+        let inf = Float::INFINITY;
+        AABB::with_bounds(Point::new(-inf, -inf, -inf), Point::new(inf, inf, inf))
+    }
+
+    fn centroid(&self) -> Point {
+        self.origin
+    }
+
+    fn is_bounded(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn simple_plane() -> Plane {
+        Plane::new(Point::origin(), Vector::new(0., 2., 0.))
+    }
+
+    #[test]
+    fn new_works() {
+        assert_eq!(
+            simple_plane(),
+            Plane {
+                origin: Point::origin(),
+                normal: Vector::y_axis(),
+            }
+        )
+    }
+
+    #[test]
+    fn intersect_works() {
+        let plane = simple_plane();
+        let ray = Ray::new(
+            Point::new(3., 2., -7.),
+            Unit::new_normalize(Vector::new(0., -1., 0.)),
+        );
+        let hit = plane.intersect(&ray).unwrap();
+        assert_eq!(hit.distance, 2.);
+        assert_eq!(hit.normal, Vector::y_axis());
+        // Seen from below
+        let ray = Ray::new(Point::new(0., -1., 0.), Vector::y_axis());
+        assert_eq!(plane.intersect(&ray).map(|hit| hit.distance), Some(1.));
+    }
+
+    #[test]
+    fn intersect_misses() {
+        let plane = simple_plane();
+        let ray = Ray::new(Point::new(0., 1., 0.), Vector::x_axis());
+        assert!(plane.intersect(&ray).is_none());
+        let ray = Ray::new(Point::new(0., 1., 0.), Vector::y_axis());
+        assert!(plane.intersect(&ray).is_none());
+    }
+
+    #[test]
+    fn project_texel_works() {
+        let plane = simple_plane();
+        let texel = plane.project_texel(&Point::new(12.25, 0., -3.5));
+        assert!(texel.x >= 0. && texel.x < 1.);
+        assert!(texel.y >= 0. && texel.y < 1.);
+    }
+
+    #[test]
+    fn texel_scale_works() {
+        let plane = simple_plane();
+        let scale = plane.texel_scale(&Point::new(12.25, 0., -3.5), &Vector::y_axis());
+        assert!((scale - 1.).abs() < 1e-2);
+    }
+
+    #[test]
+    fn is_not_bounded() {
+        assert!(!simple_plane().is_bounded())
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            origin: [0.0, 0.0, 0.0]
+            normal: [0.0, 2.0, 0.0]
+        "#;
+        let plane: Plane = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(plane, simple_plane())
+    }
+}
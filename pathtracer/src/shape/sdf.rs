@@ -0,0 +1,450 @@
+use super::{bounds_interval, Hit, Shape, Span};
+use crate::{Float, Point, Point2D, Vector};
+use beevee::aabb::AABB;
+use beevee::ray::Ray;
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
+
+/// The built-in signed distance functions, expressed in the shape's local coordinates.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "primitive")]
+#[serde(rename_all = "snake_case")]
+pub enum SdfPrimitive {
+    /// A sphere centered on the origin.
+    Sphere {
+        /// The radius of the sphere.
+        radius: Float,
+    },
+    /// A box centered on the origin, with rounded edges.
+    RoundedBox {
+        /// Half of the box's size along each axis, including the rounded edges.
+        half_extents: Vector,
+        /// The radius of the rounded edges.
+        radius: Float,
+    },
+    /// A torus lying in the XZ-plane, centered on the origin.
+    Torus {
+        /// The distance from the center of the torus to the center of its tube.
+        major_radius: Float,
+        /// The radius of the tube.
+        minor_radius: Float,
+    },
+    /// A thickened gyroid minimal surface, cut to fit inside a box centered on the origin.
+    Gyroid {
+        /// The number of periods of the surface per unit length.
+        frequency: Float,
+        /// The thickness of the surface.
+        thickness: Float,
+        /// Half of the box's size along each axis.
+        half_extents: Vector,
+    },
+    /// The mandelbulb fractal, contained inside the unit sphere.
+    Mandelbulb {
+        /// The power used in the iterated formula, 8 gives the classic shape.
+        #[serde(default = "default_mandelbulb_power")]
+        power: Float,
+        /// The number of iterations used to approximate the distance.
+        #[serde(default = "default_mandelbulb_iterations")]
+        iterations: u32,
+    },
+}
+
+fn default_mandelbulb_power() -> Float {
+    8.
+}
+
+fn default_mandelbulb_iterations() -> u32 {
+    10
+}
+
+impl SdfPrimitive {
+    /// Return the signed distance from the point to the primitive's surface.
+    ///
+    /// The distance is negative inside of the primitive, and positive outside of it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::SdfPrimitive;
+    /// # use pathtracer::Point;
+    /// #
+    /// let sphere = SdfPrimitive::Sphere { radius: 1.0 };
+    /// assert_eq!(sphere.distance(&Point::new(2.0, 0.0, 0.0)), 1.0);
+    /// assert_eq!(sphere.distance(&Point::origin()), -1.0);
+    /// ```
+    pub fn distance(&self, point: &Point) -> Float {
+        match self {
+            SdfPrimitive::Sphere { radius } => point.coords.norm() - radius,
+            SdfPrimitive::RoundedBox {
+                half_extents,
+                radius,
+            } => box_distance(point, &(half_extents - Vector::repeat(*radius))) - radius,
+            SdfPrimitive::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let ring = (point.x * point.x + point.z * point.z).sqrt() - major_radius;
+                (ring * ring + point.y * point.y).sqrt() - minor_radius
+            }
+            SdfPrimitive::Gyroid {
+                frequency,
+                thickness,
+                half_extents,
+            } => {
+                let p = point.coords * (2. * crate::consts::PI * frequency);
+                let gyroid = p.x.sin() * p.y.cos() + p.y.sin() * p.z.cos() + p.z.sin() * p.x.cos();
+                // The gyroid's gradient is at most sqrt(3) for each unit of phase
+                let surface = gyroid.abs() / (2. * crate::consts::PI * frequency * Float::sqrt(3.))
+                    - thickness / 2.;
+                surface.max(box_distance(point, half_extents))
+            }
+            SdfPrimitive::Mandelbulb { power, iterations } => {
+                let mut z = point.coords;
+                let mut dr = 1.;
+                let mut r = z.norm();
+                for _ in 0..*iterations {
+                    if r > 2. || r == 0. {
+                        break;
+                    }
+                    let theta = (z.z / r).acos() * power;
+                    let phi = z.y.atan2(z.x) * power;
+                    dr = r.powf(power - 1.) * power * dr + 1.;
+                    z = Vector::new(
+                        theta.sin() * phi.cos(),
+                        phi.sin() * theta.sin(),
+                        theta.cos(),
+                    ) * r.powf(*power)
+                        + point.coords;
+                    r = z.norm();
+                }
+                if r == 0. {
+                    0.
+                } else {
+                    0.5 * r.ln() * r / dr
+                }
+            }
+        }
+    }
+
+    /// Return half of the size of the box centered on the origin which contains the primitive.
+    fn half_extents(&self) -> Vector {
+        match self {
+            SdfPrimitive::Sphere { radius } => Vector::repeat(*radius),
+            SdfPrimitive::RoundedBox { half_extents, .. } => *half_extents,
+            SdfPrimitive::Torus {
+                major_radius,
+                minor_radius,
+            } => Vector::new(
+                major_radius + minor_radius,
+                *minor_radius,
+                major_radius + minor_radius,
+            ),
+            SdfPrimitive::Gyroid { half_extents, .. } => *half_extents,
+            SdfPrimitive::Mandelbulb { .. } => Vector::repeat(1.2),
+        }
+    }
+}
+
+/// Return the signed distance from the point to a box centered on the origin.
+fn box_distance(point: &Point, half_extents: &Vector) -> Float {
+    let q = point.coords.abs() - half_extents;
+    let outside = q.map(|c| c.max(0.)).norm();
+    let inside = q.x.max(q.y).max(q.z).min(0.);
+    outside + inside
+}
+
+/// Represent a shape defined by a signed distance function, rendered using sphere tracing.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Sdf {
+    /// Where the primitive's origin is placed in the scene.
+    center: Point,
+    /// The uniform scaling applied to the primitive.
+    #[serde(default = "crate::serialize::default_identity")]
+    scale: Float,
+    /// The distance function being rendered.
+    #[serde(flatten)]
+    primitive: SdfPrimitive,
+}
+
+/// The distance under which the surface is considered reached.
+const SURFACE_EPSILON: Float = 1e-4;
+/// The maximum number of steps taken while marching along a ray.
+const MAX_STEPS: usize = 512;
+
+impl Sdf {
+    /// Creates a new `Sdf` shape.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::{Sdf, SdfPrimitive};
+    /// # use pathtracer::Point;
+    /// #
+    /// let mandelbulb = Sdf::new(
+    ///     Point::origin(),
+    ///     2.0,
+    ///     SdfPrimitive::Mandelbulb { power: 8.0, iterations: 10 },
+    /// );
+    /// ```
+    pub fn new(center: Point, scale: Float, primitive: SdfPrimitive) -> Self {
+        Sdf {
+            center,
+            scale,
+            primitive,
+        }
+    }
+
+    /// Return the signed distance from a point in the scene to the shape's surface.
+    pub fn distance(&self, point: &Point) -> Float {
+        let local = Point::origin() + (point - self.center) / self.scale;
+        self.primitive.distance(&local) * self.scale
+    }
+
+    /// March along the ray's line inside the bounding box from `start`, returning the sorted
+    /// distances at which the surface is crossed. When starting inside of the shape, the start of
+    /// the march is included unless only looking for the first crossing.
+    fn march(&self, ray: &Ray, start: Float, first_only: bool) -> Vec<Float> {
+        let (t_min, t_max) = match bounds_interval(&self.aabb(), ray) {
+            Some((t_min, t_max)) => (t_min.max(start), t_max),
+            None => return vec![],
+        };
+        let at = |t: Float| ray.origin + ray.direction.as_ref() * t;
+        let mut t = t_min;
+        let mut dist = self.distance(&at(t));
+        let mut crossings = Vec::new();
+        if dist < 0. && !first_only {
+            crossings.push(t);
+        }
+        for _ in 0..MAX_STEPS {
+            if t > t_max {
+                break;
+            }
+            if first_only && dist.abs() < SURFACE_EPSILON {
+                crossings.push(t);
+                break;
+            }
+            // Always make progress to be able to cross the surface
+            let next_t = t + dist.abs().max(SURFACE_EPSILON);
+            let next_dist = self.distance(&at(next_t));
+            if (next_dist < 0.) != (dist < 0.) {
+                crossings.push(next_t);
+                if first_only {
+                    break;
+                }
+            }
+            t = next_t;
+            dist = next_dist;
+        }
+        crossings
+    }
+
+    /// Return the [`Hit`] at which the ray crosses the surface at distance `t`.
+    ///
+    /// [`Hit`]: struct.Hit.html
+    fn hit(&self, ray: &Ray, t: Float) -> Hit {
+        let point = ray.origin + ray.direction.as_ref() * t;
+        Hit::new(t, self.normal(&point), self.project_texel(&point))
+    }
+}
+
+impl Shape for Sdf {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let t = *self.march(ray, 0., true).first()?;
+        Some(self.hit(ray, t))
+    }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        self.march(ray, Float::NEG_INFINITY, false)
+            .chunks(2)
+            .map(|bounds| {
+                Span::new(
+                    self.hit(ray, bounds[0]),
+                    self.hit(ray, *bounds.last().unwrap()),
+                )
+            })
+            .collect()
+    }
+
+    fn normal(&self, point: &Point) -> Unit<Vector> {
+        // Approximate the gradient using the tetrahedron technique
+        let h = SURFACE_EPSILON * self.scale;
+        let offsets = [
+            Vector::new(1., -1., -1.),
+            Vector::new(-1., -1., 1.),
+            Vector::new(-1., 1., -1.),
+            Vector::new(1., 1., 1.),
+        ];
+        let gradient = offsets
+            .iter()
+            .map(|offset| offset * self.distance(&(point + offset * h)))
+            .sum::<Vector>();
+        Unit::new_normalize(gradient)
+    }
+
+    fn project_texel(&self, point: &Point) -> Point2D {
+        // Project the bounding box on the XY-plane
+        let aabb = self.aabb();
+        let diagonal = aabb.diagonal();
+        Point2D::new(
+            (point.x - aabb.low.x) / diagonal.x,
+            (point.y - aabb.low.y) / diagonal.y,
+        )
+    }
+
+    fn aabb(&self) -> AABB {
+        // Add some leeway to make sure that the surface is reached from outside of the box
+        let delt = self.primitive.half_extents() * self.scale + Vector::repeat(SURFACE_EPSILON);
+        AABB::with_bounds(self.center - delt, self.center + delt)
+    }
+
+    fn centroid(&self) -> Point {
+        self.center
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sdf_sphere() -> Sdf {
+        Sdf::new(Point::origin(), 1., SdfPrimitive::Sphere { radius: 1. })
+    }
+
+    #[test]
+    fn sphere_intersect_works() {
+        let sdf = sdf_sphere();
+        let ray = Ray::new(Point::new(-2., 0., 0.), Vector::x_axis());
+        let t = sdf.intersect(&ray).unwrap().distance;
+        assert!((t - 1.).abs() < 1e-3)
+    }
+
+    #[test]
+    fn sphere_miss_works() {
+        let sdf = sdf_sphere();
+        let ray = Ray::new(Point::new(-2., 0., 0.), -Vector::x_axis());
+        assert_eq!(sdf.intersect(&ray), None)
+    }
+
+    #[test]
+    fn sphere_intersect_from_inside_works() {
+        let sdf = sdf_sphere();
+        let ray = Ray::new(Point::origin(), Vector::y_axis());
+        let t = sdf.intersect(&ray).unwrap().distance;
+        assert!((t - 1.).abs() < 1e-3)
+    }
+
+    #[test]
+    fn scaled_sphere_works() {
+        let sdf = Sdf::new(
+            Point::new(1., 0., 0.),
+            2.,
+            SdfPrimitive::Sphere { radius: 1. },
+        );
+        let ray = Ray::new(Point::new(-3., 0., 0.), Vector::x_axis());
+        let t = sdf.intersect(&ray).unwrap().distance;
+        assert!((t - 2.).abs() < 1e-3)
+    }
+
+    #[test]
+    fn sphere_spans_work() {
+        let sdf = sdf_sphere();
+        let ray = Ray::new(Point::new(-2., 0., 0.), Vector::x_axis());
+        let spans = sdf.spans(&ray);
+        assert_eq!(spans.len(), 1);
+        assert!((spans[0].enter.distance - 1.).abs() < 1e-3);
+        assert!((spans[0].exit.distance - 3.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn rounded_box_normal_works() {
+        let sdf = Sdf::new(
+            Point::origin(),
+            1.,
+            SdfPrimitive::RoundedBox {
+                half_extents: Vector::new(1., 1., 1.),
+                radius: 0.1,
+            },
+        );
+        let ray = Ray::new(Point::new(-2., 0., 0.), Vector::x_axis());
+        let t = sdf.intersect(&ray).unwrap().distance;
+        assert!((t - 1.).abs() < 1e-3);
+        let normal = sdf.normal(&Point::new(-1., 0., 0.));
+        assert!((normal.as_ref() - Vector::new(-1., 0., 0.)).norm() < 1e-3)
+    }
+
+    #[test]
+    fn torus_hole_can_be_seen_through() {
+        let sdf = Sdf::new(
+            Point::origin(),
+            1.,
+            SdfPrimitive::Torus {
+                major_radius: 1.,
+                minor_radius: 0.25,
+            },
+        );
+        let through_hole = Ray::new(Point::new(0., -2., 0.), Vector::y_axis());
+        assert_eq!(sdf.intersect(&through_hole), None);
+        let through_tube = Ray::new(Point::new(1., -2., 0.), Vector::y_axis());
+        let t = sdf.intersect(&through_tube).unwrap().distance;
+        assert!((t - 1.75).abs() < 1e-3)
+    }
+
+    #[test]
+    fn mandelbulb_intersect_works() {
+        let sdf = Sdf::new(
+            Point::origin(),
+            1.,
+            SdfPrimitive::Mandelbulb {
+                power: 8.,
+                iterations: 10,
+            },
+        );
+        let ray = Ray::new(Point::new(-3., 0., 0.), Vector::x_axis());
+        let t = sdf.intersect(&ray).unwrap().distance;
+        // The bulb is contained in the unit sphere
+        assert!(t > 1.8 && t < 3.)
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            center: [0.0, 1.0, 0.0]
+            scale: 2.0
+            primitive: rounded_box
+            half_extents: [1.0, 0.5, 0.5]
+            radius: 0.1
+        "#;
+        let sdf: Sdf = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            sdf,
+            Sdf::new(
+                Point::new(0., 1., 0.),
+                2.,
+                SdfPrimitive::RoundedBox {
+                    half_extents: Vector::new(1., 0.5, 0.5),
+                    radius: 0.1,
+                }
+            )
+        )
+    }
+
+    #[test]
+    fn deserialization_defaults_work() {
+        let yaml = r#"
+            center: [0.0, 0.0, 0.0]
+            primitive: mandelbulb
+        "#;
+        let sdf: Sdf = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            sdf,
+            Sdf::new(
+                Point::origin(),
+                1.,
+                SdfPrimitive::Mandelbulb {
+                    power: 8.,
+                    iterations: 10,
+                }
+            )
+        )
+    }
+}
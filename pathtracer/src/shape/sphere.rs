@@ -1,12 +1,98 @@
-use super::Shape;
-use crate::{Point, Point2D, Vector};
+use super::{Hit, Shape, Span};
+use crate::consts::PI;
+use crate::{Float, Point, Point2D, Vector};
 use beevee::aabb::AABB;
 use beevee::ray::Ray;
 use nalgebra::Unit;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+/// How the surface of a [`Sphere`] is projected to its texel coordinates.
+///
+/// [`Sphere`]: struct.Sphere.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SphereProjection {
+    /// Project the sphere on the XY-plane, its front and back sharing the same texels.
+    #[default]
+    Planar,
+    /// Longitudes along the abscissa and latitudes along the ordinate, the poles being along the
+    /// Y axis, e.g: for maps of the world.
+    Equirectangular,
+    /// Project the sphere on the faces of a cube, laid out in two rows of three faces: `+X`, `-X`,
+    /// `+Y` on top of `-Y`, `+Z`, `-Z`. Each face is upright as seen from outside the sphere,
+    /// with `-Z` being up on the `+Y` face and `+Z` on the `-Y` one.
+    Cube,
+    /// A disk centered on the north pole, the distance to its center growing with the latitude,
+    /// e.g: for textures seen from above, the south pole being spread along its rim.
+    Polar,
+}
+
+impl SphereProjection {
+    /// Project a point of a sphere to its texel coordinates, given relative to its center and
+    /// scaled down to a unit radius.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::SphereProjection;
+    /// # use pathtracer::Vector;
+    /// #
+    /// let north = SphereProjection::Equirectangular.project(&Vector::y());
+    /// assert_eq!(north.y, 1.0);
+    /// let north = SphereProjection::Polar.project(&Vector::y());
+    /// assert_eq!((north.x, north.y), (0.5, 0.5));
+    /// ```
+    pub fn project(self, offset: &Vector) -> Point2D {
+        let direction = Unit::new_normalize(*offset);
+        let phi = direction.z.atan2(direction.x);
+        match self {
+            SphereProjection::Planar => Point2D::new(0.5 + offset.x / 2., 0.5 + offset.y / 2.),
+            SphereProjection::Equirectangular => Point2D::new(
+                (phi / (2. * PI)).rem_euclid(1.),
+                0.5 + direction.y.clamp(-1., 1.).asin() / PI,
+            ),
+            SphereProjection::Cube => cube_texel(&direction),
+            SphereProjection::Polar => {
+                let radius = direction.y.clamp(-1., 1.).acos() / PI;
+                Point2D::new(0.5 + radius * phi.cos() / 2., 0.5 + radius * phi.sin() / 2.)
+            }
+        }
+    }
+}
+
+/// Project a direction on the face of the cube it points to, see [`SphereProjection::Cube`].
+///
+/// [`SphereProjection::Cube`]: enum.SphereProjection.html#variant.Cube
+fn cube_texel(direction: &Unit<Vector>) -> Point2D {
+    let (x, y, z) = (direction.x, direction.y, direction.z);
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    // The index of the face, and the coordinates along it in `[-1, 1]`
+    let (face, s, t) = if ax >= ay && ax >= az {
+        if x > 0. {
+            (0, -z / ax, y / ax)
+        } else {
+            (1, z / ax, y / ax)
+        }
+    } else if ay >= az {
+        if y > 0. {
+            (2, x / ay, -z / ay)
+        } else {
+            (3, x / ay, z / ay)
+        }
+    } else if z > 0. {
+        (4, x / az, y / az)
+    } else {
+        (5, -x / az, y / az)
+    };
+    let (column, row) = ((face % 3) as Float, (face / 3) as Float);
+    Point2D::new(
+        (column + (s + 1.) / 2.) / 3.,
+        (1. - row + (t + 1.) / 2.) / 2.,
+    )
+}
 
 /// Represent a sphere shape inside the scene.
-#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Sphere {
     /// The sphere is inverted if it is expected to be seen from the inside.
     #[serde(default)]
@@ -14,31 +100,87 @@ pub struct Sphere {
     /// The center of the sphere in space.
     center: Point,
     /// The radius of the sphere being rendered.
-    radius: f32,
+    radius: Float,
+    /// How the surface is projected to its texel coordinates.
+    #[serde(default)]
+    projection: SphereProjection,
 }
 
 impl Sphere {
     /// Return a sphere which should be rendered as seen from the outside.
-    pub fn new(center: Point, radius: f32) -> Self {
+    pub fn new(center: Point, radius: Float) -> Self {
         Sphere {
             center,
             radius,
             inverted: false,
+            projection: SphereProjection::default(),
         }
     }
 
     /// Return a sphere which should be rendered as seen from the inside.
-    pub fn inverted_new(center: Point, radius: f32) -> Self {
+    pub fn inverted_new(center: Point, radius: Float) -> Self {
         Sphere {
             center,
             radius,
             inverted: true,
+            projection: SphereProjection::default(),
         }
     }
+
+    /// Get the center of the sphere.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::Sphere;
+    /// # use pathtracer::Point;
+    /// #
+    /// let sphere = Sphere::inverted_new(Point::new(1.0, 2.0, 3.0), 0.5);
+    /// assert_eq!(sphere.center(), &Point::new(1.0, 2.0, 3.0));
+    /// assert_eq!(sphere.radius(), 0.5);
+    /// assert!(sphere.is_inverted());
+    /// ```
+    pub fn center(&self) -> &Point {
+        &self.center
+    }
+
+    /// Get the radius of the sphere.
+    pub fn radius(&self) -> Float {
+        self.radius
+    }
+
+    /// Whether the sphere is seen from the inside.
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Change how the surface is projected to its texel coordinates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::{Shape, Sphere, SphereProjection};
+    /// # use pathtracer::Point;
+    /// #
+    /// let sphere =
+    ///     Sphere::new(Point::origin(), 2.0).with_projection(SphereProjection::Equirectangular);
+    /// assert_eq!(sphere.projection(), SphereProjection::Equirectangular);
+    /// let texel = sphere.project_texel(&Point::new(2.0, 0.0, 0.0));
+    /// assert_eq!((texel.x, texel.y), (0.0, 0.5));
+    /// ```
+    pub fn with_projection(mut self, projection: SphereProjection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Get how the surface is projected to its texel coordinates.
+    pub fn projection(&self) -> SphereProjection {
+        self.projection
+    }
 }
 
 impl Shape for Sphere {
-    fn intersect(&self, ray: &Ray) -> Option<f32> {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
         use std::mem;
 
         let delt = self.center - ray.origin;
@@ -64,7 +206,45 @@ impl Shape for Sphere {
         if t_0 < 0. {
             None
         } else {
-            Some(t_0)
+            let point = ray.origin + ray.direction.as_ref() * t_0;
+            Some(Hit::new(
+                t_0,
+                self.normal(&point),
+                self.project_texel(&point),
+            ))
+        }
+    }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let delt = self.center - ray.origin;
+        let tca = ray.direction.dot(&delt);
+        let d2 = delt.norm_squared() - tca * tca;
+        let r_2 = self.radius * self.radius;
+
+        if d2 > r_2 {
+            return if self.inverted {
+                vec![Span::new(
+                    Hit::unbounded(Float::NEG_INFINITY),
+                    Hit::unbounded(Float::INFINITY),
+                )]
+            } else {
+                vec![]
+            };
+        }
+
+        let thc = (r_2 - d2).sqrt();
+        let hit = |t: Float| {
+            let point = ray.origin + ray.direction.as_ref() * t;
+            Hit::new(t, self.normal(&point), self.project_texel(&point))
+        };
+        if self.inverted {
+            // The inside of an inverted sphere is everything outside of it
+            vec![
+                Span::new(Hit::unbounded(Float::NEG_INFINITY), hit(tca - thc)),
+                Span::new(hit(tca + thc), Hit::unbounded(Float::INFINITY)),
+            ]
+        } else {
+            vec![Span::new(hit(tca - thc), hit(tca + thc))]
         }
     }
 
@@ -78,11 +258,8 @@ impl Shape for Sphere {
     }
 
     fn project_texel(&self, point: &Point) -> Point2D {
-        // Project the sphere on the XY-plane
-        Point2D::new(
-            0.5 + (point.x - self.center.x) / (2. * self.radius),
-            0.5 + (point.y - self.center.y) / (2. * self.radius),
-        )
+        self.projection
+            .project(&((point - self.center) / self.radius))
     }
 
     fn aabb(&self) -> AABB {
@@ -112,7 +289,7 @@ mod test {
             Point::new(-2., 0., 0.),
             Unit::new_normalize(Vector::new(1., 0., 0.)),
         );
-        assert_eq!(sphere.intersect(&ray), Some(1.))
+        assert_eq!(sphere.intersect(&ray).map(|hit| hit.distance), Some(1.))
     }
 
     #[test]
@@ -132,7 +309,41 @@ mod test {
             Point::new(1., 1., 1.),
             Unit::new_normalize(Vector::new(-1., -1., -1.)),
         );
-        assert_eq!(sphere.intersect(&ray), Some(f32::sqrt(3.) - 1.))
+        let distance = sphere.intersect(&ray).unwrap().distance;
+        assert!((distance - (Float::sqrt(3.) - 1.)).abs() < 1e-6)
+    }
+
+    #[test]
+    fn intersect_hit_works() {
+        let sphere = simple_sphere();
+        let ray = Ray::new(Point::new(-2., 0., 0.), Vector::x_axis());
+        assert_eq!(
+            sphere.intersect(&ray),
+            Some(Hit::new(
+                1.,
+                Unit::new_normalize(Vector::new(-1., 0., 0.)),
+                Point2D::new(0., 0.5)
+            ))
+        )
+    }
+
+    #[test]
+    fn spans_work() {
+        let sphere = simple_sphere();
+        let ray = Ray::new(Point::origin(), Vector::x_axis());
+        let spans: Vec<_> = sphere.spans(&ray).iter().map(Span::distances).collect();
+        assert_eq!(spans, vec![(-1., 1.)])
+    }
+
+    #[test]
+    fn inverted_spans_work() {
+        let sphere = Sphere::inverted_new(Point::origin(), 1.);
+        let ray = Ray::new(Point::origin(), Vector::x_axis());
+        let spans: Vec<_> = sphere.spans(&ray).iter().map(Span::distances).collect();
+        assert_eq!(
+            spans,
+            vec![(Float::NEG_INFINITY, -1.), (1., Float::INFINITY)]
+        )
     }
 
     #[test]
@@ -177,6 +388,46 @@ mod test {
         assert!((projection.y - 0.5).abs() < 1e-5)
     }
 
+    #[test]
+    fn equirectangular_projection_works() {
+        let sphere = simple_sphere().with_projection(SphereProjection::Equirectangular);
+        let texel = sphere.project_texel(&Point::new(0., 0., 1.));
+        assert!((texel.x - 0.25).abs() < 1e-5);
+        assert!((texel.y - 0.5).abs() < 1e-5);
+        let texel = sphere.project_texel(&Point::new(0., -1., 0.));
+        assert!(texel.y.abs() < 1e-5)
+    }
+
+    #[test]
+    fn cube_projection_works() {
+        let sphere = simple_sphere().with_projection(SphereProjection::Cube);
+        // The middle of each face, in the order they are laid out
+        let faces = [
+            (Point::new(1., 0., 0.), Point2D::new(1. / 6., 0.75)),
+            (Point::new(-1., 0., 0.), Point2D::new(0.5, 0.75)),
+            (Point::new(0., 1., 0.), Point2D::new(5. / 6., 0.75)),
+            (Point::new(0., -1., 0.), Point2D::new(1. / 6., 0.25)),
+            (Point::new(0., 0., 1.), Point2D::new(0.5, 0.25)),
+            (Point::new(0., 0., -1.), Point2D::new(5. / 6., 0.25)),
+        ];
+        for (point, expected) in faces.iter() {
+            let texel = sphere.project_texel(point);
+            assert!((texel - expected).norm() < 1e-5, "{} {}", point, texel);
+        }
+        // The top of the +Z face is the edge it shares with the +Y face
+        let texel = sphere.project_texel(&Point::new(0., 0.9999, 1.));
+        assert!((texel - Point2D::new(0.5, 0.5)).norm() < 1e-3)
+    }
+
+    #[test]
+    fn polar_projection_works() {
+        let sphere = simple_sphere().with_projection(SphereProjection::Polar);
+        let texel = sphere.project_texel(&Point::new(1., 0., 0.));
+        assert!((texel - Point2D::new(0.75, 0.5)).norm() < 1e-5);
+        let texel = sphere.project_texel(&Point::new(0., -1., 0.));
+        assert!(((texel - Point2D::new(0.5, 0.5)).norm() - 0.5).abs() < 1e-5)
+    }
+
     #[test]
     fn deserialization_works() {
         let yaml = r#"
@@ -187,4 +438,18 @@ mod test {
         let sphere: Sphere = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(sphere, Sphere::new(Point::new(0.5, 1.0, 2.0), 2.5))
     }
+
+    #[test]
+    fn projection_deserialization_works() {
+        let yaml = r#"
+            center: [0.0, 0.0, 0.0]
+            radius: 1.0
+            projection: equirectangular
+        "#;
+        let sphere: Sphere = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            sphere,
+            simple_sphere().with_projection(SphereProjection::Equirectangular)
+        )
+    }
 }
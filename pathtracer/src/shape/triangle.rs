@@ -1,9 +1,10 @@
-use super::Shape;
-use crate::{Point, Point2D, Vector};
+use super::{Hit, Shape};
+use crate::{Float, Point, Point2D, Vector};
 use beevee::aabb::AABB;
 use beevee::ray::Ray;
+use beevee::Axis;
 use nalgebra::Unit;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// Represent a triangle inside the scene.
 #[derive(Clone, Debug, PartialEq)]
@@ -11,6 +12,8 @@ pub struct Triangle {
     c0: Point,
     c0c1: Vector,
     c0c2: Vector,
+    texcoords: Option<[Point2D; 3]>,
+    normals: Option<[Unit<Vector>; 3]>,
 }
 
 impl Triangle {
@@ -35,9 +38,137 @@ impl Triangle {
             c0,
             c0c1: c1 - c0,
             c0c2: c2 - c0,
+            texcoords: None,
+            normals: None,
         }
     }
 
+    /// Use the given texture coordinates at each of the `Triangle`'s corners, interpolating them
+    /// over its surface, instead of its barycentric coordinates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::{Shape, Triangle};
+    /// # use pathtracer::{Point, Point2D};
+    /// #
+    /// let t = Triangle::new(
+    ///     Point::new(0.0, 0.0, 0.0),
+    ///     Point::new(1.0, 0.0, 0.0),
+    ///     Point::new(0.0, 1.0, 0.0),
+    /// )
+    /// .with_texcoords([
+    ///     Point2D::new(0.5, 0.5),
+    ///     Point2D::new(1.0, 0.5),
+    ///     Point2D::new(0.5, 1.0),
+    /// ]);
+    /// assert_eq!(t.project_texel(&Point::new(1.0, 0.0, 0.0)), Point2D::new(1.0, 0.5));
+    /// ```
+    pub fn with_texcoords(mut self, texcoords: [Point2D; 3]) -> Self {
+        self.texcoords = Some(texcoords);
+        self
+    }
+
+    /// Use the given normals at each of the `Triangle`'s corners, interpolating them over its
+    /// surface to shade it smoothly, instead of its flat normal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::{Shape, Triangle};
+    /// # use pathtracer::{Point, Vector};
+    /// #
+    /// let t = Triangle::new(
+    ///     Point::new(0.0, 0.0, 0.0),
+    ///     Point::new(1.0, 0.0, 0.0),
+    ///     Point::new(0.0, 1.0, 0.0),
+    /// )
+    /// .with_normals([Vector::z_axis(), Vector::x_axis(), Vector::y_axis()]);
+    /// assert_eq!(t.normal(&Point::new(1.0, 0.0, 0.0)), Vector::x_axis());
+    /// ```
+    pub fn with_normals(mut self, normals: [Unit<Vector>; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    /// Get the three corners of the `Triangle`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::shape::Triangle;
+    /// # use pathtracer::Point;
+    /// #
+    /// let corners = [
+    ///     Point::new(1.0, 0.0, 0.0),
+    ///     Point::new(0.0, 1.0, 0.0),
+    ///     Point::new(0.0, 0.0, 1.0),
+    /// ];
+    /// let t = Triangle::new(corners[0], corners[1], corners[2]);
+    /// assert_eq!(t.corners(), corners);
+    /// ```
+    pub fn corners(&self) -> [Point; 3] {
+        [self.c0, self.c0 + self.c0c1, self.c0 + self.c0c2]
+    }
+
+    /// Get the texture coordinates at each of the `Triangle`'s corners, if it was given any.
+    pub fn texcoords(&self) -> Option<[Point2D; 3]> {
+        self.texcoords
+    }
+
+    /// Get the normals at each of the `Triangle`'s corners, if it was given any.
+    pub fn normals(&self) -> Option<[Unit<Vector>; 3]> {
+        self.normals
+    }
+
+    /// Get the normal at the given barycentric coordinates.
+    fn interpolated_normal(&self, barycentric: Point2D) -> Unit<Vector> {
+        let flat = || Unit::new_normalize(self.c0c1.cross(&self.c0c2));
+        match &self.normals {
+            Some([n0, n1, n2]) => Unit::try_new(
+                n0.as_ref() * (1. - barycentric.x - barycentric.y)
+                    + n1.as_ref() * barycentric.x
+                    + n2.as_ref() * barycentric.y,
+                0.,
+            )
+            .unwrap_or_else(flat),
+            None => flat(),
+        }
+    }
+
+    /// Get the texture coordinates at the given barycentric coordinates.
+    fn texel(&self, barycentric: Point2D) -> Point2D {
+        match &self.texcoords {
+            Some([t0, t1, t2]) => Point2D::from(
+                t0.coords * (1. - barycentric.x - barycentric.y)
+                    + t1.coords * barycentric.x
+                    + t2.coords * barycentric.y,
+            ),
+            None => barycentric,
+        }
+    }
+
+    /// Return the distance from the point to the closest point of the `Triangle`.
+    pub(crate) fn distance_to_point(&self, point: Point) -> Float {
+        let barycentric = self.barycentric(&point);
+        let (u, v) = (barycentric.x, barycentric.y);
+        if u >= 0. && v >= 0. && u + v <= 1. {
+            // The point is right above the triangle
+            let normal = self.c0c1.cross(&self.c0c2).normalize();
+            return (point - self.c0).dot(&normal).abs();
+        }
+        // Otherwise its closest point is on one of the edges
+        let [c0, c1, c2] = self.corners();
+        let segment = |start: Point, end: Point| {
+            let edge = end - start;
+            let t = ((point - start).dot(&edge) / edge.norm_squared()).clamp(0., 1.);
+            // Edges of degenerate triangles can be reduced to a point
+            let t = if t.is_nan() { 0. } else { t };
+            (point - (start + edge * t)).norm()
+        };
+        segment(c0, c1).min(segment(c1, c2)).min(segment(c2, c0))
+    }
+
     fn barycentric(&self, point: &Point) -> Point2D {
         let c0_pos = point - self.c0;
         // P - A  =  u * (B - A) + v * (C - A)
@@ -58,7 +189,7 @@ impl Triangle {
 }
 
 impl Shape for Triangle {
-    fn intersect(&self, ray: &Ray) -> Option<f32> {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
         let pvec = ray.direction.cross(&self.c0c2);
         let det = self.c0c1.dot(&pvec);
 
@@ -85,16 +216,25 @@ impl Shape for Triangle {
         if t < 0. {
             None
         } else {
-            Some(t)
+            // The barycentric coordinates are known from the intersection test
+            let barycentric = Point2D::new(u, v);
+            Some(Hit::new(
+                t,
+                self.interpolated_normal(barycentric),
+                self.texel(barycentric),
+            ))
         }
     }
 
-    fn normal(&self, _: &Point) -> Unit<Vector> {
-        Unit::new_normalize(self.c0c1.cross(&self.c0c2))
+    fn normal(&self, point: &Point) -> Unit<Vector> {
+        match self.normals {
+            Some(_) => self.interpolated_normal(self.barycentric(point)),
+            None => Unit::new_normalize(self.c0c1.cross(&self.c0c2)),
+        }
     }
 
     fn project_texel(&self, point: &Point) -> Point2D {
-        self.barycentric(point)
+        self.texel(self.barycentric(point))
     }
 
     fn aabb(&self) -> AABB {
@@ -107,20 +247,78 @@ impl Shape for Triangle {
     fn centroid(&self) -> Point {
         self.c0 + (self.c0c1 + self.c0c2) / 2.
     }
+
+    fn clipped_aabb(&self, clip: &AABB) -> AABB {
+        // Clip the triangle's polygon against each plane of the box, in turn
+        let mut polygon = vec![self.c0, self.c0 + self.c0c1, self.c0 + self.c0c2];
+        for &axis in [Axis::X, Axis::Y, Axis::Z].iter() {
+            polygon = clip_polygon(&polygon, |p| p[axis] - clip.low[axis]);
+            polygon = clip_polygon(&polygon, |p| clip.high[axis] - p[axis]);
+        }
+        // Rounding errors can put the points slightly outside of the box
+        polygon
+            .iter()
+            .fold(AABB::empty(), |acc, p| acc.grow(p))
+            .intersection(clip)
+    }
 }
 
-#[derive(Debug, Deserialize)]
+/// Keep the part of a convex polygon on the side of a plane where `distance` is positive.
+fn clip_polygon<F: Fn(&Point) -> Float>(polygon: &[Point], distance: F) -> Vec<Point> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, current) in polygon.iter().enumerate() {
+        let next = &polygon[(i + 1) % polygon.len()];
+        let (dist_current, dist_next) = (distance(current), distance(next));
+        if dist_current >= 0. {
+            clipped.push(*current);
+        }
+        // Add the point where the edge crosses the plane
+        if (dist_current >= 0.) != (dist_next >= 0.) {
+            let t = dist_current / (dist_current - dist_next);
+            clipped.push(current + (next - current) * t);
+        }
+    }
+    clipped
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 struct SerializedTriangle {
     corners: [Point; 3],
+    #[serde(default)]
+    texcoords: Option<[Point2D; 3]>,
+    #[serde(default)]
+    normals: Option<[Vector; 3]>,
 }
 
 impl From<SerializedTriangle> for Triangle {
     fn from(triangle: SerializedTriangle) -> Self {
-        Triangle::new(
+        let ans = Triangle::new(
             triangle.corners[0],
             triangle.corners[1],
             triangle.corners[2],
-        )
+        );
+        let ans = match triangle.texcoords {
+            Some(texcoords) => ans.with_texcoords(texcoords),
+            None => ans,
+        };
+        match triangle.normals {
+            Some(normals) => ans.with_normals(normals.map(Unit::new_normalize)),
+            None => ans,
+        }
+    }
+}
+
+impl From<&Triangle> for SerializedTriangle {
+    fn from(triangle: &Triangle) -> Self {
+        SerializedTriangle {
+            corners: [
+                triangle.c0,
+                triangle.c0 + triangle.c0c1,
+                triangle.c0 + triangle.c0c2,
+            ],
+            texcoords: triangle.texcoords,
+            normals: (triangle.normals).map(|normals| normals.map(Unit::into_inner)),
+        }
     }
 }
 
@@ -134,6 +332,15 @@ impl<'de> Deserialize<'de> for Triangle {
     }
 }
 
+impl Serialize for Triangle {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        SerializedTriangle::from(self).serialize(serializer)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -147,6 +354,19 @@ mod test {
         )
     }
 
+    #[test]
+    fn clipped_aabb_works() {
+        let triangle = simple_triangle();
+        let clip = AABB::with_bounds(Point::new(-1., 0., 0.), Point::new(1., 0.5, 1.));
+        let expected = AABB::with_bounds(Point::origin(), Point::new(0., 0.5, 0.5));
+        assert_eq!(triangle.clipped_aabb(&clip), expected);
+        // Tighter than the clipped bounding box
+        let loose = AABB::with_bounds(Point::origin(), Point::new(0., 0.5, 1.));
+        assert_eq!(triangle.aabb().intersection(&clip), loose);
+        let far = AABB::with_bounds(Point::new(1., 0., 0.), Point::new(2., 1., 1.));
+        assert!(triangle.clipped_aabb(&far).is_empty());
+    }
+
     #[test]
     fn intersect_along_normal_works() {
         let triangle = simple_triangle();
@@ -154,7 +374,7 @@ mod test {
             Point::new(-1., 0.5, 0.5),
             Unit::new_normalize(Vector::new(1., 0., 0.)),
         ));
-        assert_eq!(ans, Some(1.0))
+        assert_eq!(ans.map(|hit| hit.distance), Some(1.0))
     }
 
     #[test]
@@ -165,7 +385,7 @@ mod test {
             Unit::new_normalize(Vector::new(1., 0., 0.5)),
         ));
         assert!(ans.is_some());
-        assert!((ans.unwrap() - f32::sqrt(1.0 + 0.25)).abs() < 1e-5)
+        assert!((ans.unwrap().distance - Float::sqrt(1.0 + 0.25)).abs() < 1e-5)
     }
 
     #[test]
@@ -178,6 +398,19 @@ mod test {
         assert_eq!(ans, None)
     }
 
+    #[test]
+    fn intersect_hit_works() {
+        let triangle = simple_triangle();
+        let hit = triangle
+            .intersect(&Ray::new(
+                Point::new(-1., 0.5, 0.5),
+                Unit::new_normalize(Vector::new(1., 0., 0.)),
+            ))
+            .unwrap();
+        assert_eq!(hit.normal, triangle.normal(&Point::new(0., 0.5, 0.5)));
+        assert!((hit.uv - triangle.project_texel(&Point::new(0., 0.5, 0.5))).norm() < 1e-5)
+    }
+
     #[test]
     fn normal_works() {
         let triangle = simple_triangle();
@@ -209,19 +442,19 @@ mod test {
     #[test]
     fn project_texel_works_4() {
         let triangle = Triangle::new(
-            Point::new(0., f32::sqrt(3.) / 2., 0.),
+            Point::new(0., Float::sqrt(3.) / 2., 0.),
             Point::new(-0.5, 0., 0.),
             Point::new(0.5, 0., 0.),
         );
         // The centroid is at a third of the length of the height of the triangle
-        let ans = triangle.project_texel(&Point::new(0., f32::sqrt(3.) / 6., 0.));
+        let ans = triangle.project_texel(&Point::new(0., Float::sqrt(3.) / 6., 0.));
         assert!((ans - Point2D::new(1. / 3., 1. / 3.)).norm() < 1e-5);
     }
 
     #[test]
     fn project_texel_works_5() {
         let triangle = Triangle::new(
-            Point::new(0., f32::sqrt(3.) / 2., 0.),
+            Point::new(0., Float::sqrt(3.) / 2., 0.),
             Point::new(-0.5, 0., 0.),
             Point::new(0.5, 0., 0.),
         );
@@ -230,6 +463,50 @@ mod test {
         assert!((ans - Point2D::new(0.5, 0.5)).norm() < 1e-5);
     }
 
+    #[test]
+    fn texcoords_are_interpolated() {
+        let triangle = simple_triangle().with_texcoords([
+            Point2D::new(0., 0.),
+            Point2D::new(2., 0.),
+            Point2D::new(0., 4.),
+        ]);
+        let hit = triangle
+            .intersect(&Ray::new(
+                Point::new(-1., 0.5, 0.5),
+                Unit::new_normalize(Vector::new(1., 0., 0.)),
+            ))
+            .unwrap();
+        assert!((hit.uv - Point2D::new(1., 0.)).norm() < 1e-5);
+        let ans = triangle.project_texel(&Point::new(0., 1., 0.));
+        assert!((ans - Point2D::new(0., 4.)).norm() < 1e-5)
+    }
+
+    #[test]
+    fn normals_are_interpolated() {
+        let triangle =
+            simple_triangle().with_normals([Vector::x_axis(), Vector::y_axis(), Vector::z_axis()]);
+        let hit = triangle
+            .intersect(&Ray::new(
+                Point::new(-1., 0.5, 0.5),
+                Unit::new_normalize(Vector::new(1., 0., 0.)),
+            ))
+            .unwrap();
+        let expected = Vector::new(1., 1., 0.).normalize();
+        assert!((hit.normal.as_ref() - expected).norm() < 1e-5);
+        let normal = triangle.normal(&Point::new(0., 1., 0.));
+        assert!((normal.as_ref() - Vector::z()).norm() < 1e-5)
+    }
+
+    #[test]
+    fn distance_to_point_works() {
+        let triangle = simple_triangle();
+        // Above the triangle, beside an edge, and beside a corner
+        let distance = |point| triangle.distance_to_point(point);
+        assert!((distance(Point::new(1., 0.75, 0.25)) - 1.).abs() < 1e-6);
+        assert!((distance(Point::new(0., 0.5, -1.)) - 1.).abs() < 1e-6);
+        assert!((distance(Point::new(0., 2., 2.)) - (2. as Float).sqrt()).abs() < 1e-6);
+    }
+
     #[test]
     fn deserialization_works() {
         let yaml = r#"
@@ -248,4 +525,18 @@ mod test {
             )
         )
     }
+
+    #[test]
+    fn serialization_works() {
+        let triangle = simple_triangle()
+            .with_texcoords([
+                Point2D::new(0., 0.),
+                Point2D::new(1., 0.),
+                Point2D::new(0., 1.),
+            ])
+            .with_normals([Vector::x_axis(), Vector::y_axis(), Vector::z_axis()]);
+        let yaml = serde_yaml::to_string(&triangle).unwrap();
+        let deserialized: Triangle = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(deserialized, triangle)
+    }
 }
@@ -0,0 +1,379 @@
+//! Golden-image regression testing helpers
+//!
+//! Renders small canonical scenes with a fixed seed and compares them against reference images
+//! embedded in the crate, so that changes to shapes, materials or the renderer can be checked for
+//! unintended differences. The same comparison helpers can be used against your own references.
+
+use crate::render::Scene;
+use crate::Float;
+use image::RgbImage;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// The environment variable which, when set, overwrites references instead of comparing to them.
+pub const BLESS_VAR: &str = "PATHTRACER_BLESS";
+
+/// How different an image is allowed to be from its reference.
+///
+/// Small differences are expected between platforms, because of the rounding behaviour of
+/// trigonometric functions and compiler optimisations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    /// The maximum difference allowed on a single channel before a pixel is considered different.
+    pub max_difference: u8,
+    /// The maximum ratio of pixels which are allowed to be different, between 0.0 and 1.0.
+    pub max_mismatched_ratio: Float,
+}
+
+impl Tolerance {
+    /// A tolerance which does not allow any difference.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::testing::Tolerance;
+    /// #
+    /// let exact = Tolerance::exact();
+    /// assert_eq!(exact.max_difference, 0);
+    /// assert_eq!(exact.max_mismatched_ratio, 0.0);
+    /// ```
+    pub fn exact() -> Self {
+        Tolerance {
+            max_difference: 0,
+            max_mismatched_ratio: 0.,
+        }
+    }
+}
+
+impl Default for Tolerance {
+    /// Allow off-by-a-few rounding differences on at most one pixel in a thousand.
+    fn default() -> Self {
+        Tolerance {
+            max_difference: 3,
+            max_mismatched_ratio: 0.001,
+        }
+    }
+}
+
+/// The reason why an image did not match its reference.
+#[derive(Debug)]
+pub enum GoldenError {
+    /// The image and its reference do not have the same dimensions.
+    SizeMismatch {
+        /// The (width, height) of the reference.
+        expected: (u32, u32),
+        /// The (width, height) of the rendered image.
+        actual: (u32, u32),
+    },
+    /// Too many pixels were different from the reference.
+    PixelMismatch {
+        /// How many pixels were outside of tolerance.
+        mismatched: usize,
+        /// The total number of pixels in the image.
+        total: usize,
+        /// The largest difference found on a single channel.
+        max_difference: u8,
+    },
+    /// The reference could not be read or written.
+    Image(image::ImageError),
+}
+
+impl Display for GoldenError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            GoldenError::SizeMismatch { expected, actual } => write!(
+                f,
+                "expected a {}x{} image, got {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            GoldenError::PixelMismatch {
+                mismatched,
+                total,
+                max_difference,
+            } => write!(
+                f,
+                "{}/{} pixels are different from the reference (max channel difference: {})",
+                mismatched, total, max_difference
+            ),
+            GoldenError::Image(err) => write!(f, "could not use reference image: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+impl From<image::ImageError> for GoldenError {
+    fn from(err: image::ImageError) -> Self {
+        GoldenError::Image(err)
+    }
+}
+
+/// Compare an image to its reference, within the given [`Tolerance`].
+///
+/// [`Tolerance`]: struct.Tolerance.html
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::testing::{compare, Tolerance};
+/// use image::{Rgb, RgbImage};
+///
+/// let reference = RgbImage::from_pixel(4, 4, Rgb([128, 128, 128]));
+/// let mut image = reference.clone();
+/// image.put_pixel(0, 0, Rgb([129, 128, 128]));
+///
+/// assert!(compare(&image, &reference, &Tolerance::default()).is_ok());
+/// assert!(compare(&image, &reference, &Tolerance::exact()).is_err());
+/// ```
+pub fn compare(
+    image: &RgbImage,
+    reference: &RgbImage,
+    tolerance: &Tolerance,
+) -> Result<(), GoldenError> {
+    if image.dimensions() != reference.dimensions() {
+        return Err(GoldenError::SizeMismatch {
+            expected: reference.dimensions(),
+            actual: image.dimensions(),
+        });
+    }
+    let differences: Vec<u8> = image
+        .pixels()
+        .zip(reference.pixels())
+        .map(|(lhs, rhs)| {
+            lhs.0
+                .iter()
+                .zip(rhs.0.iter())
+                .map(|(l, r)| l.max(r) - l.min(r))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mismatched = differences
+        .iter()
+        .filter(|&&diff| diff > tolerance.max_difference)
+        .count();
+    let total = differences.len();
+    if mismatched as Float > tolerance.max_mismatched_ratio * total as Float {
+        Err(GoldenError::PixelMismatch {
+            mismatched,
+            total,
+            max_difference: differences.into_iter().max().unwrap_or(0),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Compare an image to a reference PNG file on disk.
+///
+/// When the `PATHTRACER_BLESS` environment variable is set, the reference is overwritten with the
+/// image instead, which is how new references should be recorded.
+pub fn check_reference_file<P: AsRef<Path>>(
+    image: &RgbImage,
+    path: P,
+    tolerance: &Tolerance,
+) -> Result<(), GoldenError> {
+    if std::env::var_os(BLESS_VAR).is_some() {
+        image.save(path)?;
+        return Ok(());
+    }
+    let reference = image::open(path)?.to_rgb8();
+    compare(image, &reference, tolerance)
+}
+
+/// Compute a stable 64-bit FNV-1a hash of an image's dimensions and pixels.
+///
+/// Useful to detect any change in the output of a renderer when exact reproducibility is
+/// expected, e.g: on a single platform.
+///
+/// # Examples
+///
+/// ```
+/// # use pathtracer::testing::image_hash;
+/// use image::{Rgb, RgbImage};
+///
+/// let black = RgbImage::new(2, 2);
+/// let white = RgbImage::from_pixel(2, 2, Rgb([255, 255, 255]));
+/// assert_eq!(image_hash(&black), image_hash(&black.clone()));
+/// assert_ne!(image_hash(&black), image_hash(&white));
+/// ```
+pub fn image_hash(image: &RgbImage) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let (width, height) = image.dimensions();
+    width
+        .to_le_bytes()
+        .iter()
+        .chain(height.to_le_bytes().iter())
+        .chain(image.as_raw().iter())
+        .fold(OFFSET_BASIS, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+        })
+}
+
+/// A canonical scene along with its reference rendering.
+#[derive(Clone, Copy, Debug)]
+pub struct GoldenScene {
+    /// The name of the scene.
+    pub name: &'static str,
+    /// The YAML description of the scene.
+    pub description: &'static str,
+    /// The PNG-encoded reference image.
+    pub reference: &'static [u8],
+    /// The seed used when rendering the scene.
+    pub seed: u64,
+}
+
+impl GoldenScene {
+    /// Load the [`Scene`] being described.
+    ///
+    /// [`Scene`]: ../render/scene/struct.Scene.html
+    pub fn scene(&self) -> Scene {
+        serde_yaml::from_str(self.description).expect("canonical scenes are valid")
+    }
+
+    /// Render the scene with its fixed seed.
+    pub fn render(&self) -> RgbImage {
+        self.scene().render_with_seed(self.seed)
+    }
+
+    /// Decode the embedded reference image.
+    pub fn reference_image(&self) -> RgbImage {
+        image::load_from_memory(self.reference)
+            .expect("canonical references are valid PNGs")
+            .to_rgb8()
+    }
+
+    /// Render the scene and compare it to its reference, within the given [`Tolerance`].
+    ///
+    /// [`Tolerance`]: struct.Tolerance.html
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::testing::{canonical_scenes, Tolerance};
+    /// #
+    /// for golden in canonical_scenes() {
+    ///     golden.check(&Tolerance::default()).unwrap();
+    /// }
+    /// ```
+    pub fn check(&self, tolerance: &Tolerance) -> Result<(), GoldenError> {
+        compare(&self.render(), &self.reference_image(), tolerance)
+    }
+}
+
+/// The directory holding the references, which depend on the floating point type: rendering with
+/// `f64` rounds differently and draws different random samples than with `f32`.
+#[cfg(not(feature = "f64"))]
+macro_rules! references {
+    () => {
+        "golden/"
+    };
+}
+#[cfg(feature = "f64")]
+macro_rules! references {
+    () => {
+        "golden/f64/"
+    };
+}
+
+macro_rules! golden_scene {
+    ($name:literal, $seed:expr) => {
+        GoldenScene {
+            name: $name,
+            description: include_str!(concat!("golden/", $name, ".yaml")),
+            reference: include_bytes!(concat!(references!(), $name, ".png")),
+            seed: $seed,
+        }
+    };
+}
+
+const CANONICAL_SCENES: &[GoldenScene] = &[
+    golden_scene!("shading", 42),
+    golden_scene!("reflections", 42),
+    golden_scene!("geometry", 42),
+];
+
+/// Return the list of canonical scenes shipped with the crate.
+pub fn canonical_scenes() -> &'static [GoldenScene] {
+    CANONICAL_SCENES
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn compare_identical_works() {
+        let image = RgbImage::from_pixel(4, 4, Rgb([12, 34, 56]));
+        assert!(compare(&image, &image.clone(), &Tolerance::exact()).is_ok())
+    }
+
+    #[test]
+    fn compare_size_mismatch() {
+        let image = RgbImage::new(4, 4);
+        let reference = RgbImage::new(4, 2);
+        match compare(&image, &reference, &Tolerance::default()) {
+            Err(GoldenError::SizeMismatch { expected, actual }) => {
+                assert_eq!(expected, (4, 2));
+                assert_eq!(actual, (4, 4));
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn compare_pixel_mismatch() {
+        let reference = RgbImage::new(4, 4);
+        let mut image = reference.clone();
+        image.put_pixel(1, 2, Rgb([0, 10, 0]));
+        match compare(&image, &reference, &Tolerance::default()) {
+            Err(GoldenError::PixelMismatch {
+                mismatched,
+                total,
+                max_difference,
+            }) => {
+                assert_eq!(mismatched, 1);
+                assert_eq!(total, 16);
+                assert_eq!(max_difference, 10);
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn compare_within_ratio_works() {
+        let reference = RgbImage::new(4, 4);
+        let mut image = reference.clone();
+        image.put_pixel(1, 2, Rgb([0, 10, 0]));
+        let tolerance = Tolerance {
+            max_difference: 0,
+            max_mismatched_ratio: 0.1,
+        };
+        assert!(compare(&image, &reference, &tolerance).is_ok())
+    }
+
+    #[test]
+    fn rendering_is_reproducible() {
+        let golden = &canonical_scenes()[0];
+        let first = golden.render();
+        let second = golden.render();
+        assert_eq!(image_hash(&first), image_hash(&second));
+    }
+
+    #[test]
+    fn canonical_scenes_match_references() {
+        for golden in canonical_scenes() {
+            let path = format!(
+                "{}/src/testing/{}{}.png",
+                env!("CARGO_MANIFEST_DIR"),
+                references!(),
+                golden.name
+            );
+            if let Err(err) = check_reference_file(&golden.render(), path, &Tolerance::default()) {
+                panic!("golden scene '{}' changed: {}", golden.name, err)
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use super::{Texture, TextureEnum};
+use crate::core::sampling;
+use crate::shape::Shape;
+use crate::{Float, Point, Point2D, Vector};
+use nalgebra::{Unit, Vector2};
+use serde::{Deserialize, Serialize};
+
+/// The step used for finite differences, both in texel and world space.
+const EPSILON: Float = 1e-3;
+
+/// A grayscale height texture perturbing the shading normal of an object, to give the appearance
+/// of small bumps without modifying its geometry.
+///
+/// The height at a texel is the luminance of the texture's color.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct BumpMap {
+    /// The height texture.
+    texture: TextureEnum,
+    /// The factor applied to the height.
+    #[serde(default = "crate::serialize::default_identity")]
+    strength: Float,
+}
+
+impl BumpMap {
+    /// Creates a new `BumpMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::texture::{BumpMap, UniformTexture};
+    /// #
+    /// let bump = BumpMap::new(UniformTexture::new(LinearColor::black()).into(), 0.5);
+    /// ```
+    pub fn new(texture: TextureEnum, strength: Float) -> Self {
+        BumpMap { texture, strength }
+    }
+
+    /// Perturb the normal of a shape at a point of its surface, following the gradient of the
+    /// height texture.
+    pub fn perturb(&self, shape: &dyn Shape, point: &Point, normal: Unit<Vector>) -> Unit<Vector> {
+        let height = |uv| self.strength * self.texture.texel_color(uv).luminance();
+        perturb_normal(height, shape, point, normal)
+    }
+}
+
+fn perturb_normal(
+    height: impl Fn(Point2D) -> Float,
+    shape: &dyn Shape,
+    point: &Point,
+    normal: Unit<Vector>,
+) -> Unit<Vector> {
+    let uv = shape.project_texel(point);
+    // The gradient of the height in texel space
+    let (step_u, step_v) = (Vector2::x() * EPSILON, Vector2::y() * EPSILON);
+    let dh_du = (height(uv + step_u) - height(uv - step_u)) / (2. * EPSILON);
+    let dh_dv = (height(uv + step_v) - height(uv - step_v)) / (2. * EPSILON);
+
+    let (tangent, bitangent) = sampling::orthonormal_basis(&normal);
+
+    // Chain the texel space gradient with the change of texel coordinates along the surface
+    let slope = |direction: &Vector| {
+        let delta = shape.project_texel(&(point + direction * EPSILON)) - uv;
+        // Texel coordinates may wrap around, only keep the local variation
+        let (du, dv) = (delta.x - delta.x.round(), delta.y - delta.y.round());
+        (dh_du * du + dh_dv * dv) / EPSILON
+    };
+    let gradient = tangent * slope(&tangent) + bitangent * slope(&bitangent);
+    Unit::new_normalize(normal.as_ref() - gradient)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::LinearColor;
+    use crate::shape::{Plane, Sphere};
+    use crate::texture::UniformTexture;
+
+    #[test]
+    fn new_works() {
+        let texture: TextureEnum = UniformTexture::new(LinearColor::black()).into();
+        assert_eq!(
+            BumpMap::new(texture.clone(), 0.5),
+            BumpMap {
+                texture,
+                strength: 0.5
+            }
+        )
+    }
+
+    #[test]
+    fn flat_height_keeps_normal() {
+        let bump = BumpMap::new(UniformTexture::new(LinearColor::new(1., 1., 1.)).into(), 1.);
+        let sphere = Sphere::new(Point::origin(), 1.);
+        let point = Point::new(0., 0., -1.);
+        let normal = sphere.normal(&point);
+        assert_eq!(bump.perturb(&sphere, &point, normal), normal);
+    }
+
+    #[test]
+    fn slope_tilts_normal() {
+        let plane = Plane::new(Point::origin(), Vector::y());
+        let point = Point::new(0.3, 0., 0.6);
+        let normal = Vector::y_axis();
+        let uv = plane.project_texel(&point);
+        let direction = plane.project_texel(&(point + Vector::x() * 0.01)) - uv;
+        // The height increases along the X axis
+        let height = |texel: Point2D| (texel - uv).dot(&direction) * 100.;
+        let perturbed = perturb_normal(height, &plane, &point, normal);
+        assert!(perturbed.x < -0.5);
+        assert!(perturbed.y > 0.);
+        assert!(perturbed.z.abs() < 1e-2);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            texture:
+              type: uniform
+              color: {r: 0.5, g: 0.5, b: 0.5}
+            strength: 0.1
+        "#;
+        let bump: BumpMap = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            bump,
+            BumpMap::new(
+                UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5)).into(),
+                0.1
+            )
+        )
+    }
+}
@@ -0,0 +1,130 @@
+use super::{SurfacePoint, Texture, TextureEnum};
+use crate::core::LinearColor;
+use crate::{Float, Point2D};
+use serde::{Deserialize, Serialize};
+
+/// The component of a color read by a [`ChannelTexture`].
+///
+/// [`ChannelTexture`]: struct.ChannelTexture.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// The relative luminance of the color.
+    #[default]
+    Luminance,
+    /// The red component.
+    Red,
+    /// The green component.
+    Green,
+    /// The blue component.
+    Blue,
+}
+
+impl Channel {
+    /// Read the channel of a color.
+    pub fn of(self, color: &LinearColor) -> Float {
+        match self {
+            Channel::Luminance => color.luminance(),
+            Channel::Red => color.r,
+            Channel::Green => color.g,
+            Channel::Blue => color.b,
+        }
+    }
+}
+
+/// A grayscale texture reading a single channel of another one, e.g: to use the roughness and
+/// metallic parameters packed in the green and blue channels of a glTF texture.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ChannelTexture {
+    /// The texture whose channel is read.
+    texture: Box<TextureEnum>,
+    /// The channel which is read.
+    #[serde(default)]
+    channel: Channel,
+}
+
+impl ChannelTexture {
+    /// Creates a new `ChannelTexture`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::texture::{Channel, ChannelTexture, Texture, UniformTexture};
+    /// # use pathtracer::Point2D;
+    /// #
+    /// let packed = UniformTexture::new(LinearColor::new(1., 0.25, 0.75));
+    /// let roughness = ChannelTexture::new(packed.into(), Channel::Green);
+    /// assert_eq!(roughness.texel_value(Point2D::origin()), 0.25);
+    /// assert_eq!(
+    ///     roughness.texel_color(Point2D::origin()),
+    ///     LinearColor::new(0.25, 0.25, 0.25)
+    /// );
+    /// ```
+    pub fn new(texture: TextureEnum, channel: Channel) -> Self {
+        ChannelTexture {
+            texture: Box::new(texture),
+            channel,
+        }
+    }
+
+    fn grey(&self, color: LinearColor) -> LinearColor {
+        let value = self.channel.of(&color);
+        LinearColor::new(value, value, value)
+    }
+}
+
+impl Texture for ChannelTexture {
+    fn texel_color(&self, point: Point2D) -> LinearColor {
+        self.grey(self.texture.texel_color(point))
+    }
+
+    fn texel_value(&self, point: Point2D) -> Float {
+        self.channel.of(&self.texture.texel_color(point))
+    }
+
+    fn filtered_color(&self, point: Point2D, footprint: Float) -> LinearColor {
+        self.grey(self.texture.filtered_color(point, footprint))
+    }
+
+    fn surface_color(&self, point: &SurfacePoint) -> LinearColor {
+        self.grey(self.texture.surface_color(point))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::texture::UniformTexture;
+
+    fn packed() -> TextureEnum {
+        UniformTexture::new(LinearColor::new(0.5, 0.25, 1.)).into()
+    }
+
+    #[test]
+    fn channels_work() {
+        let value = |channel| ChannelTexture::new(packed(), channel).texel_value(Point2D::origin());
+        assert_eq!(value(Channel::Red), 0.5);
+        assert_eq!(value(Channel::Green), 0.25);
+        assert_eq!(value(Channel::Blue), 1.);
+        let luminance = LinearColor::new(0.5, 0.25, 1.).luminance();
+        assert_eq!(value(Channel::Luminance), luminance);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            texture: {type: uniform, color: {r: 0.5, g: 0.25, b: 1.0}}
+            channel: blue
+        "#;
+        let texture: ChannelTexture = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(texture, ChannelTexture::new(packed(), Channel::Blue));
+    }
+
+    #[test]
+    fn default_channel_is_luminance() {
+        let yaml = "texture: {type: uniform, color: {r: 0.5, g: 0.25, b: 1.0}}";
+        let texture: ChannelTexture = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(texture, ChannelTexture::new(packed(), Channel::Luminance));
+    }
+}
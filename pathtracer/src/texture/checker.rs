@@ -0,0 +1,147 @@
+use super::Texture;
+use crate::core::LinearColor;
+use crate::{Float, Point2D};
+use serde::{Deserialize, Serialize};
+
+/// A procedural checkerboard, alternating between two colors.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CheckerTexture {
+    /// The color of the squares whose coordinates have an even sum, including the first one.
+    even: LinearColor,
+    /// The color of the other squares.
+    odd: LinearColor,
+    /// The number of squares along each axis of the texel space.
+    #[serde(default = "default_squares")]
+    squares: Float,
+}
+
+fn default_squares() -> Float {
+    8.
+}
+
+impl CheckerTexture {
+    /// Creates a new `CheckerTexture`, with `squares` squares along each axis of the texel space.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::texture::{CheckerTexture, Texture};
+    /// # use pathtracer::Point2D;
+    /// #
+    /// let checker = CheckerTexture::new(LinearColor::black(), LinearColor::new(1., 1., 1.), 2.);
+    /// assert_eq!(checker.texel_color(Point2D::new(0.25, 0.25)), LinearColor::black());
+    /// // Seen from afar, the squares blend together
+    /// let far = checker.filtered_color(Point2D::new(0.25, 0.25), 10.);
+    /// assert!((far.r - 0.5).abs() < 0.1);
+    /// ```
+    pub fn new(even: LinearColor, odd: LinearColor, squares: Float) -> Self {
+        CheckerTexture { even, odd, squares }
+    }
+
+    fn mix(&self, odd_weight: Float) -> LinearColor {
+        self.even.clone() * (1. - odd_weight) + self.odd.clone() * odd_weight
+    }
+}
+
+/// The square wave alternating between 1 and -1 on each unit interval.
+fn square_wave(x: Float) -> Float {
+    if x.floor().rem_euclid(2.) == 0. {
+        1.
+    } else {
+        -1.
+    }
+}
+
+/// The average of the square wave over `[x - width / 2, x + width / 2]`, using its integral which
+/// is a triangle wave.
+fn filtered_square_wave(x: Float, width: Float) -> Float {
+    let integral = |x: Float| 1. - (x.rem_euclid(2.) - 1.).abs();
+    (integral(x + width / 2.) - integral(x - width / 2.)) / width
+}
+
+impl Texture for CheckerTexture {
+    fn texel_color(&self, point: Point2D) -> LinearColor {
+        let parity = square_wave(point.x * self.squares) * square_wave(point.y * self.squares);
+        self.mix((1. - parity) / 2.)
+    }
+
+    fn filtered_color(&self, point: Point2D, footprint: Float) -> LinearColor {
+        let width = footprint * self.squares;
+        // Avoid dividing by a vanishing width
+        if width < 1e-4 {
+            return self.texel_color(point);
+        }
+        // The checkerboard is the product of two square waves, box filtering each one separately
+        let parity = filtered_square_wave(point.x * self.squares, width)
+            * filtered_square_wave(point.y * self.squares, width);
+        self.mix((1. - parity) / 2.)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn simple_checker() -> CheckerTexture {
+        CheckerTexture::new(LinearColor::black(), LinearColor::new(1., 1., 1.), 4.)
+    }
+
+    #[test]
+    fn new_works() {
+        let white = LinearColor::new(1., 1., 1.);
+        assert_eq!(
+            CheckerTexture::new(LinearColor::black(), white.clone(), 4.),
+            CheckerTexture {
+                even: LinearColor::black(),
+                odd: white,
+                squares: 4.,
+            }
+        )
+    }
+
+    #[test]
+    fn texel_color_works() {
+        let checker = simple_checker();
+        let white = LinearColor::new(1., 1., 1.);
+        assert_eq!(
+            checker.texel_color(Point2D::new(0.1, 0.1)),
+            LinearColor::black()
+        );
+        assert_eq!(checker.texel_color(Point2D::new(0.3, 0.1)), white);
+        assert_eq!(
+            checker.texel_color(Point2D::new(0.3, 0.3)),
+            LinearColor::black()
+        );
+        assert_eq!(checker.texel_color(Point2D::new(-0.1, 0.1)), white);
+    }
+
+    #[test]
+    fn filtered_color_converges() {
+        let checker = simple_checker();
+        let point = Point2D::new(0.1, 0.1);
+        // A small footprint inside of a square keeps its color
+        assert_eq!(
+            checker.filtered_color(point, 0.),
+            checker.texel_color(point)
+        );
+        let close = checker.filtered_color(point, 0.01);
+        assert!((close.r - 0.).abs() < 1e-5);
+        // Covering whole squares averages both colors
+        let far = checker.filtered_color(point, 1.);
+        assert!((far.r - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            even: {r: 0.0, g: 0.0, b: 0.0}
+            odd: {r: 1.0, g: 1.0, b: 1.0}
+        "#;
+        let checker: CheckerTexture = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            checker,
+            CheckerTexture::new(LinearColor::black(), LinearColor::new(1., 1., 1.), 8.)
+        )
+    }
+}
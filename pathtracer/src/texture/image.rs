@@ -0,0 +1,454 @@
+use super::Texture;
+use crate::core::{srgb_to_linear, LinearColor};
+use crate::{Error, Float, Point2D, Result};
+use image::{ImageBuffer, Rgb, RgbImage};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// An image whose pixels are linear colors.
+type LinearImage = ImageBuffer<Rgb<Float>, Vec<Float>>;
+
+/// How the 8-bit values of an image are decoded to linear colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSpace {
+    /// Values are encoded with the sRGB transfer function, as most color images are.
+    #[default]
+    Srgb,
+    /// Values are stored linearly, e.g: for roughness or bump maps, which are not colors.
+    Linear,
+}
+
+impl ColorSpace {
+    /// Decode an 8-bit image to linear colors.
+    fn decode(self, image: &RgbImage) -> LinearImage {
+        let table: Vec<Float> = (0..=255)
+            .map(|value| value as Float / 255.)
+            .map(|value| match self {
+                ColorSpace::Srgb => srgb_to_linear(value),
+                ColorSpace::Linear => value,
+            })
+            .collect();
+        let (width, height) = image.dimensions();
+        LinearImage::from_fn(width, height, |x, y| {
+            let [r, g, b] = image.get_pixel(x, y).0;
+            Rgb([table[r as usize], table[g as usize], table[b as usize]])
+        })
+    }
+}
+
+/// A texture read from an image file, repeating itself outside of the `[0, 1]` texel range.
+///
+/// The texel `(0, 0)` is the bottom-left corner of the image. Its pixels are decoded to linear
+/// colors when it is loaded, and a chain of mipmaps, each half the size of the previous one, is
+/// built along with it to filter the texture when seen from afar.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageTexture {
+    /// The image's pixels, as they were read, shared by every copy of the texture.
+    image: Arc<RgbImage>,
+    /// The decoded image, followed by its successive mipmaps down to a single pixel.
+    levels: Arc<Vec<LinearImage>>,
+    /// How the image's pixels are decoded.
+    color_space: ColorSpace,
+    /// The file the image was read from, if any.
+    file: Option<PathBuf>,
+    /// Added to the level of detail computed from the footprints, to sharpen or blur the texture.
+    lod_bias: Float,
+}
+
+impl ImageTexture {
+    /// Creates a new `ImageTexture` from an sRGB image.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use image::{Rgb, RgbImage};
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::texture::{ImageTexture, Texture};
+    /// # use pathtracer::Point2D;
+    /// #
+    /// let texture = ImageTexture::new(RgbImage::from_pixel(2, 2, Rgb([255, 0, 0])));
+    /// assert_eq!(
+    ///     texture.texel_color(Point2D::new(0.5, 0.5)),
+    ///     LinearColor::new(1.0, 0.0, 0.0),
+    /// );
+    /// ```
+    pub fn new(image: RgbImage) -> Self {
+        let color_space = ColorSpace::default();
+        ImageTexture {
+            levels: Arc::new(build_levels(&image, color_space)),
+            image: Arc::new(image),
+            color_space,
+            file: None,
+            lod_bias: 0.,
+        }
+    }
+
+    /// Creates a new `ImageTexture` from the image at `path`, remembering it to write the texture
+    /// back into a scene description.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use pathtracer::texture::ImageTexture;
+    /// #
+    /// let texture = ImageTexture::open("textures/wood.png").unwrap();
+    /// assert_eq!(texture.file().unwrap().to_str(), Some("textures/wood.png"));
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path).map_err(|err| Error::from(err).with_path(path))?;
+        Ok(ImageTexture {
+            file: Some(path.to_path_buf()),
+            ..ImageTexture::new(image.to_rgb8())
+        })
+    }
+
+    /// Set how the image's pixels are decoded, e.g: to read data stored linearly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use image::{Rgb, RgbImage};
+    /// # use pathtracer::texture::{ColorSpace, ImageTexture, Texture};
+    /// # use pathtracer::Point2D;
+    /// #
+    /// let grey = ImageTexture::new(RgbImage::from_pixel(1, 1, Rgb([128, 128, 128])));
+    /// assert!((grey.texel_value(Point2D::origin()) - 0.216).abs() < 1e-3);
+    /// let roughness = grey.with_color_space(ColorSpace::Linear);
+    /// assert!((roughness.texel_value(Point2D::origin()) - 0.502).abs() < 1e-3);
+    /// ```
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        if color_space != self.color_space {
+            self.levels = Arc::new(build_levels(&self.image, color_space));
+            self.color_space = color_space;
+        }
+        self
+    }
+
+    /// Set the bias added to the level of detail at which the texture is sampled when filtered:
+    /// positive values blur it, negative values sharpen it at the cost of some aliasing.
+    pub fn with_lod_bias(mut self, lod_bias: Float) -> Self {
+        self.lod_bias = lod_bias;
+        self
+    }
+
+    /// Get the file the image was read from, if any.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Get the number of levels of detail of the texture, the image itself being the level 0.
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Get the color at a given texel coordinate, interpolating between the pixels of the two
+    /// mipmaps closest to the level of detail `lod`. Each level halves the resolution of the
+    /// previous one, starting with the image at level 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use image::{Rgb, RgbImage};
+    /// # use pathtracer::texture::ImageTexture;
+    /// # use pathtracer::Point2D;
+    /// #
+    /// // A black and white checkerboard
+    /// let image = RgbImage::from_fn(8, 8, |x, y| Rgb([((x + y) % 2 * 255) as u8; 3]));
+    /// let texture = ImageTexture::new(image);
+    /// assert_eq!(texture.levels(), 4);
+    /// // The last level is the average of the whole image
+    /// let far = texture.sample(Point2D::new(0.3, 0.6), 3.);
+    /// assert!((far.r - 0.5).abs() < 0.01);
+    /// ```
+    pub fn sample(&self, point: Point2D, lod: Float) -> LinearColor {
+        let max = (self.levels.len() - 1) as Float;
+        let lod = if lod.is_nan() {
+            0.
+        } else {
+            lod.max(0.).min(max)
+        };
+        let (level, t) = (lod.floor() as usize, lod.fract());
+        let color = self.bilinear(level, point);
+        if t > 0. {
+            color * (1. - t) + self.bilinear(level + 1, point) * t
+        } else {
+            color
+        }
+    }
+
+    /// Interpolate between the four pixels of the given level closest to a texel coordinate.
+    fn bilinear(&self, level: usize, point: Point2D) -> LinearColor {
+        let image = &self.levels[level];
+        let (width, height) = image.dimensions();
+        // Pixel centers are at half-integer coordinates
+        let x = point.x * width as Float - 0.5;
+        let y = (1. - point.y) * height as Float - 0.5;
+        let (tx, ty) = (x - x.floor(), y - y.floor());
+        let pixel = |dx: Float, dy: Float| {
+            // The texture repeats itself
+            let px = (x.floor() + dx).rem_euclid(width as Float) as u32;
+            let py = (y.floor() + dy).rem_euclid(height as Float) as u32;
+            to_color(image.get_pixel(px.min(width - 1), py.min(height - 1)))
+        };
+        let top = pixel(0., 0.) * (1. - tx) + pixel(1., 0.) * tx;
+        let bottom = pixel(0., 1.) * (1. - tx) + pixel(1., 1.) * tx;
+        top * (1. - ty) + bottom * ty
+    }
+}
+
+/// Decode an image, and build its chain of mipmaps after it.
+fn build_levels(image: &RgbImage, color_space: ColorSpace) -> Vec<LinearImage> {
+    let mut levels = vec![color_space.decode(image)];
+    while let Some(mipmap) = downsample(&levels[levels.len() - 1]) {
+        levels.push(mipmap);
+    }
+    levels
+}
+
+/// Halve the size of an image, averaging each square of 2x2 pixels, or return `None` for images
+/// of a single pixel.
+fn downsample(image: &LinearImage) -> Option<LinearImage> {
+    let (width, height) = image.dimensions();
+    if width <= 1 && height <= 1 {
+        return None;
+    }
+    let (half_width, half_height) = ((width / 2).max(1), (height / 2).max(1));
+    Some(LinearImage::from_fn(half_width, half_height, |x, y| {
+        let mut sum = [0.; 3];
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+            // Dimensions of 1 are not halved, use the same pixel twice
+            let px = (2 * x + dx).min(width - 1);
+            let py = (2 * y + dy).min(height - 1);
+            for (sum, channel) in sum.iter_mut().zip(image.get_pixel(px, py).0.iter()) {
+                *sum += channel;
+            }
+        }
+        Rgb([sum[0] / 4., sum[1] / 4., sum[2] / 4.])
+    }))
+}
+
+fn to_color(pixel: &Rgb<Float>) -> LinearColor {
+    let [r, g, b] = pixel.0;
+    LinearColor::new(r, g, b)
+}
+
+impl Texture for ImageTexture {
+    fn texel_color(&self, point: Point2D) -> LinearColor {
+        let image = &self.levels[0];
+        let (width, height) = image.dimensions();
+        let (u, v) = (point.x.rem_euclid(1.), 1. - point.y.rem_euclid(1.));
+        let x = ((u * width as Float) as u32).min(width - 1);
+        let y = ((v * height as Float) as u32).min(height - 1);
+        to_color(image.get_pixel(x, y))
+    }
+
+    fn filtered_color(&self, point: Point2D, footprint: Float) -> LinearColor {
+        if footprint <= 0. {
+            return self.texel_color(point);
+        }
+        // The level at which a pixel of the mipmap is as wide as the footprint
+        let (width, height) = self.image.dimensions();
+        let lod = (footprint * width.max(height) as Float).log2() + self.lod_bias;
+        self.sample(point, lod)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SerializedImageTexture {
+    file: PathBuf,
+    #[serde(default)]
+    color_space: ColorSpace,
+    #[serde(default)]
+    lod_bias: Float,
+}
+
+impl<'de> Deserialize<'de> for ImageTexture {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let texture: SerializedImageTexture = Deserialize::deserialize(deserializer)?;
+        ImageTexture::open(&texture.file)
+            .map(|image| image.with_color_space(texture.color_space))
+            .map(|image| image.with_lod_bias(texture.lod_bias))
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Only textures read from a file can be serialized, referring to that file.
+impl Serialize for ImageTexture {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::Error;
+
+        let file = (self.file.clone())
+            .ok_or_else(|| S::Error::custom("image texture was not read from a file"))?;
+        let (color_space, lod_bias) = (self.color_space, self.lod_bias);
+        SerializedImageTexture {
+            file,
+            color_space,
+            lod_bias,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn simple_texture() -> ImageTexture {
+        // Black on the bottom row, white on the top one
+        let mut image = RgbImage::new(2, 2);
+        image.put_pixel(0, 0, Rgb([255, 255, 255]));
+        image.put_pixel(1, 0, Rgb([255, 255, 255]));
+        ImageTexture::new(image)
+    }
+
+    #[test]
+    fn texel_color_works() {
+        let texture = simple_texture();
+        let white = LinearColor::new(1., 1., 1.);
+        assert_eq!(
+            texture.texel_color(Point2D::new(0.25, 0.25)),
+            LinearColor::black()
+        );
+        assert_eq!(texture.texel_color(Point2D::new(0.75, 0.75)), white);
+        // The bottom edge is clamped to the last row of the image
+        assert_eq!(
+            texture.texel_color(Point2D::new(0., 0.)),
+            LinearColor::black()
+        );
+    }
+
+    #[test]
+    fn texel_color_repeats() {
+        let texture = simple_texture();
+        assert_eq!(
+            texture.texel_color(Point2D::new(3.25, -0.25)),
+            texture.texel_color(Point2D::new(0.25, 0.75))
+        );
+    }
+
+    #[test]
+    fn mipmaps_are_averaged() {
+        let texture = simple_texture();
+        assert_eq!(texture.levels(), 2);
+        // Pixels are averaged once decoded
+        assert_eq!(
+            texture.sample(Point2D::new(0.3, 0.9), 1.),
+            LinearColor::new(0.5, 0.5, 0.5)
+        );
+        // Levels past the last one are clamped to it
+        assert_eq!(
+            texture.sample(Point2D::new(0.3, 0.9), 10.),
+            texture.sample(Point2D::new(0.3, 0.9), 1.)
+        );
+    }
+
+    #[test]
+    fn odd_sizes_are_downsampled() {
+        let texture = ImageTexture::new(RgbImage::new(5, 3));
+        let sizes: Vec<_> = texture.levels.iter().map(|m| m.dimensions()).collect();
+        assert_eq!(sizes, vec![(5, 3), (2, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn sample_interpolates_levels() {
+        let texture = simple_texture();
+        let point = Point2D::new(0.25, 0.75);
+        // At the center of a pixel of the image
+        assert_eq!(texture.sample(point, 0.), LinearColor::new(1., 1., 1.));
+        let half = texture.sample(point, 0.5);
+        let expected = (1. + 0.5) / 2.;
+        assert!((half.r - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sample_is_bilinear() {
+        let texture = simple_texture();
+        // Halfway between the two rows, repeating across the edges
+        let middle = texture.sample(Point2D::new(0.1, 0.5), 0.);
+        assert!((middle.g - 0.5).abs() < 1e-6);
+        let edge = texture.sample(Point2D::new(0.1, 0.), 0.);
+        assert!((edge.g - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn filtered_color_fades_with_footprint() {
+        // A one pixel checkerboard
+        let image = RgbImage::from_fn(16, 16, |x, y| Rgb([((x + y) % 2 * 255) as u8; 3]));
+        let texture = ImageTexture::new(image);
+        let point = Point2D::new(0.53, 0.28);
+        assert_eq!(
+            texture.filtered_color(point, 0.),
+            texture.texel_color(point)
+        );
+        let far = texture.filtered_color(point, 1.);
+        assert!((far.r - 0.5).abs() < 0.01);
+        // A negative bias keeps the texture sharp
+        let sharp = texture.with_lod_bias(-10.).filtered_color(point, 1. / 16.);
+        assert!((sharp.r - 0.5).abs() > 0.1);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let path = std::env::temp_dir().join("pathtracer-image-texture.png");
+        simple_texture().image.save(&path).unwrap();
+        let yaml = format!("file: {}", path.display());
+        let texture: ImageTexture = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(texture.image, simple_texture().image);
+        assert_eq!(texture.file(), Some(path.as_path()));
+        assert_eq!(texture.lod_bias, 0.);
+    }
+
+    #[test]
+    fn deserialization_with_lod_bias_works() {
+        let path = std::env::temp_dir().join("pathtracer-image-texture-biased.png");
+        simple_texture().image.save(&path).unwrap();
+        let yaml = format!("file: {}\nlod_bias: -0.5", path.display());
+        let texture: ImageTexture = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(texture.lod_bias, -0.5);
+    }
+
+    #[test]
+    fn deserialization_with_color_space_works() {
+        let path = std::env::temp_dir().join("pathtracer-image-texture-linear.png");
+        RgbImage::from_pixel(1, 1, Rgb([64, 128, 255]))
+            .save(&path)
+            .unwrap();
+        let yaml = format!("file: {}\ncolor_space: linear", path.display());
+        let texture: ImageTexture = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(
+            texture.texel_color(Point2D::origin()),
+            LinearColor::new(64. / 255., 128. / 255., 1.)
+        );
+    }
+
+    #[test]
+    fn missing_file_fails() {
+        let yaml = "file: does-not-exist.png";
+        assert!(serde_yaml::from_str::<ImageTexture>(yaml).is_err())
+    }
+
+    #[test]
+    fn serialization_works() {
+        let path = std::env::temp_dir().join("pathtracer-image-texture-saved.png");
+        simple_texture().image.save(&path).unwrap();
+        let texture = ImageTexture::open(&path).unwrap();
+        let yaml = serde_yaml::to_string(&texture).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<ImageTexture>(&yaml).unwrap(),
+            texture
+        );
+        // The image cannot be referred to without a file
+        assert!(serde_yaml::to_string(&simple_texture()).is_err());
+    }
+}
@@ -1,18 +1,37 @@
 //! Various texture implementations
 
 use super::core::LinearColor;
-use super::Point2D;
-use serde::Deserialize;
+use super::{Point, Point2D, Vector};
+use crate::Float;
+use nalgebra::Unit;
+use serde::{Deserialize, Serialize};
 
 /// All the existing `Texture` implementation.
 #[serde(tag = "type")]
 #[serde(rename_all = "lowercase")]
 #[allow(missing_docs)]
 #[enum_dispatch::enum_dispatch]
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum TextureEnum {
+    #[serde(rename = "channel")]
+    ChannelTexture,
+    #[serde(rename = "checker")]
+    CheckerTexture,
     #[serde(rename = "uniform")]
     UniformTexture,
+    #[serde(rename = "image")]
+    ImageTexture,
+    #[serde(rename = "noise")]
+    NoiseTexture,
+    #[serde(rename = "transformed")]
+    TransformedTexture,
+    #[serde(rename = "triplanar")]
+    TriplanarTexture,
+    /// Given by name in the scene description, see [`SharedTexture`]
+    ///
+    /// [`SharedTexture`]: struct.SharedTexture.html
+    #[serde(skip)]
+    SharedTexture,
 }
 
 /// Represent an object's texture.
@@ -20,7 +39,96 @@ pub enum TextureEnum {
 pub trait Texture: std::fmt::Debug {
     /// Get the color at a given texel coordinate
     fn texel_color(&self, point: Point2D) -> LinearColor;
+    /// Get the value at a given texel coordinate, for grayscale textures driving a scalar
+    /// parameter of a material.
+    ///
+    /// The luminance of the color at `point` is used by default.
+    fn texel_value(&self, point: Point2D) -> Float {
+        self.texel_color(point).luminance()
+    }
+    /// Get the average color over a square of texel space `footprint` wide, centered on `point`,
+    /// so that patterns finer than what a pixel can show fade out instead of aliasing.
+    ///
+    /// The color at `point` is used by default.
+    fn filtered_color(&self, point: Point2D, _footprint: Float) -> LinearColor {
+        self.texel_color(point)
+    }
+    /// Get the color of a surface at the given point, for textures which are projected on it
+    /// rather than using its texel coordinates.
+    ///
+    /// The filtered color at the point's texel coordinates is used by default.
+    fn surface_color(&self, point: &SurfacePoint) -> LinearColor {
+        self.filtered_color(point.texel, point.footprint)
+    }
 }
 
+/// A point on the surface of an object, with everything needed to compute its texture.
+///
+/// Positions and normals are given for the object at rest, so that textures stick to moving
+/// objects.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SurfacePoint {
+    /// The position of the point.
+    pub position: Point,
+    /// The normal of the surface at the point.
+    pub normal: Unit<Vector>,
+    /// The texel coordinates of the point.
+    pub texel: Point2D,
+    /// The width of the footprint of the ray around the point in texel space, see
+    /// [`Texture::filtered_color`].
+    ///
+    /// [`Texture::filtered_color`]: trait.Texture.html#method.filtered_color
+    pub footprint: Float,
+    /// The width of the footprint of the ray around the point on the surface itself.
+    pub width: Float,
+}
+
+impl SurfacePoint {
+    /// Creates a new `SurfacePoint` without any footprint.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::texture::SurfacePoint;
+    /// # use pathtracer::{Point, Point2D, Vector};
+    /// #
+    /// let point = SurfacePoint::new(Point::origin(), Vector::y_axis(), Point2D::new(0.5, 0.5));
+    /// assert_eq!(point.footprint, 0.);
+    /// ```
+    pub fn new(position: Point, normal: Unit<Vector>, texel: Point2D) -> Self {
+        SurfacePoint {
+            position,
+            normal,
+            texel,
+            footprint: 0.,
+            width: 0.,
+        }
+    }
+}
+
+mod bump_map;
+pub use bump_map::*;
+
+mod channel;
+pub use channel::*;
+
+mod checker;
+pub use checker::*;
+
+mod image;
+pub use self::image::*;
+
+mod noise;
+pub use noise::*;
+
+mod shared;
+pub use shared::*;
+
+mod transformed;
+pub use transformed::*;
+
+mod triplanar;
+pub use triplanar::*;
+
 mod uniform;
 pub use uniform::*;
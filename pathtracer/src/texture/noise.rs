@@ -0,0 +1,337 @@
+use super::{SurfacePoint, Texture};
+use crate::core::{LinearColor, Perlin, Worley};
+use crate::{Float, Point, Point2D};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::Arc;
+
+/// The procedural patterns of a [`NoiseTexture`].
+///
+/// [`NoiseTexture`]: struct.NoiseTexture.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoisePattern {
+    /// Fractal Perlin noise, e.g: for clouds.
+    Fbm,
+    /// Fractal absolute Perlin noise, giving sharper billows.
+    Turbulence,
+    /// Fractal cellular noise, e.g: for scales or cracked mud.
+    Worley,
+    /// Stripes along the x axis, distorted by turbulence.
+    Marble,
+    /// Rings around the y axis, distorted by fractal noise.
+    Wood,
+}
+
+/// A color of a [`NoiseTexture`]'s ramp, used where the noise reaches `position`.
+///
+/// [`NoiseTexture`]: struct.NoiseTexture.html
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ColorStop {
+    /// The value of the noise at which the color is used, between 0 and 1.
+    pub position: Float,
+    /// The color used at this position.
+    pub color: LinearColor,
+}
+
+impl ColorStop {
+    /// Creates a new `ColorStop`.
+    pub fn new(position: Float, color: LinearColor) -> Self {
+        ColorStop { position, color }
+    }
+}
+
+/// A procedural texture, mapping the values of a noise pattern through a color ramp, e.g: for
+/// marble, wood or clouds without any image.
+///
+/// The noise is evaluated on the surface's position for objects, and on the texel coordinates
+/// otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoiseTexture {
+    pattern: NoisePattern,
+    /// The number of noise features per unit of length.
+    frequency: Float,
+    /// The number of layers of noise, each one adding finer details.
+    octaves: u32,
+    /// The colors interpolated between depending on the noise, sorted by position.
+    ramp: Vec<ColorStop>,
+    /// The noises, shared by every copy of the texture.
+    noise: Arc<Noise>,
+}
+
+/// The seeded noises used by a [`NoiseTexture`].
+///
+/// [`NoiseTexture`]: struct.NoiseTexture.html
+#[derive(Debug, PartialEq)]
+struct Noise {
+    seed: u64,
+    perlin: Perlin,
+    worley: Worley,
+}
+
+fn default_ramp() -> Vec<ColorStop> {
+    vec![
+        ColorStop::new(0., LinearColor::black()),
+        ColorStop::new(1., LinearColor::new(1., 1., 1.)),
+    ]
+}
+
+impl NoiseTexture {
+    /// Creates a new `NoiseTexture`, with a single octave and a ramp going from black to white.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::texture::{ColorStop, NoisePattern, NoiseTexture, Texture};
+    /// # use pathtracer::Point2D;
+    /// #
+    /// let blue = LinearColor::new(0.2, 0.4, 0.9);
+    /// let white = LinearColor::new(1., 1., 1.);
+    /// let clouds = NoiseTexture::new(NoisePattern::Fbm, 4., 42)
+    ///     .with_octaves(5)
+    ///     .with_ramp(vec![ColorStop::new(0.4, blue), ColorStop::new(0.7, white)]);
+    /// let color = clouds.texel_color(Point2D::new(0.3, 0.6));
+    /// assert!(color.b >= 0.9);
+    /// ```
+    pub fn new(pattern: NoisePattern, frequency: Float, seed: u64) -> Self {
+        NoiseTexture {
+            pattern,
+            frequency,
+            octaves: 1,
+            ramp: default_ramp(),
+            noise: Arc::new(Noise {
+                seed,
+                perlin: Perlin::new(seed),
+                worley: Worley::new(seed),
+            }),
+        }
+    }
+
+    /// Set the number of layers of noise.
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    /// Set the colors used for each value of the noise, an empty ramp going from black to white.
+    pub fn with_ramp(mut self, mut ramp: Vec<ColorStop>) -> Self {
+        if ramp.is_empty() {
+            ramp = default_ramp();
+        }
+        ramp.sort_by(|lhs, rhs| lhs.position.total_cmp(&rhs.position));
+        self.ramp = ramp;
+        self
+    }
+
+    /// Return the value of the pattern at the given point, between 0 and 1, filtered over a
+    /// footprint `footprint` wide.
+    pub fn value(&self, point: &Point, footprint: Float) -> Float {
+        let (perlin, worley, octaves) = (&self.noise.perlin, &self.noise.worley, self.octaves);
+        let point = point * self.frequency;
+        let footprint = footprint * self.frequency;
+        match self.pattern {
+            NoisePattern::Fbm => (perlin.filtered_fractal(&point, octaves, footprint) + 1.) / 2.,
+            NoisePattern::Turbulence => perlin.turbulence(&point, octaves, footprint),
+            NoisePattern::Worley => worley.filtered_fractal(&point, octaves, footprint),
+            NoisePattern::Marble => {
+                let turbulence = perlin.turbulence(&point, octaves, footprint);
+                let stripes = (crate::consts::PI * (point.x + 4. * turbulence)).sin();
+                fade_to_average(0.5 + 0.5 * stripes, footprint)
+            }
+            NoisePattern::Wood => {
+                let distortion = perlin.filtered_fractal(&point, octaves, footprint);
+                let radius = (point.x * point.x + point.z * point.z).sqrt() + distortion / 2.;
+                let rings = (2. * crate::consts::PI * radius).cos();
+                fade_to_average(0.5 - 0.5 * rings, footprint)
+            }
+        }
+    }
+
+    /// Get the color of the ramp for a given value of the noise.
+    fn ramp_color(&self, value: Float) -> LinearColor {
+        let next = self.ramp.iter().position(|stop| stop.position > value);
+        match next {
+            None => self.ramp[self.ramp.len() - 1].color.clone(),
+            Some(0) => self.ramp[0].color.clone(),
+            Some(index) => {
+                let (low, high) = (&self.ramp[index - 1], &self.ramp[index]);
+                let t = (value - low.position) / (high.position - low.position);
+                low.color.clone() * (1. - t) + high.color.clone() * t
+            }
+        }
+    }
+}
+
+/// Fade a periodic pattern with a unit period to its average of 0.5, once the footprint is wide
+/// enough to cover a full period.
+fn fade_to_average(value: Float, footprint: Float) -> Float {
+    let weight = (2. - 2. * footprint).clamp(0., 1.);
+    // A NaN footprint fades the pattern out entirely
+    let weight = if weight.is_nan() { 0. } else { weight };
+    0.5 + (value - 0.5) * weight
+}
+
+impl Texture for NoiseTexture {
+    fn texel_color(&self, point: Point2D) -> LinearColor {
+        self.filtered_color(point, 0.)
+    }
+
+    fn filtered_color(&self, point: Point2D, footprint: Float) -> LinearColor {
+        let point = Point::new(point.x, point.y, 0.);
+        self.ramp_color(self.value(&point, footprint))
+    }
+
+    fn surface_color(&self, point: &SurfacePoint) -> LinearColor {
+        self.ramp_color(self.value(&point.position, point.width))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SerializedNoiseTexture {
+    pattern: NoisePattern,
+    #[serde(default = "crate::serialize::default_identity")]
+    frequency: Float,
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    ramp: Vec<ColorStop>,
+}
+
+fn default_octaves() -> u32 {
+    1
+}
+
+impl<'de> Deserialize<'de> for NoiseTexture {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let texture: SerializedNoiseTexture = Deserialize::deserialize(deserializer)?;
+        Ok(
+            NoiseTexture::new(texture.pattern, texture.frequency, texture.seed)
+                .with_octaves(texture.octaves)
+                .with_ramp(texture.ramp),
+        )
+    }
+}
+
+impl Serialize for NoiseTexture {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        SerializedNoiseTexture {
+            pattern: self.pattern,
+            frequency: self.frequency,
+            octaves: self.octaves,
+            seed: self.noise.seed,
+            ramp: self.ramp.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PATTERNS: [NoisePattern; 5] = [
+        NoisePattern::Fbm,
+        NoisePattern::Turbulence,
+        NoisePattern::Worley,
+        NoisePattern::Marble,
+        NoisePattern::Wood,
+    ];
+
+    fn sample_points() -> impl Iterator<Item = Point> {
+        (0..200).map(|i| {
+            let i = i as Float;
+            Point::new(i * 0.173, i * -0.291 + 3., i * 0.057 - 20.)
+        })
+    }
+
+    #[test]
+    fn values_are_bounded() {
+        for &pattern in PATTERNS.iter() {
+            let texture = NoiseTexture::new(pattern, 2., 0).with_octaves(4);
+            assert!(
+                sample_points()
+                    .map(|p| texture.value(&p, 0.))
+                    .all(|v| (0. ..=1.).contains(&v)),
+                "{:?}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn wide_footprints_fade_out() {
+        for &pattern in PATTERNS.iter() {
+            let texture = NoiseTexture::new(pattern, 2., 0).with_octaves(4);
+            let values: Vec<_> = sample_points().map(|p| texture.value(&p, 10.)).collect();
+            assert!(
+                values.iter().all(|v| (v - values[0]).abs() < 1e-5),
+                "{:?}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn ramp_works() {
+        let red = LinearColor::new(1., 0., 0.);
+        let blue = LinearColor::new(0., 0., 1.);
+        let texture = NoiseTexture::new(NoisePattern::Fbm, 1., 0).with_ramp(vec![
+            ColorStop::new(0.75, blue.clone()),
+            ColorStop::new(0.25, red.clone()),
+        ]);
+        assert_eq!(texture.ramp_color(0.), red);
+        assert_eq!(texture.ramp_color(0.5), LinearColor::new(0.5, 0., 0.5));
+        assert_eq!(texture.ramp_color(1.), blue);
+    }
+
+    #[test]
+    fn surface_uses_position() {
+        let texture = NoiseTexture::new(NoisePattern::Worley, 1., 3);
+        let position = Point::new(0.3, 1.7, -2.2);
+        let point = SurfacePoint::new(position, crate::Vector::y_axis(), Point2D::origin());
+        let value = texture.value(&position, 0.);
+        assert_eq!(
+            texture.surface_color(&point),
+            LinearColor::new(value, value, value)
+        );
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            pattern: marble
+            frequency: 2.0
+            octaves: 3
+            seed: 7
+            ramp:
+              - {position: 0.0, color: {r: 0.1, g: 0.1, b: 0.1}}
+              - {position: 1.0, color: {r: 0.9, g: 0.9, b: 0.9}}
+        "#;
+        let texture: NoiseTexture = serde_yaml::from_str(yaml).unwrap();
+        let expected = NoiseTexture::new(NoisePattern::Marble, 2., 7)
+            .with_octaves(3)
+            .with_ramp(vec![
+                ColorStop::new(0., LinearColor::new(0.1, 0.1, 0.1)),
+                ColorStop::new(1., LinearColor::new(0.9, 0.9, 0.9)),
+            ]);
+        assert_eq!(texture, expected);
+    }
+
+    #[test]
+    fn serialization_works() {
+        let texture = NoiseTexture::new(NoisePattern::Wood, 3., 5).with_octaves(2);
+        let yaml = serde_yaml::to_string(&texture).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<NoiseTexture>(&yaml).unwrap(),
+            texture
+        );
+    }
+}
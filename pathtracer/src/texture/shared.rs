@@ -0,0 +1,126 @@
+use super::{SurfacePoint, Texture, TextureEnum, UniformTexture};
+use crate::core::LinearColor;
+use crate::{Error, Result};
+use crate::{Float, Point2D};
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+
+/// A texture defined once in the scene, and shared by name between the objects using it, e.g: to
+/// only load an image once.
+///
+/// When deserializing a scene, objects refer to the textures of its `textures` dictionary by
+/// name, and are given the shared texture once the whole scene has been read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SharedTexture {
+    name: String,
+    texture: Option<Arc<TextureEnum>>,
+}
+
+impl SharedTexture {
+    /// Creates a new `SharedTexture`, giving a name to the texture it shares.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::texture::{SharedTexture, Texture, UniformTexture};
+    /// # use pathtracer::Point2D;
+    /// use std::sync::Arc;
+    ///
+    /// let grey = Arc::new(UniformTexture::new(LinearColor::new(0.5, 0.5, 0.5)).into());
+    /// let shared = SharedTexture::new("grey", grey);
+    /// assert_eq!(shared.name(), "grey");
+    /// assert_eq!(
+    ///     shared.texel_color(Point2D::origin()),
+    ///     LinearColor::new(0.5, 0.5, 0.5)
+    /// );
+    /// ```
+    pub fn new<S: Into<String>>(name: S, texture: Arc<TextureEnum>) -> Self {
+        SharedTexture {
+            name: name.into(),
+            texture: Some(texture),
+        }
+    }
+
+    /// Refer to a texture by name, before it has been defined.
+    pub(crate) fn named(name: String) -> Self {
+        SharedTexture {
+            name,
+            texture: None,
+        }
+    }
+
+    /// Get the name of the shared texture.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Look up the shared texture by name among the scene's `textures`.
+    pub(crate) fn resolve(&mut self, textures: &BTreeMap<String, Arc<TextureEnum>>) -> Result<()> {
+        let texture =
+            (textures.get(&self.name)).ok_or_else(|| Error::unknown_name("texture", &self.name))?;
+        self.texture = Some(Arc::clone(texture));
+        Ok(())
+    }
+
+    /// Get the shared texture, or an [`Error::UnknownName`] if it was not resolved.
+    ///
+    /// [`Error::UnknownName`]: ../enum.Error.html#variant.UnknownName
+    pub(crate) fn texture(&self) -> Result<&TextureEnum> {
+        (self.texture.as_deref()).ok_or_else(|| Error::unknown_name("texture", &self.name))
+    }
+
+    /// Get the shared texture, rendering it black if it was not resolved, which never happens to
+    /// the textures of a loaded scene.
+    fn shared(&self) -> &TextureEnum {
+        static UNRESOLVED: OnceLock<TextureEnum> = OnceLock::new();
+        self.texture().unwrap_or_else(|_| {
+            UNRESOLVED.get_or_init(|| UniformTexture::new(LinearColor::black()).into())
+        })
+    }
+}
+
+impl Texture for SharedTexture {
+    fn texel_color(&self, point: Point2D) -> LinearColor {
+        self.shared().texel_color(point)
+    }
+
+    fn texel_value(&self, point: Point2D) -> Float {
+        self.shared().texel_value(point)
+    }
+
+    fn filtered_color(&self, point: Point2D, footprint: Float) -> LinearColor {
+        self.shared().filtered_color(point, footprint)
+    }
+
+    fn surface_color(&self, point: &SurfacePoint) -> LinearColor {
+        self.shared().surface_color(point)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolve_works() {
+        let texture: TextureEnum = UniformTexture::new(LinearColor::new(1., 0., 0.)).into();
+        let mut textures = BTreeMap::new();
+        textures.insert("red".to_string(), Arc::new(texture));
+        let mut shared = SharedTexture::named("red".to_string());
+        shared.resolve(&textures).unwrap();
+        assert_eq!(
+            shared.texel_color(Point2D::origin()),
+            LinearColor::new(1., 0., 0.)
+        );
+        let mut unknown = SharedTexture::named("blue".to_string());
+        assert!(unknown.texture().is_err());
+        assert!(matches!(
+            unknown.resolve(&textures),
+            Err(Error::UnknownName {
+                kind: "texture",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+use super::{SurfacePoint, Texture, TextureEnum};
+use crate::core::LinearColor;
+use crate::{Float, Point2D};
+use nalgebra::{Rotation2, Vector2};
+use serde::{Deserialize, Serialize};
+
+/// How texel coordinates outside of the `[0, 1]` range are brought back inside it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WrapMode {
+    /// Tile the texture.
+    #[default]
+    Repeat,
+    /// Stretch the edges of the texture.
+    Clamp,
+    /// Tile the texture, flipping every other tile so that they join seamlessly.
+    Mirror,
+}
+
+impl WrapMode {
+    /// Bring a coordinate back inside the `[0, 1)` range.
+    fn wrap(self, x: Float) -> Float {
+        // Keep the last texel inside the texture, instead of wrapping back to the first one
+        const LAST: Float = 1. - Float::EPSILON;
+        match self {
+            WrapMode::Repeat => x.rem_euclid(1.),
+            // NaN coordinates fall on the first texel, as coordinates below 0 do
+            WrapMode::Clamp if x.is_nan() => 0.,
+            WrapMode::Clamp => x.clamp(0., LAST),
+            WrapMode::Mirror => {
+                let x = x.rem_euclid(2.);
+                if x > 1. { 2. - x } else { x }.min(LAST)
+            }
+        }
+    }
+}
+
+/// A texture whose texel coordinates are transformed before sampling it, e.g: to tile a small
+/// texture over a large floor.
+///
+/// Coordinates are scaled, then rotated around the origin, then offset, and finally wrapped.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TransformedTexture {
+    /// The transformed texture.
+    texture: Box<TextureEnum>,
+    /// The scale applied to each texel coordinate, i.e: the number of repetitions of the texture.
+    #[serde(default = "default_scale")]
+    scale: Vector2<Float>,
+    /// The rotation applied after scaling, in degrees.
+    #[serde(default)]
+    rotation: Float,
+    /// The offset added after rotating.
+    #[serde(default = "Vector2::zeros")]
+    offset: Vector2<Float>,
+    /// How coordinates outside of the texture are handled.
+    #[serde(default)]
+    wrap: WrapMode,
+}
+
+fn default_scale() -> Vector2<Float> {
+    Vector2::new(1., 1.)
+}
+
+impl TransformedTexture {
+    /// Creates a new `TransformedTexture`, without any transformation, repeating the texture.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::texture::{CheckerTexture, Texture, TransformedTexture, WrapMode};
+    /// # use pathtracer::Point2D;
+    /// # use nalgebra::Vector2;
+    /// #
+    /// let (black, white) = (LinearColor::black(), LinearColor::new(1., 1., 1.));
+    /// let checker = CheckerTexture::new(black.clone(), white.clone(), 2.);
+    /// assert_eq!(checker.texel_color(Point2D::new(0.2, 0.1)), black);
+    /// // Repeat the checkerboard 4 times along each axis
+    /// let tiled = TransformedTexture::new(checker.into()).with_scale(Vector2::new(4., 4.));
+    /// assert_eq!(tiled.texel_color(Point2D::new(0.2, 0.1)), white);
+    /// assert_eq!(tiled.texel_color(Point2D::new(0.3, 0.1)), black);
+    ///
+    /// // Stretch the last column of squares instead
+    /// let clamped = tiled.with_wrap(WrapMode::Clamp);
+    /// assert_eq!(clamped.texel_color(Point2D::new(0.3, 0.1)), white);
+    /// ```
+    pub fn new(texture: TextureEnum) -> Self {
+        TransformedTexture {
+            texture: Box::new(texture),
+            scale: default_scale(),
+            rotation: 0.,
+            offset: Vector2::zeros(),
+            wrap: WrapMode::default(),
+        }
+    }
+
+    /// Set the scale applied to the texel coordinates.
+    pub fn with_scale(mut self, scale: Vector2<Float>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Set the rotation applied to the texel coordinates, in degrees.
+    pub fn with_rotation(mut self, rotation: Float) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Set the offset applied to the texel coordinates.
+    pub fn with_offset(mut self, offset: Vector2<Float>) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Set how coordinates outside of the texture are handled.
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Get the texel coordinate of the wrapped texture at a given texel coordinate.
+    pub fn transform(&self, point: Point2D) -> Point2D {
+        let rotation = Rotation2::new(self.rotation.to_radians());
+        let point = rotation * Point2D::from(point.coords.component_mul(&self.scale)) + self.offset;
+        Point2D::new(self.wrap.wrap(point.x), self.wrap.wrap(point.y))
+    }
+
+    fn scaled_footprint(&self, footprint: Float) -> Float {
+        // The footprint grows with the scale, rotations keep it the same
+        footprint * self.scale.x.abs().max(self.scale.y.abs())
+    }
+}
+
+impl Texture for TransformedTexture {
+    fn texel_color(&self, point: Point2D) -> LinearColor {
+        self.texture.texel_color(self.transform(point))
+    }
+
+    fn texel_value(&self, point: Point2D) -> Float {
+        self.texture.texel_value(self.transform(point))
+    }
+
+    fn filtered_color(&self, point: Point2D, footprint: Float) -> LinearColor {
+        self.texture
+            .filtered_color(self.transform(point), self.scaled_footprint(footprint))
+    }
+
+    fn surface_color(&self, point: &SurfacePoint) -> LinearColor {
+        let point = SurfacePoint {
+            texel: self.transform(point.texel),
+            footprint: self.scaled_footprint(point.footprint),
+            ..point.clone()
+        };
+        self.texture.surface_color(&point)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::texture::UniformTexture;
+
+    fn black() -> TextureEnum {
+        UniformTexture::new(LinearColor::black()).into()
+    }
+
+    fn assert_close(lhs: Point2D, rhs: Point2D) {
+        assert!((lhs - rhs).norm() < 1e-5, "{} != {}", lhs, rhs)
+    }
+
+    #[test]
+    fn identity_works() {
+        let texture = TransformedTexture::new(black());
+        assert_close(
+            texture.transform(Point2D::new(0.25, 0.75)),
+            Point2D::new(0.25, 0.75),
+        );
+    }
+
+    #[test]
+    fn transform_order_works() {
+        let texture = TransformedTexture::new(black())
+            .with_scale(Vector2::new(0.5, 0.25))
+            .with_rotation(90.)
+            .with_offset(Vector2::new(0.5, 0.));
+        // Scaled to (0.5, 0.25), rotated to (-0.25, 0.5), offset to (0.25, 0.5)
+        assert_close(
+            texture.transform(Point2D::new(1., 1.)),
+            Point2D::new(0.25, 0.5),
+        );
+    }
+
+    #[test]
+    fn wrap_modes_work() {
+        let repeat = WrapMode::Repeat;
+        assert!((repeat.wrap(1.25) - 0.25).abs() < 1e-6);
+        assert!((repeat.wrap(-0.25) - 0.75).abs() < 1e-6);
+        let clamp = WrapMode::Clamp;
+        assert_eq!(clamp.wrap(-0.5), 0.);
+        assert!(clamp.wrap(3.) < 1.);
+        assert!((clamp.wrap(0.5) - 0.5).abs() < 1e-6);
+        let mirror = WrapMode::Mirror;
+        assert!((mirror.wrap(1.25) - 0.75).abs() < 1e-6);
+        assert!((mirror.wrap(-0.25) - 0.25).abs() < 1e-6);
+        assert!((mirror.wrap(2.25) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn footprint_is_scaled() {
+        let checker = crate::texture::CheckerTexture::new(
+            LinearColor::black(),
+            LinearColor::new(1., 1., 1.),
+            2.,
+        );
+        let tiled = TransformedTexture::new(checker.into()).with_scale(Vector2::new(8., 1.));
+        // A small footprint on the surface covers many squares of the tiled texture
+        let color = tiled.filtered_color(Point2D::new(0.3, 0.3), 0.25);
+        assert!((color.r - 0.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            texture:
+              type: uniform
+              color: {r: 1.0, g: 0.5, b: 0.0}
+            scale: [4.0, 2.0]
+            rotation: 45.0
+            wrap: mirror
+        "#;
+        let texture: TransformedTexture = serde_yaml::from_str(yaml).unwrap();
+        let uniform = UniformTexture::new(LinearColor::new(1., 0.5, 0.));
+        assert_eq!(
+            texture,
+            TransformedTexture::new(uniform.into())
+                .with_scale(Vector2::new(4., 2.))
+                .with_rotation(45.)
+                .with_wrap(WrapMode::Mirror)
+        );
+    }
+
+    #[test]
+    fn deserialization_defaults_work() {
+        let yaml = "texture: {type: uniform, color: {r: 1.0, g: 0.5, b: 0.0}}";
+        let texture: TransformedTexture = serde_yaml::from_str(yaml).unwrap();
+        let uniform = UniformTexture::new(LinearColor::new(1., 0.5, 0.));
+        assert_eq!(texture, TransformedTexture::new(uniform.into()));
+    }
+}
@@ -0,0 +1,169 @@
+use super::{SurfacePoint, Texture, TextureEnum};
+use crate::core::LinearColor;
+use crate::{Float, Point2D};
+use serde::{Deserialize, Serialize};
+
+/// A texture projected along each axis onto the surface, blending the three projections depending
+/// on the surface's normal, e.g: for shapes without usable texel coordinates such as CSG or
+/// signed distance fields.
+///
+/// The texel coordinates of the surface are only used where its position is unknown, e.g: for
+/// light gobos.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TriplanarTexture {
+    /// The projected texture.
+    texture: Box<TextureEnum>,
+    /// The number of repetitions of the texture per unit of length.
+    #[serde(default = "crate::serialize::default_identity")]
+    scale: Float,
+    /// How sharp the transition between projections is, higher values give narrower blends.
+    #[serde(default = "default_sharpness")]
+    sharpness: Float,
+}
+
+fn default_sharpness() -> Float {
+    4.
+}
+
+/// The weight under which a projection is ignored.
+const MIN_WEIGHT: Float = 1e-3;
+
+impl TriplanarTexture {
+    /// Creates a new `TriplanarTexture`, repeating `texture` `scale` times per unit of length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pathtracer::core::LinearColor;
+    /// # use pathtracer::texture::{CheckerTexture, SurfacePoint, Texture, TriplanarTexture};
+    /// # use pathtracer::{Point, Point2D, Vector};
+    /// #
+    /// let (black, white) = (LinearColor::black(), LinearColor::new(1., 1., 1.));
+    /// let checker = CheckerTexture::new(black.clone(), white.clone(), 2.);
+    /// let triplanar = TriplanarTexture::new(checker.into(), 1.);
+    /// // The texel coordinates are ignored, the position is projected along the normal
+    /// let point = |x, y, z, normal| SurfacePoint::new(Point::new(x, y, z), normal, Point2D::origin());
+    /// assert_eq!(triplanar.surface_color(&point(0.25, 0.75, 0.25, Vector::x_axis())), white);
+    /// assert_eq!(triplanar.surface_color(&point(0.25, 0.75, 0.25, Vector::y_axis())), black);
+    /// assert_eq!(triplanar.surface_color(&point(0.25, 0.75, 0.25, Vector::z_axis())), white);
+    /// ```
+    pub fn new(texture: TextureEnum, scale: Float) -> Self {
+        TriplanarTexture {
+            texture: Box::new(texture),
+            scale,
+            sharpness: default_sharpness(),
+        }
+    }
+
+    /// Set how sharp the transition between projections is.
+    pub fn with_sharpness(mut self, sharpness: Float) -> Self {
+        self.sharpness = sharpness;
+        self
+    }
+}
+
+impl Texture for TriplanarTexture {
+    fn texel_color(&self, point: Point2D) -> LinearColor {
+        self.texture.texel_color(point)
+    }
+
+    fn texel_value(&self, point: Point2D) -> Float {
+        self.texture.texel_value(point)
+    }
+
+    fn filtered_color(&self, point: Point2D, footprint: Float) -> LinearColor {
+        self.texture.filtered_color(point, footprint)
+    }
+
+    fn surface_color(&self, point: &SurfacePoint) -> LinearColor {
+        let position = point.position.coords * self.scale;
+        let footprint = point.width * self.scale;
+        let weights = point.normal.map(|n| n.abs().powf(self.sharpness));
+        let total = weights.sum();
+        // Project along each axis onto the plane of the two others
+        let projections = [
+            (weights.x, Point2D::new(position.z, position.y)),
+            (weights.y, Point2D::new(position.x, position.z)),
+            (weights.z, Point2D::new(position.x, position.y)),
+        ];
+        projections
+            .iter()
+            .map(|&(weight, texel)| (weight / total, texel))
+            .filter(|&(weight, _)| weight > MIN_WEIGHT)
+            .map(|(weight, texel)| self.texture.filtered_color(texel, footprint) * weight)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::texture::CheckerTexture;
+    use crate::{Point, Vector};
+    use nalgebra::Unit;
+
+    fn checker() -> TextureEnum {
+        CheckerTexture::new(LinearColor::black(), LinearColor::new(1., 1., 1.), 1.).into()
+    }
+
+    #[test]
+    fn scale_works() {
+        let triplanar = TriplanarTexture::new(checker(), 2.);
+        let color = |y| {
+            let position = Point::new(0.25, y, 0.25);
+            triplanar.surface_color(&SurfacePoint::new(
+                position,
+                Vector::x_axis(),
+                Point2D::origin(),
+            ))
+        };
+        // Each square is half a unit wide
+        assert_eq!(color(0.25), LinearColor::black());
+        assert_eq!(color(0.75), LinearColor::new(1., 1., 1.));
+    }
+
+    #[test]
+    fn projections_are_blended() {
+        let triplanar = TriplanarTexture::new(checker(), 1.);
+        // White when projected along x, black along y
+        let position = Point::new(1.25, 0.25, 1.25);
+        let diagonal = Unit::new_normalize(Vector::new(1., 1., 0.));
+        let color =
+            triplanar.surface_color(&SurfacePoint::new(position, diagonal, Point2D::origin()));
+        assert!((color.r - 0.5).abs() < 1e-5);
+        // A sharper blend only keeps the projection closest to the normal
+        let tilted = Unit::new_normalize(Vector::new(1., 0.5, 0.));
+        let sharp = triplanar.with_sharpness(64.);
+        let color = sharp.surface_color(&SurfacePoint::new(position, tilted, Point2D::origin()));
+        assert_eq!(color, LinearColor::new(1., 1., 1.));
+    }
+
+    #[test]
+    fn footprint_uses_width() {
+        let triplanar = TriplanarTexture::new(checker(), 4.);
+        let point = SurfacePoint {
+            width: 1.,
+            ..SurfacePoint::new(
+                Point::new(0.1, 0.2, 0.3),
+                Vector::z_axis(),
+                Point2D::origin(),
+            )
+        };
+        let color = triplanar.surface_color(&point);
+        assert!((color.r - 0.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn deserialization_works() {
+        let yaml = r#"
+            texture:
+              type: checker
+              even: {r: 0.0, g: 0.0, b: 0.0}
+              odd: {r: 1.0, g: 1.0, b: 1.0}
+              squares: 1.0
+            scale: 0.5
+        "#;
+        let texture: TriplanarTexture = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(texture, TriplanarTexture::new(checker(), 0.5));
+    }
+}
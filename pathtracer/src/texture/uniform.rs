@@ -1,10 +1,10 @@
 use super::Texture;
 use crate::core::LinearColor;
 use crate::Point2D;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// A texture with the same color on all points.
-#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct UniformTexture {
     color: LinearColor,
 }
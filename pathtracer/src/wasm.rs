@@ -0,0 +1,82 @@
+//! A JavaScript interface to render scenes into HTML canvases, e.g: for demos
+//!
+//! Build it for the web with the `wasm` feature, without the default features, as threads are
+//! not available there, then generate its JavaScript bindings:
+//!
+//! ```sh
+//! cargo rustc --package pathtracer --lib --release --target wasm32-unknown-unknown \
+//!     --no-default-features --features wasm --crate-type cdylib
+//! wasm-bindgen --target web --out-dir pkg \
+//!     target/wasm32-unknown-unknown/release/pathtracer.wasm
+//! ```
+//!
+//! Scenes are given as the text of their description, as there are no files to read them from:
+//! scenes using meshes or image textures cannot be rendered.
+//!
+//! ```js
+//! import init, { WebScene } from "./pkg/pathtracer.js";
+//!
+//! await init();
+//! const scene = new WebScene(description);
+//! scene.renderToCanvas(document.getElementById("canvas"), 42);
+//! ```
+
+use crate::render::Scene;
+use image::RgbImage;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+
+/// A scene loaded from its description, to be rendered from JavaScript.
+#[wasm_bindgen]
+pub struct WebScene(Scene);
+
+#[wasm_bindgen]
+impl WebScene {
+    /// Load a scene from the YAML text of its description.
+    #[wasm_bindgen(constructor)]
+    pub fn new(description: &str) -> Result<WebScene, JsValue> {
+        let scene = serde_yaml::from_str(description).map_err(|err| err.to_string())?;
+        Ok(WebScene(scene))
+    }
+
+    /// Render the scene, drawing every random sample from the given seed.
+    pub fn render(&self, seed: u32) -> Result<ImageData, JsValue> {
+        let image = self.0.render_with_seed(seed as u64);
+        let (width, height) = image.dimensions();
+        ImageData::new_with_u8_clamped_array_and_sh(Clamped(&to_rgba(&image)), width, height)
+    }
+
+    /// Render the scene into a canvas, resizing it to the size of the image.
+    #[wasm_bindgen(js_name = renderToCanvas)]
+    pub fn render_to_canvas(&self, canvas: &HtmlCanvasElement, seed: u32) -> Result<(), JsValue> {
+        let image = self.render(seed)?;
+        canvas.set_width(image.width());
+        canvas.set_height(image.height());
+        let context = canvas
+            .get_context("2d")?
+            .ok_or("the canvas cannot be drawn in 2D")?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+        context.put_image_data(&image, 0., 0.)
+    }
+}
+
+/// Get the pixels of an image as opaque RGBA, the layout of canvases.
+fn to_rgba(image: &RgbImage) -> Vec<u8> {
+    image
+        .pixels()
+        .flat_map(|pixel| vec![pixel[0], pixel[1], pixel[2], u8::MAX])
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn to_rgba_works() {
+        let mut image = RgbImage::new(2, 1);
+        image.put_pixel(1, 0, image::Rgb([1, 2, 3]));
+        assert_eq!(to_rgba(&image), vec![0, 0, 0, 255, 1, 2, 3, 255]);
+    }
+}